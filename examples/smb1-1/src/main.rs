@@ -8,7 +8,10 @@ use hv_core::{
     filesystem::Filesystem,
     input::{GamepadAxis, GamepadButton, InputBinding, InputState, KeyCode, KeyMods, MouseButton},
     prelude::*,
-    spaces::{Object, Space, Spaces},
+    spaces::{
+        object_table::{call_method_on_all, call_method_on_objects},
+        Object, Space, Spaces,
+    },
     timer::TimeContext,
 };
 
@@ -16,16 +19,19 @@ use hv_friends::{
     collision::Collider,
     graphics::{
         sprite::{CachedSpriteSheet, SpriteAnimation, SpriteSheetCache},
-        texture::TextureCache,
         CachedTexture, DrawableMut, GraphicsLock, GraphicsLockExt, Instance, SpriteBatch,
+        SpriteSheetAtlas, SpriteSheetAtlasOptions,
     },
     math::*,
-    parry2d, Position, SimpleHandler, Velocity,
+    parry2d, Position, SimpleHandler, SubstepCount, Velocity,
 };
 use hv_tiled::{BoxExt, CoordSpace, TileId, TilesetRenderData};
 
 const TIMESTEP: f32 = 1. / 60.;
 const LOAD_DISTANCE_IN_PIXELS: f32 = 32.0;
+/// Substeps for objects without their own `SubstepCount`. Two is enough to stop Mario jittering
+/// against blocks when falling at full speed.
+const DEFAULT_SUBSTEPS: SubstepCount = SubstepCount(2);
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 enum Button {
@@ -99,13 +105,8 @@ struct SmbOneOne {
     map_initial_state: hv_tiled::Map,
     map: AtomicRefCell<hv_tiled::Map>,
     ts_render_data: TilesetRenderData,
-    to_update: AtomicRefCell<Vec<Object>>,
-    to_collide: AtomicRefCell<Vec<(Object, Object)>>,
-    to_headbutt: AtomicRefCell<Vec<(Object, (i32, i32, TileId, Option<u32>))>>,
-    to_load: AtomicRefCell<Vec<Object>>,
 
-    goomba_batch: AtomicRefCell<SpriteBatch<CachedTexture>>,
-    koopa_batch: AtomicRefCell<SpriteBatch<CachedTexture>>,
+    enemy_batch: AtomicRefCell<SpriteBatch<CachedTexture>>,
     mario_batch: AtomicRefCell<SpriteBatch<CachedTexture>>,
     item_batch: AtomicRefCell<SpriteBatch<CachedTexture>>,
 
@@ -119,17 +120,22 @@ struct SmbOneOne {
 impl SmbOneOne {
     fn new(engine: &Engine, input_state: Shared<InputState<Axis, Button>>) -> Result<Shared<Self>> {
         let space = engine.get::<Spaces>().borrow_mut().create_space();
-        let mut texture_cache = engine.get::<TextureCache>().owned_borrow_mut();
-        let goomba_texture = texture_cache.get_or_load("/sprite_sheets/goomba-sheet.png")?;
-        let koopa_texture = texture_cache.get_or_load("/sprite_sheets/koopa.png")?;
-        let mario_texture = texture_cache.get_or_load("/sprite_sheets/mario_ss.png")?;
-        drop(texture_cache);
 
+        // Goombas, koopas, and Mario all share a single texture.
+        const GOOMBA_SHEET: &str = "/sprite_sheets/goomba.json";
+        const KOOPA_SHEET: &str = "/sprite_sheets/koopa.json";
+        const MARIO_SHEET: &str = "/sprite_sheets/mario_ss.json";
         let mut sprite_sheet_cache = engine.get::<SpriteSheetCache>().owned_borrow_mut();
-        let goomba_sheet = sprite_sheet_cache.get_or_load("/sprite_sheets/goomba.json")?;
-        let koopa_sheet = sprite_sheet_cache.get_or_load("/sprite_sheets/koopa.json")?;
-        let mario_sheet = sprite_sheet_cache.get_or_load("/sprite_sheets/mario_ss.json")?;
+        let atlas = SpriteSheetAtlas::load(
+            engine,
+            &mut sprite_sheet_cache,
+            &[GOOMBA_SHEET, KOOPA_SHEET, MARIO_SHEET],
+            &SpriteSheetAtlasOptions::default(),
+        )?;
         drop(sprite_sheet_cache);
+        let goomba_sheet = atlas.get(GOOMBA_SHEET).unwrap();
+        let koopa_sheet = atlas.get(KOOPA_SHEET).unwrap();
+        let mario_sheet = atlas.get(MARIO_SHEET).unwrap();
 
         let button_table;
         let sprite_sheets_table;
@@ -175,9 +181,8 @@ impl SmbOneOne {
 
         let gfx_lock = engine.get::<GraphicsLock>();
         let mut gfx = gfx_lock.lock();
-        let goomba_batch = AtomicRefCell::new(SpriteBatch::new(&mut gfx, goomba_texture));
-        let koopa_batch = AtomicRefCell::new(SpriteBatch::new(&mut gfx, koopa_texture));
-        let mario_batch = AtomicRefCell::new(SpriteBatch::new(&mut gfx, mario_texture));
+        let enemy_batch = AtomicRefCell::new(SpriteBatch::new(&mut gfx, atlas.texture().clone()));
+        let mario_batch = AtomicRefCell::new(SpriteBatch::new(&mut gfx, atlas.texture().clone()));
         let item_batch = AtomicRefCell::new(SpriteBatch::new(
             &mut gfx,
             ts_render_data
@@ -198,14 +203,9 @@ impl SmbOneOne {
             map_initial_state: map.clone(),
             map: AtomicRefCell::new(map),
             ts_render_data,
-            to_update: AtomicRefCell::new(Vec::new()),
-            to_collide: AtomicRefCell::new(Vec::new()),
-            to_headbutt: AtomicRefCell::new(Vec::new()),
-            to_load: AtomicRefCell::new(Vec::new()),
             render_reader: AtomicRefCell::new(render_reader),
 
-            goomba_batch,
-            koopa_batch,
+            enemy_batch,
             mario_batch,
             item_batch,
 
@@ -218,6 +218,7 @@ impl SmbOneOne {
 
 impl SmbOneOne {
     fn load_nearby_objects(&self, engine: &Engine, lua: &Lua) -> Result<()> {
+        let mut to_load = self.space.borrow().scratch::<Object>();
         for (obj, (Position(pos), _)) in self
             .space
             .borrow_mut()
@@ -229,23 +230,21 @@ impl SmbOneOne {
                     + 8.0
                     + LOAD_DISTANCE_IN_PIXELS)
             {
-                self.to_load.borrow_mut().push(obj);
+                to_load.push(obj);
             }
         }
 
-        for obj_to_load in self.to_load.borrow_mut().drain(..) {
+        for &obj_to_load in to_load.iter() {
             self.space
                 .borrow_mut()
                 .remove_one::<Unloaded>(obj_to_load)?;
-            let table = LuaTable::from_lua(obj_to_load.to_lua(lua)?, lua)?;
-            table.call_method("on_load", ())?;
         }
 
-        Ok(())
+        call_method_on_objects(lua, to_load.drain(..), "on_load", ())
     }
 
     fn run_required_lua_updates(&self, _engine: &Engine, lua: &Lua, dt: f32) -> Result<()> {
-        let mut to_update = self.to_update.borrow_mut();
+        let mut to_update = self.space.borrow().scratch::<Object>();
 
         for (obj, ()) in self
             .space
@@ -257,12 +256,7 @@ impl SmbOneOne {
             to_update.push(obj);
         }
 
-        for obj_to_update in to_update.drain(..) {
-            let table = LuaTable::from_lua(obj_to_update.to_lua(lua)?, lua)?;
-            table.call_method("update", dt)?;
-        }
-
-        Ok(())
+        call_method_on_objects(lua, to_update.drain(..), "update", dt)
     }
 
     fn integrate_objects_without_colliders(
@@ -284,94 +278,37 @@ impl SmbOneOne {
     }
 
     fn integrate_object_positions(&self, _engine: &Engine, lua: &Lua, _dt: f32) -> Result<()> {
-        let mut to_headbutt = self.to_headbutt.borrow_mut();
+        let mut to_headbutt = self
+            .space
+            .borrow()
+            .scratch::<(Object, (i32, i32, TileId, Option<u32>))>();
         let map = self.map.borrow();
 
         // Query: handle collisions between blocks and objects with positions, velocities, and
         // colliders. In addition, collect "headbutt" events to be dispatched to Lua once the
         // query is finished and the borrows are released.
-        to_headbutt.clear();
-        for (player_object, (Position(pos), Velocity(vel), collider, maybe_player)) in
+        for (player_object, (Position(pos), Velocity(vel), collider, maybe_player, substeps)) in
             self.space.borrow_mut().query_mut::<(
                 &mut Position,
                 &mut Velocity,
                 &Collider,
                 Option<&PlayerMarker>,
+                Option<&SubstepCount>,
             )>()
         {
             let mut is_grounded = false;
+            // Collisions are resolved once per substep, but a headbutt should only be reported
+            // once per frame; the first substep to find one wins.
+            let mut headbutt = None;
 
-            // First, resolve X-axis collisions and movement.
-            pos.translation.vector.x += vel.linear.x * TIMESTEP;
-
-            let mut aabb = collider.compute_aabb(pos);
-            let pixel_aabb = aabb.floor_to_i32();
-
-            for (tile, x, y) in map.get_tiles_in_bb(
-                pixel_aabb,
-                *map.tile_layer_map.get("Foreground").unwrap(),
-                CoordSpace::Pixel,
-            ) {
-                let mut tile_bb = Box2::<f32>::invalid();
-                if let Some(object_group) = map.get_obj_grp_from_tile_id(&tile) {
-                    for object in map.get_objs_from_obj_group(object_group) {
-                        tile_bb.merge(&Box2::new(
-                            object.x + (x * map.meta_data.tilewidth as i32) as f32,
-                            object.y + (y * map.meta_data.tileheight as i32) as f32,
-                            object.width,
-                            object.height,
-                        ));
-                    }
-                }
+            let substeps = substeps.copied().unwrap_or(DEFAULT_SUBSTEPS);
+            for sub_dt in substeps.timesteps(TIMESTEP) {
+                // First, resolve X-axis collisions and movement.
+                pos.translation.vector.x += vel.linear.x * sub_dt;
 
-                if aabb.intersects(&tile_bb) {
-                    let overlap = aabb.overlap(&tile_bb);
-                    let intersection = aabb.intersection(&tile_bb);
-
-                    // Only process this collision if we are more than "touching".
-                    if intersection.extents().x > 0. && intersection.extents().y > 0. {
-                        pos.translation.vector.x -= overlap.x;
-                        aabb = collider.compute_aabb(pos);
-
-                        if vel.linear.x.signum() == overlap.x.signum() {
-                            if maybe_player.is_none() {
-                                // If we're not a player, swap the direction
-                                vel.linear.x *= -1.;
-                            } else {
-                                // If the collision is in the direction we're moving, stop.
-                                vel.linear.x = 0.;
-                            }
+                let mut aabb = collider.compute_aabb(pos);
+                let pixel_aabb = aabb.floor_to_i32();
 
-                            // TODO: Collision state (touching left/right)
-                        }
-                    }
-                }
-            }
-
-            // Second, resolve Y-axis collisions and movement.
-            pos.translation.vector.y += vel.linear.y * TIMESTEP;
-
-            let mut aabb = collider.compute_aabb(pos);
-            let pixel_aabb = aabb.floor_to_i32();
-
-            // This is a specialized variant of the collision checks from before where we only look
-            // for Y collisions happening "above" the player. This is our "block headbutt" check,
-            // and it tries to find the collision candidate above the player which is closest to the
-            // player's coordinate; this is so that there's no mysterious behavior where the player
-            // can't headbutt a block because they're just barely touching an adjacent block or
-            // something. "Distance" used for picking these candidates is just X axis distance; no
-            // need to consider Y.
-            //
-            // The biggest difference is that this loop does not change the player's velocity or
-            // position. Its only job is to check for headbutts. Resolution is taken care of for all
-            // collider + position objects after this if block.
-            if maybe_player.is_some() {
-                let mut closest = None;
-
-                // This is most likely overkill - we only really need to check the tiles above the
-                // player. But that would depend on the player's hitbox, which will change when
-                // transforming from big to small or vice versa, and this is general enough to cover
-                // all the possibilities.
                 for (tile, x, y) in map.get_tiles_in_bb(
                     pixel_aabb,
                     *map.tile_layer_map.get("Foreground").unwrap(),
@@ -393,84 +330,154 @@ impl SmbOneOne {
                         let overlap = aabb.overlap(&tile_bb);
                         let intersection = aabb.intersection(&tile_bb);
 
-                        // If we're colliding, our velocity is positive, we're colliding from the
-                        // bottom, and we're the player (in this loop), then register a headbutt
-                        // candidate.
-                        if intersection.extents().x > 0.
-                            && intersection.extents().y > 0.
-                            && vel.linear.y.signum() > 0.
-                            && overlap.y.signum() > 0.
-                        {
-                            // Woo that's a long string to get out an `Option<u32>` containing
-                            // `Some` if the tileset has a tile this tile should turn into when it
-                            // gets hit!
-                            let hittable = map
-                                .tilesets
-                                .get_tile(&tile)
-                                .unwrap()
-                                .properties
-                                .get_property("hittable")
-                                .map(hv_tiled::Property::as_int)
-                                .transpose()?
-                                .copied()
-                                .map(|x| x as u32);
-
-                            let distance = (pos.center().x
-                                - (x as f32 + 0.5) * (map.meta_data.tilewidth as f32))
-                                .abs();
-
-                            match closest {
-                                Some((_, _, _, _, cdistance)) if cdistance <= distance => {}
-                                _ => closest = Some((x, y, tile, hittable, distance)),
+                        // Only process this collision if we are more than "touching".
+                        if intersection.extents().x > 0. && intersection.extents().y > 0. {
+                            pos.translation.vector.x -= overlap.x;
+                            aabb = collider.compute_aabb(pos);
+
+                            if vel.linear.x.signum() == overlap.x.signum() {
+                                if maybe_player.is_none() {
+                                    // If we're not a player, swap the direction
+                                    vel.linear.x *= -1.;
+                                } else {
+                                    // If the collision is in the direction we're moving, stop.
+                                    vel.linear.x = 0.;
+                                }
+
+                                // TODO: Collision state (touching left/right)
                             }
                         }
                     }
                 }
 
-                // If we were headbutting a block, then `closest` now contains the closest headbutt
-                // candidate.
-                if let Some((x, y, tile, hittable, _)) = closest {
-                    to_headbutt.push((player_object, (x, y, tile, hittable)));
-                }
-            }
+                // Second, resolve Y-axis collisions and movement.
+                pos.translation.vector.y += vel.linear.y * sub_dt;
+
+                let mut aabb = collider.compute_aabb(pos);
+                let pixel_aabb = aabb.floor_to_i32();
+
+                // This is a specialized variant of the collision checks from before where we only
+                // look for Y collisions happening "above" the player. This is our "block headbutt"
+                // check, and it tries to find the collision candidate above the player which is
+                // closest to the player's coordinate; this is so that there's no mysterious
+                // behavior where the player can't headbutt a block because they're just barely
+                // touching an adjacent block or something. "Distance" used for picking these
+                // candidates is just X axis distance; no need to consider Y.
+                //
+                // The biggest difference is that this loop does not change the player's velocity or
+                // position. Its only job is to check for headbutts. Resolution is taken care of for
+                // all collider + position objects after this if block.
+                if maybe_player.is_some() {
+                    let mut closest = None;
+
+                    // This is most likely overkill - we only really need to check the tiles above
+                    // the player. But that would depend on the player's hitbox, which will change
+                    // when transforming from big to small or vice versa, and this is general enough
+                    // to cover all the possibilities.
+                    for (tile, x, y) in map.get_tiles_in_bb(
+                        pixel_aabb,
+                        *map.tile_layer_map.get("Foreground").unwrap(),
+                        CoordSpace::Pixel,
+                    ) {
+                        let mut tile_bb = Box2::<f32>::invalid();
+                        if let Some(object_group) = map.get_obj_grp_from_tile_id(&tile) {
+                            for object in map.get_objs_from_obj_group(object_group) {
+                                tile_bb.merge(&Box2::new(
+                                    object.x + (x * map.meta_data.tilewidth as i32) as f32,
+                                    object.y + (y * map.meta_data.tileheight as i32) as f32,
+                                    object.width,
+                                    object.height,
+                                ));
+                            }
+                        }
 
-            for (tile, x, y) in map.get_tiles_in_bb(
-                pixel_aabb,
-                *map.tile_layer_map.get("Foreground").unwrap(),
-                CoordSpace::Pixel,
-            ) {
-                let mut tile_bb = Box2::<f32>::invalid();
-                if let Some(object_group) = map.get_obj_grp_from_tile_id(&tile) {
-                    for object in map.get_objs_from_obj_group(object_group) {
-                        tile_bb.merge(&Box2::new(
-                            object.x + (x * map.meta_data.tilewidth as i32) as f32,
-                            object.y + (y * map.meta_data.tileheight as i32) as f32,
-                            object.width,
-                            object.height,
-                        ));
+                        if aabb.intersects(&tile_bb) {
+                            let overlap = aabb.overlap(&tile_bb);
+                            let intersection = aabb.intersection(&tile_bb);
+
+                            // If we're colliding, our velocity is positive, we're colliding from
+                            // the bottom, and we're the player (in this loop), then register a
+                            // headbutt candidate.
+                            if intersection.extents().x > 0.
+                                && intersection.extents().y > 0.
+                                && vel.linear.y.signum() > 0.
+                                && overlap.y.signum() > 0.
+                            {
+                                // Woo that's a long string to get out an `Option<u32>` containing
+                                // `Some` if the tileset has a tile this tile should turn into when
+                                // it gets hit!
+                                let hittable = map
+                                    .tilesets
+                                    .get_tile(&tile)
+                                    .unwrap()
+                                    .properties
+                                    .get_property("hittable")
+                                    .map(hv_tiled::Property::as_int)
+                                    .transpose()?
+                                    .copied()
+                                    .map(|x| x as u32);
+
+                                let distance = (pos.center().x
+                                    - (x as f32 + 0.5) * (map.meta_data.tilewidth as f32))
+                                    .abs();
+
+                                match closest {
+                                    Some((_, _, _, _, cdistance)) if cdistance <= distance => {}
+                                    _ => closest = Some((x, y, tile, hittable, distance)),
+                                }
+                            }
+                        }
+                    }
+
+                    // If we were headbutting a block, then `closest` now contains the closest
+                    // headbutt candidate.
+                    if let Some((x, y, tile, hittable, _)) = closest {
+                        headbutt.get_or_insert((x, y, tile, hittable));
                     }
                 }
 
-                if aabb.intersects(&tile_bb) {
-                    let overlap = aabb.overlap(&tile_bb);
-                    let intersection = aabb.intersection(&tile_bb);
+                for (tile, x, y) in map.get_tiles_in_bb(
+                    pixel_aabb,
+                    *map.tile_layer_map.get("Foreground").unwrap(),
+                    CoordSpace::Pixel,
+                ) {
+                    let mut tile_bb = Box2::<f32>::invalid();
+                    if let Some(object_group) = map.get_obj_grp_from_tile_id(&tile) {
+                        for object in map.get_objs_from_obj_group(object_group) {
+                            tile_bb.merge(&Box2::new(
+                                object.x + (x * map.meta_data.tilewidth as i32) as f32,
+                                object.y + (y * map.meta_data.tileheight as i32) as f32,
+                                object.width,
+                                object.height,
+                            ));
+                        }
+                    }
 
-                    if intersection.extents().x > 0. && intersection.extents().y > 0. {
-                        pos.translation.vector.y -= overlap.y;
-                        aabb = collider.compute_aabb(pos);
+                    if aabb.intersects(&tile_bb) {
+                        let overlap = aabb.overlap(&tile_bb);
+                        let intersection = aabb.intersection(&tile_bb);
 
-                        if vel.linear.y.signum() == overlap.y.signum() {
-                            vel.linear.y = 0.;
+                        if intersection.extents().x > 0. && intersection.extents().y > 0. {
+                            pos.translation.vector.y -= overlap.y;
+                            aabb = collider.compute_aabb(pos);
 
-                            // TODO: Collision state (touching up/down)
-                            if overlap.y.signum() < 0. {
-                                is_grounded = true;
+                            if vel.linear.y.signum() == overlap.y.signum() {
+                                vel.linear.y = 0.;
+
+                                // TODO: Collision state (touching up/down)
+                                if overlap.y.signum() < 0. {
+                                    is_grounded = true;
+                                }
                             }
                         }
                     }
                 }
             }
 
+            if let Some(headbutt) = headbutt {
+                to_headbutt.push((player_object, headbutt));
+            }
+
             player_object
                 .to_table(lua)?
                 .set("is_grounded", is_grounded)?;
@@ -489,16 +496,16 @@ impl SmbOneOne {
     }
 
     fn dispatch_object_on_object_collisions(&self, _engine: &Engine, lua: &Lua) -> Result<()> {
-        let mut to_collide = self.to_collide.borrow_mut();
+        let mut to_collide = self.space.borrow().scratch::<(Object, Object)>();
         // Mario can only collide with 1 enemy per frame, so we limit the amount of objects here
         // to just 1
 
-        // Collect any object-on-object collisions events, for later dispatch to Lua.
-        to_collide.clear();
+        // Collect any object-on-object collisions events, for later dispatch to Lua. The pairs are
+        // generated in slot order, so that collisions are dispatched in the same order every frame.
         for (object1, (Position(pos1), collider1)) in self
             .space
             .borrow()
-            .query::<(&Position, &Collider)>()
+            .query_sorted::<(&Position, &Collider)>()
             .without::<Unloaded>()
             .without::<PlayerMarker>()
             .iter()
@@ -506,7 +513,7 @@ impl SmbOneOne {
             for (object2, (Position(pos2), collider2)) in self
                 .space
                 .borrow()
-                .query::<(&Position, &Collider)>()
+                .query_sorted::<(&Position, &Collider)>()
                 .without::<Unloaded>()
                 .iter()
                 .filter(|&(object2, _)| object1 != object2)
@@ -562,8 +569,9 @@ impl SmbOneOne {
 
     fn update_object_sprite_batches(&self, engine: &Engine, lua: &Lua) -> Result<()> {
         {
-            let mut goomba_batch = self.goomba_batch.borrow_mut();
-            goomba_batch.clear();
+            let mut enemy_batch = self.enemy_batch.borrow_mut();
+            enemy_batch.clear();
+
             let goomba_sheet = self.goomba_sheet.get();
             for (_, (Position(pos), animation)) in self
                 .space
@@ -572,18 +580,14 @@ impl SmbOneOne {
                 .with::<GoombaMarker>()
             {
                 let frame = goomba_sheet[animation.animation.frame_id];
-                goomba_batch.insert(
+                enemy_batch.insert(
                     Instance::new()
                         .translate2(pos.center().coords - Vector2::new(8., 8.))
                         .src(frame.uvs)
                         .translate2(frame.offset),
                 );
             }
-        }
 
-        {
-            let mut koopa_batch = self.koopa_batch.borrow_mut();
-            koopa_batch.clear();
             let koopa_sheet = self.koopa_sheet.get();
             for (_, (Position(pos), animation)) in self
                 .space
@@ -592,7 +596,7 @@ impl SmbOneOne {
                 .with::<KoopaMarker>()
             {
                 let frame = koopa_sheet[animation.animation.frame_id];
-                koopa_batch.insert(
+                enemy_batch.insert(
                     Instance::new()
                         .translate2(pos.center().coords - Vector2::new(8., 8.))
                         .src(frame.uvs)
@@ -688,24 +692,7 @@ impl SmbOneOne {
             .borrow_mut()
             .update_all_batches(dt, &self.ts_render_data);
 
-        let mut to_update = self.to_update.borrow_mut();
-
-        for (obj, ()) in self
-            .space
-            .borrow()
-            .query::<()>()
-            .with::<PlayerMarker>()
-            .iter()
-        {
-            to_update.push(obj);
-        }
-
-        for obj in to_update.drain(..) {
-            let table = LuaTable::from_lua(obj.to_lua(lua)?, lua)?;
-            table.call_method("update", ())?;
-        }
-
-        Ok(())
+        call_method_on_all::<PlayerMarker, _>(lua, &self.space, "update", ())
     }
 
     fn draw(&self, engine: &Engine) -> Result<()> {
@@ -741,10 +728,7 @@ impl SmbOneOne {
         self.item_batch
             .borrow_mut()
             .draw_mut(&mut gfx, Instance::new());
-        self.goomba_batch
-            .borrow_mut()
-            .draw_mut(&mut gfx, Instance::new());
-        self.koopa_batch
+        self.enemy_batch
             .borrow_mut()
             .draw_mut(&mut gfx, Instance::new());
         tile_layer_batches
@@ -967,6 +951,12 @@ impl EventHandler for SmbOneOneEventHandler {
         }
     }
 
+    fn focus_event(&mut self, _engine: &Engine, focused: bool) {
+        if !focused {
+            self.input_state.borrow_mut().reset_input_state();
+        }
+    }
+
     fn char_event(&mut self, _engine: &Engine, _character: char, _keymods: KeyMods, _repeat: bool) {
         // self.inner
         //     .borrow_mut()
@@ -0,0 +1,271 @@
+//! Declarative asset manifests, for loading everything a game needs up front.
+//!
+//! Rather than scattering `get_or_load` calls through constructors and finding out about a missing
+//! file the first time it's needed, a game can list its assets in a JSON manifest:
+//!
+//! ```json
+//! {
+//!     "assets": [
+//!         { "kind": "texture", "path": "/sprites/player.png" },
+//!         { "kind": "sprite_sheet", "path": "/sprites/player.json" }
+//!     ]
+//! }
+//! ```
+//!
+//! and load it with [`Engine::preload_manifest`](crate::engine::Engine::preload_manifest). Each
+//! kind of asset is preloaded by a function registered with
+//! [`Engine::register_asset_kind`](crate::engine::Engine::register_asset_kind), usually by the
+//! plugin which owns the corresponding cache; `hv-friends`, for example, registers `texture` and
+//! `sprite_sheet`.
+//!
+//! The whole manifest is validated before anything is loaded: every entry must have a registered
+//! kind and an absolute path to a file which exists in the [`Filesystem`], and every problem found
+//! is reported in a single error.
+
+use std::{collections::HashMap, fmt, io::Read};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{error::*, filesystem::Filesystem};
+
+/// A single asset listed in an [`AssetManifest`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AssetEntry {
+    /// The kind of asset, which decides which cache it's loaded into.
+    pub kind: String,
+    /// The absolute path to the asset in the [`Filesystem`].
+    pub path: String,
+}
+
+impl fmt::Display for AssetEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} `{}`", self.kind, self.path)
+    }
+}
+
+/// A list of assets to preload. See the [module-level docs](self).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AssetManifest {
+    /// Every asset in the manifest, in the order they'll be loaded.
+    pub assets: Vec<AssetEntry>,
+}
+
+impl AssetManifest {
+    /// Parse a manifest from JSON.
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json).context("error parsing asset manifest")
+    }
+
+    /// Parse a manifest from a reader containing JSON.
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self> {
+        serde_json::from_reader(reader).context("error parsing asset manifest")
+    }
+}
+
+/// How far along preloading a manifest is, passed to the progress callback of
+/// [`AssetKinds::preload`] after each asset is loaded. Useful for drawing a loading bar.
+#[derive(Debug, Clone, Copy)]
+pub struct PreloadProgress<'a> {
+    /// The asset which was just loaded.
+    pub entry: &'a AssetEntry,
+    /// The number of assets loaded so far, including this one.
+    pub loaded: usize,
+    /// The total number of assets in the manifest.
+    pub total: usize,
+}
+
+impl<'a> PreloadProgress<'a> {
+    /// The fraction of the manifest which has been loaded, from `0.` to `1.`.
+    pub fn fraction(&self) -> f32 {
+        self.loaded as f32 / self.total.max(1) as f32
+    }
+}
+
+type PreloadFn = Box<dyn Fn(&str) -> Result<()> + Send + Sync>;
+
+/// The registered kinds of asset, and how to load each of them into its cache.
+#[derive(Default)]
+pub struct AssetKinds {
+    kinds: HashMap<String, PreloadFn>,
+}
+
+impl fmt::Debug for AssetKinds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.kinds.keys()).finish()
+    }
+}
+
+impl AssetKinds {
+    /// Create an empty set of asset kinds.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a kind of asset. `preload` is called with the path of every manifest entry of
+    /// this kind, and should load it into the relevant cache. Registering a kind again replaces
+    /// the old preload function.
+    pub fn register(
+        &mut self,
+        kind: impl Into<String>,
+        preload: impl Fn(&str) -> Result<()> + Send + Sync + 'static,
+    ) {
+        self.kinds.insert(kind.into(), Box::new(preload));
+    }
+
+    /// Whether a kind of asset has been registered.
+    pub fn contains(&self, kind: &str) -> bool {
+        self.kinds.contains_key(kind)
+    }
+
+    /// Check that every entry in the manifest has a registered kind and an absolute path to a
+    /// file which exists, reporting every entry which doesn't.
+    pub fn validate(&self, manifest: &AssetManifest, fs: &Filesystem) -> Result<()> {
+        let problems = manifest
+            .assets
+            .iter()
+            .filter_map(|entry| {
+                if !self.contains(&entry.kind) {
+                    Some(format!("{}: unknown asset kind", entry))
+                } else if !entry.path.starts_with('/') {
+                    Some(format!("{}: path must be absolute", entry))
+                } else if !fs.is_file(&entry.path) {
+                    Some(format!("{}: file not found", entry))
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        ensure!(
+            problems.is_empty(),
+            "invalid asset manifest:\n{}",
+            problems.join("\n")
+        );
+
+        Ok(())
+    }
+
+    /// Load every asset in the manifest, in order, calling `progress` after each one. The manifest
+    /// should already have been checked with [`AssetKinds::validate`].
+    pub fn preload(
+        &self,
+        manifest: &AssetManifest,
+        mut progress: impl FnMut(PreloadProgress),
+    ) -> Result<()> {
+        let total = manifest.assets.len();
+        for (i, entry) in manifest.assets.iter().enumerate() {
+            let preload = self
+                .kinds
+                .get(&entry.kind)
+                .ok_or_else(|| anyhow!("{}: unknown asset kind", entry))?;
+            preload(&entry.path).with_context(|| format!("error preloading {}", entry))?;
+            progress(PreloadProgress {
+                entry,
+                loaded: i + 1,
+                total,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        shared::Shared,
+        swappable_cache::{Loader, SwappableCache, UncachedHandle},
+    };
+    use std::path::PathBuf;
+
+    /// Loads files as their length in bytes.
+    struct LengthLoader(Shared<Filesystem>);
+
+    impl Loader<String, usize> for LengthLoader {
+        fn load(&mut self, key: &String) -> Result<UncachedHandle<usize>> {
+            let mut buf = Vec::new();
+            self.0.borrow_mut().open(key)?.read_to_end(&mut buf)?;
+            Ok(UncachedHandle::new(buf.len()))
+        }
+    }
+
+    fn resources_fs() -> Shared<Filesystem> {
+        let mut fs = Filesystem::new();
+        fs.mount(
+            &PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("resources"),
+            true,
+        );
+        Shared::new(fs)
+    }
+
+    #[test]
+    fn manifest_entries_are_preloaded_into_their_caches() {
+        let fs = resources_fs();
+        let scripts = Shared::new(SwappableCache::new(LengthLoader(fs.clone())));
+        let mut kinds = AssetKinds::new();
+        let cache = scripts.clone();
+        kinds.register("script", move |path| {
+            cache.borrow_mut().get_or_load(path.to_owned()).map(drop)
+        });
+
+        let manifest = AssetManifest::from_json(
+            r#"{
+                "assets": [
+                    { "kind": "script", "path": "/scripts/std.lua" },
+                    { "kind": "script", "path": "/scripts/std/binser.lua" }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        kinds.validate(&manifest, &fs.borrow()).unwrap();
+        let mut reported = Vec::new();
+        kinds
+            .preload(&manifest, |progress| {
+                reported.push((progress.entry.path.clone(), progress.loaded, progress.total))
+            })
+            .unwrap();
+
+        assert_eq!(
+            reported,
+            [
+                ("/scripts/std.lua".to_owned(), 1, 2),
+                ("/scripts/std/binser.lua".to_owned(), 2, 2),
+            ]
+        );
+        for entry in &manifest.assets {
+            assert!(scripts.borrow().contains_key(&entry.path));
+        }
+    }
+
+    #[test]
+    fn validation_reports_every_bad_entry() {
+        let fs = resources_fs();
+        let mut kinds = AssetKinds::new();
+        kinds.register("script", |_| Ok(()));
+
+        let manifest = AssetManifest::from_json(
+            r#"{
+                "assets": [
+                    { "kind": "script", "path": "/scripts/std.lua" },
+                    { "kind": "texture", "path": "/scripts/std.lua" },
+                    { "kind": "script", "path": "scripts/std.lua" },
+                    { "kind": "script", "path": "/scripts/missing.lua" }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let message = kinds
+            .validate(&manifest, &fs.borrow())
+            .unwrap_err()
+            .to_string();
+        assert!(message.contains("texture `/scripts/std.lua`: unknown asset kind"));
+        assert!(message.contains("script `scripts/std.lua`: path must be absolute"));
+        assert!(message.contains("script `/scripts/missing.lua`: file not found"));
+        assert!(!message.contains("script `/scripts/std.lua`"));
+
+        assert!(AssetManifest::from_json(r#"{ "assets": [{ "kind": "script" }] }"#).is_err());
+    }
+}
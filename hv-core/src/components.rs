@@ -5,13 +5,18 @@
 //! wrapped in an extra layer which tells Rust *how* to add the component. For unfortunate technical
 //! reasons, the component type itself cannot be the Lua userdata passed to functions like
 //! `Space::spawn`.
+//!
+//! If you need to refer to a component *type* from Lua, for example to query a space for all
+//! objects with a given set of components, the type needs to be registered by name with
+//! [`component_type!`](crate::component_type).
 
 use smallbox::{space::S4, SmallBox};
+use std::any::{self, TypeId};
 
 use crate::{
     engine::Engine,
     error::*,
-    hecs::{ColumnBatchType, Component, EntityBuilder},
+    hecs::{ColumnBatchType, Component, EntityBuilder, With},
     mlua::prelude::*,
     plugins::{ModuleWrapper, Plugin},
     spaces::{Object, Space},
@@ -123,6 +128,80 @@ impl DynamicComponentConstructor {
 
 impl LuaUserData for DynamicComponentConstructor {}
 
+/// A component type registered under a name usable from Lua. See [`component_type!`].
+///
+/// [`component_type!`]: crate::component_type
+pub struct ComponentType {
+    name: &'static str,
+    type_id: TypeId,
+    type_name: &'static str,
+    is_on_object: fn(&Space, Object) -> bool,
+}
+
+inventory::collect!(ComponentType);
+
+impl ComponentType {
+    #[doc(hidden)]
+    pub fn new<T: Component>(name: &'static str) -> Self {
+        Self {
+            name,
+            type_id: TypeId::of::<T>(),
+            type_name: any::type_name::<T>(),
+            is_on_object: |space, object| {
+                // `With` checks for the component without borrowing it, so this never conflicts
+                // with borrows of the component held elsewhere.
+                space
+                    .query_one::<With<T, ()>>(object)
+                    .map(|mut query| query.get().is_some())
+                    .unwrap_or(false)
+            },
+        }
+    }
+
+    /// Look up a registered component type by the name it was registered with.
+    pub fn lookup(name: &str) -> Option<&'static ComponentType> {
+        inventory::iter::<ComponentType>
+            .into_iter()
+            .find(|component_type| component_type.name == name)
+    }
+
+    /// The name this component type was registered under.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The [`TypeId`] of the registered component type.
+    pub fn type_id(&self) -> TypeId {
+        self.type_id
+    }
+
+    /// The Rust type name of the registered component type, for debugging purposes.
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    /// Check whether an object has a component of this type. Returns `false` if the object does
+    /// not exist in the space.
+    pub fn is_on_object(&self, space: &Space, object: Object) -> bool {
+        (self.is_on_object)(space, object)
+    }
+}
+
+/// Register a component type under a name, so that it can be referred to from Lua (for example, in
+/// `space:query { "Position", "Velocity" }`.)
+///
+/// Like [`serializable!`](crate::serializable), this uses [`inventory::submit!`] under the hood and
+/// can be placed at the top level of a module.
+#[macro_export]
+macro_rules! component_type {
+    ($name:expr, $ty:ty) => {
+        const _: () = {
+            use $crate::inventory;
+            $crate::inventory::submit!($crate::components::ComponentType::new::<$ty>($name));
+        };
+    };
+}
+
 #[doc(hidden)]
 pub struct ComponentWrapper {
     object: Box<dyn Plugin>,
@@ -1,14 +1,36 @@
 //! Configuration options for starting an `Engine`.
+//!
+//! Everything in a [`Conf`] except for the filesystem and window icon can be saved to and loaded
+//! from a JSON settings file, so that players can change things like the resolution without
+//! recompiling:
+//!
+//! ```json
+//! { "window_width": 1280, "window_height": 720, "fullscreen": true }
+//! ```
+//!
+//! Settings files are parsed leniently so that they keep working across versions of a game:
+//! fields which are missing are given their default values, and fields which aren't recognized are
+//! ignored. Key bindings can be saved alongside them by serializing an
+//! [`InputBinding`](crate::input::InputBinding).
 
-use crate::filesystem::Filesystem;
+use std::path::Path;
+
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+
+use crate::{error::*, filesystem::Filesystem};
 
 /// Miscellaneous configuration options for [`Engine`](crate::engine::Engine).
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Conf {
     /// The filesystem object to be used by the [`Engine`](crate::engine::Engine). Setting this with
     /// custom settings allows you to "mount" new directories onto it, add ZIP files, set an
     /// "offset" for your resource directory, and more; please refer to the [`Filesystem`] type for
     /// more options.
+    ///
+    /// Not saved to or loaded from settings files.
+    #[serde(skip)]
     pub filesystem: Filesystem,
     /// The window's title.
     pub window_title: String,
@@ -16,6 +38,28 @@ pub struct Conf {
     pub window_width: u32,
     /// The height of the window in pixels.
     pub window_height: u32,
+    /// The window's icon. It will be scaled down to the sizes the platform expects, so a square
+    /// image at least 64x64 pixels in size is best.
+    ///
+    /// Not saved to or loaded from settings files.
+    #[serde(skip)]
+    pub window_icon: Option<RgbaImage>,
+    /// Whether the window should start out fullscreen.
+    pub fullscreen: bool,
+    /// The number of samples per pixel to use for multisample anti-aliasing of the main
+    /// framebuffer. If the platform doesn't support the requested count, it falls back to a lower
+    /// one, usually 1.
+    pub sample_count: u8,
+    /// Whether to render at the display's physical resolution on HiDPI displays, rather than at
+    /// the logical window size and letting the OS upscale. Window sizes and mouse positions are
+    /// reported in logical pixels either way; see the [`window`](crate::window#dpi) module.
+    pub high_dpi: bool,
+    /// Whether to wait for the display's vertical refresh before presenting each frame. Turning
+    /// this off lets the game run as fast as it can, unless capped with [`Conf::target_fps`].
+    pub vsync: bool,
+    /// A frame rate to cap the game to, independently of vsync. Can be changed while the game is
+    /// running with [`Engine::set_target_fps`](crate::engine::Engine::set_target_fps).
+    pub target_fps: Option<u32>,
 }
 
 impl Default for Conf {
@@ -25,6 +69,71 @@ impl Default for Conf {
             window_title: "HEAVY \\m/".to_string(),
             window_width: 800,
             window_height: 680,
+            window_icon: None,
+            fullscreen: false,
+            sample_count: 1,
+            high_dpi: false,
+            vsync: true,
+            target_fps: None,
         }
     }
 }
+
+impl Conf {
+    /// Parse a configuration from a JSON settings string. Missing fields are given their default
+    /// values, and unknown fields are ignored.
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json).context("error parsing configuration")
+    }
+
+    /// Serialize the configuration to a JSON settings string.
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Load a configuration from a JSON settings file. Missing fields are given their default
+    /// values, and unknown fields are ignored.
+    pub fn load_from<P: AsRef<Path>>(fs: &mut Filesystem, path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file = fs.open(path)?;
+        serde_json::from_reader(file)
+            .with_context(|| format!("error parsing configuration file {}", path.display()))
+    }
+
+    /// Save the configuration to a JSON settings file, overwriting it if it already exists.
+    pub fn save_to<P: AsRef<Path>>(&self, fs: &mut Filesystem, path: P) -> Result<()> {
+        let file = fs.create(path)?;
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_config_is_filled_with_defaults() {
+        let conf = Conf::from_json(
+            r#"{
+                "window_width": 1280,
+                "fullscreen": true,
+                "from_a_newer_version": [1, 2, 3]
+            }"#,
+        )
+        .unwrap();
+
+        let default = Conf::default();
+        assert_eq!(conf.window_width, 1280);
+        assert!(conf.fullscreen);
+        assert_eq!(conf.window_height, default.window_height);
+        assert_eq!(conf.window_title, default.window_title);
+        assert_eq!(conf.sample_count, default.sample_count);
+        assert!(conf.vsync);
+        assert_eq!(conf.target_fps, None);
+
+        let round_tripped = Conf::from_json(&conf.to_json().unwrap()).unwrap();
+        assert_eq!(round_tripped.window_width, 1280);
+        assert!(round_tripped.fullscreen);
+    }
+}
@@ -0,0 +1,134 @@
+//! Debugging aids built into the engine loop.
+//!
+//! The main one is frame stepping: with [`Engine::set_frame_step`] enabled, the engine stops
+//! calling [`EventHandler::update`](crate::engine::EventHandler::update) every frame and instead
+//! runs exactly one update per call to [`Engine::step_frame`], while drawing and event handling
+//! carry on as usual. That makes it possible to bind a key to advance the game one update at a time
+//! and inspect what's on screen in between, which is handy for physics and replay debugging.
+//!
+//! Each stepped update is passed the same fixed `dt` as a normal one, no matter how long the game
+//! sat paused in between, so stepping through a stretch of the game runs exactly the same updates
+//! as playing through it. Anything the game does with that `dt` (slowing it down, or skipping
+//! updates while its own pause menu is open) applies to stepped updates just the same.
+//!
+//! From Lua, this is available as `hv.debug.set_frame_step(enabled)`, `hv.debug.is_frame_step()`
+//! and `hv.debug.step()`.
+
+use crate::{
+    engine::Engine,
+    error::*,
+    mlua::prelude::*,
+    plugins::{ModuleWrapper, Plugin},
+};
+
+/// Decides which frames run an update, for frame stepping. See the [module docs](self).
+#[derive(Debug, Clone, Default)]
+pub struct FrameStep {
+    enabled: bool,
+    pending: u32,
+}
+
+impl FrameStep {
+    /// Whether frame stepping is on.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Turn frame stepping on or off. Steps requested but not yet run are dropped either way, so
+    /// turning it back on never runs a burst of stale steps.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.pending = 0;
+    }
+
+    /// Request one more update. Does nothing unless frame stepping is on.
+    pub fn step(&mut self) {
+        if self.enabled {
+            self.pending += 1;
+        }
+    }
+
+    /// Called once per frame by the engine loop: whether this frame should run an update. Always
+    /// true with frame stepping off; with it on, true once per requested step, one frame at a time.
+    pub fn should_update(&mut self) -> bool {
+        if !self.enabled {
+            true
+        } else if self.pending > 0 {
+            self.pending -= 1;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+struct DebugModule;
+
+impl Plugin for DebugModule {
+    fn name(&self) -> &'static str {
+        "debug"
+    }
+
+    fn open<'lua>(&self, lua: &'lua Lua, engine: &Engine) -> Result<LuaTable<'lua>> {
+        let weak = engine.downgrade();
+        let set_frame_step = lua.create_function(move |_, enabled: bool| {
+            weak.upgrade().set_frame_step(enabled);
+            Ok(())
+        })?;
+
+        let weak = engine.downgrade();
+        let is_frame_step = lua.create_function(move |_, ()| Ok(weak.upgrade().is_frame_step()))?;
+
+        let weak = engine.downgrade();
+        let step = lua.create_function(move |_, ()| {
+            weak.upgrade().step_frame();
+            Ok(())
+        })?;
+
+        Ok(lua
+            .load(mlua::chunk! {
+                {
+                    set_frame_step = $set_frame_step,
+                    is_frame_step = $is_frame_step,
+                    step = $step,
+                }
+            })
+            .eval()?)
+    }
+}
+
+inventory::submit!(ModuleWrapper::new(DebugModule));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run `frames` frames, returning how many of them updated.
+    fn run(frame_step: &mut FrameStep, frames: usize) -> usize {
+        (0..frames).filter(|_| frame_step.should_update()).count()
+    }
+
+    #[test]
+    fn each_step_runs_exactly_one_update() {
+        let mut frame_step = FrameStep::default();
+        assert_eq!(run(&mut frame_step, 3), 3);
+
+        frame_step.set_enabled(true);
+        assert_eq!(run(&mut frame_step, 10), 0);
+
+        for _ in 0..3 {
+            frame_step.step();
+        }
+        // Steps are run one per frame, however many frames go by.
+        assert_eq!(run(&mut frame_step, 1), 1);
+        assert_eq!(run(&mut frame_step, 10), 2);
+        assert_eq!(run(&mut frame_step, 10), 0);
+
+        // Disabling frame stepping drops unrun steps and goes back to updating every frame.
+        frame_step.step();
+        frame_step.set_enabled(false);
+        assert_eq!(run(&mut frame_step, 5), 5);
+        frame_step.set_enabled(true);
+        assert_eq!(run(&mut frame_step, 5), 0);
+    }
+}
@@ -10,20 +10,29 @@ use {
     std::{
         any::{Any, TypeId},
         collections::HashMap,
+        io::{Read, Write},
         marker::PhantomData,
         sync::{Arc as StdArc, Mutex, MutexGuard, Weak as StdWeak},
     },
 };
 
 use gilrs::Gilrs;
+use image::{imageops, RgbaImage};
+use nalgebra::Point2;
+use shrev::EventChannel;
 
 use crate::{
+    assets::{AssetKinds, AssetManifest, PreloadProgress},
     conf::Conf,
+    debug::FrameStep,
     error::*,
     filesystem::Filesystem,
-    input::{CursorIcon, GamepadAxis, GamepadButton, KeyCode, KeyMods, MouseButton},
+    input::{CursorIcon, GamepadAxis, GamepadButton, KeyCode, KeyMods, MouseButton, TouchPhase},
     mlua::prelude::*,
     shared::{Shared, Weak},
+    spaces::{serialize, Space, Spaces},
+    timer::FrameLimiter,
+    window::{physical_to_logical, WindowState},
 };
 
 /// Currently miniquad's update rate is fixed to 60 frames per second.
@@ -64,6 +73,9 @@ struct EngineInner {
     lua: Mutex<Lua>,
     mq: Mutex<mq::Context>,
     fs: Mutex<Filesystem>,
+    window: Mutex<WindowState>,
+    frame_limiter: Mutex<FrameLimiter>,
+    frame_step: Mutex<FrameStep>,
     gilrs: Mutex<SendWrapper<Gilrs>>,
     resources: Mutex<HashMap<TypeId, Box<dyn Any + Send + Sync>>>,
 }
@@ -93,7 +105,13 @@ impl Engine<'static> {
     ///
     /// ***Normally you will never call this yourself!*** You will almost always want to use
     /// [`Engine::run`] instead!!
-    pub fn new(fs: Filesystem, mq: mq::Context, handler: impl EventHandler) -> Result<Self> {
+    pub fn new(
+        fs: Filesystem,
+        window: WindowState,
+        frame_limiter: FrameLimiter,
+        mq: mq::Context,
+        handler: impl EventHandler,
+    ) -> Result<Self> {
         use mlua::StdLib;
         let lua = Lua::new_with(
             /* /* if using Lua 5.2 or above and *not* 5.1 or LuaJIT: */ StdLib::COROUTINE | */
@@ -108,6 +126,9 @@ impl Engine<'static> {
                 lua: Mutex::new(lua),
                 mq: Mutex::new(mq),
                 fs: Mutex::new(fs),
+                window: Mutex::new(window),
+                frame_limiter: Mutex::new(frame_limiter),
+                frame_step: Mutex::new(FrameStep::default()),
                 gilrs: Mutex::new(send_wrapper::SendWrapper::new(
                     Gilrs::new().expect("unrecoverable error initializing gilrs"),
                 )),
@@ -155,14 +176,28 @@ impl Engine<'static> {
         handler_constructor: impl FnOnce(&Engine) -> Result<H> + Send + Sync + 'static,
     ) {
         let handler = LazyHandler::new(handler_constructor);
+        let window = WindowState::from_conf(&conf);
+        let frame_limiter = FrameLimiter::new(conf.target_fps);
         mq::start(
             mq::conf::Conf {
                 window_title: conf.window_title.clone(),
                 window_width: conf.window_width as i32,
                 window_height: conf.window_height as i32,
+                fullscreen: conf.fullscreen,
+                sample_count: conf.sample_count.max(1) as i32,
+                high_dpi: conf.high_dpi,
+                icon: conf.window_icon.as_ref().map(to_mq_icon),
+                platform: mq::conf::Platform {
+                    swap_interval: Some(if conf.vsync { 1 } else { 0 }),
+                    ..mq::conf::Platform::default()
+                },
                 ..mq::conf::Conf::default()
             },
-            move |ctx| mq::UserData::free(Self::new(conf.filesystem, ctx, handler).unwrap()),
+            move |ctx| {
+                mq::UserData::free(
+                    Self::new(conf.filesystem, window, frame_limiter, ctx, handler).unwrap(),
+                )
+            },
         );
     }
 }
@@ -190,6 +225,29 @@ impl<'a> Engine<'a> {
         self.inner.mq.try_lock().unwrap()
     }
 
+    /// The number of physical pixels per logical pixel. This is `1.` unless [`Conf::high_dpi`] is
+    /// set and the window is on a HiDPI display.
+    ///
+    /// Everything the engine hands to an [`EventHandler`] (mouse and touch positions, window sizes)
+    /// is in logical pixels, and is converted back to physical pixels only where the framebuffer
+    /// needs it, so that the game, egui, and the graphics viewport all agree on where the pointer
+    /// is. See [`window::physical_to_logical`](crate::window::physical_to_logical).
+    pub fn dpi_scale(&self) -> f32 {
+        self.mq().dpi_scale()
+    }
+
+    /// The size of the window in logical pixels.
+    pub fn screen_size(&self) -> (f32, f32) {
+        let mq = self.mq();
+        let (w, h) = mq.screen_size();
+        (w / mq.dpi_scale(), h / mq.dpi_scale())
+    }
+
+    /// Acquire a lock on the [`WindowState`].
+    pub fn window(&self) -> MutexGuard<WindowState> {
+        self.inner.window.try_lock().unwrap()
+    }
+
     /// Acquire a lock on the GilRs context.
     pub fn gilrs(&self) -> MutexGuard<SendWrapper<Gilrs>> {
         self.inner.gilrs.try_lock().unwrap()
@@ -234,6 +292,92 @@ impl<'a> Engine<'a> {
             .map(|entry| entry.downcast_ref::<Shared<T>>().unwrap().clone())
     }
 
+    /// Get the channel which events of type `E` are published to by [`Engine::emit`], creating it
+    /// if it doesn't exist yet. Subscribers register a reader on the channel, so that systems can
+    /// send each other events without holding references to each other.
+    pub fn event_channel<E: shrev::Event>(&self) -> Shared<EventChannel<E>> {
+        self.inner
+            .resources
+            .lock()
+            .unwrap()
+            .entry(TypeId::of::<EventChannel<E>>())
+            .or_insert_with(|| Box::new(Shared::new(EventChannel::<E>::new())))
+            .downcast_ref::<Shared<EventChannel<E>>>()
+            .unwrap()
+            .clone()
+    }
+
+    /// Publish an event to every reader subscribed to the channel for its type; see
+    /// [`Engine::event_channel`].
+    pub fn emit<E: shrev::Event>(&self, event: E) {
+        self.event_channel::<E>().borrow_mut().single_write(event);
+    }
+
+    /// Register a kind of asset which can be listed in an asset manifest; see
+    /// [`AssetKinds::register`] and [`Engine::preload_manifest`].
+    pub fn register_asset_kind(
+        &self,
+        kind: impl Into<String>,
+        preload: impl Fn(&str) -> Result<()> + Send + Sync + 'static,
+    ) {
+        self.asset_kinds().borrow_mut().register(kind, preload);
+    }
+
+    fn asset_kinds(&self) -> Shared<AssetKinds> {
+        self.inner
+            .resources
+            .lock()
+            .unwrap()
+            .entry(TypeId::of::<AssetKinds>())
+            .or_insert_with(|| Box::new(Shared::new(AssetKinds::new())))
+            .downcast_ref::<Shared<AssetKinds>>()
+            .unwrap()
+            .clone()
+    }
+
+    /// Load the [`AssetManifest`] at the given path in the [`Filesystem`] and preload every asset
+    /// it lists into the relevant caches, calling `progress` after each one. The manifest is
+    /// validated first, so if any entry has an unknown kind or a missing file, nothing is loaded.
+    /// See the [`assets`](crate::assets) module for the manifest format.
+    pub fn preload_manifest(
+        &self,
+        path: &str,
+        progress: impl FnMut(PreloadProgress),
+    ) -> Result<AssetManifest> {
+        let file = self.fs().open(path)?;
+        let manifest = AssetManifest::from_reader(file)
+            .with_context(|| format!("error reading asset manifest `{}`", path))?;
+
+        let kinds = self.asset_kinds();
+        let kinds = kinds.borrow();
+        kinds.validate(&manifest, &self.fs())?;
+        kinds.preload(&manifest, progress)?;
+
+        Ok(manifest)
+    }
+
+    /// Save every [`Space`](crate::spaces::Space) and the Lua object tables of their objects to a
+    /// writer, for quicksaves. Anything else which needs saving, such as an RNG's state, should be
+    /// kept in a serializable component or object table so that it's saved along with the rest.
+    /// See [`serialize::save_state`].
+    ///
+    /// Locks the Lua context, so this can't be called from inside a Lua callback.
+    pub fn save_state<W: Write>(&self, writer: W) -> Result<()> {
+        let spaces = self.get::<Spaces>();
+        let spaces = spaces.borrow();
+        serialize::save_state(&spaces, &self.lua(), writer)
+    }
+
+    /// Load a save state written by [`Engine::save_state`], replacing the contents of the engine's
+    /// spaces and returning them in the order they were saved. See [`serialize::load_state`].
+    ///
+    /// Locks the Lua context, so this can't be called from inside a Lua callback.
+    pub fn load_state<R: Read>(&self, reader: R) -> Result<Vec<Shared<Space>>> {
+        let spaces = self.get::<Spaces>();
+        let mut spaces = spaces.borrow_mut();
+        serialize::load_state(&mut spaces, &self.lua(), reader)
+    }
+
     /// Set whether the mouse is shown on-screen.
     pub fn show_mouse(&self, show: bool) {
         self.mq().show_mouse(show);
@@ -248,6 +392,79 @@ impl<'a> Engine<'a> {
     pub fn set_mouse_cursor(&self, icon: CursorIcon) {
         self.mq().set_mouse_cursor(icon.into());
     }
+
+    /// Set the window's title.
+    ///
+    /// miniquad can only set the title when the window is created, so for now this only updates
+    /// the title stored in [`Engine::window`].
+    pub fn set_window_title(&self, title: &str) {
+        self.window().set_title(title);
+    }
+
+    /// Set whether the window is fullscreen. The internal resolution in [`Engine::window`] is
+    /// unaffected; use [`WindowState::letterbox`] to keep rendering at the right aspect ratio
+    /// after the window changes size.
+    pub fn set_fullscreen(&self, fullscreen: bool) {
+        self.window().set_fullscreen(fullscreen);
+        self.mq().set_fullscreen(fullscreen);
+    }
+
+    /// The frame rate the game is capped to, if any.
+    pub fn target_fps(&self) -> Option<u32> {
+        self.inner.frame_limiter.try_lock().unwrap().target_fps()
+    }
+
+    /// Cap the frame rate, or stop capping it with `None`. The cap is enforced by waiting at the
+    /// end of each frame, after [`EventHandler::draw`]; see [`FrameLimiter`].
+    pub fn set_target_fps(&self, target_fps: Option<u32>) {
+        self.inner
+            .frame_limiter
+            .try_lock()
+            .unwrap()
+            .set_target_fps(target_fps);
+    }
+
+    /// Whether frame stepping is on; see [`Engine::set_frame_step`].
+    pub fn is_frame_step(&self) -> bool {
+        self.inner.frame_step.lock().unwrap().is_enabled()
+    }
+
+    /// Turn frame stepping on or off. While it's on, [`EventHandler::update`] is only called once
+    /// for each call to [`Engine::step_frame`], while drawing and events carry on as usual. See
+    /// the [`debug`](crate::debug) module.
+    pub fn set_frame_step(&self, enabled: bool) {
+        self.inner.frame_step.lock().unwrap().set_enabled(enabled);
+    }
+
+    /// Run a single update on the next frame, if frame stepping is on.
+    pub fn step_frame(&self) {
+        self.inner.frame_step.lock().unwrap().step();
+    }
+
+    /// Set the window's icon.
+    ///
+    /// miniquad can only set the icon when the window is created, so for now this only updates
+    /// the icon stored in [`Engine::window`]. Use [`Conf::window_icon`] to set the icon the window
+    /// opens with.
+    pub fn set_window_icon(&self, icon: RgbaImage) {
+        self.window().set_icon(icon);
+    }
+}
+
+fn to_mq_icon(image: &RgbaImage) -> mq::conf::Icon {
+    fn resized<const N: usize>(image: &RgbaImage, size: u32) -> [u8; N] {
+        let mut out = [0; N];
+        out.copy_from_slice(
+            &imageops::resize(image, size, size, imageops::FilterType::Triangle).into_raw(),
+        );
+        out
+    }
+
+    mq::conf::Icon {
+        small: resized(image, 16),
+        medium: resized(image, 32),
+        big: resized(image, 64),
+    }
 }
 
 impl Default for EngineRef {
@@ -419,7 +636,8 @@ pub trait EventHandler: Send + Sync + 'static {
     /// Called when the mouse is moved.
     ///
     /// Arguments are the delta/change in mouse position, not the new position. Will still be
-    /// generated if the mouse is grabbed.
+    /// generated if the mouse is grabbed. Like all positions passed to an [`EventHandler`], they're
+    /// in logical pixels; see [`Engine::dpi_scale`].
     fn mouse_motion_event(&mut self, _engine: &Engine, x: f32, y: f32) {
         log::trace!("unhandled mouse_motion_event({}, {})", x, y);
     }
@@ -492,11 +710,35 @@ pub trait EventHandler: Send + Sync + 'static {
         log::trace!("unhandled gamepad_disconnected_event()");
     }
 
-    /// Called when the window size changes.
+    /// Called when the window size changes. The size is in logical pixels.
     fn resize_event(&mut self, _engine: &Engine, width: f32, height: f32) {
         log::trace!("unhandled resize_event({}, {})", width, height);
     }
 
+    /// Called when a finger touches, moves across, or leaves a touch screen. `id` identifies the
+    /// finger for as long as it stays on the screen, so multiple simultaneous touches can be told
+    /// apart (see [`GestureRecognizer`](crate::input::gesture::GestureRecognizer).)
+    ///
+    /// Touches are also emulated as left mouse button events, which are delivered after this.
+    fn touch_event(&mut self, _engine: &Engine, phase: TouchPhase, id: u64, x: f32, y: f32) {
+        log::trace!("unhandled touch_event({:?}, {}, {}, {})", phase, id, x, y);
+    }
+
+    /// Called when the window loses (`focused == false`) or regains (`focused == true`) focus.
+    /// While the window is unfocused, key and button releases aren't delivered, so anything held
+    /// down when focus was lost will look stuck; this is the place to call
+    /// [`InputState::reset_input_state`](crate::input::InputState::reset_input_state).
+    ///
+    /// miniquad reports this as the window being minimized and restored, which on some platforms
+    /// only happens when the application is backgrounded.
+    fn focus_event(&mut self, _engine: &Engine, focused: bool) {
+        log::trace!("unhandled focus_event({})", focused);
+    }
+
+    /// Called when the user closes the window or the game requests to quit, just before the
+    /// application exits. Use this to save anything which should persist between sessions.
+    fn quit_requested_event(&mut self, _engine: &Engine) {}
+
     /// Called after the [`Engine`] is created. Normally you won't need this, as [`Engine::run`]
     /// lets you construct an [`EventHandler`] directly from a strong reference to the engine.
     /// Internally however that call uses a [`LazyHandler`], which runs your constructor closure in
@@ -536,19 +778,25 @@ impl mq::EventHandlerFree for Engine<'static> {
             }
         }
 
-        handler.update(self, MINIQUAD_DT).unwrap();
+        if self.inner.frame_step.lock().unwrap().should_update() {
+            handler.update(self, MINIQUAD_DT).unwrap();
+        }
     }
 
     fn draw(&mut self) {
         self.handler().draw(self).unwrap();
+        self.inner.frame_limiter.try_lock().unwrap().wait();
     }
 
     fn resize_event(&mut self, width: f32, height: f32) {
-        self.handler().resize_event(self, width, height);
+        let scale = self.dpi_scale();
+        self.handler()
+            .resize_event(self, width / scale, height / scale);
     }
 
     fn mouse_motion_event(&mut self, x: f32, y: f32) {
-        self.handler().mouse_motion_event(self, x, y);
+        let p = physical_to_logical(Point2::new(x, y), self.dpi_scale());
+        self.handler().mouse_motion_event(self, p.x, p.y);
     }
 
     fn mouse_wheel_event(&mut self, x: f32, y: f32) {
@@ -556,13 +804,15 @@ impl mq::EventHandlerFree for Engine<'static> {
     }
 
     fn mouse_button_down_event(&mut self, button: mq::MouseButton, x: f32, y: f32) {
+        let p = physical_to_logical(Point2::new(x, y), self.dpi_scale());
         self.handler()
-            .mouse_button_down_event(self, MouseButton::from(button), x, y)
+            .mouse_button_down_event(self, MouseButton::from(button), p.x, p.y)
     }
 
     fn mouse_button_up_event(&mut self, button: mq::MouseButton, x: f32, y: f32) {
+        let p = physical_to_logical(Point2::new(x, y), self.dpi_scale());
         self.handler()
-            .mouse_button_up_event(self, MouseButton::from(button), x, y);
+            .mouse_button_up_event(self, MouseButton::from(button), p.x, p.y);
     }
 
     fn char_event(&mut self, character: char, keymods: mq::KeyMods, repeat: bool) {
@@ -580,8 +830,12 @@ impl mq::EventHandlerFree for Engine<'static> {
             .key_up_event(self, KeyCode::from(keycode), KeyMods::from(keymods));
     }
 
-    /// Default implementation emulates mouse clicks
-    fn touch_event(&mut self, phase: mq::TouchPhase, _id: u64, x: f32, y: f32) {
+    /// Touch events are passed on to the handler, and then also emulate mouse clicks.
+    fn touch_event(&mut self, phase: mq::TouchPhase, id: u64, x: f32, y: f32) {
+        let p = physical_to_logical(Point2::new(x, y), self.dpi_scale());
+        self.handler()
+            .touch_event(self, TouchPhase::from(phase), id, p.x, p.y);
+
         if phase == mq::TouchPhase::Started {
             self.mouse_button_down_event(mq::MouseButton::Left, x, y);
         }
@@ -600,12 +854,22 @@ impl mq::EventHandlerFree for Engine<'static> {
     /// hardware units instead. And those units may be different from pixels depending on the target platform
     fn raw_mouse_motion(&mut self, _dx: f32, _dy: f32) {}
 
+    fn window_minimized_event(&mut self) {
+        self.handler().focus_event(self, false);
+    }
+
+    fn window_restored_event(&mut self) {
+        self.handler().focus_event(self, true);
+    }
+
     /// This event is sent when the userclicks the window's close button
     /// or application code calls the ctx.request_quit() function. The event
     /// handler callback code can handle this event by calling
     /// ctx.cancel_quit() to cancel the quit.
     /// If the event is ignored, the application will quit as usual.
-    fn quit_requested_event(&mut self) {}
+    fn quit_requested_event(&mut self) {
+        self.handler().quit_requested_event(self);
+    }
 }
 
 impl<T: EventHandler> EventHandler for Shared<T> {
@@ -676,6 +940,18 @@ impl<T: EventHandler> EventHandler for Shared<T> {
     fn resize_event(&mut self, engine: &Engine, width: f32, height: f32) {
         self.borrow_mut().resize_event(engine, width, height)
     }
+
+    fn touch_event(&mut self, engine: &Engine, phase: TouchPhase, id: u64, x: f32, y: f32) {
+        self.borrow_mut().touch_event(engine, phase, id, x, y)
+    }
+
+    fn focus_event(&mut self, engine: &Engine, focused: bool) {
+        self.borrow_mut().focus_event(engine, focused)
+    }
+
+    fn quit_requested_event(&mut self, engine: &Engine) {
+        self.borrow_mut().quit_requested_event(engine)
+    }
 }
 
 enum LazyHandlerState {
@@ -789,4 +1065,16 @@ impl EventHandler for LazyHandler {
     fn resize_event(&mut self, engine: &Engine, width: f32, height: f32) {
         self.get_mut().resize_event(engine, width, height)
     }
+
+    fn touch_event(&mut self, engine: &Engine, phase: TouchPhase, id: u64, x: f32, y: f32) {
+        self.get_mut().touch_event(engine, phase, id, x, y)
+    }
+
+    fn focus_event(&mut self, engine: &Engine, focused: bool) {
+        self.get_mut().focus_event(engine, focused)
+    }
+
+    fn quit_requested_event(&mut self, engine: &Engine) {
+        self.get_mut().quit_requested_event(engine)
+    }
 }
@@ -0,0 +1,177 @@
+//! Event queues for publishing events from Lua, with independent per-reader cursors.
+//!
+//! [`Engine::event_channel`](crate::engine::Engine::event_channel) is a typed event bus for Rust;
+//! an [`EventQueue`] carries serializable values instead, so that scripts can publish and
+//! subscribe to events they define themselves (damage dealt, item picked up, and so on.) Every
+//! reader has its own cursor into the queue, so any number of systems can consume the same events
+//! independently of each other. A reader only sees events pushed after it was registered.
+//!
+//! The `events` plugin registers a [`LuaEventQueue`] as a resource, available from Lua as
+//! `hv.events.queue`:
+//!
+//! ```lua
+//! local queue = hv.events.queue
+//! local reader = queue:register_reader()
+//!
+//! queue:push { type = "damage", amount = 3 }
+//!
+//! for _, event in ipairs(queue:read(reader)) do
+//!     print(event.type, event.amount)
+//! end
+//! ```
+//!
+//! Values are converted to and from Lua with serde as they're pushed and read, so events can
+//! contain strings, numbers, booleans, and tables of them, but not functions or userdata. Since
+//! each read converts the event back into a fresh Lua value, readers can't see each other's
+//! modifications to an event.
+
+use serde::{de::DeserializeOwned, Serialize};
+use shrev::{EventChannel, ReaderId};
+
+use crate::{
+    engine::{Engine, LuaExt, LuaResource},
+    error::*,
+    mlua::{prelude::*, LuaSerdeExt},
+    plugins::{ModuleWrapper, Plugin},
+    shared::Shared,
+};
+
+/// A queue of events of type `T`, read through [`EventReader`]s which each keep their own place in
+/// the queue.
+#[derive(Debug)]
+pub struct EventQueue<T: Send + Sync + 'static> {
+    channel: EventChannel<T>,
+}
+
+impl<T: Send + Sync + 'static> Default for EventQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Send + Sync + 'static> EventQueue<T> {
+    /// Create an empty queue with no readers.
+    pub fn new() -> Self {
+        Self {
+            channel: EventChannel::new(),
+        }
+    }
+
+    /// Push an event onto the queue, to be seen by every reader.
+    pub fn push(&mut self, event: T) {
+        self.channel.single_write(event);
+    }
+
+    /// Register a new reader, which will see every event pushed from now on.
+    pub fn register_reader(&mut self) -> EventReader<T> {
+        EventReader(self.channel.register_reader())
+    }
+
+    /// Read every event pushed since the last time this reader was read from (or since it was
+    /// registered), in the order they were pushed.
+    pub fn read<'a>(&'a self, reader: &mut EventReader<T>) -> impl Iterator<Item = &'a T> + 'a {
+        self.channel.read(&mut reader.0)
+    }
+}
+
+/// A cursor into an [`EventQueue`], registered with [`EventQueue::register_reader`].
+#[derive(Debug)]
+pub struct EventReader<T: 'static>(ReaderId<T>);
+
+impl<T: Send + Sync + 'static> LuaUserData for EventReader<T> {}
+
+/// An event queue carrying arbitrary serializable Lua values, as registered by the `events`
+/// plugin.
+pub type LuaEventQueue = EventQueue<serde_json::Value>;
+
+impl<T> LuaUserData for EventQueue<T>
+where
+    T: Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method_mut("push", |lua, this, value: LuaValue| {
+            this.push(lua.from_value(value)?);
+            Ok(())
+        });
+
+        methods.add_method_mut("register_reader", |_, this, ()| Ok(this.register_reader()));
+
+        methods.add_method("read", |lua, this, reader: LuaAnyUserData| {
+            let mut reader = reader.borrow_mut::<EventReader<T>>()?;
+            let events = this
+                .read(&mut reader)
+                .map(|event| lua.to_value(event))
+                .collect::<LuaResult<Vec<_>>>()?;
+            lua.create_sequence_from(events)
+        });
+    }
+}
+
+impl LuaResource for LuaEventQueue {
+    const REGISTRY_KEY: &'static str = "HV_EVENT_QUEUE";
+}
+
+struct EventsModule;
+
+impl Plugin for EventsModule {
+    fn name(&self) -> &'static str {
+        "events"
+    }
+
+    fn open<'lua>(&self, lua: &'lua Lua, engine: &Engine) -> Result<LuaTable<'lua>> {
+        let queue = engine.insert(LuaEventQueue::new());
+        lua.insert_resource(queue.clone())?;
+
+        let create_queue = lua.create_function(|_, ()| Ok(Shared::new(LuaEventQueue::new())))?;
+
+        Ok(lua
+            .load(mlua::chunk! {
+                {
+                    queue = $queue,
+                    create_queue = $create_queue,
+                }
+            })
+            .eval()?)
+    }
+}
+
+inventory::submit!(ModuleWrapper::new(EventsModule));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn readers_consume_lua_events_independently() -> Result<()> {
+        let lua = Lua::new();
+        lua.globals()
+            .set("queue", Shared::new(LuaEventQueue::new()))?;
+
+        lua.load(
+            r#"
+            local physics = queue:register_reader()
+            local hud = queue:register_reader()
+
+            queue:push { type = "damage", amount = 3 }
+            queue:push { type = "pickup", item = "key" }
+
+            for _, reader in ipairs { physics, hud } do
+                local events = queue:read(reader)
+                assert(#events == 2)
+                assert(events[1].type == "damage" and events[1].amount == 3)
+                assert(events[2].type == "pickup" and events[2].item == "key")
+            end
+
+            -- Reading again only returns events pushed since the last read.
+            queue:push "tick"
+            assert(#queue:read(physics) == 1)
+            assert(#queue:read(physics) == 0)
+            local events = queue:read(hud)
+            assert(#events == 1 and events[1] == "tick")
+            "#,
+        )
+        .exec()?;
+
+        Ok(())
+    }
+}
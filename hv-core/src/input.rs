@@ -13,6 +13,14 @@
 //!   controllers (rather based on Unity3D),
 //! * Do some tweening of input axes and stuff just for fun.
 //! * Present event- or state-based API so you can do whichever you want.
+//!
+//! ## Determinism
+//!
+//! [`InputState`] is deterministic: two input states fed the same sequence of effects and updates
+//! iterate their axes and buttons in the same order and serialize to identical bytes. Its internal
+//! maps use a hasher with fixed keys rather than a randomly seeded one, so this costs nothing at
+//! runtime. The order is only guaranteed to match between copies of the same build, since the
+//! standard library's default hashing algorithm may change between Rust releases.
 
 /*
  * MIT License
@@ -43,7 +51,16 @@
 use mlua::prelude::*;
 use nalgebra::{Point2, Vector2};
 use serde::*;
-use std::{collections::HashMap, hash::Hash};
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{BuildHasher, BuildHasherDefault, Hash},
+};
+
+/// A hasher with fixed keys, so that maps using it iterate in the same order across runs when
+/// given the same sequence of insertions.
+type DeterministicState = BuildHasherDefault<DefaultHasher>;
+
+pub mod gesture;
 
 // Okay, but how does it actually work?
 // Basically we have to bind input events to buttons and axes.
@@ -66,8 +83,22 @@ use std::{collections::HashMap, hash::Hash};
 // Easy way?  Hash map of event -> axis/button bindings.
 
 /// Supported key codes.
+///
+/// Key codes are named in Lua by their variant names, e.g. `"Space"` or `"Key0"`; these convert
+/// with [`std::str::FromStr`] (ignoring case) and `<&'static str>::from`.
 #[allow(missing_docs)]
-#[derive(Debug, Copy, Clone, PartialEq, Hash, Eq, strum::EnumString, Serialize, Deserialize)]
+#[derive(
+    Debug,
+    Copy,
+    Clone,
+    PartialEq,
+    Hash,
+    Eq,
+    strum::EnumString,
+    strum::IntoStaticStr,
+    Serialize,
+    Deserialize,
+)]
 #[strum(ascii_case_insensitive)]
 #[repr(u32)]
 pub enum KeyCode {
@@ -350,7 +381,7 @@ impl From<miniquad::KeyMods> for KeyMods {
 }
 
 /// Supported mouse buttons.
-#[derive(Debug, Hash, Eq, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[derive(Debug, Hash, Eq, PartialEq, Copy, Clone, strum::IntoStaticStr, Serialize, Deserialize)]
 #[allow(missing_docs)]
 pub enum MouseButton {
     Left,
@@ -373,8 +404,44 @@ impl From<miniquad::MouseButton> for MouseButton {
     }
 }
 
+/// The phase of a touch event.
+#[derive(Debug, Hash, Eq, PartialEq, Copy, Clone, strum::IntoStaticStr, Serialize, Deserialize)]
+pub enum TouchPhase {
+    /// A finger touched the screen.
+    Started,
+    /// A finger moved while touching the screen.
+    Moved,
+    /// A finger was lifted from the screen.
+    Ended,
+    /// The system cancelled tracking the touch, for example because the window lost focus.
+    Cancelled,
+}
+
+impl From<miniquad::TouchPhase> for TouchPhase {
+    fn from(mq: miniquad::TouchPhase) -> Self {
+        match mq {
+            miniquad::TouchPhase::Started => TouchPhase::Started,
+            miniquad::TouchPhase::Moved => TouchPhase::Moved,
+            miniquad::TouchPhase::Ended => TouchPhase::Ended,
+            miniquad::TouchPhase::Cancelled => TouchPhase::Cancelled,
+        }
+    }
+}
+
 /// Supported gamepad buttons.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    strum::IntoStaticStr,
+    Serialize,
+    Deserialize,
+)]
 #[allow(missing_docs)]
 pub enum GamepadButton {
     South,
@@ -428,7 +495,19 @@ impl From<gilrs::Button> for GamepadButton {
 }
 
 /// Supported gamepad axes. The DPads of a gamepad can also be read as axes with this input module.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    strum::IntoStaticStr,
+    Serialize,
+    Deserialize,
+)]
 #[allow(missing_docs)]
 pub enum GamepadAxis {
     LeftStickX,
@@ -459,7 +538,7 @@ impl From<gilrs::Axis> for GamepadAxis {
     }
 }
 
-#[derive(Debug, Hash, Eq, PartialEq, Copy, Clone)]
+#[derive(Debug, Hash, Eq, PartialEq, Copy, Clone, Serialize, Deserialize)]
 enum InputType {
     Key(KeyCode),
     GamepadButton(GamepadButton),
@@ -482,6 +561,10 @@ where
     Button(Buttons, Option<Point2<f32>>),
     /// An event indicating the mouse was moved, and where it was moved to.
     Cursor(Point2<f32>),
+    /// An event setting an axis's position from an analog device such as a gamepad stick. Unlike
+    /// [`InputEffect::Axis`], the exact position is also recorded as the axis's hardware position;
+    /// see [`InputState::get_axis_hardware`].
+    AnalogAxis(Axes, f32),
 }
 
 impl<Axes, Buttons> InputEffect<Axes, Buttons>
@@ -501,12 +584,13 @@ where
     pub fn with_axis_position(self, position: f32) -> Self {
         match self {
             Self::Axis(axis, factor) => Self::Axis(axis, position * factor),
+            Self::AnalogAxis(axis, factor) => Self::AnalogAxis(axis, position * factor),
             _ => self,
         }
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 struct CursorState {
     // Where the cursor currently is.
     position: Point2<f32>,
@@ -526,13 +610,15 @@ impl Default for CursorState {
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 struct AxisState {
     // Where the axis currently is, in [-1, 1]
     position: f32,
     // Where the axis is moving towards.  Possible values are -1, 0, +1 (or a continuous range for
     // analog devices I guess)
     direction: f32,
+    // The last position reported by an analog device, unaffected by keyboard input.
+    hardware: f32,
     // Speed in units per second that the axis moves towards the target value.
     acceleration: f32,
     // Speed in units per second that the axis will fall back toward 0 if the input stops.
@@ -544,21 +630,118 @@ impl Default for AxisState {
         AxisState {
             position: 0.0,
             direction: 0.0,
+            hardware: 0.0,
             acceleration: 16.0,
             gravity: 12.0,
         }
     }
 }
 
-#[derive(Debug, Copy, Clone, Default)]
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize)]
 struct ButtonState {
     pressed: bool,
     pressed_last_frame: bool,
     event_location: Option<Point2<f32>>,
 }
 
+/// A pointer event synthesized by a virtual cursor; see [`InputState::enable_virtual_cursor`].
+/// These are meant to be forwarded to whatever else consumes real mouse events, such as an egui
+/// integration, so that the virtual cursor is indistinguishable from a mouse.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum VirtualCursorEvent {
+    /// The virtual cursor moved to this position.
+    Moved(Point2<f32>),
+    /// The virtual cursor's button was pressed or released at this position.
+    Button {
+        /// Whether the button was pressed (`true`) or released (`false`).
+        pressed: bool,
+        /// Where the virtual cursor was when the button changed state.
+        position: Point2<f32>,
+    },
+}
+
+/// A cursor driven by a pair of logical axes rather than a mouse, for navigating mouse-driven UI
+/// with a gamepad stick. Created by [`InputState::enable_virtual_cursor`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "Axes: Serialize, Buttons: Serialize",
+    deserialize = "Axes: Deserialize<'de>, Buttons: Deserialize<'de>"
+))]
+pub struct VirtualCursor<Axes, Buttons> {
+    x_axis: Axes,
+    y_axis: Axes,
+    button: Option<Buttons>,
+    // Speed in pixels per second at full deflection, when the axes first start moving.
+    speed: f32,
+    // Increase in speed per second, for as long as the axes are held.
+    acceleration: f32,
+    // The speed acceleration can't push the cursor past, if any.
+    max_speed: Option<f32>,
+    // The size of the screen, which the cursor is kept inside of, if any.
+    bounds: Option<Vector2<f32>>,
+    // How long the axes have been held away from zero.
+    held: f32,
+}
+
+impl<Axes, Buttons> VirtualCursor<Axes, Buttons> {
+    /// Synthesize mouse button events from a logical button, usually one bound to a gamepad
+    /// button. Pressing or releasing it records the cursor position as its event location, like
+    /// a mouse click would.
+    pub fn set_button(&mut self, button: Option<Buttons>) -> &mut Self {
+        self.button = button;
+        self
+    }
+
+    /// Set the speed of the cursor at full deflection, in pixels per second.
+    pub fn set_speed(&mut self, speed: f32) -> &mut Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Make the cursor speed up the longer the axes are held, by `acceleration` pixels per second
+    /// every second, up to `max_speed` if given. Letting go of the axes resets the speed. The
+    /// default is no acceleration.
+    pub fn set_acceleration(&mut self, acceleration: f32, max_speed: Option<f32>) -> &mut Self {
+        self.acceleration = acceleration;
+        self.max_speed = max_speed;
+        self
+    }
+
+    /// Keep the cursor inside a screen of the given size, with the origin at the top left. By
+    /// default the cursor is unbounded; this should generally be kept up to date with the window
+    /// size.
+    pub fn set_bounds(&mut self, bounds: Option<Vector2<f32>>) -> &mut Self {
+        self.bounds = bounds;
+        self
+    }
+
+    fn current_speed(&self) -> f32 {
+        let speed = self.speed + self.acceleration * self.held;
+        self.max_speed.map_or(speed, |max| speed.min(max))
+    }
+
+    fn clamp(&self, position: Point2<f32>) -> Point2<f32> {
+        match self.bounds {
+            Some(size) => Point2::new(
+                position.x.max(0.).min(size.x),
+                position.y.max(0.).min(size.y),
+            ),
+            None => position,
+        }
+    }
+}
+
 /// A struct that contains a mapping from physical input events (currently just `KeyCode`s) to
 /// whatever your logical Axis/Button types are.
+///
+/// Bindings can be serialized (for example, to store a binding alongside a recorded replay); they
+/// are serialized as a sequence of pairs, so formats without support for non-string map keys are
+/// fine.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "Axes: Serialize, Buttons: Serialize",
+    deserialize = "Axes: Deserialize<'de>, Buttons: Deserialize<'de>"
+))]
 pub struct InputBinding<Axes, Buttons>
 where
     Axes: Hash + Eq + Clone,
@@ -566,9 +749,37 @@ where
 {
     // Once EnumSet is stable it should be used for these instead of BTreeMap. ♥? Binding of keys to
     // input values.
+    #[serde(with = "map_as_pairs")]
     bindings: HashMap<InputType, InputEffect<Axes, Buttons>>,
 }
 
+/// (De)serialize a map as a sequence of key/value pairs, so that it can be used with formats which
+/// only support string keys in maps.
+mod map_as_pairs {
+    use super::*;
+
+    pub fn serialize<K, V, H, S>(map: &HashMap<K, V, H>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        K: Serialize,
+        V: Serialize,
+        S: Serializer,
+    {
+        serializer.collect_seq(map.iter())
+    }
+
+    pub fn deserialize<'de, K, V, H, D>(deserializer: D) -> Result<HashMap<K, V, H>, D::Error>
+    where
+        K: Deserialize<'de> + Eq + Hash,
+        V: Deserialize<'de>,
+        H: BuildHasher + Default,
+        D: Deserializer<'de>,
+    {
+        Ok(Vec::<(K, V)>::deserialize(deserializer)?
+            .into_iter()
+            .collect())
+    }
+}
+
 impl<Axes, Buttons> Default for InputBinding<Axes, Buttons>
 where
     Axes: Hash + Eq + Clone,
@@ -623,7 +834,7 @@ where
     pub fn bind_gamepad_axis_to_axis(mut self, gamepad_axis: GamepadAxis, axis: Axes) -> Self {
         self.bindings.insert(
             InputType::GamepadAxis(gamepad_axis),
-            InputEffect::Axis(axis, 1.0),
+            InputEffect::AnalogAxis(axis, 1.0),
         );
         self
     }
@@ -679,18 +890,32 @@ where
 }
 
 /// Represents an input state for a given set of logical axes and buttons.
-#[derive(Debug)]
+///
+/// Input states can be serialized, for example to save and restore them for rollback netplay. See
+/// the [module-level documentation](crate::input#determinism) for guarantees on determinism.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "Axes: Serialize, Buttons: Serialize",
+    deserialize = "Axes: Deserialize<'de>, Buttons: Deserialize<'de>"
+))]
 pub struct InputState<Axes, Buttons>
 where
     Axes: Hash + Eq + Clone,
     Buttons: Hash + Eq + Clone,
 {
     // Input state for axes
-    axes: HashMap<Axes, AxisState>,
+    #[serde(with = "map_as_pairs")]
+    axes: HashMap<Axes, AxisState, DeterministicState>,
     // Input states for buttons
-    buttons: HashMap<Buttons, ButtonState>,
+    #[serde(with = "map_as_pairs")]
+    buttons: HashMap<Buttons, ButtonState, DeterministicState>,
     // Input state for the mouse cursor
     mouse: CursorState,
+    // The virtual cursor driving the mouse cursor, if enabled
+    virtual_cursor: Option<VirtualCursor<Axes, Buttons>>,
+    // Pointer events synthesized by the virtual cursor and not yet taken
+    #[serde(skip)]
+    virtual_cursor_events: Vec<VirtualCursorEvent>,
 }
 
 impl<Axes, Buttons> Default for InputState<Axes, Buttons>
@@ -711,15 +936,88 @@ where
     /// Create a fresh [`InputState`].
     pub fn new() -> Self {
         InputState {
-            axes: HashMap::new(),
-            buttons: HashMap::new(),
+            axes: HashMap::default(),
+            buttons: HashMap::default(),
             mouse: CursorState::default(),
+            virtual_cursor: None,
+            virtual_cursor_events: Vec::new(),
+        }
+    }
+
+    /// Drive the mouse cursor with a pair of logical axes, moving it at `speed` pixels per second
+    /// when the axes are fully deflected. Every [`InputState::update`] integrates the axes into
+    /// the cursor position and feeds it to [`InputState::update_mouse_position`], so anything
+    /// reading the mouse position sees the virtual cursor. The raw axis positions are used rather
+    /// than the smoothed ones, so that a gamepad stick controls the cursor directly.
+    ///
+    /// Returns the virtual cursor so that a button, acceleration, and screen bounds can be set.
+    /// The pointer events it synthesizes can be forwarded elsewhere using
+    /// [`InputState::take_virtual_cursor_events`]. Calling this again replaces the old virtual
+    /// cursor.
+    pub fn enable_virtual_cursor(
+        &mut self,
+        x_axis: Axes,
+        y_axis: Axes,
+        speed: f32,
+    ) -> &mut VirtualCursor<Axes, Buttons> {
+        self.virtual_cursor.insert(VirtualCursor {
+            x_axis,
+            y_axis,
+            button: None,
+            speed,
+            acceleration: 0.,
+            max_speed: None,
+            bounds: None,
+            held: 0.,
+        })
+    }
+
+    /// Stop driving the mouse cursor with a virtual cursor. The mouse cursor stays where the
+    /// virtual cursor left it.
+    pub fn disable_virtual_cursor(&mut self) {
+        self.virtual_cursor = None;
+    }
+
+    /// The virtual cursor, if one is enabled.
+    pub fn virtual_cursor_mut(&mut self) -> Option<&mut VirtualCursor<Axes, Buttons>> {
+        self.virtual_cursor.as_mut()
+    }
+
+    /// Take every pointer event synthesized by the virtual cursor since the last call, in the
+    /// order they happened.
+    pub fn take_virtual_cursor_events(&mut self) -> Vec<VirtualCursorEvent> {
+        std::mem::take(&mut self.virtual_cursor_events)
+    }
+
+    fn update_virtual_cursor(&mut self, dt: f32) {
+        let cursor = match self.virtual_cursor.as_mut() {
+            Some(cursor) => cursor,
+            None => return,
+        };
+
+        let axes = &self.axes;
+        let raw = |axis: &Axes| axes.get(axis).map_or(0., |status| status.direction);
+        let direction = Vector2::new(raw(&cursor.x_axis), raw(&cursor.y_axis));
+        if direction == Vector2::zeros() {
+            cursor.held = 0.;
+            return;
+        }
+
+        let position = self.mouse.position + direction * cursor.current_speed() * dt;
+        let position = cursor.clamp(position);
+        cursor.held += dt;
+        if position != self.mouse.position {
+            self.virtual_cursor_events
+                .push(VirtualCursorEvent::Moved(position));
+            self.update_mouse_position(position);
         }
     }
 
     /// Updates the logical input state based on the actual physical input state.  Should be called
     /// in your update() handler. So, it will do things like move the axes and so on.
     pub fn update(&mut self, dt: f32) {
+        self.update_virtual_cursor(dt);
+
         for (_axis, axis_status) in self.axes.iter_mut() {
             if axis_status.direction != 0.0 {
                 // Accelerate the axis towards the input'ed direction.
@@ -799,8 +1097,25 @@ where
                     axis_status.direction = 0.0;
                 }
             }
-            InputEffect::Button(button, point) => {
+            InputEffect::AnalogAxis(axis, position) => {
+                let axis_status = self.axes.entry(axis).or_insert_with(AxisState::default);
+                axis_status.hardware = position;
+                axis_status.direction = if started { position } else { 0.0 };
+            }
+            InputEffect::Button(button, mut point) => {
+                let is_cursor_button = self
+                    .virtual_cursor
+                    .as_ref()
+                    .map_or(false, |cursor| cursor.button.as_ref() == Some(&button));
                 let button_status = self.buttons.entry(button).or_default();
+                if is_cursor_button && button_status.pressed != started {
+                    let position = self.mouse.position;
+                    point = point.or(Some(position));
+                    self.virtual_cursor_events.push(VirtualCursorEvent::Button {
+                        pressed: started,
+                        position,
+                    });
+                }
                 button_status.pressed = started;
                 button_status.event_location = point;
             }
@@ -825,6 +1140,15 @@ where
         axis_status.direction
     }
 
+    /// Get the last position reported for a logical axis by an analog device such as a gamepad
+    /// stick, through an [`InputEffect::AnalogAxis`]. Keyboard input never affects this, so it
+    /// stays at zero for axes which are only bound to keys.
+    pub fn get_axis_hardware(&self, axis: Axes) -> f32 {
+        let d = AxisState::default();
+        let axis_status = self.axes.get(&axis).unwrap_or(&d);
+        axis_status.hardware
+    }
+
     fn get_button(&self, button: Buttons) -> ButtonState {
         let d = ButtonState::default();
         let button_status = self.buttons.get(&button).unwrap_or(&d);
@@ -877,20 +1201,34 @@ where
 
     /// Reset the input state, all axes at zero, all buttons unpresseed, all positions and deltas
     /// zeroed out.
+    ///
+    /// Buttons are reset as though they had been up for more than a frame, so a reset never
+    /// produces any edges: neither [`InputState::get_button_pressed`] nor
+    /// [`InputState::get_button_released`] will report a button which was held down when the state
+    /// was reset. Call this when the window loses focus (see
+    /// [`EventHandler::focus_event`](crate::engine::EventHandler::focus_event)), since any
+    /// releases which happen while unfocused are never seen.
     pub fn reset_input_state(&mut self) {
         for (_axis, axis_status) in self.axes.iter_mut() {
             axis_status.position = 0.0;
             axis_status.direction = 0.0;
+            axis_status.hardware = 0.0;
         }
 
         for (_button, button_status) in self.buttons.iter_mut() {
             button_status.pressed = false;
             button_status.pressed_last_frame = false;
+            button_status.event_location = None;
         }
 
         self.mouse.position = Point2::origin();
         self.mouse.last_position = Point2::origin();
         self.mouse.delta = Vector2::zeros();
+
+        if let Some(cursor) = &mut self.virtual_cursor {
+            cursor.held = 0.;
+        }
+        self.virtual_cursor_events.clear();
     }
 }
 
@@ -976,6 +1314,10 @@ where
 
         methods.add_method("get_axis_raw", |_, this, axis| Ok(this.get_axis_raw(axis)));
 
+        methods.add_method("get_axis_hardware", |_, this, axis| {
+            Ok(this.get_axis_hardware(axis))
+        });
+
         methods.add_method("mouse_position", |_, this, ()| {
             let pt = this.mouse_position();
             Ok((pt.x, pt.y))
@@ -985,6 +1327,11 @@ where
             let v = this.mouse_delta();
             Ok((v.x, v.y))
         });
+
+        methods.add_method_mut("reset", |_, this, ()| {
+            this.reset_input_state();
+            Ok(())
+        });
     }
 }
 
@@ -1140,4 +1487,179 @@ mod tests {
         assert!(!im.get_button_pressed(Buttons::A));
         assert!(!im.get_button_released(Buttons::A));
     }
+
+    #[test]
+    fn identical_effects_serialize_identically() {
+        fn run() -> Vec<u8> {
+            let mut im: InputState<u32, u32> = InputState::new();
+            for frame in 0..16 {
+                for i in 0..32 {
+                    let started = (frame + i) % 3 != 0;
+                    im.update_effect(
+                        InputEffect::Axis(i, if i % 2 == 0 { 1. } else { -1. }),
+                        started,
+                    );
+                    im.update_effect(InputEffect::Button(i * 7, None), started);
+                }
+                im.update_mouse_position(Point2::new(frame as f32, 2. * frame as f32));
+                im.update(1. / 60.);
+            }
+            bincode::serialize(&im).unwrap()
+        }
+
+        assert_eq!(run(), run());
+    }
+
+    #[test]
+    fn gamepad_axis_hardware_position() {
+        let binding = InputBinding::<Axes, Buttons>::new()
+            .bind_gamepad_axis_to_axis(GamepadAxis::LeftStickX, Axes::Horz)
+            .bind_key_to_axis(KeyCode::Up, Axes::Vert, 1.);
+        let mut im: InputState<Axes, Buttons> = InputState::new();
+
+        let effect = binding
+            .resolve_gamepad_axis(GamepadAxis::LeftStickX, 0.3)
+            .unwrap();
+        im.update_effect(effect, true);
+        im.update(0.01);
+        assert_eq!(im.get_axis_hardware(Axes::Horz), 0.3);
+        assert_eq!(im.get_axis_raw(Axes::Horz), 0.3);
+        assert!(im.get_axis(Axes::Horz) < 0.3);
+
+        let effect = binding
+            .resolve_gamepad_axis(GamepadAxis::LeftStickX, 0.)
+            .unwrap();
+        im.update_effect(effect, false);
+        assert_eq!(im.get_axis_hardware(Axes::Horz), 0.);
+        assert_eq!(im.get_axis_raw(Axes::Horz), 0.);
+
+        // Keyboard-driven axes don't touch the hardware position.
+        im.update_effect(binding.resolve_keycode(KeyCode::Up).unwrap(), true);
+        assert_eq!(im.get_axis_raw(Axes::Vert), 1.);
+        assert_eq!(im.get_axis_hardware(Axes::Vert), 0.);
+    }
+
+    #[test]
+    fn virtual_cursor_moves_at_speed_and_clicks() {
+        let binding = InputBinding::<Axes, Buttons>::new()
+            .bind_gamepad_axis_to_axis(GamepadAxis::LeftStickX, Axes::Horz)
+            .bind_gamepad_axis_to_axis(GamepadAxis::LeftStickY, Axes::Vert)
+            .bind_gamepad_button_to_button(GamepadButton::South, Buttons::A);
+        let mut im: InputState<Axes, Buttons> = InputState::new();
+        im.enable_virtual_cursor(Axes::Horz, Axes::Vert, 200.)
+            .set_button(Some(Buttons::A))
+            .set_bounds(Some(Vector2::new(100., 100.)));
+        im.update_mouse_position(Point2::new(10., 50.));
+
+        let effect = binding
+            .resolve_gamepad_axis(GamepadAxis::LeftStickX, 1.)
+            .unwrap();
+        im.update_effect(effect, true);
+        im.update(0.1);
+        assert!((im.mouse_position() - Point2::new(30., 50.)).norm() < 1e-5);
+        assert!((im.mouse_delta() - Vector2::new(20., 0.)).norm() < 1e-5);
+
+        // Held long enough, the cursor stops at the edge of the screen.
+        for _ in 0..10 {
+            im.update(0.1);
+        }
+        assert_eq!(im.mouse_position(), Point2::new(100., 50.));
+
+        let effect = binding
+            .resolve_gamepad_button(GamepadButton::South)
+            .unwrap();
+        im.update_effect(effect, true);
+        im.update_effect(effect, false);
+        assert_eq!(
+            im.get_button_event_location(Buttons::A),
+            Some(Point2::new(100., 50.))
+        );
+
+        let events = im.take_virtual_cursor_events();
+        assert_eq!(
+            events.first(),
+            Some(&VirtualCursorEvent::Moved(Point2::new(30., 50.)))
+        );
+        assert_eq!(
+            events[events.len() - 2..],
+            [
+                VirtualCursorEvent::Button {
+                    pressed: true,
+                    position: Point2::new(100., 50.)
+                },
+                VirtualCursorEvent::Button {
+                    pressed: false,
+                    position: Point2::new(100., 50.)
+                },
+            ]
+        );
+        assert!(im.take_virtual_cursor_events().is_empty());
+    }
+
+    #[test]
+    fn virtual_cursor_accelerates_while_held() {
+        let mut im: InputState<Axes, Buttons> = InputState::new();
+        im.enable_virtual_cursor(Axes::Horz, Axes::Vert, 100.)
+            .set_acceleration(100., Some(150.));
+
+        im.update_effect(InputEffect::AnalogAxis(Axes::Horz, 1.), true);
+        let mut deltas = Vec::new();
+        for _ in 0..3 {
+            im.update(1.);
+            deltas.push(im.mouse_delta().x);
+        }
+        assert_eq!(deltas, [100., 150., 150.]);
+
+        // Letting go resets the speed.
+        im.update_effect(InputEffect::AnalogAxis(Axes::Horz, 0.), false);
+        im.update(1.);
+        im.update_effect(InputEffect::AnalogAxis(Axes::Horz, -1.), true);
+        im.update(1.);
+        assert_eq!(im.mouse_delta().x, -100.);
+    }
+
+    #[allow(clippy::float_cmp)]
+    #[test]
+    fn reset_releases_everything_without_edges() {
+        fn assert_all_released(im: &InputState<Axes, Buttons>) {
+            for &button in &[Buttons::A, Buttons::B, Buttons::Select, Buttons::Start] {
+                assert!(im.get_button_up(button));
+                assert!(!im.get_button_pressed(button));
+                assert!(!im.get_button_released(button), "{:?} released", button);
+                assert_eq!(im.get_button_event_location(button), None);
+            }
+
+            for &axis in &[Axes::Horz, Axes::Vert] {
+                assert_eq!(im.get_axis(axis), 0.);
+                assert_eq!(im.get_axis_raw(axis), 0.);
+                assert_eq!(im.get_axis_hardware(axis), 0.);
+            }
+
+            assert_eq!(im.mouse_position(), Point2::origin());
+            assert_eq!(im.mouse_delta(), Vector2::zeros());
+        }
+
+        let mut im: InputState<Axes, Buttons> = InputState::new();
+        let click = Some(Point2::new(4., 2.));
+        im.update_button_down(Buttons::A);
+        im.update_effect(InputEffect::Button(Buttons::B, click), true);
+        im.update_axis_start(Axes::Horz, 1.);
+        im.update_effect(InputEffect::AnalogAxis(Axes::Vert, -0.5), true);
+        im.update_mouse_position(Point2::new(10., 20.));
+        for _ in 0..10 {
+            im.update(0.16);
+        }
+        assert!(im.get_button_down(Buttons::A));
+        assert!(im.get_axis(Axes::Horz) > 0.);
+
+        im.reset_input_state();
+        assert_all_released(&im);
+        // Buttons which were held don't show up as released on the next frame, either.
+        im.update(0.16);
+        assert_all_released(&im);
+
+        // Pressing a button again after a reset is an ordinary press.
+        im.update_button_down(Buttons::A);
+        assert!(im.get_button_pressed(Buttons::A));
+    }
 }
@@ -0,0 +1,219 @@
+//! Recognizing multi-touch gestures (pinches, two-finger pans, and taps) from raw touch events.
+//!
+//! Feed a [`GestureRecognizer`] every touch event from
+//! [`EventHandler::touch_event`](crate::engine::EventHandler::touch_event), then drain the
+//! recognized [`Gesture`]s with [`GestureRecognizer::drain`] and apply them to a camera or editor
+//! view. At most two fingers are tracked at once; any further touches are ignored until one of the
+//! tracked fingers is lifted.
+
+use nalgebra::{Point2, Vector2};
+
+use crate::input::TouchPhase;
+
+/// A gesture recognized by a [`GestureRecognizer`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Gesture {
+    /// Two fingers moved towards or away from each other. `scale_delta` is the relative change in
+    /// the distance between them: positive when pinching out, negative when pinching in, so that
+    /// multiplying a zoom level by `1. + scale_delta` follows the fingers. `center` is the point
+    /// halfway between the fingers.
+    Pinch {
+        scale_delta: f32,
+        center: Point2<f32>,
+    },
+    /// The point halfway between two fingers moved by `delta`.
+    Pan { delta: Vector2<f32> },
+    /// A single finger touched and was lifted without moving further than the recognizer's tap
+    /// slop, and without a second finger joining it.
+    Tap { position: Point2<f32> },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Touch {
+    id: u64,
+    position: Point2<f32>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TapCandidate {
+    id: u64,
+    start: Point2<f32>,
+}
+
+/// Turns a stream of raw touch events into [`Gesture`]s.
+#[derive(Debug, Clone)]
+pub struct GestureRecognizer {
+    /// How far, in pixels, a finger may move before a touch stops counting as a tap.
+    pub tap_slop: f32,
+    touches: Vec<Touch>,
+    tap: Option<TapCandidate>,
+    gestures: Vec<Gesture>,
+}
+
+impl Default for GestureRecognizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GestureRecognizer {
+    /// Create a recognizer with a tap slop of 10 pixels.
+    pub fn new() -> Self {
+        Self {
+            tap_slop: 10.,
+            touches: Vec::with_capacity(2),
+            tap: None,
+            gestures: Vec::new(),
+        }
+    }
+
+    /// The number of fingers currently being tracked; never more than two.
+    pub fn active_touches(&self) -> usize {
+        self.touches.len()
+    }
+
+    /// Feed a raw touch event to the recognizer.
+    pub fn touch_event(&mut self, phase: TouchPhase, id: u64, x: f32, y: f32) {
+        let position = Point2::new(x, y);
+        match phase {
+            TouchPhase::Started => self.touch_started(id, position),
+            TouchPhase::Moved => self.touch_moved(id, position),
+            TouchPhase::Ended => self.touch_ended(id, position, true),
+            TouchPhase::Cancelled => self.touch_ended(id, position, false),
+        }
+    }
+
+    /// Take all the gestures recognized since the last call.
+    pub fn drain(&mut self) -> impl Iterator<Item = Gesture> + '_ {
+        self.gestures.drain(..)
+    }
+
+    fn touch_started(&mut self, id: u64, position: Point2<f32>) {
+        if self.touches.len() >= 2 || self.touches.iter().any(|touch| touch.id == id) {
+            return;
+        }
+
+        self.touches.push(Touch { id, position });
+        self.tap = match self.touches.len() {
+            1 => Some(TapCandidate {
+                id,
+                start: position,
+            }),
+            _ => None,
+        };
+    }
+
+    fn touch_moved(&mut self, id: u64, position: Point2<f32>) {
+        let index = match self.touches.iter().position(|touch| touch.id == id) {
+            Some(index) => index,
+            None => return,
+        };
+
+        if let Some(tap) = self.tap {
+            if tap.id == id && (position - tap.start).norm() > self.tap_slop {
+                self.tap = None;
+            }
+        }
+
+        if self.touches.len() == 2 {
+            let (a, b) = (self.touches[0].position, self.touches[1].position);
+            let (old_center, old_distance) = (nalgebra::center(&a, &b), (a - b).norm());
+            self.touches[index].position = position;
+            let (a, b) = (self.touches[0].position, self.touches[1].position);
+            let (center, distance) = (nalgebra::center(&a, &b), (a - b).norm());
+
+            if old_distance > f32::EPSILON && distance != old_distance {
+                self.gestures.push(Gesture::Pinch {
+                    scale_delta: distance / old_distance - 1.,
+                    center,
+                });
+            }
+
+            if center != old_center {
+                self.gestures.push(Gesture::Pan {
+                    delta: center - old_center,
+                });
+            }
+        } else {
+            self.touches[index].position = position;
+        }
+    }
+
+    fn touch_ended(&mut self, id: u64, position: Point2<f32>, completed: bool) {
+        let index = match self.touches.iter().position(|touch| touch.id == id) {
+            Some(index) => index,
+            None => return,
+        };
+
+        self.touches.remove(index);
+        if let Some(tap) = self.tap.take() {
+            if completed && tap.id == id && (position - tap.start).norm() <= self.tap_slop {
+                self.gestures.push(Gesture::Tap { position });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diverging_fingers_pinch_out() {
+        let mut recognizer = GestureRecognizer::new();
+        recognizer.touch_event(TouchPhase::Started, 1, 100., 100.);
+        recognizer.touch_event(TouchPhase::Started, 2, 200., 100.);
+        recognizer.touch_event(TouchPhase::Moved, 2, 300., 100.);
+
+        let gestures = recognizer.drain().collect::<Vec<_>>();
+        assert_eq!(
+            gestures,
+            vec![
+                Gesture::Pinch {
+                    scale_delta: 1.,
+                    center: Point2::new(200., 100.),
+                },
+                Gesture::Pan {
+                    delta: Vector2::new(50., 0.),
+                },
+            ]
+        );
+
+        // Neither finger lifting afterwards counts as a tap.
+        recognizer.touch_event(TouchPhase::Ended, 1, 100., 100.);
+        recognizer.touch_event(TouchPhase::Ended, 2, 300., 100.);
+        assert_eq!(recognizer.drain().count(), 0);
+    }
+
+    #[test]
+    fn third_touch_is_ignored() {
+        let mut recognizer = GestureRecognizer::new();
+        recognizer.touch_event(TouchPhase::Started, 1, 0., 0.);
+        recognizer.touch_event(TouchPhase::Started, 2, 100., 0.);
+        recognizer.touch_event(TouchPhase::Started, 3, 50., 50.);
+        recognizer.touch_event(TouchPhase::Moved, 3, 500., 500.);
+        recognizer.touch_event(TouchPhase::Ended, 3, 500., 500.);
+        assert_eq!(recognizer.active_touches(), 2);
+        assert_eq!(recognizer.drain().count(), 0);
+
+        recognizer.touch_event(TouchPhase::Moved, 1, -100., 0.);
+        assert!(matches!(
+            recognizer.drain().next(),
+            Some(Gesture::Pinch { scale_delta, .. }) if (scale_delta - 1.).abs() < 1e-6
+        ));
+    }
+
+    #[test]
+    fn single_touch_taps() {
+        let mut recognizer = GestureRecognizer::new();
+        recognizer.touch_event(TouchPhase::Started, 7, 40., 40.);
+        recognizer.touch_event(TouchPhase::Moved, 7, 43., 44.);
+        recognizer.touch_event(TouchPhase::Ended, 7, 43., 44.);
+        assert_eq!(
+            recognizer.drain().collect::<Vec<_>>(),
+            vec![Gesture::Tap {
+                position: Point2::new(43., 44.)
+            }]
+        );
+    }
+}
@@ -1,4 +1,15 @@
 //! The core functionality of the Heavy game framework.
+//!
+//! ## Determinism
+//!
+//! Some parts of the engine are safe to use for lockstep or rollback simulation, in the sense that
+//! feeding them the same inputs produces identical state:
+//!
+//! - [`input::InputState`], including its serialized form.
+//! - Iteration over [`spaces::Space`]s, through queries or otherwise.
+//!
+//! Others are not: the [`timer`] measures wall-clock time, and iterating Lua tables with `pairs` has
+//! no guaranteed order.
 
 #![feature(coerce_unsized, unsize)]
 #![feature(ptr_metadata)]
@@ -9,15 +20,19 @@ pub extern crate inventory;
 pub extern crate miniquad as mq;
 pub extern crate mlua;
 pub extern crate nalgebra as na;
+pub extern crate shrev;
 
 mod logger;
 mod package;
 mod path_clean;
 mod vfs;
 
+pub mod assets;
 pub mod components;
 pub mod conf;
+pub mod debug;
 pub mod engine;
+pub mod events;
 pub mod filesystem;
 pub mod input;
 pub mod plugins;
@@ -25,6 +40,7 @@ pub mod shared;
 pub mod spaces;
 pub mod swappable_cache;
 pub mod timer;
+pub mod window;
 pub mod xsbox;
 
 pub mod error {
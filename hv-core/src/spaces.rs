@@ -3,8 +3,36 @@
 //!
 //! It is built on the [`hecs`] ECS, but adds space IDs to [`Object`]s so that they cannot be used
 //! with the wrong `Space`.
-
-use std::{cell::RefCell, fmt, sync::RwLock};
+//!
+//! ## Determinism
+//!
+//! Queries and [`Space::iter`] visit objects in an order which depends only on the history of
+//! spawns, despawns, and component insertions/removals in the space, so replaying the same
+//! operations yields the same iteration order. Nothing in a [`Space`] depends on hashing with a
+//! random seed.
+//!
+//! That order is not any particular order, though, and it changes as objects move between
+//! archetypes. Where the order objects are visited in is visible, such as when generating
+//! collision pairs or deciding draw order, use [`Space::query_sorted`], which sorts its results by
+//! slot or by a key of your choosing.
+//!
+//! ## Lifecycle events
+//!
+//! Every [`Space`] publishes a [`SpaceEvent`] whenever an object is spawned or despawned, or a
+//! component is inserted onto or removed from one, including changes applied from a
+//! [`CommandBuffer`] or the space's own queue. Anything keeping an index over the objects in a
+//! space can subscribe with [`Space::events_mut`] and [`EventChannel::register_reader`] rather than
+//! rescanning the space every frame.
+
+use std::{
+    any::TypeId,
+    cell::RefCell,
+    collections::hash_map::DefaultHasher,
+    fmt,
+    hash::Hasher,
+    sync::{Mutex, RwLock},
+    vec,
+};
 
 use crate::{
     engine::{LuaExt, LuaResource},
@@ -12,7 +40,10 @@ use crate::{
     mlua::prelude::*,
     plugins::{ModuleWrapper, Plugin},
     shared::Shared,
-    spaces::command::CommandBuffer,
+    spaces::{
+        command::CommandBuffer,
+        scratch::{Scratch, ScratchVec},
+    },
 };
 
 use {
@@ -23,11 +54,13 @@ use {
 pub use hecs::{Bundle, Component, DynamicBundle, Query};
 use hecs::{QueryItem, QueryOne, With, Without};
 use serde::{Deserialize, Serialize};
+pub use shrev::{EventChannel, ReaderId};
 
 mod lua;
 
 pub mod command;
 pub mod object_table;
+pub mod scratch;
 pub mod serialize;
 
 pub use self::lua::SpaceCache;
@@ -93,7 +126,9 @@ pub struct Spaces {
 }
 
 impl Spaces {
-    fn new() -> Self {
+    /// Create an empty registry. Normally the `spaces` plugin creates this for you and inserts it
+    /// as a resource; constructing one yourself is mostly useful for tests.
+    pub fn new() -> Self {
         Self {
             registry: Arena::new(),
         }
@@ -112,6 +147,17 @@ impl Spaces {
     pub fn get_space(&self, space_id: SpaceId) -> Shared<Space> {
         self.registry[space_id.0].clone()
     }
+
+    /// Iterate over every [`Space`] in the registry, in the order they were created.
+    pub fn iter(&self) -> impl Iterator<Item = Shared<Space>> + '_ {
+        self.registry.iter().map(|(_, space)| space.clone())
+    }
+}
+
+impl Default for Spaces {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl LuaUserData for Spaces {}
@@ -291,6 +337,39 @@ pub fn with_space_id<T>(space_id: SpaceId, f: impl FnOnce() -> T) -> T {
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct RawEntity(hecs::Entity);
 
+/// A change to the objects in a [`Space`], published to its [`Space::events`] channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SpaceEvent {
+    /// An object was spawned. Each component it was spawned with is reported in a following
+    /// [`SpaceEvent::ComponentAdded`].
+    Spawned(Object),
+    /// An object was despawned, taking all of its components with it.
+    Despawned(Object),
+    /// A component was inserted onto an object, possibly replacing an older one of the same type.
+    ComponentAdded(Object, TypeId),
+    /// A component was removed from an object which is still alive.
+    ComponentRemoved(Object, TypeId),
+}
+
+impl SpaceEvent {
+    fn write_spawned(events: &mut EventChannel<Self>, object: Object, ids: &[TypeId]) {
+        events.single_write(SpaceEvent::Spawned(object));
+        Self::write_added(events, object, ids);
+    }
+
+    fn write_added(events: &mut EventChannel<Self>, object: Object, ids: &[TypeId]) {
+        for &id in ids {
+            events.single_write(SpaceEvent::ComponentAdded(object, id));
+        }
+    }
+
+    fn write_removed(events: &mut EventChannel<Self>, object: Object, ids: &[TypeId]) {
+        for &id in ids {
+            events.single_write(SpaceEvent::ComponentRemoved(object, id));
+        }
+    }
+}
+
 /// An iterator over all objects in a space.
 pub struct Iter<'a> {
     id: SpaceId,
@@ -316,6 +395,8 @@ where
 {
     id: SpaceId,
     inner: hecs::SpawnBatchIter<'a, I>,
+    events: &'a mut EventChannel<SpaceEvent>,
+    ids: Vec<TypeId>,
 }
 
 impl<'a, I> Iterator for SpawnBatchIter<'a, I>
@@ -327,20 +408,34 @@ where
 
     fn next(&mut self) -> Option<Self::Item> {
         let entity = self.inner.next()?;
-        Some(Object {
+        let object = Object {
             space: self.id,
             entity,
-        })
+        };
+        SpaceEvent::write_spawned(self.events, object, &self.ids);
+        Some(object)
+    }
+}
+
+impl<'a, I> Drop for SpawnBatchIter<'a, I>
+where
+    I: Iterator,
+    I::Item: Bundle,
+{
+    fn drop(&mut self) {
+        // Anything left in the batch is still spawned when the inner iterator is dropped, so spawn
+        // it here instead in order to publish events for it.
+        self.by_ref().for_each(drop);
     }
 }
 
 /// An iterator returning entities spawned from [`Space::spawn_column_batch`].
-pub struct SpawnColumnBatchIter<'a> {
+pub struct SpawnColumnBatchIter {
     id: SpaceId,
-    inner: hecs::SpawnColumnBatchIter<'a>,
+    inner: std::vec::IntoIter<hecs::Entity>,
 }
 
-impl<'a> Iterator for SpawnColumnBatchIter<'a> {
+impl Iterator for SpawnColumnBatchIter {
     type Item = Object;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -410,6 +505,46 @@ impl<'w, Q: Query> QueryBorrow<'w, Q> {
     }
 }
 
+/// A borrowed query on a [`Space`] which visits its objects in a stable order, created with
+/// [`Space::query_sorted`].
+pub struct SortedQueryBorrow<'w, Q: Query> {
+    inner: QueryBorrow<'w, Q>,
+}
+
+impl<'w, Q: Query> SortedQueryBorrow<'w, Q> {
+    /// Iterate over the returned query items in ascending order of their objects' slots.
+    pub fn iter(&mut self) -> vec::IntoIter<(Object, QueryItem<'_, Q>)> {
+        self.iter_by_key(|(object, _)| object.slot())
+    }
+
+    /// Iterate over the returned query items sorted by `key`, such as a depth component for
+    /// drawing. The sort is stable, and items are sorted by slot first, so items with equal keys
+    /// are still visited in the same order every time.
+    pub fn iter_by_key<'q, K: Ord>(
+        &'q mut self,
+        key: impl FnMut(&(Object, QueryItem<'q, Q>)) -> K,
+    ) -> vec::IntoIter<(Object, QueryItem<'q, Q>)> {
+        let mut items = self.inner.iter().collect::<Vec<_>>();
+        items.sort_by_key(|(object, _)| object.slot());
+        items.sort_by_key(key);
+        items.into_iter()
+    }
+
+    /// Efficiently filter the query such that it only returns objects with a `T` component.
+    pub fn with<T: Component>(self) -> SortedQueryBorrow<'w, With<T, Q>> {
+        SortedQueryBorrow {
+            inner: self.inner.with(),
+        }
+    }
+
+    /// Efficiently filter the query such that it only returns objects without a `T` component.
+    pub fn without<T: Component>(self) -> SortedQueryBorrow<'w, Without<T, Q>> {
+        SortedQueryBorrow {
+            inner: self.inner.without(),
+        }
+    }
+}
+
 /// A mutably borrowed query on a [`Space`], created with [`Space::query_mut`].
 pub struct QueryMut<'q, Q: Query> {
     space: SpaceId,
@@ -451,6 +586,9 @@ impl<'q, Q: Query> IntoIterator for QueryMut<'q, Q> {
 pub struct Space {
     id: SpaceId,
     command_buffer: RwLock<CommandBuffer>,
+    reserved: Mutex<Vec<hecs::Entity>>,
+    events: EventChannel<SpaceEvent>,
+    scratch: Scratch,
 
     #[doc(hidden)]
     pub ecs: hecs::World,
@@ -461,6 +599,9 @@ impl Space {
         Self {
             id: SpaceId::invalid(),
             command_buffer: RwLock::new(CommandBuffer::new()),
+            reserved: Mutex::new(Vec::new()),
+            events: EventChannel::new(),
+            scratch: Scratch::new(),
             ecs: hecs::World::new(),
         }
     }
@@ -477,18 +618,62 @@ impl Space {
         }
     }
 
+    /// The channel this space's [`SpaceEvent`]s are published to.
+    pub fn events(&self) -> &EventChannel<SpaceEvent> {
+        &self.events
+    }
+
+    /// Mutable access to the space's event channel, for registering readers with
+    /// [`EventChannel::register_reader`].
+    pub fn events_mut(&mut self) -> &mut EventChannel<SpaceEvent> {
+        &mut self.events
+    }
+
+    /// Borrow an empty buffer from this space's [`Scratch`] pool, for collecting objects (or
+    /// anything else) during a query to act on afterwards. The buffer is returned to the pool when
+    /// dropped, so doing this every frame doesn't allocate once the pool has warmed up. See the
+    /// [`scratch`] module.
+    pub fn scratch<T: Send + 'static>(&self) -> ScratchVec<T> {
+        self.scratch.take()
+    }
+
+    /// Turn reserved objects into real ones, as hecs does implicitly before most mutating
+    /// operations, and publish their spawns.
+    fn flush_reserved(&mut self) {
+        self.ecs.flush();
+        for entity in self.reserved.get_mut().unwrap().drain(..) {
+            let object = Object {
+                space: self.id,
+                entity,
+            };
+            self.events.single_write(SpaceEvent::Spawned(object));
+        }
+    }
+
     /// Spawn an object with a given set of components.
     pub fn spawn(&mut self, components: impl DynamicBundle) -> Object {
+        self.flush_reserved();
+        let ids = components.with_ids(<[TypeId]>::to_vec);
         let e = self.ecs.spawn(components);
-        self.wrap_entity(e)
+        let object = self.wrap_entity(e);
+        SpaceEvent::write_spawned(&mut self.events, object, &ids);
+        object
     }
 
     /// Spawn an object with a given [`hecs::Entity`]. If an object exists with that entity inside
     /// the [`Space`], all components belonging to it will be dropped and overwritten with the new
     /// object and its components.
     pub fn spawn_at(&mut self, handle: hecs::Entity, components: impl DynamicBundle) -> Object {
+        self.flush_reserved();
+        let object = self.wrap_entity(handle);
+        if self.ecs.contains(handle) {
+            self.events.single_write(SpaceEvent::Despawned(object));
+        }
+
+        let ids = components.with_ids(<[TypeId]>::to_vec);
         self.ecs.spawn_at(handle, components);
-        self.wrap_entity(handle)
+        SpaceEvent::write_spawned(&mut self.events, object, &ids);
+        object
     }
 
     /// Spawn a number of entities which are statically known to have the same type. This is much
@@ -499,18 +684,39 @@ impl Space {
         I: IntoIterator,
         I::Item: Bundle + 'static,
     {
+        self.flush_reserved();
         let id = self.id;
+        let ids = I::Item::with_static_ids(<[TypeId]>::to_vec);
         let inner = self.ecs.spawn_batch(iter);
-        SpawnBatchIter { id, inner }
+        SpawnBatchIter {
+            id,
+            inner,
+            events: &mut self.events,
+            ids,
+        }
     }
 
     /// An even more efficient batch spawning method than [`Space::spawn_batch`], and capable of
     /// being called with a dynamically typed batch. This is roughly what's used under the hood when
     /// deserializing a [`Space`].
     pub fn spawn_column_batch(&mut self, batch: ColumnBatch) -> SpawnColumnBatchIter {
-        let id = self.id;
-        let inner = self.ecs.spawn_column_batch(batch);
-        SpawnColumnBatchIter { id, inner }
+        self.flush_reserved();
+        let entities = self.ecs.spawn_column_batch(batch).collect::<Vec<_>>();
+        for &entity in &entities {
+            let object = self.wrap_entity(entity);
+            let ids = self
+                .ecs
+                .entity(entity)
+                .unwrap()
+                .component_types()
+                .collect::<Vec<_>>();
+            SpaceEvent::write_spawned(&mut self.events, object, &ids);
+        }
+
+        SpawnColumnBatchIter {
+            id: self.id,
+            inner: entities.into_iter(),
+        }
     }
 
     /// Reserve a number of [`Object`] handles for later usage, such as inserting components onto
@@ -523,8 +729,10 @@ impl Space {
         count: u32,
     ) -> impl Iterator<Item = Object> + ExactSizeIterator + '_ {
         let id = self.id;
-        self.ecs
-            .reserve_entities(count)
+        let entities = self.ecs.reserve_entities(count).collect::<Vec<_>>();
+        self.reserved.lock().unwrap().extend_from_slice(&entities);
+        entities
+            .into_iter()
             .map(move |entity| Object { space: id, entity })
     }
 
@@ -533,15 +741,20 @@ impl Space {
     /// space.
     pub fn despawn(&mut self, object: Object) -> Result<(), ObjectError> {
         if self.id != object.space {
-            Err(ObjectError::WrongSpace)
-        } else {
-            self.ecs.despawn(object.entity).map_err(ObjectError::from)
+            return Err(ObjectError::WrongSpace);
         }
+
+        self.flush_reserved();
+        self.ecs.despawn(object.entity)?;
+        self.events.single_write(SpaceEvent::Despawned(object));
+        Ok(())
     }
 
     /// Reserve a single [`Object`]; see [`Space::reserve_objects`].
     pub fn reserve_object(&self) -> Object {
-        self.wrap_entity(self.ecs.reserve_entity())
+        let entity = self.ecs.reserve_entity();
+        self.reserved.lock().unwrap().push(entity);
+        self.wrap_entity(entity)
     }
 
     /// Reserve storage for `additional` more objects with the given components.
@@ -551,7 +764,19 @@ impl Space {
 
     /// Clear the [`Space`], despawning all objects in it and dropping all components attached to
     /// them. The allocated memory inside the space is preserved and can be re-used.
+    ///
+    /// This is the way to start a level over in the same space. A [`SpaceEvent::Despawned`] is
+    /// published for every object, so anything indexing the space through its events drops them
+    /// the same way it would for [`Space::despawn`]; object tables are released from the
+    /// [`ObjectTableRegistry`](object_table::ObjectTableRegistry) as their components are dropped.
+    /// Commands still waiting in the space's queue refer to objects which no longer exist, so they
+    /// are discarded rather than run.
     pub fn clear(&mut self) {
+        self.flush_reserved();
+        self.command_buffer.get_mut().unwrap().clear();
+        for object in self.iter().collect::<Vec<_>>() {
+            self.events.single_write(SpaceEvent::Despawned(object));
+        }
         self.ecs.clear()
     }
 
@@ -569,6 +794,20 @@ impl Space {
         }
     }
 
+    /// Like [`Space::query`], but visits objects in a stable, sorted order rather than the order
+    /// they happen to be stored in: by slot, or by a key given to
+    /// [`SortedQueryBorrow::iter_by_key`].
+    ///
+    /// This isn't free. Every matching object is collected into a `Vec` and sorted before the
+    /// first one is yielded, costing an allocation and an `O(n log n)` sort on every iteration
+    /// where [`Space::query`] costs neither; prefer [`Space::query`] wherever the order objects
+    /// are visited in doesn't matter.
+    pub fn query_sorted<Q: Query>(&self) -> SortedQueryBorrow<'_, Q> {
+        SortedQueryBorrow {
+            inner: self.query(),
+        }
+    }
+
     /// Similar to [`Space::query`], but skips the dynamic borrow checking step because a mutable
     /// borrow on the [`Space`] means that we're guaranteed no one else is accessing it right now.
     pub fn query_mut<Q: Query>(&mut self) -> QueryMut<'_, Q> {
@@ -666,6 +905,32 @@ impl Space {
         }
     }
 
+    /// Compute a checksum of the listed components across every object in the space, for detecting
+    /// when two copies of a simulation (netplay peers, or a replay and its recording) have
+    /// diverged. `components` are the names the component types were registered with in
+    /// [`serializable!`](crate::serializable); each must support
+    /// [`ComponentSerde::serialize_one`](serialize::ComponentSerde::serialize_one), which
+    /// components registered with [`serialize::with_serde`] do.
+    ///
+    /// Components are hashed in order of object slot rather than iteration order, so two spaces
+    /// holding the same objects in the same slots hash identically however they got there. Like
+    /// the replay harness, this uses [`DefaultHasher`], so hashes are only comparable between
+    /// builds made with the same toolchain.
+    ///
+    /// The space doesn't own any random number generator state; a simulation with its own RNG
+    /// should hash the space with [`Space::hash_state_into`] and then feed its RNG state into the
+    /// same hasher.
+    pub fn state_hash(&self, components: &[&str]) -> Result<u64> {
+        let mut hasher = DefaultHasher::new();
+        self.hash_state_into(components, &mut hasher)?;
+        Ok(hasher.finish())
+    }
+
+    /// Like [`Space::state_hash`], but feed the serialized components into an existing hasher.
+    pub fn hash_state_into<H: Hasher>(&self, components: &[&str], hasher: &mut H) -> Result<()> {
+        serialize::hash_components(self, components, hasher)
+    }
+
     /// Insert a set of components on a given [`Object`]. If any component is already on the object,
     /// the older component will be dropped and replaced with the new value.
     pub fn insert(
@@ -677,25 +942,32 @@ impl Space {
             return Err(ObjectError::WrongSpace);
         }
 
+        self.flush_reserved();
+        let ids = components.with_ids(<[TypeId]>::to_vec);
         self.ecs
             .insert(object.entity, components)
-            .or(Err(ObjectError::WrongSpace))
+            .or(Err(ObjectError::WrongSpace))?;
+        SpaceEvent::write_added(&mut self.events, object, &ids);
+        Ok(())
     }
 
     /// Insert a single component on a given [`Object`]. Slightly faster than [`Space::insert`] if
     /// you're only inserting one component.
-    pub fn insert_one(
+    pub fn insert_one<T: Component>(
         &mut self,
         object: Object,
-        component: impl Component,
+        component: T,
     ) -> Result<(), ObjectError> {
         if self.id != object.space {
             return Err(ObjectError::WrongSpace);
         }
 
+        self.flush_reserved();
         self.ecs
             .insert_one(object.entity, component)
-            .or(Err(ObjectError::WrongSpace))
+            .or(Err(ObjectError::WrongSpace))?;
+        SpaceEvent::write_added(&mut self.events, object, &[TypeId::of::<T>()]);
+        Ok(())
     }
 
     /// Remove a bundle of components from a given [`Object`]. If successful, the entire bundle is
@@ -705,7 +977,10 @@ impl Space {
             return Err(ComponentError::WrongSpace);
         }
 
-        self.ecs.remove(object.entity).map_err(ComponentError::from)
+        self.flush_reserved();
+        let removed = self.ecs.remove(object.entity)?;
+        T::with_static_ids(|ids| SpaceEvent::write_removed(&mut self.events, object, ids));
+        Ok(removed)
     }
 
     /// Remove a single component from a given [`Object`].
@@ -714,9 +989,10 @@ impl Space {
             return Err(ComponentError::WrongSpace);
         }
 
-        self.ecs
-            .remove_one(object.entity)
-            .map_err(ComponentError::from)
+        self.flush_reserved();
+        let removed = self.ecs.remove_one(object.entity)?;
+        SpaceEvent::write_removed(&mut self.events, object, &[TypeId::of::<T>()]);
+        Ok(removed)
     }
 
     /// Borrows the `T` component of the given [`Object`], bypassing all safety checks.
@@ -750,7 +1026,7 @@ impl Space {
     /// [`Space::spawn`], [`Space::insert`], [`Space::insert_one`], [`Space::remove`],
     /// [`Space::remove_one`], and [`Space::despawn`].
     pub fn flush(&mut self) {
-        self.ecs.flush();
+        self.flush_reserved();
     }
 
     /// Inspect the archetypes that objects in this [`Space`] are organized into.
@@ -809,10 +1085,12 @@ impl Space {
     /// All commands will be drained and run even if an error occurs. Errors will be gathered and
     /// returned *after* all commands are run, if any occur.
     pub fn run_queued(&mut self) -> Result<()> {
-        self.command_buffer
-            .get_mut()
-            .unwrap()
-            .run_internal(self.id, &mut self.ecs)
+        self.flush_reserved();
+        self.command_buffer.get_mut().unwrap().run_internal(
+            self.id,
+            &mut self.ecs,
+            &mut self.events,
+        )
     }
 }
 
@@ -833,6 +1111,7 @@ impl LuaUserData for Space {
         methods.add_method("id", |_, this, ()| Ok(this.id));
 
         methods.add_method("objects", spaces_objects());
+        methods.add_method("query", spaces_query());
     }
 }
 
@@ -868,3 +1147,151 @@ impl Plugin for SpacesPlugin {
 }
 
 inventory::submit!(ModuleWrapper::new(SpacesPlugin));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Position(i32);
+    #[derive(Debug, PartialEq)]
+    struct Name(&'static str);
+
+    #[test]
+    fn spawns_and_despawns_publish_events() -> Result<()> {
+        let space = Spaces::new().create_space();
+        let mut space = space.borrow_mut();
+        let mut reader = space.events_mut().register_reader();
+
+        let a = space.spawn((Position(0),));
+        space.insert_one(a, Name("a"))?;
+        space.remove_one::<Position>(a)?;
+        space.despawn(a)?;
+
+        assert_eq!(
+            space
+                .events()
+                .read(&mut reader)
+                .copied()
+                .collect::<Vec<_>>(),
+            [
+                SpaceEvent::Spawned(a),
+                SpaceEvent::ComponentAdded(a, TypeId::of::<Position>()),
+                SpaceEvent::ComponentAdded(a, TypeId::of::<Name>()),
+                SpaceEvent::ComponentRemoved(a, TypeId::of::<Position>()),
+                SpaceEvent::Despawned(a),
+            ]
+        );
+
+        // Changes applied from the queue are published too.
+        let b = space.queue_spawn((Name("b"),));
+        space.run_queued()?;
+        space.queue_despawn(b);
+        space.run_queued()?;
+
+        assert_eq!(
+            space
+                .events()
+                .read(&mut reader)
+                .copied()
+                .collect::<Vec<_>>(),
+            [
+                SpaceEvent::Spawned(b),
+                SpaceEvent::ComponentAdded(b, TypeId::of::<Name>()),
+                SpaceEvent::Despawned(b),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn clearing_despawns_everything_and_allows_respawning() -> Result<()> {
+        let lua = Lua::new();
+        let registry = object_table::insert_registry(&lua)?;
+        let space = Spaces::new().create_space();
+        let mut space = space.borrow_mut();
+
+        let a = space.spawn((Position(0),));
+        let b = space.spawn((Position(1), Name("b")));
+        let otc = registry.borrow_mut().insert(&lua, lua.create_table()?, b)?;
+        space.insert_one(b, otc)?;
+        let reserved = space.reserve_object();
+        space.queue_insert(a, (Name("a"),));
+        space.queue_despawn(b);
+
+        let mut reader = space.events_mut().register_reader();
+        space.clear();
+
+        assert_eq!(space.len(), 0);
+        assert!(space.is_empty());
+        for &object in &[a, b, reserved] {
+            assert!(!space.contains(object));
+        }
+        let mut despawned = space
+            .events()
+            .read(&mut reader)
+            .filter_map(|event| match event {
+                SpaceEvent::Despawned(object) => Some(*object),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        despawned.sort_by_key(Object::slot);
+        let mut expected = vec![a, b, reserved];
+        expected.sort_by_key(Object::slot);
+        assert_eq!(despawned, expected);
+        assert!(registry.borrow().by_object(b).is_none());
+
+        // Commands queued before the clear are dropped along with their objects.
+        space.run_queued()?;
+        assert!(space.is_empty());
+
+        let c = space.spawn((Position(2),));
+        assert_eq!(space.len(), 1);
+        assert_eq!(*space.get::<Position>(c)?, Position(2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn sorted_queries_visit_objects_in_slot_order() -> Result<()> {
+        let space = Spaces::new().create_space();
+        let mut space = space.borrow_mut();
+
+        // Spread objects across archetypes and reuse freed slots, so that neither spawn order nor
+        // storage order matches slot order.
+        let objects = (0..6)
+            .map(|i| space.spawn((Position(i),)))
+            .collect::<Vec<_>>();
+        space.insert_one(objects[1], Name("b"))?;
+        space.insert_one(objects[4], Name("e"))?;
+        space.despawn(objects[0])?;
+        space.despawn(objects[3])?;
+        let late = space.spawn((Name("late"), Position(10)));
+        let later = space.spawn((Position(11),));
+
+        let slots = space
+            .query_sorted::<&Position>()
+            .iter()
+            .map(|(object, _)| object.slot())
+            .collect::<Vec<_>>();
+        let mut expected = slots.clone();
+        expected.sort_unstable();
+        assert_eq!(slots, expected);
+        assert_eq!(slots.len(), 6);
+        assert!(slots.contains(&late.slot()) && slots.contains(&later.slot()));
+
+        // Keys can come from the query items, and ties are still broken by slot.
+        let by_parity = space
+            .query_sorted::<&Position>()
+            .with::<Name>()
+            .iter_by_key(|(_, position)| position.0 % 2)
+            .map(|(object, _)| object)
+            .collect::<Vec<_>>();
+        let mut evens = vec![objects[4], late];
+        evens.sort_by_key(Object::slot);
+        assert_eq!(by_parity, [evens[0], evens[1], objects[1]]);
+
+        Ok(())
+    }
+}
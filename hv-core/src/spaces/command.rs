@@ -8,11 +8,14 @@
 //! be immediately known; the builtin queueing functionality of [`Space`] does cover this, so you
 //! likely won't need a [`CommandBuffer`] in most cases, as [`Space`] contains a convenient one.
 
+use std::any::TypeId;
+
 use hecs::{Bundle, DynamicBundle, EntityBuilder};
+use shrev::EventChannel;
 
 use crate::{
     error::*,
-    spaces::{ComponentError, Object, Space, SpaceId},
+    spaces::{ComponentError, Object, Space, SpaceEvent, SpaceId},
 };
 
 struct SpawnCommand {
@@ -30,7 +33,12 @@ struct InsertCommand {
 
 struct RemoveCommand {
     target: Object,
-    remove: fn(Object, SpaceId, &mut hecs::World) -> Result<(), ComponentError>,
+    remove: fn(
+        Object,
+        SpaceId,
+        &mut hecs::World,
+        &mut EventChannel<SpaceEvent>,
+    ) -> Result<(), ComponentError>,
 }
 
 enum Command {
@@ -104,11 +112,13 @@ impl CommandBuffer {
             object: Object,
             space_id: SpaceId,
             ecs: &mut hecs::World,
+            events: &mut EventChannel<SpaceEvent>,
         ) -> Result<(), ComponentError> {
             if object.space != space_id {
                 Err(ComponentError::WrongSpace)
             } else {
                 ecs.remove::<T>(object.entity)?;
+                T::with_static_ids(|ids| SpaceEvent::write_removed(events, object, ids));
                 Ok(())
             }
         }
@@ -119,12 +129,26 @@ impl CommandBuffer {
         }));
     }
 
-    pub(super) fn run_internal(&mut self, space_id: SpaceId, ecs: &mut hecs::World) -> Result<()> {
+    /// Run all queued commands, publishing [`SpaceEvent`]s for their changes. Reserved objects
+    /// must already have been flushed by the caller.
+    pub(super) fn run_internal(
+        &mut self,
+        space_id: SpaceId,
+        ecs: &mut hecs::World,
+        events: &mut EventChannel<SpaceEvent>,
+    ) -> Result<()> {
         let mut errors = Vec::new();
         for command in self.queue.drain(..) {
             let maybe_err = match command {
                 Command::Spawn(mut spawn_cmd) => {
-                    ecs.spawn(spawn_cmd.builder.build());
+                    let built = spawn_cmd.builder.build();
+                    let ids = built.with_ids(<[TypeId]>::to_vec);
+                    let entity = ecs.spawn(built);
+                    let object = Object {
+                        space: space_id,
+                        entity,
+                    };
+                    SpaceEvent::write_spawned(events, object, &ids);
                     self.entity_builder_pool.push(spawn_cmd.builder);
                     None
                 }
@@ -132,22 +156,29 @@ impl CommandBuffer {
                     if despawn_cmd.target.space != space_id {
                         Some(ComponentError::WrongSpace)
                     } else {
-                        ecs.despawn(despawn_cmd.target.entity).err().map(From::from)
+                        let maybe_err = ecs.despawn(despawn_cmd.target.entity).err();
+                        if maybe_err.is_none() {
+                            events.single_write(SpaceEvent::Despawned(despawn_cmd.target));
+                        }
+                        maybe_err.map(From::from)
                     }
                 }
                 Command::Insert(mut insert_cmd) => {
                     if insert_cmd.target.space != space_id {
                         Some(ComponentError::WrongSpace)
                     } else {
-                        let maybe_err = ecs
-                            .insert(insert_cmd.target.entity, insert_cmd.builder.build())
-                            .err();
+                        let built = insert_cmd.builder.build();
+                        let ids = built.with_ids(<[TypeId]>::to_vec);
+                        let maybe_err = ecs.insert(insert_cmd.target.entity, built).err();
+                        if maybe_err.is_none() {
+                            SpaceEvent::write_added(events, insert_cmd.target, &ids);
+                        }
                         self.entity_builder_pool.push(insert_cmd.builder);
                         maybe_err.map(From::from)
                     }
                 }
                 Command::Remove(remove_cmd) => {
-                    (remove_cmd.remove)(remove_cmd.target, space_id, ecs).err()
+                    (remove_cmd.remove)(remove_cmd.target, space_id, ecs, events).err()
                 }
             };
 
@@ -164,9 +195,24 @@ impl CommandBuffer {
         }
     }
 
+    /// Discard every queued command without running it.
+    pub fn clear(&mut self) {
+        for command in self.queue.drain(..) {
+            match command {
+                Command::Spawn(SpawnCommand { mut builder })
+                | Command::Insert(InsertCommand { mut builder, .. }) => {
+                    builder.clear();
+                    self.entity_builder_pool.push(builder);
+                }
+                Command::Despawn(_) | Command::Remove(_) => {}
+            }
+        }
+    }
+
     /// Drain this command buffer and run all commands in it on a [`Space`]. All commands will be
     /// run, even if a command fails; errors will be reported together afterwards.
     pub fn run(&mut self, space: &mut Space) -> Result<()> {
-        self.run_internal(space.id, &mut space.ecs)
+        space.flush_reserved();
+        self.run_internal(space.id, &mut space.ecs, &mut space.events)
     }
 }
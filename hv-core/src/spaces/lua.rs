@@ -2,7 +2,7 @@ use hecs::EntityBuilder;
 use mlua::{prelude::*, Variadic as LuaVariadic};
 
 use crate::{
-    components::DynamicComponentConstructor,
+    components::{ComponentType, DynamicComponentConstructor},
     engine::{Engine, EngineRef},
     shared::{Shared, Weak},
     spaces::{object_table::ObjectTableComponent, Object, Space, SpaceId, Spaces},
@@ -122,6 +122,50 @@ pub fn spaces_objects() -> lua_fn!(Fn<'lua>(&Space, ()) -> Vec<Object>) {
     }
 }
 
+fn component_types<'lua>(
+    names: impl Iterator<Item = LuaResult<LuaString<'lua>>>,
+) -> LuaResult<Vec<&'static ComponentType>> {
+    names
+        .map(|name| {
+            let name = name?;
+            let name = name.to_str()?;
+            ComponentType::lookup(name).ok_or_else(|| {
+                LuaError::external(format!("no component type registered as `{}`", name))
+            })
+        })
+        .collect()
+}
+
+/// Query a space for all objects with object tables which have every component named in the
+/// sequence part of the given table, and none of the components named in its optional `without`
+/// field. Component names are those registered with [`component_type!`](crate::component_type).
+///
+/// Only a shared borrow of the space is needed, and no components are borrowed at all, so this
+/// can be called while other code holds borrows on the space or on the queried components. The
+/// results are collected into a table before returning, so nothing stays borrowed while the
+/// caller iterates over them, even across a coroutine yield. Components are read and written
+/// through their own accessors, which only borrow them for the duration of the access.
+pub fn spaces_query() -> lua_fn!(Fn<'lua>(&Space, LuaTable<'lua>) -> Vec<Object>) {
+    |_, space, spec| {
+        let with = component_types(spec.clone().sequence_values())?;
+        let without = match spec.get::<_, Option<LuaTable>>("without")? {
+            Some(table) => component_types(table.sequence_values())?,
+            None => Vec::new(),
+        };
+
+        Ok(space
+            .query::<()>()
+            .with::<ObjectTableComponent>()
+            .iter()
+            .map(|(obj, _)| obj)
+            .filter(|&obj| {
+                with.iter().all(|ty| ty.is_on_object(space, obj))
+                    && !without.iter().any(|ty| ty.is_on_object(space, obj))
+            })
+            .collect())
+    }
+}
+
 /// A specialized cache for [`Space`]s to reduce access to the [`Spaces`] resource.
 pub struct SpaceCache {
     weak_engine: EngineRef,
@@ -162,3 +206,68 @@ impl SpaceCache {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{error::*, spaces::object_table};
+
+    struct A;
+    struct B;
+    struct C;
+
+    crate::component_type!("test.A", A);
+    crate::component_type!("test.B", B);
+    crate::component_type!("test.C", C);
+
+    #[test]
+    fn query_objects_from_lua() -> Result<()> {
+        let lua = Lua::new();
+        let registry = object_table::insert_registry(&lua)?;
+        let space = Spaces::new().create_space();
+
+        let spawn = |name: &str, components: &mut EntityBuilder| -> Result<()> {
+            let mut space = space.borrow_mut();
+            let object = space.spawn(components.build());
+            let table = lua.create_table()?;
+            table.set("name", name)?;
+            let otc = registry.borrow_mut().insert(&lua, table, object)?;
+            space.insert_one(object, otc)?;
+            Ok(())
+        };
+
+        spawn("a", EntityBuilder::new().add(A))?;
+        spawn("ab", EntityBuilder::new().add(A).add(B))?;
+        spawn("b", EntityBuilder::new().add(B))?;
+        spawn("abc", EntityBuilder::new().add(A).add(B).add(C))?;
+        // Objects without object tables can't be represented in Lua, so they're never returned.
+        space.borrow_mut().spawn((A, B));
+
+        lua.globals().set("space", space.clone())?;
+        let names = |query: &str| -> Result<Vec<String>> {
+            let chunk = format!(
+                r#"
+                    local names = {{}}
+                    for _, object in ipairs(space:query {}) do
+                        table.insert(names, object.name)
+                    end
+                    table.sort(names)
+                    return names
+                "#,
+                query
+            );
+            Ok(lua.load(&chunk).eval()?)
+        };
+
+        assert_eq!(names(r#"{ "test.A", "test.B" }"#)?, vec!["ab", "abc"]);
+        assert_eq!(names(r#"{ "test.B", without = { "test.A" } }"#)?, vec!["b"]);
+        assert_eq!(
+            names(r#"{ "test.A", without = { "test.C" } }"#)?,
+            vec!["a", "ab"]
+        );
+        assert_eq!(names("{}")?.len(), 4);
+        assert!(names(r#"{ "test.Nonexistent" }"#).is_err());
+
+        Ok(())
+    }
+}
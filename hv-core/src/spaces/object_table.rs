@@ -19,7 +19,7 @@ use crate::{
     mlua::prelude::*,
     plugins::Plugin,
     shared::{Shared, Weak},
-    spaces::Object,
+    spaces::{Component, Object, Space},
 };
 
 use thunderdome::{Arena, Index};
@@ -290,6 +290,94 @@ impl ObjectTableRegistry {
     }
 }
 
+/// Call the method `method` on the object table of every object in `space` with a `C` component,
+/// passing `args` after the object table itself. Objects are visited in order of their slot in the
+/// space. See [`call_method_on_objects`] for how objects without object tables or without the
+/// method are handled, and how errors are reported.
+///
+/// The matching objects are collected before any methods are called, so the space is not borrowed
+/// while Lua runs, and methods are free to query the space or spawn and despawn objects.
+pub fn call_method_on_all<'lua, C, A>(
+    lua: &'lua Lua,
+    space: &Shared<Space>,
+    method: &str,
+    args: A,
+) -> Result<()>
+where
+    C: Component,
+    A: ToLuaMulti<'lua> + Clone,
+{
+    let mut objects = space
+        .borrow()
+        .query::<()>()
+        .with::<C>()
+        .iter()
+        .map(|(object, ())| object)
+        .collect::<Vec<_>>();
+    objects.sort_unstable_by_key(|object| object.slot());
+
+    call_method_on_objects(lua, objects, method, args)
+}
+
+/// Call the method `method` on the object table of every object in `objects`, passing `args` after
+/// the object table itself.
+///
+/// Objects without an object table, and object tables without the method, are skipped, as are
+/// objects despawned by an earlier call before their own turn comes up. An error in one call
+/// doesn't stop the rest from running; every error is collected and reported together once all
+/// the methods have been called.
+pub fn call_method_on_objects<'lua, A>(
+    lua: &'lua Lua,
+    objects: impl IntoIterator<Item = Object>,
+    method: &str,
+    args: A,
+) -> Result<()>
+where
+    A: ToLuaMulti<'lua> + Clone,
+{
+    let registry = lua.get_resource::<ObjectTableRegistry>()?;
+    let tables = {
+        let registry = registry.borrow();
+        objects
+            .into_iter()
+            .filter_map(|object| registry.by_object(object).map(|entry| (object, entry)))
+            .map(|(object, entry)| Ok((object, lua.registry_value::<LuaTable>(entry.key())?)))
+            .collect::<Result<Vec<_>>>()?
+    };
+
+    let mut errors = Vec::new();
+    for (object, table) in tables {
+        // An earlier call may have despawned this object, removing its object table entry.
+        let despawned = registry.borrow().by_object(object).is_none();
+        if despawned || table.get::<_, Option<LuaFunction>>(method)?.is_none() {
+            continue;
+        }
+
+        if let Err(err) = table.call_method::<_, _, ()>(method, args.clone()) {
+            errors.push(format!("{:?}: {}", object, err));
+        }
+    }
+
+    ensure!(
+        errors.is_empty(),
+        "{} call(s) to `{}` failed:\n{}",
+        errors.len(),
+        method,
+        errors.join("\n")
+    );
+
+    Ok(())
+}
+
+/// Create an [`ObjectTableRegistry`] and insert it into the Lua state, along with the Lua-side
+/// table mapping object tables back to their registry entries.
+pub(crate) fn insert_registry(lua: &Lua) -> Result<Shared<ObjectTableRegistry>> {
+    let otable_resource = ObjectTableRegistry::new();
+    lua.insert_resource(otable_resource.clone())?;
+    lua.set_named_registry_value(HV_LUA_OBJECT_TABLE, lua.create_table()?)?;
+    Ok(otable_resource)
+}
+
 struct ObjectTableComponentPlugin;
 
 impl Plugin for ObjectTableComponentPlugin {
@@ -298,10 +386,8 @@ impl Plugin for ObjectTableComponentPlugin {
     }
 
     fn open<'lua>(&self, lua: &'lua Lua, engine: &Engine) -> Result<LuaTable<'lua>> {
-        let otable_resource = ObjectTableRegistry::new();
+        let otable_resource = insert_registry(lua)?;
         engine.insert_wrapped(otable_resource.clone());
-        lua.insert_resource(otable_resource.clone())?;
-        lua.set_named_registry_value(HV_LUA_OBJECT_TABLE, lua.create_table()?)?;
 
         let otr_weak = otable_resource.downgrade();
         let object_table_new = lua.create_function(move |lua, table: LuaTable| {
@@ -332,6 +418,7 @@ impl Plugin for ObjectTableComponentPlugin {
 }
 
 inventory::submit!(ComponentWrapper::new(ObjectTableComponentPlugin));
+crate::component_type!("ObjectTable", ObjectTableComponent);
 
 impl LuaUserData for ObjectTableComponent {}
 
@@ -360,3 +447,94 @@ impl Plugin for UpdateHookComponentPlugin {
 }
 
 inventory::submit!(ComponentWrapper::new(UpdateHookComponentPlugin));
+crate::component_type!("UpdateHook", UpdateHookComponent);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spaces::Spaces;
+
+    struct Tagged;
+    struct Untagged;
+
+    #[test]
+    fn methods_are_called_once_on_each_tagged_object() -> Result<()> {
+        let lua = Lua::new();
+        let registry = insert_registry(&lua)?;
+        let space = Spaces::new().create_space();
+        lua.globals().set("space", space.clone())?;
+
+        let spawn = |tagged: bool, source: &str| -> Result<LuaTable> {
+            let mut space = space.borrow_mut();
+            let object = if tagged {
+                space.spawn((Tagged,))
+            } else {
+                space.spawn((Untagged,))
+            };
+            let table: LuaTable = lua.load(source).eval()?;
+            let otc = registry.borrow_mut().insert(&lua, table.clone(), object)?;
+            space.insert_one(object, otc)?;
+            Ok(table)
+        };
+
+        // The space must not be borrowed while methods run, so querying it from Lua has to work.
+        let updating = r#"{
+            calls = 0,
+            update = function(self, dt)
+                assert(#space:query {} == 4)
+                self.calls = self.calls + 1
+                self.dt = dt
+            end,
+        }"#;
+        let tagged = [spawn(true, updating)?, spawn(true, updating)?];
+        let untagged = spawn(false, updating)?;
+        // Tagged objects without the method are skipped rather than treated as an error.
+        spawn(true, "{}")?;
+
+        call_method_on_all::<Tagged, _>(&lua, &space, "update", 0.5)?;
+
+        for table in tagged.iter() {
+            assert_eq!(table.get::<_, u32>("calls")?, 1);
+            assert_eq!(table.get::<_, f32>("dt")?, 0.5);
+        }
+        assert_eq!(untagged.get::<_, u32>("calls")?, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn errors_are_collected_after_every_call_runs() -> Result<()> {
+        let lua = Lua::new();
+        let registry = insert_registry(&lua)?;
+        let space = Spaces::new().create_space();
+
+        let mut tables = Vec::new();
+        for source in &[
+            r#"{ update = function(self) self.called = true; error("first") end }"#,
+            r#"{ update = function(self) self.called = true end }"#,
+            r#"{ update = function(self) self.called = true; error("third") end }"#,
+        ] {
+            let mut space = space.borrow_mut();
+            let object = space.spawn((Tagged,));
+            let table: LuaTable = lua.load(*source).eval()?;
+            let otc = registry.borrow_mut().insert(&lua, table.clone(), object)?;
+            space.insert_one(object, otc)?;
+            tables.push(table);
+        }
+
+        let message = call_method_on_all::<Tagged, _>(&lua, &space, "update", ())
+            .unwrap_err()
+            .to_string();
+        assert!(
+            message.starts_with("2 call(s) to `update` failed"),
+            "{}",
+            message
+        );
+        assert!(message.contains("first") && message.contains("third"));
+        for table in tables {
+            assert!(table.get::<_, bool>("called")?);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,146 @@
+//! Pooled scratch buffers for deferring changes to a [`Space`] without allocating every frame.
+//!
+//! A common pattern when working with a space is to query it, collect the objects which need
+//! something done to them into a `Vec`, release the query, and then act on the collected objects
+//! (calling into Lua, despawning them, and so on.) Allocating a fresh `Vec` for this every frame is
+//! wasteful, so every [`Space`] keeps a [`Scratch`] pool of buffers keyed by element type:
+//!
+//! ```ignore
+//! let mut to_update = space.borrow().scratch::<Object>();
+//! for (obj, ()) in space.borrow().query::<()>().with::<NeedsUpdate>().iter() {
+//!     to_update.push(obj);
+//! }
+//! call_method_on_objects(lua, to_update.drain(..), "update", dt)?;
+//! ```
+//!
+//! A [`ScratchVec`] is always empty when handed out, and is cleared and returned to the pool when
+//! it's dropped, so the next borrow of the same element type reuses its allocation. It doesn't
+//! borrow the space, so the space is free to be mutated while the buffer is alive.
+//!
+//! [`Space`]: crate::spaces::Space
+
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    fmt,
+    ops::{Deref, DerefMut},
+    sync::{Arc, Mutex},
+};
+
+/// A pool of reusable `Vec`s, keyed by element type. Cloning a `Scratch` gives another handle to
+/// the same pool.
+#[derive(Clone, Default)]
+pub struct Scratch {
+    pools: Arc<Mutex<HashMap<TypeId, Box<dyn Any + Send>>>>,
+}
+
+impl fmt::Debug for Scratch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set()
+            .entries(self.pools.lock().unwrap().keys())
+            .finish()
+    }
+}
+
+impl Scratch {
+    /// Create an empty pool.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take an empty buffer from the pool, allocating a new one if every pooled buffer of this
+    /// element type is already in use.
+    pub fn take<T: Send + 'static>(&self) -> ScratchVec<T> {
+        let buf = self
+            .pools
+            .lock()
+            .unwrap()
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|pool| pool.downcast_mut::<Vec<Vec<T>>>().unwrap().pop())
+            .unwrap_or_default();
+
+        ScratchVec {
+            buf,
+            scratch: self.clone(),
+        }
+    }
+
+    fn give_back<T: Send + 'static>(&self, mut buf: Vec<T>) {
+        // Clear before locking, since dropping the elements could end up taking another buffer.
+        buf.clear();
+        self.pools
+            .lock()
+            .unwrap()
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(Vec::<Vec<T>>::new()))
+            .downcast_mut::<Vec<Vec<T>>>()
+            .unwrap()
+            .push(buf);
+    }
+}
+
+/// A buffer borrowed from a [`Scratch`] pool. Dereferences to a `Vec<T>`, and is cleared and
+/// returned to the pool when dropped.
+pub struct ScratchVec<T: Send + 'static> {
+    buf: Vec<T>,
+    scratch: Scratch,
+}
+
+impl<T: Send + fmt::Debug + 'static> fmt::Debug for ScratchVec<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.buf.fmt(f)
+    }
+}
+
+impl<T: Send + 'static> Deref for ScratchVec<T> {
+    type Target = Vec<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.buf
+    }
+}
+
+impl<T: Send + 'static> DerefMut for ScratchVec<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.buf
+    }
+}
+
+impl<T: Send + 'static> Drop for ScratchVec<T> {
+    fn drop(&mut self) {
+        self.scratch.give_back(std::mem::take(&mut self.buf));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::spaces::{Object, Spaces};
+
+    #[test]
+    fn sequential_borrows_reuse_the_same_allocation() {
+        let space = Spaces::new().create_space();
+        let mut space = space.borrow_mut();
+        let objects = (0..64).map(|_| space.spawn(())).collect::<Vec<_>>();
+
+        let (ptr, capacity) = {
+            let mut scratch = space.scratch::<Object>();
+            assert!(scratch.is_empty());
+            scratch.extend(objects.iter().copied());
+            (scratch.as_ptr(), scratch.capacity())
+        };
+
+        // The space can be mutated while a buffer is out, and the buffer comes back empty.
+        let mut scratch = space.scratch::<Object>();
+        space.despawn(objects[0]).unwrap();
+        assert!(scratch.is_empty());
+        assert_eq!((scratch.as_ptr(), scratch.capacity()), (ptr, capacity));
+        scratch.push(objects[1]);
+
+        // Buffers are keyed by element type, and a nested borrow of the same type gets its own.
+        let other = space.scratch::<(Object, Object)>();
+        assert_eq!(other.capacity(), 0);
+        let nested = space.scratch::<Object>();
+        assert!(nested.is_empty());
+        assert_ne!(nested.as_ptr(), ptr);
+    }
+}
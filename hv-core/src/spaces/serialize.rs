@@ -90,10 +90,31 @@
 //!     }
 //! ));
 //! ```
+//!
+//! ## Transient components
+//!
+//! Components which only make sense at runtime, such as caches or handles to GPU resources, don't
+//! need to be registered at all to be left out of a serialized space. But a component which *used*
+//! to be serialized will still show up in spaces saved before it stopped being serialized, and
+//! deserializing those would fail if it simply stopped being registered. Wrapping its
+//! [`ComponentSerde`] with [`transient`] instead means it is never serialized, but its data is
+//! still read (and thrown away) when it's found in an older save:
+//!
+//! ```rust
+//! # use hv_core::{spaces::serialize, serializable};
+//! # use serde::*;
+//! #[derive(Debug, Clone, Serialize, Deserialize)]
+//! pub struct PathCache(pub Vec<u32>);
+//!
+//! serializable!(serialize::transient(serialize::with_serde::<PathCache>(
+//!     "my.PathCacheComponent"
+//! )));
+//! ```
 
 use std::{
     collections::{BTreeMap, VecDeque},
     fmt,
+    hash::{Hash, Hasher},
     io::{Read, Write},
     marker::PhantomData,
 };
@@ -106,7 +127,7 @@ use crate::{
     prelude::Shared,
     spaces::{
         object_table::{ObjectTableComponent, ObjectTableRegistry},
-        Component, Space,
+        Component, Object, Space, Spaces,
     },
 };
 
@@ -167,10 +188,27 @@ pub trait ComponentSerde {
     fn finalize(&self, _lua: &Lua, _space: &mut Space) -> Result<()> {
         Ok(())
     }
+
+    /// Serialize a single component on its own, for [`Space::state_hash`]. Returns `None` if
+    /// components of this type can only be serialized a whole column at a time, which is the
+    /// default. Components made with [`with_serde`] support this; components made with
+    /// [`with_lua`] don't, since their Lua values can only be serialized through a
+    /// [`SerdeContext`].
+    fn serialize_one(&self, _component: &Self::Component) -> Option<Result<Vec<u8>>> {
+        None
+    }
+
+    /// Whether this component type is left out when serializing. Components of a transient type
+    /// which are found while deserializing are read and then dropped, rather than added to the
+    /// space. See [`transient`].
+    fn is_transient(&self) -> bool {
+        false
+    }
 }
 
 trait ErasedComponentSerde {
     fn name(&self) -> &'static str;
+    fn is_transient(&self) -> bool;
     fn contained_in(&self, archetype: &Archetype) -> bool;
     fn add_to_column_batch_type(&self, column_batch_type: &mut ColumnBatchType);
 
@@ -193,6 +231,10 @@ trait ErasedComponentSerde {
     ) -> Result<()>;
 
     fn finalize(&self, lua: &Lua, space: &mut Space) -> Result<()>;
+
+    /// Serialize each component of this type in the space on its own, along with the object it
+    /// belongs to.
+    fn serialize_each(&self, space: &Space) -> Result<Vec<(Object, Vec<u8>)>>;
 }
 
 impl<T: ComponentSerde> ErasedComponentSerde for T {
@@ -200,6 +242,10 @@ impl<T: ComponentSerde> ErasedComponentSerde for T {
         T::name(self)
     }
 
+    fn is_transient(&self) -> bool {
+        T::is_transient(self)
+    }
+
     fn contained_in(&self, archetype: &Archetype) -> bool {
         archetype.has::<T::Component>()
     }
@@ -231,6 +277,22 @@ impl<T: ComponentSerde> ErasedComponentSerde for T {
     fn finalize(&self, lua: &Lua, space: &mut Space) -> Result<()> {
         T::finalize(self, lua, space)
     }
+
+    fn serialize_each(&self, space: &Space) -> Result<Vec<(Object, Vec<u8>)>> {
+        space
+            .query::<&T::Component>()
+            .iter()
+            .map(|(object, component)| {
+                let bytes = T::serialize_one(self, component).ok_or_else(|| {
+                    anyhow!(
+                        "component `{}` can't be serialized on its own",
+                        T::name(self)
+                    )
+                })??;
+                Ok((object, bytes))
+            })
+            .collect()
+    }
 }
 
 #[doc(hidden)]
@@ -481,6 +543,10 @@ pub fn with_serde<T: Component + Serialize + for<'de> Deserialize<'de>>(
                 archetype.get::<T>().expect("already checked").iter(),
             )))
         }
+
+        fn serialize_one(&self, component: &T) -> Option<Result<Vec<u8>>> {
+            Some(bincode::serialize(component).map_err(Error::from))
+        }
     }
 
     SerdeShim::<T> {
@@ -540,11 +606,97 @@ pub fn with_finalizer(
         fn finalize(&self, lua: &Lua, space: &mut Space) -> Result<()> {
             (self.f)(lua, space)
         }
+
+        fn serialize_one(&self, component: &Self::Component) -> Option<Result<Vec<u8>>> {
+            self.cs.serialize_one(component)
+        }
+
+        fn is_transient(&self) -> bool {
+            self.cs.is_transient()
+        }
     }
 
     FinalizedShim { cs, f }
 }
 
+/// Mark a [`ComponentSerde`] as transient: components of its type are skipped when serializing a
+/// space, and any found while deserializing one (from a save made before the type was marked
+/// transient) are deserialized as usual and then dropped instead of being added to the space.
+pub fn transient<C: ComponentSerde>(cs: C) -> impl ComponentSerde<Component = C::Component> {
+    struct TransientShim<S: ComponentSerde> {
+        cs: S,
+    }
+
+    impl<S: ComponentSerde> ComponentSerde for TransientShim<S> {
+        type Component = S::Component;
+
+        fn name(&self) -> &'static str {
+            self.cs.name()
+        }
+
+        fn deserialize_components<'de, D>(
+            &self,
+            count: u32,
+            _column_batch_builder: &mut ColumnBatchBuilder,
+            serde_ctx: &mut SerdeContext,
+            deserializer: D,
+        ) -> Result<()>
+        where
+            D: Deserializer<'de>,
+            D::Error: Send + Sync + 'static,
+        {
+            // The column batch being built doesn't have a column for this type, so read the
+            // components into a scratch batch of their own and then drop it.
+            let mut column_batch_type = ColumnBatchType::new();
+            column_batch_type.add::<S::Component>();
+            let mut scratch = column_batch_type.into_batch(count);
+            self.cs
+                .deserialize_components(count, &mut scratch, serde_ctx, deserializer)?;
+            scratch.build().map_err(|_| {
+                anyhow!(
+                    "incomplete column for transient component `{}`",
+                    self.cs.name()
+                )
+            })?;
+
+            log::trace!(
+                "dropped {} components of transient type {}",
+                count,
+                self.cs.name()
+            );
+
+            Ok(())
+        }
+
+        fn serialize_components<F>(
+            &self,
+            archetype: &Archetype,
+            serde_ctx: &mut SerdeContext,
+            serialize: F,
+        ) -> Result<()>
+        where
+            F: FnOnce(&dyn erased_serde::Serialize) -> Result<()>,
+        {
+            self.cs
+                .serialize_components(archetype, serde_ctx, serialize)
+        }
+
+        fn finalize(&self, lua: &Lua, space: &mut Space) -> Result<()> {
+            self.cs.finalize(lua, space)
+        }
+
+        fn serialize_one(&self, component: &Self::Component) -> Option<Result<Vec<u8>>> {
+            self.cs.serialize_one(component)
+        }
+
+        fn is_transient(&self) -> bool {
+            true
+        }
+    }
+
+    TransientShim { cs }
+}
+
 serializable!(with_finalizer(
     with_lua::<ObjectTableComponent>("hv.ObjectTable"),
     |lua, space| {
@@ -696,7 +848,11 @@ impl<'a> hecs::serialize::column::SerializeContext for SerdeContext<'a> {
         out: &mut S,
     ) -> Result<(), S::Error> {
         self.components.clear();
-        for &bt_serde in self.serdes.values().filter(|bs| bs.contained_in(archetype)) {
+        for &bt_serde in self
+            .serdes
+            .values()
+            .filter(|bs| !bs.is_transient() && bs.contained_in(archetype))
+        {
             out.serialize_element(bt_serde.name())?;
             self.components.push_back(bt_serde);
         }
@@ -731,11 +887,16 @@ impl<'a> hecs::serialize::column::DeserializeContext for SerdeContext<'a> {
         log::trace!("beginning deserializing column component IDs:");
         let mut batch = ColumnBatchType::new();
         while let Some(id) = seq.next_element::<String>()? {
-            let bt_serde = *self
-                .serdes
-                .get(id.as_str())
-                .expect("no such component name registered!");
-            bt_serde.add_to_column_batch_type(&mut batch);
+            let bt_serde = *self.serdes.get(id.as_str()).ok_or_else(|| {
+                de::Error::custom(format!(
+                    "no serializable component type registered as `{}`",
+                    id
+                ))
+            })?;
+            // Transient components still have a column to be read, but it isn't kept.
+            if !bt_serde.is_transient() {
+                bt_serde.add_to_column_batch_type(&mut batch);
+            }
             self.components.push_back(bt_serde);
             log::trace!("component ID: {}", id);
         }
@@ -960,3 +1121,344 @@ pub fn deserialize_whole<R: Read>(space: &Shared<Space>, lua: &Lua, reader: R) -
         ),
     )
 }
+
+/// Identifies a chunk of bytes as a save state written by [`save_state`].
+const SAVE_STATE_MAGIC: [u8; 4] = *b"HVSS";
+
+/// The version of the save state format. Bump this whenever the layout of [`SaveState`] changes.
+const SAVE_STATE_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct SaveState {
+    magic: [u8; 4],
+    version: u32,
+    /// Every space in the [`Spaces`] registry, in order, as written by [`serialize_whole`].
+    spaces: Vec<Vec<u8>>,
+}
+
+/// Serialize every [`Space`] in the [`Spaces`] registry, along with all their objects' Lua object
+/// tables, to a writer as a single chunk of bytes. References between Lua values are preserved
+/// within each space. This is what [`Engine::save_state`] uses to implement quicksaves.
+///
+/// # Locking behavior
+///
+/// Transient immutable borrows: every [`Space`] in the registry
+///
+/// [`Engine::save_state`]: crate::engine::Engine::save_state
+pub fn save_state<W: Write>(spaces: &Spaces, lua: &Lua, writer: W) -> Result<()> {
+    let spaces = spaces
+        .iter()
+        .enumerate()
+        .map(|(i, space)| {
+            let mut buf = Vec::new();
+            serialize_whole(&space, lua, &mut buf)
+                .with_context(|| format!("error saving space {}", i))?;
+            Ok(buf)
+        })
+        .collect::<Result<_>>()?;
+
+    bincode::serialize_into(
+        writer,
+        &SaveState {
+            magic: SAVE_STATE_MAGIC,
+            version: SAVE_STATE_VERSION,
+            spaces,
+        },
+    )?;
+
+    Ok(())
+}
+
+/// Load a save state written by [`save_state`], returning the loaded spaces in the order they were
+/// saved.
+///
+/// The `n`th saved space is loaded into the `n`th space in the registry, replacing everything in it,
+/// so a game which creates its spaces in the same order every time it starts can keep using the
+/// handles it already has. If the save has more spaces than the registry, new ones are created for
+/// the rest; if it has fewer, the leftover spaces are cleared. Fails with an error if the bytes
+/// aren't a save state, were written by an incompatible version, or contain components which
+/// aren't registered as serializable in this build.
+///
+/// # Locking behavior
+///
+/// Transient mutable borrows: every [`Space`] in the registry
+pub fn load_state<R: Read>(
+    spaces: &mut Spaces,
+    lua: &Lua,
+    reader: R,
+) -> Result<Vec<Shared<Space>>> {
+    let state: SaveState = bincode::deserialize_from(reader).context("not a Heavy save state")?;
+    ensure!(state.magic == SAVE_STATE_MAGIC, "not a Heavy save state");
+    ensure!(
+        state.version == SAVE_STATE_VERSION,
+        "save state is format version {}, but only version {} is supported",
+        state.version,
+        SAVE_STATE_VERSION
+    );
+
+    let existing = spaces.iter().collect::<Vec<_>>();
+    let mut loaded = Vec::with_capacity(state.spaces.len());
+    for (i, bytes) in state.spaces.iter().enumerate() {
+        let space = match existing.get(i) {
+            Some(space) => space.clone(),
+            None => spaces.create_space(),
+        };
+
+        deserialize_whole(&space, lua, bytes.as_slice())
+            .with_context(|| format!("error loading space {}", i))?;
+        loaded.push(space);
+    }
+
+    for space in existing.iter().skip(loaded.len()) {
+        space.borrow_mut().clear();
+    }
+
+    Ok(loaded)
+}
+
+/// Feed the serialized bytes of every listed component in the space into `hasher`, ordered by
+/// object slot and then by the order the components were listed in. See [`Space::state_hash`].
+pub(crate) fn hash_components<H: Hasher>(
+    space: &Space,
+    components: &[&str],
+    hasher: &mut H,
+) -> Result<()> {
+    let mut entries = Vec::new();
+    for (index, &name) in components.iter().enumerate() {
+        let registered = inventory::iter::<Serializable>
+            .into_iter()
+            .find(|serializable| serializable.inner.name() == name)
+            .ok_or_else(|| anyhow!("no serializable component type registered as `{}`", name))?;
+        let serialized = registered
+            .inner
+            .serialize_each(space)
+            .with_context(|| format!("error hashing component `{}`", name))?;
+        entries.extend(
+            serialized
+                .into_iter()
+                .map(|(object, bytes)| (object.slot(), index, bytes)),
+        );
+    }
+
+    entries.sort_by_key(|&(slot, index, _)| (slot, index));
+    for (slot, index, bytes) in entries {
+        (slot, components[index], bytes).hash(hasher);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spaces::{object_table, Object};
+
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+    struct Health(u32);
+
+    crate::serializable!(with_serde::<Health>("test.Health"));
+
+    /// A Lua state with just enough of the engine's resources to hold objects with object tables.
+    fn fresh_engine() -> Result<(Lua, Shared<Spaces>)> {
+        let lua = Lua::new();
+        let binser: LuaValue = lua
+            .load(include_str!("../../resources/scripts/std/binser.lua"))
+            .eval()?;
+        lua.globals()
+            .get::<_, LuaTable>("package")?
+            .get::<_, LuaTable>("loaded")?
+            .set("std.binser", binser)?;
+        object_table::insert_registry(&lua)?;
+        let spaces = Shared::new(Spaces::new());
+        lua.insert_resource(spaces.clone())?;
+        Ok((lua, spaces))
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+    struct Mana(u32);
+
+    crate::serializable!(with_serde::<Mana>("test.Mana"));
+
+    #[test]
+    fn identical_spaces_hash_identically() -> Result<()> {
+        const COMPONENTS: &[&str] = &["test.Health", "test.Mana"];
+
+        let build = || {
+            let space = Spaces::new().create_space();
+            {
+                let mut space = space.borrow_mut();
+                space.spawn((Health(3), Mana(5)));
+                let temporary = space.spawn((Health(1),));
+                space.spawn((Mana(7),));
+                space.despawn(temporary).unwrap();
+                space.spawn((Health(10),));
+            }
+            space
+        };
+
+        let (a, b) = (build(), build());
+        let hash = a.borrow().state_hash(COMPONENTS)?;
+        assert_eq!(hash, b.borrow().state_hash(COMPONENTS)?);
+
+        // Moving an object out of its archetype and back can change iteration order, but not the
+        // hash.
+        {
+            let mut b = b.borrow_mut();
+            let object = b.query::<(&Health, &Mana)>().iter().next().unwrap().0;
+            let mana = b.remove_one::<Mana>(object)?;
+            b.insert_one(object, mana)?;
+        }
+        assert_eq!(hash, b.borrow().state_hash(COMPONENTS)?);
+
+        for (_, health) in b.borrow_mut().query_mut::<&mut Health>() {
+            health.0 += 1;
+        }
+        assert_ne!(hash, b.borrow().state_hash(COMPONENTS)?);
+
+        // Components that weren't asked for don't count.
+        assert_eq!(
+            a.borrow().state_hash(&["test.Mana"])?,
+            b.borrow().state_hash(&["test.Mana"])?
+        );
+        assert!(a.borrow().state_hash(&["test.Nonexistent"]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn scripted_objects_round_trip_into_a_fresh_engine() -> Result<()> {
+        let mut saved = Vec::new();
+        {
+            let (lua, spaces) = fresh_engine()?;
+            let registry = lua.get_resource::<ObjectTableRegistry>()?;
+            let space = spaces.borrow_mut().create_space();
+
+            let goblin: LuaTable = lua
+                .load("{ name = 'goblin', loot = { 'dagger' } }")
+                .eval()?;
+            let troll: LuaTable = lua.load("{ name = 'troll' }").eval()?;
+            goblin.set("rival", troll.clone())?;
+            troll.set("rival", goblin.clone())?;
+
+            for (table, hp) in vec![(goblin, 3), (troll, 10)] {
+                let mut space = space.borrow_mut();
+                let object = space.spawn((Health(hp),));
+                let otc = registry.borrow_mut().insert(&lua, table, object)?;
+                space.insert_one(object, otc)?;
+            }
+
+            save_state(&spaces.borrow(), &lua, &mut saved)?;
+        }
+
+        let (lua, spaces) = fresh_engine()?;
+        let loaded = load_state(&mut spaces.borrow_mut(), &lua, saved.as_slice())?;
+        assert_eq!(loaded.len(), 1);
+
+        let space = loaded[0].borrow();
+        let mut objects = space
+            .query::<&Health>()
+            .iter()
+            .map(|(object, &health)| (health, object))
+            .collect::<Vec<(Health, Object)>>();
+        objects.sort_by_key(|&(Health(hp), _)| hp);
+        let goblin = objects[0].1.to_table(&lua)?;
+        let troll = objects[1].1.to_table(&lua)?;
+
+        assert_eq!(objects[0].0, Health(3));
+        assert_eq!(goblin.get::<_, String>("name")?, "goblin");
+        assert_eq!(troll.get::<_, String>("name")?, "troll");
+        assert_eq!(
+            goblin.get::<_, LuaTable>("loot")?.get::<_, String>(1)?,
+            "dagger"
+        );
+        // References between object tables survive the trip.
+        assert_eq!(goblin.get::<_, LuaTable>("rival")?, troll);
+        assert_eq!(troll.get::<_, LuaTable>("rival")?, goblin);
+        // And the tables still map back to their objects.
+        assert_eq!(
+            Object::from_lua(LuaValue::Table(troll), &lua)?,
+            objects[1].1
+        );
+
+        Ok(())
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+    struct PathCache(u32);
+
+    crate::serializable!(transient(with_serde::<PathCache>("test.PathCache")));
+
+    #[test]
+    fn transient_components_are_skipped_but_tolerated() -> Result<()> {
+        let options = || {
+            bincode::DefaultOptions::new()
+                .with_fixint_encoding()
+                .allow_trailing_bytes()
+        };
+
+        let (lua, spaces) = fresh_engine()?;
+        let space = spaces.borrow_mut().create_space();
+        space.borrow_mut().spawn((Health(4), PathCache(9)));
+
+        let mut saved = Vec::new();
+        serialize_whole(&space, &lua, &mut saved)?;
+
+        // Only the persistent component comes back.
+        let loaded = spaces.borrow_mut().create_space();
+        deserialize_whole(&loaded, &lua, saved.as_slice())?;
+        let loaded = loaded.borrow();
+        assert_eq!(
+            loaded
+                .query::<&Health>()
+                .iter()
+                .map(|(_, &h)| h)
+                .collect::<Vec<_>>(),
+            [Health(4)]
+        );
+        assert_eq!(loaded.query::<&PathCache>().iter().count(), 0);
+
+        // Simulate a save from before `PathCache` was transient, which still has a column for it.
+        let mut serde_ctx = SerdeContext::new(&lua)?;
+        let persistent: &'static dyn ErasedComponentSerde =
+            Box::leak(Box::new(with_serde::<PathCache>("test.PathCache")));
+        serde_ctx.serdes.insert("test.PathCache", persistent);
+        let (mut ecs_buf, mut lua_buf) = (Vec::new(), Vec::new());
+        hecs::serialize::column::serialize(
+            &space.borrow().ecs,
+            &mut serde_ctx,
+            &mut bincode::Serializer::new(&mut ecs_buf, options()),
+        )?;
+        serde_ctx.dump_lua_objects(&mut bincode::Serializer::new(&mut lua_buf, options()))?;
+
+        let old = spaces.borrow_mut().create_space();
+        deserialize_separate(
+            &old,
+            &lua,
+            &mut bincode::Deserializer::from_slice(&ecs_buf, options()),
+            &mut bincode::Deserializer::from_slice(&lua_buf, options()),
+        )?;
+        let old = old.borrow();
+        assert_eq!(
+            old.query::<&Health>()
+                .iter()
+                .map(|(_, &h)| h)
+                .collect::<Vec<_>>(),
+            [Health(4)]
+        );
+        assert_eq!(old.query::<&PathCache>().iter().count(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn loading_garbage_is_an_error() -> Result<()> {
+        let (lua, spaces) = fresh_engine()?;
+        let err = load_state(&mut spaces.borrow_mut(), &lua, &b"not a save"[..]).unwrap_err();
+        assert!(
+            err.to_string().contains("not a Heavy save state"),
+            "{}",
+            err
+        );
+        Ok(())
+    }
+}
@@ -72,6 +72,11 @@ impl<K: Key, T, L: Loader<K, T>> SwappableCache<K, T, L> {
         }
     }
 
+    /// Check whether a value has been loaded for the given key.
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.map.contains_key(key)
+    }
+
     /// Re-load the value corresponding to the given key. After reloading, all handles for this key
     /// will point to the newly loaded value rather than the old one.
     pub fn reload(&mut self, key: &K) -> Result<()> {
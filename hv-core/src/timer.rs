@@ -125,12 +125,22 @@ pub struct TimeContext {
     frame_durations: LogBuffer<time::Duration>,
     residual_update_dt: time::Duration,
     frame_count: usize,
+    max_updates_per_frame: u32,
+    updates_this_frame: u32,
+    // Whether this frame owed more updates than it was allowed to run
+    behind: bool,
+    clamped_frames: usize,
+    dropped_time: time::Duration,
 }
 
 // How many frames we log update times for.
 const TIME_LOG_FRAMES: usize = 200;
 
 impl TimeContext {
+    /// The default maximum number of updates [`TimeContext::check_update_time`] and
+    /// [`TimeContext::check_update_time_forced`] will allow in a single frame.
+    pub const DEFAULT_MAX_UPDATES_PER_FRAME: u32 = 8;
+
     /// Creates a new `TimeContext` and initializes the start to this instant.
     pub fn new() -> TimeContext {
         let initial_dt = time::Duration::from_millis(16);
@@ -140,6 +150,11 @@ impl TimeContext {
             frame_durations: LogBuffer::new(TIME_LOG_FRAMES, initial_dt),
             residual_update_dt: time::Duration::from_secs(0),
             frame_count: 0,
+            max_updates_per_frame: Self::DEFAULT_MAX_UPDATES_PER_FRAME,
+            updates_this_frame: 0,
+            behind: false,
+            clamped_frames: 0,
+            dropped_time: time::Duration::from_secs(0),
         }
     }
 
@@ -150,11 +165,19 @@ impl TimeContext {
     pub fn tick(&mut self) {
         let now = time();
         let time_since_last = now - self.last_instant;
-        self.frame_durations.push(f64_to_duration(time_since_last));
         self.last_instant = now;
+        self.tick_by(f64_to_duration(time_since_last));
+    }
+
+    /// Like [`TimeContext::tick`], but records a frame of the given length rather than measuring
+    /// it with the system clock; for driving the timer from recorded frame times, or in tests.
+    pub fn tick_by(&mut self, frame_duration: time::Duration) {
+        self.frame_durations.push(frame_duration);
         self.frame_count += 1;
+        self.residual_update_dt += frame_duration;
 
-        self.residual_update_dt += f64_to_duration(time_since_last);
+        self.updates_this_frame = 0;
+        self.behind = false;
     }
 }
 
@@ -228,21 +251,21 @@ impl TimeContext {
     /// }
     /// # }
     /// ```
+    ///
+    /// At most [`TimeContext::max_updates_per_frame`] updates are allowed per frame. If a frame
+    /// owes more than that, the whole steps still owed are dropped rather than carried over to
+    /// the next frame, and the frame is reported as [behind](TimeContext::is_behind).
     pub fn check_update_time(&mut self, target_fps: u32) -> bool {
         let target_dt = fps_as_duration(target_fps);
-        if self.residual_update_dt > target_dt {
-            self.residual_update_dt -= target_dt;
-            true
-        } else {
-            false
-        }
+        self.residual_update_dt > target_dt && self.run_update(target_dt)
     }
 
     /// This is a variant of `check_update_time` which intends you to pass an iteration
     /// counter. If the iteration counter is zero, it will do an update regardless of
     /// whether there's enough remaining time, and set the residual delta time to zero.
     /// This helps avoid cascading stutters where the game performs no updates one frame
-    /// and then many the next.
+    /// and then many the next. Updates are limited per frame the same way as with
+    /// `check_update_time`.
     ///
     /// ```rust
     /// # use hv_core::{prelude::*, timer::{self, TimeContext}};
@@ -261,20 +284,79 @@ impl TimeContext {
     /// ```
     pub fn check_update_time_forced(&mut self, target_fps: u32, iteration: &mut u32) -> bool {
         let target_dt = fps_as_duration(target_fps);
-        if self.residual_update_dt > target_dt
-            || (*iteration == 0 && self.fps() < 2. * target_fps as f64)
+        if (self.residual_update_dt > target_dt
+            || (*iteration == 0 && self.fps() < 2. * target_fps as f64))
+            && self.run_update(target_dt)
         {
             *iteration += 1;
-            self.residual_update_dt = self
-                .residual_update_dt
-                .checked_sub(target_dt)
-                .unwrap_or_default();
             true
         } else {
             false
         }
     }
 
+    // Consume one update's worth of time if this frame hasn't run out of updates yet; otherwise,
+    // drop every whole update still owed.
+    fn run_update(&mut self, target_dt: time::Duration) -> bool {
+        if self.updates_this_frame >= self.max_updates_per_frame {
+            let owed = self.residual_update_dt.as_nanos();
+            let kept = time::Duration::from_nanos((owed % target_dt.as_nanos()) as u64);
+            self.dropped_time += self.residual_update_dt - kept;
+            self.residual_update_dt = kept;
+            if !self.behind {
+                self.behind = true;
+                self.clamped_frames += 1;
+            }
+            return false;
+        }
+
+        self.updates_this_frame += 1;
+        self.residual_update_dt = self
+            .residual_update_dt
+            .checked_sub(target_dt)
+            .unwrap_or_default();
+        true
+    }
+
+    /// The most updates [`TimeContext::check_update_time`] and
+    /// [`TimeContext::check_update_time_forced`] will allow in a single frame.
+    pub fn max_updates_per_frame(&self) -> u32 {
+        self.max_updates_per_frame
+    }
+
+    /// Set the most updates allowed in a single frame. Lower limits keep a slow machine from
+    /// spending ever longer frames catching up (the "spiral of death"), at the cost of the game
+    /// running slower than real time while it's behind.
+    ///
+    /// Panics if `max_updates` is zero.
+    pub fn set_max_updates_per_frame(&mut self, max_updates: u32) {
+        assert!(max_updates > 0, "max updates per frame must be positive");
+        self.max_updates_per_frame = max_updates;
+    }
+
+    /// The number of updates allowed so far this frame, that is, since the last
+    /// [`TimeContext::tick`].
+    pub fn updates_this_frame(&self) -> u32 {
+        self.updates_this_frame
+    }
+
+    /// Whether this frame owed more updates than [`TimeContext::max_updates_per_frame`] allows,
+    /// and had to drop some. This is the thing to check for showing a "running slow" indicator.
+    pub fn is_behind(&self) -> bool {
+        self.behind
+    }
+
+    /// The number of frames which have been [behind](TimeContext::is_behind).
+    pub fn clamped_frames(&self) -> usize {
+        self.clamped_frames
+    }
+
+    /// The total time which has been dropped from frames which were behind, rather than being
+    /// caught up on.
+    pub fn dropped_time(&self) -> time::Duration {
+        self.dropped_time
+    }
+
     /// Returns the fractional amount of a frame not consumed
     /// by  [`check_update_time()`](fn.check_update_time.html).
     /// For example, if the desired
@@ -301,6 +383,143 @@ impl TimeContext {
     }
 }
 
+/// Splits variable frame times into a whole number of fixed-length steps, keeping track of the
+/// time left over so that rendering can interpolate between the last two steps.
+///
+/// ```rust
+/// # use hv_core::timer::FixedTimestep;
+/// let mut timestep = FixedTimestep::new(1. / 60.);
+/// for _ in 0..timestep.advance(1. / 30.) {
+///     // update_game_physics(timestep.step());
+/// }
+/// // draw_interpolated(timestep.alpha());
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct FixedTimestep {
+    step: f32,
+    accumulator: f32,
+    max_steps: u32,
+}
+
+impl FixedTimestep {
+    /// The default maximum number of steps run per call to [`FixedTimestep::advance`].
+    pub const DEFAULT_MAX_STEPS: u32 = 8;
+
+    /// Create a timestep which runs steps of `step` seconds each.
+    pub fn new(step: f32) -> Self {
+        assert!(step > 0., "fixed timestep must be positive, got {}", step);
+        Self {
+            step,
+            accumulator: 0.,
+            max_steps: Self::DEFAULT_MAX_STEPS,
+        }
+    }
+
+    /// Limit the number of steps a single call to [`FixedTimestep::advance`] may run. If a frame
+    /// takes long enough to owe more steps than this, the excess time is dropped rather than
+    /// carried over, so that one slow frame can't snowball into every following frame running
+    /// more and more steps.
+    pub fn with_max_steps(self, max_steps: u32) -> Self {
+        Self { max_steps, ..self }
+    }
+
+    /// The length of a single step, in seconds.
+    pub fn step(&self) -> f32 {
+        self.step
+    }
+
+    /// Add `dt` seconds of elapsed time, and return how many fixed steps should be run to catch
+    /// up with it.
+    pub fn advance(&mut self, dt: f32) -> u32 {
+        self.accumulator += dt.max(0.);
+        // A little slack, so that repeatedly adding a frame time equal to the step (which isn't
+        // exactly representable) doesn't alternate between zero and two steps per frame.
+        let owed = (self.accumulator / self.step + 1e-4).floor() as u32;
+        let steps = owed.min(self.max_steps);
+        self.accumulator = (self.accumulator - steps as f32 * self.step).max(0.);
+        if owed > self.max_steps {
+            self.accumulator %= self.step;
+        }
+        steps
+    }
+
+    /// How far into the next step the leftover time reaches, from `0.` to `1.`: the blend factor
+    /// for interpolating between the previous and current step's state.
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.step).clamp(0., 1.)
+    }
+}
+
+/// Caps the frame rate by waiting out whatever is left of each frame's time budget once the frame
+/// is done. The [`Engine`](crate::engine::Engine) keeps one of these, set from
+/// [`Conf::target_fps`](crate::conf::Conf::target_fps), and waits on it after every draw.
+///
+/// The wait happens after drawing and before the next frame starts, so it's simply part of the
+/// frame as far as [`TimeContext`] (or anything else measuring time between frames) is concerned:
+/// a capped frame measures as one full frame's length, and nothing is double-counted or lost from
+/// a fixed-timestep accumulator.
+#[derive(Debug, Clone)]
+pub struct FrameLimiter {
+    target_fps: Option<u32>,
+    frame_start: Instant,
+}
+
+impl FrameLimiter {
+    /// How close to the end of a frame the limiter stops sleeping and starts spinning, since
+    /// sleeps routinely overshoot by a millisecond or so.
+    pub const SPIN_MARGIN: time::Duration = time::Duration::from_millis(2);
+
+    /// Create a limiter with the given target frame rate, or an inactive one if `None`.
+    pub fn new(target_fps: Option<u32>) -> Self {
+        let mut this = Self {
+            target_fps: None,
+            frame_start: time(),
+        };
+        this.set_target_fps(target_fps);
+        this
+    }
+
+    /// The frame rate being capped to, if any.
+    pub fn target_fps(&self) -> Option<u32> {
+        self.target_fps
+    }
+
+    /// Set the frame rate to cap to, or `None` to stop limiting.
+    pub fn set_target_fps(&mut self, target_fps: Option<u32>) {
+        assert_ne!(target_fps, Some(0), "target FPS must be positive");
+        self.target_fps = target_fps;
+    }
+
+    /// How long a frame which has taken `elapsed` so far still has to wait to last one whole frame
+    /// at `target_fps`.
+    pub fn sleep_duration(target_fps: u32, elapsed: time::Duration) -> time::Duration {
+        fps_as_duration(target_fps)
+            .checked_sub(elapsed)
+            .unwrap_or_default()
+    }
+
+    /// Wait until one whole frame has passed since the last call returned, and start timing the
+    /// next frame. Does nothing but restart the timing if there's no target frame rate.
+    pub fn wait(&mut self) {
+        if let Some(target_fps) = self.target_fps {
+            let remaining = Self::sleep_duration(target_fps, self.elapsed());
+            if remaining > Self::SPIN_MARGIN {
+                sleep(remaining - Self::SPIN_MARGIN);
+            }
+
+            while !Self::sleep_duration(target_fps, self.elapsed()).is_zero() {
+                yield_now();
+            }
+        }
+
+        self.frame_start = time();
+    }
+
+    fn elapsed(&self) -> time::Duration {
+        f64_to_duration((time() - self.frame_start).max(f64::EPSILON))
+    }
+}
+
 /// Pauses the current thread for the target duration.
 /// Just calls [`std::thread::sleep()`](https://doc.rust-lang.org/std/thread/fn.sleep.html)
 /// so it's as accurate as that is (which is usually not very).
@@ -346,3 +565,119 @@ fn fps_as_duration(fps: u32) -> time::Duration {
     let target_dt_seconds = 1.0 / f64::from(fps);
     f64_to_duration(target_dt_seconds)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_limiter_sleeps_for_the_rest_of_the_frame() {
+        let ms = time::Duration::from_millis;
+        let frame = fps_as_duration(60);
+        assert_eq!(FrameLimiter::sleep_duration(60, ms(10)), frame - ms(10));
+        assert_eq!(
+            FrameLimiter::sleep_duration(30, ms(10)),
+            fps_as_duration(30) - ms(10)
+        );
+        assert_eq!(FrameLimiter::sleep_duration(60, ms(0)), frame);
+
+        // Frames which already ran long don't wait at all.
+        assert_eq!(FrameLimiter::sleep_duration(60, frame), ms(0));
+        assert_eq!(FrameLimiter::sleep_duration(60, ms(50)), ms(0));
+    }
+
+    #[test]
+    fn long_frames_are_clamped_and_reported() {
+        let target_dt = fps_as_duration(60);
+        let mut timer = TimeContext::new();
+        timer.set_max_updates_per_frame(4);
+
+        timer.tick_by(time::Duration::from_secs(1));
+        let mut counter = 0;
+        while timer.check_update_time_forced(60, &mut counter) {}
+        assert_eq!(counter, 4);
+        assert_eq!(timer.updates_this_frame(), 4);
+        assert!(timer.is_behind());
+        assert_eq!(timer.clamped_frames(), 1);
+
+        // Everything past the four updates which ran is dropped, except for a partial step.
+        assert!(timer.remaining_update_time() < target_dt);
+        let accounted = timer.dropped_time() + timer.remaining_update_time() + target_dt * 4;
+        assert_eq!(accounted, time::Duration::from_secs(1));
+
+        // Checking again this frame doesn't count the frame twice.
+        assert!(!timer.check_update_time(60));
+        assert_eq!(timer.clamped_frames(), 1);
+
+        // The next frame starts caught up.
+        timer.tick_by(target_dt * 2 + time::Duration::from_millis(1));
+        let mut counter = 0;
+        while timer.check_update_time_forced(60, &mut counter) {}
+        assert!(!timer.is_behind());
+        assert_eq!(timer.updates_this_frame(), 2);
+        assert_eq!(timer.clamped_frames(), 1);
+    }
+
+    /// Drives a fixed timestep the way `hv-friends`' `SimpleHandler` does, recording what its Lua
+    /// hooks would be called with instead of calling them.
+    struct MockHandler {
+        timestep: FixedTimestep,
+        updates: Vec<f32>,
+        alphas: Vec<f32>,
+    }
+
+    impl MockHandler {
+        fn frame(&mut self, dt: f32) {
+            for _ in 0..self.timestep.advance(dt) {
+                self.updates.push(self.timestep.step());
+            }
+            self.alphas.push(self.timestep.alpha());
+        }
+    }
+
+    #[test]
+    fn forwarded_dt_is_the_fixed_step_and_alpha_stays_in_range() {
+        let step = 1. / 60.;
+        let mut handler = MockHandler {
+            timestep: FixedTimestep::new(step),
+            updates: Vec::new(),
+            alphas: Vec::new(),
+        };
+
+        let frames = [
+            1. / 60.,
+            1. / 144.,
+            1. / 30.,
+            0.013,
+            0.021,
+            1. / 60.,
+            0.5,
+            0.,
+        ];
+        for &dt in frames.iter().cycle().take(frames.len() * 10) {
+            handler.frame(dt);
+        }
+
+        assert!(handler.updates.iter().all(|&dt| dt == step));
+        assert!(handler
+            .alphas
+            .iter()
+            .all(|alpha| (0. ..=1.).contains(alpha)));
+
+        // Frames exactly one step long run exactly one update each.
+        handler.updates.clear();
+        for _ in 0..120 {
+            handler.frame(step);
+        }
+        assert_eq!(handler.updates.len(), 120);
+
+        // A long stall runs at most `max_steps` updates, then carries on as normal.
+        handler.updates.clear();
+        handler.frame(10.);
+        assert_eq!(
+            handler.updates.len(),
+            FixedTimestep::DEFAULT_MAX_STEPS as usize
+        );
+        assert!(handler.timestep.alpha() < 1.);
+    }
+}
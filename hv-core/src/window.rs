@@ -0,0 +1,210 @@
+//! Window state: title, icon, fullscreen, and the game's internal resolution.
+//!
+//! The [`Engine`] keeps a [`WindowState`] around so that the current window settings can be queried
+//! at any time, and so that the internal resolution the game was configured with survives
+//! fullscreen transitions. Rather than stretching the picture to fit whatever size the window ends
+//! up being, renderers can use [`WindowState::letterbox`] to find the largest region of the window
+//! with the internal resolution's aspect ratio.
+//!
+//! ## DPI
+//!
+//! On HiDPI displays, a logical pixel (the unit the OS sizes windows in) covers several physical
+//! pixels. By default the framebuffer is created at the logical size and upscaled by the OS; with
+//! [`Conf::high_dpi`] it's created at the physical size instead, so the game renders at full
+//! resolution. Either way, the engine reports window sizes and mouse and touch positions in
+//! logical pixels, and graphics viewports are specified in logical pixels too, so nothing else
+//! needs to care which mode the game is in. [`Engine::dpi_scale`] gives the number of physical
+//! pixels per logical pixel, for the places which do (such as egui's `pixels_per_point`.)
+
+use image::RgbaImage;
+use nalgebra::Point2;
+
+use crate::{
+    conf::Conf,
+    engine::Engine,
+    error::*,
+    mlua::prelude::*,
+    plugins::{ModuleWrapper, Plugin},
+};
+
+/// The current settings of the window, as last set through [`Conf`] or the setters on [`Engine`].
+#[derive(Debug, Clone)]
+pub struct WindowState {
+    title: String,
+    icon: Option<RgbaImage>,
+    fullscreen: bool,
+    resolution: (u32, u32),
+}
+
+impl WindowState {
+    /// Create the initial window state from a [`Conf`]. The configured window size is used as the
+    /// internal resolution.
+    pub fn from_conf(conf: &Conf) -> Self {
+        Self {
+            title: conf.window_title.clone(),
+            icon: conf.window_icon.clone(),
+            fullscreen: conf.fullscreen,
+            resolution: (conf.window_width, conf.window_height),
+        }
+    }
+
+    /// The window's title.
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// Set the stored window title. This doesn't touch the window itself; use
+    /// [`Engine::set_window_title`] for that.
+    pub fn set_title(&mut self, title: &str) {
+        self.title = title.to_owned();
+    }
+
+    /// The window's icon, if one has been set.
+    pub fn icon(&self) -> Option<&RgbaImage> {
+        self.icon.as_ref()
+    }
+
+    /// Set the stored window icon. This doesn't touch the window itself; use
+    /// [`Engine::set_window_icon`] for that.
+    pub fn set_icon(&mut self, icon: RgbaImage) {
+        self.icon = Some(icon);
+    }
+
+    /// Whether the window is fullscreen.
+    pub fn is_fullscreen(&self) -> bool {
+        self.fullscreen
+    }
+
+    /// Set the stored fullscreen flag. This doesn't touch the window itself; use
+    /// [`Engine::set_fullscreen`] for that.
+    pub fn set_fullscreen(&mut self, fullscreen: bool) {
+        self.fullscreen = fullscreen;
+    }
+
+    /// The internal resolution of the game, in pixels. This is the window size the game was
+    /// configured with, and does not change when the window is resized or goes fullscreen.
+    pub fn resolution(&self) -> (u32, u32) {
+        self.resolution
+    }
+
+    /// Compute the largest region of a window of the given size which has the same aspect ratio as
+    /// the internal resolution, centered in the window. The result is `(x, y, width, height)` in
+    /// window coordinates, with the origin at the top left.
+    pub fn letterbox(&self, screen_size: (f32, f32)) -> (f32, f32, f32, f32) {
+        let (screen_w, screen_h) = screen_size;
+        let (res_w, res_h) = (self.resolution.0 as f32, self.resolution.1 as f32);
+        let scale = (screen_w / res_w).min(screen_h / res_h);
+        let (w, h) = (res_w * scale, res_h * scale);
+        ((screen_w - w) / 2., (screen_h - h) / 2., w, h)
+    }
+}
+
+/// Convert a point in physical pixels, as reported by the platform, into logical pixels, given the
+/// number of physical pixels per logical pixel.
+pub fn physical_to_logical(physical: Point2<f32>, dpi_scale: f32) -> Point2<f32> {
+    physical / dpi_scale
+}
+
+/// Convert a point in logical pixels into physical pixels, given the number of physical pixels per
+/// logical pixel.
+pub fn logical_to_physical(logical: Point2<f32>, dpi_scale: f32) -> Point2<f32> {
+    logical * dpi_scale
+}
+
+struct WindowModule;
+
+impl Plugin for WindowModule {
+    fn name(&self) -> &'static str {
+        "window"
+    }
+
+    fn open<'lua>(&self, lua: &'lua Lua, engine: &Engine) -> Result<LuaTable<'lua>> {
+        let weak = engine.downgrade();
+        let set_fullscreen = lua.create_function(move |_, fullscreen: bool| {
+            weak.upgrade().set_fullscreen(fullscreen);
+            Ok(())
+        })?;
+
+        let weak = engine.downgrade();
+        let is_fullscreen =
+            lua.create_function(move |_, ()| Ok(weak.upgrade().window().is_fullscreen()))?;
+
+        let weak = engine.downgrade();
+        let set_title = lua.create_function(move |_, title: LuaString| {
+            weak.upgrade().set_window_title(title.to_str()?);
+            Ok(())
+        })?;
+
+        let weak = engine.downgrade();
+        let get_title =
+            lua.create_function(move |_, ()| Ok(weak.upgrade().window().title().to_owned()))?;
+
+        let weak = engine.downgrade();
+        let get_resolution =
+            lua.create_function(move |_, ()| Ok(weak.upgrade().window().resolution()))?;
+
+        let weak = engine.downgrade();
+        let set_target_fps = lua.create_function(move |_, fps: Option<u32>| {
+            if fps == Some(0) {
+                return Err(anyhow!("target FPS must be positive")).to_lua_err();
+            }
+            weak.upgrade().set_target_fps(fps);
+            Ok(())
+        })?;
+
+        let weak = engine.downgrade();
+        let get_target_fps = lua.create_function(move |_, ()| Ok(weak.upgrade().target_fps()))?;
+
+        Ok(lua
+            .load(mlua::chunk! {
+                {
+                    set_fullscreen = $set_fullscreen,
+                    is_fullscreen = $is_fullscreen,
+                    set_title = $set_title,
+                    get_title = $get_title,
+                    get_resolution = $get_resolution,
+                    set_target_fps = $set_target_fps,
+                    get_target_fps = $get_target_fps,
+                }
+            })
+            .eval()?)
+    }
+}
+
+inventory::submit!(ModuleWrapper::new(WindowModule));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conf_fields_and_setters() {
+        let conf = Conf {
+            window_title: "window test".to_owned(),
+            window_width: 320,
+            window_height: 240,
+            window_icon: Some(RgbaImage::new(16, 16)),
+            fullscreen: true,
+            ..Conf::default()
+        };
+
+        let mut window = WindowState::from_conf(&conf);
+        assert_eq!(window.title(), "window test");
+        assert_eq!(window.icon().map(|icon| icon.dimensions()), Some((16, 16)));
+        assert!(window.is_fullscreen());
+        assert_eq!(window.resolution(), (320, 240));
+
+        window.set_title("retitled");
+        window.set_icon(RgbaImage::new(32, 32));
+        window.set_fullscreen(false);
+        assert_eq!(window.title(), "retitled");
+        assert_eq!(window.icon().map(|icon| icon.dimensions()), Some((32, 32)));
+        assert!(!window.is_fullscreen());
+
+        // Going fullscreen on a 16:9 display keeps the 4:3 internal resolution, pillarboxed.
+        window.set_fullscreen(true);
+        assert_eq!(window.resolution(), (320, 240));
+        assert_eq!(window.letterbox((1920., 1080.)), (240., 0., 1440., 1080.));
+        assert_eq!(window.letterbox((320., 480.)), (0., 120., 320., 240.));
+    }
+}
@@ -0,0 +1,114 @@
+//! Access to the system clipboard, shared between [`Egui`](crate::Egui) and
+//! [`TextInput`](crate::text_input::TextInput).
+
+use hv_core::mq;
+
+#[cfg(target_os = "macos")] // https://github.com/not-fl3/miniquad/issues/172
+use copypasta::ClipboardProvider;
+
+/// Somewhere text can be copied to and pasted from.
+pub trait ClipboardAccess {
+    /// The text currently on the clipboard, if there is any and it could be read.
+    fn get(&mut self) -> Option<String>;
+
+    /// Replace the text on the clipboard.
+    fn set(&mut self, text: String);
+}
+
+/// An in-memory clipboard, for tests and for games which don't want to touch the system
+/// clipboard.
+impl ClipboardAccess for Option<String> {
+    fn get(&mut self) -> Option<String> {
+        self.clone()
+    }
+
+    fn set(&mut self, text: String) {
+        *self = Some(text);
+    }
+}
+
+/// The system clipboard. On most platforms this goes through miniquad, but miniquad has no
+/// clipboard support on macOS, so there it goes through `copypasta` instead.
+pub struct Clipboard {
+    #[cfg(target_os = "macos")]
+    inner: Option<copypasta::ClipboardContext>,
+}
+
+impl Default for Clipboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clipboard {
+    pub fn new() -> Self {
+        Self {
+            #[cfg(target_os = "macos")]
+            inner: match copypasta::ClipboardContext::new() {
+                Ok(clipboard) => Some(clipboard),
+                Err(err) => {
+                    eprintln!("Failed to initialize clipboard: {}", err);
+                    None
+                }
+            },
+        }
+    }
+
+    /// Borrow the clipboard along with the miniquad context it needs, as a [`ClipboardAccess`].
+    pub fn access<'a>(&'a mut self, mq: &'a mut mq::Context) -> SystemClipboard<'a> {
+        SystemClipboard {
+            clipboard: self,
+            mq,
+        }
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    pub fn set(&mut self, mq: &mut mq::Context, text: String) {
+        mq::clipboard::set(mq, text.as_str());
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    pub fn get(&mut self, mq: &mut mq::Context) -> Option<String> {
+        mq::clipboard::get(mq)
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn set(&mut self, _mq: &mut mq::Context, text: String) {
+        if let Some(clipboard) = &mut self.inner {
+            if let Err(err) = clipboard.set_contents(text) {
+                eprintln!("Copy/Cut error: {}", err);
+            }
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn get(&mut self, _mq: &mut mq::Context) -> Option<String> {
+        if let Some(clipboard) = &mut self.inner {
+            match clipboard.get_contents() {
+                Ok(contents) => Some(contents),
+                Err(err) => {
+                    eprintln!("Paste error: {}", err);
+                    None
+                }
+            }
+        } else {
+            None
+        }
+    }
+}
+
+/// The system [`Clipboard`], borrowed with [`Clipboard::access`].
+pub struct SystemClipboard<'a> {
+    clipboard: &'a mut Clipboard,
+    mq: &'a mut mq::Context,
+}
+
+impl<'a> ClipboardAccess for SystemClipboard<'a> {
+    fn get(&mut self) -> Option<String> {
+        self.clipboard.get(self.mq)
+    }
+
+    fn set(&mut self, text: String) {
+        self.clipboard.set(self.mq, text)
+    }
+}
@@ -30,27 +30,31 @@ DEALINGS IN THE SOFTWARE.
 
 pub extern crate egui;
 
+pub mod clipboard;
 mod input;
+pub mod memory;
 mod painter;
+pub mod text_input;
 
 use egui::CursorIcon;
 use hv_core::{
     engine::{Engine, LuaExt, LuaResource},
-    input::{KeyCode, KeyMods, MouseButton},
+    input::{KeyCode, KeyMods, MouseButton, VirtualCursorEvent},
     mq,
+    plugins::Plugin,
     prelude::*,
 };
 
-#[cfg(target_os = "macos")] // https://github.com/not-fl3/miniquad/issues/172
-use copypasta::ClipboardProvider;
+use std::io::{Read, Write};
+
+use crate::clipboard::Clipboard;
 
 pub struct Egui {
     egui_ctx: egui::CtxRef,
     egui_ctx_resource: Shared<egui::CtxRef>,
     egui_input: egui::RawInput,
     painter: painter::Painter,
-    #[cfg(target_os = "macos")]
-    clipboard: Option<copypasta::ClipboardContext>,
+    clipboard: Clipboard,
     shapes: Option<Vec<egui::epaint::ClippedShape>>,
 }
 
@@ -63,16 +67,21 @@ impl Egui {
         let egui_ctx = egui::CtxRef::default();
         let egui_ctx_resource = engine.insert(egui_ctx.clone());
 
-        let this = Self {
+        let mut this = Self {
             egui_ctx,
             egui_ctx_resource,
             painter: painter::Painter::new(engine),
             egui_input: Default::default(),
-            #[cfg(target_os = "macos")]
-            clipboard: init_clipboard(),
+            clipboard: Clipboard::new(),
             shapes: None,
         };
 
+        // A missing or unreadable memory file shouldn't stop the UI from starting; it'll just
+        // start with the default layout.
+        if let Err(err) = this.load_memory_from_filesystem(engine) {
+            eprintln!("Failed to load egui memory: {:#}", err);
+        }
+
         let resource = engine.insert(this);
         engine.lua().insert_resource(resource.clone())?;
 
@@ -85,6 +94,46 @@ impl Egui {
         &self.egui_ctx
     }
 
+    /// Serialize egui's memory: window positions and sizes, collapsed states, and so on.
+    pub fn save_memory(&self) -> Result<Vec<u8>> {
+        memory::serialize(&self.egui_ctx.memory())
+    }
+
+    /// Replace egui's memory with memory previously produced by [`Self::save_memory`]. If the bytes
+    /// can't be parsed, an error is returned and the current memory is left untouched.
+    pub fn load_memory(&mut self, bytes: &[u8]) -> Result<()> {
+        let memory = memory::deserialize(bytes)?;
+        *self.egui_ctx.memory() = memory;
+        Ok(())
+    }
+
+    /// Save egui's memory to [`memory::MEMORY_PATH`] in the engine filesystem. This is a good
+    /// thing to call from [`EventHandler::quit_requested_event`].
+    ///
+    /// [`EventHandler::quit_requested_event`]: hv_core::engine::EventHandler::quit_requested_event
+    pub fn save_memory_to_filesystem(&self, engine: &Engine) -> Result<()> {
+        let bytes = self.save_memory()?;
+        engine.fs().create(memory::MEMORY_PATH)?.write_all(&bytes)?;
+        Ok(())
+    }
+
+    /// Load egui's memory from [`memory::MEMORY_PATH`] in the engine filesystem, if it exists.
+    /// Returns whether anything was loaded. This is done automatically by [`Self::new`].
+    pub fn load_memory_from_filesystem(&mut self, engine: &Engine) -> Result<bool> {
+        let mut bytes = Vec::new();
+        {
+            let mut fs = engine.fs();
+            if !fs.is_file(memory::MEMORY_PATH) {
+                return Ok(false);
+            }
+
+            fs.open(memory::MEMORY_PATH)?.read_to_end(&mut bytes)?;
+        }
+
+        self.load_memory(&bytes)?;
+        Ok(true)
+    }
+
     /// Call this at the start of each `draw` call.
     pub fn begin_frame(&mut self, engine: &Engine) {
         let mq = &engine.mq();
@@ -93,6 +142,38 @@ impl Egui {
         *self.egui_ctx_resource.borrow_mut() = self.egui_ctx.clone();
     }
 
+    /// Whether egui is currently using keyboard input, for example because a text field has focus.
+    /// When this is true, the game should ignore keyboard input so that typing into a text field
+    /// doesn't also move the player around.
+    ///
+    /// This reflects the state of the UI as of the most recent [`Self::begin_frame`], so it's
+    /// accurate for input events received during the current frame.
+    ///
+    /// ```no_run
+    /// # use hv_core::{engine::Engine, input::{KeyCode, KeyMods}};
+    /// # use hv_egui::Egui;
+    /// fn key_down_event(egui: &mut Egui, engine: &Engine, keycode: KeyCode, keymods: KeyMods) {
+    ///     // Always forward input to egui, so that it can tell when it loses focus.
+    ///     egui.key_down_event(engine, keycode, keymods);
+    ///
+    ///     if egui.wants_keyboard_input() {
+    ///         return;
+    ///     }
+    ///
+    ///     // ... game input handling goes here ...
+    /// }
+    /// ```
+    pub fn wants_keyboard_input(&self) -> bool {
+        self.egui_ctx.wants_keyboard_input()
+    }
+
+    /// Whether egui is currently using pointer input, for example because the mouse is over a
+    /// window or a widget is being dragged. Like [`Self::wants_keyboard_input`], this reflects the
+    /// state of the UI as of the most recent [`Self::begin_frame`].
+    pub fn wants_pointer_input(&self) -> bool {
+        self.egui_ctx.wants_pointer_input()
+    }
+
     /// Call this at the end of each `draw` call.
     /// This will draw the `egui` interface.
     pub fn end_frame(&mut self, engine: &Engine) {
@@ -130,7 +211,7 @@ impl Egui {
         }
 
         if !copied_text.is_empty() {
-            self.set_clipboard(mq, copied_text);
+            self.clipboard.set(mq, copied_text);
         }
     }
 
@@ -146,9 +227,10 @@ impl Egui {
         }
     }
 
-    pub fn mouse_motion_event(&mut self, engine: &Engine, x: f32, y: f32) {
-        let mq = &engine.mq();
-        let pos = egui::pos2(x as f32 / mq.dpi_scale(), y as f32 / mq.dpi_scale());
+    /// Forward a mouse motion event. Like every position the engine passes to an event handler, `x`
+    /// and `y` are in logical pixels, which are the same as egui's points.
+    pub fn mouse_motion_event(&mut self, _engine: &Engine, x: f32, y: f32) {
+        let pos = egui::pos2(x, y);
         self.egui_input.events.push(egui::Event::PointerMoved(pos))
     }
 
@@ -163,9 +245,8 @@ impl Egui {
         }
     }
 
-    pub fn mouse_button_down_event(&mut self, engine: &Engine, mb: MouseButton, x: f32, y: f32) {
-        let mq = &engine.mq();
-        let pos = egui::pos2(x as f32 / mq.dpi_scale(), y as f32 / mq.dpi_scale());
+    pub fn mouse_button_down_event(&mut self, _engine: &Engine, mb: MouseButton, x: f32, y: f32) {
+        let pos = egui::pos2(x, y);
         let button = to_egui_button(mb);
         self.egui_input.events.push(egui::Event::PointerButton {
             pos,
@@ -175,9 +256,8 @@ impl Egui {
         })
     }
 
-    pub fn mouse_button_up_event(&mut self, engine: &Engine, mb: MouseButton, x: f32, y: f32) {
-        let mq = &engine.mq();
-        let pos = egui::pos2(x as f32 / mq.dpi_scale(), y as f32 / mq.dpi_scale());
+    pub fn mouse_button_up_event(&mut self, _engine: &Engine, mb: MouseButton, x: f32, y: f32) {
+        let pos = egui::pos2(x, y);
         let button = to_egui_button(mb);
 
         self.egui_input.events.push(egui::Event::PointerButton {
@@ -188,6 +268,24 @@ impl Egui {
         })
     }
 
+    /// Forward a pointer event synthesized by an [`InputState`](hv_core::input::InputState)'s
+    /// virtual cursor, so that egui treats it exactly like the mouse. Call this for every event
+    /// returned by `take_virtual_cursor_events` after updating the input state. The virtual
+    /// cursor's button acts as the primary mouse button.
+    pub fn virtual_cursor_event(&mut self, engine: &Engine, event: VirtualCursorEvent) {
+        match event {
+            VirtualCursorEvent::Moved(pos) => self.mouse_motion_event(engine, pos.x, pos.y),
+            VirtualCursorEvent::Button {
+                pressed: true,
+                position,
+            } => self.mouse_button_down_event(engine, MouseButton::Left, position.x, position.y),
+            VirtualCursorEvent::Button {
+                pressed: false,
+                position,
+            } => self.mouse_button_up_event(engine, MouseButton::Left, position.x, position.y),
+        }
+    }
+
     pub fn char_event(&mut self, chr: char) {
         if input::is_printable_char(chr)
             && !self.egui_input.modifiers.ctrl
@@ -209,7 +307,7 @@ impl Egui {
             self.egui_input.events.push(egui::Event::Copy);
         } else if modifiers.command && keycode == KeyCode::V {
             let mq = &mut engine.mq();
-            if let Some(text) = self.get_clipboard(mq) {
+            if let Some(text) = self.clipboard.get(mq) {
                 self.egui_input.events.push(egui::Event::Text(text));
             }
         } else if let Some(key) = input::egui_key_from_hv_key(keycode) {
@@ -232,40 +330,6 @@ impl Egui {
             })
         }
     }
-
-    #[cfg(not(target_os = "macos"))]
-    fn set_clipboard(&mut self, mq: &mut mq::Context, text: String) {
-        mq::clipboard::set(mq, text.as_str());
-    }
-
-    #[cfg(not(target_os = "macos"))]
-    fn get_clipboard(&mut self, mq: &mut mq::Context) -> Option<String> {
-        mq::clipboard::get(mq)
-    }
-
-    #[cfg(target_os = "macos")]
-    fn set_clipboard(&mut self, _mq: &mut mq::Context, text: String) {
-        if let Some(clipboard) = &mut self.clipboard {
-            if let Err(err) = clipboard.set_contents(text) {
-                eprintln!("Copy/Cut error: {}", err);
-            }
-        }
-    }
-
-    #[cfg(target_os = "macos")]
-    fn get_clipboard(&mut self, _mq: &mut mq::Context) -> Option<String> {
-        if let Some(clipboard) = &mut self.clipboard {
-            match clipboard.get_contents() {
-                Ok(contents) => Some(contents),
-                Err(err) => {
-                    eprintln!("Paste error: {}", err);
-                    None
-                }
-            }
-        } else {
-            None
-        }
-    }
 }
 
 impl LuaResource for Egui {
@@ -274,17 +338,43 @@ impl LuaResource for Egui {
 
 impl LuaUserData for Egui {}
 
-#[cfg(target_os = "macos")]
-fn init_clipboard() -> Option<copypasta::ClipboardContext> {
-    match copypasta::ClipboardContext::new() {
-        Ok(clipboard) => Some(clipboard),
-        Err(err) => {
-            eprintln!("Failed to initialize clipboard: {}", err);
-            None
-        }
+struct HvEguiPlugin;
+
+impl Plugin for HvEguiPlugin {
+    fn name(&self) -> &'static str {
+        "egui"
+    }
+
+    fn open<'lua>(&self, lua: &'lua Lua, _engine: &Engine) -> Result<LuaTable<'lua>> {
+        // If egui hasn't been set up, it can't be capturing any input.
+        let wants_keyboard_input = lua.create_function(|lua, ()| {
+            Ok(lua
+                .get_resource::<Egui>()
+                .map_or(false, |egui| egui.borrow().wants_keyboard_input()))
+        })?;
+
+        let wants_pointer_input = lua.create_function(|lua, ()| {
+            Ok(lua
+                .get_resource::<Egui>()
+                .map_or(false, |egui| egui.borrow().wants_pointer_input()))
+        })?;
+
+        Ok(lua
+            .load(mlua::chunk! {
+                {
+                    wants_keyboard_input = $wants_keyboard_input,
+                    wants_pointer_input = $wants_pointer_input,
+                }
+            })
+            .eval()?)
     }
 }
 
+hv_core::plugin!(HvEguiPlugin);
+
+#[doc(hidden)]
+pub fn link_me() {}
+
 fn to_egui_button(mb: MouseButton) -> egui::PointerButton {
     match mb {
         MouseButton::Left => egui::PointerButton::Primary,
@@ -330,3 +420,70 @@ fn to_mq_cursor_icon(cursor_icon: egui::CursorIcon) -> Option<mq::CursorIcon> {
         | egui::CursorIcon::ZoomOut => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hv_core::window::{logical_to_physical, physical_to_logical};
+    use hv_friends::{
+        graphics::{screen_to_world_point2, YAxis},
+        math::*,
+    };
+
+    #[test]
+    fn egui_and_graphics_agree_on_the_pointer_position() {
+        // An 800x600 (logical) window on a display with two physical pixels per logical pixel.
+        let dpi_scale = 2.;
+        let physical = Point2::new(500., 300.);
+        let logical = physical_to_logical(physical, dpi_scale);
+        assert_eq!(logical, Point2::new(250., 150.));
+        assert_eq!(logical_to_physical(logical, dpi_scale), physical);
+
+        let mut ctx = egui::CtxRef::default();
+        let mut input = egui::RawInput {
+            screen_rect: Some(egui::Rect::from_min_size(
+                Default::default(),
+                egui::vec2(800., 600.),
+            )),
+            pixels_per_point: Some(dpi_scale),
+            ..Default::default()
+        };
+        input
+            .events
+            .push(egui::Event::PointerMoved(egui::pos2(logical.x, logical.y)));
+        ctx.begin_frame(input);
+        let egui_pos = ctx.input().pointer.hover_pos().unwrap();
+        let _ = ctx.end_frame();
+
+        // A default projection over the whole window, with the origin at the top left like egui.
+        let world = screen_to_world_point2(
+            &YAxis::Down.orthographic(800., 600.),
+            &Box2::new(0., 0., 800., 600.),
+            logical,
+        );
+        assert!((world - Point2::new(egui_pos.x, egui_pos.y)).norm() < 1e-3);
+    }
+
+    #[test]
+    fn focused_text_edit_wants_keyboard_after_begin_frame() {
+        let mut ctx = egui::CtxRef::default();
+        let mut text = String::new();
+
+        ctx.begin_frame(egui::RawInput::default());
+        assert!(!ctx.wants_keyboard_input());
+        egui::CentralPanel::default().show(&ctx, |ui| {
+            ui.text_edit_singleline(&mut text).request_focus();
+        });
+        let _ = ctx.end_frame();
+
+        ctx.begin_frame(egui::RawInput::default());
+        assert!(ctx.wants_keyboard_input());
+        egui::CentralPanel::default().show(&ctx, |ui| {
+            ui.text_edit_singleline(&mut text).surrender_focus();
+        });
+        let _ = ctx.end_frame();
+
+        ctx.begin_frame(egui::RawInput::default());
+        assert!(!ctx.wants_keyboard_input());
+    }
+}
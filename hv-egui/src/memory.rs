@@ -0,0 +1,45 @@
+//! Saving and loading egui's memory (window positions, collapsed headers, and so on) so that the
+//! layout of the UI survives restarts.
+//!
+//! Memory is stored as JSON. egui fills in any fields missing from the saved memory with their
+//! defaults and ignores unknown ones, so memory saved by an older or newer version of egui will
+//! still load as long as it's well-formed.
+
+use hv_core::prelude::*;
+
+/// The path in the engine filesystem where [`Egui`](crate::Egui) saves its memory.
+pub const MEMORY_PATH: &str = "/egui_memory.json";
+
+pub(crate) fn serialize(memory: &egui::Memory) -> Result<Vec<u8>> {
+    Ok(serde_json::to_vec(memory)?)
+}
+
+pub(crate) fn deserialize(bytes: &[u8]) -> Result<egui::Memory> {
+    serde_json::from_slice(bytes).context("failed to parse saved egui memory")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_roundtrips() {
+        let mut ctx = egui::CtxRef::default();
+        ctx.begin_frame(egui::RawInput::default());
+        egui::Window::new("Inspector")
+            .default_pos(egui::pos2(64., 32.))
+            .show(&ctx, |ui| {
+                ui.collapsing("Details", |ui| ui.label("hello"));
+            });
+        let _ = ctx.end_frame();
+
+        let bytes = serialize(&ctx.memory()).unwrap();
+        let restored = deserialize(&bytes).unwrap();
+        assert_eq!(serialize(&restored).unwrap(), bytes);
+
+        // A blob from some other version of egui, with fields this one doesn't know about, still
+        // loads; garbage is an error rather than a crash.
+        assert!(deserialize(br#"{"from_the_future": true}"#).is_ok());
+        assert!(deserialize(b"\x00\x01 not json").is_err());
+    }
+}
@@ -0,0 +1,384 @@
+//! Single-line text editing for custom-rendered text fields, without going through egui.
+//!
+//! A [`TextInput`] turns the character and key events an
+//! [`EventHandler`](hv_core::engine::EventHandler) receives into an edited string, with a cursor,
+//! a selection, and copy/cut/paste through a [`ClipboardAccess`] (usually the system
+//! [`Clipboard`](crate::clipboard::Clipboard).) It doesn't draw anything; render
+//! [`TextInput::text`] however suits the game, using [`TextInput::cursor`] and
+//! [`TextInput::selection`] to place the caret and highlight.
+//!
+//! Forward every `char_event` to [`TextInput::char_event`] and every `key_down_event` to
+//! [`TextInput::key_down_event`] while the field has focus. Key repeats should be forwarded like
+//! any other key press, so that holding backspace keeps deleting. miniquad doesn't report IME
+//! composition, so composed characters arrive as ordinary `char_event`s once they're committed.
+
+use hv_core::input::{KeyCode, KeyMods};
+
+use crate::{clipboard::ClipboardAccess, input::is_printable_char};
+
+/// Something a key press did to a [`TextInput`] which the game might want to react to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextInputEvent {
+    /// The text was edited.
+    Changed,
+    /// Enter was pressed.
+    Submitted,
+}
+
+/// An editable single line of text with a cursor and selection. Positions are byte offsets into
+/// [`TextInput::text`], always on `char` boundaries.
+#[derive(Debug, Clone, Default)]
+pub struct TextInput {
+    text: String,
+    cursor: usize,
+    // The other end of the selection from the cursor, if anything is selected
+    anchor: Option<usize>,
+    max_chars: Option<usize>,
+}
+
+/// On macOS, shortcuts use the command key rather than control.
+fn command(keymods: KeyMods) -> bool {
+    if cfg!(target_os = "macos") {
+        keymods.logo
+    } else {
+        keymods.ctrl
+    }
+}
+
+impl TextInput {
+    /// An empty text input, with no length limit.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A text input containing `text`, with the cursor at the end.
+    pub fn with_text(text: impl Into<String>) -> Self {
+        let mut this = Self::new();
+        this.set_text(text);
+        this
+    }
+
+    /// The current text.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Replace the text, moving the cursor to the end and clearing the selection.
+    pub fn set_text(&mut self, text: impl Into<String>) {
+        self.text = text.into();
+        self.truncate_to_max();
+        self.cursor = self.text.len();
+        self.anchor = None;
+    }
+
+    /// The most characters the text may hold, if it's limited.
+    pub fn max_chars(&self) -> Option<usize> {
+        self.max_chars
+    }
+
+    /// Limit the number of characters the text may hold. Input which would go over the limit is
+    /// cut short; text already over it is truncated.
+    pub fn set_max_chars(&mut self, max_chars: Option<usize>) {
+        self.max_chars = max_chars;
+        self.truncate_to_max();
+        self.cursor = self.cursor.min(self.text.len());
+        self.anchor = self.anchor.map(|anchor| anchor.min(self.text.len()));
+    }
+
+    /// The byte offset of the cursor.
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// The byte range of the selected text, if any.
+    pub fn selection(&self) -> Option<std::ops::Range<usize>> {
+        let anchor = self.anchor.filter(|&anchor| anchor != self.cursor)?;
+        Some(anchor.min(self.cursor)..anchor.max(self.cursor))
+    }
+
+    /// The selected text, or `""` if nothing is selected.
+    pub fn selected_text(&self) -> &str {
+        self.selection().map_or("", |range| &self.text[range])
+    }
+
+    /// Select the whole text.
+    pub fn select_all(&mut self) {
+        self.anchor = Some(0);
+        self.cursor = self.text.len();
+    }
+
+    /// Handle a character typed by the user. Control characters (including the ones miniquad sends
+    /// for backspace, enter and so on) and characters typed while holding the command modifier are
+    /// ignored, since the matching key events are handled by [`TextInput::key_down_event`].
+    /// Returns whether the text changed.
+    pub fn char_event(&mut self, character: char, keymods: KeyMods) -> bool {
+        if !is_printable_char(character) || command(keymods) {
+            return false;
+        }
+
+        let mut buf = [0; 4];
+        self.insert(character.encode_utf8(&mut buf))
+    }
+
+    /// Handle a key press: editing, cursor movement, selection, and the clipboard shortcuts.
+    /// Keys which don't mean anything to a text field are ignored.
+    pub fn key_down_event(
+        &mut self,
+        keycode: KeyCode,
+        keymods: KeyMods,
+        clipboard: &mut dyn ClipboardAccess,
+    ) -> Option<TextInputEvent> {
+        let word = command(keymods) || keymods.alt;
+        let changed = match keycode {
+            KeyCode::Backspace => {
+                if self.selection().is_none() {
+                    self.anchor = Some(self.prev_boundary(self.cursor, word));
+                }
+                self.delete_selection()
+            }
+            KeyCode::Delete => {
+                if self.selection().is_none() {
+                    self.anchor = Some(self.next_boundary(self.cursor, word));
+                }
+                self.delete_selection()
+            }
+            KeyCode::Left => {
+                let to = match self.selection() {
+                    Some(range) if !keymods.shift => range.start,
+                    _ => self.prev_boundary(self.cursor, word),
+                };
+                self.move_cursor(to, keymods.shift);
+                false
+            }
+            KeyCode::Right => {
+                let to = match self.selection() {
+                    Some(range) if !keymods.shift => range.end,
+                    _ => self.next_boundary(self.cursor, word),
+                };
+                self.move_cursor(to, keymods.shift);
+                false
+            }
+            KeyCode::Home | KeyCode::Up => {
+                self.move_cursor(0, keymods.shift);
+                false
+            }
+            KeyCode::End | KeyCode::Down => {
+                self.move_cursor(self.text.len(), keymods.shift);
+                false
+            }
+            KeyCode::Enter | KeyCode::KpEnter => return Some(TextInputEvent::Submitted),
+            KeyCode::A if command(keymods) => {
+                self.select_all();
+                false
+            }
+            KeyCode::C if command(keymods) => {
+                if self.selection().is_some() {
+                    clipboard.set(self.selected_text().to_owned());
+                }
+                false
+            }
+            KeyCode::X if command(keymods) => {
+                if self.selection().is_some() {
+                    clipboard.set(self.selected_text().to_owned());
+                }
+                self.delete_selection()
+            }
+            KeyCode::V if command(keymods) => match clipboard.get() {
+                Some(pasted) => self.paste(&pasted),
+                None => false,
+            },
+            _ => false,
+        };
+
+        if changed {
+            Some(TextInputEvent::Changed)
+        } else {
+            None
+        }
+    }
+
+    /// Insert text as though it had been pasted, replacing the selection. Since the field is a
+    /// single line, line breaks and tabs become spaces and any other non-printable characters are
+    /// dropped. Returns whether the text changed.
+    pub fn paste(&mut self, pasted: &str) -> bool {
+        let crlf = pasted.contains("\r\n");
+        let cleaned = pasted
+            .chars()
+            .filter_map(|c| match c {
+                '\r' if crlf => None,
+                '\n' | '\r' | '\t' => Some(' '),
+                c if is_printable_char(c) => Some(c),
+                _ => None,
+            })
+            .collect::<String>();
+        self.insert(&cleaned)
+    }
+
+    fn insert(&mut self, inserted: &str) -> bool {
+        let deleted = self.delete_selection();
+
+        let inserted = match self.max_chars {
+            Some(max_chars) => {
+                let room = max_chars.saturating_sub(self.text.chars().count());
+                let end = inserted
+                    .char_indices()
+                    .nth(room)
+                    .map_or(inserted.len(), |(i, _)| i);
+                &inserted[..end]
+            }
+            None => inserted,
+        };
+
+        self.text.insert_str(self.cursor, inserted);
+        self.cursor += inserted.len();
+        deleted || !inserted.is_empty()
+    }
+
+    fn delete_selection(&mut self) -> bool {
+        let range = self.selection();
+        self.anchor = None;
+        match range {
+            Some(range) => {
+                self.cursor = range.start;
+                self.text.replace_range(range, "");
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn move_cursor(&mut self, to: usize, extend_selection: bool) {
+        if extend_selection {
+            self.anchor.get_or_insert(self.cursor);
+        } else {
+            self.anchor = None;
+        }
+        self.cursor = to;
+    }
+
+    fn truncate_to_max(&mut self) {
+        if let Some((end, _)) = self
+            .max_chars
+            .and_then(|max_chars| self.text.char_indices().nth(max_chars))
+        {
+            self.text.truncate(end);
+        }
+    }
+
+    /// The position one character before `from`, or the start of the previous word.
+    fn prev_boundary(&self, from: usize, word: bool) -> usize {
+        let mut chars = self.text[..from].char_indices().rev().peekable();
+        if !word {
+            return chars.next().map_or(0, |(i, _)| i);
+        }
+
+        while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+        let mut start = chars.peek().map_or(0, |&(i, _)| i);
+        while let Some((i, _)) = chars.next_if(|(_, c)| !c.is_whitespace()) {
+            start = i;
+        }
+        start
+    }
+
+    /// The position one character after `from`, or the end of the next word.
+    fn next_boundary(&self, from: usize, word: bool) -> usize {
+        let mut chars = self.text[from..]
+            .char_indices()
+            .map(|(i, c)| (from + i + c.len_utf8(), c))
+            .peekable();
+        if !word {
+            return chars.next().map_or(from, |(end, _)| end);
+        }
+
+        let mut end = from;
+        while let Some((i, _)) = chars.next_if(|(_, c)| c.is_whitespace()) {
+            end = i;
+        }
+        while let Some((i, _)) = chars.next_if(|(_, c)| !c.is_whitespace()) {
+            end = i;
+        }
+        end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mods(shift: bool, command: bool) -> KeyMods {
+        KeyMods {
+            shift,
+            ctrl: command && !cfg!(target_os = "macos"),
+            alt: false,
+            logo: command && cfg!(target_os = "macos"),
+        }
+    }
+
+    #[test]
+    fn typing_and_backspacing_edits_the_text() {
+        let mut clipboard = None;
+        let mut input = TextInput::new();
+        let none = KeyMods::default();
+
+        for c in "héllo".chars() {
+            assert!(input.char_event(c, none));
+        }
+        // miniquad sends backspace as a character as well as a key press; only the key counts.
+        assert!(!input.char_event('\u{8}', none));
+        assert!(!input.char_event('\u{f700}', none));
+        let backspace = input.key_down_event(KeyCode::Backspace, none, &mut clipboard);
+        assert_eq!(backspace, Some(TextInputEvent::Changed));
+        input.key_down_event(KeyCode::Backspace, none, &mut clipboard);
+        assert_eq!(input.text(), "hél");
+
+        // Move back over the multibyte character and type in front of it.
+        input.key_down_event(KeyCode::Left, none, &mut clipboard);
+        input.key_down_event(KeyCode::Left, none, &mut clipboard);
+        input.char_event('e', none);
+        assert_eq!(input.text(), "heél");
+        input.key_down_event(KeyCode::Delete, none, &mut clipboard);
+        assert_eq!(input.text(), "hel");
+        assert_eq!(&input.text()[..input.cursor()], "he");
+
+        // Backspacing at the start does nothing.
+        input.key_down_event(KeyCode::Home, none, &mut clipboard);
+        assert_eq!(
+            input.key_down_event(KeyCode::Backspace, none, &mut clipboard),
+            None
+        );
+        assert_eq!(
+            input.key_down_event(KeyCode::Enter, none, &mut clipboard),
+            Some(TextInputEvent::Submitted)
+        );
+        assert_eq!(input.text(), "hel");
+    }
+
+    #[test]
+    fn selection_and_clipboard_shortcuts() {
+        let mut clipboard = None;
+        let mut input = TextInput::with_text("save one");
+        assert!(!input.char_event('a', mods(false, true)));
+
+        // Select "one" word-wise and cut it.
+        input.key_down_event(KeyCode::Left, mods(true, true), &mut clipboard);
+        assert_eq!(input.selected_text(), "one");
+        input.key_down_event(KeyCode::X, mods(false, true), &mut clipboard);
+        assert_eq!(input.text(), "save ");
+        assert_eq!(clipboard.as_deref(), Some("one"));
+
+        // Pasting strips line breaks and control characters.
+        clipboard = Some("two\r\nthree\u{7}".to_owned());
+        input.key_down_event(KeyCode::V, mods(false, true), &mut clipboard);
+        assert_eq!(input.text(), "save two three");
+
+        // Typing replaces the selection, and the length limit cuts input short.
+        input.key_down_event(KeyCode::A, mods(false, true), &mut clipboard);
+        input.key_down_event(KeyCode::C, mods(false, true), &mut clipboard);
+        assert_eq!(clipboard.as_deref(), Some("save two three"));
+        input.set_max_chars(Some(4));
+        input.char_event('x', KeyMods::default());
+        assert_eq!(input.text(), "x");
+        assert!(input.paste("yzzy"));
+        assert_eq!(input.text(), "xyzz");
+    }
+}
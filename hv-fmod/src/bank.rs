@@ -1,5 +1,12 @@
-use crate::{event::EventDescription, CheckError};
-use {hv_core::prelude::*, hv_fmod_sys::*, std::ptr};
+use crate::{
+    bus::{Bus, Vca},
+    event::EventDescription,
+    CheckError,
+};
+use {
+    enum_primitive_derive::*, hv_core::prelude::*, hv_fmod_sys::*, num_traits::FromPrimitive,
+    std::ptr,
+};
 
 bitflags::bitflags! {
     pub struct LoadBankFlags: u32 {
@@ -24,6 +31,47 @@ impl<'lua> FromLua<'lua> for LoadBankFlags {
     }
 }
 
+/// The loading state of a bank's metadata or sample data.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Primitive)]
+#[repr(i32)]
+pub enum LoadingState {
+    Unloading = FMOD_STUDIO_LOADING_STATE_FMOD_STUDIO_LOADING_STATE_UNLOADING as i32,
+    Unloaded = FMOD_STUDIO_LOADING_STATE_FMOD_STUDIO_LOADING_STATE_UNLOADED as i32,
+    Loading = FMOD_STUDIO_LOADING_STATE_FMOD_STUDIO_LOADING_STATE_LOADING as i32,
+    Loaded = FMOD_STUDIO_LOADING_STATE_FMOD_STUDIO_LOADING_STATE_LOADED as i32,
+    Error = FMOD_STUDIO_LOADING_STATE_FMOD_STUDIO_LOADING_STATE_ERROR as i32,
+}
+
+impl LoadingState {
+    /// Interpret the state and result code produced by one of FMOD's `Get*LoadingState`
+    /// functions. When loading has failed, FMOD reports the state as an error *and* returns the
+    /// error which caused the failure; that error is logged and `Error` is returned rather than
+    /// failing the query itself.
+    fn from_raw(state: FMOD_STUDIO_LOADING_STATE, result: FMOD_RESULT) -> Result<Self> {
+        let state =
+            Self::from_i32(state as i32).ok_or_else(|| anyhow!("bad loading state {}", state))?;
+
+        match result.check_err() {
+            Ok(()) => Ok(state),
+            Err(err) if state == LoadingState::Error => {
+                log::error!("error while loading bank: {}", err);
+                Ok(state)
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LoadingState::Unloading => "unloading",
+            LoadingState::Unloaded => "unloaded",
+            LoadingState::Loading => "loading",
+            LoadingState::Loaded => "loaded",
+            LoadingState::Error => "error",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 #[repr(C)]
 pub struct Bank {
@@ -42,6 +90,18 @@ impl Bank {
         unsafe { FMOD_Studio_Bank_IsValid(self.ptr) != 0 }
     }
 
+    /// The loading state of the bank's metadata. Banks loaded with
+    /// [`LoadBankFlags::NONBLOCKING`] can't be used to look up events until this is
+    /// [`LoadingState::Loaded`]. Loading progresses asynchronously, or during `Fmod::update` if the
+    /// system was initialized with `LOAD_FROM_UPDATE`.
+    pub fn get_loading_state(&self) -> Result<LoadingState> {
+        let mut state = 0;
+        let result = unsafe { FMOD_Studio_Bank_GetLoadingState(self.ptr, &mut state) };
+        LoadingState::from_raw(state, result)
+    }
+
+    /// Start loading all of the bank's non-streaming sample data. This is asynchronous; use
+    /// [`Bank::get_sample_loading_state`] to find out when it has finished.
     pub fn load_sample_data(&self) -> Result<()> {
         unsafe {
             FMOD_Studio_Bank_LoadSampleData(self.ptr).check_err()?;
@@ -49,6 +109,13 @@ impl Bank {
         Ok(())
     }
 
+    /// The loading state of the sample data requested through [`Bank::load_sample_data`].
+    pub fn get_sample_loading_state(&self) -> Result<LoadingState> {
+        let mut state = 0;
+        let result = unsafe { FMOD_Studio_Bank_GetSampleLoadingState(self.ptr, &mut state) };
+        LoadingState::from_raw(state, result)
+    }
+
     pub fn unload_sample_data(&self) -> Result<()> {
         unsafe {
             FMOD_Studio_Bank_UnloadSampleData(self.ptr).check_err()?;
@@ -90,6 +157,66 @@ impl Bank {
         Ok(events)
     }
 
+    pub fn get_bus_count(&self) -> Result<u32> {
+        let mut count = 0;
+        unsafe {
+            FMOD_Studio_Bank_GetBusCount(self.ptr, &mut count).check_err()?;
+        }
+        Ok(count as u32)
+    }
+
+    pub fn get_bus_list(&self) -> Result<Vec<Bus>> {
+        let mut count = 0;
+        let mut buses = vec![
+            Bus {
+                ptr: ptr::null_mut()
+            };
+            self.get_bus_count()? as usize
+        ];
+        unsafe {
+            FMOD_Studio_Bank_GetBusList(
+                self.ptr,
+                buses.as_mut_ptr() as *mut *mut FMOD_STUDIO_BUS,
+                buses.len() as i32,
+                &mut count,
+            )
+            .check_err()?;
+        }
+
+        buses.truncate(count as usize);
+        Ok(buses)
+    }
+
+    pub fn get_vca_count(&self) -> Result<u32> {
+        let mut count = 0;
+        unsafe {
+            FMOD_Studio_Bank_GetVCACount(self.ptr, &mut count).check_err()?;
+        }
+        Ok(count as u32)
+    }
+
+    pub fn get_vca_list(&self) -> Result<Vec<Vca>> {
+        let mut count = 0;
+        let mut vcas = vec![
+            Vca {
+                ptr: ptr::null_mut()
+            };
+            self.get_vca_count()? as usize
+        ];
+        unsafe {
+            FMOD_Studio_Bank_GetVCAList(
+                self.ptr,
+                vcas.as_mut_ptr() as *mut *mut FMOD_STUDIO_VCA,
+                vcas.len() as i32,
+                &mut count,
+            )
+            .check_err()?;
+        }
+
+        vcas.truncate(count as usize);
+        Ok(vcas)
+    }
+
     pub fn unload(&self) -> Result<()> {
         for event in self.get_event_list()? {
             event.unset_callback()?;
@@ -115,9 +242,124 @@ impl LuaUserData for Bank {
             this.unload_sample_data().to_lua_err()?;
             Ok(())
         });
+
+        methods.add_method("get_event_list", |_lua, this, ()| {
+            this.get_event_list().to_lua_err()
+        });
+
+        methods.add_method("get_bus_list", |_lua, this, ()| {
+            this.get_bus_list().to_lua_err()
+        });
+        methods.add_method("get_vca_list", |_lua, this, ()| {
+            this.get_vca_list().to_lua_err()
+        });
+
+        methods.add_method("get_loading_state", |_lua, this, ()| {
+            Ok(this.get_loading_state().to_lua_err()?.as_str())
+        });
+
+        methods.add_method("get_sample_loading_state", |_lua, this, ()| {
+            Ok(this.get_sample_loading_state().to_lua_err()?.as_str())
+        });
     }
 }
 
 // inventory::submit! {
 //     Module::parse("fmod.LoadBankFlags", load)
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FmodCoreInitFlags, FmodStudioInitFlags, FmodSystemBuilder, OutputType};
+    use std::time::{Duration, Instant};
+
+    /// Needs a real bank, so this only runs when asked for. Point `HV_FMOD_TEST_BANK` at a bank
+    /// file and run with `cargo test -- --ignored`. No audio hardware is needed.
+    #[test]
+    #[ignore]
+    fn nonblocking_bank_load_can_be_polled() -> Result<()> {
+        let path = std::env::var("HV_FMOD_TEST_BANK")?;
+        let fmod = FmodSystemBuilder::create()?
+            .output(OutputType::NoSound)
+            .initialize(32, FmodStudioInitFlags::NORMAL, FmodCoreInitFlags::NORMAL)?;
+
+        let bank = fmod.load_bank_file(&path, LoadBankFlags::NONBLOCKING)?;
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            fmod.update()?;
+            match bank.get_loading_state()? {
+                LoadingState::Loaded => break,
+                LoadingState::Loading => {}
+                other => panic!("unexpected loading state {:?}", other),
+            }
+            assert!(
+                Instant::now() < deadline,
+                "timed out waiting for bank to load"
+            );
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        bank.get_event_list()?;
+
+        bank.load_sample_data()?;
+        while bank.get_sample_loading_state()? == LoadingState::Loading {
+            fmod.update()?;
+            assert!(
+                Instant::now() < deadline,
+                "timed out waiting for samples to load"
+            );
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(bank.get_sample_loading_state()?, LoadingState::Loaded);
+
+        bank.unload()?;
+        Ok(())
+    }
+
+    /// Needs real banks, so this only runs when asked for. Point `HV_FMOD_TEST_BANK` at a bank
+    /// file containing at least one event and `HV_FMOD_TEST_STRINGS_BANK` at its strings bank,
+    /// then run with `cargo test -- --ignored`.
+    #[test]
+    #[ignore]
+    fn lists_bank_contents() -> Result<()> {
+        let path = std::env::var("HV_FMOD_TEST_BANK")?;
+        let strings_path = std::env::var("HV_FMOD_TEST_STRINGS_BANK")?;
+        let fmod = FmodSystemBuilder::create()?
+            .output(OutputType::NoSound)
+            .initialize(32, FmodStudioInitFlags::NORMAL, FmodCoreInitFlags::NORMAL)?;
+        fmod.load_bank_file(&strings_path, LoadBankFlags::NORMAL)?;
+        let bank = fmod.load_bank_file(&path, LoadBankFlags::NORMAL)?;
+
+        let events = bank.get_event_list()?;
+        assert_eq!(events.len() as u32, bank.get_event_count()?);
+        assert!(!events.is_empty());
+        for event in &events {
+            let event_path = event.get_path()?;
+            assert!(event_path.starts_with("event:/"), "{}", event_path);
+
+            let descriptions = event.get_parameter_descriptions()?;
+            assert_eq!(
+                descriptions.len() as u32,
+                event.get_parameter_description_count()?
+            );
+            for desc in descriptions {
+                assert!(!desc.name.is_empty());
+                assert!(desc.minimum <= desc.default_value && desc.default_value <= desc.maximum);
+            }
+        }
+
+        let buses = bank.get_bus_list()?;
+        assert_eq!(buses.len() as u32, bank.get_bus_count()?);
+        for bus in buses {
+            assert!(bus.get_path()?.starts_with("bus:/"));
+        }
+
+        let vcas = bank.get_vca_list()?;
+        assert_eq!(vcas.len() as u32, bank.get_vca_count()?);
+        for vca in vcas {
+            assert!(vca.get_path()?.starts_with("vca:/"));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,66 @@
+use crate::get_fmod_string;
+use {hv_core::prelude::*, hv_fmod_sys::*};
+
+/// A mixer bus, as retrieved from [`Bank::get_bus_list`](crate::Bank::get_bus_list).
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct Bus {
+    pub(crate) ptr: *mut FMOD_STUDIO_BUS,
+}
+
+unsafe impl Send for Bus {}
+unsafe impl Sync for Bus {}
+
+impl Bus {
+    pub fn is_valid(&self) -> bool {
+        unsafe { FMOD_Studio_Bus_IsValid(self.ptr) != 0 }
+    }
+
+    /// The bus's path, such as `bus:/SFX`. This requires the bank's strings bank to be loaded.
+    pub fn get_path(&self) -> Result<String> {
+        unsafe {
+            get_fmod_string(|buf, size, retrieved| {
+                FMOD_Studio_Bus_GetPath(self.ptr, buf, size, retrieved)
+            })
+        }
+    }
+}
+
+impl LuaUserData for Bus {
+    fn add_methods<'lua, T: LuaUserDataMethods<'lua, Self>>(methods: &mut T) {
+        methods.add_method("is_valid", |_lua, this, ()| Ok(this.is_valid()));
+        methods.add_method("get_path", |_lua, this, ()| this.get_path().to_lua_err());
+    }
+}
+
+/// A VCA, as retrieved from [`Bank::get_vca_list`](crate::Bank::get_vca_list).
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct Vca {
+    pub(crate) ptr: *mut FMOD_STUDIO_VCA,
+}
+
+unsafe impl Send for Vca {}
+unsafe impl Sync for Vca {}
+
+impl Vca {
+    pub fn is_valid(&self) -> bool {
+        unsafe { FMOD_Studio_VCA_IsValid(self.ptr) != 0 }
+    }
+
+    /// The VCA's path, such as `vca:/Music`. This requires the bank's strings bank to be loaded.
+    pub fn get_path(&self) -> Result<String> {
+        unsafe {
+            get_fmod_string(|buf, size, retrieved| {
+                FMOD_Studio_VCA_GetPath(self.ptr, buf, size, retrieved)
+            })
+        }
+    }
+}
+
+impl LuaUserData for Vca {
+    fn add_methods<'lua, T: LuaUserDataMethods<'lua, Self>>(methods: &mut T) {
+        methods.add_method("is_valid", |_lua, this, ()| Ok(this.is_valid()));
+        methods.add_method("get_path", |_lua, this, ()| this.get_path().to_lua_err());
+    }
+}
@@ -0,0 +1,180 @@
+//! The queue FMOD callbacks are sent through on their way to [`Fmod::flush_callbacks`].
+//!
+//! FMOD calls event callbacks from its own threads, so rather than running them there, their
+//! parameters are pushed onto a [`CallbackQueue`] and dispatched on the game's thread when
+//! callbacks are flushed. By default the queue is unbounded; a game which registers a lot of beat
+//! or marker callbacks and flushes them rarely can bound it with
+//! [`FmodSystemBuilder::callback_queue_capacity`], choosing what happens when it fills up with
+//! [`FmodSystemBuilder::callback_overflow_policy`].
+//!
+//! [`Fmod::flush_callbacks`]: crate::Fmod::flush_callbacks
+//! [`FmodSystemBuilder::callback_queue_capacity`]: crate::FmodSystemBuilder::callback_queue_capacity
+//! [`FmodSystemBuilder::callback_overflow_policy`]: crate::FmodSystemBuilder::callback_overflow_policy
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Condvar, Mutex},
+};
+
+/// What a bounded [`CallbackQueue`] does with an event pushed while it's full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Discard the oldest queued event to make room, so the queue always holds the most recent
+    /// events. The default.
+    DropOldest,
+    /// Discard the event being pushed, keeping the events already queued.
+    DropNewest,
+    /// Wait for the queue to be flushed before pushing. Since events are pushed from FMOD's own
+    /// threads, this stalls FMOD until the game next flushes its callbacks; only use it if no
+    /// event can be allowed to go missing, and callbacks are flushed every frame.
+    Block,
+}
+
+impl Default for OverflowPolicy {
+    fn default() -> Self {
+        OverflowPolicy::DropOldest
+    }
+}
+
+#[derive(Debug)]
+struct Inner<T> {
+    queue: Mutex<VecDeque<T>>,
+    not_full: Condvar,
+    capacity: Option<usize>,
+    policy: OverflowPolicy,
+}
+
+/// A multi-producer queue of events, optionally bounded. Cloning a `CallbackQueue` gives another
+/// handle to the same queue.
+#[derive(Debug)]
+pub struct CallbackQueue<T> {
+    inner: Arc<Inner<T>>,
+}
+
+impl<T> Clone for CallbackQueue<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> CallbackQueue<T> {
+    /// Create an unbounded queue.
+    pub fn unbounded() -> Self {
+        Self::new(None, OverflowPolicy::default())
+    }
+
+    /// Create a queue holding at most `capacity` events, if given, which handles events pushed
+    /// while it's full according to `policy`. A capacity of zero is treated as one.
+    pub fn new(capacity: Option<usize>, policy: OverflowPolicy) -> Self {
+        let capacity = capacity.map(|capacity| capacity.max(1));
+        Self {
+            inner: Arc::new(Inner {
+                queue: Mutex::new(VecDeque::with_capacity(capacity.unwrap_or(0))),
+                not_full: Condvar::new(),
+                capacity,
+                policy,
+            }),
+        }
+    }
+
+    /// The most events this queue will hold, or `None` if it's unbounded.
+    pub fn capacity(&self) -> Option<usize> {
+        self.inner.capacity
+    }
+
+    /// What this queue does with events pushed while it's full.
+    pub fn policy(&self) -> OverflowPolicy {
+        self.inner.policy
+    }
+
+    /// Push an event onto the back of the queue. If the queue is full, the event is handled
+    /// according to the queue's [`OverflowPolicy`].
+    pub fn push(&self, event: T) {
+        let mut queue = self.inner.queue.lock().unwrap();
+        if let Some(capacity) = self.inner.capacity {
+            match self.inner.policy {
+                OverflowPolicy::DropOldest => {
+                    while queue.len() >= capacity {
+                        queue.pop_front();
+                    }
+                }
+                OverflowPolicy::DropNewest if queue.len() >= capacity => return,
+                OverflowPolicy::DropNewest => {}
+                OverflowPolicy::Block => {
+                    while queue.len() >= capacity {
+                        queue = self.inner.not_full.wait(queue).unwrap();
+                    }
+                }
+            }
+        }
+
+        queue.push_back(event);
+    }
+
+    /// Take every event currently in the queue, oldest first. Events pushed while the returned
+    /// events are being handled stay queued until the next drain.
+    pub fn drain(&self) -> Vec<T> {
+        let events = self.inner.queue.lock().unwrap().drain(..).collect();
+        self.inner.not_full.notify_all();
+        events
+    }
+
+    /// The number of events waiting in the queue.
+    pub fn len(&self) -> usize {
+        self.inner.queue.lock().unwrap().len()
+    }
+
+    /// Whether the queue has no events waiting in it.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drop_oldest_keeps_the_newest_events() {
+        let queue = CallbackQueue::new(Some(3), OverflowPolicy::DropOldest);
+        for i in 1..=5 {
+            queue.push(i);
+        }
+
+        assert_eq!(queue.len(), 3);
+        assert_eq!(queue.drain(), [3, 4, 5]);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn drop_newest_keeps_the_oldest_events() {
+        let queue = CallbackQueue::new(Some(3), OverflowPolicy::DropNewest);
+        for i in 1..=5 {
+            queue.push(i);
+        }
+
+        assert_eq!(queue.drain(), [1, 2, 3]);
+    }
+
+    #[test]
+    fn blocked_pushes_resume_after_a_drain() {
+        let queue = CallbackQueue::new(Some(1), OverflowPolicy::Block);
+        queue.push(1);
+
+        let producer = std::thread::spawn({
+            let queue = queue.clone();
+            move || queue.push(2)
+        });
+
+        let mut received = Vec::new();
+        while received.len() < 2 {
+            received.extend(queue.drain());
+            std::thread::yield_now();
+        }
+        producer.join().unwrap();
+
+        assert_eq!(received, [1, 2]);
+    }
+}
@@ -0,0 +1,210 @@
+//! Errors produced by FMOD.
+//!
+//! Every failing FMOD call in this crate is reported as an [`FmodError`], which keeps the original
+//! `FMOD_RESULT` code around. Most functions here return `anyhow` errors, so to handle a specific
+//! failure, downcast to [`FmodError`]:
+//!
+//! ```no_run
+//! # use hv_core::prelude::*;
+//! # use hv_fmod::{EventDescription, Fmod, FmodError};
+//! # fn find_event(fmod: &Fmod) -> Result<Option<EventDescription>> {
+//! match fmod.get_event("event:/Maybe/Missing") {
+//!     Ok(event) => Ok(Some(event)),
+//!     Err(err) if err.downcast_ref::<FmodError>() == Some(&FmodError::EventNotFound) => Ok(None),
+//!     Err(err) => Err(err),
+//! }
+//! # }
+//! ```
+
+use hv_fmod_sys::*;
+use std::fmt;
+
+/// A broad classification of [`FmodError`]s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FmodErrorKind {
+    /// Something being looked up (an event, file, DSP, tag, or plugin) doesn't exist.
+    NotFound,
+    /// FMOD ran out of memory.
+    Memory,
+    /// A file couldn't be read or was malformed.
+    File,
+    /// A network/HTTP operation failed.
+    Network,
+    /// A handle, parameter, or command was invalid.
+    InvalidArgument,
+    /// The audio output or recording device failed.
+    Output,
+    /// The operation isn't valid in the current state of the system, such as before
+    /// initialization or before a bank has loaded.
+    InvalidState,
+    /// The operation isn't supported by this version of FMOD or the current hardware.
+    Unsupported,
+    /// A limit on channels, samples, or audible voices was hit.
+    Exhausted,
+    /// Anything else.
+    Other,
+}
+
+macro_rules! fmod_errors {
+    ($($variant:ident => $code:ident, $kind:ident;)*) => {
+        /// An error returned by FMOD, with one variant per non-OK `FMOD_RESULT`.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum FmodError {
+            $($variant,)*
+            /// A result code this crate doesn't know about, likely from a newer FMOD version.
+            Unknown(FMOD_RESULT),
+        }
+
+        impl FmodError {
+            /// Convert an `FMOD_RESULT` into an error, returning `None` for `FMOD_OK`.
+            pub fn from_code(code: FMOD_RESULT) -> Option<Self> {
+                match code {
+                    FMOD_RESULT_FMOD_OK => None,
+                    $($code => Some(FmodError::$variant),)*
+                    other => Some(FmodError::Unknown(other)),
+                }
+            }
+
+            /// The original `FMOD_RESULT` code.
+            pub fn code(&self) -> FMOD_RESULT {
+                match self {
+                    $(FmodError::$variant => $code,)*
+                    FmodError::Unknown(code) => *code,
+                }
+            }
+
+            /// The broad category this error falls into.
+            pub fn kind(&self) -> FmodErrorKind {
+                match self {
+                    $(FmodError::$variant => FmodErrorKind::$kind,)*
+                    FmodError::Unknown(_) => FmodErrorKind::Other,
+                }
+            }
+        }
+
+        impl fmt::Display for FmodError {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                match self {
+                    $(FmodError::$variant => f.write_str(stringify!($code)),)*
+                    FmodError::Unknown(code) => {
+                        write!(f, "unknown FMOD_RESULT error code: {}", code)
+                    }
+                }
+            }
+        }
+    };
+}
+
+fmod_errors! {
+    AlreadyLocked => FMOD_RESULT_FMOD_ERR_ALREADY_LOCKED, InvalidState;
+    BadCommand => FMOD_RESULT_FMOD_ERR_BADCOMMAND, InvalidArgument;
+    ChannelAlloc => FMOD_RESULT_FMOD_ERR_CHANNEL_ALLOC, Exhausted;
+    ChannelStolen => FMOD_RESULT_FMOD_ERR_CHANNEL_STOLEN, Exhausted;
+    Dma => FMOD_RESULT_FMOD_ERR_DMA, Output;
+    DspConnection => FMOD_RESULT_FMOD_ERR_DSP_CONNECTION, Other;
+    DspDontProcess => FMOD_RESULT_FMOD_ERR_DSP_DONTPROCESS, Other;
+    DspFormat => FMOD_RESULT_FMOD_ERR_DSP_FORMAT, Other;
+    DspInUse => FMOD_RESULT_FMOD_ERR_DSP_INUSE, Other;
+    DspNotFound => FMOD_RESULT_FMOD_ERR_DSP_NOTFOUND, NotFound;
+    DspReserved => FMOD_RESULT_FMOD_ERR_DSP_RESERVED, Other;
+    DspSilence => FMOD_RESULT_FMOD_ERR_DSP_SILENCE, Other;
+    DspType => FMOD_RESULT_FMOD_ERR_DSP_TYPE, Other;
+    EventAlreadyLoaded => FMOD_RESULT_FMOD_ERR_EVENT_ALREADY_LOADED, InvalidState;
+    EventLiveUpdateBusy => FMOD_RESULT_FMOD_ERR_EVENT_LIVEUPDATE_BUSY, Other;
+    EventLiveUpdateMismatch => FMOD_RESULT_FMOD_ERR_EVENT_LIVEUPDATE_MISMATCH, Other;
+    EventLiveUpdateTimeout => FMOD_RESULT_FMOD_ERR_EVENT_LIVEUPDATE_TIMEOUT, Other;
+    EventNotFound => FMOD_RESULT_FMOD_ERR_EVENT_NOTFOUND, NotFound;
+    FileBad => FMOD_RESULT_FMOD_ERR_FILE_BAD, File;
+    FileCouldNotSeek => FMOD_RESULT_FMOD_ERR_FILE_COULDNOTSEEK, File;
+    FileDiskEjected => FMOD_RESULT_FMOD_ERR_FILE_DISKEJECTED, File;
+    FileEndOfData => FMOD_RESULT_FMOD_ERR_FILE_ENDOFDATA, File;
+    FileEof => FMOD_RESULT_FMOD_ERR_FILE_EOF, File;
+    FileNotFound => FMOD_RESULT_FMOD_ERR_FILE_NOTFOUND, NotFound;
+    Format => FMOD_RESULT_FMOD_ERR_FORMAT, File;
+    HeaderMismatch => FMOD_RESULT_FMOD_ERR_HEADER_MISMATCH, File;
+    Http => FMOD_RESULT_FMOD_ERR_HTTP, Network;
+    HttpAccess => FMOD_RESULT_FMOD_ERR_HTTP_ACCESS, Network;
+    HttpProxyAuth => FMOD_RESULT_FMOD_ERR_HTTP_PROXY_AUTH, Network;
+    HttpServerError => FMOD_RESULT_FMOD_ERR_HTTP_SERVER_ERROR, Network;
+    HttpTimeout => FMOD_RESULT_FMOD_ERR_HTTP_TIMEOUT, Network;
+    Initialization => FMOD_RESULT_FMOD_ERR_INITIALIZATION, InvalidState;
+    Initialized => FMOD_RESULT_FMOD_ERR_INITIALIZED, InvalidState;
+    Internal => FMOD_RESULT_FMOD_ERR_INTERNAL, Other;
+    InvalidFloat => FMOD_RESULT_FMOD_ERR_INVALID_FLOAT, InvalidArgument;
+    InvalidHandle => FMOD_RESULT_FMOD_ERR_INVALID_HANDLE, InvalidArgument;
+    InvalidParam => FMOD_RESULT_FMOD_ERR_INVALID_PARAM, InvalidArgument;
+    InvalidPosition => FMOD_RESULT_FMOD_ERR_INVALID_POSITION, InvalidArgument;
+    InvalidSpeaker => FMOD_RESULT_FMOD_ERR_INVALID_SPEAKER, InvalidArgument;
+    InvalidString => FMOD_RESULT_FMOD_ERR_INVALID_STRING, InvalidArgument;
+    InvalidSyncPoint => FMOD_RESULT_FMOD_ERR_INVALID_SYNCPOINT, InvalidArgument;
+    InvalidThread => FMOD_RESULT_FMOD_ERR_INVALID_THREAD, InvalidArgument;
+    InvalidVector => FMOD_RESULT_FMOD_ERR_INVALID_VECTOR, InvalidArgument;
+    MaxAudible => FMOD_RESULT_FMOD_ERR_MAXAUDIBLE, Exhausted;
+    Memory => FMOD_RESULT_FMOD_ERR_MEMORY, Memory;
+    MemoryCantPoint => FMOD_RESULT_FMOD_ERR_MEMORY_CANTPOINT, Memory;
+    Needs3d => FMOD_RESULT_FMOD_ERR_NEEDS3D, Unsupported;
+    NeedsHardware => FMOD_RESULT_FMOD_ERR_NEEDSHARDWARE, Unsupported;
+    NetConnect => FMOD_RESULT_FMOD_ERR_NET_CONNECT, Network;
+    NetSocketError => FMOD_RESULT_FMOD_ERR_NET_SOCKET_ERROR, Network;
+    NetUrl => FMOD_RESULT_FMOD_ERR_NET_URL, Network;
+    NetWouldBlock => FMOD_RESULT_FMOD_ERR_NET_WOULD_BLOCK, Network;
+    NotReady => FMOD_RESULT_FMOD_ERR_NOTREADY, InvalidState;
+    NotLocked => FMOD_RESULT_FMOD_ERR_NOT_LOCKED, InvalidState;
+    OutputAllocated => FMOD_RESULT_FMOD_ERR_OUTPUT_ALLOCATED, Output;
+    OutputCreateBuffer => FMOD_RESULT_FMOD_ERR_OUTPUT_CREATEBUFFER, Output;
+    OutputDriverCall => FMOD_RESULT_FMOD_ERR_OUTPUT_DRIVERCALL, Output;
+    OutputFormat => FMOD_RESULT_FMOD_ERR_OUTPUT_FORMAT, Output;
+    OutputInit => FMOD_RESULT_FMOD_ERR_OUTPUT_INIT, Output;
+    OutputNoDrivers => FMOD_RESULT_FMOD_ERR_OUTPUT_NODRIVERS, Output;
+    Plugin => FMOD_RESULT_FMOD_ERR_PLUGIN, Other;
+    PluginMissing => FMOD_RESULT_FMOD_ERR_PLUGIN_MISSING, NotFound;
+    PluginResource => FMOD_RESULT_FMOD_ERR_PLUGIN_RESOURCE, Other;
+    PluginVersion => FMOD_RESULT_FMOD_ERR_PLUGIN_VERSION, Unsupported;
+    Record => FMOD_RESULT_FMOD_ERR_RECORD, Output;
+    RecordDisconnected => FMOD_RESULT_FMOD_ERR_RECORD_DISCONNECTED, Output;
+    ReverbChannelGroup => FMOD_RESULT_FMOD_ERR_REVERB_CHANNELGROUP, Other;
+    ReverbInstance => FMOD_RESULT_FMOD_ERR_REVERB_INSTANCE, Other;
+    StudioNotLoaded => FMOD_RESULT_FMOD_ERR_STUDIO_NOT_LOADED, InvalidState;
+    StudioUninitialized => FMOD_RESULT_FMOD_ERR_STUDIO_UNINITIALIZED, InvalidState;
+    Subsounds => FMOD_RESULT_FMOD_ERR_SUBSOUNDS, Other;
+    SubsoundAllocated => FMOD_RESULT_FMOD_ERR_SUBSOUND_ALLOCATED, Other;
+    SubsoundCantMove => FMOD_RESULT_FMOD_ERR_SUBSOUND_CANTMOVE, Other;
+    TagNotFound => FMOD_RESULT_FMOD_ERR_TAGNOTFOUND, NotFound;
+    TooManyChannels => FMOD_RESULT_FMOD_ERR_TOOMANYCHANNELS, Exhausted;
+    TooManySamples => FMOD_RESULT_FMOD_ERR_TOOMANYSAMPLES, Exhausted;
+    Truncated => FMOD_RESULT_FMOD_ERR_TRUNCATED, Other;
+    Unimplemented => FMOD_RESULT_FMOD_ERR_UNIMPLEMENTED, Unsupported;
+    Uninitialized => FMOD_RESULT_FMOD_ERR_UNINITIALIZED, InvalidState;
+    Unsupported => FMOD_RESULT_FMOD_ERR_UNSUPPORTED, Unsupported;
+    Version => FMOD_RESULT_FMOD_ERR_VERSION, Unsupported;
+}
+
+impl std::error::Error for FmodError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn result_codes_map_to_variants() {
+        assert_eq!(FmodError::from_code(FMOD_RESULT_FMOD_OK), None);
+
+        let err = FmodError::from_code(FMOD_RESULT_FMOD_ERR_EVENT_NOTFOUND).unwrap();
+        assert_eq!(err, FmodError::EventNotFound);
+        assert_eq!(err.code(), FMOD_RESULT_FMOD_ERR_EVENT_NOTFOUND);
+        assert_eq!(err.kind(), FmodErrorKind::NotFound);
+        assert_eq!(err.to_string(), "FMOD_RESULT_FMOD_ERR_EVENT_NOTFOUND");
+
+        let err = FmodError::from_code(FMOD_RESULT_FMOD_ERR_MEMORY).unwrap();
+        assert_eq!(err, FmodError::Memory);
+        assert_eq!(err.kind(), FmodErrorKind::Memory);
+
+        // Errors survive a round trip through `anyhow`, with the same message as before.
+        let anyhow_err = hv_core::error::Error::from(FmodError::FileNotFound);
+        assert_eq!(
+            anyhow_err.downcast_ref::<FmodError>(),
+            Some(&FmodError::FileNotFound)
+        );
+        assert_eq!(anyhow_err.to_string(), "FMOD_RESULT_FMOD_ERR_FILE_NOTFOUND");
+    }
+}
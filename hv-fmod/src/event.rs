@@ -1,4 +1,4 @@
-use crate::{CheckError, Fmod};
+use crate::{get_fmod_string, CheckError, Fmod, QueuedCallback};
 use {
     enum_primitive_derive::*,
     hv_core::prelude::*,
@@ -7,7 +7,7 @@ use {
     num_traits::FromPrimitive,
     std::{
         ffi::{CStr, CString},
-        ptr, str,
+        mem, ptr, str,
         sync::{Arc, Mutex},
     },
 };
@@ -306,6 +306,49 @@ pub struct ParameterValue {
     pub final_value: f32,
 }
 
+/// The description of one of an event's parameters, as retrieved from
+/// [`EventDescription::get_parameter_descriptions`].
+#[derive(Debug, Clone)]
+pub struct ParameterDescription {
+    /// The parameter's name.
+    pub name: String,
+
+    /// The parameter's ID, for use with [`EventInstance::set_parameter_by_id`].
+    pub id: ParameterId,
+
+    /// The parameter's minimum value.
+    pub minimum: f32,
+
+    /// The parameter's maximum value.
+    pub maximum: f32,
+
+    /// The parameter's default value.
+    pub default_value: f32,
+}
+
+impl ParameterDescription {
+    unsafe fn from_raw(raw: &FMOD_STUDIO_PARAMETER_DESCRIPTION) -> Result<Self> {
+        Ok(Self {
+            name: CStr::from_ptr(raw.name).to_str()?.to_owned(),
+            id: raw.id.into(),
+            minimum: raw.minimum,
+            maximum: raw.maximum,
+            default_value: raw.defaultvalue,
+        })
+    }
+}
+
+impl<'lua> ToLua<'lua> for ParameterDescription {
+    fn to_lua(self, lua: &'lua Lua) -> LuaResult<LuaValue<'lua>> {
+        let table = lua.create_table()?;
+        table.set("name", self.name)?;
+        table.set("minimum", self.minimum)?;
+        table.set("maximum", self.maximum)?;
+        table.set("default", self.default_value)?;
+        table.to_lua(lua)
+    }
+}
+
 /// An identifier for an event parameter.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[repr(C)]
@@ -359,6 +402,15 @@ impl EventInstance {
         Ok(())
     }
 
+    /// Mark this instance for release. The instance will be destroyed by FMOD once it has stopped,
+    /// and the handle must not be used afterwards.
+    pub fn release(&self) -> Result<()> {
+        unsafe {
+            FMOD_Studio_EventInstance_Release(self.ptr).check_err()?;
+        }
+        Ok(())
+    }
+
     pub fn get_playback_state(&self) -> Result<PlaybackState> {
         let mut state = 0;
         unsafe {
@@ -389,6 +441,8 @@ impl EventInstance {
         Ok(())
     }
 
+    /// Set a pitch multiplier for the event. This scales any pitch set in FMOD Studio rather than
+    /// overriding it; `1.0` leaves the pitch unchanged.
     pub fn set_pitch(&self, pitch_multiplier: f32) -> Result<()> {
         unsafe {
             FMOD_Studio_EventInstance_SetPitch(self.ptr, pitch_multiplier).check_err()?;
@@ -396,6 +450,8 @@ impl EventInstance {
         Ok(())
     }
 
+    /// The `value` field is the multiplier set by `set_pitch`, and the `final_value` field is the
+    /// final pitch as modified by automation/modulation.
     pub fn get_pitch(&self) -> Result<ParameterValue> {
         let mut pitch = ParameterValue {
             value: 0.,
@@ -626,6 +682,30 @@ impl EventInstance {
         Ok(())
     }
 
+    /// Set a Rust closure to receive this instance's events. Unlike [`set_callback`], the closure
+    /// is never run on FMOD's threads: events are queued in the `Fmod` object and the closure is
+    /// called from [`Fmod::flush_callbacks`], the same as callbacks set from Lua. It's dropped once
+    /// the instance's callback is unset or replaced, or the instance is released.
+    ///
+    /// [`set_callback`]: EventInstance::set_callback
+    pub fn set_rust_callback(
+        &self,
+        fmod: &Fmod,
+        mask: EventCallbackMask,
+        callback: Box<dyn FnMut(EventCallbackInfo) + Send>,
+    ) -> Result<()> {
+        let cq = fmod.cq.clone();
+        let cb_guard = fmod.insert_callback(QueuedCallback::Rust(Arc::new(Mutex::new(callback))));
+
+        self.set_callback(
+            move |event_instance, event_info| {
+                cq.push((cb_guard.index, event_instance, event_info));
+                Ok(())
+            },
+            mask,
+        )
+    }
+
     pub fn unset_callback(&self) -> Result<()> {
         unsafe {
             if let Some(ud_ptr) = self.get_userdata().unwrap() {
@@ -646,12 +726,21 @@ impl EventInstance {
 
 impl LuaUserData for EventInstance {
     fn add_methods<'lua, T: LuaUserDataMethods<'lua, Self>>(methods: &mut T) {
-        methods.add_method("start", |_lua, this, ()| this.start().to_lua_err());
-        methods.add_method("stop", |_lua, this, stop_mode: StopMode| {
-            this.stop(stop_mode).to_lua_err()
+        // Methods which would otherwise return nothing return the instance itself, so that calls
+        // can be chained: `desc:play():set_volume(0.5):set_pitch(1.2)`.
+        methods.add_method("start", |_lua, this, ()| {
+            this.start().to_lua_err()?;
+            Ok(*this)
         });
+        methods.add_method("stop", |_lua, this, stop_mode: Option<StopMode>| {
+            this.stop(stop_mode.unwrap_or(StopMode::AllowFadeout))
+                .to_lua_err()?;
+            Ok(*this)
+        });
+        methods.add_method("release", |_lua, this, ()| this.release().to_lua_err());
         methods.add_method("trigger_cue", |_lua, this, ()| {
-            this.trigger_cue().to_lua_err()
+            this.trigger_cue().to_lua_err()?;
+            Ok(*this)
         });
 
         methods.add_method("get_playback_state", |_lua, this, ()| {
@@ -666,11 +755,13 @@ impl LuaUserData for EventInstance {
 
         methods.add_method("is_paused", |_lua, this, ()| this.is_paused().to_lua_err());
         methods.add_method("set_paused", |_lua, this, paused| {
-            this.set_paused(paused).to_lua_err()
+            this.set_paused(paused).to_lua_err()?;
+            Ok(*this)
         });
 
         methods.add_method("set_pitch", |_lua, this, pitch_multiplier| {
-            this.set_pitch(pitch_multiplier).to_lua_err()
+            this.set_pitch(pitch_multiplier).to_lua_err()?;
+            Ok(*this)
         });
 
         methods.add_method("get_pitch", |_lua, this, ()| {
@@ -678,25 +769,51 @@ impl LuaUserData for EventInstance {
             Ok((param_value.value, param_value.final_value))
         });
 
+        methods.add_method("set_volume", |_lua, this, volume| {
+            this.set_volume(volume).to_lua_err()?;
+            Ok(*this)
+        });
+
+        methods.add_method("get_volume", |_lua, this, ()| {
+            let param_value = this.get_volume().to_lua_err()?;
+            Ok((param_value.value, param_value.final_value))
+        });
+
+        methods.add_method(
+            "set_parameter",
+            |_lua, this, (name, value, ignore_seek_speed): (LuaString, f32, Option<bool>)| {
+                this.set_parameter_by_name(
+                    name.as_bytes(),
+                    value,
+                    ignore_seek_speed.unwrap_or(false),
+                )
+                .to_lua_err()?;
+                Ok(*this)
+            },
+        );
+
+        methods.add_method("get_parameter", |_lua, this, name: LuaString| {
+            let param_value = this.get_parameter_by_name(name.as_bytes()).to_lua_err()?;
+            Ok((param_value.value, param_value.final_value))
+        });
+
         methods.add_method(
             "set_callback",
             |lua, this, (maybe_cb, mask): (Option<LuaFunction>, Option<EventCallbackMask>)| {
                 if let Some(cb) = maybe_cb {
                     let fmod = lua.get_resource::<Fmod>()?;
-                    let (cq_send, cb_guard) = {
+                    let (cq, cb_guard) = {
                         let fmod_mut = &mut fmod.borrow_mut();
-                        let cq_send = Mutex::new(fmod.borrow().cq_send.clone());
-                        let cb_guard = fmod_mut.insert_callback(lua.create_registry_value(cb)?);
-                        (cq_send, cb_guard)
+                        let cq = fmod_mut.cq.clone();
+                        let cb_guard = fmod_mut
+                            .insert_callback(QueuedCallback::Lua(lua.create_registry_value(cb)?));
+                        (cq, cb_guard)
                     };
 
                     this.set_callback(
                         move |event_instance, event_info| {
-                            cq_send
-                                .lock()
-                                .unwrap()
-                                .send((cb_guard.index, event_instance, event_info))
-                                .map_err(|_| anyhow!("error while sending callback info"))
+                            cq.push((cb_guard.index, event_instance, event_info));
+                            Ok(())
                         },
                         mask.unwrap_or(EventCallbackMask::ALL),
                     )
@@ -748,6 +865,135 @@ impl EventDescription {
         Ok(EventInstance { ptr })
     }
 
+    /// Create, start, and immediately release a new instance of this event. The instance will be
+    /// destroyed by FMOD once it stops, which makes this the right way to fire off one-shots; the
+    /// returned handle can still be used to adjust the instance (or stop it early) until then.
+    ///
+    /// From Lua, this is available as `desc:play()`, and the returned instance supports chaining:
+    ///
+    /// ```no_run
+    /// # use hv_core::prelude::*;
+    /// # use hv_fmod::*;
+    /// // Note: this snippet is marked `no_run` for the same reason as the `Guid::from_str` example:
+    /// // doctests can't reliably find the FMOD DLLs, and it also needs a real bank to play from.
+    /// # fn main() -> Result<()> {
+    /// let fmod = FmodSystemBuilder::create()?.initialize(
+    ///     32,
+    ///     FmodStudioInitFlags::NORMAL,
+    ///     FmodCoreInitFlags::NORMAL,
+    /// )?;
+    /// fmod.load_bank_file("Master.bank", LoadBankFlags::NORMAL)?;
+    /// let desc = fmod.get_event("event:/Explosion")?;
+    ///
+    /// let lua = Lua::new();
+    /// lua.load(mlua::chunk! {
+    ///     local instance = $desc:play():set_volume(0.5):set_pitch(1.25):set_parameter("Size", 3)
+    ///     assert(not (instance:get_playback_state() == "stopped"))
+    ///     instance:stop("immediate")
+    /// })
+    /// .exec()?;
+    ///
+    /// // Once FMOD processes the stop, the released instance is destroyed.
+    /// fmod.update()?;
+    /// assert_eq!(desc.get_instance_count()?, 0);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn play(&self) -> Result<EventInstance> {
+        let instance = self.create_instance()?;
+        instance.start()?;
+        instance.release()?;
+        Ok(instance)
+    }
+
+    /// The number of live instances of this event.
+    pub fn get_instance_count(&self) -> Result<u32> {
+        let mut count = 0;
+        unsafe {
+            FMOD_Studio_EventDescription_GetInstanceCount(self.ptr, &mut count).check_err()?;
+        }
+        Ok(count as u32)
+    }
+
+    /// The event's path, such as `event:/UI/Select`. This requires the bank's strings bank to be
+    /// loaded.
+    pub fn get_path(&self) -> Result<String> {
+        unsafe {
+            get_fmod_string(|buf, size, retrieved| {
+                FMOD_Studio_EventDescription_GetPath(self.ptr, buf, size, retrieved)
+            })
+        }
+    }
+
+    /// The length of the event's timeline in milliseconds, or `0` if it has no timeline.
+    pub fn get_length(&self) -> Result<i32> {
+        let mut length = 0;
+        unsafe {
+            FMOD_Studio_EventDescription_GetLength(self.ptr, &mut length).check_err()?;
+        }
+        Ok(length)
+    }
+
+    /// Whether the event is 3D, that is, whether it contains any spatializers and so should be
+    /// given a position with [`EventInstance::set_3d_attributes`].
+    pub fn is_3d(&self) -> Result<bool> {
+        let mut is_3d = 0;
+        unsafe {
+            FMOD_Studio_EventDescription_Is3D(self.ptr, &mut is_3d).check_err()?;
+        }
+        Ok(is_3d != 0)
+    }
+
+    /// Whether the event is a oneshot, which is guaranteed to stop on its own. Oneshots are safe
+    /// to release as soon as they're started, as [`EventDescription::play`] does; anything else
+    /// will play until it's explicitly stopped.
+    pub fn is_oneshot(&self) -> Result<bool> {
+        let mut is_oneshot = 0;
+        unsafe {
+            FMOD_Studio_EventDescription_IsOneshot(self.ptr, &mut is_oneshot).check_err()?;
+        }
+        Ok(is_oneshot != 0)
+    }
+
+    /// The minimum and maximum distances of the event's 3D attenuation, as set by its
+    /// spatializers.
+    pub fn get_min_max_distance(&self) -> Result<(f32, f32)> {
+        let (mut min, mut max) = (0., 0.);
+        unsafe {
+            FMOD_Studio_EventDescription_GetMinMaxDistance(self.ptr, &mut min, &mut max)
+                .check_err()?;
+        }
+        Ok((min, max))
+    }
+
+    pub fn get_parameter_description_count(&self) -> Result<u32> {
+        let mut count = 0;
+        unsafe {
+            FMOD_Studio_EventDescription_GetParameterDescriptionCount(self.ptr, &mut count)
+                .check_err()?;
+        }
+        Ok(count as u32)
+    }
+
+    /// Describe every parameter of the event, in FMOD's index order.
+    pub fn get_parameter_descriptions(&self) -> Result<Vec<ParameterDescription>> {
+        let count = self.get_parameter_description_count()?;
+        let mut descriptions = Vec::with_capacity(count as usize);
+        for index in 0..count {
+            unsafe {
+                let mut raw = mem::zeroed::<FMOD_STUDIO_PARAMETER_DESCRIPTION>();
+                FMOD_Studio_EventDescription_GetParameterDescriptionByIndex(
+                    self.ptr,
+                    index as i32,
+                    &mut raw,
+                )
+                .check_err()?;
+                descriptions.push(ParameterDescription::from_raw(&raw)?);
+            }
+        }
+        Ok(descriptions)
+    }
+
     pub(crate) unsafe fn from_ptr(ptr: *mut FMOD_STUDIO_EVENTDESCRIPTION) -> Result<Self> {
         let this = EventDescription { ptr };
 
@@ -821,25 +1067,49 @@ impl LuaUserData for EventDescription {
             this.create_instance().to_lua_err()
         });
 
+        methods.add_method("play", |_lua, this, ()| this.play().to_lua_err());
+
+        methods.add_method("get_instance_count", |_lua, this, ()| {
+            this.get_instance_count().to_lua_err()
+        });
+
+        methods.add_method("get_path", |_lua, this, ()| this.get_path().to_lua_err());
+
+        methods.add_method("get_length", |_lua, this, ()| {
+            this.get_length().to_lua_err()
+        });
+
+        methods.add_method("is_3d", |_lua, this, ()| this.is_3d().to_lua_err());
+
+        methods.add_method("is_oneshot", |_lua, this, ()| {
+            this.is_oneshot().to_lua_err()
+        });
+
+        methods.add_method("get_min_max_distance", |_lua, this, ()| {
+            this.get_min_max_distance().to_lua_err()
+        });
+
+        methods.add_method("get_parameter_descriptions", |_lua, this, ()| {
+            this.get_parameter_descriptions().to_lua_err()
+        });
+
         methods.add_method(
             "set_callback",
             |lua, this, (maybe_cb, mask): (Option<LuaFunction>, Option<EventCallbackMask>)| {
                 if let Some(cb) = maybe_cb {
                     let fmod = lua.get_resource::<Fmod>()?;
-                    let (cq_send, cb_guard) = {
+                    let (cq, cb_guard) = {
                         let fmod_mut = &mut fmod.borrow_mut();
-                        let cq_send = Mutex::new(fmod.borrow().cq_send.clone());
-                        let cb_guard = fmod_mut.insert_callback(lua.create_registry_value(cb)?);
-                        (cq_send, cb_guard)
+                        let cq = fmod_mut.cq.clone();
+                        let cb_guard = fmod_mut
+                            .insert_callback(QueuedCallback::Lua(lua.create_registry_value(cb)?));
+                        (cq, cb_guard)
                     };
 
                     this.set_callback(
                         move |event_instance, event_info| {
-                            cq_send
-                                .lock()
-                                .unwrap()
-                                .send((cb_guard.index, event_instance, event_info))
-                                .map_err(|_| anyhow!("error while sending callback info"))
+                            cq.push((cb_guard.index, event_instance, event_info));
+                            Ok(())
                         },
                         mask.unwrap_or(EventCallbackMask::ALL),
                     )
@@ -879,4 +1149,94 @@ mod tests {
         assert_eq!(rust_param.data1, c_param.data1);
         assert_eq!(rust_param.data2, c_param.data2);
     }
+
+    /// Needs real banks and an audio device, so this only runs when asked for. Point
+    /// `HV_FMOD_TEST_BANKS` at a `:`-separated list of bank files (including the master bank and
+    /// its strings bank) and `HV_FMOD_TEST_EVENT` at an event path inside them, then run with
+    /// `cargo test -- --ignored`.
+    #[test]
+    #[ignore]
+    fn volume_and_pitch_roundtrip_on_started_event() -> Result<()> {
+        use crate::{
+            bank::LoadBankFlags, FmodCoreInitFlags, FmodStudioInitFlags, FmodSystemBuilder,
+        };
+
+        let banks = std::env::var("HV_FMOD_TEST_BANKS")?;
+        let event = std::env::var("HV_FMOD_TEST_EVENT")?;
+
+        let fmod = FmodSystemBuilder::create()?.initialize(
+            32,
+            FmodStudioInitFlags::NORMAL,
+            FmodCoreInitFlags::NORMAL,
+        )?;
+        for bank in banks.split(':') {
+            fmod.load_bank_file(bank, LoadBankFlags::NORMAL)?;
+        }
+
+        let instance = fmod.get_event(&event)?.play()?;
+        fmod.update()?;
+
+        instance.set_volume(0.25)?;
+        instance.set_pitch(1.5)?;
+        fmod.update()?;
+
+        let volume = instance.get_volume()?;
+        assert_eq!(volume.value, 0.25);
+        assert!(volume.final_value >= 0.);
+        let pitch = instance.get_pitch()?;
+        assert_eq!(pitch.value, 1.5);
+        assert!(pitch.final_value > 0.);
+
+        instance.stop(StopMode::Immediate)?;
+        instance.release()?;
+        fmod.update()?;
+
+        Ok(())
+    }
+
+    /// Like the test above, this needs `HV_FMOD_TEST_BANKS` and `HV_FMOD_TEST_EVENT` to be set.
+    #[test]
+    #[ignore]
+    fn event_description_properties_match_between_rust_and_lua() -> Result<()> {
+        use crate::{
+            bank::LoadBankFlags, FmodCoreInitFlags, FmodStudioInitFlags, FmodSystemBuilder,
+            OutputType,
+        };
+
+        let banks = std::env::var("HV_FMOD_TEST_BANKS")?;
+        let event = std::env::var("HV_FMOD_TEST_EVENT")?;
+
+        let fmod = FmodSystemBuilder::create()?
+            .output(OutputType::NoSound)
+            .initialize(32, FmodStudioInitFlags::NORMAL, FmodCoreInitFlags::NORMAL)?;
+        for bank in banks.split(':') {
+            fmod.load_bank_file(bank, LoadBankFlags::NORMAL)?;
+        }
+
+        let description = fmod.get_event(&event)?;
+        let length = description.get_length()?;
+        let is_3d = description.is_3d()?;
+        let is_oneshot = description.is_oneshot()?;
+        let (min, max) = description.get_min_max_distance()?;
+        assert!(length >= 0);
+        assert!(0. <= min && min <= max, "{} > {}", min, max);
+
+        let lua = Lua::new();
+        lua.globals().set("description", description)?;
+        let (lua_length, lua_is_3d, lua_is_oneshot, lua_min, lua_max) = lua
+            .load(
+                r#"
+                local min, max = description:get_min_max_distance()
+                return description:get_length(), description:is_3d(),
+                    description:is_oneshot(), min, max
+                "#,
+            )
+            .eval::<(i32, bool, bool, f32, f32)>()?;
+        assert_eq!(
+            (lua_length, lua_is_3d, lua_is_oneshot, lua_min, lua_max),
+            (length, is_3d, is_oneshot, min, max)
+        );
+
+        Ok(())
+    }
 }
@@ -11,147 +11,65 @@ use {
     },
     hv_fmod_sys::*,
     lazy_static::lazy_static,
+    libc::c_char,
     regex::Regex,
-    std::{
-        ffi::CString,
-        ptr, str,
-        sync::mpsc::{Receiver, Sender},
-    },
+    std::{ffi::CString, fmt, ptr, str, sync::Arc},
 };
 
 pub mod bank;
+pub mod bus;
+pub mod callback_queue;
+pub mod error;
 pub mod event;
+pub mod music;
+pub mod rhythm;
+pub mod spatial;
 
 use std::sync::Mutex;
 
 pub use bank::*;
+pub use bus::*;
+pub use callback_queue::*;
+pub use error::*;
 pub use event::*;
 use hibitset::{AtomicBitSet, DrainableBitSet};
+pub use music::*;
+pub use rhythm::*;
+pub use spatial::*;
 use thunderdome::{Arena, Index};
 
 trait CheckError {
-    fn check_err(self) -> Result<()>;
+    fn check_err(self) -> Result<(), FmodError>;
 }
 
-impl CheckError for FMOD_RESULT {
-    fn check_err(self) -> Result<()> {
-        if self == FMOD_RESULT_FMOD_OK {
-            return Ok(());
-        }
+/// Read a string out of one of FMOD's `GetPath`-style functions. These are called once with no
+/// buffer to find out how large the string is, and then again to actually fill a buffer.
+pub(crate) unsafe fn get_fmod_string<F>(mut get: F) -> Result<String>
+where
+    F: FnMut(*mut c_char, i32, *mut i32) -> FMOD_RESULT,
+{
+    let mut len = 0;
+    get(ptr::null_mut(), 0, &mut len).check_err()?;
+
+    let mut buf = vec![0u8; len as usize];
+    let mut retrieved = 0;
+    get(
+        buf.as_mut_ptr() as *mut c_char,
+        buf.len() as i32,
+        &mut retrieved,
+    )
+    .check_err()?;
+
+    let end = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    buf.truncate(end);
+    Ok(String::from_utf8(buf)?)
+}
 
-        match self {
-            FMOD_RESULT_FMOD_ERR_ALREADY_LOCKED => bail!("FMOD_RESULT_FMOD_ERR_ALREADY_LOCKED"),
-            FMOD_RESULT_FMOD_ERR_BADCOMMAND => bail!("FMOD_RESULT_FMOD_ERR_BADCOMMAND"),
-            FMOD_RESULT_FMOD_ERR_CHANNEL_ALLOC => bail!("FMOD_RESULT_FMOD_ERR_CHANNEL_ALLOC"),
-            FMOD_RESULT_FMOD_ERR_CHANNEL_STOLEN => bail!("FMOD_RESULT_FMOD_ERR_CHANNEL_STOLEN"),
-            FMOD_RESULT_FMOD_ERR_DMA => bail!("FMOD_RESULT_FMOD_ERR_DMA"),
-            FMOD_RESULT_FMOD_ERR_DSP_CONNECTION => bail!("FMOD_RESULT_FMOD_ERR_DSP_CONNECTION"),
-            FMOD_RESULT_FMOD_ERR_DSP_DONTPROCESS => bail!("FMOD_RESULT_FMOD_ERR_DSP_DONTPROCESS"),
-            FMOD_RESULT_FMOD_ERR_DSP_FORMAT => bail!("FMOD_RESULT_FMOD_ERR_DSP_FORMAT"),
-            FMOD_RESULT_FMOD_ERR_DSP_INUSE => bail!("FMOD_RESULT_FMOD_ERR_DSP_INUSE"),
-            FMOD_RESULT_FMOD_ERR_DSP_NOTFOUND => bail!("FMOD_RESULT_FMOD_ERR_DSP_NOTFOUND"),
-            FMOD_RESULT_FMOD_ERR_DSP_RESERVED => bail!("FMOD_RESULT_FMOD_ERR_DSP_RESERVED"),
-            FMOD_RESULT_FMOD_ERR_DSP_SILENCE => bail!("FMOD_RESULT_FMOD_ERR_DSP_SILENCE"),
-            FMOD_RESULT_FMOD_ERR_DSP_TYPE => bail!("FMOD_RESULT_FMOD_ERR_DSP_TYPE"),
-            FMOD_RESULT_FMOD_ERR_EVENT_ALREADY_LOADED => {
-                bail!("FMOD_RESULT_FMOD_ERR_EVENT_ALREADY_LOADED")
-            }
-            FMOD_RESULT_FMOD_ERR_EVENT_LIVEUPDATE_BUSY => {
-                bail!("FMOD_RESULT_FMOD_ERR_EVENT_LIVEUPDATE_BUSY")
-            }
-            FMOD_RESULT_FMOD_ERR_EVENT_LIVEUPDATE_MISMATCH => {
-                bail!("FMOD_RESULT_FMOD_ERR_EVENT_LIVEUPDATE_MISMATCH")
-            }
-            FMOD_RESULT_FMOD_ERR_EVENT_LIVEUPDATE_TIMEOUT => {
-                bail!("FMOD_RESULT_FMOD_ERR_EVENT_LIVEUPDATE_TIMEOUT")
-            }
-            FMOD_RESULT_FMOD_ERR_EVENT_NOTFOUND => bail!("FMOD_RESULT_FMOD_ERR_EVENT_NOTFOUND"),
-            FMOD_RESULT_FMOD_ERR_FILE_BAD => bail!("FMOD_RESULT_FMOD_ERR_FILE_BAD"),
-            FMOD_RESULT_FMOD_ERR_FILE_COULDNOTSEEK => {
-                bail!("FMOD_RESULT_FMOD_ERR_FILE_COULDNOTSEEK")
-            }
-            FMOD_RESULT_FMOD_ERR_FILE_DISKEJECTED => bail!("FMOD_RESULT_FMOD_ERR_FILE_DISKEJECTED"),
-            FMOD_RESULT_FMOD_ERR_FILE_ENDOFDATA => bail!("FMOD_RESULT_FMOD_ERR_FILE_ENDOFDATA"),
-            FMOD_RESULT_FMOD_ERR_FILE_EOF => bail!("FMOD_RESULT_FMOD_ERR_FILE_EOF"),
-            FMOD_RESULT_FMOD_ERR_FILE_NOTFOUND => bail!("FMOD_RESULT_FMOD_ERR_FILE_NOTFOUND"),
-            FMOD_RESULT_FMOD_ERR_FORMAT => bail!("FMOD_RESULT_FMOD_ERR_FORMAT"),
-            FMOD_RESULT_FMOD_ERR_HEADER_MISMATCH => bail!("FMOD_RESULT_FMOD_ERR_HEADER_MISMATCH"),
-            FMOD_RESULT_FMOD_ERR_HTTP => bail!("FMOD_RESULT_FMOD_ERR_HTTP"),
-            FMOD_RESULT_FMOD_ERR_HTTP_ACCESS => bail!("FMOD_RESULT_FMOD_ERR_HTTP_ACCESS"),
-            FMOD_RESULT_FMOD_ERR_HTTP_PROXY_AUTH => bail!("FMOD_RESULT_FMOD_ERR_HTTP_PROXY_AUTH"),
-            FMOD_RESULT_FMOD_ERR_HTTP_SERVER_ERROR => {
-                bail!("FMOD_RESULT_FMOD_ERR_HTTP_SERVER_ERROR")
-            }
-            FMOD_RESULT_FMOD_ERR_HTTP_TIMEOUT => bail!("FMOD_RESULT_FMOD_ERR_HTTP_TIMEOUT"),
-            FMOD_RESULT_FMOD_ERR_INITIALIZATION => bail!("FMOD_RESULT_FMOD_ERR_INITIALIZATION"),
-            FMOD_RESULT_FMOD_ERR_INITIALIZED => bail!("FMOD_RESULT_FMOD_ERR_INITIALIZED"),
-            FMOD_RESULT_FMOD_ERR_INTERNAL => bail!("FMOD_RESULT_FMOD_ERR_INTERNAL"),
-            FMOD_RESULT_FMOD_ERR_INVALID_FLOAT => bail!("FMOD_RESULT_FMOD_ERR_INVALID_FLOAT"),
-            FMOD_RESULT_FMOD_ERR_INVALID_HANDLE => bail!("FMOD_RESULT_FMOD_ERR_INVALID_HANDLE"),
-            FMOD_RESULT_FMOD_ERR_INVALID_PARAM => bail!("FMOD_RESULT_FMOD_ERR_INVALID_PARAM"),
-            FMOD_RESULT_FMOD_ERR_INVALID_POSITION => bail!("FMOD_RESULT_FMOD_ERR_INVALID_POSITION"),
-            FMOD_RESULT_FMOD_ERR_INVALID_SPEAKER => bail!("FMOD_RESULT_FMOD_ERR_INVALID_SPEAKER"),
-            FMOD_RESULT_FMOD_ERR_INVALID_STRING => bail!("FMOD_RESULT_FMOD_ERR_INVALID_STRING"),
-            FMOD_RESULT_FMOD_ERR_INVALID_SYNCPOINT => {
-                bail!("FMOD_RESULT_FMOD_ERR_INVALID_SYNCPOINT")
-            }
-            FMOD_RESULT_FMOD_ERR_INVALID_THREAD => bail!("FMOD_RESULT_FMOD_ERR_INVALID_THREAD"),
-            FMOD_RESULT_FMOD_ERR_INVALID_VECTOR => bail!("FMOD_RESULT_FMOD_ERR_INVALID_VECTOR"),
-            FMOD_RESULT_FMOD_ERR_MAXAUDIBLE => bail!("FMOD_RESULT_FMOD_ERR_MAXAUDIBLE"),
-            FMOD_RESULT_FMOD_ERR_MEMORY => bail!("FMOD_RESULT_FMOD_ERR_MEMORY"),
-            FMOD_RESULT_FMOD_ERR_MEMORY_CANTPOINT => bail!("FMOD_RESULT_FMOD_ERR_MEMORY_CANTPOINT"),
-            FMOD_RESULT_FMOD_ERR_NEEDS3D => bail!("FMOD_RESULT_FMOD_ERR_NEEDS3D"),
-            FMOD_RESULT_FMOD_ERR_NEEDSHARDWARE => bail!("FMOD_RESULT_FMOD_ERR_NEEDSHARDWARE"),
-            FMOD_RESULT_FMOD_ERR_NET_CONNECT => bail!("FMOD_RESULT_FMOD_ERR_NET_CONNECT"),
-            FMOD_RESULT_FMOD_ERR_NET_SOCKET_ERROR => bail!("FMOD_RESULT_FMOD_ERR_NET_SOCKET_ERROR"),
-            FMOD_RESULT_FMOD_ERR_NET_URL => bail!("FMOD_RESULT_FMOD_ERR_NET_URL"),
-            FMOD_RESULT_FMOD_ERR_NET_WOULD_BLOCK => bail!("FMOD_RESULT_FMOD_ERR_NET_WOULD_BLOCK"),
-            FMOD_RESULT_FMOD_ERR_NOTREADY => bail!("FMOD_RESULT_FMOD_ERR_NOTREADY"),
-            FMOD_RESULT_FMOD_ERR_NOT_LOCKED => bail!("FMOD_RESULT_FMOD_ERR_NOT_LOCKED"),
-            FMOD_RESULT_FMOD_ERR_OUTPUT_ALLOCATED => bail!("FMOD_RESULT_FMOD_ERR_OUTPUT_ALLOCATED"),
-            FMOD_RESULT_FMOD_ERR_OUTPUT_CREATEBUFFER => {
-                bail!("FMOD_RESULT_FMOD_ERR_OUTPUT_CREATEBUFFER")
-            }
-            FMOD_RESULT_FMOD_ERR_OUTPUT_DRIVERCALL => {
-                bail!("FMOD_RESULT_FMOD_ERR_OUTPUT_DRIVERCALL")
-            }
-            FMOD_RESULT_FMOD_ERR_OUTPUT_FORMAT => bail!("FMOD_RESULT_FMOD_ERR_OUTPUT_FORMAT"),
-            FMOD_RESULT_FMOD_ERR_OUTPUT_INIT => bail!("FMOD_RESULT_FMOD_ERR_OUTPUT_INIT"),
-            FMOD_RESULT_FMOD_ERR_OUTPUT_NODRIVERS => bail!("FMOD_RESULT_FMOD_ERR_OUTPUT_NODRIVERS"),
-            FMOD_RESULT_FMOD_ERR_PLUGIN => bail!("FMOD_RESULT_FMOD_ERR_PLUGIN"),
-            FMOD_RESULT_FMOD_ERR_PLUGIN_MISSING => bail!("FMOD_RESULT_FMOD_ERR_PLUGIN_MISSING"),
-            FMOD_RESULT_FMOD_ERR_PLUGIN_RESOURCE => bail!("FMOD_RESULT_FMOD_ERR_PLUGIN_RESOURCE"),
-            FMOD_RESULT_FMOD_ERR_PLUGIN_VERSION => bail!("FMOD_RESULT_FMOD_ERR_PLUGIN_VERSION"),
-            FMOD_RESULT_FMOD_ERR_RECORD => bail!("FMOD_RESULT_FMOD_ERR_RECORD"),
-            FMOD_RESULT_FMOD_ERR_RECORD_DISCONNECTED => {
-                bail!("FMOD_RESULT_FMOD_ERR_RECORD_DISCONNECTED")
-            }
-            FMOD_RESULT_FMOD_ERR_REVERB_CHANNELGROUP => {
-                bail!("FMOD_RESULT_FMOD_ERR_REVERB_CHANNELGROUP")
-            }
-            FMOD_RESULT_FMOD_ERR_REVERB_INSTANCE => bail!("FMOD_RESULT_FMOD_ERR_REVERB_INSTANCE"),
-            FMOD_RESULT_FMOD_ERR_STUDIO_NOT_LOADED => {
-                bail!("FMOD_RESULT_FMOD_ERR_STUDIO_NOT_LOADED")
-            }
-            FMOD_RESULT_FMOD_ERR_STUDIO_UNINITIALIZED => {
-                bail!("FMOD_RESULT_FMOD_ERR_STUDIO_UNINITIALIZED")
-            }
-            FMOD_RESULT_FMOD_ERR_SUBSOUNDS => bail!("FMOD_RESULT_FMOD_ERR_SUBSOUNDS"),
-            FMOD_RESULT_FMOD_ERR_SUBSOUND_ALLOCATED => {
-                bail!("FMOD_RESULT_FMOD_ERR_SUBSOUND_ALLOCATED")
-            }
-            FMOD_RESULT_FMOD_ERR_SUBSOUND_CANTMOVE => {
-                bail!("FMOD_RESULT_FMOD_ERR_SUBSOUND_CANTMOVE")
-            }
-            FMOD_RESULT_FMOD_ERR_TAGNOTFOUND => bail!("FMOD_RESULT_FMOD_ERR_TAGNOTFOUND"),
-            FMOD_RESULT_FMOD_ERR_TOOMANYCHANNELS => bail!("FMOD_RESULT_FMOD_ERR_TOOMANYCHANNELS"),
-            FMOD_RESULT_FMOD_ERR_TOOMANYSAMPLES => bail!("FMOD_RESULT_FMOD_ERR_TOOMANYSAMPLES"),
-            FMOD_RESULT_FMOD_ERR_TRUNCATED => bail!("FMOD_RESULT_FMOD_ERR_TRUNCATED"),
-            FMOD_RESULT_FMOD_ERR_UNIMPLEMENTED => bail!("FMOD_RESULT_FMOD_ERR_UNIMPLEMENTED"),
-            FMOD_RESULT_FMOD_ERR_UNINITIALIZED => bail!("FMOD_RESULT_FMOD_ERR_UNINITIALIZED"),
-            FMOD_RESULT_FMOD_ERR_UNSUPPORTED => bail!("FMOD_RESULT_FMOD_ERR_UNSUPPORTED"),
-            FMOD_RESULT_FMOD_ERR_VERSION => bail!("FMOD_RESULT_FMOD_ERR_VERSION"),
-            other => unreachable!("unknown FMOD_RESULT error code: {}", other),
+impl CheckError for FMOD_RESULT {
+    fn check_err(self) -> Result<(), FmodError> {
+        match FmodError::from_code(self) {
+            None => Ok(()),
+            Some(err) => Err(err),
         }
     }
 }
@@ -267,11 +185,36 @@ bitflags::bitflags! {
     }
 }
 
-/// A builder struct for initializing the FMOD Studio System. At current we don't
-/// really have any options to set here in between `create` and `initialize` but
-/// they'll be implemented eventually.
+/// Where FMOD sends its mixed audio. Selected with [`FmodSystemBuilder::output`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OutputType {
+    /// Pick the best output for the current platform. The default.
+    AutoDetect,
+    /// Mix in real time, but don't play anything. Needs no audio hardware or drivers, so this
+    /// is the output to use for tests and CI; every other part of the API works as usual.
+    NoSound,
+    /// Like [`OutputType::NoSound`], but mix only when [`Fmod::update`] is called rather than in
+    /// real time, so that playback advances deterministically with updates.
+    NoSoundNrt,
+}
+
+impl From<OutputType> for FMOD_OUTPUTTYPE {
+    fn from(output: OutputType) -> Self {
+        match output {
+            OutputType::AutoDetect => FMOD_OUTPUTTYPE_FMOD_OUTPUTTYPE_AUTODETECT,
+            OutputType::NoSound => FMOD_OUTPUTTYPE_FMOD_OUTPUTTYPE_NOSOUND,
+            OutputType::NoSoundNrt => FMOD_OUTPUTTYPE_FMOD_OUTPUTTYPE_NOSOUND_NRT,
+        }
+    }
+}
+
+/// A builder struct for initializing the FMOD Studio System. Options set here are applied by
+/// `initialize`, before FMOD itself is initialized.
 pub struct FmodSystemBuilder {
     system: *mut FMOD_STUDIO_SYSTEM,
+    output: Option<OutputType>,
+    callback_queue_capacity: Option<usize>,
+    callback_overflow_policy: OverflowPolicy,
 }
 
 impl FmodSystemBuilder {
@@ -283,7 +226,42 @@ impl FmodSystemBuilder {
             FMOD_Studio_System_Create(&mut system, FMOD_VERSION).check_err()?;
         }
 
-        Ok(Self { system })
+        Ok(Self {
+            system,
+            output: None,
+            callback_queue_capacity: None,
+            callback_overflow_policy: OverflowPolicy::default(),
+        })
+    }
+
+    /// Choose where FMOD sends its output. Use [`OutputType::NoSound`] to run without audio
+    /// hardware, such as in tests and CI.
+    pub fn output(mut self, output: OutputType) -> Self {
+        self.output = Some(output);
+        self
+    }
+
+    /// Bound the queue which event callbacks wait in until [`Fmod::flush_callbacks`], so that a
+    /// flood of callbacks between flushes can't grow it without limit. Unbounded by default.
+    pub fn callback_queue_capacity(mut self, capacity: usize) -> Self {
+        self.callback_queue_capacity = Some(capacity);
+        self
+    }
+
+    /// Choose what happens to callbacks fired while a bounded callback queue is full. Defaults
+    /// to [`OverflowPolicy::DropOldest`], which never blocks FMOD's threads.
+    pub fn callback_overflow_policy(mut self, policy: OverflowPolicy) -> Self {
+        self.callback_overflow_policy = policy;
+        self
+    }
+
+    /// Use [`OutputType::NoSound`] if the `HV_FMOD_NOSOUND` environment variable is set, so that
+    /// a game can be run headlessly without any changes to its code.
+    pub fn output_from_env(self) -> Self {
+        match std::env::var_os("HV_FMOD_NOSOUND") {
+            Some(_) => self.output(OutputType::NoSound),
+            None => self,
+        }
     }
 
     /// Initialize the builder's FMOD studio system object, finishing the building
@@ -302,6 +280,13 @@ impl FmodSystemBuilder {
         );
 
         unsafe {
+            if let Some(output) = self.output {
+                // The output type has to be set on the core system before it's initialized.
+                let mut core_system = ptr::null_mut();
+                FMOD_Studio_System_GetCoreSystem(self.system, &mut core_system).check_err()?;
+                FMOD_System_SetOutput(core_system, output.into()).check_err()?;
+            }
+
             FMOD_Studio_System_Initialize(
                 self.system,
                 max_channels as i32,
@@ -312,13 +297,11 @@ impl FmodSystemBuilder {
             .check_err()?;
         }
 
-        let (cq_send, cq_recv) = std::sync::mpsc::channel();
         let fmod = Fmod {
             ptr: self.system,
             callbacks: Mutex::new(Arena::new()),
             cleanup: Shared::new(AtomicBitSet::new()),
-            cq_recv,
-            cq_send,
+            cq: CallbackQueue::new(self.callback_queue_capacity, self.callback_overflow_policy),
         };
 
         Ok(fmod)
@@ -332,11 +315,10 @@ impl FmodSystemBuilder {
 pub struct Fmod {
     pub(crate) ptr: *mut FMOD_STUDIO_SYSTEM,
 
-    callbacks: Mutex<Arena<LuaRegistryKey>>,
+    callbacks: Mutex<Arena<QueuedCallback>>,
     cleanup: Shared<AtomicBitSet>,
 
-    pub(crate) cq_recv: Receiver<(Index, EventInstance, EventCallbackInfo)>,
-    pub(crate) cq_send: Sender<(Index, EventInstance, EventCallbackInfo)>,
+    pub(crate) cq: CallbackQueue<(Index, EventInstance, EventCallbackInfo)>,
 }
 
 // FMOD Studio API is thread safe by default, and we panic if we see something which
@@ -363,27 +345,54 @@ impl Fmod {
         Ok(())
     }
 
-    /// If callbacks are registered through the Lua system, then their execution
-    /// is deferred by sending their parameters into a queue in the `Fmod` object
-    /// and then flushing the queue with this method and calling all the relevant
-    /// Lua closures.
+    /// Where FMOD is sending its output.
+    pub fn get_output(&self) -> Result<OutputType> {
+        let mut output = 0;
+        unsafe {
+            let mut core_system = ptr::null_mut();
+            FMOD_Studio_System_GetCoreSystem(self.ptr, &mut core_system).check_err()?;
+            FMOD_System_GetOutput(core_system, &mut output).check_err()?;
+        }
+
+        match output {
+            FMOD_OUTPUTTYPE_FMOD_OUTPUTTYPE_NOSOUND => Ok(OutputType::NoSound),
+            FMOD_OUTPUTTYPE_FMOD_OUTPUTTYPE_NOSOUND_NRT => Ok(OutputType::NoSoundNrt),
+            _ => Ok(OutputType::AutoDetect),
+        }
+    }
+
+    /// If callbacks are registered through the Lua system or through
+    /// [`EventInstance::set_rust_callback`], then their execution is deferred by
+    /// sending their parameters into a queue in the `Fmod` object and then
+    /// flushing the queue with this method and calling all the relevant Lua and
+    /// Rust closures, on the calling thread.
+    ///
+    /// Only the callbacks queued when this is called are dispatched; any fired while they run are
+    /// left for the next flush.
     pub fn flush_callbacks(&self, lua: &Lua) -> Result<()> {
-        {
-            let callbacks = &mut self.callbacks.lock().unwrap();
-            for (_, key) in self
-                .cleanup
-                .borrow_mut()
-                .drain()
-                .filter_map(|i| callbacks.remove_by_slot(i))
-            {
-                lua.remove_registry_value(key)?;
-            }
+        enum Dispatch<'lua> {
+            Lua(LuaFunction<'lua>),
+            Rust(SharedRustCallback),
         }
 
-        for (index, event_instance, event_info) in self.cq_recv.try_iter() {
+        for (index, event_instance, event_info) in self.cq.drain() {
+            // The arena lock must not be held while calling the callback, since it may well
+            // register another one.
             let cb = {
                 let callbacks = &self.callbacks.lock().unwrap();
-                lua.registry_value::<LuaFunction>(&callbacks[index])?
+                match callbacks.get(index) {
+                    Some(QueuedCallback::Lua(key)) => Dispatch::Lua(lua.registry_value(key)?),
+                    Some(QueuedCallback::Rust(cb)) => Dispatch::Rust(cb.clone()),
+                    None => continue,
+                }
+            };
+
+            let cb = match cb {
+                Dispatch::Lua(cb) => cb,
+                Dispatch::Rust(cb) => {
+                    (*cb.lock().unwrap())(event_info);
+                    continue;
+                }
             };
 
             use EventCallbackInfo::*;
@@ -415,9 +424,28 @@ impl Fmod {
             }
         }
 
+        // Clean up after dispatching, so that events queued right before a callback was dropped
+        // (such as an instance's `Destroyed` event) are still delivered.
+        let callbacks = &mut self.callbacks.lock().unwrap();
+        for (_, cb) in self
+            .cleanup
+            .borrow_mut()
+            .drain()
+            .filter_map(|i| callbacks.remove_by_slot(i))
+        {
+            if let QueuedCallback::Lua(key) = cb {
+                lua.remove_registry_value(key)?;
+            }
+        }
+
         Ok(())
     }
 
+    /// The number of callbacks waiting to be dispatched by the next [`Fmod::flush_callbacks`].
+    pub fn pending_callback_count(&self) -> usize {
+        self.cq.len()
+    }
+
     /// Load a bank file from a path, relative to your current directory. Banks will not be
     /// unloaded by dropping the `Bank` object, and must be manually released if desired either
     /// through `Bank::unload` or `Fmod::unloadAll`.
@@ -519,7 +547,7 @@ impl Fmod {
         }
     }
 
-    pub(crate) fn insert_callback(&self, callback: LuaRegistryKey) -> CallbackDropGuard {
+    pub(crate) fn insert_callback(&self, callback: QueuedCallback) -> CallbackDropGuard {
         CallbackDropGuard {
             cleanup: self.cleanup.clone(),
             index: self.callbacks.lock().unwrap().insert(callback),
@@ -543,6 +571,24 @@ impl LuaResource for Fmod {
     const REGISTRY_KEY: &'static str = "HV_FMOD";
 }
 
+pub(crate) type SharedRustCallback = Arc<Mutex<Box<dyn FnMut(EventCallbackInfo) + Send>>>;
+
+/// A callback waiting in the `Fmod` object for events to be flushed to it by
+/// `Fmod::flush_callbacks`.
+pub(crate) enum QueuedCallback {
+    Lua(LuaRegistryKey),
+    Rust(SharedRustCallback),
+}
+
+impl fmt::Debug for QueuedCallback {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Lua(key) => f.debug_tuple("Lua").field(key).finish(),
+            Self::Rust(_) => f.debug_tuple("Rust").finish(),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct CallbackDropGuard {
     cleanup: Shared<AtomicBitSet>,
@@ -563,11 +609,12 @@ impl Plugin for HvFmodPlugin {
     }
 
     fn open<'lua>(&self, lua: &'lua Lua, engine: &Engine) -> Result<LuaTable<'lua>> {
-        let fmod_resource = engine.insert(FmodSystemBuilder::create()?.initialize(
+        let fmod = FmodSystemBuilder::create()?.output_from_env().initialize(
             1024,
             FmodStudioInitFlags::NORMAL,
             FmodCoreInitFlags::NORMAL,
-        )?);
+        )?;
+        let fmod_resource = engine.insert(fmod);
         lua.insert_resource(fmod_resource.clone())?;
 
         let fmod = fmod_resource.clone();
@@ -685,4 +732,64 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn no_sound_system_initializes_without_audio_hardware() -> Result<()> {
+        let fmod = FmodSystemBuilder::create()?
+            .output(OutputType::NoSound)
+            .initialize(32, FmodStudioInitFlags::NORMAL, FmodCoreInitFlags::NORMAL)?;
+        assert_eq!(fmod.get_output()?, OutputType::NoSound);
+        fmod.update()?;
+
+        // Everything else behaves as it would with real output.
+        let err = fmod
+            .load_bank_file("no/such/file.bank", LoadBankFlags::NORMAL)
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<FmodError>().map(FmodError::kind),
+            Some(FmodErrorKind::NotFound)
+        );
+        assert_eq!(fmod.get_bank_count()?, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn rust_callback_receives_queued_events() -> Result<()> {
+        let fmod = FmodSystemBuilder::create()?
+            .output(OutputType::NoSound)
+            .initialize(32, FmodStudioInitFlags::NORMAL, FmodCoreInitFlags::NORMAL)?;
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let callback: Box<dyn FnMut(EventCallbackInfo) + Send> = Box::new({
+            let received = received.clone();
+            move |info| received.lock().unwrap().push(info)
+        });
+        let cb_guard = fmod.insert_callback(QueuedCallback::Rust(Arc::new(Mutex::new(callback))));
+
+        // Stand in for FMOD's thread, which would only ever see the queue.
+        let instance = EventInstance {
+            ptr: ptr::null_mut(),
+        };
+        fmod.cq
+            .push((cb_guard.index, instance, EventCallbackInfo::Started));
+        assert!(received.lock().unwrap().is_empty());
+        assert_eq!(fmod.pending_callback_count(), 1);
+
+        fmod.flush_callbacks(&Lua::new())?;
+        assert!(matches!(
+            received.lock().unwrap().as_slice(),
+            [EventCallbackInfo::Started]
+        ));
+
+        // Events queued before the callback is dropped are still delivered, and then it's gone.
+        fmod.cq
+            .push((cb_guard.index, instance, EventCallbackInfo::Stopped));
+        drop(cb_guard);
+        fmod.flush_callbacks(&Lua::new())?;
+        assert_eq!(received.lock().unwrap().len(), 2);
+        assert!(fmod.callbacks.lock().unwrap().is_empty());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,416 @@
+//! Background music with cross-fading between tracks.
+//!
+//! A [`MusicManager`] keeps track of a single "current" track, fading it in when it's started and
+//! fading out whatever was playing before it. Fades are driven by [`MusicManager::update`], which
+//! should be called once per frame; once a fading-out track reaches silence, it's stopped and
+//! released.
+//!
+//! From Lua, the manager for FMOD event instances is available as `hv.music`:
+//!
+//! ```lua
+//! hv.music.add_track("level2", "event:/Music/Level2")
+//! hv.music.play("level2", 1.5)
+//! -- ... and every frame:
+//! hv.music.update(dt)
+//! ```
+
+use std::collections::HashMap;
+
+use hv_core::{
+    engine::{Engine, LuaExt, LuaResource},
+    plugins::Plugin,
+    prelude::*,
+};
+
+use crate::{EventInstance, Fmod, StopMode};
+
+/// A playing piece of music which a [`MusicManager`] can fade in and out. This is implemented for
+/// [`EventInstance`], and mostly exists so that fading can be tested without loading FMOD.
+pub trait MusicTrack {
+    /// Start playing the track.
+    fn start(&self) -> Result<()>;
+
+    /// Set the track's volume, from `0.` (silent) to `1.` (full volume.)
+    fn set_volume(&self, volume: f32) -> Result<()>;
+
+    /// Stop the track immediately and release any resources associated with it. The track won't
+    /// be used again after this is called.
+    fn stop_and_release(&self) -> Result<()>;
+}
+
+impl MusicTrack for EventInstance {
+    fn start(&self) -> Result<()> {
+        EventInstance::start(self)
+    }
+
+    fn set_volume(&self, volume: f32) -> Result<()> {
+        EventInstance::set_volume(self, volume)
+    }
+
+    fn stop_and_release(&self) -> Result<()> {
+        self.stop(StopMode::Immediate)?;
+        self.release()
+    }
+}
+
+#[derive(Debug)]
+struct Fade<T> {
+    name: String,
+    track: T,
+    volume: f32,
+    /// Change in volume per second; positive when fading in, negative when fading out.
+    rate: f32,
+}
+
+impl<T: MusicTrack> Fade<T> {
+    /// Start fading in a silent track over `duration` seconds.
+    fn new(name: String, track: T, duration: f32) -> Self {
+        let mut fade = Self {
+            name,
+            track,
+            volume: 0.,
+            rate: 0.,
+        };
+        fade.fade_in(duration);
+        fade
+    }
+
+    fn fade_in(&mut self, duration: f32) {
+        self.rate = Self::rate(duration);
+    }
+
+    fn fade_out(&mut self, duration: f32) {
+        self.rate = -Self::rate(duration);
+    }
+
+    /// The rate needed to fade across the whole volume range in `duration` seconds. A non-positive
+    /// duration jumps straight to the end of the fade on the next update.
+    fn rate(duration: f32) -> f32 {
+        if duration > 0. {
+            duration.recip()
+        } else {
+            f32::INFINITY
+        }
+    }
+
+    fn step(&mut self, dt: f32) -> Result<()> {
+        self.volume = (self.volume + self.rate * dt).clamp(0., 1.);
+        if !self.volume.is_finite() {
+            // Only possible when an infinite rate was multiplied by a zero timestep.
+            self.volume = if self.rate > 0. { 1. } else { 0. };
+        }
+        self.track.set_volume(self.volume)
+    }
+}
+
+/// Cross-fades background music between tracks. See the [module-level docs](self).
+#[derive(Debug)]
+pub struct MusicManager<T: MusicTrack> {
+    current: Option<Fade<T>>,
+    fading_out: Vec<Fade<T>>,
+    paths: HashMap<String, String>,
+}
+
+impl<T: MusicTrack> Default for MusicManager<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: MusicTrack> MusicManager<T> {
+    /// Create a music manager with nothing playing.
+    pub fn new() -> Self {
+        Self {
+            current: None,
+            fading_out: Vec::new(),
+            paths: HashMap::new(),
+        }
+    }
+
+    /// Register a short name for a track. Names passed to [`MusicManager::play`] are looked up
+    /// here first, and used as-is if they aren't registered.
+    pub fn add_track(&mut self, name: &str, path: &str) {
+        self.paths.insert(name.to_owned(), path.to_owned());
+    }
+
+    /// Resolve a track name registered with [`MusicManager::add_track`] to its path.
+    pub fn resolve<'a>(&'a self, name: &'a str) -> &'a str {
+        self.paths.get(name).map(String::as_str).unwrap_or(name)
+    }
+
+    /// The name of the current track, if any. A track which is being faded out by
+    /// [`MusicManager::stop`] or by another track starting is not current.
+    pub fn current(&self) -> Option<&str> {
+        self.current.as_ref().map(|fade| fade.name.as_str())
+    }
+
+    /// Whether any fades are still in progress.
+    pub fn is_fading(&self) -> bool {
+        !self.fading_out.is_empty() || matches!(&self.current, Some(fade) if fade.volume < 1.)
+    }
+
+    /// Cross-fade to the track with the given name over `duration` seconds. If the track is
+    /// already current, this does nothing. If it's still fading out from an earlier switch, it's
+    /// faded back in from its current volume rather than restarted; otherwise `create` is called
+    /// with the name resolved through [`MusicManager::add_track`] to create a new track, which is
+    /// started silent.
+    ///
+    /// Any fade-in in progress is cancelled, and the interrupted track fades out from wherever it
+    /// had gotten to.
+    pub fn play_with(
+        &mut self,
+        name: &str,
+        duration: f32,
+        create: impl FnOnce(&str) -> Result<T>,
+    ) -> Result<()> {
+        if self.current() == Some(name) {
+            return Ok(());
+        }
+
+        let incoming = match self.fading_out.iter().position(|fade| fade.name == name) {
+            Some(i) => {
+                let mut fade = self.fading_out.swap_remove(i);
+                fade.fade_in(duration);
+                fade
+            }
+            None => {
+                let track = create(self.resolve(name))?;
+                track.set_volume(0.)?;
+                track.start()?;
+                Fade::new(name.to_owned(), track, duration)
+            }
+        };
+
+        self.fade_out_current(duration);
+        self.current = Some(incoming);
+
+        Ok(())
+    }
+
+    /// Fade out the current track over `duration` seconds, leaving nothing playing.
+    pub fn stop(&mut self, duration: f32) {
+        self.fade_out_current(duration);
+    }
+
+    fn fade_out_current(&mut self, duration: f32) {
+        if let Some(mut outgoing) = self.current.take() {
+            outgoing.fade_out(duration);
+            self.fading_out.push(outgoing);
+        }
+    }
+
+    /// Advance all fades by `dt` seconds. Tracks which have faded out completely are stopped and
+    /// released.
+    pub fn update(&mut self, dt: f32) -> Result<()> {
+        if let Some(current) = &mut self.current {
+            if current.volume < 1. {
+                current.step(dt)?;
+            }
+        }
+
+        for fade in &mut self.fading_out {
+            fade.step(dt)?;
+        }
+
+        let mut i = 0;
+        while i < self.fading_out.len() {
+            if self.fading_out[i].volume <= 0. {
+                self.fading_out.swap_remove(i).track.stop_and_release()?;
+            } else {
+                i += 1;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl MusicManager<EventInstance> {
+    /// Cross-fade to an FMOD event, creating an instance of it if necessary. See
+    /// [`MusicManager::play_with`].
+    pub fn play(&mut self, fmod: &Fmod, name: &str, duration: f32) -> Result<()> {
+        self.play_with(name, duration, |path| {
+            fmod.get_event(path)
+                .and_then(|event| event.create_instance())
+                .with_context(|| format!("error creating music track `{}`", name))
+        })
+    }
+}
+
+impl LuaUserData for MusicManager<EventInstance> {}
+
+impl LuaResource for MusicManager<EventInstance> {
+    const REGISTRY_KEY: &'static str = "HV_FMOD_MUSIC";
+}
+
+struct MusicPlugin;
+
+impl Plugin for MusicPlugin {
+    fn name(&self) -> &'static str {
+        "music"
+    }
+
+    fn open<'lua>(&self, lua: &'lua Lua, engine: &Engine) -> Result<LuaTable<'lua>> {
+        let music_resource = engine.insert(MusicManager::<EventInstance>::new());
+        lua.insert_resource(music_resource.clone())?;
+
+        let music = music_resource.clone();
+        let add_track = lua.create_function(move |_, (name, path): (LuaString, LuaString)| {
+            music.borrow_mut().add_track(name.to_str()?, path.to_str()?);
+            Ok(())
+        })?;
+
+        let music = music_resource.clone();
+        let play =
+            lua.create_function(move |lua, (name, duration): (LuaString, Option<f32>)| {
+                let fmod = lua.get_resource::<Fmod>()?;
+                let fmod = fmod.borrow();
+                music
+                    .borrow_mut()
+                    .play(&fmod, name.to_str()?, duration.unwrap_or(0.))
+                    .to_lua_err()
+            })?;
+
+        let music = music_resource.clone();
+        let stop = lua.create_function(move |_, duration: Option<f32>| {
+            music.borrow_mut().stop(duration.unwrap_or(0.));
+            Ok(())
+        })?;
+
+        let music = music_resource.clone();
+        let update =
+            lua.create_function(move |_, dt: f32| music.borrow_mut().update(dt).to_lua_err())?;
+
+        let music = music_resource.clone();
+        let current =
+            lua.create_function(move |_, ()| Ok(music.borrow().current().map(str::to_owned)))?;
+
+        let music = music_resource;
+        let is_fading = lua.create_function(move |_, ()| Ok(music.borrow().is_fading()))?;
+
+        Ok(lua
+            .load(mlua::chunk! {
+                {
+                    add_track = $add_track,
+                    play = $play,
+                    stop = $stop,
+                    update = $update,
+                    current = $current,
+                    is_fading = $is_fading,
+                }
+            })
+            .eval()?)
+    }
+
+    fn load<'lua>(&self, lua: &'lua Lua, _engine: &Engine) -> Result<()> {
+        lua.load(mlua::chunk! {
+            hv.music = hv.plugins.music
+        })
+        .exec()?;
+
+        Ok(())
+    }
+}
+
+hv_core::plugin!(MusicPlugin);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, Default)]
+    struct TrackState {
+        started: bool,
+        volume: f32,
+        released: bool,
+    }
+
+    #[derive(Debug, Clone)]
+    struct FakeTrack(Arc<Mutex<TrackState>>);
+
+    impl MusicTrack for FakeTrack {
+        fn start(&self) -> Result<()> {
+            self.0.lock().unwrap().started = true;
+            Ok(())
+        }
+
+        fn set_volume(&self, volume: f32) -> Result<()> {
+            let mut state = self.0.lock().unwrap();
+            ensure!(!state.released, "set_volume on a released track");
+            state.volume = volume;
+            Ok(())
+        }
+
+        fn stop_and_release(&self) -> Result<()> {
+            let mut state = self.0.lock().unwrap();
+            ensure!(!state.released, "track released twice");
+            state.released = true;
+            Ok(())
+        }
+    }
+
+    /// Play a track through the manager, returning a handle to the fake track's state if one was
+    /// created.
+    fn play(
+        music: &mut MusicManager<FakeTrack>,
+        name: &str,
+        duration: f32,
+    ) -> Option<Arc<Mutex<TrackState>>> {
+        let mut created = None;
+        music
+            .play_with(name, duration, |_| {
+                let state = Arc::new(Mutex::new(TrackState::default()));
+                created = Some(state.clone());
+                Ok(FakeTrack(state))
+            })
+            .unwrap();
+        created
+    }
+
+    #[test]
+    fn cross_fade_releases_old_track() {
+        let mut music = MusicManager::new();
+        let level1 = play(&mut music, "level1", 0.).unwrap();
+        music.update(1. / 60.).unwrap();
+        assert_eq!(level1.lock().unwrap().volume, 1.);
+
+        let level2 = play(&mut music, "level2", 1.).unwrap();
+        assert!(level2.lock().unwrap().started);
+        assert_eq!(music.current(), Some("level2"));
+
+        music.update(0.5).unwrap();
+        assert!((level1.lock().unwrap().volume - 0.5).abs() < 1e-6);
+        assert!((level2.lock().unwrap().volume - 0.5).abs() < 1e-6);
+        assert!(!level1.lock().unwrap().released);
+
+        music.update(0.5).unwrap();
+        assert!(level1.lock().unwrap().released);
+        assert_eq!(level2.lock().unwrap().volume, 1.);
+        assert!(!music.is_fading());
+    }
+
+    #[test]
+    fn rapid_switches_cancel_in_flight_fades() {
+        let mut music = MusicManager::new();
+        let a = play(&mut music, "a", 0.).unwrap();
+        music.update(0.).unwrap();
+
+        let b = play(&mut music, "b", 1.).unwrap();
+        music.update(0.25).unwrap();
+
+        // Switching back to `a` mid-fade revives it rather than starting a second instance, and
+        // `b` starts fading out from where its fade-in got to.
+        assert!(play(&mut music, "a", 1.).is_none());
+        music.update(0.25).unwrap();
+        assert!((a.lock().unwrap().volume - 1.).abs() < 1e-6);
+        assert!(b.lock().unwrap().volume.abs() < 1e-6);
+        assert!(b.lock().unwrap().released);
+        assert!(!a.lock().unwrap().released);
+        assert!(!music.is_fading());
+
+        // Playing the current track again is a no-op.
+        assert!(play(&mut music, "a", 1.).is_none());
+        assert_eq!(music.current(), Some("a"));
+    }
+}
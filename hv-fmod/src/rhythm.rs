@@ -0,0 +1,223 @@
+//! Beat tracking for syncing gameplay to music, for rhythm games and the like.
+//!
+//! FMOD reports beats through `TimelineBeat` event callbacks, but those are only seen when
+//! [`Fmod::flush_callbacks`](crate::Fmod::flush_callbacks) is called, which can be some time after
+//! the beat actually happened. A [`BeatScheduler`] records the timeline position each beat was
+//! reported at, and is then updated every frame with the event instance's *current* timeline
+//! position; comparing the two tells us exactly how far we are from the last beat, regardless of
+//! when the callback got through.
+//!
+//! ```ignore
+//! let scheduler = Arc::new(Mutex::new(BeatScheduler::new(50.)));
+//! let cb_scheduler = scheduler.clone();
+//! music.set_rust_callback(
+//!     &fmod,
+//!     EventCallbackMask::TIMELINE_BEAT,
+//!     Box::new(move |info| cb_scheduler.lock().unwrap().handle_event(&info)),
+//! )?;
+//!
+//! // ... and every frame, after `flush_callbacks`:
+//! let mut scheduler = scheduler.lock().unwrap();
+//! for action in scheduler.update(music.get_timeline_position()?) {
+//!     // ...
+//! }
+//! ```
+
+use crate::{EventCallbackInfo, TimelineBeatProperties};
+
+#[derive(Debug)]
+struct Scheduled<T> {
+    /// The timeline position the action was scheduled at; it fires on the first beat after this.
+    after: f32,
+    /// Where we expect that beat to be, or `None` if no beats have been seen yet.
+    target: Option<f32>,
+    action: T,
+}
+
+/// Tracks beats reported by an FMOD event's timeline, answering whether the music is currently on
+/// a beat and firing actions scheduled for the next beat. All positions are timeline positions in
+/// milliseconds.
+///
+/// The length of a beat is taken from the tempo reported by the most recent beat, so tempo changes
+/// take effect as soon as the first beat at the new tempo is recorded. Until then, the next beat is
+/// predicted at the old tempo; once the beat is actually recorded, any pending actions waiting on
+/// it are moved to its real position.
+#[derive(Debug)]
+pub struct BeatScheduler<T> {
+    tolerance: f32,
+    last_beat: Option<TimelineBeatProperties>,
+    position: f32,
+    scheduled: Vec<Scheduled<T>>,
+}
+
+impl<T> BeatScheduler<T> {
+    /// Create a scheduler which considers anything within `tolerance` milliseconds of a beat to be
+    /// on that beat.
+    pub fn new(tolerance: f32) -> Self {
+        Self {
+            tolerance,
+            last_beat: None,
+            position: 0.,
+            scheduled: Vec::new(),
+        }
+    }
+
+    /// Record a beat reported by FMOD.
+    pub fn record_beat(&mut self, beat: TimelineBeatProperties) {
+        let position = beat.position as f32;
+        for scheduled in &mut self.scheduled {
+            if scheduled.after < position {
+                scheduled.target = Some(scheduled.target.map_or(position, |t| t.min(position)));
+            }
+        }
+
+        self.last_beat = Some(beat);
+    }
+
+    /// Record the beat if the event is a `TimelineBeat`, and ignore it otherwise. Convenient for
+    /// forwarding events straight from an event callback.
+    pub fn handle_event(&mut self, info: &EventCallbackInfo) {
+        if let EventCallbackInfo::TimelineBeat(beat) = info {
+            self.record_beat(*beat);
+        }
+    }
+
+    /// The most recently recorded beat, if any.
+    pub fn last_beat(&self) -> Option<&TimelineBeatProperties> {
+        self.last_beat.as_ref()
+    }
+
+    /// The length of a beat at the current tempo, in milliseconds.
+    pub fn beat_length(&self) -> Option<f32> {
+        self.last_beat
+            .as_ref()
+            .filter(|beat| beat.tempo > 0.)
+            .map(|beat| 60_000. / beat.tempo)
+    }
+
+    /// How far into the current beat the timeline is, in milliseconds.
+    pub fn phase(&self) -> Option<f32> {
+        let beat = self.last_beat.as_ref()?;
+        let since = self.position - beat.position as f32;
+        Some(since.rem_euclid(self.beat_length()?))
+    }
+
+    /// The predicted timeline position of the first beat after the current position.
+    pub fn next_beat_position(&self) -> Option<f32> {
+        Some(self.position - self.phase()? + self.beat_length()?)
+    }
+
+    /// Whether the current timeline position is within the tolerance window of a beat, either
+    /// just after the last one or just before the next.
+    pub fn on_beat(&self) -> bool {
+        match (self.phase(), self.beat_length()) {
+            (Some(phase), Some(length)) => {
+                phase <= self.tolerance || length - phase <= self.tolerance
+            }
+            _ => false,
+        }
+    }
+
+    /// Schedule an action to fire on the next beat. If no beats have been recorded yet, it fires
+    /// on the first one.
+    pub fn schedule(&mut self, action: T) {
+        self.scheduled.push(Scheduled {
+            after: self.position,
+            target: self.next_beat_position(),
+            action,
+        });
+    }
+
+    /// The number of actions waiting for their beat.
+    pub fn pending(&self) -> usize {
+        self.scheduled.len()
+    }
+
+    /// Update the scheduler with the event instance's current timeline position, as returned by
+    /// `EventInstance::get_timeline_position`, returning every scheduled action whose beat has
+    /// been reached in the order they were scheduled. This should be called once per update, after
+    /// flushing callbacks.
+    pub fn update(&mut self, timeline_position: u32) -> Vec<T> {
+        self.position = timeline_position as f32;
+
+        let position = self.position;
+        let (due, pending) = self
+            .scheduled
+            .drain(..)
+            .partition::<Vec<_>, _>(|s| s.target.map_or(false, |target| target <= position));
+        self.scheduled = pending;
+
+        due.into_iter().map(|s| s.action).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn beat(beat: i32, position: i32, tempo: f32) -> EventCallbackInfo {
+        EventCallbackInfo::TimelineBeat(TimelineBeatProperties {
+            bar: 1,
+            beat,
+            position,
+            tempo,
+            time_signature_numerator: 4,
+            time_signature_denominator: 4,
+        })
+    }
+
+    #[test]
+    fn on_beat_within_tolerance_and_across_tempo_changes() {
+        let mut scheduler = BeatScheduler::<()>::new(50.);
+        scheduler.update(0);
+        assert!(!scheduler.on_beat());
+
+        // 120 BPM: a beat every 500ms. The callback only gets through 20ms late.
+        scheduler.handle_event(&beat(1, 0, 120.));
+        scheduler.update(20);
+        assert!(scheduler.on_beat());
+        scheduler.update(250);
+        assert!(!scheduler.on_beat());
+        // Approaching the next beat before its callback arrives.
+        scheduler.update(470);
+        assert!(scheduler.on_beat());
+        // Past the next beat, even though its callback still hasn't arrived.
+        scheduler.update(530);
+        assert!(scheduler.on_beat());
+        scheduler.update(600);
+        assert!(!scheduler.on_beat());
+
+        // Slowing down to 60 BPM: a beat every second.
+        scheduler.handle_event(&beat(3, 1000, 60.));
+        scheduler.update(1500);
+        assert!(!scheduler.on_beat());
+        scheduler.update(1960);
+        assert!(scheduler.on_beat());
+    }
+
+    #[test]
+    fn scheduled_actions_fire_on_the_next_beat() {
+        let mut scheduler = BeatScheduler::new(50.);
+
+        // Nothing to go on yet, so this waits for the first beat.
+        scheduler.schedule("first");
+        assert!(scheduler.update(100).is_empty());
+        scheduler.handle_event(&beat(1, 500, 120.));
+        assert_eq!(scheduler.update(510), ["first"]);
+
+        scheduler.update(700);
+        scheduler.schedule("second");
+        assert_eq!(scheduler.next_beat_position(), Some(1000.));
+        assert!(scheduler.update(990).is_empty());
+        assert_eq!(scheduler.update(1005), ["second"]);
+
+        // The tempo doubles at 1000; the prediction made at 1200 is too late, but the beat
+        // recorded at 1250 corrects it.
+        scheduler.update(1200);
+        scheduler.schedule("third");
+        assert_eq!(scheduler.next_beat_position(), Some(1500.));
+        scheduler.handle_event(&beat(4, 1250, 240.));
+        assert_eq!(scheduler.update(1260), ["third"]);
+        assert_eq!(scheduler.pending(), 0);
+    }
+}
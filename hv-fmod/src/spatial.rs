@@ -0,0 +1,295 @@
+//! 3D spatialization support, and a bridge between FMOD's listener/event attributes and objects in
+//! a [`Space`].
+//!
+//! Heavy is primarily a 2D framework, so all of the helpers here map an object's 2D [`Position`]
+//! and [`Velocity`] onto the XY plane, with "forward" pointing into the screen (+Z) and "up"
+//! pointing along +Y.
+
+use crate::{CheckError, EventDescription, EventInstance, Fmod, PlaybackState, StopMode};
+use {
+    hv_core::{prelude::*, spaces::Space},
+    hv_fmod_sys::*,
+    hv_friends::{
+        math::{Vector2, Vector3},
+        Position, Velocity,
+    },
+    std::ptr,
+};
+
+/// The position, velocity, and orientation of a 3D sound source or listener. Corresponds to
+/// `FMOD_3D_ATTRIBUTES`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Attributes3D {
+    /// Position in world space.
+    pub position: Vector3<f32>,
+    /// Velocity in world units per second.
+    pub velocity: Vector3<f32>,
+    /// Forwards orientation; must be of unit length and perpendicular to `up`.
+    pub forward: Vector3<f32>,
+    /// Upwards orientation; must be of unit length and perpendicular to `forward`.
+    pub up: Vector3<f32>,
+}
+
+impl Default for Attributes3D {
+    fn default() -> Self {
+        Self {
+            position: Vector3::zeros(),
+            velocity: Vector3::zeros(),
+            forward: Vector3::z(),
+            up: Vector3::y(),
+        }
+    }
+}
+
+impl Attributes3D {
+    /// Construct attributes for an object living on the XY plane, with the default forward/up
+    /// orientation.
+    pub fn from_2d(position: Vector2<f32>, velocity: Vector2<f32>) -> Self {
+        Self {
+            position: Vector3::new(position.x, position.y, 0.),
+            velocity: Vector3::new(velocity.x, velocity.y, 0.),
+            ..Self::default()
+        }
+    }
+
+    /// Construct attributes from an object's [`Position`] and optional [`Velocity`] components.
+    pub fn from_components(position: &Position, velocity: Option<&Velocity>) -> Self {
+        Self::from_2d(
+            position.0.translation.vector,
+            velocity.map(|v| v.0.linear).unwrap_or_else(Vector2::zeros),
+        )
+    }
+}
+
+fn to_fmod_vector(v: &Vector3<f32>) -> FMOD_VECTOR {
+    FMOD_VECTOR {
+        x: v.x,
+        y: v.y,
+        z: v.z,
+    }
+}
+
+fn from_fmod_vector(v: &FMOD_VECTOR) -> Vector3<f32> {
+    Vector3::new(v.x, v.y, v.z)
+}
+
+impl From<Attributes3D> for FMOD_3D_ATTRIBUTES {
+    fn from(attrs: Attributes3D) -> Self {
+        FMOD_3D_ATTRIBUTES {
+            position: to_fmod_vector(&attrs.position),
+            velocity: to_fmod_vector(&attrs.velocity),
+            forward: to_fmod_vector(&attrs.forward),
+            up: to_fmod_vector(&attrs.up),
+        }
+    }
+}
+
+impl From<FMOD_3D_ATTRIBUTES> for Attributes3D {
+    fn from(attrs: FMOD_3D_ATTRIBUTES) -> Self {
+        Attributes3D {
+            position: from_fmod_vector(&attrs.position),
+            velocity: from_fmod_vector(&attrs.velocity),
+            forward: from_fmod_vector(&attrs.forward),
+            up: from_fmod_vector(&attrs.up),
+        }
+    }
+}
+
+impl Fmod {
+    /// Set the 3D attributes of the listener with the given index. Most games will only ever use
+    /// listener `0`.
+    pub fn set_listener_attributes(&self, listener: u32, attributes: &Attributes3D) -> Result<()> {
+        let mut attributes = FMOD_3D_ATTRIBUTES::from(*attributes);
+        unsafe {
+            FMOD_Studio_System_SetListenerAttributes(
+                self.ptr,
+                listener as i32,
+                &mut attributes,
+                ptr::null_mut(),
+            )
+            .check_err()?;
+        }
+        Ok(())
+    }
+
+    /// Get the 3D attributes of the listener with the given index.
+    pub fn get_listener_attributes(&self, listener: u32) -> Result<Attributes3D> {
+        let mut attributes = FMOD_3D_ATTRIBUTES::from(Attributes3D::default());
+        unsafe {
+            FMOD_Studio_System_GetListenerAttributes(
+                self.ptr,
+                listener as i32,
+                &mut attributes,
+                ptr::null_mut(),
+            )
+            .check_err()?;
+        }
+        Ok(attributes.into())
+    }
+}
+
+impl EventInstance {
+    /// Set the 3D attributes of this instance. The event must contain a spatializer for this to
+    /// have any audible effect.
+    pub fn set_3d_attributes(&self, attributes: &Attributes3D) -> Result<()> {
+        let mut attributes = FMOD_3D_ATTRIBUTES::from(*attributes);
+        unsafe {
+            FMOD_Studio_EventInstance_Set3DAttributes(self.ptr, &mut attributes).check_err()?;
+        }
+        Ok(())
+    }
+
+    /// Get the 3D attributes of this instance.
+    pub fn get_3d_attributes(&self) -> Result<Attributes3D> {
+        let mut attributes = FMOD_3D_ATTRIBUTES::from(Attributes3D::default());
+        unsafe {
+            FMOD_Studio_EventInstance_Get3DAttributes(self.ptr, &mut attributes).check_err()?;
+        }
+        Ok(attributes.into())
+    }
+}
+
+/// Marker component for the object whose [`Position`] (and [`Velocity`], if present) should drive
+/// FMOD listener `0`. There should only ever be one of these in a space; see
+/// [`update_audio_listener`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AudioListener;
+
+/// A component which plays instances of an event "from" an object. Every frame,
+/// [`update_audio_emitters`] copies the object's position and velocity into the 3D attributes of
+/// all instances started through [`AudioEmitter::play`], and releases instances which have stopped.
+#[derive(Debug, Clone)]
+pub struct AudioEmitter {
+    /// The event this emitter plays.
+    pub event: EventDescription,
+    instances: Vec<EventInstance>,
+}
+
+impl AudioEmitter {
+    /// Create an emitter for the given event. No instances are created until
+    /// [`AudioEmitter::play`] is called.
+    pub fn new(event: EventDescription) -> Self {
+        Self {
+            event,
+            instances: Vec::new(),
+        }
+    }
+
+    /// Create and start a new instance of the emitter's event, tracking it so that its 3D
+    /// attributes follow the emitter's object.
+    pub fn play(&mut self) -> Result<EventInstance> {
+        let instance = self.event.create_instance()?;
+        instance.start()?;
+        self.instances.push(instance);
+        Ok(instance)
+    }
+
+    /// Stop all instances started by this emitter.
+    pub fn stop_all(&self, stop_mode: StopMode) -> Result<()> {
+        for instance in &self.instances {
+            instance.stop(stop_mode)?;
+        }
+        Ok(())
+    }
+
+    /// The instances currently tracked by this emitter.
+    pub fn instances(&self) -> &[EventInstance] {
+        &self.instances
+    }
+}
+
+/// Compute the listener attributes from the [`AudioListener`] object in the space, if there is
+/// one. If more than one object is marked as a listener, a warning is logged and the first one
+/// found is used.
+pub fn listener_attributes(space: &Space) -> Option<Attributes3D> {
+    let mut query = space
+        .query::<(&Position, Option<&Velocity>)>()
+        .with::<AudioListener>();
+    let mut iter = query.iter();
+    let (object, (position, velocity)) = iter.next()?;
+
+    if iter.next().is_some() {
+        log::warn!(
+            "multiple AudioListener objects in space {:?}; using {:?}",
+            space.id(),
+            object
+        );
+    }
+
+    Some(Attributes3D::from_components(position, velocity))
+}
+
+/// Update FMOD listener `0` from the [`AudioListener`] object in the space. Does nothing if there
+/// is no listener.
+pub fn update_audio_listener(space: &Space, fmod: &Fmod) -> Result<()> {
+    if let Some(attributes) = listener_attributes(space) {
+        fmod.set_listener_attributes(0, &attributes)?;
+    }
+
+    Ok(())
+}
+
+/// Update the 3D attributes of every instance played by an [`AudioEmitter`] to match its object,
+/// releasing and forgetting any instances which have stopped.
+pub fn update_audio_emitters(space: &mut Space) -> Result<()> {
+    for (_, (emitter, position, velocity)) in
+        space.query_mut::<(&mut AudioEmitter, &Position, Option<&Velocity>)>()
+    {
+        let attributes = Attributes3D::from_components(position, velocity);
+        let mut err = None;
+        emitter.instances.retain(|instance| {
+            let result = (|| -> Result<bool> {
+                if instance.get_playback_state()? == PlaybackState::Stopped {
+                    instance.release()?;
+                    return Ok(false);
+                }
+
+                instance.set_3d_attributes(&attributes)?;
+                Ok(true)
+            })();
+
+            match result {
+                Ok(keep) => keep,
+                Err(e) => {
+                    err.get_or_insert(e);
+                    false
+                }
+            }
+        });
+
+        if let Some(e) = err {
+            return Err(e);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hv_core::spaces::Spaces;
+    use hv_friends::math::{Position2, Velocity2};
+
+    #[test]
+    fn listener_attributes_from_positioned_object() {
+        let mut spaces = Spaces::new();
+        let space = spaces.create_space();
+        let mut space = space.borrow_mut();
+
+        assert_eq!(listener_attributes(&space), None);
+
+        space.spawn((Position(Position2::translation(3., 4.)),));
+        space.spawn((
+            AudioListener,
+            Position(Position2::translation(-1., 2.)),
+            Velocity(Velocity2::linear(5., -6.)),
+        ));
+
+        let attributes = listener_attributes(&space).unwrap();
+        assert_eq!(attributes.position, Vector3::new(-1., 2., 0.));
+        assert_eq!(attributes.velocity, Vector3::new(5., -6., 0.));
+        assert_eq!(attributes.forward, Vector3::z());
+        assert_eq!(attributes.up, Vector3::y());
+    }
+}
@@ -14,7 +14,7 @@ use hv_friends::{
         MeshBuilder,
     },
     math::*,
-    Position, SimpleHandler, Velocity,
+    tick_lifetimes, Position, SimpleHandler, Velocity,
 };
 
 const CIRCLE_MESH_RADIUS: f32 = 64.;
@@ -34,9 +34,7 @@ pub struct Asteroid;
 pub struct Player;
 
 #[derive(Debug, Clone, Copy)]
-pub struct Bullet {
-    time_left: f32,
-}
+pub struct Bullet;
 
 pub struct Asteroids {
     simple_handler: SimpleHandler,
@@ -59,11 +57,7 @@ impl Asteroids {
         })?;
         let make_asteroid = DynamicComponentConstructor::copy(Asteroid);
         let make_player = DynamicComponentConstructor::copy(Player);
-        let make_bullet = lua.create_function(|_, time_limit| {
-            Ok(DynamicComponentConstructor::copy(Bullet {
-                time_left: time_limit,
-            }))
-        })?;
+        let make_bullet = DynamicComponentConstructor::copy(Bullet);
         let space_ref = space.clone();
 
         lua.load(mlua::chunk! {
@@ -123,25 +117,24 @@ impl EventHandler for Asteroids {
             pos.translation.vector = new_center.coords;
         }
 
-        for (bullet_object, (Position(bullet_pos), bullet, bullet_circle)) in
-            space.query::<(&Position, &mut Bullet, &Circle)>().iter()
+        // Bullets only last a few seconds.
+        tick_lifetimes(&mut space, dt)?;
+
+        for (bullet_object, (Position(bullet_pos), bullet_circle)) in space
+            .query::<(&Position, &Circle)>()
+            .with::<Bullet>()
+            .iter()
         {
-            bullet.time_left -= dt;
-
-            if bullet.time_left <= 0. {
-                self.to_remove.push(bullet_object);
-            } else {
-                for (asteroid_object, (Position(asteroid_pos), asteroid_circle)) in space
-                    .query::<(&Position, &Circle)>()
-                    .with::<Asteroid>()
-                    .iter()
+            for (asteroid_object, (Position(asteroid_pos), asteroid_circle)) in space
+                .query::<(&Position, &Circle)>()
+                .with::<Asteroid>()
+                .iter()
+            {
+                if na::distance_squared(&bullet_pos.center(), &asteroid_pos.center())
+                    < (bullet_circle.radius + asteroid_circle.radius).powi(2)
                 {
-                    if na::distance_squared(&bullet_pos.center(), &asteroid_pos.center())
-                        < (bullet_circle.radius + asteroid_circle.radius).powi(2)
-                    {
-                        self.to_remove.push(bullet_object);
-                        self.to_destroy.push(asteroid_object);
-                    }
+                    self.to_remove.push(bullet_object);
+                    self.to_destroy.push(asteroid_object);
                 }
             }
         }
@@ -74,7 +74,8 @@ function Ship:fire()
         Position(tx:transform_point2(20, 0)),
         Velocity(tx:transform_vector2(bullet_speed, 0)),
         Circle(5, 0, 1, 0),
-        BulletMarker(4)
+        BulletMarker,
+        hf.lifetime(4)
     )
 end
 
@@ -1,13 +1,24 @@
+local hf_camera = require("hf.camera")
 local hf_collision = require("hf.collision")
 local hf_components = require("hf.components")
 local hf_keyboard = require("hf.keyboard")
 local hf_math = require("hf.math")
 local hf_graphics = require("hf.graphics")
+local hf_timeline = require("hf.timeline")
+
+local hf_proximity = assert(hv.plugins.friends.proximity)
 
 return {
+    camera = hf_camera,
     collision = hf_collision,
     components = hf_components,
     keyboard = hf_keyboard,
     math = hf_math,
     graphics = hf_graphics,
+    timeline = hf_timeline,
+
+    animate = hf_timeline.animate,
+    lifetime = hf_components.Lifetime,
+    objects_in_radius = hf_proximity.objects_in_radius,
+    objects_in_box = hf_proximity.objects_in_box,
 }
@@ -0,0 +1,10 @@
+local hf_camera = assert(hv.plugins.friends.camera)
+
+local Camera = {}
+do
+    Camera.new = assert(hf_camera.create_camera_object)
+
+    setmetatable(Camera, { __call = function(_, w, h) return Camera.new(w, h) end })
+end
+
+return { Camera = Camera }
@@ -3,6 +3,9 @@ local hf_collision = assert(hv.plugins.friends.collision)
 local collision = {}
 do
     collision.intersection_test = hf_collision.intersection_test
+    collision.intersects = hf_collision.intersects
+    collision.distance = hf_collision.distance
+    collision.time_of_impact = hf_collision.time_of_impact
 
     local Collider = {}
     do
@@ -16,7 +19,18 @@ do
         Collider.convex_polyline = hf_collision.create_convex_polyline
         Collider.polyline = hf_collision.create_polyline
         Collider.segment = hf_collision.create_segment
+        Collider.box = hf_collision.create_box
+        Collider.polygon = hf_collision.create_polygon
     end
+
+    collision.ball = Collider.ball
+    collision.box = Collider.box
+    collision.polygon = Collider.polygon
+
+    -- Component constructors, for attaching colliders to objects when spawning them.
+    collision.box_collider = hf_collision.box_collider
+    collision.circle_collider = hf_collision.circle_collider
+    collision.polygon_collider = hf_collision.polygon_collider
 end
 
 return collision
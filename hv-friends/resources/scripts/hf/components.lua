@@ -68,6 +68,14 @@ do
         tmp:add_angle(angle)
         set_position2(self, tmp)
     end
+
+    -- Move this object by `velocity` (a Velocity2, angular part in radians per second) over `dt`
+    -- seconds.
+    function Position:position_integrate(velocity, dt)
+        get_position2(self, tmp)
+        tmp:integrate_mut(velocity, dt)
+        set_position2(self, tmp)
+    end
 end
 
 local Velocity = {}
@@ -139,6 +147,49 @@ do
     end
 end
 
+local Lifetime = {}
+do
+    local hf_lifetime = hv.plugins.friends.lifetime
+
+    local hf_create_lifetime_constructor = hf_lifetime.create_lifetime_constructor
+    setmetatable(
+        Lifetime,
+        { __call = function(_, seconds) return hf_create_lifetime_constructor(seconds) end }
+    )
+
+    Lifetime.lifetime_get_remaining = hf_lifetime.get_remaining
+    Lifetime.lifetime_set_remaining = hf_lifetime.set_remaining
+    Lifetime.tick = hf_lifetime.tick_lifetimes
+end
+
+local SubstepCount = {}
+do
+    local hf_substep = hv.plugins.friends.substep
+
+    local hf_create_substep_count_constructor = hf_substep.create_substep_count_constructor
+    setmetatable(
+        SubstepCount,
+        { __call = function(_, count) return hf_create_substep_count_constructor(count or 1) end }
+    )
+
+    SubstepCount.substep_count_get = hf_substep.get_substep_count
+    SubstepCount.substep_count_set = hf_substep.set_substep_count
+end
+
+local ZOrder = {}
+do
+    local hf_z_order = hv.plugins.friends.z_order
+
+    local hf_create_z_order_constructor = hf_z_order.create_z_order_constructor
+    setmetatable(
+        ZOrder,
+        { __call = function(_, z) return hf_create_z_order_constructor(z or 0) end }
+    )
+
+    ZOrder.z_order_get = hf_z_order.get_z_order
+    ZOrder.z_order_set = hf_z_order.set_z_order
+end
+
 local Collider = {}
 do
     local hf_collision = assert(hv.plugins.friends.collision)
@@ -261,7 +312,10 @@ end
 
 return {
     Collider = Collider,
+    Lifetime = Lifetime,
     Position = Position,
     Velocity = Velocity,
     SpriteAnimation = SpriteAnimation,
+    SubstepCount = SubstepCount,
+    ZOrder = ZOrder,
 }
@@ -14,8 +14,13 @@ do
 
     local load_texture_from_filesystem = hf_graphics.load_texture_from_filesystem
 
-    function Texture:init(path) self._texture = load_texture_from_filesystem(path) end
+    function Texture:init(path, options)
+        self._texture = load_texture_from_filesystem(path, options)
+    end
+
     function Texture:draw(instance) self._texture:draw(instance) end
+    function Texture:set_filter(filter) self._texture:set_filter(filter) end
+    function Texture:set_wrap(wrap) self._texture:set_wrap(wrap) end
 end
 
 local reload_textures = hf_graphics.reload_textures
@@ -55,6 +60,9 @@ return {
 
     set_color = hf_graphics.set_color,
 
+    set_y_axis = hf_graphics.set_y_axis,
+    get_y_axis = hf_graphics.get_y_axis,
+
     apply_transform = hf_graphics.apply_transform,
     inverse_transform_point = hf_graphics.inverse_transform_point,
     origin = hf_graphics.origin,
@@ -0,0 +1,37 @@
+local hf_timeline = assert(hv.plugins.friends.timeline)
+
+local create_sequence_object = assert(hf_timeline.create_sequence_object)
+local create_parallel_object = assert(hf_timeline.create_parallel_object)
+
+-- Timelines started with `play` (or `hf.animate`) which haven't finished yet.
+local playing = {}
+
+local function play(timeline)
+    playing[#playing + 1] = timeline
+    return timeline
+end
+
+local function update(dt)
+    local i = 1
+    while i <= #playing do
+        local timeline = playing[i]
+        timeline:update(dt)
+        if timeline:is_finished() then
+            table.remove(playing, i)
+        else
+            i = i + 1
+        end
+    end
+end
+
+local function animate(object, key, from, to, duration, easing)
+    return play(create_sequence_object():animate(object, key, from, to, duration, easing))
+end
+
+return {
+    sequence = create_sequence_object,
+    parallel = create_parallel_object,
+    play = play,
+    update = update,
+    animate = animate,
+}
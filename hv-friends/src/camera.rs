@@ -39,9 +39,36 @@
 //!   if you want the subject to view the place at any other angle, this parameter sets the
 //!   resulting orientation of the calculated transform whenever this focus is the "hot focus" that
 //!   the subject is currently "inside".
+//!
+//! # Deadzone and look-ahead
+//!
+//! Rather than following the subject exactly, the camera can be given a deadzone: a rectangle, in
+//! world units, within which the subject can move around without moving the camera. Once the
+//! subject pushes past an edge of the deadzone, the camera is dragged along with it. The camera
+//! can also look ahead of the subject, offsetting itself in the direction the subject is moving,
+//! by an amount proportional to the subject's velocity. Both are set in [`CameraParameters`], and
+//! are disabled by default.
+//!
+//! How snappily the camera catches up with the subject is controlled by its [`FollowMode`], set
+//! with [`Camera::set_follow`]. By default it follows exactly; it can instead move at a fixed
+//! speed, ease in exponentially, or be pulled along by a spring. All of these behave the same
+//! regardless of frame rate.
+//!
+//! # Screen shake
+//!
+//! The camera shakes according to its "trauma", a value from `0` to `1` which is raised with
+//! [`Camera::add_trauma`] and decays over time. The shake offset is proportional to the square of
+//! the trauma, so small hits give a slight wobble while large ones are violent. Rather than
+//! calling [`Camera::add_trauma`] directly, gameplay code can emit a [`ScreenShakeEvent`] through
+//! [`Engine::emit`]; a camera subscribed with [`Camera::subscribe_to_shake_events`] picks these up
+//! on its next update, so that nothing needs a reference to the camera to shake it.
 use crate::{math::*, parry2d::shape::SharedShape};
 
-use hv_core::mlua::prelude::*;
+use hv_core::{
+    engine::Engine,
+    prelude::*,
+    shrev::{EventChannel, ReaderId},
+};
 use thunderdome::{Arena, Index};
 
 #[derive(Clone)]
@@ -112,6 +139,72 @@ impl Focus {
     }
 }
 
+/// An event asking every subscribed [`Camera`] to shake, by adding `trauma` to its current trauma.
+/// Emit it with [`Engine::emit`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScreenShakeEvent {
+    pub trauma: f32,
+}
+
+/// How the camera moves towards the point it's following. See [`Camera::set_follow`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FollowMode {
+    /// Follow exactly, with no smoothing.
+    Snap,
+    /// Move towards the subject at a constant speed, in world units per second, without
+    /// overshooting.
+    Linear { speed: f32 },
+    /// Close `factor` of the remaining distance every sixtieth of a second, easing in as the
+    /// camera gets closer. `factor` is between `0` (never move) and `1` (snap).
+    Exponential { factor: f32 },
+    /// Pull the camera along with a damped spring. With enough damping (at least
+    /// `2 * stiffness.sqrt()`) the camera settles without overshooting; with less, it wobbles.
+    Spring { stiffness: f32, damping: f32 },
+}
+
+impl Default for FollowMode {
+    fn default() -> Self {
+        FollowMode::Snap
+    }
+}
+
+impl FollowMode {
+    /// Move `current` towards `target` over `dt` seconds, returning the new position. `velocity`
+    /// is only used (and updated) by [`FollowMode::Spring`].
+    fn step(
+        self,
+        current: Point2<f32>,
+        target: Point2<f32>,
+        velocity: &mut Vector2<f32>,
+        dt: f32,
+    ) -> Point2<f32> {
+        let offset = target - current;
+        match self {
+            FollowMode::Snap => target,
+            FollowMode::Linear { speed } => {
+                let distance = offset.norm();
+                let step = speed * dt;
+                if distance <= step {
+                    target
+                } else {
+                    current + offset * (step / distance)
+                }
+            }
+            FollowMode::Exponential { factor } => {
+                // Scaling the exponent by the number of 60Hz frames in `dt` keeps the smoothing
+                // the same no matter how often we're updated.
+                let t = 1. - (1. - factor.clamp(0., 1.)).powf(dt * 60.);
+                current + offset * t
+            }
+            FollowMode::Spring { stiffness, damping } => {
+                // Semi-implicit Euler, which stays stable for the timesteps we care about.
+                *velocity += (offset * stiffness - *velocity * damping) * dt;
+                current + *velocity * dt
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct FocusIndex(Index);
 
@@ -194,6 +287,21 @@ pub struct CameraParameters {
     /// The exponential decay parameter controlling how fast the target transform approaches the
     /// focus transform.
     pub time_constant: f32,
+    /// The width and height of the deadzone, in world units. The deadzone is centered on the point
+    /// the camera is following, and the subject can move freely inside it without moving the
+    /// camera. `None` disables the deadzone, so that the camera follows the subject exactly.
+    pub deadzone: Option<Vector2<f32>>,
+    /// How far ahead of the subject the camera looks, as a number of seconds of the subject's
+    /// current velocity. Zero disables look-ahead.
+    pub look_ahead_time: f32,
+    /// The largest distance, in world units, that look-ahead may offset the camera by.
+    pub max_look_ahead: f32,
+    /// How the camera catches up with the subject.
+    pub follow: FollowMode,
+    /// The distance, in world units, that the camera shakes by at full trauma.
+    pub max_shake_offset: f32,
+    /// How much trauma is lost per second.
+    pub trauma_decay: f32,
 }
 
 impl CameraParameters {
@@ -204,6 +312,12 @@ impl CameraParameters {
             secondary_foci_weight_factor: 0.5,
             idw_power: 2.5,
             time_constant: 1.,
+            deadzone: None,
+            look_ahead_time: 0.,
+            max_look_ahead: f32::INFINITY,
+            follow: FollowMode::Snap,
+            max_shake_offset: 16.,
+            trauma_decay: 1.,
         }
     }
 }
@@ -220,6 +334,26 @@ pub struct Camera {
     /// The current position of the subject. We don't care about orientation of the subject here
     /// because the orientation is determined by the main focus.
     subject_pos: Point2<f32>,
+    /// The point the camera follows instead of the subject itself, which lags behind the subject
+    /// when it's inside the deadzone or being smoothed by the follow mode. `None` until the first
+    /// update, where it snaps to the subject.
+    follow_pos: Option<Point2<f32>>,
+    /// The velocity of the followed point, for [`FollowMode::Spring`].
+    follow_velocity: Vector2<f32>,
+    /// The position of the subject as of the last update, used to estimate its velocity.
+    last_subject_pos: Option<Point2<f32>>,
+    /// The current look-ahead offset, which smoothly approaches the subject's velocity scaled by
+    /// the look-ahead time.
+    look_ahead: Vector2<f32>,
+    /// The current trauma, from zero to one, which determines how hard the camera shakes.
+    trauma: f32,
+    /// Time spent shaking, used to drive the shake's wobble.
+    shake_time: f32,
+    /// The channel of shake events the camera is subscribed to, if any.
+    shake_events: Option<(
+        Shared<EventChannel<ScreenShakeEvent>>,
+        ReaderId<ScreenShakeEvent>,
+    )>,
     /// The base scaling factor.
     base_scale: f32,
     /// The calculated transform, calculated from the position of the subject and the foci. This is
@@ -241,6 +375,13 @@ impl Camera {
             foci: Arena::new(),
             hot_focus: None,
             subject_pos: Point2::origin(),
+            follow_pos: None,
+            follow_velocity: Vector2::zeros(),
+            last_subject_pos: None,
+            look_ahead: Vector2::zeros(),
+            trauma: 0.,
+            shake_time: 0.,
+            shake_events: None,
             base_scale: 1.,
             calculated_tx: Similarity2::identity(),
             target_tx: Similarity2::identity(),
@@ -257,6 +398,26 @@ impl Camera {
         self.subject_pos = subject_pos;
     }
 
+    /// The point the camera is currently centered on when no focus is pulling it elsewhere: the
+    /// subject's position as held back by the deadzone, plus the look-ahead offset.
+    pub fn follow_pos(&self) -> Point2<f32> {
+        self.follow_pos.unwrap_or(self.subject_pos) + self.look_ahead
+    }
+
+    /// Change how the camera catches up with the subject.
+    pub fn set_follow(&mut self, mode: FollowMode) {
+        self.params.follow = mode;
+        self.follow_velocity = Vector2::zeros();
+    }
+
+    pub fn parameters(&self) -> &CameraParameters {
+        &self.params
+    }
+
+    pub fn parameters_mut(&mut self) -> &mut CameraParameters {
+        &mut self.params
+    }
+
     pub fn scale(&self) -> f32 {
         self.base_scale
     }
@@ -265,6 +426,47 @@ impl Camera {
         self.base_scale = scale;
     }
 
+    pub fn trauma(&self) -> f32 {
+        self.trauma
+    }
+
+    /// Add trauma, shaking the camera. Trauma is capped at `1`.
+    pub fn add_trauma(&mut self, trauma: f32) {
+        self.trauma = (self.trauma + trauma).clamp(0., 1.);
+    }
+
+    /// Subscribe to a channel of [`ScreenShakeEvent`]s, usually
+    /// `engine.event_channel::<ScreenShakeEvent>()`. Every event sent since the last update is
+    /// added to the camera's trauma at the start of the next one.
+    pub fn subscribe_to_shake_events(&mut self, channel: Shared<EventChannel<ScreenShakeEvent>>) {
+        let reader = channel.borrow_mut().register_reader();
+        self.shake_events = Some((channel, reader));
+    }
+
+    /// The offset the camera is currently shaken by, in world units.
+    pub fn shake_offset(&self) -> Vector2<f32> {
+        // A cheap deterministic wobble; two incommensurate frequencies keep it from looking like
+        // it's moving along a line.
+        let wobble = Vector2::new(
+            (self.shake_time * 41.).sin(),
+            (self.shake_time * 53. + 1.7).sin(),
+        );
+        wobble * self.params.max_shake_offset * self.trauma * self.trauma
+    }
+
+    fn update_shake(&mut self, dt: f32) {
+        if let Some((channel, reader)) = &mut self.shake_events {
+            let total = channel
+                .borrow()
+                .read(reader)
+                .map(|event| event.trauma)
+                .sum::<f32>();
+            self.add_trauma(total);
+        }
+
+        self.shake_time += dt;
+    }
+
     pub fn insert_focus(&mut self, focus: Focus) -> FocusIndex {
         FocusIndex(self.foci.insert(focus))
     }
@@ -319,7 +521,7 @@ impl Camera {
         // translations.
         let interpolated_translation = match closest_focus {
             Some(closest) if closest_focus_distance == 0. => self.foci[closest].center.coords,
-            _ if total_weight == 0. => self.subject_pos.coords,
+            _ if total_weight == 0. => self.follow_pos().coords,
             _ => total_weighted_translations / total_weight,
         };
 
@@ -355,11 +557,58 @@ impl Camera {
         self.calculated_tx.set_scaling(lerped_scale);
     }
 
+    fn update_follow_pos(&mut self, dt: f32) {
+        // Drag the followed point along just far enough to keep the subject inside the deadzone.
+        // The deadzone is in world units, so it's unaffected by the camera's scale.
+        let target = match (self.follow_pos, self.params.deadzone) {
+            (Some(follow_pos), Some(deadzone)) => {
+                let half_extents = deadzone / 2.;
+                let offset = self.subject_pos - follow_pos;
+                let clamped = Vector2::new(
+                    offset.x.clamp(-half_extents.x, half_extents.x),
+                    offset.y.clamp(-half_extents.y, half_extents.y),
+                );
+                follow_pos + (offset - clamped)
+            }
+            _ => self.subject_pos,
+        };
+
+        let follow_pos = match self.follow_pos {
+            Some(follow_pos) => {
+                self.params
+                    .follow
+                    .step(follow_pos, target, &mut self.follow_velocity, dt)
+            }
+            None => target,
+        };
+        self.follow_pos = Some(follow_pos);
+
+        let velocity = match self.last_subject_pos {
+            Some(last_subject_pos) if dt > 0. => (self.subject_pos - last_subject_pos) / dt,
+            _ => Vector2::zeros(),
+        };
+        self.last_subject_pos = Some(self.subject_pos);
+
+        let mut target_look_ahead = velocity * self.params.look_ahead_time;
+        let distance = target_look_ahead.norm();
+        if distance > self.params.max_look_ahead {
+            target_look_ahead *= self.params.max_look_ahead / distance;
+        }
+
+        // Ease towards the new offset so that the camera doesn't jerk around when the subject
+        // starts, stops, or turns.
+        let t = (self.params.time_constant * dt).clamp(0., 1.);
+        self.look_ahead += (target_look_ahead - self.look_ahead) * t;
+    }
+
     pub fn update(&mut self, dt: f32) {
         if self.transition_state.is_in_flux() {
             self.transition_state.t += dt;
         }
 
+        self.update_follow_pos(dt);
+        self.update_shake(dt);
+
         self.recalculate();
 
         // Performing these lerp assignments creates an exponential decay which causes the
@@ -393,17 +642,20 @@ impl Camera {
         self.world_tx
             .append_scaling_mut(self.target_tx.scaling() * self.base_scale);
         self.world_tx.append_translation_mut(&Translation2::from(
-            self.subject_pos.coords.lerp(
+            self.follow_pos().coords.lerp(
                 &self.target_tx.isometry.translation.vector,
                 self.hot_focus
                     .map(|hf| self.foci[hf].weight_against_subject.clamp(0.0, 1.0))
                     .unwrap_or(0.0),
-            ),
+            ) + self.shake_offset(),
         ));
         self.world_tx
             .append_rotation_wrt_center_mut(&self.target_tx.isometry.rotation);
 
         self.screen_tx = self.world_tx.inverse();
+
+        // Decay after shaking, so that trauma added this frame is felt at full strength.
+        self.add_trauma(-self.params.trauma_decay * dt);
     }
 
     /// The calculated "world transform" which maps from screen space to world space.
@@ -423,4 +675,217 @@ impl Camera {
     }
 }
 
-impl LuaUserData for Camera {}
+impl LuaUserData for Camera {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("get_subject_pos", |_, this, ()| {
+            Ok((this.subject_pos.x, this.subject_pos.y))
+        });
+
+        methods.add_method_mut("set_subject_pos", |_, this, (x, y)| {
+            this.set_subject_pos(Point2::new(x, y));
+            Ok(())
+        });
+
+        methods.add_method("get_follow_pos", |_, this, ()| {
+            let follow_pos = this.follow_pos();
+            Ok((follow_pos.x, follow_pos.y))
+        });
+
+        methods.add_method("get_scale", |_, this, ()| Ok(this.scale()));
+
+        methods.add_method_mut("set_scale", |_, this, scale| {
+            this.set_scale(scale);
+            Ok(())
+        });
+
+        methods.add_method_mut(
+            "set_deadzone",
+            |_, this, (w, h): (Option<f32>, Option<f32>)| {
+                this.params.deadzone = w.zip(h).map(|(w, h)| Vector2::new(w, h));
+                Ok(())
+            },
+        );
+
+        methods.add_method_mut(
+            "set_look_ahead",
+            |_, this, (time, max_distance): (f32, Option<f32>)| {
+                this.params.look_ahead_time = time;
+                this.params.max_look_ahead = max_distance.unwrap_or(f32::INFINITY);
+                Ok(())
+            },
+        );
+
+        methods.add_method_mut(
+            "set_follow",
+            |_, this, (mode, a, b): (LuaString, Option<f32>, Option<f32>)| {
+                let mode = match mode.to_str()? {
+                    "snap" => FollowMode::Snap,
+                    "linear" => FollowMode::Linear {
+                        speed: a.unwrap_or(f32::INFINITY),
+                    },
+                    "exponential" => FollowMode::Exponential {
+                        factor: a.unwrap_or(0.1),
+                    },
+                    "spring" => {
+                        let stiffness = a.unwrap_or(100.);
+                        FollowMode::Spring {
+                            stiffness,
+                            damping: b.unwrap_or(2. * stiffness.sqrt()),
+                        }
+                    }
+                    other => {
+                        return Err(anyhow!("unknown camera follow mode `{}`", other)).to_lua_err()
+                    }
+                };
+                this.set_follow(mode);
+                Ok(())
+            },
+        );
+
+        methods.add_method("get_trauma", |_, this, ()| Ok(this.trauma()));
+
+        methods.add_method_mut("add_trauma", |_, this, trauma| {
+            this.add_trauma(trauma);
+            Ok(())
+        });
+
+        methods.add_method_mut("update", |_, this, dt| {
+            this.update(dt);
+            Ok(())
+        });
+    }
+}
+
+pub(crate) fn open<'lua>(lua: &'lua Lua, _engine: &Engine) -> Result<LuaTable<'lua>> {
+    let create_camera_object = lua.create_function(|_, (w, h): (u32, u32)| {
+        Ok(Camera::new(CameraParameters::new(Vector2::new(w, h))))
+    })?;
+
+    Ok(lua
+        .load(mlua::chunk! {
+            {
+                create_camera_object = $create_camera_object,
+            }
+        })
+        .eval()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_points_eq(a: Point2<f32>, b: Point2<f32>) {
+        assert!((a - b).norm() < 1e-3, "{} != {}", a, b);
+    }
+
+    /// The world-space point at the center of the screen.
+    fn screen_center(camera: &Camera) -> Point2<f32> {
+        camera
+            .screen_to_world_tx()
+            .transform_point(&Point2::new(160., 120.))
+    }
+
+    #[test]
+    fn deadzone_holds_camera_until_subject_leaves_it() {
+        for &scale in &[1., 2.] {
+            let mut params = CameraParameters::new(Vector2::new(320, 240));
+            params.deadzone = Some(Vector2::new(100., 60.));
+            let mut camera = Camera::new(params);
+            camera.set_scale(scale);
+
+            camera.set_subject_pos(Point2::new(10., 10.));
+            camera.update(1. / 60.);
+            assert_points_eq(screen_center(&camera), Point2::new(10., 10.));
+
+            // Moving around inside the 100x60 deadzone doesn't move the camera, regardless of
+            // zoom.
+            for &(x, y) in &[(50., 10.), (-30., 35.), (59., -19.)] {
+                camera.set_subject_pos(Point2::new(x, y));
+                camera.update(1. / 60.);
+                assert_points_eq(screen_center(&camera), Point2::new(10., 10.));
+            }
+
+            // Leaving it drags the camera along so that the subject is on the deadzone's edge.
+            camera.set_subject_pos(Point2::new(90., 10.));
+            camera.update(1. / 60.);
+            assert_points_eq(screen_center(&camera), Point2::new(40., 10.));
+            camera.set_subject_pos(Point2::new(90., -50.));
+            camera.update(1. / 60.);
+            assert_points_eq(screen_center(&camera), Point2::new(40., -20.));
+        }
+    }
+
+    #[test]
+    fn look_ahead_leads_in_direction_of_movement() {
+        let mut params = CameraParameters::new(Vector2::new(320, 240));
+        params.look_ahead_time = 0.5;
+        params.max_look_ahead = 20.;
+        params.time_constant = 60.;
+        let mut camera = Camera::new(params);
+
+        for i in 0..10 {
+            camera.set_subject_pos(Point2::new(i as f32, 0.));
+            camera.update(1. / 60.);
+        }
+
+        // Moving at 60 units per second, half a second of look-ahead would be 30 units, which is
+        // capped to 20.
+        assert_points_eq(camera.follow_pos(), Point2::new(29., 0.));
+    }
+
+    #[test]
+    fn exponential_follow_converges_without_overshoot() {
+        let run = |dt: f32, steps: usize| {
+            let mut camera = Camera::new(CameraParameters::new(Vector2::new(320, 240)));
+            camera.update(dt);
+            camera.set_follow(FollowMode::Exponential { factor: 0.1 });
+            camera.set_subject_pos(Point2::new(100., 0.));
+
+            let mut last = 0.;
+            for _ in 0..steps {
+                camera.update(dt);
+                let x = camera.follow_pos().x;
+                assert!(x >= last && x <= 100., "{} after {}", x, last);
+                last = x;
+            }
+            last
+        };
+
+        // One second at 60 and 30 frames per second ends up in the same place: 1 - 0.9^60 of the
+        // way there.
+        let at_60 = run(1. / 60., 60);
+        let at_30 = run(1. / 30., 30);
+        assert!((at_60 - 100. * (1. - 0.9f32.powi(60))).abs() < 1e-2);
+        assert!((at_60 - at_30).abs() < 1e-2);
+
+        assert!(run(1. / 60., 600) > 99.99);
+    }
+
+    #[test]
+    fn shake_events_accumulate_and_cap_trauma() {
+        let channel = Shared::new(EventChannel::new());
+        let mut camera = Camera::new(CameraParameters::new(Vector2::new(320, 240)));
+        camera.subscribe_to_shake_events(channel.clone());
+
+        // This is what `Engine::emit` does with the engine's channel.
+        let emit = |trauma| {
+            channel
+                .borrow_mut()
+                .single_write(ScreenShakeEvent { trauma })
+        };
+
+        emit(0.25);
+        emit(0.5);
+        camera.update(0.);
+        assert!((camera.trauma() - 0.75).abs() < 1e-6);
+
+        emit(0.5);
+        emit(0.5);
+        camera.update(0.);
+        assert_eq!(camera.trauma(), 1.);
+
+        // Trauma decays once the shaking stops coming in.
+        camera.update(0.25);
+        assert!((camera.trauma() - 0.75).abs() < 1e-6);
+    }
+}
@@ -8,12 +8,36 @@ use hv_core::{
 };
 use na::Isometry2;
 use parry2d::shape::{
-    Ball, Compound, ConvexPolygon, Cuboid, HalfSpace, Polyline, Segment, SharedShape,
+    Ball, Compound, ConvexPolygon, Cuboid, HalfSpace, Polyline, Segment, Shape, SharedShape,
 };
 use serde::*;
 
 use crate::math::*;
 
+/// Convert a flat list of `x, y` coordinates into points.
+fn vertices_from_coords(vertex_coords: &[f32]) -> Result<Vec<Point2<f32>>> {
+    if vertex_coords.len() % 2 == 1 {
+        bail!(
+            "expected an even number of vertex coordinates, got {}!",
+            vertex_coords.len()
+        );
+    }
+
+    Ok(vertex_coords
+        .chunks_exact(2)
+        .map(|xy| Point2::new(xy[0], xy[1]))
+        .collect())
+}
+
+/// Check that a shape dimension (a radius, width, etc.) is usable, producing an error naming the
+/// offending dimension if it isn't.
+fn check_dimension(what: &str, value: f32) -> Result<f32> {
+    if !value.is_finite() || value <= 0. {
+        bail!("{} must be positive and finite, got {}", what, value);
+    }
+    Ok(value)
+}
+
 mod compound_helper {
     use serde::ser::SerializeSeq;
 
@@ -143,6 +167,54 @@ impl From<ClosedShape> for SharedShape {
     }
 }
 
+/// A single contact between two shapes, as computed by [`collide`]. Everything is in world space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Contact {
+    /// The unit contact normal, pointing from the first shape towards the second. Moving the second
+    /// shape by `normal * depth` (or the first by `-normal * depth`) separates them.
+    pub normal: Vector2<f32>,
+    /// How far the shapes overlap along the normal. Negative if the shapes are separated, but close
+    /// enough to be reported because of the prediction distance.
+    pub depth: f32,
+    /// The point on the first shape which is deepest inside (or closest to) the second.
+    pub point_a: Point2<f32>,
+    /// The point on the second shape which is deepest inside (or closest to) the first.
+    pub point_b: Point2<f32>,
+}
+
+impl LuaUserData for Contact {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("normal", |_, this, ()| Ok((this.normal.x, this.normal.y)));
+        methods.add_method("depth", |_, this, ()| Ok(this.depth));
+        methods.add_method("point_a", |_, this, ()| {
+            Ok((this.point_a.x, this.point_a.y))
+        });
+        methods.add_method("point_b", |_, this, ()| {
+            Ok((this.point_b.x, this.point_b.y))
+        });
+    }
+}
+
+/// Compute the contact between `shape_a` at `pos_a` and `shape_b` at `pos_b`, if they overlap or
+/// are within `prediction` of each other. The normal of the returned contact always points from
+/// `shape_a` towards `shape_b`.
+pub fn collide(
+    pos_a: &Isometry2<f32>,
+    shape_a: &dyn Shape,
+    pos_b: &Isometry2<f32>,
+    shape_b: &dyn Shape,
+    prediction: f32,
+) -> Result<Option<Contact>> {
+    let contact = parry2d::query::contact(pos_a, shape_a, pos_b, shape_b, prediction)
+        .map_err(|_| anyhow!("contact queries between these shapes are not supported"))?;
+    Ok(contact.map(|contact| Contact {
+        normal: contact.normal1.into_inner(),
+        depth: -contact.dist,
+        point_a: contact.point1,
+        point_b: contact.point2,
+    }))
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Collider {
     #[serde(with = "shape_handle_helper")]
@@ -151,6 +223,7 @@ pub struct Collider {
 }
 
 hv_core::serializable!(serialize::with_serde::<Collider>("friends.Collider"));
+hv_core::component_type!("Collider", Collider);
 
 impl Collider {
     pub fn new(local_tx: Isometry2<f32>, shape: SharedShape) -> Self {
@@ -173,21 +246,116 @@ impl Collider {
         self.shape.compute_swept_aabb(start_pos, end_pos).into()
     }
 
-    pub fn lua_compute_local_aabb(_: &Lua, this: &Self, out: LuaAnyUserData) -> LuaResult<()> {
-        *out.borrow_mut::<Box2<f32>>()? = this.compute_local_aabb();
-        Ok(())
+    /// Test whether this collider, at `position`, overlaps `other` at `other_position`.
+    pub fn intersects(
+        &self,
+        position: &Isometry2<f32>,
+        other: &Collider,
+        other_position: &Isometry2<f32>,
+    ) -> Result<bool> {
+        parry2d::query::intersection_test(
+            &(position * self.local_tx),
+            self.shape.as_ref(),
+            &(other_position * other.local_tx),
+            other.shape.as_ref(),
+        )
+        .map_err(|_| anyhow!("intersection tests between these shapes are not supported"))
     }
 
-    pub fn lua_compute_aabb(
-        _: &Lua,
+    /// Compute the contact between this collider, at `position`, and `other` at `other_position`,
+    /// if they overlap or are within `prediction` of each other. The contact normal points from
+    /// this collider towards `other`.
+    pub fn contact(
+        &self,
+        position: &Isometry2<f32>,
+        other: &Collider,
+        other_position: &Isometry2<f32>,
+        prediction: f32,
+    ) -> Result<Option<Contact>> {
+        collide(
+            &(position * self.local_tx),
+            self.shape.as_ref(),
+            &(other_position * other.local_tx),
+            other.shape.as_ref(),
+            prediction,
+        )
+    }
+
+    /// Compute the distance between this collider, at `position`, and `other` at
+    /// `other_position`. Overlapping colliders are at distance zero.
+    pub fn distance(
+        &self,
+        position: &Isometry2<f32>,
+        other: &Collider,
+        other_position: &Isometry2<f32>,
+    ) -> Result<f32> {
+        parry2d::query::distance(
+            &(position * self.local_tx),
+            self.shape.as_ref(),
+            &(other_position * other.local_tx),
+            other.shape.as_ref(),
+        )
+        .map_err(|_| anyhow!("distance queries between these shapes are not supported"))
+    }
+
+    /// Compute the time at which this collider and `other`, starting at the given positions and
+    /// moving with the given linear velocities, first touch. Returns `None` if they don't touch
+    /// within `max_toi`.
+    pub fn time_of_impact(
+        &self,
+        position: &Isometry2<f32>,
+        velocity: &Vector2<f32>,
+        other: &Collider,
+        other_position: &Isometry2<f32>,
+        other_velocity: &Vector2<f32>,
+        max_toi: f32,
+    ) -> Result<Option<f32>> {
+        let toi = parry2d::query::time_of_impact(
+            &(position * self.local_tx),
+            velocity,
+            self.shape.as_ref(),
+            &(other_position * other.local_tx),
+            other_velocity,
+            other.shape.as_ref(),
+            max_toi,
+        )
+        .map_err(|_| anyhow!("time of impact queries between these shapes are not supported"))?;
+        Ok(toi.map(|toi| toi.toi))
+    }
+
+    /// Write `aabb` into `out` if given, or into a new box otherwise, and return it.
+    fn lua_output_aabb<'lua>(
+        lua: &'lua Lua,
+        aabb: Box2<f32>,
+        out: Option<LuaAnyUserData<'lua>>,
+    ) -> LuaResult<LuaAnyUserData<'lua>> {
+        match out {
+            Some(out) => {
+                *out.borrow_mut::<Box2<f32>>()? = aabb;
+                Ok(out)
+            }
+            None => lua.create_userdata(aabb),
+        }
+    }
+
+    pub fn lua_compute_local_aabb<'lua>(
+        lua: &'lua Lua,
         this: &Self,
-        (tx, out): (Tx<f32>, LuaAnyUserData),
-    ) -> LuaResult<()> {
-        *out.borrow_mut::<Box2<f32>>()? = this.compute_aabb(
+        out: Option<LuaAnyUserData<'lua>>,
+    ) -> LuaResult<LuaAnyUserData<'lua>> {
+        Self::lua_output_aabb(lua, this.compute_local_aabb(), out)
+    }
+
+    pub fn lua_compute_aabb<'lua>(
+        lua: &'lua Lua,
+        this: &Self,
+        (tx, out): (Tx<f32>, Option<LuaAnyUserData<'lua>>),
+    ) -> LuaResult<LuaAnyUserData<'lua>> {
+        let aabb = this.compute_aabb(
             &tx.to_isometry2()
                 .ok_or_else(|| anyhow!("could not convert transform to Isometry2").to_lua_err())?,
         );
-        Ok(())
+        Self::lua_output_aabb(lua, aabb, out)
     }
 
     pub fn lua_compute_swept_aabb(
@@ -207,7 +375,7 @@ impl Collider {
 
     pub fn lua_ball(lua: &Lua, (radius, more): (f32, LuaMultiValue)) -> LuaResult<Self> {
         Ok(Self {
-            shape: SharedShape::ball(radius),
+            shape: SharedShape::ball(check_dimension("ball radius", radius).to_lua_err()?),
             local_tx: Position2::lua_new(lua, more)?.to_isometry(),
         })
     }
@@ -240,6 +408,15 @@ impl Collider {
         })
     }
 
+    pub fn lua_box(lua: &Lua, (w, h, more): (f32, f32, LuaMultiValue)) -> LuaResult<Self> {
+        let w = check_dimension("box width", w).to_lua_err()?;
+        let h = check_dimension("box height", h).to_lua_err()?;
+        Ok(Collider {
+            shape: SharedShape::cuboid(w / 2., h / 2.),
+            local_tx: Position2::lua_new(lua, more)?.to_isometry(),
+        })
+    }
+
     pub fn lua_halfspace(lua: &Lua, (nx, ny, more): (f32, f32, LuaMultiValue)) -> LuaResult<Self> {
         Ok(Collider {
             shape: SharedShape::halfspace(UnitVector2::new_normalize(Vector2::new(nx, ny))),
@@ -251,16 +428,7 @@ impl Collider {
         lua: &Lua,
         (vertex_coords, more): (Vec<f32>, LuaMultiValue),
     ) -> LuaResult<Self> {
-        if vertex_coords.len() % 2 == 1 {
-            return Err(LuaError::external(anyhow!(
-                "expected an even number of vertex coordinates!"
-            )));
-        }
-
-        let mut vertices = Vec::new();
-        for xy in vertex_coords.chunks_exact(2) {
-            vertices.push(Point2::new(xy[0], xy[1]));
-        }
+        let vertices = vertices_from_coords(&vertex_coords).to_lua_err()?;
 
         Ok(Collider {
             shape: SharedShape::convex_hull(&vertices)
@@ -270,20 +438,114 @@ impl Collider {
         })
     }
 
-    pub fn lua_convex_polyline(
+    /// Create a convex polygon from a flat list of vertex coordinates. Points inside the polygon,
+    /// and the order the points are given in, don't matter, as the convex hull is used.
+    pub fn lua_polygon(
         lua: &Lua,
         (vertex_coords, more): (Vec<f32>, LuaMultiValue),
     ) -> LuaResult<Self> {
-        if vertex_coords.len() % 2 == 1 {
-            return Err(LuaError::external(anyhow!(
-                "expected an even number of vertex coordinates!"
-            )));
+        let vertices = vertices_from_coords(&vertex_coords).to_lua_err()?;
+        if vertices.len() < 3 {
+            return Err(anyhow!(
+                "a polygon needs at least 3 points, got {}",
+                vertices.len()
+            ))
+            .to_lua_err();
         }
 
-        let mut vertices = Vec::new();
-        for xy in vertex_coords.chunks_exact(2) {
-            vertices.push(Point2::new(xy[0], xy[1]));
-        }
+        Ok(Collider {
+            shape: SharedShape::convex_hull(&vertices)
+                .ok_or_else(|| anyhow!("polygon points are collinear or coincident"))
+                .to_lua_err()?,
+            local_tx: Position2::lua_new(lua, more)?.to_isometry(),
+        })
+    }
+
+    /// Create a component constructor for a box collider `w` wide and `h` tall, centered on its
+    /// local transform, which is taken from any further arguments the same way as a position.
+    pub fn lua_box_collider(
+        lua: &Lua,
+        args: (f32, f32, LuaMultiValue),
+    ) -> LuaResult<DynamicComponentConstructor> {
+        Self::lua_box(lua, args).map(DynamicComponentConstructor::clone)
+    }
+
+    /// Create a component constructor for a circle collider of radius `r`. See
+    /// [`Collider::lua_box_collider`] for the local transform.
+    pub fn lua_circle_collider(
+        lua: &Lua,
+        args: (f32, LuaMultiValue),
+    ) -> LuaResult<DynamicComponentConstructor> {
+        Self::lua_ball(lua, args).map(DynamicComponentConstructor::clone)
+    }
+
+    /// Create a component constructor for a convex polygon collider from a flat list of vertex
+    /// coordinates, as with [`Collider::lua_polygon`]. See [`Collider::lua_box_collider`] for the
+    /// local transform.
+    pub fn lua_polygon_collider(
+        lua: &Lua,
+        args: (Vec<f32>, LuaMultiValue),
+    ) -> LuaResult<DynamicComponentConstructor> {
+        Self::lua_polygon(lua, args).map(DynamicComponentConstructor::clone)
+    }
+
+    pub fn lua_intersects(
+        _: &Lua,
+        (a, pos_a, b, pos_b): (Collider, Position2<f32>, Collider, Position2<f32>),
+    ) -> LuaResult<bool> {
+        a.intersects(&pos_a, &b, &pos_b).to_lua_err()
+    }
+
+    pub fn lua_distance(
+        _: &Lua,
+        (a, pos_a, b, pos_b): (Collider, Position2<f32>, Collider, Position2<f32>),
+    ) -> LuaResult<f32> {
+        a.distance(&pos_a, &b, &pos_b).to_lua_err()
+    }
+
+    pub fn lua_contact(
+        _: &Lua,
+        (a, pos_a, b, pos_b, prediction): (
+            Collider,
+            Position2<f32>,
+            Collider,
+            Position2<f32>,
+            Option<f32>,
+        ),
+    ) -> LuaResult<Option<Contact>> {
+        a.contact(&pos_a, &b, &pos_b, prediction.unwrap_or(0.))
+            .to_lua_err()
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub fn lua_time_of_impact(
+        _: &Lua,
+        (a, pos_a, vel_a, b, pos_b, vel_b, max_toi): (
+            Collider,
+            Position2<f32>,
+            Velocity2<f32>,
+            Collider,
+            Position2<f32>,
+            Velocity2<f32>,
+            Option<f32>,
+        ),
+    ) -> LuaResult<Option<f32>> {
+        a.time_of_impact(
+            &pos_a,
+            &vel_a.linear,
+            &b,
+            &pos_b,
+            &vel_b.linear,
+            max_toi.unwrap_or(f32::MAX),
+        )
+        .to_lua_err()
+    }
+
+    pub fn lua_convex_polyline(
+        lua: &Lua,
+        (vertex_coords, more): (Vec<f32>, LuaMultiValue),
+    ) -> LuaResult<Self> {
+        let vertices = vertices_from_coords(&vertex_coords).to_lua_err()?;
 
         Ok(Collider {
             shape: SharedShape::convex_polyline(vertices)
@@ -297,16 +559,7 @@ impl Collider {
         lua: &Lua,
         (vertex_coords, more): (Vec<f32>, LuaMultiValue),
     ) -> LuaResult<Self> {
-        if vertex_coords.len() % 2 == 1 {
-            return Err(LuaError::external(anyhow!(
-                "expected an even number of vertex coordinates!"
-            )));
-        }
-
-        let mut vertices = Vec::new();
-        for xy in vertex_coords.chunks_exact(2) {
-            vertices.push(Point2::new(xy[0], xy[1]));
-        }
+        let vertices = vertices_from_coords(&vertex_coords).to_lua_err()?;
 
         Ok(Collider {
             shape: SharedShape::polyline(vertices, None),
@@ -339,15 +592,20 @@ pub(crate) fn open<'lua>(lua: &'lua Lua, engine: &Engine) -> Result<LuaTable<'lu
     let create_ball = lua.create_function(Collider::lua_ball)?;
     let create_compound = lua.create_function(Collider::lua_compound)?;
     let create_cuboid = lua.create_function(Collider::lua_cuboid)?;
+    let create_box = lua.create_function(Collider::lua_box)?;
     let create_halfspace = lua.create_function(Collider::lua_halfspace)?;
     let create_convex_hull = lua.create_function(Collider::lua_convex_hull)?;
     let create_convex_polyline = lua.create_function(Collider::lua_convex_polyline)?;
+    let create_polygon = lua.create_function(Collider::lua_polygon)?;
     let create_polyline = lua.create_function(Collider::lua_polyline)?;
     let create_segment = lua.create_function(Collider::lua_segment)?;
 
     let create_collider_component = lua.create_function(|_, collider: Collider| {
         Ok(DynamicComponentConstructor::clone(collider))
     })?;
+    let box_collider = lua.create_function(Collider::lua_box_collider)?;
+    let circle_collider = lua.create_function(Collider::lua_circle_collider)?;
+    let polygon_collider = lua.create_function(Collider::lua_polygon_collider)?;
 
     let mut space_cache = SpaceCache::new(engine);
     let get_collider =
@@ -393,23 +651,183 @@ pub(crate) fn open<'lua>(lua: &'lua Lua, engine: &Engine) -> Result<LuaTable<'lu
         },
     )?;
 
+    let intersects = lua.create_function(Collider::lua_intersects)?;
+    let distance = lua.create_function(Collider::lua_distance)?;
+    let contact = lua.create_function(Collider::lua_contact)?;
+    let time_of_impact = lua.create_function(Collider::lua_time_of_impact)?;
+
     let chunk = mlua::chunk! {{
         create_ball = $create_ball,
         create_compound = $create_compound,
         create_cuboid = $create_cuboid,
+        create_box = $create_box,
         create_halfspace = $create_halfspace,
         create_convex_hull = $create_convex_hull,
         create_convex_polyline = $create_convex_polyline,
+        create_polygon = $create_polygon,
         create_polyline = $create_polyline,
         create_segment = $create_segment,
 
         create_collider_component = $create_collider_component,
+        box_collider = $box_collider,
+        circle_collider = $circle_collider,
+        polygon_collider = $polygon_collider,
         get_collider = $get_collider,
         set_collider = $set_collider,
         remove_collider_component = $remove_collider_component,
 
         intersection_test = $intersection_test,
+        intersects = $intersects,
+        distance = $distance,
+        contact = $contact,
+        time_of_impact = $time_of_impact,
     }};
 
     Ok(lua.load(chunk).eval()?)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lua_with_queries() -> Lua {
+        let lua = Lua::new();
+        let box_ = lua.create_function(Collider::lua_box).unwrap();
+        let polygon = lua.create_function(Collider::lua_polygon).unwrap();
+        let intersects = lua.create_function(Collider::lua_intersects).unwrap();
+        let distance = lua.create_function(Collider::lua_distance).unwrap();
+        let contact = lua.create_function(Collider::lua_contact).unwrap();
+        let ball = lua.create_function(Collider::lua_ball).unwrap();
+        let position = lua
+            .create_function(|_, (x, y)| Ok(Position2::<f32>::translation(x, y)))
+            .unwrap();
+        lua.load(mlua::chunk! {
+            collision = {
+                box = $box_,
+                polygon = $polygon,
+                intersects = $intersects,
+                distance = $distance,
+                contact = $contact,
+                ball = $ball,
+            }
+            position = $position
+        })
+        .exec()
+        .unwrap();
+        lua
+    }
+
+    #[test]
+    fn lua_box_intersections() {
+        let lua = lua_with_queries();
+        lua.load(mlua::chunk! {
+            local a = collision.box(2, 2)
+            local b = collision.box(2, 2)
+            assert(collision.intersects(a, position(0, 0), b, position(1.5, 0.5)))
+            assert(not collision.intersects(a, position(0, 0), b, position(10, 0)))
+            assert(math.abs(collision.distance(a, position(0, 0), b, position(10, 0)) - 8) < 1e-4)
+        })
+        .exec()
+        .unwrap();
+    }
+
+    #[test]
+    fn overlapping_circles_report_normal_and_depth() {
+        let a = Collider::new(Isometry2::identity(), SharedShape::ball(1.));
+        let b = Collider::new(Isometry2::identity(), SharedShape::ball(1.));
+        let origin = Isometry2::identity();
+        let right = Isometry2::translation(1.5, 0.);
+
+        let contact = a.contact(&origin, &b, &right, 0.).unwrap().unwrap();
+        assert!((contact.normal - Vector2::new(1., 0.)).norm() < 1e-5);
+        assert!((contact.depth - 0.5).abs() < 1e-5);
+        assert!((contact.point_a - Point2::new(1., 0.)).norm() < 1e-5);
+        assert!((contact.point_b - Point2::new(0.5, 0.)).norm() < 1e-5);
+
+        // Swapping the shapes flips the normal, so it always points from the first to the second.
+        let swapped = b.contact(&right, &a, &origin, 0.).unwrap().unwrap();
+        assert!((swapped.normal + contact.normal).norm() < 1e-5);
+        assert!((swapped.depth - contact.depth).abs() < 1e-5);
+
+        // Separated shapes are only reported within the prediction distance.
+        let far = Isometry2::translation(2.5, 0.);
+        assert!(a.contact(&origin, &b, &far, 0.).unwrap().is_none());
+        let predicted = a.contact(&origin, &b, &far, 1.).unwrap().unwrap();
+        assert!((predicted.depth + 0.5).abs() < 1e-5);
+
+        let lua = lua_with_queries();
+        lua.load(mlua::chunk! {
+            local a = collision.ball(1)
+            local b = collision.ball(1)
+            local contact = collision.contact(a, position(0, 0), b, position(0, 1.5))
+            local nx, ny = contact:normal()
+            assert(math.abs(nx) < 1e-4 and math.abs(ny - 1) < 1e-4)
+            assert(math.abs(contact:depth() - 0.5) < 1e-4)
+            assert(collision.contact(a, position(0, 0), b, position(0, 3)) == nil)
+        })
+        .exec()
+        .unwrap();
+    }
+
+    #[test]
+    fn box_collider_attached_from_lua() -> Result<()> {
+        let lua = lua_with_queries();
+        let box_collider = lua.create_function(Collider::lua_box_collider)?;
+        lua.globals().set("box_collider", box_collider)?;
+        let constructor: LuaAnyUserData = lua
+            .load(mlua::chunk! { box_collider(4, 2, 10, 0) })
+            .eval()?;
+
+        let space = hv_core::spaces::Spaces::new().create_space();
+        let object = space.borrow_mut().spawn(());
+        constructor
+            .borrow::<DynamicComponentConstructor>()?
+            .insert_on_object(&lua, object, &mut space.borrow_mut())?;
+
+        let collider = space.borrow().get::<Collider>(object)?.clone();
+        lua.globals().set("collider", collider)?;
+        lua.globals().set("identity", Tx::<f32>::identity())?;
+        lua.load(mlua::chunk! {
+            local aabb = collider:compute_aabb(identity)
+            local x0, y0 = aabb:mins()
+            local x1, y1 = aabb:maxs()
+            assert(math.abs(x0 - 8) < 1e-4 and math.abs(y0 + 1) < 1e-4)
+            assert(math.abs(x1 - 12) < 1e-4 and math.abs(y1 - 1) < 1e-4)
+        })
+        .exec()?;
+
+        let err = lua
+            .load(mlua::chunk! { box_collider(-1, 2) })
+            .exec()
+            .unwrap_err();
+        assert!(err.to_string().contains("box width"), "{}", err);
+
+        Ok(())
+    }
+
+    #[test]
+    fn degenerate_shapes_have_clean_errors() {
+        let lua = lua_with_queries();
+        let err = lua
+            .load(mlua::chunk! { collision.box(0, 2) })
+            .exec()
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("box width must be positive"),
+            "{}",
+            err
+        );
+
+        let err = lua
+            .load(mlua::chunk! { collision.polygon({ 0, 0, 1, 1, 2, 2 }) })
+            .exec()
+            .unwrap_err();
+        assert!(err.to_string().contains("collinear"), "{}", err);
+
+        let err = lua
+            .load(mlua::chunk! { collision.polygon({ 0, 0, 1 }) })
+            .exec()
+            .unwrap_err();
+        assert!(err.to_string().contains("even number"), "{}", err);
+    }
+}
@@ -26,7 +26,9 @@ use hv_core::{
 use serde::*;
 
 use crate::{
+    camera::Camera,
     graphics::{
+        basic::MAX_CUSTOM_FLOATS,
         bindings::Bindings,
         lua::{LuaDrawMode, LuaGraphicsState},
         pipeline::{Pipeline, PipelineRegistry, ShaderRegistry},
@@ -45,21 +47,29 @@ mod color;
 mod lua;
 pub mod mesh;
 pub mod pipeline;
+pub mod pixel_camera;
+pub mod post_process;
 pub mod render_pass;
 pub mod sprite;
+pub mod sprite_atlas;
 pub mod text;
 pub mod texture;
 mod transform_stack;
+pub mod viewport;
 
 pub use basic::{InstanceProperties, Uniforms, Vertex};
 pub use buffer::{Buffer, BufferElement, BufferFormat, BufferType, OwnedBuffer};
 pub use canvas::Canvas;
 pub use color::{Color, LinearColor};
-pub use mesh::{DrawMode, Mesh, MeshBuilder};
+pub use mesh::{DrawMode, Mesh, MeshBuilder, DEFAULT_TOLERANCE};
+pub use pixel_camera::{PixelCamera, PixelScroll};
+pub use post_process::{PostProcess, PostProcessPass};
 pub use render_pass::{OwnedRenderPass, RenderPass};
 pub use sprite::{Sprite, SpriteBatch, SpriteId};
-pub use texture::{CachedTexture, Texture, SharedTexture};
+pub use sprite_atlas::{SpriteSheetAtlas, SpriteSheetAtlasOptions};
+pub use texture::{CachedTexture, Texture, SharedTexture, TextureOptions};
 pub use transform_stack::TransformStack;
+pub use viewport::Viewport;
 
 fn quad_vertices() -> [Vertex; 4] {
     [
@@ -90,6 +100,46 @@ fn quad_indices() -> [u16; 6] {
     [0, 1, 2, 0, 2, 3]
 }
 
+/// Map a point in window coordinates (pixels, with the origin at the top left and Y pointing down,
+/// as reported by mouse events) through the inverse of `transform` into world space. `viewport` is
+/// the region of the window being rendered to, also in window coordinates; when letterboxing, this
+/// is the letterboxed region rather than the whole window.
+///
+/// `transform` is the full transform from world space to normalized device coordinates, usually
+/// the projection multiplied by the modelview. If it isn't invertible, the result is NaN.
+pub fn screen_to_world_point2(
+    transform: &Matrix4<f32>,
+    viewport: &Box2<f32>,
+    screen: Point2<f32>,
+) -> Point2<f32> {
+    let extents = viewport.extents();
+    let ndc = Point3::new(
+        2. * (screen.x - viewport.mins.x) / extents.x - 1.,
+        1. - 2. * (screen.y - viewport.mins.y) / extents.y,
+        0.,
+    );
+
+    match transform.try_inverse() {
+        Some(inverse) => inverse.transform_point(&ndc).xy(),
+        None => Point2::new(f32::NAN, f32::NAN),
+    }
+}
+
+/// The inverse of [`screen_to_world_point2`]; map a point in world space through `transform` into
+/// window coordinates.
+pub fn world_to_screen_point2(
+    transform: &Matrix4<f32>,
+    viewport: &Box2<f32>,
+    world: Point2<f32>,
+) -> Point2<f32> {
+    let extents = viewport.extents();
+    let ndc = transform.transform_point(&Point3::from(world.coords.push(0.)));
+    Point2::new(
+        viewport.mins.x + (ndc.x + 1.) / 2. * extents.x,
+        viewport.mins.y + (1. - ndc.y) / 2. * extents.y,
+    )
+}
+
 /// Represents the parameters available for a single instance using the default shaders and render
 /// pipeline.
 #[derive(Debug, Copy, Clone, Serialize, Deserialize)]
@@ -103,6 +153,20 @@ pub struct Instance {
     /// The color of this instance. Defaults to [`Color::WHITE`], which in essence is the "identity"
     /// value.
     pub color: Color,
+    /// The texture page this instance samples from, when drawn as part of a [`SpriteBatch`] with
+    /// several pages. Defaults to 0, the first (and usually only) texture.
+    #[serde(default)]
+    pub page: u32,
+    /// Extra data for pipelines with custom instance attributes, packed into the custom instance
+    /// buffer in order; only as many floats as the pipeline declares are uploaded. Defaults to all
+    /// zeroes. See [`Instance::with_custom`].
+    #[serde(default)]
+    pub custom: [f32; MAX_CUSTOM_FLOATS],
+    /// The sort key of this instance when drawn as part of a [`SpriteBatch`] with
+    /// [`SpriteBatch::draw_sorted`]; lower depths are drawn first, and so end up underneath. This
+    /// only affects draw order, and isn't uploaded to the GPU. Defaults to 0.
+    #[serde(default)]
+    pub depth: f32,
 }
 
 impl Default for Instance {
@@ -111,6 +175,9 @@ impl Default for Instance {
             src: Box2::new(0., 0., 1., 1.),
             tx: Transform3::identity(),
             color: Color::WHITE,
+            page: 0,
+            custom: [0.; MAX_CUSTOM_FLOATS],
+            depth: 0.,
         }
     }
 }
@@ -134,6 +201,49 @@ impl Instance {
         Self { color, ..self }
     }
 
+    /// Builder method for setting the texture page of an `Instance`.
+    #[inline]
+    pub fn page(self, page: u32) -> Self {
+        Self { page, ..self }
+    }
+
+    /// Builder method for setting the depth of an `Instance`, which orders it within a
+    /// [`SpriteBatch`] drawn with [`SpriteBatch::draw_sorted`].
+    #[inline]
+    pub fn depth(self, depth: f32) -> Self {
+        Self { depth, ..self }
+    }
+
+    /// Builder method for setting the custom data of an `Instance`, for pipelines which declare
+    /// custom instance attributes with [`PipelineLayout::with_custom_instance_attributes`]. The
+    /// data fills the custom attributes in the order they were declared, and any floats after it
+    /// are set to zero.
+    ///
+    /// Panics if `N` is greater than [`MAX_CUSTOM_FLOATS`](basic::MAX_CUSTOM_FLOATS).
+    ///
+    /// [`PipelineLayout::with_custom_instance_attributes`]:
+    ///     pipeline::PipelineLayout::with_custom_instance_attributes
+    #[inline]
+    pub fn with_custom<const N: usize>(self, data: [f32; N]) -> Self {
+        self.with_custom_slice(&data)
+    }
+
+    /// Like [`Instance::with_custom`], but for data whose length isn't known at compile time.
+    ///
+    /// Panics if `data` has more than [`MAX_CUSTOM_FLOATS`](basic::MAX_CUSTOM_FLOATS) floats.
+    #[inline]
+    pub fn with_custom_slice(self, data: &[f32]) -> Self {
+        assert!(
+            data.len() <= MAX_CUSTOM_FLOATS,
+            "an instance can carry at most {} custom floats, got {}",
+            MAX_CUSTOM_FLOATS,
+            data.len()
+        );
+        let mut custom = [0.; MAX_CUSTOM_FLOATS];
+        custom[..data.len()].copy_from_slice(data);
+        Self { custom, ..self }
+    }
+
     /// Builder method for right-multiplying a 2D rotation onto the transform of an `Instance`.
     #[inline]
     pub fn rotate2(self, angle: f32) -> Self {
@@ -213,10 +323,83 @@ impl Instance {
             src: Vector4::new(mins.x, mins.y, extents.x, extents.y),
             tx: *self.tx.matrix(),
             color: LinearColor::from(self.color),
+            page: self.page as f32,
+        }
+    }
+
+    /// Interpolate between this instance and `other`, where `t = 0` gives `self` and `t = 1` gives
+    /// `other`.
+    ///
+    /// Transforms are treated as 2D and decomposed into a translation, a rotation about the Z axis,
+    /// and a scale, which are interpolated separately before being recomposed; rotation goes the
+    /// short way around, and any shear is lost. Colors are interpolated in linear space, source
+    /// rectangles and custom data componentwise, depth linearly, and the page is taken from
+    /// whichever instance `t` is closer to.
+    pub fn lerp(&self, other: &Instance, t: f32) -> Instance {
+        let (t0, angle0, s0) = decompose_transform2(&self.tx);
+        let (t1, angle1, s1) = decompose_transform2(&other.tx);
+
+        let mut delta = (angle1 - angle0) % std::f32::consts::TAU;
+        if delta > std::f32::consts::PI {
+            delta -= std::f32::consts::TAU;
+        } else if delta < -std::f32::consts::PI {
+            delta += std::f32::consts::TAU;
+        }
+
+        let translation = t0.lerp(&t1, t);
+        let angle = angle0 + delta * t;
+        let scale = s0.lerp(&s1, t);
+        let (sin, cos) = angle.sin_cos();
+        #[rustfmt::skip]
+        let tx = Matrix4::new(
+            cos * scale.x, -sin * scale.y, 0., translation.x,
+            sin * scale.x,  cos * scale.y, 0., translation.y,
+            0.,             0.,            scale.z, translation.z,
+            0.,             0.,            0., 1.,
+        );
+
+        let c0 = LinearColor::from(self.color);
+        let c1 = LinearColor::from(other.color);
+        let color = LinearColor {
+            r: c0.r + (c1.r - c0.r) * t,
+            g: c0.g + (c1.g - c0.g) * t,
+            b: c0.b + (c1.b - c0.b) * t,
+            a: c0.a + (c1.a - c0.a) * t,
+        };
+
+        let mins = self.src.mins.coords.lerp(&other.src.mins.coords, t);
+        let extents = self.src.extents().lerp(&other.src.extents(), t);
+
+        let mut custom = self.custom;
+        for (c, &c1) in custom.iter_mut().zip(&other.custom) {
+            *c += (c1 - *c) * t;
+        }
+
+        Instance {
+            src: Box2::new(mins.x, mins.y, extents.x, extents.y),
+            tx: Transform3::from_matrix_unchecked(tx),
+            color: Color::from(color),
+            page: if t < 0.5 { self.page } else { other.page },
+            custom,
+            depth: self.depth + (other.depth - self.depth) * t,
         }
     }
 }
 
+/// Split an affine transform into a translation, an angle of rotation about the Z axis, and a
+/// per-axis scale, ignoring shear. A reflection is represented as a negative Y scale.
+fn decompose_transform2(tx: &Transform3<f32>) -> (Vector3<f32>, f32, Vector3<f32>) {
+    let m = tx.matrix();
+    let translation = Vector3::new(m[(0, 3)], m[(1, 3)], m[(2, 3)]);
+    let angle = m[(1, 0)].atan2(m[(0, 0)]);
+    let sx = m[(0, 0)].hypot(m[(1, 0)]);
+    let mut sy = m[(0, 1)].hypot(m[(1, 1)]);
+    if m[(0, 0)] * m[(1, 1)] - m[(0, 1)] * m[(1, 0)] < 0. {
+        sy = -sy;
+    }
+    (translation, angle, Vector3::new(sx, sy, m[(2, 2)]))
+}
+
 impl LuaUserData for Instance {
     fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
         methods.add_method_mut("reset", |_, this, ()| {
@@ -253,6 +436,33 @@ impl LuaUserData for Instance {
             *this = this.color(color);
             Ok(())
         });
+
+        methods.add_method_mut("page", |_, this, page| {
+            *this = this.page(page);
+            Ok(())
+        });
+
+        methods.add_method_mut("depth", |_, this, depth| {
+            *this = this.depth(depth);
+            Ok(())
+        });
+
+        methods.add_method_mut("custom", |_, this, data: Vec<f32>| {
+            if data.len() > MAX_CUSTOM_FLOATS {
+                return Err(anyhow!(
+                    "an instance can carry at most {} custom floats, got {}",
+                    MAX_CUSTOM_FLOATS,
+                    data.len()
+                ))
+                .to_lua_err();
+            }
+            *this = this.with_custom_slice(&data);
+            Ok(())
+        });
+
+        methods.add_method("lerp", |_, this, (other, t): (Instance, f32)| {
+            Ok(this.lerp(&other, t))
+        });
     }
 }
 
@@ -351,6 +561,48 @@ impl<'lua> FromLua<'lua> for FilterMode {
     }
 }
 
+impl From<FilterMode> for mq::FilterMode {
+    fn from(filter: FilterMode) -> Self {
+        match filter {
+            FilterMode::Nearest => mq::FilterMode::Nearest,
+            FilterMode::Linear => mq::FilterMode::Linear,
+        }
+    }
+}
+
+/// Represents how a texture is sampled outside of the `[0, 1]` range of texture coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum WrapMode {
+    /// Tile the texture.
+    Repeat,
+    /// Tile the texture, flipping every other repetition.
+    Mirror,
+    /// Stretch the edge texels of the texture outwards.
+    Clamp,
+}
+
+impl<'lua> ToLua<'lua> for WrapMode {
+    fn to_lua(self, lua: &'lua Lua) -> LuaResult<LuaValue<'lua>> {
+        lua.to_value(&self)
+    }
+}
+
+impl<'lua> FromLua<'lua> for WrapMode {
+    fn from_lua(lua_value: LuaValue<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        lua.from_value(lua_value)
+    }
+}
+
+impl From<WrapMode> for mq::TextureWrap {
+    fn from(wrap: WrapMode) -> Self {
+        match wrap {
+            WrapMode::Repeat => mq::TextureWrap::Repeat,
+            WrapMode::Mirror => mq::TextureWrap::Mirror,
+            WrapMode::Clamp => mq::TextureWrap::Clamp,
+        }
+    }
+}
+
 /// `BlendEquation` represents the different types of equations that can be used to blend colors.
 #[derive(Debug, Clone, Copy)]
 pub enum BlendEquation {
@@ -507,12 +759,67 @@ impl LuaUserData for ClearOptions {
     }
 }
 
+/// Which way the Y axis points in projections made by [`Graphics::set_default_projection`].
+///
+/// With [`YAxis::Up`], the origin is at the bottom left of the screen and positive Y is up, like
+/// in most math and physics code. With [`YAxis::Down`], the origin is at the top left and positive
+/// Y is down, like window coordinates and Tiled maps. Either way, positive X is to the right. The
+/// default is [`YAxis::Up`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum YAxis {
+    Up,
+    Down,
+}
+
+impl Default for YAxis {
+    fn default() -> Self {
+        YAxis::Up
+    }
+}
+
+impl YAxis {
+    /// An orthographic projection of a `width` by `height` region with its origin in the corner
+    /// this Y axis direction calls for.
+    pub fn orthographic(self, width: f32, height: f32) -> Matrix4<f32> {
+        match self {
+            YAxis::Up => Orthographic3::new(0., width, 0., height, -1., 1.),
+            YAxis::Down => Orthographic3::new(0., width, height, 0., -1., 1.),
+        }
+        .to_homogeneous()
+    }
+}
+
+impl<'lua> ToLua<'lua> for YAxis {
+    fn to_lua(self, lua: &'lua Lua) -> LuaResult<LuaValue<'lua>> {
+        match self {
+            YAxis::Up => "up",
+            YAxis::Down => "down",
+        }
+        .to_lua(lua)
+    }
+}
+
+impl<'lua> FromLua<'lua> for YAxis {
+    fn from_lua(lua_value: LuaValue<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        match LuaString::from_lua(lua_value, lua)?.to_str()? {
+            "up" => Ok(YAxis::Up),
+            "down" => Ok(YAxis::Down),
+            other => Err(anyhow!("expected `up` or `down`, got `{}`", other)).to_lua_err(),
+        }
+    }
+}
+
 pub struct GraphicsState {
     default_pipeline: mq::Pipeline,
+    paged_pipeline: mq::Pipeline,
     pub null_texture: CachedTexture,
     projection: Matrix4<f32>,
+    y_axis: YAxis,
+    default_projection_size: Option<Vector2<f32>>,
     modelview: TransformStack,
     modelview_dirty: bool,
+    viewport: Option<Box2<f32>>,
+    scissor: Option<Box2<f32>>,
     quad_bindings: mq::Bindings,
     render_passes: RenderPassRegistry,
     shaders: ShaderRegistry,
@@ -529,31 +836,39 @@ impl GraphicsState {
             basic::meta(),
         )?;
 
-        let pipeline = mq::Pipeline::with_params(
-            mq,
-            &[
-                mq::BufferLayout::default(),
-                mq::BufferLayout {
-                    step_func: mq::VertexStep::PerInstance,
-                    ..mq::BufferLayout::default()
-                },
-            ],
-            &[
-                mq::VertexAttribute::with_buffer("a_Pos", mq::VertexFormat::Float3, 0),
-                mq::VertexAttribute::with_buffer("a_Uv", mq::VertexFormat::Float2, 0),
-                mq::VertexAttribute::with_buffer("a_VertColor", mq::VertexFormat::Float4, 0),
-                mq::VertexAttribute::with_buffer("a_Src", mq::VertexFormat::Float4, 1),
-                mq::VertexAttribute::with_buffer("a_Tx", mq::VertexFormat::Mat4, 1),
-                mq::VertexAttribute::with_buffer("a_Color", mq::VertexFormat::Float4, 1),
-            ],
-            shader,
-            mq::PipelineParams {
-                color_blend: Some(BlendMode::default().into()),
-                depth_test: mq::Comparison::LessOrEqual,
-                depth_write: true,
-                ..mq::PipelineParams::default()
+        let buffer_layouts = [
+            mq::BufferLayout::default(),
+            mq::BufferLayout {
+                step_func: mq::VertexStep::PerInstance,
+                ..mq::BufferLayout::default()
             },
-        );
+        ];
+        let attributes = [
+            mq::VertexAttribute::with_buffer("a_Pos", mq::VertexFormat::Float3, 0),
+            mq::VertexAttribute::with_buffer("a_Uv", mq::VertexFormat::Float2, 0),
+            mq::VertexAttribute::with_buffer("a_VertColor", mq::VertexFormat::Float4, 0),
+            mq::VertexAttribute::with_buffer("a_Src", mq::VertexFormat::Float4, 1),
+            mq::VertexAttribute::with_buffer("a_Tx", mq::VertexFormat::Mat4, 1),
+            mq::VertexAttribute::with_buffer("a_Color", mq::VertexFormat::Float4, 1),
+            mq::VertexAttribute::with_buffer("a_Page", mq::VertexFormat::Float1, 1),
+        ];
+        let params = mq::PipelineParams {
+            color_blend: Some(BlendMode::default().into()),
+            depth_test: mq::Comparison::LessOrEqual,
+            depth_write: true,
+            ..mq::PipelineParams::default()
+        };
+
+        let pipeline = mq::Pipeline::with_params(mq, &buffer_layouts, &attributes, shader, params);
+
+        let paged_shader = mq::Shader::new(
+            mq,
+            basic::BASIC_VERTEX,
+            basic::BASIC_PAGED_FRAGMENT,
+            basic::paged_meta(),
+        )?;
+        let paged_pipeline =
+            mq::Pipeline::with_params(mq, &buffer_layouts, &attributes, paged_shader, params);
 
         let mut null_texture =
             CachedTexture::from(mq::Texture::from_rgba8(mq, 1, 1, &[0xFF, 0xFF, 0xFF, 0xFF]));
@@ -576,10 +891,15 @@ impl GraphicsState {
 
         Ok(Self {
             default_pipeline: pipeline,
+            paged_pipeline,
             null_texture,
             projection: Matrix4::identity(),
+            y_axis: YAxis::default(),
+            default_projection_size: None,
             modelview: TransformStack::new(),
             modelview_dirty: true,
+            viewport: None,
+            scissor: None,
             quad_bindings,
             render_passes: RenderPassRegistry::new(),
             shaders: ShaderRegistry::new(),
@@ -704,6 +1024,133 @@ impl<'a> Graphics<'a> {
         M: Into<Matrix4<f32>>,
     {
         self.state.projection = projection.into();
+        self.state.default_projection_size = None;
+        self.state.modelview_dirty = true;
+    }
+
+    /// The current projection matrix.
+    #[inline]
+    pub fn projection(&self) -> &Matrix4<f32> {
+        &self.state.projection
+    }
+
+    /// Set the projection to an orthographic projection of a `width` by `height` region, oriented
+    /// according to [`Graphics::y_axis`]. Unlike a projection set with [`Graphics::set_projection`],
+    /// this one is updated if the Y axis direction is changed afterwards.
+    #[inline]
+    pub fn set_default_projection(&mut self, width: f32, height: f32) {
+        self.set_projection(self.state.y_axis.orthographic(width, height));
+        self.state.default_projection_size = Some(Vector2::new(width, height));
+    }
+
+    /// The size of the region covered by the current projection, if it was set with
+    /// [`Graphics::set_default_projection`]. Code which temporarily changes the projection should
+    /// restore it with `set_default_projection` when this is `Some`, so that it keeps following
+    /// [`Graphics::set_y_axis`].
+    #[inline]
+    pub fn default_projection_size(&self) -> Option<Vector2<f32>> {
+        self.state.default_projection_size
+    }
+
+    /// Set which way the Y axis points in the default projection. If the current projection was set
+    /// with [`Graphics::set_default_projection`], it's flipped to match immediately.
+    #[inline]
+    pub fn set_y_axis(&mut self, y_axis: YAxis) {
+        self.state.y_axis = y_axis;
+        if let Some(size) = self.state.default_projection_size {
+            self.set_default_projection(size.x, size.y);
+        }
+    }
+
+    /// Which way the Y axis points in the default projection.
+    #[inline]
+    pub fn y_axis(&self) -> YAxis {
+        self.state.y_axis
+    }
+
+    /// The number of physical pixels per logical pixel; see [`Engine::dpi_scale`].
+    #[inline]
+    pub fn dpi_scale(&self) -> f32 {
+        self.mq.dpi_scale()
+    }
+
+    /// The size of the window in logical pixels, the units window coordinates are measured in.
+    #[inline]
+    pub fn screen_size(&self) -> (f32, f32) {
+        let (w, h) = self.mq.screen_size();
+        (w / self.dpi_scale(), h / self.dpi_scale())
+    }
+
+    /// Restrict rendering to a region of the window, given in window coordinates (logical pixels,
+    /// with the origin at the top left.) This is reset to the whole window whenever a render pass
+    /// begins.
+    #[inline]
+    pub fn apply_viewport(&mut self, viewport: Box2<f32>) {
+        let (_, h) = self.screen_size();
+        let (x, y, w, h) = viewport::window_rect_to_gl(h, self.dpi_scale(), &viewport);
+        self.mq.apply_viewport(x, y, w, h);
+        self.state.viewport = Some(viewport);
+    }
+
+    /// The region of the window currently being rendered to, in window coordinates.
+    #[inline]
+    pub fn viewport(&self) -> Box2<f32> {
+        self.state.viewport.unwrap_or_else(|| {
+            let (w, h) = self.screen_size();
+            Box2::new(0., 0., w, h)
+        })
+    }
+
+    /// Restrict clears and drawing to a region of the window, given in window coordinates. This is
+    /// reset to the whole window whenever a render pass begins.
+    #[inline]
+    pub fn apply_scissor(&mut self, scissor: Box2<f32>) {
+        let (_, h) = self.screen_size();
+        let (x, y, w, h) = viewport::window_rect_to_gl(h, self.dpi_scale(), &scissor);
+        self.mq.apply_scissor_rect(x, y, w, h);
+        self.state.scissor = Some(scissor);
+    }
+
+    /// Stop restricting clears and drawing to a region of the window.
+    #[inline]
+    pub fn reset_scissor(&mut self) {
+        let (w, h) = self.mq.screen_size();
+        self.mq.apply_scissor_rect(0, 0, w as i32, h as i32);
+        self.state.scissor = None;
+    }
+
+    /// The region of the window clears and drawing are currently restricted to, if any.
+    #[inline]
+    pub fn scissor(&self) -> Option<Box2<f32>> {
+        self.state.scissor
+    }
+
+    /// Convert a point in window coordinates (such as the mouse position) into world space, using
+    /// the current projection, modelview, and viewport.
+    pub fn screen_to_world(&self, screen: Point2<f32>) -> Point2<f32> {
+        let transform = self.state.projection * self.state.modelview.top();
+        screen_to_world_point2(&transform, &self.viewport(), screen)
+    }
+
+    /// Convert a point in world space into window coordinates, using the current projection,
+    /// modelview, and viewport.
+    pub fn world_to_screen(&self, world: Point2<f32>) -> Point2<f32> {
+        let transform = self.state.projection * self.state.modelview.top();
+        world_to_screen_point2(&transform, &self.viewport(), world)
+    }
+
+    /// Like [`Graphics::screen_to_world`], but also inverts the camera's view transform, for when
+    /// the camera's transform has not been pushed onto the modelview.
+    pub fn screen_to_world_with_camera(&self, camera: &Camera, screen: Point2<f32>) -> Point2<f32> {
+        let transform = self.state.projection * camera.view_tx() * self.state.modelview.top();
+        screen_to_world_point2(&transform, &self.viewport(), screen)
+    }
+
+    /// Like [`Graphics::world_to_screen`], but also applies the camera's view transform, for when
+    /// the camera's transform has not been pushed onto the modelview.
+    pub fn world_to_screen_with_camera(&self, camera: &Camera, world: Point2<f32>) -> Point2<f32> {
+        let transform = self.state.projection * camera.view_tx() * self.state.modelview.top();
+        world_to_screen_point2(&transform, &self.viewport(), world)
     }
 
     #[inline]
@@ -717,6 +1164,13 @@ impl<'a> Graphics<'a> {
         self.mq.apply_pipeline(&self.state.default_pipeline);
     }
 
+    /// Apply the built-in pipeline which samples from one of several textures according to each
+    /// instance's [`Instance::page`]. Its bindings must have [`basic::MAX_PAGES`] images.
+    #[inline]
+    pub fn apply_paged_pipeline(&mut self) {
+        self.mq.apply_pipeline(&self.state.paged_pipeline);
+    }
+
     #[inline]
     pub fn apply_pipeline(&mut self, pipeline: &Pipeline) {
         self.mq.apply_pipeline(&pipeline.handle);
@@ -743,6 +1197,8 @@ impl<'a> Graphics<'a> {
         pass: Option<&RenderPass>,
         clear_options: Option<ClearOptions>,
     ) {
+        self.state.viewport = None;
+        self.state.scissor = None;
         self.mq.begin_pass(
             pass.map(|rp| rp.handle),
             match clear_options {
@@ -789,10 +1245,19 @@ pub(crate) fn open<'lua>(lua: &'lua Lua, engine: &Engine) -> Result<LuaTable<'lu
     lua.insert_resource(texture_cache.clone())?;
 
     let clone = texture_cache.clone();
-    let load_texture_from_filesystem = lua.create_function(move |_, path: LuaString| {
-        let cache = &mut clone.borrow_mut();
-        cache.get_or_load(path.to_str()?).to_lua_err()
-    })?;
+    let gfx = gfx_lock.clone();
+    let load_texture_from_filesystem = lua.create_function(
+        move |_, (path, options): (LuaString, Option<TextureOptions>)| {
+            let texture = clone
+                .borrow_mut()
+                .get_or_load(path.to_str()?)
+                .to_lua_err()?;
+            if let Some(options) = options {
+                texture.get().set_options(&mut gfx.lock(), options);
+            }
+            Ok(texture)
+        },
+    )?;
 
     let sprite_sheet_cache = engine.insert(SpriteSheetCache::new(engine));
     lua.insert_resource(sprite_sheet_cache.clone())?;
@@ -803,6 +1268,16 @@ pub(crate) fn open<'lua>(lua: &'lua Lua, engine: &Engine) -> Result<LuaTable<'lu
         cache.get_or_load(path.to_str()?).to_lua_err()
     })?;
 
+    let clone = texture_cache.clone();
+    engine.register_asset_kind("texture", move |path| {
+        clone.borrow_mut().get_or_load(path).map(drop)
+    });
+
+    let clone = sprite_sheet_cache.clone();
+    engine.register_asset_kind("sprite_sheet", move |path| {
+        clone.borrow_mut().get_or_load(path).map(drop)
+    });
+
     let reload_textures =
         lua.create_function(move |_, ()| texture_cache.borrow_mut().reload_all().to_lua_err())?;
 
@@ -813,13 +1288,27 @@ pub(crate) fn open<'lua>(lua: &'lua Lua, engine: &Engine) -> Result<LuaTable<'lu
 
     let gfx = gfx_lock.clone();
     let create_sprite_batch_object = lua.create_function(
-        move |_, (texture, maybe_capacity): (CachedTexture, Option<usize>)| match maybe_capacity {
-            Some(capacity) => Ok(SpriteBatch::with_capacity(
+        move |lua, (texture_or_pages, maybe_capacity): (LuaValue, Option<usize>)| {
+            // Either a single texture, or a table of textures to use as pages.
+            let pages = match texture_or_pages {
+                LuaValue::Table(_) => Vec::<CachedTexture>::from_lua(texture_or_pages, lua)?,
+                other => vec![CachedTexture::from_lua(other, lua)?],
+            };
+
+            if pages.is_empty() || pages.len() > basic::MAX_PAGES {
+                return Err(anyhow!(
+                    "a spritebatch must have between 1 and {} pages, got {}",
+                    basic::MAX_PAGES,
+                    pages.len()
+                ))
+                .to_lua_err();
+            }
+
+            Ok(SpriteBatch::with_pages(
                 &mut gfx.lock(),
-                texture,
-                capacity,
-            )),
-            None => Ok(SpriteBatch::new(&mut gfx.lock(), texture)),
+                pages,
+                maybe_capacity.unwrap_or(sprite::DEFAULT_SPRITEBATCH_CAPACITY),
+            ))
         },
     )?;
 
@@ -853,6 +1342,15 @@ pub(crate) fn open<'lua>(lua: &'lua Lua, engine: &Engine) -> Result<LuaTable<'lu
         Ok(())
     })?;
 
+    let gfx = gfx_lock.clone();
+    let set_y_axis = lua.create_function(move |_, y_axis: YAxis| {
+        gfx.lock().set_y_axis(y_axis);
+        Ok(())
+    })?;
+
+    let gfx = gfx_lock.clone();
+    let get_y_axis = lua.create_function(move |_, ()| Ok(gfx.lock().y_axis()))?;
+
     let gfx = gfx_lock.clone();
     let apply_default_pipeline = lua.create_function(move |_, ()| {
         gfx.lock().apply_default_pipeline();
@@ -909,6 +1407,8 @@ pub(crate) fn open<'lua>(lua: &'lua Lua, engine: &Engine) -> Result<LuaTable<'lu
     let scale = lua.create_function(self::lua::scale(gfx_lock.clone()))?;
     let shear = lua.create_function(self::lua::shear(gfx_lock.clone()))?;
     let transform_point = lua.create_function(self::lua::transform_point(gfx_lock.clone()))?;
+    let screen_to_world = lua.create_function(self::lua::screen_to_world(gfx_lock.clone()))?;
+    let world_to_screen = lua.create_function(self::lua::world_to_screen(gfx_lock.clone()))?;
     let translate = lua.create_function(self::lua::translate(gfx_lock.clone()))?;
 
     let get_dimensions = lua.create_function(self::lua::get_dimensions(gfx_lock))?;
@@ -931,6 +1431,9 @@ pub(crate) fn open<'lua>(lua: &'lua Lua, engine: &Engine) -> Result<LuaTable<'lu
 
                 apply_modelview = $apply_modelview,
 
+                set_y_axis = $set_y_axis,
+                get_y_axis = $get_y_axis,
+
                 apply_default_pipeline = $apply_default_pipeline,
                 apply_pipeline = $apply_pipeline,
                 begin_render_pass = $begin_render_pass,
@@ -963,6 +1466,8 @@ pub(crate) fn open<'lua>(lua: &'lua Lua, engine: &Engine) -> Result<LuaTable<'lu
                 scale = $scale,
                 shear = $shear,
                 transform_point = $transform_point,
+                screen_to_world = $screen_to_world,
+                world_to_screen = $world_to_screen,
                 translate = $translate,
 
                 get_dimensions = $get_dimensions,
@@ -975,3 +1480,99 @@ pub(crate) fn open<'lua>(lua: &'lua Lua, engine: &Engine) -> Result<LuaTable<'lu
         })
         .eval()?)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_points_eq(a: Point2<f32>, b: Point2<f32>) {
+        assert!((a - b).norm() < 1e-3, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn screen_world_roundtrip_letterboxed() {
+        // A 320x240 game letterboxed into the middle of an 800x480 window, drawn with a
+        // non-uniformly scaled and translated modelview.
+        let projection = Orthographic3::new(0., 320., 0., 240., -1., 1.).to_homogeneous();
+        let mut modelview = TransformStack::new();
+        modelview
+            .translate2(Vector2::new(10., 20.))
+            .scale2(Vector2::new(2., 3.));
+        let transform = projection * modelview.top();
+        let viewport = Box2::new(80., 0., 640., 480.);
+
+        // The world origin ends up at (10, 20) in game space, which is 1/32 of the way across and
+        // 1/12 of the way up the viewport.
+        let origin = world_to_screen_point2(&transform, &viewport, Point2::origin());
+        assert_points_eq(origin, Point2::new(100., 440.));
+        assert_points_eq(
+            screen_to_world_point2(&transform, &viewport, origin),
+            Point2::origin(),
+        );
+
+        for &(x, y) in &[(1., 1.), (-7.5, 12.), (150., -30.)] {
+            let world = Point2::new(x, y);
+            let screen = world_to_screen_point2(&transform, &viewport, world);
+            assert_points_eq(screen_to_world_point2(&transform, &viewport, screen), world);
+        }
+    }
+
+    #[test]
+    fn y_axis_projections_are_mirrored() {
+        let up = YAxis::Up.orthographic(320., 240.);
+        let down = YAxis::Down.orthographic(320., 240.);
+        let flip = Matrix4::new_nonuniform_scaling(&Vector3::new(1., -1., 1.));
+        assert!((flip * up - down).norm() < 1e-6);
+
+        // The top left corner of the screen is the origin when Y points down, and (0, height) when
+        // it points up.
+        let top_left = Point3::new(-1., 1., 0.);
+        assert!((down.transform_point(&Point3::origin()) - top_left).norm() < 1e-6);
+        assert!((up.transform_point(&Point3::new(0., 240., 0.)) - top_left).norm() < 1e-6);
+    }
+
+    #[test]
+    fn instance_lerp_halfway() {
+        let a = Instance::new()
+            .src(Box2::new(0., 0., 0.5, 0.5))
+            .color(Color::new(1., 0., 0., 1.))
+            .rotate2(170f32.to_radians())
+            .scale2(Vector2::new(1., 2.));
+        let b = Instance::new()
+            .src(Box2::new(0.5, 0.5, 0.5, 0.25))
+            .color(Color::new(0., 0., 1., 0.))
+            .page(1)
+            .translate2(Vector2::new(10., -4.))
+            .rotate2(-170f32.to_radians())
+            .scale2(Vector2::new(3., 4.));
+
+        let mid = a.lerp(&b, 0.5);
+
+        // 170 and -170 degrees are 20 degrees apart going the short way, through 180 degrees, and
+        // not through 0 degrees.
+        let expected = Instance::new()
+            .translate2(Vector2::new(5., -2.))
+            .rotate2(std::f32::consts::PI)
+            .scale2(Vector2::new(2., 3.));
+        let diff = mid.tx.matrix() - expected.tx.matrix();
+        assert!(
+            diff.norm() < 1e-4,
+            "{} != {}",
+            mid.tx.matrix(),
+            expected.tx.matrix()
+        );
+
+        // Halfway between full and no intensity in linear space is brighter than 0.5 in sRGB.
+        let linear = LinearColor::from(mid.color);
+        assert!((linear.r - 0.5).abs() < 1e-4);
+        assert!(linear.g.abs() < 1e-4);
+        assert!((linear.b - 0.5).abs() < 1e-4);
+        assert!((mid.color.a - 0.5).abs() < 1e-4);
+        assert!((mid.color.r - 0.735).abs() < 1e-3);
+
+        assert_points_eq(mid.src.mins, Point2::new(0.25, 0.25));
+        assert!((mid.src.extents() - Vector2::new(0.5, 0.375)).norm() < 1e-4);
+        assert_eq!(mid.page, 1);
+        assert_eq!(a.lerp(&b, 0.25).page, 0);
+    }
+}
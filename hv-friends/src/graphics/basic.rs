@@ -3,6 +3,19 @@ use hv_core::mq;
 
 pub const BASIC_VERTEX: &str = include_str!("basic_es300.glslv");
 pub const BASIC_FRAGMENT: &str = include_str!("basic_es300.glslf");
+pub const BASIC_PAGED_FRAGMENT: &str = include_str!("basic_paged_es300.glslf");
+
+/// The number of texture pages the paged fragment shader ([`BASIC_PAGED_FRAGMENT`]) can sample
+/// from. Instances select a page with [`Instance::page`](crate::graphics::Instance::page).
+pub const MAX_PAGES: usize = 4;
+
+/// The number of custom floats an instance can carry for a pipeline with custom instance
+/// attributes. See [`Instance::with_custom`](crate::graphics::Instance::with_custom) and
+/// [`PipelineLayout::with_custom_instance_attributes`].
+///
+/// [`PipelineLayout::with_custom_instance_attributes`]:
+///     crate::graphics::pipeline::PipelineLayout::with_custom_instance_attributes
+pub const MAX_CUSTOM_FLOATS: usize = 8;
 
 pub fn meta() -> mq::ShaderMeta {
     mq::ShaderMeta {
@@ -13,6 +26,24 @@ pub fn meta() -> mq::ShaderMeta {
     }
 }
 
+/// Shader metadata for [`BASIC_PAGED_FRAGMENT`]. Page `n` is sampled from the `n`th image.
+pub fn paged_meta() -> mq::ShaderMeta {
+    mq::ShaderMeta {
+        images: (0..MAX_PAGES).map(page_sampler_name).collect(),
+        uniforms: mq::UniformBlockLayout {
+            uniforms: vec![mq::UniformDesc::new("u_MVP", mq::UniformType::Mat4)],
+        },
+    }
+}
+
+/// The name of the sampler uniform which the paged fragment shader reads page `page` from.
+pub fn page_sampler_name(page: usize) -> String {
+    match page {
+        0 => "t_Texture".to_string(),
+        n => format!("t_Texture{}", n),
+    }
+}
+
 #[repr(C)]
 pub struct Uniforms {
     pub mvp: Matrix4<f32>,
@@ -32,4 +63,5 @@ pub struct InstanceProperties {
     pub src: Vector4<f32>,
     pub tx: Matrix4<f32>,
     pub color: LinearColor,
+    pub page: f32,
 }
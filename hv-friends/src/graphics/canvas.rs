@@ -1,12 +1,49 @@
 use hv_core::{mlua::prelude::*, mq};
 
-use crate::graphics::{Drawable, DrawableMut, Graphics, Instance, RenderPass, SharedTexture};
+use crate::{
+    graphics::{
+        ClearOptions, Color, Drawable, DrawableMut, FilterMode, Graphics, Instance, RenderPass,
+        SharedTexture, Texture, TextureOptions,
+    },
+    math::*,
+};
+
+/// The sample counts a [`Canvas`] can be created with, in ascending order.
+///
+/// miniquad doesn't expose multisampled render targets, so multisampled canvases are rendered at a
+/// higher resolution and then resolved down with linear filtering. A single linear sample exactly
+/// averages a 2x2 block of texels, which is where the 4x limit comes from.
+pub const SUPPORTED_SAMPLE_COUNTS: &[u8] = &[1, 4];
+
+/// Find the sample count a [`Canvas`] will actually use when `requested` samples are asked for:
+/// the largest supported count not greater than `requested`, or 1 if there is none.
+pub fn effective_sample_count(requested: u8) -> u8 {
+    SUPPORTED_SAMPLE_COUNTS
+        .iter()
+        .copied()
+        .filter(|&count| count <= requested)
+        .max()
+        .unwrap_or(1)
+}
+
+#[derive(Debug)]
+struct Multisampled {
+    resolve_pass: RenderPass,
+    sample_buffer: SharedTexture,
+    _depth_buffer: SharedTexture,
+}
 
 #[derive(Debug)]
 pub struct Canvas {
+    /// The render pass to draw into.
     pub render_pass: RenderPass,
+    /// The texture drawn when the canvas itself is drawn. For multisampled canvases, this only has
+    /// up-to-date contents after [`Canvas::resolve`].
     pub color_buffer: SharedTexture,
+    /// The depth buffer attached alongside [`Canvas::color_buffer`].
     pub depth_buffer: SharedTexture,
+    samples: u8,
+    multisampled: Option<Multisampled>,
 }
 
 impl AsRef<RenderPass> for Canvas {
@@ -15,29 +52,43 @@ impl AsRef<RenderPass> for Canvas {
     }
 }
 
+fn render_texture(
+    ctx: &mut Graphics,
+    width: u32,
+    height: u32,
+    format: mq::TextureFormat,
+    filter: FilterMode,
+) -> SharedTexture {
+    let options = TextureOptions::default().with_filter(filter);
+    let handle = mq::Texture::new_render_texture(
+        &mut ctx.mq,
+        mq::TextureParams {
+            width,
+            height,
+            format,
+            filter: options.filter.into(),
+            wrap: options.wrap.into(),
+        },
+    );
+    SharedTexture::from(Texture::from_inner_with_options(handle, options))
+}
+
 impl Canvas {
     pub fn new(ctx: &mut Graphics, width: u32, height: u32) -> Self {
-        let color_img = SharedTexture::from(mq::Texture::new_render_texture(
-            &mut ctx.mq,
-            mq::TextureParams {
-                width,
-                height,
-                format: mq::TextureFormat::RGBA8,
-                filter: mq::FilterMode::Nearest,
-                ..Default::default()
-            },
-        ));
-
-        let depth_img = SharedTexture::from(mq::Texture::new_render_texture(
-            &mut ctx.mq,
-            mq::TextureParams {
-                width,
-                height,
-                format: mq::TextureFormat::Depth,
-                filter: mq::FilterMode::Nearest,
-                ..Default::default()
-            },
-        ));
+        let color_img = render_texture(
+            ctx,
+            width,
+            height,
+            mq::TextureFormat::RGBA8,
+            FilterMode::Nearest,
+        );
+        let depth_img = render_texture(
+            ctx,
+            width,
+            height,
+            mq::TextureFormat::Depth,
+            FilterMode::Nearest,
+        );
 
         let render_pass = RenderPass::from_parts(ctx, color_img.handle, Some(depth_img.handle));
 
@@ -45,8 +96,99 @@ impl Canvas {
             render_pass,
             color_buffer: color_img,
             depth_buffer: depth_img,
+            samples: 1,
+            multisampled: None,
         }
     }
+
+    /// Create a canvas which is anti-aliased using the given number of samples per pixel.
+    /// Unsupported sample counts fall back to the nearest lower supported count (see
+    /// [`effective_sample_count`]) rather than failing; [`Canvas::samples`] returns the count
+    /// actually used.
+    ///
+    /// Multisampled canvases must be [resolved](Canvas::resolve) after drawing to them and before
+    /// drawing them.
+    pub fn with_samples(ctx: &mut Graphics, width: u32, height: u32, samples: u8) -> Self {
+        let samples = effective_sample_count(samples);
+        if samples == 1 {
+            return Self::new(ctx, width, height);
+        }
+
+        let factor = (samples as f32).sqrt() as u32;
+        let sample_img = render_texture(
+            ctx,
+            width * factor,
+            height * factor,
+            mq::TextureFormat::RGBA8,
+            FilterMode::Linear,
+        );
+        let sample_depth_img = render_texture(
+            ctx,
+            width * factor,
+            height * factor,
+            mq::TextureFormat::Depth,
+            FilterMode::Nearest,
+        );
+        let render_pass =
+            RenderPass::from_parts(ctx, sample_img.handle, Some(sample_depth_img.handle));
+
+        let resolved = Self::new(ctx, width, height);
+
+        Self {
+            render_pass,
+            color_buffer: resolved.color_buffer,
+            depth_buffer: resolved.depth_buffer,
+            samples,
+            multisampled: Some(Multisampled {
+                resolve_pass: resolved.render_pass,
+                sample_buffer: sample_img,
+                _depth_buffer: sample_depth_img,
+            }),
+        }
+    }
+
+    /// The number of samples per pixel this canvas actually uses.
+    pub fn samples(&self) -> u8 {
+        self.samples
+    }
+
+    /// Resolve the samples drawn into [`Canvas::render_pass`] into [`Canvas::color_buffer`]. This
+    /// must be called outside of any render pass, and does nothing if the canvas isn't
+    /// multisampled. The resolved samples are alpha-blended, so translucent contents come out
+    /// slightly darker than they would without multisampling.
+    pub fn resolve(&self, ctx: &mut Graphics) {
+        let multisampled = match &self.multisampled {
+            Some(multisampled) => multisampled,
+            None => return,
+        };
+
+        let (width, height) = (
+            self.color_buffer.width() as f32,
+            self.color_buffer.height() as f32,
+        );
+        let factor = multisampled.sample_buffer.width() as f32 / width;
+        let projection = *ctx.projection();
+        let default_projection_size = ctx.state.default_projection_size;
+
+        ctx.set_projection(Orthographic3::new(0., width, 0., height, -1., 1.).to_homogeneous());
+        ctx.modelview_mut().push(Matrix4::identity());
+        ctx.begin_render_pass(
+            Some(&multisampled.resolve_pass),
+            Some(ClearOptions {
+                color: Some(Color::ZEROS),
+                ..ClearOptions::default()
+            }),
+        );
+        ctx.apply_default_pipeline();
+        ctx.draw(
+            &multisampled.sample_buffer,
+            Instance::new().scale2(Vector2::repeat(1. / factor)),
+        );
+        ctx.end_render_pass();
+        ctx.modelview_mut().pop();
+        ctx.set_projection(projection);
+        ctx.state.default_projection_size = default_projection_size;
+    }
 }
 
 impl DrawableMut for Canvas {
@@ -64,5 +206,21 @@ impl Drawable for Canvas {
 impl LuaUserData for Canvas {
     fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
         fields.add_field_method_get("render_pass", |_, this| Ok(this.render_pass.clone()));
+        fields.add_field_method_get("samples", |_, this| Ok(this.samples));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unsupported_sample_counts_clamp() {
+        assert_eq!(effective_sample_count(0), 1);
+        assert_eq!(effective_sample_count(1), 1);
+        assert_eq!(effective_sample_count(2), 1);
+        assert_eq!(effective_sample_count(4), 4);
+        assert_eq!(effective_sample_count(8), 4);
+        assert_eq!(effective_sample_count(u8::MAX), 4);
     }
 }
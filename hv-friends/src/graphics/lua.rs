@@ -3,6 +3,7 @@ use std::sync::Arc;
 use hv_core::{engine::WeakResourceCache, prelude::*};
 
 use crate::{
+    camera::Camera,
     graphics::{
         text::{CachedFontAtlas, CharacterListType, FontAtlas, Text, TextLayout},
         CachedTexture, ClearOptions, Color, DrawMode, DrawableMut, Graphics, GraphicsLock,
@@ -61,12 +62,22 @@ impl LuaUserData for MeshBuilder {
 
         methods.add_method_mut(
             "circle",
-            |_, this, (draw_mode, x, y, radius, tolerance, color)| {
+            |_, this, (draw_mode, x, y, radius, tolerance, color): (_, _, _, _, Option<f32>, _)| {
                 this.circle(draw_mode, Point2::new(x, y), radius, tolerance, color);
                 Ok(())
             },
         );
 
+        methods.add_method_mut("set_tolerance", |_, this, tolerance| {
+            this.set_tolerance(tolerance);
+            Ok(())
+        });
+
+        methods.add_method_mut("set_anti_aliasing", |_, this, width| {
+            this.set_anti_aliasing(width);
+            Ok(())
+        });
+
         methods.add_method_mut(
             "polygon",
             |_, this, (draw_mode, points, color): (DrawMode, PointBuffer, Color)| {
@@ -80,6 +91,15 @@ impl LuaUserData for MeshBuilder {
             Ok(())
         });
 
+        methods.add_method_mut(
+            "textured_rectangle",
+            |_, this, (x, y, w, h, texture, u, v): (_, _, _, _, CachedTexture, f32, Option<f32>)| {
+                let uv_scale = Vector2::new(u, v.unwrap_or(u));
+                this.textured_rectangle(Box2::new(x, y, w, h), texture, uv_scale);
+                Ok(())
+            },
+        );
+
         methods.add_method_mut(
             "raw",
             |_, this, (vertices, indices, texture): (VertexBuffer, IndexBuffer, Option<CachedTexture>)| {
@@ -182,7 +202,7 @@ impl LuaGraphicsState {
         };
 
         self.mesh_builder
-            .circle(mode, point, radius, 0.1, self.color);
+            .circle(mode, point, radius, None, self.color);
 
         let mesh = match &mut self.mesh {
             Some(mesh) => {
@@ -452,6 +472,36 @@ pub(crate) fn inverse_transform_point(
     }
 }
 
+pub(crate) fn screen_to_world(
+    gfx_lock: Shared<GraphicsLock>,
+) -> lua_fn!(Fn<'lua>((f32, f32, Option<LuaAnyUserData<'lua>>)) -> (f32, f32)) {
+    move |_, (x, y, maybe_camera)| {
+        let gfx = gfx_lock.lock();
+        let out = match maybe_camera {
+            Some(ud) => {
+                gfx.screen_to_world_with_camera(&*ud.borrow::<Camera>()?, Point2::new(x, y))
+            }
+            None => gfx.screen_to_world(Point2::new(x, y)),
+        };
+        Ok((out.x, out.y))
+    }
+}
+
+pub(crate) fn world_to_screen(
+    gfx_lock: Shared<GraphicsLock>,
+) -> lua_fn!(Fn<'lua>((f32, f32, Option<LuaAnyUserData<'lua>>)) -> (f32, f32)) {
+    move |_, (x, y, maybe_camera)| {
+        let gfx = gfx_lock.lock();
+        let out = match maybe_camera {
+            Some(ud) => {
+                gfx.world_to_screen_with_camera(&*ud.borrow::<Camera>()?, Point2::new(x, y))
+            }
+            None => gfx.world_to_screen(Point2::new(x, y)),
+        };
+        Ok((out.x, out.y))
+    }
+}
+
 pub(crate) fn origin(gfx_lock: Shared<GraphicsLock>) -> lua_fn!(Fn<'lua>(()) -> ()) {
     move |_, ()| {
         gfx_lock.lock().modelview_mut().origin();
@@ -534,5 +584,5 @@ pub(crate) fn translate(gfx_lock: Shared<GraphicsLock>) -> lua_fn!(Fn<'lua>((f32
 pub(crate) fn get_dimensions(
     gfx_lock: Shared<GraphicsLock>,
 ) -> lua_fn!(Fn<'lua>(()) -> (f32, f32)) {
-    move |_, ()| Ok(gfx_lock.lock().mq().screen_size())
+    move |_, ()| Ok(gfx_lock.lock().screen_size())
 }
@@ -26,7 +26,10 @@ SOFTWARE.
 
 use hv_core::prelude::*;
 use lyon::tessellation::{self as t, FillOptions, StrokeOptions};
-use std::mem;
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    mem,
+};
 
 use crate::{
     graphics::{
@@ -87,10 +90,95 @@ impl t::StrokeVertexConstructor<Vertex> for VertexBuilder {
     }
 }
 
+/// The tolerance curves are flattened with when a [`MeshBuilder`] isn't given one.
+pub const DEFAULT_TOLERANCE: f32 = FillOptions::DEFAULT_TOLERANCE;
+
+/// Add an anti-aliasing fringe `width` wide around the outline of the filled triangles in
+/// `buffer`, starting from the index `first_index`. Every vertex on the outline gets one extra
+/// vertex pushed outwards from it, with the same color but fully transparent, and every edge of the
+/// outline gets a quad joining it to its pushed-out copy; so the shape fades out over the width of
+/// the fringe rather than ending on a hard, aliased edge.
+fn add_fringe(buffer: &mut t::VertexBuffers<Vertex, u16>, first_index: usize, width: f32) {
+    // Edges on the outline belong to exactly one triangle. Keep the vertex opposite each edge, so
+    // that we know which side of it is the inside.
+    let mut edges = HashMap::<(u16, u16), (usize, u16)>::new();
+    let mut edge_order = Vec::new();
+    for triangle in buffer.indices[first_index..].chunks_exact(3) {
+        for i in 0..3 {
+            let (a, b, opposite) = (triangle[i], triangle[(i + 1) % 3], triangle[(i + 2) % 3]);
+            match edges.entry((a.min(b), a.max(b))) {
+                Entry::Occupied(mut entry) => entry.get_mut().0 += 1,
+                Entry::Vacant(entry) => {
+                    entry.insert((1, opposite));
+                    edge_order.push((a, b));
+                }
+            }
+        }
+    }
+
+    let position = |index: u16| buffer.vertices[index as usize].pos.xy();
+    let mut outline = Vec::new();
+    let mut normals = HashMap::<u16, (Vector2<f32>, f32)>::new();
+    let mut vertex_order = Vec::new();
+    for (a, b) in edge_order {
+        let (count, opposite) = edges[&(a.min(b), a.max(b))];
+        let edge = position(b) - position(a);
+        if count != 1 || edge.norm() <= f32::EPSILON {
+            continue;
+        }
+
+        let mut normal = Vector2::new(edge.y, -edge.x).normalize();
+        if normal.dot(&(position(opposite) - position(a))) > 0. {
+            normal = -normal;
+        }
+
+        outline.push((a, b));
+        for vertex in [a, b] {
+            let (sum, count) = normals.entry(vertex).or_insert_with(|| {
+                vertex_order.push(vertex);
+                (Vector2::zeros(), 0.)
+            });
+            *sum += normal;
+            *count += 1.;
+        }
+    }
+
+    assert!(buffer.vertices.len() + vertex_order.len() < (std::u16::MAX as usize));
+    let mut fringe = HashMap::with_capacity(vertex_order.len());
+    for vertex in vertex_order {
+        // Push the vertex out along the miter of its edges, so the fringe is the same width all the
+        // way round. Very sharp corners would send the miter off into the distance, so it's capped
+        // at twice the width.
+        let (sum, count) = normals[&vertex];
+        let average = sum / count;
+        let offset = average / average.norm_squared().max(0.25) * width;
+
+        let inner = buffer.vertices[vertex as usize];
+        fringe.insert(vertex, buffer.vertices.len() as u16);
+        buffer.vertices.push(Vertex {
+            pos: inner.pos + Vector3::new(offset.x, offset.y, 0.),
+            uv: inner.uv + offset,
+            color: LinearColor {
+                a: 0.,
+                ..inner.color
+            },
+        });
+    }
+
+    for (a, b) in outline {
+        let (outer_a, outer_b) = (fringe[&a], fringe[&b]);
+        buffer
+            .indices
+            .extend_from_slice(&[a, b, outer_b, a, outer_b, outer_a]);
+    }
+}
+
 #[derive(Debug)]
 pub struct MeshBuilder {
     pub buffer: t::geometry_builder::VertexBuffers<Vertex, u16>,
     pub texture: CachedTexture,
+    tolerance: f32,
+    anti_aliasing: Option<f32>,
 }
 
 impl MeshBuilder {
@@ -101,6 +189,43 @@ impl MeshBuilder {
         Self {
             buffer: t::VertexBuffers::new(),
             texture: texture.into(),
+            tolerance: DEFAULT_TOLERANCE,
+            anti_aliasing: None,
+        }
+    }
+
+    /// The tolerance curves are flattened with when a shape isn't given its own. Defaults to
+    /// [`DEFAULT_TOLERANCE`].
+    pub fn tolerance(&self) -> f32 {
+        self.tolerance
+    }
+
+    /// Set the tolerance curves are flattened with when a shape isn't given its own. This is the
+    /// furthest the flattened outline may stray from the true curve, so lower values give smoother
+    /// curves made of more vertices. For the details, [see
+    /// here](https://docs.rs/lyon_geom/0.17.0/lyon_geom/#flattening).
+    pub fn set_tolerance(&mut self, tolerance: f32) -> &mut Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// The width of the anti-aliasing fringe added around filled shapes, if any.
+    pub fn anti_aliasing(&self) -> Option<f32> {
+        self.anti_aliasing
+    }
+
+    /// Add an anti-aliasing fringe of the given width around filled shapes built from now on, or
+    /// stop adding one with `None` (the default.) The fringe fades from the shape's color to fully
+    /// transparent, and lies outside the shape, so the shape grows by its width; a width of about
+    /// one pixel, in the mesh's coordinates, is usually right. Stroked shapes aren't affected.
+    pub fn set_anti_aliasing(&mut self, width: Option<f32>) -> &mut Self {
+        self.anti_aliasing = width;
+        self
+    }
+
+    fn fill_fringe(&mut self, first_index: usize) {
+        if let Some(width) = self.anti_aliasing {
+            add_fringe(&mut self.buffer, first_index, width);
         }
     }
 
@@ -132,17 +257,20 @@ impl MeshBuilder {
     /// Create a new mesh for a circle.
     ///
     /// For the meaning of the `tolerance` parameter, [see here](https://docs.rs/lyon_geom/0.11.0/lyon_geom/#flattening).
+    /// If it's `None`, the builder's [tolerance](MeshBuilder::set_tolerance) is used.
     pub fn circle<P>(
         &mut self,
         mode: DrawMode,
         point: P,
         radius: f32,
-        tolerance: f32,
+        tolerance: impl Into<Option<f32>>,
         color: Color,
     ) -> &mut Self
     where
         P: Into<mint::Point2<f32>>,
     {
+        let tolerance = tolerance.into().unwrap_or(self.tolerance);
+        let first_index = self.buffer.indices.len();
         {
             let point = point.into();
             let buffers = &mut self.buffer;
@@ -172,6 +300,11 @@ impl MeshBuilder {
                 }
             };
         }
+
+        if let DrawMode::Fill(_) = mode {
+            self.fill_fringe(first_index);
+        }
+
         self
     }
 
@@ -200,6 +333,7 @@ impl MeshBuilder {
     where
         P: Into<mint::Point2<f32>> + Clone,
     {
+        let first_index = self.buffer.indices.len();
         {
             assert!(points.len() > 1);
             let buffers = &mut self.buffer;
@@ -232,11 +366,17 @@ impl MeshBuilder {
             }
             .map_err(|e| anyhow!("error during tessellation: {:?}", e))?;
         }
+
+        if let DrawMode::Fill(_) = mode {
+            self.fill_fringe(first_index);
+        }
+
         Ok(self)
     }
 
     /// Create a new mesh for a rectangle.
     pub fn rectangle(&mut self, mode: DrawMode, bounds: Box2<f32>, color: Color) -> &mut Self {
+        let first_index = self.buffer.indices.len();
         {
             let buffers = &mut self.buffer;
             let extents = bounds.extents();
@@ -257,9 +397,50 @@ impl MeshBuilder {
                 }
             };
         }
+
+        if let DrawMode::Fill(_) = mode {
+            self.fill_fringe(first_index);
+        }
+
         self
     }
 
+    /// Add a rectangle covered by `texture`, which repeats `uv_scale.x` times across it and
+    /// `uv_scale.y` times down it. Texture coordinates run from `0` at the rectangle's minimum
+    /// corner to `uv_scale` at its maximum corner, so for the texture to actually tile rather than
+    /// smear its edge texels across the rest of the rectangle, it should use
+    /// [`WrapMode::Repeat`](crate::graphics::WrapMode::Repeat) (see
+    /// [`Texture::set_wrap`](crate::graphics::Texture::set_wrap).) A `uv_scale` of `1` stretches the
+    /// texture over the rectangle once.
+    ///
+    /// Like [`MeshBuilder::raw`], this replaces the texture the whole mesh is drawn with. The
+    /// rectangle is drawn white, so it shows the texture as-is; and it doesn't get an anti-aliasing
+    /// fringe, since there's no sensible way to extend its texture coordinates into one.
+    pub fn textured_rectangle<T>(
+        &mut self,
+        bounds: Box2<f32>,
+        texture: T,
+        uv_scale: Vector2<f32>,
+    ) -> &mut Self
+    where
+        T: Into<CachedTexture>,
+    {
+        let color = LinearColor::from(Color::WHITE);
+        let vertex = |x: f32, y: f32, u: f32, v: f32| Vertex {
+            pos: Vector3::new(x, y, 0.),
+            uv: Vector2::new(u, v),
+            color,
+        };
+        let (mins, maxs) = (bounds.mins, bounds.maxs);
+        let vertices = [
+            vertex(mins.x, mins.y, 0., 0.),
+            vertex(maxs.x, mins.y, uv_scale.x, 0.),
+            vertex(maxs.x, maxs.y, uv_scale.x, uv_scale.y),
+            vertex(mins.x, maxs.y, 0., uv_scale.y),
+        ];
+        self.raw(&vertices, &[0, 1, 2, 0, 2, 3], texture.into())
+    }
+
     /// Creates a `Mesh` from a raw list of triangles defined from vertices
     /// and indices.  You may also
     /// supply an `Image` to use as a texture, if you pass `None`, it will
@@ -397,3 +578,118 @@ impl DrawableMut for Mesh {
         ctx.mq.draw(0, self.len, self.instances);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::mq;
+
+    // The texture is never uploaded anywhere, and dropping it would try to delete it through a GL
+    // context which doesn't exist in tests, so the builder is leaked instead.
+    fn with_builder<R>(f: impl FnOnce(&mut MeshBuilder) -> R) -> R {
+        let mut builder = mem::ManuallyDrop::new(MeshBuilder::new(mq::Texture::empty()));
+        f(&mut builder)
+    }
+
+    fn circle_counts(tolerance: f32, anti_aliasing: Option<f32>) -> (usize, usize) {
+        with_builder(|builder| {
+            builder
+                .set_tolerance(tolerance)
+                .set_anti_aliasing(anti_aliasing)
+                .circle(DrawMode::fill(), Point2::origin(), 50., None, Color::WHITE);
+            (builder.buffer.vertices.len(), builder.buffer.indices.len())
+        })
+    }
+
+    #[test]
+    fn lower_tolerance_gives_smoother_circles() {
+        let (coarse, _) = circle_counts(1., None);
+        let (fine, _) = circle_counts(0.01, None);
+        assert!(fine > coarse, "{} <= {}", fine, coarse);
+
+        // An explicit tolerance overrides the builder's.
+        let explicit = with_builder(|builder| {
+            builder.set_tolerance(1.).circle(
+                DrawMode::fill(),
+                Point2::origin(),
+                50.,
+                0.01,
+                Color::WHITE,
+            );
+            builder.buffer.vertices.len()
+        });
+        assert_eq!(explicit, fine);
+    }
+
+    #[test]
+    fn anti_aliasing_adds_one_fringe_vertex_per_outline_vertex() {
+        let (vertices, indices) = circle_counts(0.1, None);
+        let (aa_vertices, aa_indices) = circle_counts(0.1, Some(1.));
+        // The outline of a circle is a single loop, with as many edges as vertices, and each edge
+        // gets a quad.
+        let fringe = aa_vertices - vertices;
+        assert!(fringe > 0);
+        assert_eq!(aa_indices - indices, fringe * 6);
+
+        with_builder(|builder| {
+            let color = Color::new(1., 0., 0., 1.);
+            let bounds = Box2::new(0., 0., 10., 10.);
+            builder.rectangle(DrawMode::fill(), bounds, color);
+            assert_eq!(builder.buffer.vertices.len(), 4);
+            assert_eq!(builder.buffer.indices.len(), 6);
+
+            builder.set_anti_aliasing(Some(1.));
+            builder.rectangle(DrawMode::fill(), bounds, color);
+            assert_eq!(builder.buffer.vertices.len(), 4 + 8);
+            assert_eq!(builder.buffer.indices.len(), 6 + 6 + 4 * 6);
+
+            // The fringe is transparent, and its corners are pushed out diagonally.
+            let mut fringe = builder.buffer.vertices[8..]
+                .iter()
+                .map(|vertex| {
+                    assert_eq!(vertex.color.a, 0.);
+                    assert_eq!(vertex.color.r, LinearColor::from(color).r);
+                    (vertex.pos.x.round() as i32, vertex.pos.y.round() as i32)
+                })
+                .collect::<Vec<_>>();
+            fringe.sort_unstable();
+            assert_eq!(fringe, [(-1, -1), (-1, 11), (11, -1), (11, 11)]);
+        });
+    }
+
+    #[test]
+    fn textured_rectangle_uvs_span_the_uv_scale() {
+        with_builder(|builder| {
+            // Something to check the indices are offset past.
+            builder.rectangle(DrawMode::fill(), Box2::new(0., 0., 1., 1.), Color::WHITE);
+            let first_vertex = builder.buffer.vertices.len();
+
+            let texture = builder.texture.clone();
+            builder.textured_rectangle(
+                Box2::new(10., 20., 100., 50.),
+                texture,
+                Vector2::repeat(4.),
+            );
+
+            let vertices = &builder.buffer.vertices[first_vertex..];
+            let uvs = vertices.iter().map(|vertex| vertex.uv).collect::<Vec<_>>();
+            let (u_min, u_max) = uvs.iter().fold((f32::MAX, f32::MIN), |(lo, hi), uv| {
+                (lo.min(uv.x), hi.max(uv.x))
+            });
+            let (v_min, v_max) = uvs.iter().fold((f32::MAX, f32::MIN), |(lo, hi), uv| {
+                (lo.min(uv.y), hi.max(uv.y))
+            });
+            assert_eq!((u_min, u_max, v_min, v_max), (0., 4., 0., 4.));
+
+            // The far corner of the rectangle gets the far corner of the texture coordinates.
+            let far = vertices
+                .iter()
+                .find(|vertex| vertex.pos.xy() == Vector2::new(110., 70.))
+                .unwrap();
+            assert_eq!(far.uv, Vector2::new(4., 4.));
+
+            let indices = &builder.buffer.indices[builder.buffer.indices.len() - 6..];
+            assert!(indices.iter().all(|&i| i as usize >= first_vertex));
+        });
+    }
+}
@@ -3,7 +3,9 @@ use hv_core::{mq, prelude::*};
 use std::{ops, sync::Arc};
 use thunderdome::{Arena, Index};
 
-use crate::graphics::{BlendMode, Graphics, GraphicsLock, GraphicsLockExt};
+use crate::graphics::{
+    basic::MAX_CUSTOM_FLOATS, BlendMode, Graphics, GraphicsLock, GraphicsLockExt,
+};
 
 /// Indicates whether or not a buffer should be indexed per-vertex or per-instance. Per-instance
 /// steps are useful for holding transforms/different parameters when drawing many instances at once.
@@ -148,6 +150,21 @@ impl From<VertexFormat> for mq::VertexFormat {
     }
 }
 
+impl VertexFormat {
+    /// Whether this format is made of 32-bit floats, and so can be filled from an instance's
+    /// custom data.
+    pub fn is_float(&self) -> bool {
+        matches!(
+            self,
+            VertexFormat::Float1
+                | VertexFormat::Float2
+                | VertexFormat::Float3
+                | VertexFormat::Float4
+                | VertexFormat::Mat4
+        )
+    }
+}
+
 impl LuaUserData for VertexFormat {}
 
 #[derive(Debug, Clone)]
@@ -196,11 +213,69 @@ impl Default for PipelineLayout {
                 VertexAttribute::new("a_Src", VertexFormat::Float4, 1),
                 VertexAttribute::new("a_Tx", VertexFormat::Mat4, 1),
                 VertexAttribute::new("a_Color", VertexFormat::Float4, 1),
+                VertexAttribute::new("a_Page", VertexFormat::Float1, 1),
             ],
         }
     }
 }
 
+/// The index of the vertex buffer holding custom instance attributes, in a layout created with
+/// [`PipelineLayout::with_custom_instance_attributes`].
+pub const CUSTOM_INSTANCE_BUFFER: usize = 2;
+
+impl PipelineLayout {
+    /// The default layout, plus a third, per-instance buffer holding the given custom instance
+    /// attributes in order. The attributes are filled from each instance's
+    /// [`Instance::custom`](crate::graphics::Instance::custom) data, so they must all be float
+    /// formats, and together they can take up at most [`MAX_CUSTOM_FLOATS`] floats. Their names
+    /// and formats must match the `in` declarations of the vertex shader the layout is used with;
+    /// for example, `("a_Age", VertexFormat::Float1)` corresponds to `in mediump float a_Age;`.
+    ///
+    /// A [`SpriteBatch`](crate::graphics::SpriteBatch) drawn with such a pipeline must be told
+    /// about the layout with
+    /// [`SpriteBatch::set_custom_layout`](crate::graphics::SpriteBatch::set_custom_layout), so that
+    /// it uploads the custom data to the matching buffer.
+    pub fn with_custom_instance_attributes<S: Into<String>>(
+        attributes: impl IntoIterator<Item = (S, VertexFormat)>,
+    ) -> Result<Self> {
+        let mut layout = Self::default();
+        layout.buffer_layouts.push(BufferLayout::instance());
+
+        for (name, ty) in attributes {
+            let name = name.into();
+            ensure!(
+                ty.is_float(),
+                "custom instance attribute `{}` must have a float format, but has format {:?}",
+                name,
+                ty
+            );
+            layout
+                .attributes
+                .push(VertexAttribute::new(name, ty, CUSTOM_INSTANCE_BUFFER));
+        }
+
+        let floats = layout.custom_instance_floats();
+        ensure!(
+            floats <= MAX_CUSTOM_FLOATS,
+            "custom instance attributes take up {} floats, but instances can carry at most {}",
+            floats,
+            MAX_CUSTOM_FLOATS
+        );
+
+        Ok(layout)
+    }
+
+    /// The number of floats of custom data each instance needs for this layout; zero if it has no
+    /// custom instance attributes.
+    pub fn custom_instance_floats(&self) -> usize {
+        self.attributes
+            .iter()
+            .filter(|attribute| attribute.buffer_index == CUSTOM_INSTANCE_BUFFER)
+            .map(|attribute| attribute.ty.size() as usize)
+            .sum()
+    }
+}
+
 impl LuaUserData for PipelineLayout {}
 
 #[derive(Debug, Clone, Copy)]
@@ -385,7 +460,7 @@ pub struct PipelineParams {
     // pub depth_test: Comparison,
     pub depth_write: bool,
     pub depth_write_offset: Option<(f32, f32)>,
-    // pub color_blend: Option<BlendState>,
+    pub color_blend: Option<BlendMode>,
     // pub alpha_blend: Option<BlendState>,
     // pub stencil_test: Option<StencilState>,
     pub color_write: (bool, bool, bool, bool),
@@ -397,6 +472,7 @@ impl Default for PipelineParams {
         Self {
             depth_write: true,
             depth_write_offset: None,
+            color_blend: Some(BlendMode::default()),
             color_write: (true, true, true, true),
         }
     }
@@ -474,8 +550,10 @@ impl Pipeline {
         gfx: &mut Graphics,
         layout: PipelineLayout,
         shader: Shader,
-        _params: Option<PipelineParams>,
+        params: Option<PipelineParams>,
     ) -> Result<Self> {
+        let params = params.unwrap_or_default();
+
         let buffer_layouts = layout
             .buffer_layouts
             .iter()
@@ -496,9 +574,11 @@ impl Pipeline {
             &vertex_attributes,
             shader.handle,
             mq::PipelineParams {
-                color_blend: Some(BlendMode::default().into()),
+                color_blend: params.color_blend.map(Into::into),
                 depth_test: mq::Comparison::LessOrEqual,
-                depth_write: true,
+                depth_write: params.depth_write,
+                depth_write_offset: params.depth_write_offset,
+                color_write: params.color_write,
                 ..mq::PipelineParams::default()
             },
         );
@@ -777,6 +857,17 @@ pub(super) fn open<'lua>(
         })?,
     )?;
 
+    pipeline.set(
+        "create_custom_instance_pipeline_layout_object",
+        lua.create_function(move |_lua, attributes: Vec<LuaTable>| {
+            let attributes = attributes
+                .into_iter()
+                .map(|pair| Ok((pair.get::<_, String>(1)?, pair.get::<_, VertexFormat>(2)?)))
+                .collect::<LuaResult<Vec<_>>>()?;
+            PipelineLayout::with_custom_instance_attributes(attributes).to_lua_err()
+        })?,
+    )?;
+
     let gfx = gfx_lock.clone();
     pipeline.set(
         "create_pipeline_object",
@@ -801,3 +892,46 @@ pub(super) fn open<'lua>(
 
     Ok(pipeline)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_instance_attributes_extend_the_default_layout() {
+        let layout = PipelineLayout::with_custom_instance_attributes(vec![
+            ("a_Age", VertexFormat::Float1),
+            ("a_Glow", VertexFormat::Float3),
+        ])
+        .unwrap();
+
+        let default = PipelineLayout::default();
+        assert_eq!(layout.buffer_layouts.len(), 3);
+        assert!(matches!(
+            layout.buffer_layouts[CUSTOM_INSTANCE_BUFFER].step,
+            VertexStep::PerInstance
+        ));
+        assert_eq!(
+            layout.attributes[..default.attributes.len()]
+                .iter()
+                .map(|attribute| &attribute.name)
+                .collect::<Vec<_>>(),
+            default
+                .attributes
+                .iter()
+                .map(|attribute| &attribute.name)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(layout.custom_instance_floats(), 4);
+        assert_eq!(default.custom_instance_floats(), 0);
+
+        let ints = vec![("a_Kind", VertexFormat::Int1)];
+        assert!(PipelineLayout::with_custom_instance_attributes(ints).is_err());
+        assert!(PipelineLayout::with_custom_instance_attributes(vec![
+            ("a_Big", VertexFormat::Float4),
+            ("a_Bigger", VertexFormat::Float4),
+            ("a_TooBig", VertexFormat::Float1),
+        ])
+        .is_err());
+    }
+}
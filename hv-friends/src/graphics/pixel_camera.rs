@@ -0,0 +1,203 @@
+//! Smooth scrolling for pixel art rendered at a fixed internal resolution.
+//!
+//! Scrolling pixel art by fractional amounts makes its pixels shimmer, as sprites and tiles land
+//! between texels; flooring the scroll to whole pixels fixes that, but steps visibly when the
+//! result is upscaled a lot. A [`PixelCamera`] does both: the world is drawn into a [`Canvas`] at
+//! the game's internal resolution, scrolled by a whole number of pixels, and the fractional part
+//! of the scroll is only applied when the canvas is blitted to the screen, as an offset into the
+//! canvas texture. Every world pixel stays aligned to a canvas texel, and the picture as a whole
+//! moves smoothly in steps of one *screen* pixel.
+//!
+//! Shifting the picture by a fraction of a texel uncovers a sliver of the canvas past its far edge,
+//! so the canvas is drawn one pixel larger than the internal resolution in each direction (see
+//! [`OVERSCAN`].)
+
+use crate::{
+    graphics::{Canvas, ClearOptions, Color, Drawable, Graphics, Instance},
+    math::*,
+};
+
+/// How many pixels wider and taller a [`PixelCamera`]'s canvas is than its internal resolution.
+/// Fractional offsets are always in `[0, 1)`, so one pixel of overscan on the far side is enough.
+pub const OVERSCAN: u32 = 1;
+
+/// A scroll position split into whole internal pixels and the remaining fraction of a pixel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PixelScroll {
+    /// The whole number of pixels the world is scrolled by while drawing into the canvas.
+    pub whole: Vector2<i32>,
+    /// The remaining fraction of a pixel, in `[0, 1)` along each axis, applied when blitting.
+    pub fraction: Vector2<f32>,
+}
+
+impl PixelScroll {
+    /// Split a scroll position into whole and fractional pixels, for a canvas which will be
+    /// upscaled by `scale`. The scroll is first snapped to the nearest screen pixel (a multiple of
+    /// `1 / scale` internal pixels), since moving by less than that can't be shown and only makes
+    /// the picture shimmer. A scroll which snaps up to the next whole pixel carries over into
+    /// `whole`, so `fraction` never reaches `1`.
+    pub fn split(scroll: Vector2<f32>, scale: f32) -> Self {
+        let snapped = (scroll * scale).map(f32::round) / scale;
+        let whole = snapped.map(f32::floor);
+        Self {
+            whole: whole.map(|w| w as i32),
+            fraction: snapped - whole,
+        }
+    }
+}
+
+/// Renders the world into a fixed-resolution [`Canvas`] and blits it to the screen with subpixel
+/// scrolling.
+#[derive(Debug)]
+pub struct PixelCamera {
+    canvas: Canvas,
+    resolution: Vector2<u32>,
+    scale: f32,
+    scroll: Vector2<f32>,
+    /// The color the canvas is cleared to before the world is drawn.
+    pub clear_color: Color,
+}
+
+impl PixelCamera {
+    /// Create a camera with an internal resolution of `width` by `height` pixels, which is
+    /// upscaled by `scale` when blitted to the screen.
+    pub fn new(ctx: &mut Graphics, width: u32, height: u32, scale: f32) -> Self {
+        assert!(scale > 0., "pixel camera scale must be positive");
+        Self {
+            canvas: Canvas::new(ctx, width + OVERSCAN, height + OVERSCAN),
+            resolution: Vector2::new(width, height),
+            scale,
+            scroll: Vector2::zeros(),
+            clear_color: Color::ZEROS,
+        }
+    }
+
+    /// The internal resolution, not counting overscan.
+    pub fn resolution(&self) -> Vector2<u32> {
+        self.resolution
+    }
+
+    /// The canvas the world is drawn into, including overscan.
+    pub fn canvas(&self) -> &Canvas {
+        &self.canvas
+    }
+
+    /// How much the canvas is scaled up when blitted.
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    pub fn set_scale(&mut self, scale: f32) {
+        assert!(scale > 0., "pixel camera scale must be positive");
+        self.scale = scale;
+    }
+
+    /// The position of the world, in internal pixels, which appears at the origin of the view.
+    pub fn scroll(&self) -> Vector2<f32> {
+        self.scroll
+    }
+
+    pub fn set_scroll(&mut self, scroll: Vector2<f32>) {
+        self.scroll = scroll;
+    }
+
+    /// The current scroll, split into whole and fractional pixels.
+    pub fn split_scroll(&self) -> PixelScroll {
+        PixelScroll::split(self.scroll, self.scale)
+    }
+
+    /// The region of the canvas texture, in normalized texture coordinates, which is shown when
+    /// blitting: the internal resolution, offset into the overscan by the fractional scroll.
+    pub fn source_rect(&self) -> Box2<f32> {
+        let canvas_size = (self.resolution + Vector2::repeat(OVERSCAN)).cast::<f32>();
+        let fraction = self.split_scroll().fraction;
+        let resolution = self.resolution.cast::<f32>();
+        Box2::new(
+            fraction.x / canvas_size.x,
+            fraction.y / canvas_size.y,
+            resolution.x / canvas_size.x,
+            resolution.y / canvas_size.y,
+        )
+    }
+
+    /// Draw the world into the canvas. For the duration of `f`, the projection covers the canvas
+    /// in internal pixels and the modelview scrolls the world by the whole part of the scroll;
+    /// both are restored afterwards. Must be called outside of any render pass.
+    pub fn draw_world<R>(&self, ctx: &mut Graphics, f: impl FnOnce(&mut Graphics) -> R) -> R {
+        let canvas_size = (self.resolution + Vector2::repeat(OVERSCAN)).cast::<f32>();
+        let whole = self.split_scroll().whole.cast::<f32>();
+        let projection = *ctx.projection();
+        let default_projection_size = ctx.state.default_projection_size;
+
+        ctx.set_projection(ctx.state.y_axis.orthographic(canvas_size.x, canvas_size.y));
+        ctx.modelview_mut()
+            .push(Matrix4::identity())
+            .translate2(-whole);
+        ctx.begin_render_pass(
+            Some(&self.canvas.render_pass),
+            Some(ClearOptions {
+                color: Some(self.clear_color),
+                ..ClearOptions::default()
+            }),
+        );
+
+        let result = f(ctx);
+
+        ctx.end_render_pass();
+        ctx.modelview_mut().pop();
+        ctx.set_projection(projection);
+        ctx.state.default_projection_size = default_projection_size;
+
+        result
+    }
+
+    /// Blit the canvas to the current render target with its top left (or bottom left, with a
+    /// [`YAxis::Up`](crate::graphics::YAxis::Up) projection) corner at `origin`, upscaled by
+    /// [`PixelCamera::scale`].
+    pub fn blit(&self, ctx: &mut Graphics, origin: Point2<f32>) {
+        let canvas_size = (self.resolution + Vector2::repeat(OVERSCAN)).cast::<f32>();
+        // Textures are drawn at their own size, so scale the quad down to the size of the region
+        // being shown before scaling it up to the screen.
+        let shown = self.resolution.cast::<f32>().component_div(&canvas_size) * self.scale;
+        self.canvas.color_buffer.draw(
+            ctx,
+            Instance::new()
+                .src(self.source_rect())
+                .translate2(origin.coords)
+                .scale2(shown),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scroll_splits_into_whole_and_snapped_fractional_pixels() {
+        let split = PixelScroll::split(Vector2::new(10.3, -2.6), 4.);
+        assert_eq!(split.whole, Vector2::new(10, -3));
+        assert_eq!(split.fraction, Vector2::new(0.25, 0.5));
+
+        // Snapping up to the next whole pixel carries, rather than leaving a fraction of 1.
+        let split = PixelScroll::split(Vector2::new(10.9, 0.), 4.);
+        assert_eq!(split.whole, Vector2::new(11, 0));
+        assert_eq!(split.fraction, Vector2::zeros());
+
+        // At scale 1 there are no subpixel steps to show, so this is just rounding.
+        let split = PixelScroll::split(Vector2::new(7.4, 7.6), 1.);
+        assert_eq!(split.whole, Vector2::new(7, 8));
+        assert_eq!(split.fraction, Vector2::zeros());
+    }
+
+    #[test]
+    fn largest_fractional_offset_stays_inside_the_overscan() {
+        let resolution = Vector2::new(256u32, 240);
+        let canvas_size = (resolution + Vector2::repeat(OVERSCAN)).cast::<f32>();
+        let split = PixelScroll::split(Vector2::new(3.75, 3.75), 4.);
+        assert_eq!(split.fraction, Vector2::new(0.75, 0.75));
+
+        let maxs = (split.fraction + resolution.cast::<f32>()).component_div(&canvas_size);
+        assert!(maxs.x <= 1. && maxs.y <= 1., "{}", maxs);
+    }
+}
@@ -0,0 +1,337 @@
+//! Full-screen post-processing passes.
+//!
+//! A [`PostProcess`] runs a chain of fragment shaders over the contents of a [`Canvas`], drawing a
+//! single triangle which covers the whole target and ping-ponging between two intermediate
+//! canvases. Built-in passes are provided for a separable gaussian blur and gamma correction, and
+//! any [`Pipeline`] using [`post_pipeline_layout`] and [`POST_VERTEX`] can be pushed as a pass.
+
+use hv_core::{mq, prelude::*};
+
+use crate::{
+    graphics::{
+        pipeline::{
+            BufferLayout, Pipeline, PipelineLayout, PipelineParams, Shader, ShaderLayout,
+            UniformDesc, UniformType, Uniforms, VertexAttribute, VertexFormat,
+        },
+        BufferType, Canvas, ClearOptions, Color, FilterMode, Graphics, OwnedBuffer, RenderPass,
+    },
+    math::*,
+};
+
+/// Vertex shader shared by all post-processing passes. Passes `a_Uv` through as `v_Uv`.
+pub const POST_VERTEX: &str = include_str!("post_es300.glslv");
+/// Fragment shader which copies its input unchanged.
+pub const POST_IDENTITY_FRAGMENT: &str = include_str!("post_identity_es300.glslf");
+/// Fragment shader for one direction of a separable gaussian blur.
+pub const POST_BLUR_FRAGMENT: &str = include_str!("post_blur_es300.glslf");
+/// Fragment shader for gamma correction.
+pub const POST_GAMMA_FRAGMENT: &str = include_str!("post_gamma_es300.glslf");
+
+/// The name of the uniform which [`PostProcess::apply`] automatically fills with the size of a
+/// single texel of a pass's input, if the pass's shader declares it.
+pub const TEXEL_SIZE_UNIFORM: &str = "u_TexelSize";
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct PostVertex {
+    pub pos: Vector2<f32>,
+    pub uv: Vector2<f32>,
+}
+
+/// A single triangle which covers all of clip space. The UVs are chosen so that they interpolate
+/// to exactly `(0, 0)` at the bottom left corner of the target and `(1, 1)` at the top right, which
+/// matches the bottom-left origin of render textures.
+pub fn fullscreen_triangle() -> [PostVertex; 3] {
+    [
+        PostVertex {
+            pos: Vector2::new(-1., -1.),
+            uv: Vector2::new(0., 0.),
+        },
+        PostVertex {
+            pos: Vector2::new(3., -1.),
+            uv: Vector2::new(2., 0.),
+        },
+        PostVertex {
+            pos: Vector2::new(-1., 3.),
+            uv: Vector2::new(0., 2.),
+        },
+    ]
+}
+
+/// The pipeline layout expected by post-processing pipelines: a single per-vertex buffer of
+/// [`PostVertex`]es.
+pub fn post_pipeline_layout() -> PipelineLayout {
+    PipelineLayout {
+        buffer_layouts: vec![BufferLayout::vertex()],
+        attributes: vec![
+            VertexAttribute::new("a_Pos", VertexFormat::Float2, 0),
+            VertexAttribute::new("a_Uv", VertexFormat::Float2, 0),
+        ],
+    }
+}
+
+/// Pipeline parameters suitable for post-processing: no blending and no depth writes, so that each
+/// pass completely replaces the contents of its target.
+pub fn post_pipeline_params() -> PipelineParams {
+    PipelineParams {
+        depth_write: false,
+        color_blend: None,
+        ..PipelineParams::default()
+    }
+}
+
+fn post_shader_layout(uniforms: Vec<UniformDesc>) -> ShaderLayout {
+    ShaderLayout {
+        uniforms,
+        images: vec!["t_Texture".to_string()],
+    }
+}
+
+fn builtin_pass(
+    gfx: &mut Graphics,
+    fragment: &str,
+    uniforms: Vec<UniformDesc>,
+) -> Result<PostProcessPass> {
+    let layout = post_shader_layout(uniforms);
+    let shader = Shader::new(gfx, POST_VERTEX, fragment, layout.clone())?;
+    let pipeline = Pipeline::new(
+        gfx,
+        post_pipeline_layout(),
+        shader,
+        Some(post_pipeline_params()),
+    )?;
+
+    Ok(PostProcessPass {
+        pipeline,
+        uniforms: Some(Uniforms::new(&layout)),
+    })
+}
+
+/// A single pass of a [`PostProcess`]: a pipeline and the uniforms to draw it with.
+#[derive(Debug)]
+pub struct PostProcessPass {
+    pub pipeline: Pipeline,
+    pub uniforms: Option<Uniforms>,
+}
+
+impl PostProcessPass {
+    /// A pass which copies its input unchanged.
+    pub fn identity(gfx: &mut Graphics) -> Result<Self> {
+        builtin_pass(gfx, POST_IDENTITY_FRAGMENT, Vec::new())
+    }
+
+    /// One direction of a separable gaussian blur. `direction` is in texels; `(1, 0)` blurs
+    /// horizontally and `(0, 1)` vertically.
+    pub fn gaussian_blur(gfx: &mut Graphics, direction: Vector2<f32>) -> Result<Self> {
+        let mut pass = builtin_pass(
+            gfx,
+            POST_BLUR_FRAGMENT,
+            vec![
+                UniformDesc::new(TEXEL_SIZE_UNIFORM, UniformType::Float2),
+                UniformDesc::new("u_Direction", UniformType::Float2),
+            ],
+        )?;
+        let uniforms = pass.uniforms.as_mut().unwrap();
+        uniforms.set_uniform_by_name("u_Direction", &[direction.x, direction.y]);
+        Ok(pass)
+    }
+
+    /// Gamma correction, raising each color channel to the power of `1 / gamma`.
+    pub fn gamma(gfx: &mut Graphics, gamma: f32) -> Result<Self> {
+        let mut pass = builtin_pass(
+            gfx,
+            POST_GAMMA_FRAGMENT,
+            vec![
+                UniformDesc::new(TEXEL_SIZE_UNIFORM, UniformType::Float2),
+                UniformDesc::new("u_Gamma", UniformType::Float1),
+            ],
+        )?;
+        let uniforms = pass.uniforms.as_mut().unwrap();
+        uniforms.set_uniform_by_name("u_Gamma", &gamma);
+        Ok(pass)
+    }
+}
+
+/// A chain of full-screen shader passes, run over the contents of a [`Canvas`].
+#[derive(Debug)]
+pub struct PostProcess {
+    passes: Vec<PostProcessPass>,
+    identity: PostProcessPass,
+    canvases: [Canvas; 2],
+    bindings: mq::Bindings,
+    _vertex_buffer: OwnedBuffer,
+    _index_buffer: OwnedBuffer,
+}
+
+impl PostProcess {
+    /// Create an empty post-processing chain whose intermediate canvases are `width` by `height`
+    /// pixels. This should be the size of the canvases passed to [`PostProcess::apply`]; odd sizes
+    /// are fine.
+    pub fn new(gfx: &mut Graphics, width: u32, height: u32) -> Result<Self> {
+        let canvases = [
+            Canvas::new(gfx, width, height),
+            Canvas::new(gfx, width, height),
+        ];
+        for canvas in canvases.iter() {
+            canvas.color_buffer.set_filter(gfx, FilterMode::Linear);
+        }
+
+        let vertex_buffer =
+            OwnedBuffer::immutable(gfx, BufferType::VertexBuffer, &fullscreen_triangle());
+        let index_buffer = OwnedBuffer::immutable(gfx, BufferType::IndexBuffer, &[0u16, 1, 2]);
+        let bindings = mq::Bindings {
+            vertex_buffers: vec![vertex_buffer.handle],
+            index_buffer: index_buffer.handle,
+            images: vec![canvases[0].color_buffer.handle],
+        };
+
+        Ok(Self {
+            passes: Vec::new(),
+            identity: PostProcessPass::identity(gfx)?,
+            canvases,
+            bindings,
+            _vertex_buffer: vertex_buffer,
+            _index_buffer: index_buffer,
+        })
+    }
+
+    /// Add a pass to the end of the chain. The pipeline should be created with
+    /// [`post_pipeline_layout`], and will usually want [`post_pipeline_params`]. Its input is
+    /// bound as the first image. If `uniforms` declares [`TEXEL_SIZE_UNIFORM`] as a `Float2`, it
+    /// is filled in automatically.
+    pub fn push_pass(&mut self, pipeline: Pipeline, uniforms: Option<Uniforms>) -> &mut Self {
+        self.passes.push(PostProcessPass { pipeline, uniforms });
+        self
+    }
+
+    /// Add a gaussian blur to the end of the chain, as a horizontal pass followed by a vertical
+    /// pass.
+    pub fn push_gaussian_blur(&mut self, gfx: &mut Graphics) -> Result<&mut Self> {
+        self.passes
+            .push(PostProcessPass::gaussian_blur(gfx, Vector2::new(1., 0.))?);
+        self.passes
+            .push(PostProcessPass::gaussian_blur(gfx, Vector2::new(0., 1.))?);
+        Ok(self)
+    }
+
+    /// Add gamma correction to the end of the chain.
+    pub fn push_gamma(&mut self, gfx: &mut Graphics, gamma: f32) -> Result<&mut Self> {
+        self.passes.push(PostProcessPass::gamma(gfx, gamma)?);
+        Ok(self)
+    }
+
+    /// The passes in the chain, in order.
+    pub fn passes(&self) -> &[PostProcessPass] {
+        &self.passes
+    }
+
+    /// Mutable access to the passes in the chain, for example to update their uniforms.
+    pub fn passes_mut(&mut self) -> &mut Vec<PostProcessPass> {
+        &mut self.passes
+    }
+
+    /// Run every pass over the contents of `input`, writing the result to `output` (or to the
+    /// screen, if `None`.) An empty chain copies `input` unchanged. This must be called outside of
+    /// any render pass; afterwards, the default pipeline is applied.
+    pub fn apply(&mut self, gfx: &mut Graphics, input: &Canvas, output: Option<&RenderPass>) {
+        let Self {
+            passes,
+            identity,
+            canvases,
+            bindings,
+            ..
+        } = self;
+
+        let passes: &mut [PostProcessPass] = if passes.is_empty() {
+            std::slice::from_mut(identity)
+        } else {
+            passes
+        };
+
+        let last = passes.len() - 1;
+        let mut source = &input.color_buffer;
+        for (i, pass) in passes.iter_mut().enumerate() {
+            let target = if i == last {
+                output
+            } else {
+                Some(&canvases[i % 2].render_pass)
+            };
+
+            gfx.begin_render_pass(
+                target,
+                Some(ClearOptions {
+                    color: Some(Color::ZEROS),
+                    ..ClearOptions::default()
+                }),
+            );
+            gfx.apply_pipeline(&pass.pipeline);
+
+            bindings.images[0] = source.handle;
+            gfx.mq.apply_bindings(bindings);
+
+            if let Some(uniforms) = pass.uniforms.as_mut() {
+                if let Some(index) = uniforms.get_uniform_index_by_name(TEXEL_SIZE_UNIFORM) {
+                    let texel_size = [1. / source.width() as f32, 1. / source.height() as f32];
+                    uniforms.set_uniform_by_index(index, &texel_size);
+                }
+                gfx.mq.apply_uniforms_from_bytes(
+                    uniforms.as_bytes().as_ptr(),
+                    uniforms.as_bytes().len(),
+                );
+            }
+
+            gfx.mq.draw(0, 3, 1);
+            gfx.end_render_pass();
+
+            source = &canvases[i % 2].color_buffer;
+        }
+
+        gfx.apply_default_pipeline();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Emulate rasterizing the full-screen triangle onto a `width` by `height` target and sampling
+    /// a same-sized texture with nearest filtering, returning the texel sampled at each pixel.
+    fn rasterize_identity(width: u32, height: u32) -> Vec<(u32, u32)> {
+        let [a, b, c] = fullscreen_triangle();
+        let area = (b.pos - a.pos).perp(&(c.pos - a.pos));
+
+        let mut sampled = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                // Pixel centers in normalized device coordinates.
+                let p = Vector2::new(
+                    (x as f32 + 0.5) / width as f32 * 2. - 1.,
+                    (y as f32 + 0.5) / height as f32 * 2. - 1.,
+                );
+
+                let wa = (b.pos - p).perp(&(c.pos - p)) / area;
+                let wb = (c.pos - p).perp(&(a.pos - p)) / area;
+                let wc = 1. - wa - wb;
+                assert!(wa >= 0. && wb >= 0. && wc >= 0., "pixel outside triangle");
+
+                let uv = a.uv * wa + b.uv * wb + c.uv * wc;
+                sampled.push((
+                    (uv.x * width as f32).floor() as u32,
+                    (uv.y * height as f32).floor() as u32,
+                ));
+            }
+        }
+
+        sampled
+    }
+
+    #[test]
+    fn identity_pass_is_pixel_exact_on_odd_sizes() {
+        for &(width, height) in &[(7, 5), (1, 1), (321, 179)] {
+            let expected = (0..height)
+                .flat_map(|y| (0..width).map(move |x| (x, y)))
+                .collect::<Vec<_>>();
+            assert_eq!(rasterize_identity(width, height), expected);
+        }
+    }
+}
@@ -13,6 +13,8 @@ use thunderdome::{Arena, Index};
 
 use crate::{
     graphics::{
+        basic::MAX_PAGES,
+        pipeline::{PipelineLayout, CUSTOM_INSTANCE_BUFFER},
         Drawable, DrawableMut, Graphics, GraphicsLock, Instance, InstanceProperties, Texture,
     },
     math::*,
@@ -86,6 +88,9 @@ impl<'lua> FromLua<'lua> for SpriteId {
     }
 }
 
+/// The initial capacity of a [`SpriteBatch`] created without an explicit capacity.
+pub const DEFAULT_SPRITEBATCH_CAPACITY: usize = 64;
+
 /// An iterator offering immutable access to all of the sprite instances in a batch.
 pub struct SpriteBatchIter<'a> {
     iter: thunderdome::Iter<'a, Instance>,
@@ -117,16 +122,43 @@ impl<'a> Iterator for SpriteBatchIterMut<'a> {
 ///
 /// If you have a lot of [`Sprite`]s using the same texture, this is a much more efficient way to
 /// render them. Way more efficient.
+///
+/// A batch can also have up to [`MAX_PAGES`] textures ("pages"), for example the pages of a large
+/// atlas; each instance then samples from the page selected by its [`Instance::page`]. A batch with
+/// a single page draws with whatever pipeline is current, exactly like any other drawable, but a
+/// batch with several pages draws with the built-in paged pipeline (see
+/// [`Graphics::apply_paged_pipeline`]) and leaves the default pipeline applied afterwards.
+///
+/// To draw with a pipeline which has custom instance attributes (see
+/// [`PipelineLayout::with_custom_instance_attributes`]), call [`SpriteBatch::set_custom_layout`]
+/// with its layout, and each instance's [`Instance::custom`] data is uploaded alongside its usual
+/// properties. Batches without a custom layout upload nothing extra.
+///
+/// Instances are normally drawn in the order the batch stores them. Drawing with
+/// [`SpriteBatch::draw_sorted`] instead draws them in order of [`Instance::depth`], so that a single
+/// batch can hold sprites on several layers, or sprites sorted for 2.5D overlap.
+///
+/// The batch's GPU buffers hold a fixed number of instances, and are recreated with double the
+/// capacity (or more) whenever a flush finds more sprites than fit. For batches whose size is known
+/// up front, create them with [`SpriteBatch::with_capacity`] or call [`SpriteBatch::reserve`] to
+/// avoid recreating the buffers as they fill up.
 #[derive(Debug)]
 pub struct SpriteBatch<T: AsCached<Texture>> {
     sprites: Arena<Instance>,
     // Used to store the result of converting InstanceParams to InstanceProperties
     instances: Vec<InstanceProperties>,
     // Capacity is used to store the length of the buffers inside of mq::Bindings
-    capacity: usize,
+    capacity: BufferCapacity,
     bindings: mq::Bindings,
     dirty: bool,
-    texture: T,
+    // Whether the instances were uploaded in depth order by the last flush
+    sorted: bool,
+    // Never empty; the first page is the batch's "texture".
+    pages: Vec<T>,
+    // The number of custom floats uploaded per instance, and the packed custom data itself. When
+    // nonzero, the custom buffer is the batch's third vertex buffer.
+    custom_floats: usize,
+    custom: Vec<f32>,
 }
 
 impl<T: AsCached<Texture>> ops::Index<SpriteId> for SpriteBatch<T> {
@@ -149,34 +181,83 @@ impl<T: AsCached<Texture>> ops::IndexMut<SpriteId> for SpriteBatch<T> {
 impl<T: AsCached<Texture>> SpriteBatch<T> {
     /// Create a new spritebatch for the given texture.
     pub fn new(ctx: &mut Graphics, texture: T) -> Self {
-        const DEFAULT_SPRITEBATCH_CAPACITY: usize = 64;
         Self::with_capacity(ctx, texture, DEFAULT_SPRITEBATCH_CAPACITY)
     }
 
     /// Create a new spritebatch for the given texture and with the given initial capacity.
-    pub fn with_capacity(ctx: &mut Graphics, mut texture: T, capacity: usize) -> Self {
+    pub fn with_capacity(ctx: &mut Graphics, texture: T, capacity: usize) -> Self {
+        Self::with_pages(ctx, vec![texture], capacity)
+    }
+
+    /// Create a new spritebatch which draws from several textures, with the given initial
+    /// capacity. Instances choose a texture with [`Instance::page`].
+    ///
+    /// Panics if `pages` is empty or has more than [`MAX_PAGES`] textures.
+    pub fn with_pages(ctx: &mut Graphics, mut pages: Vec<T>, capacity: usize) -> Self {
+        assert!(
+            !pages.is_empty() && pages.len() <= MAX_PAGES,
+            "a spritebatch must have between 1 and {} pages, got {}",
+            MAX_PAGES,
+            pages.len()
+        );
+
         let instances = mq::Buffer::stream(
             &mut ctx.mq,
             mq::BufferType::VertexBuffer,
             capacity * mem::size_of::<InstanceProperties>(),
         );
 
+        let handles = pages
+            .iter_mut()
+            .map(|page| page.as_cached().handle)
+            .collect::<Vec<_>>();
         let bindings = mq::Bindings {
             vertex_buffers: vec![ctx.state.quad_bindings.vertex_buffers[0], instances],
             index_buffer: ctx.state.quad_bindings.index_buffer,
-            images: vec![texture.as_cached().handle],
+            images: page_images(&handles),
         };
 
         Self {
             sprites: Arena::new(),
             instances: Vec::new(),
-            capacity,
+            capacity: BufferCapacity::new(capacity),
             bindings,
             dirty: true,
-            texture,
+            sorted: false,
+            pages,
+            custom_floats: 0,
+            custom: Vec::new(),
         }
     }
 
+    /// Match the batch's custom instance data to the custom instance attributes of `layout`, which
+    /// should be the layout of the pipeline the batch is drawn with. Layouts without custom
+    /// instance attributes (like the default one) remove the batch's custom buffer.
+    pub fn set_custom_layout(&mut self, ctx: &mut Graphics, layout: &PipelineLayout) {
+        let floats = layout.custom_instance_floats();
+        if floats == self.custom_floats {
+            return;
+        }
+
+        if self.custom_floats > 0 {
+            if let Some(old_custom) = self.bindings.vertex_buffers.pop() {
+                old_custom.delete();
+            }
+        }
+
+        if floats > 0 {
+            let custom = mq::Buffer::stream(
+                &mut ctx.mq,
+                mq::BufferType::VertexBuffer,
+                self.capacity.get() * floats * mem::size_of::<f32>(),
+            );
+            self.bindings.vertex_buffers.push(custom);
+        }
+
+        self.custom_floats = floats;
+        self.dirty = true;
+    }
+
     /// Insert a single sprite into the batch as an instance parameter, and get a unique identifier
     /// referring to it.
     #[inline]
@@ -241,64 +322,189 @@ impl<T: AsCached<Texture>> SpriteBatch<T> {
             .map(|(index, instance)| (SpriteId(index), instance))
     }
 
-    /// Clear the spritebatch, removing all sprites in  it.
+    /// Clear the spritebatch, removing all sprites in  it. The batch keeps its capacity, so
+    /// refilling it every frame (as with bullets) doesn't recreate its buffers.
     #[inline]
     pub fn clear(&mut self) {
         self.dirty = true;
         self.sprites.clear();
     }
 
-    /// Get a reference to the texture in this spritebatch.
+    /// Get a reference to the texture in this spritebatch. For a batch with several pages, this is
+    /// the first page.
     #[inline]
     pub fn texture(&self) -> &T {
-        &self.texture
+        &self.pages[0]
     }
 
-    /// Set the texture of this spritebatch directly. There should not often be a need for this.
+    /// Set the texture of this spritebatch directly. There should not often be a need for this. For
+    /// a batch with several pages, this replaces the first page.
     #[inline]
     pub fn set_texture(&mut self, texture: T) {
-        self.texture = texture;
+        self.pages[0] = texture;
+    }
+
+    /// Get the texture pages of this spritebatch.
+    #[inline]
+    pub fn pages(&self) -> &[T] {
+        &self.pages
+    }
+
+    /// Replace the texture pages of this spritebatch.
+    ///
+    /// Panics if `pages` is empty or has more than [`MAX_PAGES`] textures.
+    #[inline]
+    pub fn set_pages(&mut self, pages: Vec<T>) {
+        assert!(
+            !pages.is_empty() && pages.len() <= MAX_PAGES,
+            "a spritebatch must have between 1 and {} pages, got {}",
+            MAX_PAGES,
+            pages.len()
+        );
+        self.dirty = true;
+        self.pages = pages;
+    }
+
+    /// The number of sprites the batch's GPU buffers can hold without being recreated.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity.get()
+    }
+
+    /// The number of times the batch's GPU buffers have been recreated to make room for more
+    /// sprites.
+    #[inline]
+    pub fn buffer_recreations(&self) -> usize {
+        self.capacity.recreations
+    }
+
+    /// Make room for at least `additional` more sprites than the batch currently holds, recreating
+    /// its GPU buffers now if they're too small rather than on a later flush. Does nothing if they
+    /// already have room.
+    pub fn reserve(&mut self, ctx: &mut Graphics, additional: usize) {
+        self.grow_buffers(ctx, self.sprites.len() + additional);
+    }
+
+    fn grow_buffers(&mut self, ctx: &mut Graphics, needed: usize) {
+        let new_capacity = match self.capacity.grow_to_fit(needed) {
+            Some(new_capacity) => new_capacity,
+            None => return,
+        };
+
+        let new_buffer = mq::Buffer::stream(
+            &mut ctx.mq,
+            mq::BufferType::VertexBuffer,
+            new_capacity * mem::size_of::<InstanceProperties>(),
+        );
+
+        let old_buffer = mem::replace(&mut self.bindings.vertex_buffers[1], new_buffer);
+        old_buffer.delete();
+
+        if self.custom_floats > 0 {
+            let new_custom = mq::Buffer::stream(
+                &mut ctx.mq,
+                mq::BufferType::VertexBuffer,
+                new_capacity * self.custom_floats * mem::size_of::<f32>(),
+            );
+            let old_custom = mem::replace(
+                &mut self.bindings.vertex_buffers[CUSTOM_INSTANCE_BUFFER],
+                new_custom,
+            );
+            old_custom.delete();
+        }
     }
 
     /// Update the underlying GPU instance buffer with the current sprite data. This is called
     /// automatically by [`DrawableMut::draw_mut`], and is why [`SpriteBatch`] does not implement
     /// [`Drawable`].
     pub fn flush(&mut self, ctx: &mut Graphics) {
-        let texture = self.texture.as_cached();
+        self.flush_in_order(ctx, false);
+    }
+
+    /// Like [`SpriteBatch::flush`], but uploads the instances sorted by [`Instance::depth`]. This
+    /// is called automatically by [`SpriteBatch::draw_sorted`].
+    pub fn flush_sorted(&mut self, ctx: &mut Graphics) {
+        self.flush_in_order(ctx, true);
+    }
+
+    fn flush_in_order(&mut self, ctx: &mut Graphics, sorted: bool) {
+        let pages = self
+            .pages
+            .iter_mut()
+            .map(|page| {
+                let texture = page.as_cached();
+                (texture.handle, texture.width(), texture.height())
+            })
+            .collect::<Vec<_>>();
+        let handles = pages
+            .iter()
+            .map(|&(handle, _, _)| handle)
+            .collect::<Vec<_>>();
+        let images = page_images(&handles);
 
-        if !self.dirty && texture.handle == self.bindings.images[0] {
+        if !self.dirty && images == self.bindings.images && sorted == self.sorted {
             return;
         }
 
-        self.instances.clear();
-        self.instances.extend(self.sprites.iter().map(|(_, param)| {
-            param
-                .scale2(param.src.extents())
-                .scale2(Vector2::new(
-                    texture.width() as f32,
-                    texture.height() as f32,
-                ))
-                .to_instance_properties()
-        }));
-
-        if self.instances.len() > self.capacity {
-            let new_capacity = self.instances.len().checked_next_power_of_two().unwrap();
-            let new_buffer = mq::Buffer::stream(
-                &mut ctx.mq,
-                mq::BufferType::VertexBuffer,
-                new_capacity * mem::size_of::<InstanceProperties>(),
-            );
+        let sizes = pages.iter().map(|&(_, w, h)| (w, h)).collect::<Vec<_>>();
+        let mut params = self
+            .sprites
+            .iter()
+            .map(|(_, param)| param)
+            .collect::<Vec<_>>();
+        if sorted {
+            sort_by_depth(&mut params);
+        }
 
-            let old_buffer = mem::replace(&mut self.bindings.vertex_buffers[1], new_buffer);
-            old_buffer.delete();
+        self.instances.clear();
+        self.instances.extend(
+            params
+                .iter()
+                .map(|param| batch_instance_properties(param, &sizes)),
+        );
+        pack_custom_data(params, self.custom_floats, &mut self.custom);
 
-            self.capacity = new_capacity;
-        }
+        self.grow_buffers(ctx, self.instances.len());
 
         self.bindings.vertex_buffers[1].update(&mut ctx.mq, &self.instances);
-        self.bindings.images[0] = texture.handle;
+        if self.custom_floats > 0 {
+            self.bindings.vertex_buffers[CUSTOM_INSTANCE_BUFFER].update(&mut ctx.mq, &self.custom);
+        }
+        self.bindings.images = images;
 
         self.dirty = false;
+        self.sorted = sorted;
+    }
+
+    /// Draw the batch with its instances sorted by [`Instance::depth`], lowest first. Instances
+    /// with equal depths, including any which never had a depth set, are drawn in the same order
+    /// as they would be by [`DrawableMut::draw_mut`].
+    ///
+    /// Sorting happens when the instances are uploaded, so a batch which hasn't changed since it
+    /// was last drawn sorted isn't sorted again.
+    pub fn draw_sorted(&mut self, ctx: &mut Graphics, instance: Instance) {
+        self.flush_sorted(ctx);
+        self.draw_flushed(ctx, instance);
+    }
+
+    fn draw_flushed(&mut self, ctx: &mut Graphics, instance: Instance) {
+        ctx.modelview_mut().push(None);
+        ctx.modelview_mut()
+            .apply_transform(instance.tx.to_homogeneous());
+        if self.pages.len() > 1 {
+            ctx.apply_paged_pipeline();
+            // The paged pipeline has its own uniforms, so the modelview has to be reapplied.
+            ctx.state.modelview_dirty = true;
+        }
+        ctx.mq.apply_bindings(&self.bindings);
+        ctx.apply_modelview();
+        // 6 here because a quad is 6 vertices
+        ctx.mq.draw(0, 6, self.instances.len() as i32);
+        if self.pages.len() > 1 {
+            ctx.apply_default_pipeline();
+        }
+        ctx.modelview_mut().pop();
+        ctx.apply_modelview();
     }
 
     /// Get an iterator immutably borrowing the instances in this batch.
@@ -322,16 +528,93 @@ impl<T: AsCached<Texture>> SpriteBatch<T> {
 impl<T: AsCached<Texture>> DrawableMut for SpriteBatch<T> {
     fn draw_mut(&mut self, ctx: &mut Graphics, instance: Instance) {
         self.flush(ctx);
+        self.draw_flushed(ctx, instance);
+    }
+}
 
-        ctx.modelview_mut().push(None);
-        ctx.modelview_mut()
-            .apply_transform(instance.tx.to_homogeneous());
-        ctx.mq.apply_bindings(&self.bindings);
-        ctx.apply_modelview();
-        // 6 here because a quad is 6 vertices
-        ctx.mq.draw(0, 6, self.instances.len() as i32);
-        ctx.modelview_mut().pop();
-        ctx.apply_modelview();
+/// The capacity of a batch's GPU buffers, in instances, and how many times they've been recreated
+/// to grow.
+#[derive(Debug, Clone, Copy)]
+struct BufferCapacity {
+    capacity: usize,
+    recreations: usize,
+}
+
+impl BufferCapacity {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            recreations: 0,
+        }
+    }
+
+    fn get(&self) -> usize {
+        self.capacity
+    }
+
+    /// If `needed` instances don't fit, grow to the next power of two which fits them and return
+    /// the new capacity, counting the recreation of the buffers. Returns `None` if they already
+    /// fit.
+    fn grow_to_fit(&mut self, needed: usize) -> Option<usize> {
+        if needed <= self.capacity {
+            return None;
+        }
+
+        self.capacity = needed.checked_next_power_of_two().unwrap();
+        self.recreations += 1;
+        Some(self.capacity)
+    }
+}
+
+/// The images to bind for a batch with the given page textures. A single page is bound on its own
+/// so that the batch works with the default pipeline and any custom single-texture pipeline;
+/// several pages are padded out to [`MAX_PAGES`] with the first page, to fill the paged shader's
+/// unused samplers.
+fn page_images<H: Copy>(pages: &[H]) -> Vec<H> {
+    if pages.len() == 1 {
+        return pages.to_vec();
+    }
+
+    let mut images = pages.to_vec();
+    images.resize(MAX_PAGES, pages[0]);
+    images
+}
+
+/// Convert an instance in a batch to its instance properties, scaling it to the pixel size of its
+/// page. Instances with out-of-range pages are drawn from the first page.
+fn batch_instance_properties(param: &Instance, page_sizes: &[(u32, u32)]) -> InstanceProperties {
+    let page = if (param.page as usize) < page_sizes.len() {
+        param.page
+    } else {
+        0
+    };
+    let (width, height) = page_sizes[page as usize];
+
+    param
+        .page(page)
+        .scale2(param.src.extents())
+        .scale2(Vector2::new(width as f32, height as f32))
+        .to_instance_properties()
+}
+
+/// Stably sort instances by depth, lowest first, so that instances with equal depths keep their
+/// relative order.
+fn sort_by_depth(instances: &mut [&Instance]) {
+    instances.sort_by(|a, b| a.depth.total_cmp(&b.depth));
+}
+
+/// Pack the first `floats` floats of each instance's custom data into `out`, one instance after
+/// another, to be uploaded as the custom instance buffer.
+fn pack_custom_data<'a>(
+    instances: impl IntoIterator<Item = &'a Instance>,
+    floats: usize,
+    out: &mut Vec<f32>,
+) {
+    out.clear();
+    if floats > 0 {
+        for instance in instances {
+            out.extend_from_slice(&instance.custom[..floats]);
+        }
     }
 }
 
@@ -369,6 +652,21 @@ where
         });
 
         methods.add_method("texture", |_, this, ()| Ok(this.texture().clone()));
+
+        methods.add_method_mut("set_pages", |_, this, pages: Vec<T>| {
+            if pages.is_empty() || pages.len() > MAX_PAGES {
+                return Err(anyhow!(
+                    "a spritebatch must have between 1 and {} pages, got {}",
+                    MAX_PAGES,
+                    pages.len()
+                ))
+                .to_lua_err();
+            }
+            this.set_pages(pages);
+            Ok(())
+        });
+
+        methods.add_method("pages", |_, this, ()| Ok(this.pages().to_vec()));
     }
 }
 
@@ -506,12 +804,77 @@ pub struct SpriteSheetSource {
     pub size: Vector2<u32>,
 }
 
+/// A named region of a sprite, such as a UI panel or a hitbox, defined in Aseprite as a "slice".
+/// Coordinates are in pixels within a frame of the sprite, with a bottom-left origin like the rest
+/// of the sheet.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Slice {
+    /// The region of the sprite covered by the slice.
+    pub bounds: Box2<u32>,
+    /// For nine-slices, the center region, relative to the bottom-left corner of `bounds`. When
+    /// drawn as a nine-patch, the parts of `bounds` around the center are its unstretched borders.
+    pub center: Option<Box2<u32>>,
+}
+
+impl LuaUserData for Slice {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("bounds", |_, this, ()| Ok(this.bounds));
+        methods.add_method("center", |_, this, ()| Ok(this.center));
+    }
+}
+
+#[derive(Deserialize)]
+struct AseRect {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+impl AseRect {
+    /// Convert to a bottom-left origin, within a region `height` pixels tall.
+    fn flipped_within(&self, height: u32) -> Box2<u32> {
+        Box2::new(
+            self.x,
+            height.saturating_sub(self.y + self.h),
+            self.w,
+            self.h,
+        )
+    }
+}
+
+#[derive(Deserialize)]
+struct AseSliceKey {
+    bounds: AseRect,
+    center: Option<AseRect>,
+}
+
+#[derive(Deserialize)]
+struct AseSlice {
+    name: String,
+    keys: Vec<AseSliceKey>,
+}
+
+#[derive(Deserialize)]
+struct AseSliceMeta {
+    #[serde(default)]
+    slices: Vec<AseSlice>,
+}
+
+/// The parts of an Aseprite export which [`SpritesheetData`] doesn't parse.
+#[derive(Deserialize)]
+struct AseSliceData {
+    meta: AseSliceMeta,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SpriteSheet {
     pub source: Option<SpriteSheetSource>,
     pub tag_ids: HashMap<String, TagId>,
     pub tags: Vec<Tag>,
     pub frames: Vec<Frame>,
+    #[serde(default)]
+    pub slices: HashMap<String, Slice>,
 }
 
 impl ops::Index<TagId> for SpriteSheet {
@@ -562,6 +925,7 @@ impl SpriteSheet {
                 offset: Vector2::zeros(),
                 duration: 1,
             }],
+            slices: HashMap::new(),
         }
     }
 
@@ -608,8 +972,28 @@ impl SpriteSheet {
 
     pub fn from_json(s: &str) -> Result<Self> {
         let spritesheet_data = serde_json::from_str::<SpritesheetData>(s)?;
+        let slice_data = serde_json::from_str::<AseSliceData>(s)?;
         let dims = spritesheet_data.meta.size;
         let size = Vector2::new(dims.w, dims.h);
+        // Slices are positioned within a single frame of the sprite, so they're flipped to a
+        // bottom-left origin against the height of the sprite rather than of the sheet.
+        let sprite_height = spritesheet_data
+            .frames
+            .first()
+            .map_or(0, |frame| frame.source_size.h);
+
+        // Slices can be keyed to change over an animation; only the first key is used.
+        let slices = slice_data
+            .meta
+            .slices
+            .into_iter()
+            .filter_map(|slice| {
+                let key = slice.keys.into_iter().next()?;
+                let center = key.center.map(|center| center.flipped_within(key.bounds.h));
+                let bounds = key.bounds.flipped_within(sprite_height);
+                Some((slice.name, Slice { bounds, center }))
+            })
+            .collect();
 
         let mut frames = Vec::new();
         for ase_frame in spritesheet_data.frames.into_iter() {
@@ -680,6 +1064,7 @@ impl SpriteSheet {
             tag_ids,
             tags,
             frames,
+            slices,
         })
     }
 
@@ -742,6 +1127,11 @@ impl SpriteSheet {
         self.tag_ids.get(s.as_ref()).copied()
     }
 
+    /// Look up a slice by name.
+    pub fn get_slice<K: AsRef<str>>(&self, s: K) -> Option<&Slice> {
+        self.slices.get(s.as_ref())
+    }
+
     pub fn at_tag(&self, tag_id: TagId, should_loop: bool) -> AnimationState {
         let tag = &self[tag_id];
         let frame_id = tag.first_frame();
@@ -784,6 +1174,10 @@ impl LuaUserData for CachedSpriteSheet {
         methods.add_method_mut("get_tag", |_, this, name: LuaString| {
             Ok(this.get_cached().get_tag(name.to_str()?))
         });
+
+        methods.add_method_mut("get_slice", |_, this, name: LuaString| {
+            Ok(this.get_cached().get_slice(name.to_str()?).copied())
+        });
     }
 }
 
@@ -1028,3 +1422,193 @@ pub(super) fn open<'lua>(
 
     Ok(lua.load(chunk).eval()?)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::basic;
+
+    #[test]
+    fn instance_pages_map_to_samplers() {
+        let sizes = [(16, 16), (32, 8), (8, 32)];
+        let instances = [
+            Instance::new(),
+            Instance::new().page(1),
+            Instance::new().page(2),
+            Instance::new().page(7),
+        ];
+
+        let pages = instances
+            .iter()
+            .map(|instance| batch_instance_properties(instance, &sizes).page)
+            .collect::<Vec<_>>();
+        // Out-of-range pages fall back to the first page.
+        assert_eq!(pages, vec![0., 1., 2., 0.]);
+
+        // Each instance is scaled to the size of its own page.
+        let props = batch_instance_properties(&instances[1], &sizes);
+        assert_eq!((props.tx[(0, 0)], props.tx[(1, 1)]), (32., 8.));
+
+        // Page `n` is bound as the `n`th image, which the paged shader reads through the `n`th
+        // sampler; unused samplers are filled with the first page.
+        let images = page_images(&["a", "b", "c"]);
+        assert_eq!(images, vec!["a", "b", "c", "a"]);
+        let meta = basic::paged_meta();
+        for (page, &expected) in pages.iter().zip(&["a", "b", "c", "a"]) {
+            let sampler = &meta.images[*page as usize];
+            assert!(basic::BASIC_PAGED_FRAGMENT.contains(&format!("texture({}, uv)", sampler)));
+            assert_eq!(images[*page as usize], expected);
+        }
+
+        // Single-page batches bind exactly one image, like they always have.
+        assert_eq!(page_images(&["a"]), vec!["a"]);
+    }
+
+    #[test]
+    fn batch_preserves_custom_data_per_instance() {
+        let mut sprites = Arena::new();
+        let bullets = [
+            sprites.insert(Instance::new().with_custom([0.5, 1.])),
+            sprites.insert(Instance::new().with_custom([1.5, 2.])),
+            sprites.insert(Instance::new()),
+            sprites.insert(Instance::new().with_custom([3.5, 3.])),
+        ];
+        sprites.remove(bullets[1]);
+        sprites[bullets[3]] = sprites[bullets[3]].translate2(Vector2::new(4., 4.));
+
+        let mut custom = Vec::new();
+        pack_custom_data(sprites.iter().map(|(_, param)| param), 2, &mut custom);
+        // Custom data lines up with the instances in the same order as their properties, whatever
+        // else has happened to the batch; instances without custom data upload zeroes.
+        assert_eq!(custom, vec![0.5, 1., 0., 0., 3.5, 3.]);
+        assert_eq!(
+            custom.len() / 2,
+            sprites
+                .iter()
+                .map(|(_, param)| batch_instance_properties(param, &[(16, 16)]))
+                .count()
+        );
+
+        // Without a custom layout, nothing extra is uploaded.
+        pack_custom_data(sprites.iter().map(|(_, param)| param), 0, &mut custom);
+        assert!(custom.is_empty());
+
+        // Custom data survives interpolation, componentwise.
+        let a = Instance::new().with_custom([0., 2.]);
+        let b = Instance::new().with_custom([1., 4.]);
+        assert_eq!(a.lerp(&b, 0.5).custom[..2], [0.5, 3.]);
+    }
+
+    #[test]
+    fn sorted_instances_are_uploaded_in_depth_order() {
+        let mut sprites = Arena::new();
+        let x = |i: f32| Instance::new().translate2(Vector2::new(i, 0.));
+        sprites.insert(x(0.).depth(2.));
+        sprites.insert(x(1.).depth(0.));
+        sprites.insert(x(2.).depth(1.));
+
+        let uploaded_x = |params: &[&Instance]| {
+            params
+                .iter()
+                .map(|param| batch_instance_properties(param, &[(16, 16)]).tx[(0, 3)])
+                .collect::<Vec<_>>()
+        };
+
+        let mut params = sprites.iter().map(|(_, param)| param).collect::<Vec<_>>();
+        assert_eq!(uploaded_x(&params), [0., 1., 2.]);
+        sort_by_depth(&mut params);
+        let depths = params.iter().map(|param| param.depth).collect::<Vec<_>>();
+        assert_eq!(depths, [0., 1., 2.]);
+        assert_eq!(uploaded_x(&params), [1., 2., 0.]);
+
+        // Instances without a depth all sit at 0, and keep the order they'd be drawn in unsorted.
+        let mut sprites = Arena::new();
+        for i in 0..4 {
+            sprites.insert(x(i as f32));
+        }
+        sprites.insert(x(4.).depth(-1.));
+        let mut params = sprites.iter().map(|(_, param)| param).collect::<Vec<_>>();
+        sort_by_depth(&mut params);
+        assert_eq!(uploaded_x(&params), [4., 0., 1., 2., 3.]);
+    }
+
+    #[test]
+    fn buffers_only_grow_past_their_capacity() {
+        let mut capacity = BufferCapacity::new(DEFAULT_SPRITEBATCH_CAPACITY);
+
+        // Filling up to the reserved capacity, frame after frame, never recreates the buffers.
+        for needed in (0..=DEFAULT_SPRITEBATCH_CAPACITY).chain(0..DEFAULT_SPRITEBATCH_CAPACITY) {
+            assert_eq!(capacity.grow_to_fit(needed), None);
+        }
+        assert_eq!(capacity.recreations, 0);
+
+        // Growing past it recreates them once, to the next power of two...
+        assert_eq!(capacity.grow_to_fit(65), Some(128));
+        assert_eq!(capacity.recreations, 1);
+        // ...after which they have room again.
+        assert_eq!(capacity.grow_to_fit(100), None);
+        assert_eq!(capacity.grow_to_fit(1000), Some(1024));
+        assert_eq!((capacity.get(), capacity.recreations), (1024, 2));
+    }
+
+    #[test]
+    fn nine_slices_are_parsed_from_aseprite_json() {
+        let json = r##"{
+            "frames": [
+                {
+                    "filename": "panel 0.aseprite",
+                    "frame": { "x": 0, "y": 0, "w": 32, "h": 24 },
+                    "rotated": false,
+                    "trimmed": false,
+                    "spriteSourceSize": { "x": 0, "y": 0, "w": 32, "h": 24 },
+                    "sourceSize": { "w": 32, "h": 24 },
+                    "duration": 100
+                }
+            ],
+            "meta": {
+                "app": "http://www.aseprite.org/",
+                "version": "1.2.25",
+                "image": "panel.png",
+                "format": "RGBA8888",
+                "size": { "w": 32, "h": 24 },
+                "scale": "1",
+                "frameTags": [],
+                "layers": [],
+                "slices": [
+                    {
+                        "name": "panel",
+                        "color": "#0000ffff",
+                        "keys": [
+                            {
+                                "frame": 0,
+                                "bounds": { "x": 2, "y": 4, "w": 28, "h": 16 },
+                                "center": { "x": 3, "y": 5, "w": 22, "h": 8 }
+                            },
+                            {
+                                "frame": 1,
+                                "bounds": { "x": 0, "y": 0, "w": 1, "h": 1 }
+                            }
+                        ]
+                    },
+                    {
+                        "name": "pivot",
+                        "color": "#ff0000ff",
+                        "keys": [{ "frame": 0, "bounds": { "x": 0, "y": 0, "w": 4, "h": 4 } }]
+                    }
+                ]
+            }
+        }"##;
+
+        let sheet = SpriteSheet::from_json(json).unwrap();
+
+        // Both rectangles are flipped to a bottom-left origin; the center relative to the bounds.
+        let panel = sheet.get_slice("panel").unwrap();
+        assert_eq!(panel.bounds, Box2::new(2, 4, 28, 16));
+        assert_eq!(panel.center, Some(Box2::new(3, 3, 22, 8)));
+
+        let pivot = sheet.get_slice("pivot").unwrap();
+        assert_eq!(pivot.bounds, Box2::new(0, 20, 4, 4));
+        assert_eq!(pivot.center, None);
+        assert!(sheet.get_slice("missing").is_none());
+    }
+}
@@ -0,0 +1,354 @@
+//! Packing several [`SpriteSheet`]s into a single texture, so that sprites from different sheets
+//! can be drawn with one [`SpriteBatch`].
+//!
+//! [`SpriteBatch`]: crate::graphics::SpriteBatch
+
+use hv_core::{engine::Engine, prelude::*};
+use image::RgbaImage;
+use std::{
+    collections::HashMap,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    graphics::{
+        sprite::{CachedSpriteSheet, SpriteSheet, SpriteSheetCache},
+        CachedTexture, GraphicsLock, GraphicsLockExt, Texture,
+    },
+    math::*,
+};
+
+/// Options for packing sprite sheets into a [`SpriteSheetAtlas`].
+#[derive(Debug, Clone, Copy)]
+pub struct SpriteSheetAtlasOptions {
+    /// The maximum width and height of the atlas texture. Packing fails if the sheets don't fit.
+    pub max_size: u32,
+    /// The number of transparent pixels left between sheets in the atlas.
+    pub padding: u32,
+}
+
+impl Default for SpriteSheetAtlasOptions {
+    fn default() -> Self {
+        Self {
+            max_size: 4096,
+            padding: 1,
+        }
+    }
+}
+
+/// The result of packing a set of sprite sheet images into a single atlas. Contains no image data,
+/// only the position of each sheet image and the size of the atlas.
+#[derive(Debug, Clone)]
+pub struct SpriteSheetAtlasLayout {
+    /// The top left corner of each sheet in the atlas, in pixels with the origin at the top left of
+    /// the atlas (as the image is decoded.)
+    pub positions: Vec<Vector2<u32>>,
+    /// The size of each sheet image, in the same order as `positions`.
+    pub sizes: Vec<Vector2<u32>>,
+    /// The width and height in pixels of the atlas.
+    pub atlas_size: Vector2<u32>,
+}
+
+impl SpriteSheetAtlasLayout {
+    /// Pack images of the given sizes into a single atlas using a simple shelf packer. Images are
+    /// packed in order of decreasing height.
+    pub fn pack(sizes: &[Vector2<u32>], options: &SpriteSheetAtlasOptions) -> Result<Self> {
+        let padding = options.padding;
+        let mut order = (0..sizes.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&i| std::cmp::Reverse(sizes[i].y));
+
+        let mut positions = vec![Vector2::zeros(); sizes.len()];
+        let mut atlas_size = Vector2::<u32>::zeros();
+        let (mut cursor_x, mut shelf_y, mut shelf_height) = (0, 0, 0);
+
+        for i in order {
+            let (w, h) = (sizes[i].x + padding, sizes[i].y + padding);
+
+            if cursor_x + w > options.max_size {
+                shelf_y += shelf_height;
+                cursor_x = 0;
+                shelf_height = 0;
+            }
+
+            if cursor_x + w > options.max_size || shelf_y + h > options.max_size {
+                bail!(
+                    "sprite sheets do not fit in a {}x{} atlas",
+                    options.max_size,
+                    options.max_size
+                );
+            }
+
+            positions[i] = Vector2::new(cursor_x, shelf_y);
+            cursor_x += w;
+            shelf_height = shelf_height.max(h);
+
+            atlas_size.x = atlas_size.x.max(cursor_x);
+            atlas_size.y = atlas_size.y.max(shelf_y + shelf_height);
+        }
+
+        Ok(Self {
+            positions,
+            sizes: sizes.to_vec(),
+            atlas_size,
+        })
+    }
+
+    /// Copy the sheet images (in top-down order, as decoded) into a new atlas image.
+    pub fn compose(&self, images: &[RgbaImage]) -> RgbaImage {
+        let mut atlas = RgbaImage::new(self.atlas_size.x, self.atlas_size.y);
+        for (position, source) in self.positions.iter().zip(images) {
+            image::imageops::replace(&mut atlas, source, position.x, position.y);
+        }
+        atlas
+    }
+
+    /// Create a copy of a sprite sheet with all of its frames remapped to refer to the region of
+    /// the atlas the sheet at index `sheet_index` was packed into.
+    ///
+    /// Like the sheet's own UVs, the remapped UVs have their origin at the bottom left, matching
+    /// textures created by [`Texture::from_memory`] and by [`SpriteSheetAtlas::load`].
+    pub fn remap(&self, sheet_index: usize, sheet: &SpriteSheet) -> SpriteSheet {
+        let atlas_size = self.atlas_size.cast::<f32>();
+        let size = self.sizes[sheet_index];
+        let position = self.positions[sheet_index];
+        // Bottom left corner of the sheet in the atlas, with a bottom-left origin.
+        let origin = Vector2::new(position.x, self.atlas_size.y - position.y - size.y);
+        let scale = size.cast::<f32>().component_div(&atlas_size);
+        let uv_origin = origin.cast::<f32>().component_div(&atlas_size);
+
+        let mut remapped = sheet.clone();
+        for frame in &mut remapped.frames {
+            frame.uvs = Box2::new(
+                uv_origin.x + frame.uvs.mins.x * scale.x,
+                uv_origin.y + frame.uvs.mins.y * scale.y,
+                frame.uvs.extents().x * scale.x,
+                frame.uvs.extents().y * scale.y,
+            );
+
+            if let Some(source) = &mut frame.source {
+                source.frame = Box2::new(
+                    source.frame.mins.x + origin.x,
+                    source.frame.mins.y + origin.y,
+                    source.frame.extents().x,
+                    source.frame.extents().y,
+                );
+            }
+        }
+
+        if let Some(source) = &mut remapped.source {
+            source.size = self.atlas_size;
+        }
+
+        remapped
+    }
+}
+
+/// A set of sprite sheets sharing a single texture. The sheets' frames refer to sub-rectangles of
+/// the atlas texture, so any of them can be drawn through one
+/// [`SpriteBatch`](crate::graphics::SpriteBatch) using [`SpriteSheetAtlas::texture`].
+///
+/// The sheets produced by an atlas are not hot-reloaded by [`SpriteSheetCache::reload_all`]; load
+/// the atlas again if the underlying files change.
+#[derive(Debug, Clone)]
+pub struct SpriteSheetAtlas {
+    texture: CachedTexture,
+    sheets: HashMap<String, CachedSpriteSheet>,
+    layout: SpriteSheetAtlasLayout,
+}
+
+impl SpriteSheetAtlas {
+    /// Load the sprite sheets at the given paths through the [`SpriteSheetCache`], along with the
+    /// images they reference (resolved relative to each sheet's path), and pack them into a single
+    /// texture.
+    pub fn load<S: AsRef<str>>(
+        engine: &Engine,
+        sprite_sheet_cache: &mut SpriteSheetCache,
+        paths: &[S],
+        options: &SpriteSheetAtlasOptions,
+    ) -> Result<Self> {
+        let mut sheets = Vec::new();
+        let mut images = Vec::new();
+        for path in paths {
+            let path = path.as_ref();
+            let sheet = sprite_sheet_cache.get_or_load(path)?;
+            let image_path = Self::image_path(path, &sheet.get())?;
+            let mut buf = Vec::new();
+            engine.fs().open(&image_path)?.read_to_end(&mut buf)?;
+            images.push(image::load_from_memory(&buf)?.to_rgba8());
+            sheets.push(sheet);
+        }
+
+        let sizes = images
+            .iter()
+            .map(|image| Vector2::new(image.width(), image.height()))
+            .collect::<Vec<_>>();
+        let layout = SpriteSheetAtlasLayout::pack(&sizes, options)?;
+        let mut atlas_image = layout.compose(&images);
+        image::imageops::flip_vertical_in_place(&mut atlas_image);
+
+        let texture = Texture::from_rgba8(
+            &mut engine.get::<GraphicsLock>().lock(),
+            atlas_image.width() as u16,
+            atlas_image.height() as u16,
+            &atlas_image.to_vec(),
+        );
+
+        let sheets = paths
+            .iter()
+            .zip(&sheets)
+            .enumerate()
+            .map(|(i, (path, sheet))| {
+                let remapped = layout.remap(i, &sheet.get());
+                (
+                    path.as_ref().to_owned(),
+                    CachedSpriteSheet::new_uncached(remapped),
+                )
+            })
+            .collect();
+
+        Ok(Self {
+            texture: CachedTexture::from(texture),
+            sheets,
+            layout,
+        })
+    }
+
+    fn image_path(sheet_path: &str, sheet: &SpriteSheet) -> Result<PathBuf> {
+        let image = sheet
+            .source
+            .as_ref()
+            .and_then(|source| source.image.as_ref())
+            .ok_or_else(|| anyhow!("sprite sheet `{}` does not reference an image", sheet_path))?;
+        let parent = Path::new(sheet_path)
+            .parent()
+            .unwrap_or_else(|| Path::new("/"));
+        Ok(parent.join(image))
+    }
+
+    /// The shared atlas texture.
+    pub fn texture(&self) -> &CachedTexture {
+        &self.texture
+    }
+
+    /// Get the remapped sprite sheet which was loaded from the given path.
+    pub fn get(&self, path: &str) -> Option<CachedSpriteSheet> {
+        self.sheets.get(path).cloned()
+    }
+
+    /// The layout the sheets were packed with.
+    pub fn layout(&self) -> &SpriteSheetAtlasLayout {
+        &self.layout
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::sprite::{Frame, FrameId};
+
+    fn sheet(frame_pixels: &[Box2<u32>], size: Vector2<u32>) -> SpriteSheet {
+        let mut sheet = SpriteSheet::new();
+        sheet.frames.clear();
+        for px in frame_pixels {
+            sheet.insert_frame(Frame {
+                source: None,
+                offset: Vector2::zeros(),
+                uvs: Box2::new(
+                    px.mins.x as f32 / size.x as f32,
+                    px.mins.y as f32 / size.y as f32,
+                    px.extents().x as f32 / size.x as f32,
+                    px.extents().y as f32 / size.y as f32,
+                ),
+                duration: 100,
+            });
+        }
+        sheet
+    }
+
+    fn assert_uvs_eq(actual: Box2<f32>, expected: Box2<f32>) {
+        let close = |a: Point2<f32>, b: Point2<f32>| (a - b).norm() < 1e-6;
+        assert!(
+            close(actual.mins, expected.mins) && close(actual.maxs, expected.maxs),
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn frames_map_to_their_atlas_regions() {
+        // A 32x24 sheet with two 16x24 frames, and a 48x46 sheet with three 16x23 frames on the
+        // bottom row, each with a bottom-left origin.
+        let goomba = sheet(
+            &[Box2::new(0, 0, 16, 24), Box2::new(16, 0, 16, 24)],
+            Vector2::new(32, 24),
+        );
+        let koopa = sheet(
+            &[
+                Box2::new(0, 0, 16, 23),
+                Box2::new(16, 0, 16, 23),
+                Box2::new(32, 0, 16, 23),
+            ],
+            Vector2::new(48, 46),
+        );
+
+        let options = SpriteSheetAtlasOptions {
+            max_size: 128,
+            padding: 1,
+        };
+        let layout =
+            SpriteSheetAtlasLayout::pack(&[Vector2::new(32, 24), Vector2::new(48, 46)], &options)
+                .unwrap();
+
+        // The taller koopa sheet goes first, then the goomba sheet on the same shelf.
+        assert_eq!(layout.positions[1], Vector2::new(0, 0));
+        assert_eq!(layout.positions[0], Vector2::new(49, 0));
+        assert_eq!(layout.atlas_size, Vector2::new(49 + 33, 47));
+
+        let atlas_size = Vector2::new(82., 47.);
+
+        // Second goomba frame: the sheet's top edge is at the top of the atlas, so its bottom edge
+        // is 47 - 24 = 23 pixels up from the bottom of the atlas.
+        let goomba = layout.remap(0, &goomba);
+        assert_uvs_eq(
+            goomba[FrameId(1)].uvs,
+            Box2::new(
+                (49. + 16.) / atlas_size.x,
+                23. / atlas_size.y,
+                16. / atlas_size.x,
+                24. / atlas_size.y,
+            ),
+        );
+
+        // Third koopa frame: the sheet's bottom edge is 47 - 46 = 1 pixel up.
+        let koopa = layout.remap(1, &koopa);
+        assert_uvs_eq(
+            koopa[FrameId(2)].uvs,
+            Box2::new(
+                32. / atlas_size.x,
+                1. / atlas_size.y,
+                16. / atlas_size.x,
+                23. / atlas_size.y,
+            ),
+        );
+
+        // Composing puts each image's top left pixel at its packed position.
+        let mut goomba_image = RgbaImage::new(32, 24);
+        goomba_image.put_pixel(0, 0, image::Rgba([255, 0, 0, 255]));
+        let mut koopa_image = RgbaImage::new(48, 46);
+        koopa_image.put_pixel(0, 0, image::Rgba([0, 255, 0, 255]));
+        let atlas = layout.compose(&[goomba_image, koopa_image]);
+        assert_eq!(atlas.get_pixel(49, 0).0, [255, 0, 0, 255]);
+        assert_eq!(atlas.get_pixel(0, 0).0, [0, 255, 0, 255]);
+    }
+
+    #[test]
+    fn packing_fails_when_too_large() {
+        let options = SpriteSheetAtlasOptions {
+            max_size: 32,
+            padding: 0,
+        };
+        assert!(SpriteSheetAtlasLayout::pack(&[Vector2::new(64, 8)], &options).is_err());
+    }
+}
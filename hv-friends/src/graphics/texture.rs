@@ -4,60 +4,180 @@ use hv_core::{
     prelude::*,
     swappable_cache::{AsCached, Guard, Handle, Loader, SwappableCache, UncachedHandle},
 };
-use std::{io::Read, ops::Deref, path::Path, sync::Arc};
+use serde::*;
+use std::{
+    io::Read,
+    ops::Deref,
+    path::Path,
+    sync::{Arc, Mutex},
+};
 
 use crate::{
     graphics::{
         Drawable, DrawableMut, FilterMode, Graphics, GraphicsLock, GraphicsLockExt, Instance,
+        WrapMode,
     },
     math::*,
 };
 
+/// How a [`Texture`] is sampled when drawn.
+///
+/// The default is [`FilterMode::Nearest`] and [`WrapMode::Clamp`], which keeps pixel art crisp
+/// when scaled; smooth images such as gradients or photos usually want [`FilterMode::Linear`].
+///
+/// From Lua, options are a table with optional `filter` and `wrap` fields, such as
+/// `{ filter = "Linear" }`; missing fields take their default values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TextureOptions {
+    /// How texels are filtered when the texture is scaled.
+    pub filter: FilterMode,
+    /// How texture coordinates outside of `[0, 1]` are sampled.
+    pub wrap: WrapMode,
+}
+
+impl Default for TextureOptions {
+    fn default() -> Self {
+        Self {
+            filter: FilterMode::Nearest,
+            wrap: WrapMode::Clamp,
+        }
+    }
+}
+
+impl TextureOptions {
+    pub fn new(filter: FilterMode, wrap: WrapMode) -> Self {
+        Self { filter, wrap }
+    }
+
+    pub fn with_filter(self, filter: FilterMode) -> Self {
+        Self { filter, ..self }
+    }
+
+    pub fn with_wrap(self, wrap: WrapMode) -> Self {
+        Self { wrap, ..self }
+    }
+}
+
+impl<'lua> FromLua<'lua> for TextureOptions {
+    fn from_lua(lua_value: LuaValue<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        lua.from_value(lua_value)
+    }
+}
+
 /// A type which represents a handle to a GPU-allocated texture.
+///
+/// Sampler settings belong to the texture on the GPU rather than to whatever draws it, so changing
+/// the filter or wrap mode of a texture shared between several drawers (for example, through a
+/// [`CachedTexture`]) changes it for all of them.
 #[derive(Debug)]
 pub struct Texture {
     pub handle: mq::Texture,
+    options: Mutex<TextureOptions>,
 }
 
 impl Texture {
-    /// Create a texture from a given buffer of RGBA image data.
+    /// Create a texture from a given buffer of RGBA image data, with the default
+    /// [`TextureOptions`].
     pub fn from_rgba8(ctx: &mut Graphics, width: u16, height: u16, bytes: &[u8]) -> Self {
+        Self::from_rgba8_with_options(ctx, width, height, bytes, TextureOptions::default())
+    }
+
+    /// Create a texture from a given buffer of RGBA image data.
+    pub fn from_rgba8_with_options(
+        ctx: &mut Graphics,
+        width: u16,
+        height: u16,
+        bytes: &[u8],
+        options: TextureOptions,
+    ) -> Self {
         let tex = mq::Texture::from_rgba8(ctx.mq_mut(), width, height, bytes);
-        tex.set_filter(ctx.mq_mut(), mq::FilterMode::Nearest);
-        Self::from_inner(tex)
+        let texture = Self::from_inner(tex);
+        texture.set_options(ctx, options);
+        texture
     }
 
-    /// Parse a buffer containing the raw contents of an image file such as a PNG, GIF, etc.
+    /// Parse a buffer containing the raw contents of an image file such as a PNG, GIF, etc., with
+    /// the default [`TextureOptions`].
     pub fn from_memory(ctx: &mut Graphics, buffer: &[u8]) -> Result<Self> {
+        Self::from_memory_with_options(ctx, buffer, TextureOptions::default())
+    }
+
+    /// Parse a buffer containing the raw contents of an image file such as a PNG, GIF, etc.
+    pub fn from_memory_with_options(
+        ctx: &mut Graphics,
+        buffer: &[u8],
+        options: TextureOptions,
+    ) -> Result<Self> {
         let mut rgba_image = image::load_from_memory(buffer)?.to_rgba8();
         image::imageops::flip_vertical_in_place(&mut rgba_image);
-        Ok(Self::from_rgba8(
+        Ok(Self::from_rgba8_with_options(
             ctx,
             rgba_image.width() as u16,
             rgba_image.height() as u16,
             &rgba_image.to_vec(),
+            options,
         ))
     }
 
-    /// Parse a reader such as a `File` into a texture.
+    /// Parse a reader such as a `File` into a texture, with the default [`TextureOptions`].
     pub fn from_reader<R: Read>(ctx: &mut Graphics, reader: &mut R) -> Result<Self> {
+        Self::from_reader_with_options(ctx, reader, TextureOptions::default())
+    }
+
+    /// Parse a reader such as a `File` into a texture.
+    pub fn from_reader_with_options<R: Read>(
+        ctx: &mut Graphics,
+        reader: &mut R,
+        options: TextureOptions,
+    ) -> Result<Self> {
         let mut buf = Vec::new();
         reader.read_to_end(&mut buf)?;
-        Self::from_memory(ctx, &buf)
+        Self::from_memory_with_options(ctx, &buf, options)
     }
 
+    /// Wrap a raw miniquad texture, assuming it was created with the default [`TextureOptions`].
     pub fn from_inner(handle: mq::Texture) -> Self {
-        Self { handle }
+        Self::from_inner_with_options(handle, TextureOptions::default())
     }
 
-    pub fn set_filter_mode(&self, ctx: &mut Graphics, filter_mode: FilterMode) {
-        self.handle.set_filter(
-            ctx.mq_mut(),
-            match filter_mode {
-                FilterMode::Nearest => mq::FilterMode::Nearest,
-                FilterMode::Linear => mq::FilterMode::Linear,
-            },
-        );
+    /// Wrap a raw miniquad texture which was created with the given options. This doesn't apply
+    /// the options to the texture; it only records them, so they must match how the texture was
+    /// actually created.
+    pub fn from_inner_with_options(handle: mq::Texture, options: TextureOptions) -> Self {
+        Self {
+            handle,
+            options: Mutex::new(options),
+        }
+    }
+
+    /// The options this texture is currently sampled with.
+    pub fn options(&self) -> TextureOptions {
+        *self.options.lock().unwrap()
+    }
+
+    pub fn filter(&self) -> FilterMode {
+        self.options().filter
+    }
+
+    pub fn wrap(&self) -> WrapMode {
+        self.options().wrap
+    }
+
+    /// Change both the filter and wrap modes of this texture.
+    pub fn set_options(&self, ctx: &mut Graphics, options: TextureOptions) {
+        self.set_filter(ctx, options.filter);
+        self.set_wrap(ctx, options.wrap);
+    }
+
+    pub fn set_filter(&self, ctx: &mut Graphics, filter: FilterMode) {
+        self.handle.set_filter(ctx.mq_mut(), filter.into());
+        self.options.lock().unwrap().filter = filter;
+    }
+
+    pub fn set_wrap(&self, ctx: &mut Graphics, wrap: WrapMode) {
+        self.handle.set_wrap(ctx.mq_mut(), wrap.into());
+        self.options.lock().unwrap().wrap = wrap;
     }
 
     pub fn width(&self) -> u32 {
@@ -217,6 +337,27 @@ impl Drawable for CachedTexture {
 impl LuaUserData for CachedTexture {
     fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
         crate::lua::add_drawable_methods(methods);
+
+        methods.add_method("filter", |_, this, ()| Ok(this.get().filter()));
+        methods.add_method("wrap", |_, this, ()| Ok(this.get().wrap()));
+
+        methods.add_method("set_filter", |lua, this, filter: FilterMode| {
+            let gfx = lua.get_resource::<GraphicsLock>()?;
+            this.get().set_filter(&mut gfx.lock(), filter);
+            Ok(())
+        });
+
+        methods.add_method("set_wrap", |lua, this, wrap: WrapMode| {
+            let gfx = lua.get_resource::<GraphicsLock>()?;
+            this.get().set_wrap(&mut gfx.lock(), wrap);
+            Ok(())
+        });
+
+        methods.add_method("set_options", |lua, this, options: TextureOptions| {
+            let gfx = lua.get_resource::<GraphicsLock>()?;
+            this.get().set_options(&mut gfx.lock(), options);
+            Ok(())
+        });
     }
 }
 
@@ -268,3 +409,37 @@ impl TextureCache {
         self.inner.reload_all()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nearest_options_report_and_map_to_nearest_sampling() {
+        let lua = Lua::new();
+        let options: TextureOptions = lua
+            .load(r#"{ filter = "Nearest", wrap = "Repeat" }"#)
+            .eval()
+            .unwrap();
+        assert_eq!(
+            options,
+            TextureOptions::new(FilterMode::Nearest, WrapMode::Repeat)
+        );
+        assert!(matches!(
+            mq::FilterMode::from(options.filter),
+            mq::FilterMode::Nearest
+        ));
+        assert!(matches!(
+            mq::TextureWrap::from(options.wrap),
+            mq::TextureWrap::Repeat
+        ));
+
+        // Missing fields keep their defaults.
+        let linear: TextureOptions = lua.load(r#"{ filter = "Linear" }"#).eval().unwrap();
+        assert_eq!(
+            linear,
+            TextureOptions::default().with_filter(FilterMode::Linear)
+        );
+        assert_eq!(linear.wrap, WrapMode::Clamp);
+    }
+}
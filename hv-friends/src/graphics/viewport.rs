@@ -0,0 +1,228 @@
+//! Rendering independent views of the world to different regions of the window, for example for
+//! split-screen multiplayer.
+//!
+//! A [`Viewport`] pairs a region of the screen with the [`Camera`] to view the world through.
+//! [`Graphics::with_viewport`] restricts drawing to the viewport's region and applies its camera
+//! for the duration of a closure, so drawing the same world once per viewport gives each camera its
+//! own view.
+
+use crate::{
+    camera::Camera,
+    graphics::{screen_to_world_point2, world_to_screen_point2, Graphics, YAxis},
+    math::*,
+};
+
+/// Convert a rectangle in window coordinates (logical pixels, origin at the top left, with Y
+/// pointing down) into the `(x, y, width, height)` in physical pixels expected by miniquad's
+/// viewport and scissor functions, which put the origin at the bottom left of a window of height
+/// `window_height` (also in logical pixels.)
+pub(crate) fn window_rect_to_gl(
+    window_height: f32,
+    dpi_scale: f32,
+    rect: &Box2<f32>,
+) -> (i32, i32, i32, i32) {
+    let extents = rect.extents() * dpi_scale;
+    (
+        (rect.mins.x * dpi_scale) as i32,
+        ((window_height - rect.maxs.y) * dpi_scale) as i32,
+        extents.x as i32,
+        extents.y as i32,
+    )
+}
+
+/// A region of the screen which shows the world through its own [`Camera`].
+pub struct Viewport {
+    /// The region of the enclosing viewport to draw to, as fractions of its size; `(0, 0)` is its
+    /// top left corner and `(1, 1)` its bottom right. Because this is relative to the enclosing
+    /// viewport, splitting a letterboxed screen keeps it letterboxed.
+    pub rect: Box2<f32>,
+    /// The camera to view the world through. Its screen dimensions are used as the projection
+    /// inside the viewport, so they should have the same aspect ratio as the viewport's region.
+    pub camera: Camera,
+}
+
+impl Viewport {
+    pub fn new(rect: Box2<f32>, camera: Camera) -> Self {
+        Self { rect, camera }
+    }
+
+    /// The region of the window, in window coordinates, which this viewport covers when nested
+    /// inside the `enclosing` region (usually [`Graphics::viewport`].)
+    pub fn window_rect(&self, enclosing: &Box2<f32>) -> Box2<f32> {
+        let enclosing_extents = enclosing.extents();
+        let extents = self.rect.extents();
+        Box2::new(
+            enclosing.mins.x + self.rect.mins.x * enclosing_extents.x,
+            enclosing.mins.y + self.rect.mins.y * enclosing_extents.y,
+            extents.x * enclosing_extents.x,
+            extents.y * enclosing_extents.y,
+        )
+    }
+
+    /// The projection used while drawing to this viewport: an orthographic projection of the
+    /// camera's screen dimensions, oriented according to `y_axis`.
+    pub fn projection(&self, y_axis: YAxis) -> Matrix4<f32> {
+        let dimensions = self.camera.parameters().screen_dimensions.cast::<f32>();
+        y_axis.orthographic(dimensions.x, dimensions.y)
+    }
+
+    /// Whether a point in window coordinates (such as the mouse position) is inside this viewport.
+    pub fn contains_screen_point(&self, enclosing: &Box2<f32>, screen: Point2<f32>) -> bool {
+        let rect = self.window_rect(enclosing);
+        screen.x >= rect.mins.x
+            && screen.x < rect.maxs.x
+            && screen.y >= rect.mins.y
+            && screen.y < rect.maxs.y
+    }
+
+    /// Convert a point in window coordinates into world space as seen through this viewport.
+    pub fn screen_to_world(
+        &self,
+        enclosing: &Box2<f32>,
+        y_axis: YAxis,
+        screen: Point2<f32>,
+    ) -> Point2<f32> {
+        let transform = self.projection(y_axis) * self.camera.view_tx();
+        screen_to_world_point2(&transform, &self.window_rect(enclosing), screen)
+    }
+
+    /// Convert a point in world space into window coordinates as seen through this viewport.
+    pub fn world_to_screen(
+        &self,
+        enclosing: &Box2<f32>,
+        y_axis: YAxis,
+        world: Point2<f32>,
+    ) -> Point2<f32> {
+        let transform = self.projection(y_axis) * self.camera.view_tx();
+        world_to_screen_point2(&transform, &self.window_rect(enclosing), world)
+    }
+}
+
+impl<'a> Graphics<'a> {
+    /// Draw through a [`Viewport`]: restrict the viewport and scissor rectangle to its region of
+    /// the current viewport, and apply its camera's projection and view transform, for the
+    /// duration of `f`. Afterwards, the previous viewport, scissor rectangle, projection, and
+    /// modelview are restored.
+    pub fn with_viewport<R>(&mut self, viewport: &Viewport, f: impl FnOnce(&mut Self) -> R) -> R {
+        let old_viewport = self.state.viewport;
+        let old_scissor = self.state.scissor;
+        let old_projection = self.state.projection;
+        let old_default_projection_size = self.state.default_projection_size;
+
+        let rect = viewport.window_rect(&self.viewport());
+        self.apply_viewport(rect);
+        self.apply_scissor(rect);
+        self.set_projection(viewport.projection(self.state.y_axis));
+        self.modelview_mut()
+            .push(None)
+            .apply_transform(viewport.camera.view_tx());
+
+        let result = f(self);
+
+        self.modelview_mut().pop();
+        self.set_projection(old_projection);
+        self.state.default_projection_size = old_default_projection_size;
+        match old_viewport {
+            Some(old_viewport) => self.apply_viewport(old_viewport),
+            None => {
+                let (w, h) = self.screen_size();
+                self.apply_viewport(Box2::new(0., 0., w, h));
+                self.state.viewport = None;
+            }
+        }
+        match old_scissor {
+            Some(old_scissor) => self.apply_scissor(old_scissor),
+            None => self.reset_scissor(),
+        }
+
+        result
+    }
+
+    /// Like [`Graphics::screen_to_world`], but as seen through a [`Viewport`] nested in the current
+    /// viewport.
+    pub fn screen_to_world_in_viewport(
+        &self,
+        viewport: &Viewport,
+        screen: Point2<f32>,
+    ) -> Point2<f32> {
+        viewport.screen_to_world(&self.viewport(), self.state.y_axis, screen)
+    }
+
+    /// Like [`Graphics::world_to_screen`], but as seen through a [`Viewport`] nested in the current
+    /// viewport.
+    pub fn world_to_screen_in_viewport(
+        &self,
+        viewport: &Viewport,
+        world: Point2<f32>,
+    ) -> Point2<f32> {
+        viewport.world_to_screen(&self.viewport(), self.state.y_axis, world)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::CameraParameters;
+
+    fn assert_points_eq(a: Point2<f32>, b: Point2<f32>) {
+        assert!((a - b).norm() < 1e-3, "{} != {}", a, b);
+    }
+
+    fn viewport(rect: Box2<f32>, subject: Point2<f32>) -> Viewport {
+        let mut camera = Camera::new(CameraParameters::new(Vector2::new(320, 480)));
+        camera.set_subject_pos(subject);
+        camera.update(1. / 60.);
+        Viewport::new(rect, camera)
+    }
+
+    #[test]
+    fn half_width_viewports_in_letterbox() {
+        // An 800x480 window, letterboxed to 640x480 in the middle.
+        let window_height = 480.;
+        let letterbox = Box2::new(80., 0., 640., 480.);
+        let left = viewport(Box2::new(0., 0., 0.5, 1.), Point2::new(0., 0.));
+        let right = viewport(Box2::new(0.5, 0., 0.5, 1.), Point2::new(1000., 50.));
+
+        let left_rect = left.window_rect(&letterbox);
+        let right_rect = right.window_rect(&letterbox);
+        assert_eq!(
+            window_rect_to_gl(window_height, 1., &left_rect),
+            (80, 0, 320, 480)
+        );
+        assert_eq!(
+            window_rect_to_gl(window_height, 1., &right_rect),
+            (400, 0, 320, 480)
+        );
+        // Viewports are given in logical pixels, but miniquad wants physical ones.
+        assert_eq!(
+            window_rect_to_gl(window_height, 2., &left_rect),
+            (160, 0, 640, 960)
+        );
+
+        // Vertical splits have to account for the flipped Y axis.
+        let top = viewport(Box2::new(0., 0., 1., 0.5), Point2::origin());
+        assert_eq!(
+            window_rect_to_gl(window_height, 1., &top.window_rect(&letterbox)),
+            (80, 240, 640, 240)
+        );
+
+        // The mouse maps through whichever viewport it's in, to that viewport's camera.
+        let left_center = Point2::new(240., 240.);
+        let right_center = Point2::new(560., 240.);
+        assert!(left.contains_screen_point(&letterbox, left_center));
+        assert!(!left.contains_screen_point(&letterbox, right_center));
+        assert!(right.contains_screen_point(&letterbox, right_center));
+        assert_points_eq(
+            left.screen_to_world(&letterbox, YAxis::Up, left_center),
+            Point2::new(0., 0.),
+        );
+        assert_points_eq(
+            right.screen_to_world(&letterbox, YAxis::Up, right_center),
+            Point2::new(1000., 50.),
+        );
+        assert_points_eq(
+            right.world_to_screen(&letterbox, YAxis::Up, Point2::new(1000., 50.)),
+            right_center,
+        );
+    }
+}
@@ -0,0 +1,240 @@
+//! Distance constraints between pairs of objects, for chained enemies, swinging platforms, ropes and
+//! the like.
+//!
+//! Joints are solved by positional correction: every iteration of [`solve_joints`] moves the two
+//! ends of each joint towards or away from each other to bring them closer to the joint's rest
+//! length. Only objects with a [`Velocity`] are moved; an object with a [`Position`] but no
+//! [`Velocity`] acts as a fixed anchor, which is how a platform gets hung from a point in the
+//! world.
+
+use std::collections::HashMap;
+
+use hv_core::{
+    components::DynamicComponentConstructor,
+    engine::Engine,
+    prelude::*,
+    spaces::{command::CommandBuffer, serialize, Object, Space},
+};
+use serde::*;
+
+use crate::{math::*, Position, Velocity};
+
+/// The number of solver iterations used by the Lua `solve_joints` function when none is given.
+pub const DEFAULT_JOINT_ITERATIONS: u32 = 4;
+
+/// Keeps the object it's attached to at `rest_length` from `other`, when solved by
+/// [`solve_joints`].
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct DistanceJoint {
+    /// The object at the other end of the joint.
+    pub other: Object,
+    /// The distance the joint tries to keep between the two objects.
+    pub rest_length: f32,
+    /// The fraction of the remaining error corrected per solver iteration. Clamped to `[0, 1]`
+    /// when solving, so a very stiff joint behaves like a rigid rod rather than overshooting.
+    pub stiffness: f32,
+}
+
+impl DistanceJoint {
+    pub fn new(other: Object, rest_length: f32, stiffness: f32) -> Self {
+        Self {
+            other,
+            rest_length,
+            stiffness,
+        }
+    }
+}
+
+hv_core::serializable!(serialize::with_serde::<DistanceJoint>(
+    "friends.DistanceJoint"
+));
+hv_core::component_type!("DistanceJoint", DistanceJoint);
+
+impl LuaUserData for DistanceJoint {}
+
+/// The translation of an object and whether the solver is allowed to move it, or `None` if it has
+/// no position.
+fn joint_end(space: &Space, object: Object) -> Option<(Vector2<f32>, bool)> {
+    let position = space.get::<Position>(object).ok()?.0.translation.vector;
+    Some((position, space.get::<Velocity>(object).is_ok()))
+}
+
+/// Solve every [`DistanceJoint`] in the space, running `iterations` passes of positional correction
+/// over all of them. More iterations let chains of joints settle faster, at the cost of time spent
+/// solving.
+///
+/// Joints whose `other` object has been despawned are removed from their objects. Ends without a
+/// [`Position`] are skipped. Every object which is moved also has the total correction over `dt`
+/// added to its linear velocity, so that integrating afterwards doesn't pull it straight back out
+/// of place.
+pub fn solve_joints(space: &mut Space, dt: f32, iterations: u32) -> Result<()> {
+    let mut commands = CommandBuffer::new();
+    let mut joints = Vec::new();
+
+    for (object, joint) in space.query::<&DistanceJoint>().iter() {
+        if space.contains(joint.other) {
+            joints.push((object, *joint));
+        } else {
+            commands.remove::<(DistanceJoint,)>(object);
+        }
+    }
+
+    commands.run(space)?;
+
+    let mut corrections = HashMap::<Object, Vector2<f32>>::new();
+    for _ in 0..iterations {
+        for &(a, joint) in &joints {
+            let ((pos_a, movable_a), (pos_b, movable_b)) =
+                match (joint_end(space, a), joint_end(space, joint.other)) {
+                    (Some(end_a), Some(end_b)) => (end_a, end_b),
+                    _ => continue,
+                };
+
+            let weight_a = if movable_a { 1. } else { 0. };
+            let weight_b = if movable_b { 1. } else { 0. };
+            let delta = pos_b - pos_a;
+            let distance = delta.norm();
+
+            // With both ends in the same place there's no direction to push them apart in.
+            if weight_a + weight_b == 0. || distance <= f32::EPSILON {
+                continue;
+            }
+
+            let error = distance - joint.rest_length;
+            let correction =
+                delta / distance * error * joint.stiffness.clamp(0., 1.) / (weight_a + weight_b);
+
+            for (object, offset) in [
+                (a, correction * weight_a),
+                (joint.other, -correction * weight_b),
+            ] {
+                if offset != Vector2::zeros() {
+                    space.get_mut::<Position>(object)?.0.translation.vector += offset;
+                    *corrections.entry(object).or_insert_with(Vector2::zeros) += offset;
+                }
+            }
+        }
+    }
+
+    if dt > 0. {
+        for (object, correction) in corrections {
+            space.get_mut::<Velocity>(object)?.0.linear += correction / dt;
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) fn open<'lua>(lua: &'lua Lua, _engine: &Engine) -> Result<LuaTable<'lua>, Error> {
+    let create_distance_joint_constructor = lua.create_function(
+        |_, (other, rest_length, stiffness): (Object, f32, Option<f32>)| {
+            Ok(DynamicComponentConstructor::copy(DistanceJoint::new(
+                other,
+                rest_length,
+                stiffness.unwrap_or(1.),
+            )))
+        },
+    )?;
+
+    let solve_joints = lua.create_function(
+        |_, (space, dt, iterations): (Shared<Space>, f32, Option<u32>)| {
+            solve_joints(
+                &mut space.borrow_mut(),
+                dt,
+                iterations.unwrap_or(DEFAULT_JOINT_ITERATIONS),
+            )
+            .to_lua_err()
+        },
+    )?;
+
+    Ok(lua
+        .load(mlua::chunk! {
+            {
+                create_distance_joint_constructor = $create_distance_joint_constructor,
+                solve_joints = $solve_joints,
+            }
+        })
+        .eval()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hv_core::spaces::Spaces;
+
+    fn body(x: f32, y: f32) -> (Position, Velocity) {
+        (
+            Position(Position2::translation(x, y)),
+            Velocity(Velocity2::new(Vector2::zeros(), 0.)),
+        )
+    }
+
+    fn distance(space: &Space, a: Object, b: Object) -> f32 {
+        let a = space.get::<Position>(a).unwrap().0.center();
+        let b = space.get::<Position>(b).unwrap().0.center();
+        na::distance(&a, &b)
+    }
+
+    #[test]
+    fn joined_objects_converge_to_rest_length() {
+        let space = Spaces::new().create_space();
+        let mut space = space.borrow_mut();
+        let a = space.spawn(body(0., 0.));
+        let b = space.spawn(body(10., 0.));
+        space.insert(a, (DistanceJoint::new(b, 4., 0.5),)).unwrap();
+
+        let mut error = distance(&space, a, b) - 4.;
+        for _ in 0..10 {
+            solve_joints(&mut space, 1. / 60., 1).unwrap();
+            let next = distance(&space, a, b) - 4.;
+            assert!(next.abs() < error.abs(), "{} >= {}", next, error);
+            error = next;
+        }
+
+        assert!(error.abs() < 1e-2, "{}", error);
+        // Both ends are movable, so they meet in the middle.
+        let center = space.get::<Position>(a).unwrap().0.center().x
+            + space.get::<Position>(b).unwrap().0.center().x;
+        assert!((center - 10.).abs() < 1e-4, "{}", center);
+    }
+
+    #[test]
+    fn stiff_joints_do_not_overshoot_and_anchors_stay_put() {
+        let space = Spaces::new().create_space();
+        let mut space = space.borrow_mut();
+        let anchor = space.spawn((Position(Position2::translation(0., 0.)),));
+        let bob = space.spawn(body(0., 10.));
+        space
+            .insert(bob, (DistanceJoint::new(anchor, 4., 1000.),))
+            .unwrap();
+
+        for _ in 0..5 {
+            solve_joints(&mut space, 1. / 60., 8).unwrap();
+            assert!((distance(&space, anchor, bob) - 4.).abs() < 1e-4);
+        }
+
+        assert_eq!(
+            space.get::<Position>(anchor).unwrap().0.center(),
+            Point2::origin()
+        );
+        assert!(space.get::<Velocity>(bob).unwrap().0.linear.y < 0.);
+    }
+
+    #[test]
+    fn joints_to_despawned_objects_are_removed() {
+        let space = Spaces::new().create_space();
+        let mut space = space.borrow_mut();
+        let a = space.spawn(body(0., 0.));
+        let b = space.spawn(body(10., 0.));
+        space.insert(a, (DistanceJoint::new(b, 4., 1.),)).unwrap();
+        space.despawn(b).unwrap();
+
+        solve_joints(&mut space, 1. / 60., 4).unwrap();
+
+        assert!(space.get::<DistanceJoint>(a).is_err());
+        assert_eq!(
+            space.get::<Position>(a).unwrap().0.center(),
+            Point2::origin()
+        );
+    }
+}
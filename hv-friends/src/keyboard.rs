@@ -28,6 +28,12 @@ impl EngineKeyboardState {
         }
     }
 
+    /// Mark every key as up, for when key releases can't be seen, such as while the window is
+    /// unfocused.
+    pub fn release_all(&mut self) {
+        self.is_key_down.clear();
+    }
+
     pub fn is_key_down(&self, key: KeyCode) -> bool {
         self.is_key_down
             .get(&key)
@@ -14,10 +14,11 @@
 #![feature(float_interpolation)]
 
 use hv_core::{
-    engine::{Engine, EventHandler},
+    engine::{Engine, EventHandler, MINIQUAD_DT},
     input::{KeyCode, KeyMods},
     plugins::Plugin,
     prelude::*,
+    timer::FixedTimestep,
 };
 
 pub extern crate nalgebra as na;
@@ -26,23 +27,36 @@ pub extern crate parry2d;
 #[macro_use]
 mod lua;
 
+mod joints;
 mod keyboard;
+mod lifetime;
+mod lua_handler;
 mod position;
+mod proximity;
+mod substep;
 mod velocity;
+mod z_order;
 
 pub mod camera;
 pub mod collision;
 pub mod graphics;
 pub mod math;
 pub mod scene;
+pub mod timeline;
 
-use na::Orthographic3;
+pub use joints::*;
+pub use lifetime::*;
+pub use lua_handler::*;
 pub use position::*;
+pub use proximity::*;
+pub use substep::*;
 pub use velocity::*;
+pub use z_order::*;
 
 use crate::{
     graphics::{ClearOptions, GraphicsLock, GraphicsLockExt},
     keyboard::EngineKeyboardState,
+    math::Box2,
 };
 
 #[doc(hidden)]
@@ -52,17 +66,39 @@ pub fn link_me() {}
 /// loads a Lua file by using the `hv.package.require` function, runs it, and then calls the Lua
 /// hooks it finds in the `hv` table (`hv.update`, `hv.draw`, `hv.load`, etc.) which are named much
 /// like their Love2D equivalents.
+///
+/// Updates run on a fixed timestep: `hv.update(dt)` is always called with the fixed step as `dt`,
+/// as many times per frame as needed to keep up, and `hv.draw(alpha)` is passed how far between
+/// the last update and the next one the frame is drawn at, from `0` to `1`, for interpolating
+/// positions between steps.
+///
+/// Input events other than key presses (which update `hf.keyboard`) aren't passed along to Lua;
+/// use a [`LuaHandler`] to handle those from Lua as well.
 pub struct SimpleHandler {
     entrypoint: String,
+    timestep: FixedTimestep,
 }
 
 impl SimpleHandler {
-    /// Create a new `SimpleHandler` which loads the given module as its "main" Lua entrypoint.
+    /// Create a new `SimpleHandler` which loads the given module as its "main" Lua entrypoint,
+    /// updating at miniquad's fixed rate of 60 steps per second.
     pub fn new(s: impl AsRef<str>) -> Self {
+        Self::with_fixed_timestep(s, MINIQUAD_DT)
+    }
+
+    /// Create a new `SimpleHandler` which loads the given module as its "main" Lua entrypoint,
+    /// updating with a fixed step of `step` seconds.
+    pub fn with_fixed_timestep(s: impl AsRef<str>, step: f32) -> Self {
         Self {
             entrypoint: s.as_ref().to_owned(),
+            timestep: FixedTimestep::new(step),
         }
     }
+
+    /// The fixed timestep this handler updates with.
+    pub fn timestep(&self) -> &FixedTimestep {
+        &self.timestep
+    }
 }
 
 impl EventHandler for SimpleHandler {
@@ -78,8 +114,10 @@ impl EventHandler for SimpleHandler {
 
         let gfx_lock = engine.get::<GraphicsLock>();
         let mut gfx = gfx_lock.lock();
-        let (w, h) = gfx.mq.screen_size();
-        gfx.set_projection(Orthographic3::new(0., w, 0., h, -1., 1.).to_homogeneous());
+        // Project from the internal resolution rather than the current window size, so that
+        // resizing the window or going fullscreen letterboxes instead of stretching.
+        let (w, h) = engine.window().resolution();
+        gfx.set_default_projection(w as f32, h as f32);
         gfx.apply_default_pipeline();
         gfx.begin_render_pass(None, Some(ClearOptions::default()));
         drop(gfx);
@@ -94,11 +132,17 @@ impl EventHandler for SimpleHandler {
     }
 
     fn update(&mut self, engine: &Engine, dt: f32) -> Result<()> {
-        engine
-            .lua()
-            .globals()
-            .get::<_, LuaTable>("hv")?
-            .call_function("update", dt)?;
+        let lua = engine.lua();
+        let step = self.timestep.step();
+        for _ in 0..self.timestep.advance(dt) {
+            lua.globals()
+                .get::<_, LuaTable>("hf")?
+                .get::<_, LuaTable>("timeline")?
+                .call_function("update", step)?;
+            lua.globals()
+                .get::<_, LuaTable>("hv")?
+                .call_function("update", step)?;
+        }
         Ok(())
     }
 
@@ -107,13 +151,15 @@ impl EventHandler for SimpleHandler {
 
         let mut gfx = gfx_lock.lock();
         gfx.begin_render_pass(None, Some(ClearOptions::default()));
+        let (x, y, w, h) = engine.window().letterbox(gfx.screen_size());
+        gfx.apply_viewport(Box2::new(x, y, w, h));
         drop(gfx);
 
         engine
             .lua()
             .globals()
             .get::<_, LuaTable>("hv")?
-            .call_function("draw", ())?;
+            .call_function("draw", self.timestep.alpha())?;
 
         let mut gfx = gfx_lock.lock();
         gfx.end_render_pass();
@@ -141,6 +187,15 @@ impl EventHandler for SimpleHandler {
             .borrow_mut()
             .set_key_state(keycode, false, false);
     }
+
+    fn focus_event(&mut self, engine: &Engine, focused: bool) {
+        if !focused {
+            engine
+                .get::<EngineKeyboardState>()
+                .borrow_mut()
+                .release_all();
+        }
+    }
 }
 
 struct HvFriendsPlugin;
@@ -156,22 +211,36 @@ impl Plugin for HvFriendsPlugin {
             Some(std::path::PathBuf::from("hv-friends/resources/scripts")),
         )?;
 
+        let camera = crate::camera::open(lua, engine)?;
         let collision = crate::collision::open(lua, engine)?;
         let graphics = crate::graphics::open(lua, engine)?;
+        let joints = crate::joints::open(lua, engine)?;
         let keyboard = crate::keyboard::open(lua, engine)?;
+        let lifetime = crate::lifetime::open(lua, engine)?;
         let position = crate::position::open(lua, engine)?;
+        let proximity = crate::proximity::open(lua, engine)?;
+        let substep = crate::substep::open(lua, engine)?;
         let velocity = crate::velocity::open(lua, engine)?;
         let math = crate::math::open(lua, engine)?;
+        let timeline = crate::timeline::open(lua, engine)?;
+        let z_order = crate::z_order::open(lua, engine)?;
 
         Ok(lua
             .load(mlua::chunk! {
                 {
+                    camera = $camera,
                     collision = $collision,
                     graphics = $graphics,
+                    joints = $joints,
                     keyboard = $keyboard,
+                    lifetime = $lifetime,
                     math = $math,
                     position = $position,
+                    proximity = $proximity,
+                    substep = $substep,
+                    timeline = $timeline,
                     velocity = $velocity,
+                    z_order = $z_order,
                 }
             })
             .eval()?)
@@ -0,0 +1,112 @@
+use hv_core::{
+    components::DynamicComponentConstructor,
+    engine::Engine,
+    prelude::*,
+    spaces::{command::CommandBuffer, serialize, Object, Space, SpaceCache},
+};
+use serde::*;
+
+/// Despawns the object it's attached to once `remaining` runs out, when ticked by
+/// [`tick_lifetimes`].
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Lifetime {
+    /// Seconds left until the object is despawned.
+    pub remaining: f32,
+}
+
+impl Lifetime {
+    pub fn new(seconds: f32) -> Self {
+        Self { remaining: seconds }
+    }
+}
+
+hv_core::serializable!(serialize::with_serde::<Lifetime>("friends.Lifetime"));
+hv_core::component_type!("Lifetime", Lifetime);
+
+impl LuaUserData for Lifetime {}
+
+/// Count down every [`Lifetime`] in the space by `dt`, and despawn the objects whose lifetimes have
+/// run out. Despawns are queued while the lifetimes are being updated and only run afterwards, so
+/// no query is active when they happen.
+///
+/// Returns the despawned objects, so that the caller can react to them (spawning a death effect
+/// or similar.) They're no longer in the space by then, so any components needed to react to them
+/// have to be fetched ahead of time.
+pub fn tick_lifetimes(space: &mut Space, dt: f32) -> Result<Vec<Object>> {
+    let mut commands = CommandBuffer::new();
+    let mut expired = Vec::new();
+
+    for (object, lifetime) in space.query_mut::<&mut Lifetime>() {
+        lifetime.remaining -= dt;
+        if lifetime.remaining <= 0. {
+            commands.despawn(object);
+            expired.push(object);
+        }
+    }
+
+    commands.run(space)?;
+
+    Ok(expired)
+}
+
+pub(crate) fn open<'lua>(lua: &'lua Lua, engine: &Engine) -> Result<LuaTable<'lua>, Error> {
+    let create_lifetime_constructor = lua.create_function(|_, seconds: f32| {
+        Ok(DynamicComponentConstructor::copy(Lifetime::new(seconds)))
+    })?;
+
+    let mut space_cache = SpaceCache::new(engine);
+    let get_remaining = lua.create_function_mut(move |_, obj: Object| {
+        let space = space_cache.get_space(obj.space());
+        let remaining = space.borrow().get::<Lifetime>(obj).to_lua_err()?.remaining;
+        Ok(remaining)
+    })?;
+
+    let mut space_cache = SpaceCache::new(engine);
+    let set_remaining = lua.create_function_mut(move |_, (obj, remaining): (Object, f32)| {
+        let space = space_cache.get_space(obj.space());
+        space
+            .borrow()
+            .get_mut::<Lifetime>(obj)
+            .to_lua_err()?
+            .remaining = remaining;
+        Ok(())
+    })?;
+
+    let tick_lifetimes = lua.create_function(|_, (space, dt): (Shared<Space>, f32)| {
+        tick_lifetimes(&mut space.borrow_mut(), dt).to_lua_err()
+    })?;
+
+    Ok(lua
+        .load(mlua::chunk! {
+            {
+                create_lifetime_constructor = $create_lifetime_constructor,
+                get_remaining = $get_remaining,
+                set_remaining = $set_remaining,
+                tick_lifetimes = $tick_lifetimes,
+            }
+        })
+        .eval()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hv_core::spaces::Spaces;
+
+    #[test]
+    fn expired_objects_are_despawned() {
+        let space = Spaces::new().create_space();
+        let mut space = space.borrow_mut();
+        let short = space.spawn((Lifetime::new(0.1),));
+        let long = space.spawn((Lifetime::new(0.25),));
+        let forever = space.spawn(());
+
+        let mut despawned = tick_lifetimes(&mut space, 0.1).unwrap();
+        despawned.extend(tick_lifetimes(&mut space, 0.1).unwrap());
+
+        assert_eq!(despawned, [short]);
+        assert!(!space.contains(short));
+        assert!(space.contains(long));
+        assert!(space.contains(forever));
+    }
+}
@@ -0,0 +1,280 @@
+use hv_core::{
+    engine::{Engine, EventHandler},
+    input::{GamepadAxis, GamepadButton, KeyCode, KeyMods, MouseButton, TouchPhase},
+    prelude::*,
+};
+
+use crate::SimpleHandler;
+
+/// An event handler for games written entirely in Lua. It does everything a [`SimpleHandler`]
+/// does, and also forwards every input and window event to the Lua function of the same name in
+/// the `hv` table, if there is one:
+///
+/// - `hv.key_down(key, mods, is_repeat)` and `hv.key_up(key, mods)`
+/// - `hv.char(character, mods, is_repeat)`
+/// - `hv.mouse_motion(x, y)` and `hv.mouse_wheel(dx, dy)`
+/// - `hv.mouse_button_down(button, x, y)` and `hv.mouse_button_up(button, x, y)`
+/// - `hv.gamepad_button_down(button, is_repeat)` and `hv.gamepad_button_up(button)`
+/// - `hv.gamepad_axis_changed(axis, position)`
+/// - `hv.gamepad_connected()` and `hv.gamepad_disconnected()`
+/// - `hv.resize(width, height)`
+/// - `hv.touch(phase, id, x, y)`
+/// - `hv.focus(focused)`
+/// - `hv.quit_requested()`
+///
+/// Keys, buttons, axes and touch phases are passed as the names of their Rust enum variants, e.g.
+/// `"Space"`, `"Left"`, `"South"`, `"LeftStickX"` or `"Started"`, which are the same names
+/// `hf.keyboard.is_down` accepts. Key modifiers are passed as a table with boolean `shift`, `ctrl`,
+/// `alt` and `logo` fields, and characters as one-character strings.
+///
+/// Events don't return errors, so an error raised by one of these functions is logged rather than
+/// propagated.
+pub struct LuaHandler {
+    simple: SimpleHandler,
+}
+
+impl LuaHandler {
+    /// Create a new `LuaHandler` which loads the given module as its "main" Lua entrypoint,
+    /// updating at miniquad's fixed rate of 60 steps per second.
+    pub fn new(s: impl AsRef<str>) -> Self {
+        Self {
+            simple: SimpleHandler::new(s),
+        }
+    }
+
+    /// Create a new `LuaHandler` which loads the given module as its "main" Lua entrypoint,
+    /// updating with a fixed step of `step` seconds.
+    pub fn with_fixed_timestep(s: impl AsRef<str>, step: f32) -> Self {
+        Self {
+            simple: SimpleHandler::with_fixed_timestep(s, step),
+        }
+    }
+
+    /// The [`SimpleHandler`] this handler runs the game loop with.
+    pub fn simple(&self) -> &SimpleHandler {
+        &self.simple
+    }
+
+    fn forward(&self, engine: &Engine, event: LuaEvent) {
+        let name = event.hook_name();
+        if let Err(err) = event.call(&engine.lua()) {
+            log::error!("error in hv.{}: {:?}", name, err);
+        }
+    }
+}
+
+/// An [`EventHandler`] event, as passed along to Lua.
+#[derive(Debug, Clone, Copy)]
+enum LuaEvent {
+    KeyDown(KeyCode, KeyMods, bool),
+    KeyUp(KeyCode, KeyMods),
+    Char(char, KeyMods, bool),
+    MouseMotion(f32, f32),
+    MouseWheel(f32, f32),
+    MouseButtonDown(MouseButton, f32, f32),
+    MouseButtonUp(MouseButton, f32, f32),
+    GamepadButtonDown(GamepadButton, bool),
+    GamepadButtonUp(GamepadButton),
+    GamepadAxisChanged(GamepadAxis, f32),
+    GamepadConnected,
+    GamepadDisconnected,
+    Resize(f32, f32),
+    Touch(TouchPhase, u64, f32, f32),
+    Focus(bool),
+    QuitRequested,
+}
+
+impl LuaEvent {
+    fn hook_name(&self) -> &'static str {
+        match self {
+            Self::KeyDown(..) => "key_down",
+            Self::KeyUp(..) => "key_up",
+            Self::Char(..) => "char",
+            Self::MouseMotion(..) => "mouse_motion",
+            Self::MouseWheel(..) => "mouse_wheel",
+            Self::MouseButtonDown(..) => "mouse_button_down",
+            Self::MouseButtonUp(..) => "mouse_button_up",
+            Self::GamepadButtonDown(..) => "gamepad_button_down",
+            Self::GamepadButtonUp(..) => "gamepad_button_up",
+            Self::GamepadAxisChanged(..) => "gamepad_axis_changed",
+            Self::GamepadConnected => "gamepad_connected",
+            Self::GamepadDisconnected => "gamepad_disconnected",
+            Self::Resize(..) => "resize",
+            Self::Touch(..) => "touch",
+            Self::Focus(..) => "focus",
+            Self::QuitRequested => "quit_requested",
+        }
+    }
+
+    /// Call the `hv` function for this event, if it's defined.
+    fn call(self, lua: &Lua) -> Result<()> {
+        let hv = lua.globals().get::<_, LuaTable>("hv")?;
+        let hook = match hv.get::<_, Option<LuaFunction>>(self.hook_name())? {
+            Some(hook) => hook,
+            None => return Ok(()),
+        };
+
+        match self {
+            Self::KeyDown(key, keymods, repeat) => {
+                hook.call::<_, ()>((<&str>::from(key), mods(lua, keymods)?, repeat))?
+            }
+            Self::KeyUp(key, keymods) => {
+                hook.call::<_, ()>((<&str>::from(key), mods(lua, keymods)?))?
+            }
+            Self::Char(character, keymods, repeat) => {
+                hook.call::<_, ()>((character.to_string(), mods(lua, keymods)?, repeat))?
+            }
+            Self::MouseMotion(x, y) | Self::MouseWheel(x, y) | Self::Resize(x, y) => {
+                hook.call::<_, ()>((x, y))?
+            }
+            Self::MouseButtonDown(button, x, y) | Self::MouseButtonUp(button, x, y) => {
+                hook.call::<_, ()>((<&str>::from(button), x, y))?
+            }
+            Self::GamepadButtonDown(button, repeat) => {
+                hook.call::<_, ()>((<&str>::from(button), repeat))?
+            }
+            Self::GamepadButtonUp(button) => hook.call::<_, ()>(<&str>::from(button))?,
+            Self::GamepadAxisChanged(axis, position) => {
+                hook.call::<_, ()>((<&str>::from(axis), position))?
+            }
+            Self::Touch(phase, id, x, y) => hook.call::<_, ()>((<&str>::from(phase), id, x, y))?,
+            Self::Focus(focused) => hook.call::<_, ()>(focused)?,
+            Self::GamepadConnected | Self::GamepadDisconnected | Self::QuitRequested => {
+                hook.call::<_, ()>(())?
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn mods(lua: &Lua, keymods: KeyMods) -> LuaResult<LuaTable> {
+    let table = lua.create_table()?;
+    table.set("shift", keymods.shift)?;
+    table.set("ctrl", keymods.ctrl)?;
+    table.set("alt", keymods.alt)?;
+    table.set("logo", keymods.logo)?;
+    Ok(table)
+}
+
+impl EventHandler for LuaHandler {
+    fn init(&mut self, engine: &Engine) -> Result<()> {
+        self.simple.init(engine)
+    }
+
+    fn update(&mut self, engine: &Engine, dt: f32) -> Result<()> {
+        self.simple.update(engine, dt)
+    }
+
+    fn draw(&mut self, engine: &Engine) -> Result<()> {
+        self.simple.draw(engine)
+    }
+
+    fn key_down_event(
+        &mut self,
+        engine: &Engine,
+        keycode: KeyCode,
+        keymods: KeyMods,
+        repeat: bool,
+    ) {
+        self.simple.key_down_event(engine, keycode, keymods, repeat);
+        self.forward(engine, LuaEvent::KeyDown(keycode, keymods, repeat));
+    }
+
+    fn key_up_event(&mut self, engine: &Engine, keycode: KeyCode, keymods: KeyMods) {
+        self.simple.key_up_event(engine, keycode, keymods);
+        self.forward(engine, LuaEvent::KeyUp(keycode, keymods));
+    }
+
+    fn char_event(&mut self, engine: &Engine, character: char, keymods: KeyMods, repeat: bool) {
+        self.forward(engine, LuaEvent::Char(character, keymods, repeat));
+    }
+
+    fn mouse_motion_event(&mut self, engine: &Engine, x: f32, y: f32) {
+        self.forward(engine, LuaEvent::MouseMotion(x, y));
+    }
+
+    fn mouse_wheel_event(&mut self, engine: &Engine, x: f32, y: f32) {
+        self.forward(engine, LuaEvent::MouseWheel(x, y));
+    }
+
+    fn mouse_button_down_event(&mut self, engine: &Engine, button: MouseButton, x: f32, y: f32) {
+        self.forward(engine, LuaEvent::MouseButtonDown(button, x, y));
+    }
+
+    fn mouse_button_up_event(&mut self, engine: &Engine, button: MouseButton, x: f32, y: f32) {
+        self.forward(engine, LuaEvent::MouseButtonUp(button, x, y));
+    }
+
+    fn gamepad_button_down_event(&mut self, engine: &Engine, button: GamepadButton, repeat: bool) {
+        self.forward(engine, LuaEvent::GamepadButtonDown(button, repeat));
+    }
+
+    fn gamepad_button_up_event(&mut self, engine: &Engine, button: GamepadButton) {
+        self.forward(engine, LuaEvent::GamepadButtonUp(button));
+    }
+
+    fn gamepad_axis_changed_event(&mut self, engine: &Engine, axis: GamepadAxis, position: f32) {
+        self.forward(engine, LuaEvent::GamepadAxisChanged(axis, position));
+    }
+
+    fn gamepad_connected_event(&mut self, engine: &Engine) {
+        self.forward(engine, LuaEvent::GamepadConnected);
+    }
+
+    fn gamepad_disconnected_event(&mut self, engine: &Engine) {
+        self.forward(engine, LuaEvent::GamepadDisconnected);
+    }
+
+    fn resize_event(&mut self, engine: &Engine, width: f32, height: f32) {
+        self.forward(engine, LuaEvent::Resize(width, height));
+    }
+
+    fn touch_event(&mut self, engine: &Engine, phase: TouchPhase, id: u64, x: f32, y: f32) {
+        self.forward(engine, LuaEvent::Touch(phase, id, x, y));
+    }
+
+    fn focus_event(&mut self, engine: &Engine, focused: bool) {
+        self.simple.focus_event(engine, focused);
+        self.forward(engine, LuaEvent::Focus(focused));
+    }
+
+    fn quit_requested_event(&mut self, engine: &Engine) {
+        self.forward(engine, LuaEvent::QuitRequested);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_down_receives_the_key_name() -> Result<()> {
+        let lua = Lua::new();
+        lua.load(mlua::chunk! {
+            hv = {}
+            function hv.key_down(key, mods, is_repeat)
+                received = { key = key, shift = mods.shift, ctrl = mods.ctrl, is_repeat = is_repeat }
+            end
+        })
+        .exec()?;
+
+        let keymods = KeyMods {
+            shift: true,
+            ..KeyMods::default()
+        };
+        LuaEvent::KeyDown(KeyCode::Space, keymods, true).call(&lua)?;
+        lua.load(mlua::chunk! {
+            assert(received.key == "Space")
+            assert(received.shift == true and received.ctrl == false)
+            assert(received.is_repeat == true)
+        })
+        .exec()?;
+
+        // Events with no function in `hv` are ignored.
+        LuaEvent::KeyUp(KeyCode::Space, keymods).call(&lua)?;
+        LuaEvent::Resize(640., 480.).call(&lua)?;
+
+        Ok(())
+    }
+}
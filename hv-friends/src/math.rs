@@ -133,7 +133,7 @@ impl<N: RealField + Copy + for<'lua> FromLua<'lua> + for<'lua> ToLua<'lua>> Posi
 pub struct Velocity2<N: RealField + Copy> {
     /// The linear velocity.
     pub linear: Vector2<N>,
-    /// The angular velocity.
+    /// The angular velocity, in radians per unit of time.
     pub angular: N,
 }
 
@@ -663,6 +663,17 @@ impl<T: RealField + Copy + for<'lua> ToLua<'lua> + for<'lua> FromLua<'lua>> LuaU
         simple_mut(methods, "inverse_transform_mut", |t, tx: Tx<T>| {
             *t = tx.inverse_transform_position2(t)
         });
+
+        // Angles are in radians and angular velocities in radians per second, so integrating by a
+        // `dt` in seconds gives the position after that much time has passed.
+        methods.add_method("integrate", |_, this, (velocity, dt): (Velocity2<T>, T)| {
+            Ok(this.integrate(&velocity, dt))
+        });
+
+        methods.add_method_mut("integrate_mut", |_, this, (vel, dt): (Velocity2<T>, T)| {
+            this.integrate_mut(&vel, dt);
+            Ok(())
+        });
     }
 }
 
@@ -702,6 +713,11 @@ impl<T: RealField + Copy + for<'lua> ToLua<'lua> + for<'lua> FromLua<'lua>> LuaU
             this.angular += angular;
             Ok(())
         });
+
+        simple(methods, "get_linear", |t, ()| (t.linear.x, t.linear.y));
+        simple(methods, "get_angular", |t, ()| t.angular);
+
+        simple_mut(methods, "scale", |t, factor: T| *t *= factor);
     }
 }
 
@@ -757,3 +773,42 @@ pub(crate) fn open<'lua>(lua: &'lua Lua, _engine: &Engine) -> Result<LuaTable<'l
         })
         .eval()?)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lua_integrates_position_with_angular_velocity() -> Result<()> {
+        let lua = Lua::new();
+        lua.globals()
+            .set("position", Position2::<f32>::translation(1., 2.))?;
+        lua.globals()
+            .set("velocity", Velocity2::<f32>::linear(0., 0.))?;
+
+        lua.load(
+            r#"
+            velocity:set_linear(4, -2)
+            velocity:add_angular(math.pi)
+            local x, y = velocity:get_linear()
+            assert(x == 4 and y == -2)
+            assert(velocity:get_angular() == math.pi)
+
+            -- Half a second at pi radians per second is a quarter turn.
+            local moved = position:integrate(velocity, 0.5)
+            assert(math.abs(moved.x - 3) < 1e-5 and math.abs(moved.y - 1) < 1e-5)
+            assert(math.abs(moved.angle - math.pi / 2) < 1e-5)
+            assert(position.x == 1 and position.angle == 0)
+
+            position:integrate_mut(velocity, 0.25)
+            position:integrate_mut(velocity, 0.25)
+            assert(math.abs(position.x - moved.x) < 1e-5)
+            assert(math.abs(position.y - moved.y) < 1e-5)
+            assert(math.abs(position.angle - moved.angle) < 1e-5)
+            "#,
+        )
+        .exec()?;
+
+        Ok(())
+    }
+}
@@ -12,6 +12,7 @@ use crate::math::Position2;
 pub struct Position(pub Position2<f32>);
 
 hv_core::serializable!(serialize::with_serde::<Position>("friends.Position"));
+hv_core::component_type!("Position", Position);
 
 impl LuaUserData for Position {}
 
@@ -0,0 +1,184 @@
+//! Queries for finding positioned objects near a point or inside a region, such as every enemy
+//! within range of a turret or every pickup under a selection box.
+//!
+//! There's no spatial index over [`Position`]s yet, so these are brute-force queries which visit
+//! every positioned object in the space. That's fine for the object counts most spaces have; if it
+//! ever stops being fine, these are the functions to teach about a spatial hash.
+
+use std::vec;
+
+use hv_core::{
+    engine::Engine,
+    prelude::*,
+    spaces::{object_table::ObjectTableComponent, Object, Space},
+};
+
+use crate::{math::*, Position};
+
+/// Proximity queries over objects with a [`Position`] component.
+pub trait SpaceProximityExt {
+    /// Find every object whose position is within `radius` of `center` (inclusive) and for which
+    /// `filter` returns `true`, along with its distance from `center`. Objects are returned nearest
+    /// first; objects at the same distance are ordered by their slot in the space, so the order is
+    /// the same from frame to frame.
+    fn objects_in_radius(
+        &self,
+        center: Point2<f32>,
+        radius: f32,
+        filter: impl FnMut(Object) -> bool,
+    ) -> vec::IntoIter<(Object, f32)>;
+
+    /// Find every object whose position lies inside `bounds` (edges included) and for which
+    /// `filter` returns `true`, ordered by their slot in the space.
+    fn objects_in_box(
+        &self,
+        bounds: &Box2<f32>,
+        filter: impl FnMut(Object) -> bool,
+    ) -> vec::IntoIter<Object>;
+}
+
+impl SpaceProximityExt for Space {
+    fn objects_in_radius(
+        &self,
+        center: Point2<f32>,
+        radius: f32,
+        mut filter: impl FnMut(Object) -> bool,
+    ) -> vec::IntoIter<(Object, f32)> {
+        let radius_squared = radius * radius;
+        let mut found = self
+            .query::<&Position>()
+            .iter()
+            .filter_map(|(object, Position(pos))| {
+                let distance_squared = na::distance_squared(&center, &pos.center());
+                (distance_squared <= radius_squared).then(|| (object, distance_squared))
+            })
+            .collect::<Vec<_>>();
+
+        found.retain(|&(object, _)| filter(object));
+        found.sort_unstable_by(|&(a, da), &(b, db)| {
+            da.partial_cmp(&db)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then(a.slot().cmp(&b.slot()))
+        });
+
+        for (_, distance) in &mut found {
+            *distance = distance.sqrt();
+        }
+
+        found.into_iter()
+    }
+
+    fn objects_in_box(
+        &self,
+        bounds: &Box2<f32>,
+        mut filter: impl FnMut(Object) -> bool,
+    ) -> vec::IntoIter<Object> {
+        let mut found = self
+            .query::<&Position>()
+            .iter()
+            .filter(|(_, Position(pos))| {
+                let p = pos.center();
+                (bounds.mins.x..=bounds.maxs.x).contains(&p.x)
+                    && (bounds.mins.y..=bounds.maxs.y).contains(&p.y)
+            })
+            .map(|(object, _)| object)
+            .collect::<Vec<_>>();
+
+        found.retain(|&object| filter(object));
+        found.sort_unstable_by_key(|object| object.slot());
+        found.into_iter()
+    }
+}
+
+/// Only objects with object tables can be handed to Lua, so the Lua-facing queries skip the rest.
+fn has_object_table(space: &Space, object: Object) -> bool {
+    space
+        .query_one::<&ObjectTableComponent>(object)
+        .map_or(false, |mut q| q.get().is_some())
+}
+
+pub(crate) fn open<'lua>(lua: &'lua Lua, _engine: &Engine) -> Result<LuaTable<'lua>, Error> {
+    let objects_in_radius = lua.create_function(
+        |lua, (space, x, y, radius): (Shared<Space>, f32, f32, f32)| {
+            let space = space.borrow();
+            let found = space
+                .objects_in_radius(Point2::new(x, y), radius, |object| {
+                    has_object_table(&space, object)
+                })
+                .collect::<Vec<_>>();
+
+            let objects = lua.create_sequence_from(found.iter().map(|&(object, _)| object))?;
+            let distances = lua.create_sequence_from(found.iter().map(|&(_, d)| d))?;
+            Ok((objects, distances))
+        },
+    )?;
+
+    let objects_in_box =
+        lua.create_function(|lua, (space, bounds): (Shared<Space>, Box2<f32>)| {
+            let space = space.borrow();
+            let found = space.objects_in_box(&bounds, |object| has_object_table(&space, object));
+            lua.create_sequence_from(found)
+        })?;
+
+    Ok(lua
+        .load(mlua::chunk! {
+            {
+                objects_in_radius = $objects_in_radius,
+                objects_in_box = $objects_in_box,
+            }
+        })
+        .eval()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hv_core::spaces::Spaces;
+
+    #[derive(Debug, Clone, Copy)]
+    struct Unpositioned;
+
+    fn at(x: f32, y: f32) -> (Position,) {
+        (Position(Position2::translation(x, y)),)
+    }
+
+    #[test]
+    fn radius_query_includes_nearby_objects_nearest_first() {
+        let space = Spaces::new().create_space();
+        let mut space = space.borrow_mut();
+        let far = space.spawn(at(10., 0.));
+        let edge = space.spawn(at(0., 5.));
+        let near = space.spawn(at(3., 0.));
+        let behind = space.spawn(at(-1., -1.));
+        space.spawn(at(4., 4.));
+
+        let found = space
+            .objects_in_radius(Point2::origin(), 5., |_| true)
+            .collect::<Vec<_>>();
+        let objects = found.iter().map(|&(obj, _)| obj).collect::<Vec<_>>();
+        assert_eq!(objects, [behind, near, edge]);
+        assert_eq!(found[1].1, 3.);
+        assert!(!objects.contains(&far));
+
+        let filtered = space
+            .objects_in_radius(Point2::origin(), 5., |obj| obj != near)
+            .map(|(obj, _)| obj)
+            .collect::<Vec<_>>();
+        assert_eq!(filtered, [behind, edge]);
+    }
+
+    #[test]
+    fn box_query_includes_edges() {
+        let space = Spaces::new().create_space();
+        let mut space = space.borrow_mut();
+        let inside = space.spawn(at(1., 1.));
+        let corner = space.spawn(at(2., 2.));
+        space.spawn(at(3., 1.));
+        space.spawn((Unpositioned,));
+
+        let found = space
+            .objects_in_box(&Box2::new(0., 0., 2., 2.), |_| true)
+            .collect::<Vec<_>>();
+        assert_eq!(found, [inside, corner]);
+    }
+}
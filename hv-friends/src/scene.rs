@@ -16,6 +16,12 @@
 //! This is basically identical in concept to the Amethyst engine's scene
 //! system, the only difference is the details of how the pieces are put
 //! together.
+//!
+//! Each [`DynamicScene`] also carries its own [`SceneLocals`], a resource map
+//! much like the one on [`Engine`] but scoped to that one scene. Locals are
+//! handed to the scene in its `update`, `draw`, and `event` callbacks, are
+//! never visible to the scenes above or below it, and are dropped along with
+//! the scene when it's popped.
 
 use hv_core::{
     engine::{Engine, EngineRef, EventHandler},
@@ -47,19 +53,79 @@ use hv_core::{
  * SOFTWARE.
  */
 
-use std::{borrow::Cow, fmt};
+use std::{
+    any::{Any, TypeId},
+    borrow::Cow,
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+/// A map of resources local to a single scene, keyed by type. See the [module-level
+/// documentation](self).
+#[derive(Default)]
+pub struct SceneLocals {
+    resources: Mutex<HashMap<TypeId, Box<dyn Any + Send + Sync>>>,
+}
+
+impl fmt::Debug for SceneLocals {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SceneLocals").finish()
+    }
+}
+
+impl SceneLocals {
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-pub struct DynamicScene<C, Ev>(Shared<dyn Scene<C, Ev>>);
+    /// Insert a local resource, replacing any existing local of the same type, and return a shared
+    /// reference to it.
+    pub fn insert<T: Send + Sync + 'static>(&self, resource: T) -> Shared<T> {
+        let shared = Shared::new(resource);
+        self.resources
+            .lock()
+            .unwrap()
+            .insert(TypeId::of::<T>(), Box::new(shared.clone()));
+        shared
+    }
+
+    /// Get a local resource by type, if one has been inserted.
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<Shared<T>> {
+        self.resources
+            .lock()
+            .unwrap()
+            .get(&TypeId::of::<T>())
+            .map(|entry| entry.downcast_ref::<Shared<T>>().unwrap().clone())
+    }
+
+    /// Remove a local resource by type, returning it if it was present.
+    pub fn remove<T: Send + Sync + 'static>(&self) -> Option<Shared<T>> {
+        self.resources
+            .lock()
+            .unwrap()
+            .remove(&TypeId::of::<T>())
+            .map(|entry| *entry.downcast::<Shared<T>>().unwrap())
+    }
+}
+
+pub struct DynamicScene<C, Ev> {
+    scene: Shared<dyn Scene<C, Ev>>,
+    locals: Arc<SceneLocals>,
+}
 
 impl<C, Ev> Clone for DynamicScene<C, Ev> {
     fn clone(&self) -> Self {
-        Self(self.0.clone())
+        Self {
+            scene: self.scene.clone(),
+            locals: self.locals.clone(),
+        }
     }
 }
 
 impl<C: 'static, Ev: 'static> fmt::Debug for DynamicScene<C, Ev> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let borrowed = self.0.borrow();
+        let borrowed = self.scene.borrow();
         let name = borrowed.name();
         f.debug_tuple("DynamicScene").field(&name).finish()
     }
@@ -70,49 +136,79 @@ impl<C, Ev> DynamicScene<C, Ev> {
     where
         T: Scene<C, Ev> + 'static,
     {
-        Self(Shared::new(scene))
+        Self {
+            scene: Shared::new(scene),
+            locals: Arc::new(SceneLocals::new()),
+        }
+    }
+
+    /// Insert a resource local to this scene. See [`SceneLocals::insert`].
+    pub fn insert_local<T: Send + Sync + 'static>(&self, resource: T) -> Shared<T> {
+        self.locals.insert(resource)
+    }
+
+    /// Get a resource local to this scene. See [`SceneLocals::get`].
+    pub fn get_local<T: Send + Sync + 'static>(&self) -> Option<Shared<T>> {
+        self.locals.get()
     }
 
     fn map_mut_inner<F, R>(&mut self, f: F) -> R
     where
-        F: FnOnce(&mut dyn Scene<C, Ev>) -> R,
+        F: FnOnce(&mut dyn Scene<C, Ev>, &SceneLocals) -> R,
     {
-        match self.0.get_mut() {
-            Some(m) => f(m),
-            None => f(&mut *self.0.borrow_mut()),
+        let locals = &self.locals;
+        match self.scene.get_mut() {
+            Some(m) => f(m, locals),
+            None => f(&mut *self.scene.borrow_mut(), locals),
         }
     }
 }
 
 impl<C: 'static, Ev: 'static> Scene<C, Ev> for DynamicScene<C, Ev> {
-    fn update(&mut self, scene_stack: &mut SceneStack<C, Ev>, ctx: &mut C) -> Result<()> {
-        self.map_mut_inner(|s| s.update(scene_stack, ctx))
+    /// A [`DynamicScene`] always runs its inner scene with its own locals; the `locals` passed in
+    /// belong to the enclosing scene and are ignored.
+    fn update(
+        &mut self,
+        scene_stack: &mut SceneStack<C, Ev>,
+        _locals: &SceneLocals,
+        ctx: &mut C,
+    ) -> Result<()> {
+        self.map_mut_inner(|s, locals| s.update(scene_stack, locals, ctx))
     }
 
-    fn draw(&mut self, ctx: &mut C) -> Result<()> {
-        self.map_mut_inner(|s| s.draw(ctx))
+    fn draw(&mut self, _locals: &SceneLocals, ctx: &mut C) -> Result<()> {
+        self.map_mut_inner(|s, locals| s.draw(locals, ctx))
     }
 
-    fn event(&mut self, ctx: &mut C, event: Ev) -> Result<()> {
-        self.map_mut_inner(|s| s.event(ctx, event))
+    fn event(&mut self, _locals: &SceneLocals, ctx: &mut C, event: Ev) -> Result<()> {
+        self.map_mut_inner(|s, locals| s.event(locals, ctx, event))
     }
 
     fn name(&self) -> Option<Cow<'_, str>> {
-        self.0.borrow().name().map(|cow| cow.into_owned().into())
+        self.scene
+            .borrow()
+            .name()
+            .map(|cow| cow.into_owned().into())
     }
 
     fn draw_previous(&self) -> bool {
-        self.0.borrow().draw_previous()
+        self.scene.borrow().draw_previous()
     }
 }
 
 /// A trait for you to implement on a scene.
 /// Defines the callbacks the scene uses:
 /// a common context type `C`, and an input event type `Ev`.
+/// Each callback also receives the scene's own [`SceneLocals`].
 pub trait Scene<C, Ev>: Send + Sync + 'static {
-    fn update(&mut self, scene_stack: &mut SceneStack<C, Ev>, ctx: &mut C) -> Result<()>;
-    fn draw(&mut self, ctx: &mut C) -> Result<()>;
-    fn event(&mut self, ctx: &mut C, event: Ev) -> Result<()>;
+    fn update(
+        &mut self,
+        scene_stack: &mut SceneStack<C, Ev>,
+        locals: &SceneLocals,
+        ctx: &mut C,
+    ) -> Result<()>;
+    fn draw(&mut self, locals: &SceneLocals, ctx: &mut C) -> Result<()>;
+    fn event(&mut self, locals: &SceneLocals, ctx: &mut C, event: Ev) -> Result<()>;
     /// Only used for human-readable convenience (or not at all, tbh)
     fn name(&self) -> Option<Cow<'_, str>> {
         None
@@ -149,7 +245,8 @@ where
         Self { scenes: Vec::new() }
     }
 
-    /// Add a new scene to the top of the stack.
+    /// Add a new scene to the top of the stack. Its locals are dropped once it has been popped and
+    /// every other clone of it is gone.
     pub fn push(&mut self, scene: DynamicScene<C, Ev>) {
         self.scenes.push(scene)
     }
@@ -183,7 +280,8 @@ where
     // update() on the current scene it causes a double-borrow.  :/
     pub fn update(&mut self, ctx: &mut C) -> Result<()> {
         if let Some(mut current_scene) = self.scenes.last().cloned() {
-            current_scene.update(self, ctx)?;
+            let locals = current_scene.locals.clone();
+            current_scene.update(self, &locals, ctx)?;
         }
 
         Ok(())
@@ -198,7 +296,8 @@ where
             if current.draw_previous() {
                 SceneStack::draw_scenes(rest, ctx)?;
             }
-            current.draw(ctx)
+            let locals = current.locals.clone();
+            current.draw(&locals, ctx)
         } else {
             Ok(())
         }
@@ -212,7 +311,8 @@ where
     /// Feeds the given event to the current scene.
     pub fn event(&mut self, ctx: &mut C, event: Ev) -> Result<()> {
         if let Some(current_scene) = self.scenes.last_mut() {
-            current_scene.event(ctx, event)?;
+            let locals = current_scene.locals.clone();
+            current_scene.event(&locals, ctx, event)?;
         }
 
         Ok(())
@@ -298,6 +398,7 @@ where
     fn update(
         &mut self,
         scene_stack: &mut SceneStack<EngineRef, EngineEvent>,
+        _locals: &SceneLocals,
         ctx: &mut EngineRef,
     ) -> Result<()> {
         // Get ourselves off the stack.
@@ -308,26 +409,36 @@ where
         )
     }
 
-    fn draw(&mut self, _ctx: &mut EngineRef) -> Result<()> {
+    fn draw(&mut self, _locals: &SceneLocals, _ctx: &mut EngineRef) -> Result<()> {
         Ok(())
     }
 
-    fn event(&mut self, _ctx: &mut EngineRef, _event: EngineEvent) -> Result<()> {
+    fn event(
+        &mut self,
+        _locals: &SceneLocals,
+        _ctx: &mut EngineRef,
+        _event: EngineEvent,
+    ) -> Result<()> {
         Ok(())
     }
 }
 
 impl<C, E, T: Scene<C, E>> Scene<C, E> for Shared<T> {
-    fn update(&mut self, scene_stack: &mut SceneStack<C, E>, ctx: &mut C) -> Result<()> {
-        self.borrow_mut().update(scene_stack, ctx)
+    fn update(
+        &mut self,
+        scene_stack: &mut SceneStack<C, E>,
+        locals: &SceneLocals,
+        ctx: &mut C,
+    ) -> Result<()> {
+        self.borrow_mut().update(scene_stack, locals, ctx)
     }
 
-    fn draw(&mut self, ctx: &mut C) -> Result<()> {
-        self.borrow_mut().draw(ctx)
+    fn draw(&mut self, locals: &SceneLocals, ctx: &mut C) -> Result<()> {
+        self.borrow_mut().draw(locals, ctx)
     }
 
-    fn event(&mut self, ctx: &mut C, event: E) -> Result<()> {
-        self.borrow_mut().event(ctx, event)
+    fn event(&mut self, locals: &SceneLocals, ctx: &mut C, event: E) -> Result<()> {
+        self.borrow_mut().event(locals, ctx, event)
     }
 }
 
@@ -419,3 +530,75 @@ impl SceneStack<EngineRef, EngineEvent> {
         this
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Counter(u32);
+
+    /// Records the value of its `Counter` local into the context on every update and draw.
+    struct RecordingScene {
+        draw_previous: bool,
+    }
+
+    impl Scene<Vec<u32>, ()> for RecordingScene {
+        fn update(
+            &mut self,
+            _scene_stack: &mut SceneStack<Vec<u32>, ()>,
+            locals: &SceneLocals,
+            ctx: &mut Vec<u32>,
+        ) -> Result<()> {
+            let counter = locals.get::<Counter>().unwrap();
+            counter.borrow_mut().0 += 1;
+            ctx.push(counter.borrow().0);
+            Ok(())
+        }
+
+        fn draw(&mut self, locals: &SceneLocals, ctx: &mut Vec<u32>) -> Result<()> {
+            ctx.push(locals.get::<Counter>().unwrap().borrow().0);
+            Ok(())
+        }
+
+        fn event(&mut self, _locals: &SceneLocals, _ctx: &mut Vec<u32>, _event: ()) -> Result<()> {
+            Ok(())
+        }
+
+        fn draw_previous(&self) -> bool {
+            self.draw_previous
+        }
+    }
+
+    #[test]
+    fn scene_locals_are_isolated() {
+        let mut stack = SceneStack::new();
+
+        let below = DynamicScene::new(RecordingScene {
+            draw_previous: false,
+        });
+        below.insert_local(Counter(10));
+        stack.push(below);
+
+        let above = DynamicScene::new(RecordingScene {
+            draw_previous: true,
+        });
+        let above_counter = above.insert_local(Counter(20)).downgrade();
+        stack.push(above);
+
+        let mut seen = Vec::new();
+        stack.update(&mut seen).unwrap();
+        stack.draw(&mut seen).unwrap();
+        // Only the top scene is updated; both are drawn, bottom first, each seeing its own local.
+        assert_eq!(seen, vec![21, 10, 21]);
+
+        // Popping the top scene drops its locals, and the scene below never saw them.
+        drop(stack.pop());
+        assert!(above_counter.try_upgrade().is_none());
+
+        seen.clear();
+        stack.update(&mut seen).unwrap();
+        assert_eq!(seen, vec![11]);
+        assert!(stack.current().get_local::<Counter>().is_some());
+        assert!(stack.current().get_local::<u32>().is_none());
+    }
+}
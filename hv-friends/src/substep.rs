@@ -0,0 +1,178 @@
+use hv_core::{
+    components::DynamicComponentConstructor,
+    engine::Engine,
+    prelude::*,
+    spaces::{serialize, Object, Space, SpaceCache},
+};
+use serde::*;
+
+use crate::{Position, Velocity};
+
+/// How many smaller steps an object's movement is split into each frame by
+/// [`integrate_substepped`]. Fast-moving or thin objects resolved against colliders once per frame
+/// can end up deep inside (or straight through) whatever they hit; resolving after each substep
+/// catches them earlier, without the cost of full continuous collision detection.
+///
+/// Objects without a `SubstepCount` use the default passed to [`integrate_substepped`], so a
+/// whole space can be substepped by changing the default and individual objects overridden by
+/// giving them this component.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubstepCount(pub u32);
+
+impl Default for SubstepCount {
+    fn default() -> Self {
+        SubstepCount(1)
+    }
+}
+
+impl SubstepCount {
+    /// The timesteps to integrate over to cover `dt`: `dt` split evenly into this many parts. A
+    /// count of zero is treated as one.
+    pub fn timesteps(self, dt: f32) -> impl Iterator<Item = f32> {
+        let count = self.0.max(1);
+        (0..count).map(move |_| dt / count as f32)
+    }
+}
+
+hv_core::serializable!(serialize::with_serde::<SubstepCount>(
+    "friends.SubstepCount"
+));
+hv_core::component_type!("SubstepCount", SubstepCount);
+
+impl LuaUserData for SubstepCount {}
+
+/// Integrate the position of every object with a [`Position`] and [`Velocity`] over `dt`, split
+/// into substeps according to each object's [`SubstepCount`] (or `default`, if it has none.)
+/// `resolve` is called after every substep, to push the object out of anything it's now
+/// overlapping and adjust its velocity.
+///
+/// Since `resolve` may run several times per frame for the same object, anything it reports, such
+/// as collision events, should be collected and deduplicated by the caller so that they're
+/// dispatched once per frame rather than once per substep.
+pub fn integrate_substepped(
+    space: &mut Space,
+    dt: f32,
+    default: SubstepCount,
+    mut resolve: impl FnMut(Object, &mut Position, &mut Velocity) -> Result<()>,
+) -> Result<()> {
+    for (object, (position, velocity, substeps)) in
+        space.query_mut::<(&mut Position, &mut Velocity, Option<&SubstepCount>)>()
+    {
+        for sub_dt in substeps.copied().unwrap_or(default).timesteps(dt) {
+            position.0.integrate_mut(&velocity.0, sub_dt);
+            resolve(object, position, velocity)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) fn open<'lua>(lua: &'lua Lua, engine: &Engine) -> Result<LuaTable<'lua>, Error> {
+    let create_substep_count_constructor = lua.create_function(|_, count: Option<u32>| {
+        Ok(DynamicComponentConstructor::copy(SubstepCount(
+            count.unwrap_or(1),
+        )))
+    })?;
+
+    let mut space_cache = SpaceCache::new(engine);
+    let get_substep_count = lua.create_function_mut(move |_, obj: Object| {
+        let space = space_cache.get_space(obj.space());
+        let count = space
+            .borrow()
+            .query_one::<Option<&SubstepCount>>(obj)
+            .to_lua_err()?
+            .get()
+            .and_then(|count| count.copied())
+            .unwrap_or_default();
+        Ok(count.0)
+    })?;
+
+    let mut space_cache = SpaceCache::new(engine);
+    let set_substep_count = lua.create_function_mut(move |_, (obj, count): (Object, u32)| {
+        let space = space_cache.get_space(obj.space());
+        space.borrow().get_mut::<SubstepCount>(obj).to_lua_err()?.0 = count;
+        Ok(())
+    })?;
+
+    Ok(lua
+        .load(mlua::chunk! {
+            {
+                create_substep_count_constructor = $create_substep_count_constructor,
+                get_substep_count = $get_substep_count,
+                set_substep_count = $set_substep_count,
+            }
+        })
+        .eval()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::*;
+    use hv_core::spaces::Spaces;
+
+    /// Fire an 8x8 box at a 4-pixel-thick wall at 30 pixels per frame, stopping it when it
+    /// overlaps the wall. Returns how far past the wall's near face the box's leading edge got.
+    fn max_penetration(substeps: SubstepCount) -> f32 {
+        let wall = Box2::new(20., 0., 4., 8.);
+        let space = Spaces::new().create_space();
+        let mut space = space.borrow_mut();
+        space.spawn((
+            Position(Position2::translation(0., 0.)),
+            Velocity(Velocity2::new(Vector2::new(1800., 0.), 0.)),
+        ));
+
+        let mut max_penetration = 0.;
+        let mut resolve =
+            |_: Object, position: &mut Position, velocity: &mut Velocity| -> Result<()> {
+                let x = position.0.center().x;
+                let bounds = Box2::new(x, 0., 8., 8.);
+                max_penetration = f32::max(max_penetration, bounds.maxs.x - wall.mins.x);
+                if bounds.intersects(&wall) {
+                    position.0.translation.vector.x = wall.mins.x - 8.;
+                    velocity.0.linear.x = 0.;
+                }
+                Ok(())
+            };
+
+        for _ in 0..3 {
+            integrate_substepped(&mut space, 1. / 60., substeps, &mut resolve).unwrap();
+        }
+
+        max_penetration
+    }
+
+    #[test]
+    fn substeps_reduce_penetration() {
+        let single = max_penetration(SubstepCount(1));
+        let substepped = max_penetration(SubstepCount(4));
+
+        // In a single step the box ends up entirely past the wall.
+        assert!(single >= 12., "{}", single);
+        assert!(substepped < single, "{} >= {}", substepped, single);
+        assert!(substepped <= 7.5, "{}", substepped);
+    }
+
+    #[test]
+    fn objects_override_the_default_substep_count() {
+        let space = Spaces::new().create_space();
+        let mut space = space.borrow_mut();
+        let moving = (
+            Position(Position2::translation(0., 0.)),
+            Velocity(Velocity2::new(Vector2::new(60., 0.), 0.)),
+        );
+        let default = space.spawn(moving);
+        let overridden = space.spawn((moving.0, moving.1, SubstepCount(3)));
+
+        let mut calls = Vec::new();
+        integrate_substepped(&mut space, 1. / 60., SubstepCount(2), |obj, _, _| {
+            calls.push(obj);
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(calls.iter().filter(|&&obj| obj == default).count(), 2);
+        assert_eq!(calls.iter().filter(|&&obj| obj == overridden).count(), 3);
+        assert!((space.get::<Position>(default).unwrap().0.center().x - 1.).abs() < 1e-5);
+    }
+}
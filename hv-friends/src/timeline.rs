@@ -0,0 +1,521 @@
+//! Keyframed, `dt`-driven animation of arbitrary values.
+//!
+//! A [`Timeline`] is a list of children laid out either one after another (a sequence) or all
+//! starting at once (a parallel group.) Children are tweens, waits, callbacks, or other timelines,
+//! so sequences and parallel groups nest arbitrarily. Advancing a timeline with
+//! [`Timeline::update`] calls each active tween's setter with its current value; when a `dt`
+//! overshoots the end of a tween, the tween is set to exactly its final value before finishing.
+//!
+//! Setters and callbacks receive a shared reference to a context of type `C`, which is passed to
+//! [`Timeline::update`]. Rust code usually doesn't need one and can leave it as `()`; the Lua
+//! bindings use the [`Lua`] context itself, so that they can set fields on Lua objects.
+//!
+//! From Lua, `hf.animate(obj, "x", 0, 100, 1.0)` animates `obj.x` from `0` to `100` over a second,
+//! and is advanced automatically by [`SimpleHandler`](crate::SimpleHandler). More complex timelines
+//! can be built with `hf.timeline.sequence()` and `hf.timeline.parallel()`.
+
+use hv_core::{engine::Engine, prelude::*};
+
+use crate::{graphics::Color, math::*};
+
+/// Values which can be interpolated by a [`Timeline`].
+pub trait Tweenable: Clone + Send + Sync + 'static {
+    /// Interpolate between `self` and `to`, where `t` ranges from `0` to `1`. Eased `t` values may
+    /// slightly overshoot this range.
+    fn tween(&self, to: &Self, t: f32) -> Self;
+}
+
+impl Tweenable for f32 {
+    fn tween(&self, to: &Self, t: f32) -> Self {
+        self + (to - self) * t
+    }
+}
+
+impl Tweenable for Vector2<f32> {
+    fn tween(&self, to: &Self, t: f32) -> Self {
+        self.lerp(to, t)
+    }
+}
+
+impl Tweenable for Color {
+    fn tween(&self, to: &Self, t: f32) -> Self {
+        Color::new(
+            self.r.tween(&to.r, t),
+            self.g.tween(&to.g, t),
+            self.b.tween(&to.b, t),
+            self.a.tween(&to.a, t),
+        )
+    }
+}
+
+/// Easing functions, mapping linear progress through a tween to eased progress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Easing {
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    SineIn,
+    SineOut,
+    SineInOut,
+}
+
+impl Default for Easing {
+    fn default() -> Self {
+        Self::Linear
+    }
+}
+
+impl Easing {
+    /// Apply the easing function to `t`, which should be in the range `[0, 1]`.
+    pub fn apply(self, t: f32) -> f32 {
+        use std::f32::consts::PI;
+
+        match self {
+            Self::Linear => t,
+            Self::QuadIn => t * t,
+            Self::QuadOut => t * (2. - t),
+            Self::QuadInOut if t < 0.5 => 2. * t * t,
+            Self::QuadInOut => -1. + (4. - 2. * t) * t,
+            Self::CubicIn => t * t * t,
+            Self::CubicOut => (t - 1.).powi(3) + 1.,
+            Self::CubicInOut if t < 0.5 => 4. * t * t * t,
+            Self::CubicInOut => (t - 1.) * (2. * t - 2.).powi(2) + 1.,
+            Self::SineIn => 1. - (t * PI / 2.).cos(),
+            Self::SineOut => (t * PI / 2.).sin(),
+            Self::SineInOut => -((PI * t).cos() - 1.) / 2.,
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for Easing {
+    fn from_lua(lua_value: LuaValue<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        let easing = match LuaString::from_lua(lua_value, lua)?.to_str()? {
+            "linear" => Self::Linear,
+            "quad_in" => Self::QuadIn,
+            "quad_out" => Self::QuadOut,
+            "quad_in_out" => Self::QuadInOut,
+            "cubic_in" => Self::CubicIn,
+            "cubic_out" => Self::CubicOut,
+            "cubic_in_out" => Self::CubicInOut,
+            "sine_in" => Self::SineIn,
+            "sine_out" => Self::SineOut,
+            "sine_in_out" => Self::SineInOut,
+            other => {
+                return Err(anyhow!("unknown easing function `{}`", other)).to_lua_err();
+            }
+        };
+
+        Ok(easing)
+    }
+}
+
+type Callback<C> = Box<dyn FnMut(&C) -> Result<()> + Send + Sync>;
+
+enum ChildKind<C> {
+    Tween(Box<dyn FnMut(&C, f32) -> Result<()> + Send + Sync>),
+    Call(Callback<C>),
+    Timeline(Timeline<C>),
+    Wait,
+}
+
+struct Child<C> {
+    start: f32,
+    duration: f32,
+    kind: ChildKind<C>,
+    done: bool,
+}
+
+impl<C> Child<C> {
+    /// Advance this child to `time`, relative to the start of its parent.
+    fn seek(&mut self, ctx: &C, time: f32) -> Result<()> {
+        if self.done || time < self.start {
+            return Ok(());
+        }
+
+        let local = time - self.start;
+        let finished = local >= self.duration;
+
+        match &mut self.kind {
+            ChildKind::Tween(apply) => {
+                let t = if finished { 1. } else { local / self.duration };
+                apply(ctx, t)?;
+            }
+            ChildKind::Call(callback) => callback(ctx)?,
+            ChildKind::Timeline(timeline) => timeline.seek(ctx, local)?,
+            ChildKind::Wait => {}
+        }
+
+        self.done = finished;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Layout {
+    Sequence,
+    Parallel,
+}
+
+/// A sequence or parallel group of tweens, waits, callbacks, and nested timelines. See the
+/// [module-level documentation](self).
+pub struct Timeline<C = ()> {
+    layout: Layout,
+    children: Vec<Child<C>>,
+    duration: f32,
+    elapsed: f32,
+    on_complete: Vec<Callback<C>>,
+    completed: bool,
+}
+
+impl<C> std::fmt::Debug for Timeline<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Timeline")
+            .field("layout", &self.layout)
+            .field("children", &self.children.len())
+            .field("duration", &self.duration)
+            .field("elapsed", &self.elapsed)
+            .field("completed", &self.completed)
+            .finish()
+    }
+}
+
+impl<C: 'static> Timeline<C> {
+    fn new(layout: Layout) -> Self {
+        Self {
+            layout,
+            children: Vec::new(),
+            duration: 0.,
+            elapsed: 0.,
+            on_complete: Vec::new(),
+            completed: false,
+        }
+    }
+
+    /// Create an empty timeline whose children run one after another.
+    pub fn sequence() -> Self {
+        Self::new(Layout::Sequence)
+    }
+
+    /// Create an empty timeline whose children all start at once.
+    pub fn parallel() -> Self {
+        Self::new(Layout::Parallel)
+    }
+
+    fn push(&mut self, duration: f32, kind: ChildKind<C>) -> &mut Self {
+        let duration = duration.max(0.);
+        let start = match self.layout {
+            Layout::Sequence => self.duration,
+            Layout::Parallel => 0.,
+        };
+
+        self.children.push(Child {
+            start,
+            duration,
+            kind,
+            done: false,
+        });
+        self.duration = self.duration.max(start + duration);
+        self
+    }
+
+    /// Add a tween from `from` to `to` over `duration` seconds, calling `setter` with the current
+    /// value every time the timeline is updated while the tween is active.
+    pub fn animate<T, F>(
+        &mut self,
+        mut setter: F,
+        from: T,
+        to: T,
+        duration: f32,
+        easing: Easing,
+    ) -> &mut Self
+    where
+        T: Tweenable,
+        F: FnMut(&C, T) -> Result<()> + Send + Sync + 'static,
+    {
+        let apply = move |ctx: &C, t: f32| setter(ctx, from.tween(&to, easing.apply(t)));
+        self.push(duration, ChildKind::Tween(Box::new(apply)))
+    }
+
+    /// Add a pause of `duration` seconds. In a parallel group, this extends the group's duration
+    /// to at least `duration`.
+    pub fn wait(&mut self, duration: f32) -> &mut Self {
+        self.push(duration, ChildKind::Wait)
+    }
+
+    /// Add a callback, called once when the timeline reaches it.
+    pub fn call<F>(&mut self, callback: F) -> &mut Self
+    where
+        F: FnMut(&C) -> Result<()> + Send + Sync + 'static,
+    {
+        self.push(0., ChildKind::Call(Box::new(callback)))
+    }
+
+    /// Add a nested timeline, such as a parallel group inside a sequence. The nested timeline's
+    /// duration is fixed at the time it's added.
+    pub fn add(&mut self, timeline: Timeline<C>) -> &mut Self {
+        self.push(timeline.duration, ChildKind::Timeline(timeline))
+    }
+
+    /// Add a callback, called once when the whole timeline completes.
+    pub fn on_complete<F>(&mut self, callback: F) -> &mut Self
+    where
+        F: FnMut(&C) -> Result<()> + Send + Sync + 'static,
+    {
+        self.on_complete.push(Box::new(callback));
+        self
+    }
+
+    /// The total duration of the timeline in seconds.
+    pub fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    /// The time elapsed since the timeline started, in seconds.
+    pub fn elapsed(&self) -> f32 {
+        self.elapsed
+    }
+
+    /// Whether the timeline has reached its end.
+    pub fn is_finished(&self) -> bool {
+        self.completed
+    }
+
+    fn seek(&mut self, ctx: &C, time: f32) -> Result<()> {
+        for child in &mut self.children {
+            child.seek(ctx, time)?;
+        }
+
+        Ok(())
+    }
+
+    /// Advance the timeline by `dt` seconds, updating all active tweens and calling any callbacks
+    /// which have been reached.
+    pub fn update(&mut self, ctx: &C, dt: f32) -> Result<()> {
+        if self.completed {
+            return Ok(());
+        }
+
+        self.elapsed += dt;
+        self.seek(ctx, self.elapsed)?;
+
+        if self.elapsed >= self.duration {
+            self.completed = true;
+            for callback in &mut self.on_complete {
+                callback(ctx)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A [`Timeline`] driven by and animating Lua values. It's wrapped in an `Option` so that it can be
+/// moved into another timeline with `add`.
+struct LuaTimeline(Option<Timeline<Lua>>);
+
+impl LuaTimeline {
+    fn get_mut(&mut self) -> LuaResult<&mut Timeline<Lua>> {
+        self.0
+            .as_mut()
+            .ok_or_else(|| anyhow!("timeline has been added to another timeline"))
+            .to_lua_err()
+    }
+}
+
+fn lua_callback(lua: &Lua, callback: LuaFunction) -> LuaResult<Callback<Lua>> {
+    let key = lua.create_registry_value(callback)?;
+    Ok(Box::new(move |lua: &Lua| {
+        lua.registry_value::<LuaFunction>(&key)?.call::<_, ()>(())?;
+        Ok(())
+    }))
+}
+
+impl LuaUserData for LuaTimeline {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_function(
+            "animate",
+            |lua,
+             (this, object, key, from, to, duration, easing): (
+                LuaAnyUserData,
+                LuaTable,
+                LuaValue,
+                f32,
+                f32,
+                f32,
+                Option<Easing>,
+            )| {
+                let object = lua.create_registry_value(object)?;
+                let key = lua.create_registry_value(key)?;
+                let setter = move |lua: &Lua, value: f32| {
+                    lua.registry_value::<LuaTable>(&object)?
+                        .set(lua.registry_value::<LuaValue>(&key)?, value)?;
+                    Ok(())
+                };
+
+                this.borrow_mut::<LuaTimeline>()?.get_mut()?.animate(
+                    setter,
+                    from,
+                    to,
+                    duration,
+                    easing.unwrap_or_default(),
+                );
+                Ok(this)
+            },
+        );
+
+        methods.add_function("wait", |_, (this, duration): (LuaAnyUserData, f32)| {
+            this.borrow_mut::<LuaTimeline>()?.get_mut()?.wait(duration);
+            Ok(this)
+        });
+
+        methods.add_function(
+            "call",
+            |lua, (this, callback): (LuaAnyUserData, LuaFunction)| {
+                let callback = lua_callback(lua, callback)?;
+                this.borrow_mut::<LuaTimeline>()?.get_mut()?.call(callback);
+                Ok(this)
+            },
+        );
+
+        methods.add_function(
+            "add",
+            |_, (this, other): (LuaAnyUserData, LuaAnyUserData)| {
+                let other = other
+                    .borrow_mut::<LuaTimeline>()?
+                    .0
+                    .take()
+                    .ok_or_else(|| anyhow!("timeline has already been added to another timeline"))
+                    .to_lua_err()?;
+                this.borrow_mut::<LuaTimeline>()?.get_mut()?.add(other);
+                Ok(this)
+            },
+        );
+
+        methods.add_function(
+            "on_complete",
+            |lua, (this, callback): (LuaAnyUserData, LuaFunction)| {
+                let callback = lua_callback(lua, callback)?;
+                this.borrow_mut::<LuaTimeline>()?
+                    .get_mut()?
+                    .on_complete(callback);
+                Ok(this)
+            },
+        );
+
+        methods.add_method_mut("update", |lua, this, dt: f32| {
+            this.get_mut()?.update(lua, dt).to_lua_err()
+        });
+
+        methods.add_method("is_finished", |_, this, ()| {
+            Ok(this.0.as_ref().map(Timeline::is_finished).unwrap_or(true))
+        });
+
+        methods.add_method("duration", |_, this, ()| {
+            Ok(this.0.as_ref().map(Timeline::duration).unwrap_or(0.))
+        });
+    }
+}
+
+pub(crate) fn open<'lua>(lua: &'lua Lua, _engine: &Engine) -> Result<LuaTable<'lua>> {
+    let create_sequence_object =
+        lua.create_function(|_, ()| Ok(LuaTimeline(Some(Timeline::sequence()))))?;
+    let create_parallel_object =
+        lua.create_function(|_, ()| Ok(LuaTimeline(Some(Timeline::parallel()))))?;
+
+    Ok(lua
+        .load(mlua::chunk! {
+            {
+                create_sequence_object = $create_sequence_object,
+                create_parallel_object = $create_parallel_object,
+            }
+        })
+        .eval()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    };
+
+    #[test]
+    fn float_over_one_second() {
+        let value = Arc::new(Mutex::new(0.));
+        let completed = Arc::new(AtomicBool::new(false));
+
+        let mut timeline = Timeline::sequence();
+        let v = value.clone();
+        let c = completed.clone();
+        timeline
+            .animate(
+                move |_, x| {
+                    *v.lock().unwrap() = x;
+                    Ok(())
+                },
+                0.,
+                100.,
+                1.,
+                Easing::Linear,
+            )
+            .on_complete(move |_| {
+                c.store(true, Ordering::SeqCst);
+                Ok(())
+            });
+
+        timeline.update(&(), 0.25).unwrap();
+        timeline.update(&(), 0.25).unwrap();
+        assert!((*value.lock().unwrap() - 50.0f32).abs() < 1e-4);
+        assert!(!completed.load(Ordering::SeqCst));
+
+        // Overshooting the end clamps to the final value.
+        timeline.update(&(), 0.8).unwrap();
+        assert_eq!(*value.lock().unwrap(), 100.);
+        assert!(completed.load(Ordering::SeqCst));
+        assert!(timeline.is_finished());
+    }
+
+    #[test]
+    fn sequences_and_parallel_groups() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let setter = |name: &'static str| {
+            let log = log.clone();
+            move |_: &(), x: f32| {
+                log.lock().unwrap().push((name, x));
+                Ok(())
+            }
+        };
+
+        let mut group = Timeline::parallel();
+        group
+            .animate(setter("a"), 0., 1., 1., Easing::Linear)
+            .animate(setter("b"), 0., 1., 2., Easing::Linear);
+        let mut timeline = Timeline::sequence();
+        timeline
+            .add(group)
+            .animate(setter("c"), 10., 20., 1., Easing::Linear);
+        assert_eq!(timeline.duration(), 3.);
+
+        timeline.update(&(), 1.5).unwrap();
+        assert_eq!(*log.lock().unwrap(), vec![("a", 1.), ("b", 0.75)]);
+        log.lock().unwrap().clear();
+
+        // One big step past the end finishes `b` and runs all of `c`, ending on its final value.
+        timeline.update(&(), 5.).unwrap();
+        assert_eq!(*log.lock().unwrap(), vec![("b", 1.), ("c", 20.)]);
+        assert!(timeline.is_finished());
+    }
+
+    #[test]
+    fn vectors_and_colors_tween() {
+        let v = Vector2::new(0., 10.).tween(&Vector2::new(10., 0.), 0.5);
+        assert_eq!(v, Vector2::new(5., 5.));
+        let c = Color::BLACK.tween(&Color::WHITE, 0.5);
+        assert_eq!(c, Color::new(0.5, 0.5, 0.5, 1.));
+    }
+}
@@ -12,6 +12,7 @@ use crate::math::*;
 pub struct Velocity(pub Velocity2<f32>);
 
 hv_core::serializable!(serialize::with_serde::<Velocity>("friends.Velocity"));
+hv_core::component_type!("Velocity", Velocity);
 
 impl LuaUserData for Velocity {}
 
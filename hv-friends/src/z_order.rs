@@ -0,0 +1,133 @@
+use hv_core::{
+    components::DynamicComponentConstructor,
+    engine::Engine,
+    prelude::*,
+    spaces::{serialize, Component, Object, Space, SpaceCache},
+};
+use serde::*;
+
+use crate::graphics::Graphics;
+
+/// The order in which an object is drawn relative to other objects when rendering through
+/// [`draw_sorted`]. Lower values are drawn first, so higher values end up on top. Objects without a
+/// `ZOrder` are treated as having a `ZOrder` of `0`.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+)]
+pub struct ZOrder(pub i32);
+
+hv_core::serializable!(serialize::with_serde::<ZOrder>("friends.ZOrder"));
+hv_core::component_type!("ZOrder", ZOrder);
+
+impl LuaUserData for ZOrder {}
+
+/// Collect every object in the space which has a `T` component, sorted by [`ZOrder`]. Objects with
+/// equal z-orders are sorted by their slot in the space, so the resulting order doesn't depend on
+/// query iteration order and won't change from frame to frame.
+///
+/// The buffer is cleared before objects are collected into it, so that it can be reused every frame
+/// without reallocating.
+pub fn z_sorted<T: Component>(space: &Space, buf: &mut Vec<Object>) {
+    let mut entries = space
+        .query::<Option<&ZOrder>>()
+        .with::<T>()
+        .iter()
+        .map(|(object, z)| (z.copied().unwrap_or_default(), object.slot(), object))
+        .collect::<Vec<_>>();
+    entries.sort_unstable_by_key(|&(z, slot, _)| (z, slot));
+
+    buf.clear();
+    buf.extend(entries.into_iter().map(|(_, _, object)| object));
+}
+
+/// Draw every object in the space which has a `T` component, in [`ZOrder`] order (see
+/// [`z_sorted`].) The callback is given the object and its `T` component to draw.
+pub fn draw_sorted<T: Component>(
+    space: &Space,
+    gfx: &mut Graphics,
+    mut draw: impl FnMut(&mut Graphics, Object, &T),
+) -> Result<()> {
+    let mut objects = Vec::new();
+    z_sorted::<T>(space, &mut objects);
+    for object in objects {
+        draw(gfx, object, &*space.get::<T>(object)?);
+    }
+    Ok(())
+}
+
+pub(crate) fn open<'lua>(lua: &'lua Lua, engine: &Engine) -> Result<LuaTable<'lua>, Error> {
+    let create_z_order_constructor = lua.create_function(|_, z: Option<i32>| {
+        Ok(DynamicComponentConstructor::copy(ZOrder(z.unwrap_or(0))))
+    })?;
+
+    let mut space_cache = SpaceCache::new(engine);
+    let get_z_order = lua.create_function_mut(move |_, obj: Object| {
+        let space = space_cache.get_space(obj.space());
+        let z = space
+            .borrow()
+            .query_one::<Option<&ZOrder>>(obj)
+            .to_lua_err()?
+            .get()
+            .and_then(|z| z.copied())
+            .unwrap_or_default();
+        Ok(z.0)
+    })?;
+
+    let mut space_cache = SpaceCache::new(engine);
+    let set_z_order = lua.create_function_mut(move |_, (obj, z): (Object, i32)| {
+        let space = space_cache.get_space(obj.space());
+        space.borrow().get_mut::<ZOrder>(obj).to_lua_err()?.0 = z;
+        Ok(())
+    })?;
+
+    Ok(lua
+        .load(mlua::chunk! {
+            {
+                create_z_order_constructor = $create_z_order_constructor,
+                get_z_order = $get_z_order,
+                set_z_order = $set_z_order,
+            }
+        })
+        .eval()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hv_core::spaces::Spaces;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Sprite(&'static str);
+
+    #[test]
+    fn objects_draw_in_z_order() {
+        let space = Spaces::new().create_space();
+        let mut space = space.borrow_mut();
+        space.spawn((Sprite("two"), ZOrder(2)));
+        space.spawn((Sprite("zero"), ZOrder(0)));
+        space.spawn((Sprite("one"), ZOrder(1)));
+
+        let mut sorted = Vec::new();
+        z_sorted::<Sprite>(&space, &mut sorted);
+        let names = sorted
+            .iter()
+            .map(|&obj| space.get::<Sprite>(obj).unwrap().0)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["zero", "one", "two"]);
+    }
+
+    #[test]
+    fn missing_z_order_defaults_to_zero_and_sorts_stably() {
+        let space = Spaces::new().create_space();
+        let mut space = space.borrow_mut();
+        let behind = space.spawn((Sprite("behind"), ZOrder(-1)));
+        let a = space.spawn((Sprite("a"),));
+        let b = space.spawn((Sprite("b"), ZOrder(0)));
+        let c = space.spawn((Sprite("c"),));
+        let front = space.spawn((Sprite("front"), ZOrder(1)));
+
+        let mut sorted = Vec::new();
+        z_sorted::<Sprite>(&space, &mut sorted);
+        assert_eq!(sorted, [behind, a, b, c, front]);
+    }
+}
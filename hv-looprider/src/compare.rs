@@ -0,0 +1,153 @@
+//! Playing two replays back in lockstep to find where their event streams differ.
+//!
+//! A [`DualPlayback`] flushes two playback [`Looprider`]s together, one frame at a time, and hands
+//! back each side's events for that frame along with whether they differ. This is meant for tuning
+//! and regression review: record a run before and after a change, play both back side by side
+//! (each [`Looprider`] can drive its own viewport through [`DualPlayback::left`] and
+//! [`DualPlayback::right`]), and stop on the first frame where they disagree.
+//!
+//! Replays don't need to be the same length. Once one side runs out of records it keeps flushing
+//! with no events, so any events the other side still has count as a divergence.
+
+use hv_core::prelude::*;
+
+use crate::{LoopreaderId, Looprider, LoopriderEvent, Replay};
+
+/// One frame's worth of events from each side of a [`DualPlayback`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct DualFrame<E> {
+    /// The frame both sides were flushed on.
+    pub frame: u64,
+    /// The events the left replay produced this frame, in order.
+    pub left: Vec<E>,
+    /// The events the right replay produced this frame, in order.
+    pub right: Vec<E>,
+}
+
+impl<E: PartialEq> DualFrame<E> {
+    /// Whether the two sides produced different events this frame, including the same events in a
+    /// different order.
+    pub fn diverged(&self) -> bool {
+        self.left != self.right
+    }
+}
+
+/// Two [`Looprider`]s in "playback" mode, flushed together.
+#[derive(Debug)]
+pub struct DualPlayback<E: LoopriderEvent> {
+    left: Shared<Looprider<E>>,
+    right: Shared<Looprider<E>>,
+    left_reader: LoopreaderId<E>,
+    right_reader: LoopreaderId<E>,
+}
+
+impl<E: LoopriderEvent> DualPlayback<E> {
+    /// Start playing back both replays from their first frame. Fails if either replay is out of
+    /// order, the same way [`Looprider::try_playback`] does.
+    pub fn new(left: Replay<E>, right: Replay<E>) -> Result<Self> {
+        let left = Looprider::try_playback(left).context("invalid left replay")?;
+        let right = Looprider::try_playback(right).context("invalid right replay")?;
+        let left_reader = left.borrow_mut().register_reader();
+        let right_reader = right.borrow_mut().register_reader();
+
+        Ok(Self {
+            left,
+            right,
+            left_reader,
+            right_reader,
+        })
+    }
+
+    /// The left side's [`Looprider`], for registering more readers on it.
+    pub fn left(&self) -> &Shared<Looprider<E>> {
+        &self.left
+    }
+
+    /// The right side's [`Looprider`], for registering more readers on it.
+    pub fn right(&self) -> &Shared<Looprider<E>> {
+        &self.right
+    }
+
+    /// Whether both replays have played back all of their records.
+    pub fn is_finished(&self) -> bool {
+        self.left.borrow().is_finished() && self.right.borrow().is_finished()
+    }
+
+    /// Flush both sides and return the events each one produced, or `None` without flushing if
+    /// both replays have already finished.
+    pub fn flush(&mut self) -> Option<DualFrame<E>> {
+        if self.is_finished() {
+            return None;
+        }
+
+        let mut left = self.left.borrow_mut();
+        let mut right = self.right.borrow_mut();
+        debug_assert_eq!(left.record, right.record);
+        let frame = left.record;
+
+        left.flush();
+        right.flush();
+
+        Some(DualFrame {
+            frame,
+            left: left.read(&mut self.left_reader).cloned().collect(),
+            right: right.read(&mut self.right_reader).cloned().collect(),
+        })
+    }
+}
+
+impl<E: LoopriderEvent> Iterator for DualPlayback<E> {
+    type Item = DualFrame<E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replay(frames: &[&[u32]]) -> Replay<u32> {
+        let looprider = Looprider::record();
+        let mut looprider = looprider.borrow_mut();
+        for events in frames {
+            for &event in *events {
+                looprider.push(event);
+            }
+            looprider.flush();
+        }
+        looprider.to_replay().unwrap()
+    }
+
+    #[test]
+    fn divergence_is_found_on_the_right_frame() {
+        let left = replay(&[&[1], &[], &[2, 3], &[4]]);
+        let right = replay(&[&[1], &[], &[3, 2], &[4], &[], &[5]]);
+
+        let frames = DualPlayback::new(left, right).unwrap().collect::<Vec<_>>();
+        let diverged = frames
+            .iter()
+            .filter(|frame| frame.diverged())
+            .map(|frame| frame.frame)
+            .collect::<Vec<_>>();
+
+        // Frame 2 has the same events in a different order, and frame 5 only exists on the right.
+        assert_eq!(diverged, [2, 5]);
+        assert_eq!(frames.len(), 6);
+        assert_eq!(frames[2].left, [2, 3]);
+        assert_eq!(frames[2].right, [3, 2]);
+        assert!(frames[5].left.is_empty());
+        assert_eq!(frames[5].right, [5]);
+    }
+
+    #[test]
+    fn identical_replays_never_diverge() {
+        let frames = [&[7][..], &[], &[8, 9]];
+        let mut playback = DualPlayback::new(replay(&frames), replay(&frames)).unwrap();
+
+        assert!(playback.by_ref().all(|frame| !frame.diverged()));
+        assert!(playback.is_finished());
+        assert!(playback.flush().is_none());
+    }
+}
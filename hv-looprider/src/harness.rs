@@ -0,0 +1,430 @@
+//! A deterministic, fixed-timestep harness for replaying recorded input against game logic.
+//!
+//! A [`ReplayHarness`] owns a [`Space`], an [`InputPlayback`], and a [`Simulation`] - the piece of
+//! game logic under test. Every call to [`ReplayHarness::step`] feeds the next recorded input frame
+//! to the simulation, advances it by exactly one fixed timestep, and then hashes the simulation's
+//! state. Comparing those per-frame hashes against the ones recorded from another run finds the
+//! first frame on which the two runs diverged, which is usually much closer to the actual bug than
+//! wherever the game visibly goes wrong.
+//!
+//! The harness runs without a window or an [`Engine`](hv_core::engine::Engine): simulations are
+//! driven directly rather than through an
+//! [`EventHandler`](hv_core::engine::EventHandler), so whatever game logic is meant to be tested
+//! should be factored out of the event handler's `update` into a [`Simulation`].
+//!
+//! State hashes are computed with [`DefaultHasher`], so they're only comparable between runs built
+//! with the same toolchain. Don't persist them alongside replays.
+
+use hv_core::{
+    input::InputState,
+    prelude::*,
+    spaces::{Space, Spaces},
+};
+use std::{collections::hash_map::DefaultHasher, fmt, hash::Hasher};
+
+use crate::input::{InputKind, InputPlayback, InputReplay};
+
+/// Game logic which can be stepped by a [`ReplayHarness`].
+///
+/// For replays to be useful, every method here must be deterministic: given the same seed and the
+/// same sequence of inputs, a simulation must always end up in the same state.
+pub trait Simulation<Axes: InputKind, Buttons: InputKind> {
+    /// Set up the initial state of the simulation. Called once, when the harness is created, with
+    /// the seed the harness was given; any randomness in the simulation should be derived from it.
+    fn init(&mut self, space: &mut Space, seed: u64) -> Result<()>;
+
+    /// Advance the simulation by one fixed timestep using the given input state.
+    fn step(&mut self, space: &mut Space, input: &InputState<Axes, Buttons>, dt: f32)
+        -> Result<()>;
+
+    /// Feed everything which should be checked for divergence into the hasher. Floats should be
+    /// hashed by their bit patterns (`f32::to_bits`.) Components registered as serializable can be
+    /// hashed with [`Space::hash_state_into`]; any RNG state the simulation keeps outside the space
+    /// should be hashed too.
+    fn hash_state(&self, space: &Space, hasher: &mut DefaultHasher);
+}
+
+/// The first frame on which two runs of a replay produced different state hashes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Divergence {
+    /// The index of the first differing frame. Frame `0` is the state right after
+    /// [`Simulation::init`], before any steps.
+    pub frame: usize,
+    /// The hash that was expected on this frame, or `None` if the expected run ended earlier.
+    pub expected: Option<u64>,
+    /// The hash that was actually produced on this frame, or `None` if this run ended earlier.
+    pub actual: Option<u64>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let show = |hash: Option<u64>| match hash {
+            Some(hash) => format!("{:016x}", hash),
+            None => "<end of run>".to_owned(),
+        };
+
+        write!(
+            f,
+            "replay diverged on frame {} (expected {}, got {})",
+            self.frame,
+            show(self.expected),
+            show(self.actual)
+        )
+    }
+}
+
+impl std::error::Error for Divergence {}
+
+/// Find the first frame on which two lists of per-frame state hashes differ, if any. A run which
+/// ends early diverges on the first frame it's missing.
+pub fn first_divergence(expected: &[u64], actual: &[u64]) -> Option<Divergence> {
+    let len = expected.len().max(actual.len());
+    (0..len).find_map(|frame| {
+        let (e, a) = (expected.get(frame).copied(), actual.get(frame).copied());
+        (e != a).then(|| Divergence {
+            frame,
+            expected: e,
+            actual: a,
+        })
+    })
+}
+
+/// Replays an [`InputReplay`] against a [`Simulation`] at a fixed timestep, recording a state hash
+/// for every frame.
+pub struct ReplayHarness<Axes: InputKind, Buttons: InputKind, S: Simulation<Axes, Buttons>> {
+    playback: InputPlayback<Axes, Buttons>,
+    space: Shared<Space>,
+    simulation: S,
+    dt: f32,
+    seed: u64,
+    hashes: Vec<u64>,
+}
+
+impl<Axes, Buttons, S> ReplayHarness<Axes, Buttons, S>
+where
+    Axes: InputKind,
+    Buttons: InputKind,
+    S: Simulation<Axes, Buttons>,
+{
+    /// Create a harness in a fresh [`Space`], initialize the simulation with the given seed, and
+    /// hash the initial state as frame `0`.
+    pub fn new(
+        replay: InputReplay<Axes, Buttons>,
+        seed: u64,
+        dt: f32,
+        simulation: S,
+    ) -> Result<Self> {
+        let space = Spaces::new().create_space();
+        let mut this = Self {
+            playback: replay.try_playback()?,
+            space,
+            simulation,
+            dt,
+            seed,
+            hashes: Vec::new(),
+        };
+
+        this.simulation.init(&mut this.space.borrow_mut(), seed)?;
+        this.record_hash();
+
+        Ok(this)
+    }
+
+    fn record_hash(&mut self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.simulation
+            .hash_state(&self.space.borrow(), &mut hasher);
+        let hash = hasher.finish();
+        self.hashes.push(hash);
+        hash
+    }
+
+    /// Apply the next recorded input frame, step the simulation once, and return the new frame's
+    /// state hash.
+    pub fn step(&mut self) -> Result<u64> {
+        self.playback.tick(self.dt);
+        self.simulation
+            .step(&mut self.space.borrow_mut(), self.playback.state(), self.dt)
+            .with_context(|| format!("error stepping simulation on frame {}", self.frame() + 1))?;
+        Ok(self.record_hash())
+    }
+
+    /// Step until the current frame is `frame`. Does nothing if it's already at or past it.
+    pub fn run_to(&mut self, frame: usize) -> Result<()> {
+        while self.frame() < frame {
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    /// Step until the current frame is `frame`, checking each new frame's hash against the
+    /// corresponding entry of `expected` and stopping at the first mismatch. Frames beyond the end
+    /// of `expected` aren't checked.
+    pub fn run_checked(&mut self, frame: usize, expected: &[u64]) -> Result<()> {
+        self.check(expected)?;
+        while self.frame() < frame {
+            self.step()?;
+            self.check(expected)?;
+        }
+        Ok(())
+    }
+
+    fn check(&self, expected: &[u64]) -> Result<()> {
+        let frame = self.frame();
+        match expected.get(frame) {
+            Some(&e) if e != self.hashes[frame] => Err(Divergence {
+                frame,
+                expected: Some(e),
+                actual: Some(self.hashes[frame]),
+            }
+            .into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// The index of the most recently completed frame. `0` means no steps have been taken yet.
+    pub fn frame(&self) -> usize {
+        self.hashes.len() - 1
+    }
+
+    /// The state hashes of every frame so far, starting with frame `0`.
+    pub fn hashes(&self) -> &[u64] {
+        &self.hashes
+    }
+
+    /// The seed the simulation was initialized with.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The fixed timestep the simulation is advanced by.
+    pub fn dt(&self) -> f32 {
+        self.dt
+    }
+
+    /// The space the simulation runs in, for asserting on its contents.
+    pub fn space(&self) -> &Shared<Space> {
+        &self.space
+    }
+
+    /// The simulation being driven.
+    pub fn simulation(&self) -> &S {
+        &self.simulation
+    }
+
+    /// The input state as of the current frame.
+    pub fn input(&self) -> &InputState<Axes, Buttons> {
+        self.playback.state()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{input::InputEvent, Looprider};
+    use hv_core::input::InputEffect;
+    use std::hash::Hash;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    enum Axis {
+        Turn,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    enum Button {
+        Thrust,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Ship {
+        x: f32,
+        y: f32,
+        vx: f32,
+        vy: f32,
+        angle: f32,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Rock {
+        x: f32,
+        y: f32,
+        vx: f32,
+        vy: f32,
+    }
+
+    const FIELD: f32 = 100.;
+    const DT: f32 = 1. / 60.;
+
+    /// Just enough of Asteroids to be interesting: a ship which turns and thrusts, and a few rocks
+    /// placed by a seeded xorshift, all wrapping around a square field.
+    struct Asteroids;
+
+    fn xorshift(state: &mut u64) -> f32 {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        (*state % 10_000) as f32 / 10_000.
+    }
+
+    impl Simulation<Axis, Button> for Asteroids {
+        fn init(&mut self, space: &mut Space, seed: u64) -> Result<()> {
+            let mut rng = seed.max(1);
+            space.spawn((Ship {
+                x: FIELD / 2.,
+                y: FIELD / 2.,
+                vx: 0.,
+                vy: 0.,
+                angle: 0.,
+            },));
+            for _ in 0..4 {
+                space.spawn((Rock {
+                    x: xorshift(&mut rng) * FIELD,
+                    y: xorshift(&mut rng) * FIELD,
+                    vx: xorshift(&mut rng) * 20. - 10.,
+                    vy: xorshift(&mut rng) * 20. - 10.,
+                },));
+            }
+            Ok(())
+        }
+
+        fn step(
+            &mut self,
+            space: &mut Space,
+            input: &InputState<Axis, Button>,
+            dt: f32,
+        ) -> Result<()> {
+            for (_, ship) in space.query_mut::<&mut Ship>() {
+                ship.angle += input.get_axis(Axis::Turn) * 3. * dt;
+                if input.get_button_down(Button::Thrust) {
+                    ship.vx += ship.angle.cos() * 50. * dt;
+                    ship.vy += ship.angle.sin() * 50. * dt;
+                }
+                ship.x = (ship.x + ship.vx * dt).rem_euclid(FIELD);
+                ship.y = (ship.y + ship.vy * dt).rem_euclid(FIELD);
+            }
+
+            for (_, rock) in space.query_mut::<&mut Rock>() {
+                rock.x = (rock.x + rock.vx * dt).rem_euclid(FIELD);
+                rock.y = (rock.y + rock.vy * dt).rem_euclid(FIELD);
+            }
+
+            Ok(())
+        }
+
+        fn hash_state(&self, space: &Space, hasher: &mut DefaultHasher) {
+            for (_, ship) in space.query::<&Ship>().iter() {
+                [ship.x, ship.y, ship.vx, ship.vy, ship.angle]
+                    .iter()
+                    .for_each(|f| f.to_bits().hash(hasher));
+            }
+            for (_, rock) in space.query::<&Rock>().iter() {
+                [rock.x, rock.y, rock.vx, rock.vy]
+                    .iter()
+                    .for_each(|f| f.to_bits().hash(hasher));
+            }
+        }
+    }
+
+    fn input(effect: InputEffect<Axis, Button>, started: bool) -> InputEvent<Axis, Button> {
+        InputEvent { effect, started }
+    }
+
+    /// Play the game "live" for 120 frames - thrust for a second while turning for half of it -
+    /// recording both the input and the per-frame state hashes.
+    fn record_session(seed: u64) -> (InputReplay<Axis, Button>, Vec<u64>) {
+        let looprider = Looprider::record();
+        let mut reader = looprider.borrow_mut().register_reader();
+        let space = Spaces::new().create_space();
+        let mut sim = Asteroids;
+        let mut state = InputState::new();
+        let mut hashes = Vec::new();
+
+        let hash = |sim: &Asteroids, space: &Shared<Space>| {
+            let mut hasher = DefaultHasher::new();
+            sim.hash_state(&space.borrow(), &mut hasher);
+            hasher.finish()
+        };
+
+        sim.init(&mut space.borrow_mut(), seed).unwrap();
+        hashes.push(hash(&sim, &space));
+
+        for frame in 1..=120 {
+            let mut lr = looprider.borrow_mut();
+            match frame {
+                10 => lr.push(input(InputEffect::Button(Button::Thrust), true)),
+                30 => lr.push(input(InputEffect::Axis(Axis::Turn, 1.), true)),
+                60 => lr.push(input(InputEffect::Axis(Axis::Turn, 1.), false)),
+                70 => lr.push(input(InputEffect::Button(Button::Thrust), false)),
+                _ => {}
+            }
+            lr.flush();
+            for event in lr.read(&mut reader) {
+                state.update_effect(event.effect.clone(), event.started);
+            }
+            drop(lr);
+            state.update(DT);
+
+            sim.step(&mut space.borrow_mut(), &state, DT).unwrap();
+            hashes.push(hash(&sim, &space));
+        }
+
+        let replay = looprider.borrow().to_replay().unwrap();
+        (InputReplay::new(None, replay), hashes)
+    }
+
+    #[test]
+    fn asteroids_session_replays_identically() {
+        let (replay, recorded) = record_session(0xA57E_2017);
+        let mut harness = ReplayHarness::new(replay, 0xA57E_2017, DT, Asteroids).unwrap();
+
+        harness.run_checked(9, &recorded).unwrap();
+        let ship = |harness: &ReplayHarness<_, _, _>| {
+            let space = harness.space().borrow();
+            let mut query = space.query::<&Ship>();
+            let (_, ship) = query.iter().next().unwrap();
+            *ship
+        };
+        // No thrust yet, so the ship hasn't moved.
+        assert_eq!(
+            (ship(&harness).x, ship(&harness).y),
+            (FIELD / 2., FIELD / 2.)
+        );
+
+        harness.run_checked(120, &recorded).unwrap();
+        assert!(ship(&harness).vx > 0. && ship(&harness).angle > 0.);
+        assert!(harness.input().get_axis(Axis::Turn).abs() < f32::EPSILON);
+        assert_eq!(first_divergence(&recorded, harness.hashes()), None);
+    }
+
+    #[test]
+    fn wrong_seed_reports_first_divergent_frame() {
+        let (replay, recorded) = record_session(1);
+        let mut harness = ReplayHarness::new(replay, 2, DT, Asteroids).unwrap();
+
+        let err = harness.run_checked(120, &recorded).unwrap_err();
+        let divergence = err.downcast_ref::<Divergence>().unwrap();
+        assert_eq!(divergence.frame, 0);
+        assert_eq!(harness.frame(), 0);
+    }
+
+    #[test]
+    fn first_divergence_finds_truncated_runs() {
+        assert_eq!(first_divergence(&[1, 2, 3], &[1, 2, 3]), None);
+        assert_eq!(
+            first_divergence(&[1, 2, 3], &[1, 5, 3]),
+            Some(Divergence {
+                frame: 1,
+                expected: Some(2),
+                actual: Some(5)
+            })
+        );
+        assert_eq!(
+            first_divergence(&[1, 2, 3], &[1, 2]),
+            Some(Divergence {
+                frame: 2,
+                expected: Some(3),
+                actual: None
+            })
+        );
+    }
+}
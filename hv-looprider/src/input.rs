@@ -0,0 +1,207 @@
+//! Recording and playing back input through a [`Looprider`].
+//!
+//! Only [`InputEffect`]s are recorded, never raw key presses or gamepad events, so an
+//! [`InputReplay`] plays back identically no matter what bindings the game is currently using. The
+//! binding in use at recording time is stored alongside the replay anyway, purely as information
+//! for tools (for example, to show which control layout a run used.)
+
+use hv_core::{
+    input::{InputBinding, InputEffect, InputState},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+use std::hash::Hash;
+
+use crate::{LoopreaderId, Looprider, LoopriderEvent, Replay};
+
+/// Trait synonym for the requirements on logical axis and button types used with input replays.
+pub trait InputKind: Eq + Hash + Clone + Send + Sync + 'static {}
+impl<T: Eq + Hash + Clone + Send + Sync + 'static> InputKind for T {}
+
+/// A single recorded input event: an [`InputEffect`] and whether it started or stopped, exactly as
+/// passed to [`InputState::update_effect`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct InputEvent<Axes: InputKind, Buttons: InputKind> {
+    /// The logical effect.
+    pub effect: InputEffect<Axes, Buttons>,
+    /// Whether the effect started (a button/axis was pressed) or stopped (it was released.)
+    pub started: bool,
+}
+
+impl<Axes: InputKind, Buttons: InputKind> LoopriderEvent for InputEvent<Axes, Buttons> {}
+
+/// Information stored at the start of an [`InputReplay`]. None of it affects playback.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "Axes: Serialize, Buttons: Serialize",
+    deserialize = "Axes: Deserialize<'de>, Buttons: Deserialize<'de>"
+))]
+pub struct InputReplayHeader<Axes: InputKind, Buttons: InputKind> {
+    /// The binding which was used to produce the recorded effects, if known.
+    pub binding: Option<InputBinding<Axes, Buttons>>,
+}
+
+/// A self-describing recording of input: a header describing how it was recorded, and the recorded
+/// [`InputEvent`]s themselves.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "Axes: Serialize, Buttons: Serialize",
+    deserialize = "Axes: Deserialize<'de>, Buttons: Deserialize<'de>"
+))]
+pub struct InputReplay<Axes: InputKind, Buttons: InputKind> {
+    /// Information about the recording.
+    pub header: InputReplayHeader<Axes, Buttons>,
+    /// The recorded events.
+    pub replay: Replay<InputEvent<Axes, Buttons>>,
+}
+
+impl<Axes: InputKind, Buttons: InputKind> InputReplay<Axes, Buttons> {
+    /// Create an input replay from a [`Replay`] of input events and the binding used to record it.
+    pub fn new(
+        binding: Option<InputBinding<Axes, Buttons>>,
+        replay: Replay<InputEvent<Axes, Buttons>>,
+    ) -> Self {
+        Self {
+            header: InputReplayHeader { binding },
+            replay,
+        }
+    }
+
+    /// Start playing back this replay.
+    ///
+    /// Panics on invalid input; see [`Looprider::playback`].
+    pub fn playback(self) -> InputPlayback<Axes, Buttons> {
+        InputPlayback::new(self)
+    }
+
+    /// Start playing back this replay, returning an error if its records are out of order.
+    pub fn try_playback(self) -> Result<InputPlayback<Axes, Buttons>> {
+        InputPlayback::try_new(self)
+    }
+}
+
+/// Plays back an [`InputReplay`] into its own [`InputState`], driven purely by the recorded
+/// [`InputEvent`]s. Live input and live bindings are never consulted.
+#[derive(Debug)]
+pub struct InputPlayback<Axes: InputKind, Buttons: InputKind> {
+    header: InputReplayHeader<Axes, Buttons>,
+    looprider: Shared<Looprider<InputEvent<Axes, Buttons>>>,
+    reader: LoopreaderId<InputEvent<Axes, Buttons>>,
+    state: InputState<Axes, Buttons>,
+}
+
+impl<Axes: InputKind, Buttons: InputKind> InputPlayback<Axes, Buttons> {
+    /// Begin playing back a replay from its first record.
+    ///
+    /// Panics on invalid input; see [`Looprider::playback`].
+    pub fn new(replay: InputReplay<Axes, Buttons>) -> Self {
+        Self::try_new(replay).unwrap()
+    }
+
+    /// Begin playing back a replay from its first record, returning an error if its records are
+    /// out of order.
+    pub fn try_new(replay: InputReplay<Axes, Buttons>) -> Result<Self> {
+        let looprider = Looprider::try_playback(replay.replay)?;
+        let reader = looprider.borrow_mut().register_reader();
+
+        Ok(Self {
+            header: replay.header,
+            looprider,
+            reader,
+            state: InputState::new(),
+        })
+    }
+
+    /// The header of the replay being played back.
+    pub fn header(&self) -> &InputReplayHeader<Axes, Buttons> {
+        &self.header
+    }
+
+    /// Apply the next record's events to the input state and then update it. Like
+    /// [`Looprider::flush`], this must be called exactly as many times per frame as the recording
+    /// [`Looprider`] was flushed.
+    pub fn tick(&mut self, dt: f32) {
+        let mut looprider = self.looprider.borrow_mut();
+        looprider.flush();
+        for event in looprider.read(&mut self.reader) {
+            self.state
+                .update_effect(event.effect.clone(), event.started);
+        }
+        self.state.update(dt);
+    }
+
+    /// The input state being driven by the replay.
+    pub fn state(&self) -> &InputState<Axes, Buttons> {
+        &self.state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hv_core::input::{GamepadAxis, KeyCode};
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+    enum Axis {
+        Horizontal,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+    enum Button {
+        Jump,
+    }
+
+    fn record() -> (InputBinding<Axis, Button>, Replay<InputEvent<Axis, Button>>) {
+        let binding = InputBinding::new()
+            .bind_key_to_button(KeyCode::Z, Button::Jump)
+            .bind_key_to_axis(KeyCode::Left, Axis::Horizontal, -1.)
+            .bind_gamepad_axis_to_axis(GamepadAxis::LeftStickX, Axis::Horizontal);
+
+        let looprider = Looprider::record();
+        let mut looprider = looprider.borrow_mut();
+        looprider.flush();
+        looprider.push(InputEvent {
+            effect: binding.resolve_keycode(KeyCode::Z).unwrap(),
+            started: true,
+        });
+        looprider.flush();
+        looprider.flush();
+
+        (binding, looprider.to_replay().unwrap())
+    }
+
+    #[test]
+    fn replay_binding_roundtrips() {
+        let (binding, replay) = record();
+        let input_replay = InputReplay::new(Some(binding.clone()), replay);
+
+        let bytes = bincode::serialize(&input_replay).unwrap();
+        let deserialized: InputReplay<Axis, Button> = bincode::deserialize(&bytes).unwrap();
+
+        assert_eq!(deserialized.header.binding.as_ref(), Some(&binding));
+        assert_eq!(
+            deserialized
+                .header
+                .binding
+                .unwrap()
+                .resolve_keycode(KeyCode::Left),
+            Some(InputEffect::Axis(Axis::Horizontal, -1.))
+        );
+    }
+
+    #[test]
+    fn playback_ignores_binding() {
+        let (_, replay) = record();
+        // Deliberately claim a binding which doesn't bind `Jump` at all; playback only uses the
+        // recorded effects.
+        let mut playback = InputReplay::new(Some(InputBinding::new()), replay).playback();
+
+        playback.tick(1. / 60.);
+        assert!(!playback.state().get_button_down(Button::Jump));
+        playback.tick(1. / 60.);
+        assert!(playback.state().get_button_down(Button::Jump));
+        playback.tick(1. / 60.);
+        assert!(playback.state().get_button_down(Button::Jump));
+        assert!(!playback.state().get_button_pressed(Button::Jump));
+    }
+}
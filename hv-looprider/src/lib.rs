@@ -9,12 +9,15 @@
 //! back from recorded "replays".
 
 #![warn(missing_docs)]
-#![feature(is_sorted)]
 
 use hv_core::prelude::*;
 use serde::{Deserialize, Serialize};
 use shrev::{Event, EventChannel, EventIterator, ReaderId};
 
+pub mod compare;
+pub mod harness;
+pub mod input;
+
 /// Types usable as events with [`Looprider`].
 pub trait LoopriderEvent: Event + Clone {}
 
@@ -24,10 +27,83 @@ pub struct Replay<E: LoopriderEvent> {
     records: Vec<Record<E>>,
 }
 
+impl<E: LoopriderEvent> Replay<E> {
+    /// Assemble a replay from records in the order they were recorded, such as records drained
+    /// from a recording [`Looprider`] with [`Looprider::take_records`] followed by the records of
+    /// its final [`Looprider::to_replay`]. The result is [normalized](Replay::normalize).
+    pub fn from_records(records: impl IntoIterator<Item = Record<E>>) -> Self {
+        let mut records = records.into_iter().collect::<Vec<_>>();
+        records.reverse();
+        let mut replay = Self { records };
+        replay.normalize();
+        replay
+    }
+
+    /// Take the records out of this replay, first frame first.
+    pub fn into_records(self) -> Vec<Record<E>> {
+        let mut records = self.records;
+        records.reverse();
+        records
+    }
+
+    /// Sort the replay's records by frame and merge records which share a frame into one,
+    /// guaranteeing a replay which [`Looprider::playback`] accepts. Events in merged records are
+    /// concatenated in the order their records were originally appended, so events in the same
+    /// frame are played back in the order they were recorded.
+    ///
+    /// [`Looprider::to_replay`] always returns a normalized replay, so this is mostly useful for
+    /// replays which have been edited or stitched together by hand.
+    pub fn normalize(&mut self) {
+        // Records are stored last frame first, so the original append order is the reverse of the
+        // storage order. The sort is stable, so records sharing a frame stay in append order.
+        let mut records = self.records.drain(..).rev().collect::<Vec<_>>();
+        records.sort_by_key(|record| record.record);
+
+        let mut merged: Vec<Record<E>> = Vec::with_capacity(records.len());
+        for record in records {
+            match merged.last_mut() {
+                Some(last) if last.record == record.record => {
+                    last.events.extend(record.events);
+                    last.dt = record.dt.or(last.dt);
+                }
+                _ => merged.push(record),
+            }
+        }
+
+        merged.reverse();
+        self.records = merged;
+    }
+}
+
+/// A single frame's worth of events, as recorded by a [`Looprider`] in "record" mode.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-struct Record<E: LoopriderEvent> {
+pub struct Record<E: LoopriderEvent> {
     record: u64,
     events: Vec<E>,
+    // Missing from replays recorded before frame timings were, which is fine for self-describing
+    // formats; formats like `bincode` which don't store field names can't tell it's missing, and
+    // can't load those replays.
+    #[serde(default)]
+    dt: Option<f32>,
+}
+
+impl<E: LoopriderEvent> Record<E> {
+    /// The frame this record was recorded on; that is, how many times the [`Looprider`] had been
+    /// flushed before the flush which recorded it.
+    pub fn frame(&self) -> u64 {
+        self.record
+    }
+
+    /// The events recorded on this frame, in the order they were pushed.
+    pub fn events(&self) -> &[E] {
+        &self.events
+    }
+
+    /// The delta-time this frame was flushed with, if it was recorded with
+    /// [`Looprider::flush_with_dt`].
+    pub fn dt(&self) -> Option<f32> {
+        self.dt
+    }
 }
 
 /// Represents a subscription to a [`Looprider`]'s event stream.
@@ -47,12 +123,19 @@ enum LoopriderMode<E: LoopriderEvent> {
 /// call to `tick` while recording all events buffered that frame to a single "frame record".
 /// "Playback" mode ignores pushed events, and instead only pushes events coming from a previously
 /// recorded [`Replay`].
+///
+/// Replays only line up with the game if it runs the same updates during playback as it did while
+/// recording. With a fixed timestep that comes for free; otherwise, flush with
+/// [`Looprider::flush_with_dt`], which records each frame's delta-time along with its events, and
+/// drive the game with [`Looprider::frame_dt`] rather than its own delta-time. During playback,
+/// that's the delta-time the frame was recorded with.
 #[derive(Debug)]
 pub struct Looprider<E: LoopriderEvent> {
     channel: EventChannel<E>,
     mode: LoopriderMode<E>,
     records: Vec<Record<E>>,
     record: u64,
+    frame_dt: f32,
 }
 
 impl<E: LoopriderEvent> Looprider<E> {
@@ -63,34 +146,74 @@ impl<E: LoopriderEvent> Looprider<E> {
             mode: LoopriderMode::Record { buf: Vec::new() },
             records: Vec::new(),
             record: 0,
+            frame_dt: 0.,
         })
     }
 
     /// Construct a new [`Looprider`] in "playback" mode.
+    ///
+    /// Panics on invalid input, if the replay's records are out of order. Replays which come from
+    /// somewhere untrusted, such as a file on disk, should be loaded with
+    /// [`Looprider::try_playback`] instead.
     pub fn playback(replay: Replay<E>) -> Shared<Self> {
-        assert!(
-            replay
-                .records
-                .is_sorted_by(|r1, r2| Some(r1.record.cmp(&r2.record).reverse())),
-            "invalid replay data (out of order)"
-        );
+        Self::try_playback(replay).unwrap()
+    }
 
-        Shared::new(Self {
+    /// Construct a new [`Looprider`] in "playback" mode, returning an error describing the first
+    /// out-of-order frame if the replay's records are out of order.
+    pub fn try_playback(replay: Replay<E>) -> Result<Shared<Self>> {
+        // Records are stored last frame first, so that playback can pop them off the end.
+        if let Some(pair) = replay
+            .records
+            .windows(2)
+            .rev()
+            .find(|pair| pair[0].record < pair[1].record)
+        {
+            bail!(
+                "invalid replay data (out of order): frame {} comes after frame {}",
+                pair[0].record,
+                pair[1].record
+            );
+        }
+
+        Ok(Shared::new(Self {
             channel: EventChannel::new(),
             mode: LoopriderMode::Playback,
             records: replay.records,
             record: 0,
-        })
+            frame_dt: 0.,
+        }))
     }
 
     /// Convert this [`Looprider`] and all its buffered events to a [`Replay`] for playback and/or
-    /// serialization.
+    /// serialization. The replay is [normalized](Replay::normalize), so records which ended up
+    /// sharing a frame are merged.
     pub fn to_replay(&self) -> Option<Replay<E>> {
         match self.mode {
             LoopriderMode::Playback => None,
-            LoopriderMode::Record { .. } => Some(Replay {
-                records: self.records.iter().cloned().rev().collect(),
-            }),
+            LoopriderMode::Record { .. } => {
+                let mut replay = Replay {
+                    records: self.records.iter().cloned().rev().collect(),
+                };
+                replay.normalize();
+                Some(replay)
+            }
+        }
+    }
+
+    /// Drain every completed record from a [`Looprider`] in "record" mode, first frame first, so
+    /// that long recordings can be persisted incrementally rather than held in memory. The
+    /// looprider keeps recording afterwards, and frame numbers carry on from where they were, so
+    /// the drained records can later be put back together with the rest using
+    /// [`Replay::from_records`]. [`Looprider::to_replay`] only includes records which haven't been
+    /// drained.
+    ///
+    /// Events pushed since the last [`Looprider::flush`] aren't part of a completed record yet, and
+    /// are left in place. In "playback" mode, nothing is recorded and this returns nothing.
+    pub fn take_records(&mut self) -> Vec<Record<E>> {
+        match self.mode {
+            LoopriderMode::Playback => Vec::new(),
+            LoopriderMode::Record { .. } => self.records.drain(..).collect(),
         }
     }
 
@@ -98,35 +221,75 @@ impl<E: LoopriderEvent> Looprider<E> {
     /// counter. You can call this multiple times per frame, but it should be ensured that the
     /// number of times it is called per frame is deterministic - otherwise, replays will play back
     /// the wrong records at the wrong `flush` calls. It's worth noting that `Looprider` will have
-    /// serious problems with a game running at a variable delta-time; `Looprider` should *only* be
-    /// used with a fixed timestep.
+    /// serious problems with a game running at a variable delta-time; with this method,
+    /// `Looprider` should *only* be used with a fixed timestep. Games which can't guarantee one
+    /// should use [`Looprider::flush_with_dt`] instead.
     pub fn flush(&mut self) {
-        match &mut self.mode {
+        self.flush_inner(None);
+    }
+
+    /// Like [`Looprider::flush`], but in "record" mode also records `dt` as the delta-time of this
+    /// frame, even if no events were pushed on it. In "playback" mode, `dt` is only a fallback for
+    /// frames which were recorded without one, such as those of replays recorded with
+    /// [`Looprider::flush`]. Either way, [`Looprider::frame_dt`] returns the frame's delta-time
+    /// afterwards.
+    pub fn flush_with_dt(&mut self, dt: f32) {
+        self.flush_inner(Some(dt));
+    }
+
+    fn flush_inner(&mut self, dt: Option<f32>) {
+        let frame_dt = match &mut self.mode {
             LoopriderMode::Playback => {
+                let mut recorded_dt = None;
                 while matches!(self.records.last(), Some(record) if record.record <= self.record) {
                     let record = self.records.pop().unwrap();
                     assert_eq!(
                         record.record, self.record,
                         "a looprider tick was skipped! replay frame mismatch"
                     );
+                    recorded_dt = record.dt.or(recorded_dt);
                     self.channel.iter_write(record.events);
                 }
+                recorded_dt.or(dt)
             }
             LoopriderMode::Record { buf } => {
-                if !buf.is_empty() {
+                if !buf.is_empty() || dt.is_some() {
                     self.records.push(Record {
                         record: self.record,
                         events: buf.clone(),
+                        dt,
                     });
 
                     self.channel.drain_vec_write(buf);
                 }
+                dt
             }
+        };
+
+        if let Some(frame_dt) = frame_dt {
+            self.frame_dt = frame_dt;
         }
 
         self.record += 1;
     }
 
+    /// The delta-time of the most recently flushed frame: in "record" mode, the last `dt` passed to
+    /// [`Looprider::flush_with_dt`], and in "playback" mode, the delta-time the frame was recorded
+    /// with (or the fallback passed to `flush_with_dt`, for frames recorded without one.) Frames
+    /// without either keep the previous frame's delta-time, and before any, this is `0`.
+    pub fn frame_dt(&self) -> f32 {
+        self.frame_dt
+    }
+
+    /// Whether a [`Looprider`] in "playback" mode has played back every record in its replay. In
+    /// "record" mode there's no end to reach, so this is always `false`.
+    pub fn is_finished(&self) -> bool {
+        match self.mode {
+            LoopriderMode::Playback => self.records.is_empty(),
+            LoopriderMode::Record { .. } => false,
+        }
+    }
+
     /// Create a subscription handle to the event stream.
     pub fn register_reader(&mut self) -> LoopreaderId<E> {
         LoopreaderId(self.channel.register_reader())
@@ -159,11 +322,16 @@ where
     E: LoopriderEvent + for<'lua> FromLua<'lua> + for<'lua> ToLua<'lua>,
 {
     fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
-        methods.add_method_mut("flush", |_, this, ()| {
-            this.flush();
+        methods.add_method_mut("flush", |_, this, dt: Option<f32>| {
+            match dt {
+                Some(dt) => this.flush_with_dt(dt),
+                None => this.flush(),
+            }
             Ok(())
         });
 
+        methods.add_method("frame_dt", |_, this, ()| Ok(this.frame_dt()));
+
         methods.add_method_mut("register_reader", |_, this, ()| Ok(this.register_reader()));
 
         methods.add_method("read", |_, this, reader: LuaAnyUserData| {
@@ -177,3 +345,202 @@ where
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    impl LoopriderEvent for u32 {}
+
+    #[test]
+    fn out_of_order_replays_are_rejected() {
+        let looprider = Looprider::record();
+        {
+            let mut looprider = looprider.borrow_mut();
+            for event in 0..4 {
+                looprider.push(event);
+                looprider.flush();
+            }
+        }
+        let replay = looprider.borrow().to_replay().unwrap();
+        assert!(Looprider::try_playback(replay.clone()).is_ok());
+
+        // Swap the records for frames 1 and 2.
+        let mut corrupt = replay;
+        corrupt.records.swap(1, 2);
+        let message = Looprider::try_playback(corrupt).unwrap_err().to_string();
+        assert!(
+            message.contains("frame 1 comes after frame 2"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn records_sharing_a_frame_are_merged_in_append_order() {
+        let looprider = Looprider::record();
+        {
+            let mut looprider = looprider.borrow_mut();
+            looprider.push(0);
+            looprider.flush();
+            // Seek back a frame, so that the next record lands on frame 0 again.
+            looprider.record = 0;
+            looprider.push(1);
+            looprider.push(2);
+            looprider.flush();
+            looprider.push(3);
+            looprider.flush();
+        }
+
+        let replay = looprider.borrow().to_replay().unwrap();
+        let frames = replay
+            .records
+            .iter()
+            .map(|record| (record.record, record.events.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(frames, vec![(1, vec![3]), (0, vec![0, 1, 2])]);
+
+        // Hand-assembled replays are sorted as well as merged.
+        let mut stitched = Replay {
+            records: vec![
+                Record {
+                    record: 0,
+                    events: vec![1],
+                    dt: None,
+                },
+                Record {
+                    record: 2,
+                    events: vec![4],
+                    dt: None,
+                },
+                Record {
+                    record: 0,
+                    events: vec![0],
+                    dt: None,
+                },
+            ],
+        };
+        stitched.normalize();
+        let frames = stitched
+            .records
+            .iter()
+            .map(|record| (record.record, record.events.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(frames, vec![(2, vec![4]), (0, vec![0, 1])]);
+
+        let playback = Looprider::try_playback(stitched).unwrap();
+        let mut playback = playback.borrow_mut();
+        let mut reader = playback.register_reader();
+        playback.flush();
+        assert_eq!(
+            playback.read(&mut reader).copied().collect::<Vec<_>>(),
+            [0, 1]
+        );
+    }
+
+    #[test]
+    fn drained_records_reassemble_into_the_full_replay() {
+        let looprider = Looprider::record();
+        let mut drained = Vec::new();
+        {
+            let mut looprider = looprider.borrow_mut();
+            for frame in 0..5 {
+                // Frame 2 has no events, and so no record.
+                if frame != 2 {
+                    looprider.push(frame * 10);
+                    looprider.push(frame * 10 + 1);
+                }
+                looprider.flush();
+
+                if frame == 2 {
+                    drained = looprider.take_records();
+                    assert!(looprider.take_records().is_empty());
+                }
+            }
+        }
+
+        assert_eq!(
+            drained.iter().map(Record::frame).collect::<Vec<_>>(),
+            [0, 1]
+        );
+        let remaining = looprider.borrow().to_replay().unwrap().into_records();
+        assert_eq!(
+            remaining.iter().map(Record::frame).collect::<Vec<_>>(),
+            [3, 4]
+        );
+
+        let replay = Replay::from_records(drained.into_iter().chain(remaining));
+        let playback = Looprider::try_playback(replay).unwrap();
+        let mut playback = playback.borrow_mut();
+        let mut reader = playback.register_reader();
+        let mut frames = Vec::new();
+        for _ in 0..5 {
+            playback.flush();
+            frames.push(playback.read(&mut reader).copied().collect::<Vec<_>>());
+        }
+        assert_eq!(
+            frames,
+            vec![vec![0, 1], vec![10, 11], vec![], vec![30, 31], vec![40, 41]]
+        );
+    }
+
+    #[test]
+    fn playback_reports_recorded_frame_dts() -> Result<()> {
+        let dts = [1. / 60., 1. / 30., 1. / 144., 0.05, 1. / 60.];
+        let looprider = Looprider::record();
+        {
+            let mut looprider = looprider.borrow_mut();
+            for (frame, &dt) in dts.iter().enumerate() {
+                // Every frame's dt is recorded, whether or not it had any events.
+                if frame % 2 == 0 {
+                    looprider.push(frame as u32);
+                }
+                looprider.flush_with_dt(dt);
+                assert_eq!(looprider.frame_dt(), dt);
+            }
+        }
+
+        let replay = looprider.borrow().to_replay().unwrap();
+        let bytes = bincode::serialize(&replay)?;
+        let replay: Replay<u32> = bincode::deserialize(&bytes)?;
+
+        let playback = Looprider::try_playback(replay)?;
+        let mut playback = playback.borrow_mut();
+        let mut reader = playback.register_reader();
+        let mut played = Vec::new();
+        for _ in 0..dts.len() {
+            // The playback's own dt is ignored in favor of the recorded one.
+            playback.flush_with_dt(1.);
+            played.push((
+                playback.frame_dt(),
+                playback.read(&mut reader).copied().collect::<Vec<_>>(),
+            ));
+        }
+        assert_eq!(
+            played,
+            vec![
+                (dts[0], vec![0]),
+                (dts[1], vec![]),
+                (dts[2], vec![2]),
+                (dts[3], vec![]),
+                (dts[4], vec![4]),
+            ]
+        );
+
+        // Replays recorded before frame dts were have no `dt` field at all, and play back with
+        // whatever dt the playback is flushed with.
+        let lua = Lua::new();
+        let old_replay = lua
+            .load("return { records = { { record = 1, events = { 7 } } } }")
+            .eval::<LuaValue>()?;
+        let old_replay: Replay<u32> = lua.from_value(old_replay)?;
+        let playback = Looprider::try_playback(old_replay)?;
+        let mut playback = playback.borrow_mut();
+        playback.flush_with_dt(0.25);
+        playback.flush_with_dt(0.5);
+        assert_eq!(playback.frame_dt(), 0.5);
+        assert!(playback.is_finished());
+
+        Ok(())
+    }
+}
@@ -6,7 +6,7 @@ use hv_core::{
 use rustyline::{Config, EditMode, Editor};
 use std::{
     error::Error,
-    fmt::Write,
+    fmt::{self, Write},
     sync::{
         mpsc::{Receiver, Sender},
         Mutex,
@@ -58,6 +58,21 @@ impl Console {
         })
     }
 
+    /// Evaluate a line of console input, returning an echo of the input followed by either the
+    /// values it evaluated to or the error it raised.
+    pub fn eval(lua: &Lua, input: &str) -> Vec<ConsoleOutput> {
+        let mut outputs = vec![ConsoleOutput::Echo(input.to_owned())];
+        match lua.load(input).eval::<LuaMultiValue>() {
+            Ok(out) => outputs.extend(out.into_iter().map(|v| {
+                ConsoleOutput::Value(
+                    serde_json::to_string_pretty(&v).unwrap_or_else(|_| format!("{:?}", v)),
+                )
+            })),
+            Err(e) => outputs.push(ConsoleOutput::error(&e)),
+        }
+        outputs
+    }
+
     pub fn poll(&mut self, lua: &Lua) -> Result<()> {
         if let Some(start_data) = self.start_data.take() {
             start_data.into_inner().unwrap().go();
@@ -65,23 +80,9 @@ impl Console {
 
         for s in self.call_rx.lock().unwrap().try_iter() {
             let mut buf = String::new();
-            match lua.load(&s).eval::<LuaMultiValue>() {
-                Ok(out) => {
-                    for (i, v) in out.into_iter().enumerate() {
-                        if let Ok(json) = serde_json::to_string_pretty(&v) {
-                            writeln!(&mut buf, "[{}]prt: {}", i, json).unwrap();
-                        } else {
-                            writeln!(&mut buf, "[{}]dbg: {:?}", i, v).unwrap();
-                        }
-                    }
-                }
-                Err(e) => {
-                    writeln!(&mut buf, "err: {}", e)?;
-
-                    if let Some(source) = e.source() {
-                        writeln!(&mut buf, "caused by: {}", source)?;
-                    }
-                }
+            // The terminal already shows what was typed, so skip the echo.
+            for output in Self::eval(lua, &s).into_iter().skip(1) {
+                writeln!(&mut buf, "{}", output)?;
             }
 
             self.response_tx.lock().unwrap().send(buf).unwrap();
@@ -91,6 +92,52 @@ impl Console {
     }
 }
 
+/// A single piece of console output, kept structured so that a UI can style each kind differently.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConsoleOutput {
+    /// The input line being evaluated.
+    Echo(String),
+    /// One of the values the input evaluated to, formatted as JSON if possible.
+    Value(String),
+    /// An error raised while evaluating the input.
+    Error {
+        /// The error's own message.
+        msg: String,
+        /// The messages of every error in the error's `source()` chain, outermost first.
+        chain: Vec<String>,
+    },
+}
+
+impl ConsoleOutput {
+    /// Build an [`ConsoleOutput::Error`] from an error, walking its entire `source()` chain.
+    pub fn error(err: &(dyn Error + 'static)) -> Self {
+        let chain = std::iter::successors(err.source(), |e| e.source())
+            .map(ToString::to_string)
+            .collect();
+
+        ConsoleOutput::Error {
+            msg: err.to_string(),
+            chain,
+        }
+    }
+}
+
+impl fmt::Display for ConsoleOutput {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConsoleOutput::Echo(input) => write!(f, "> {}", input),
+            ConsoleOutput::Value(value) => write!(f, "prt: {}", value),
+            ConsoleOutput::Error { msg, chain } => {
+                write!(f, "err: {}", msg)?;
+                for cause in chain {
+                    write!(f, "\ncaused by: {}", cause)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
 impl LuaUserData for Console {}
 
 impl LuaResource for Console {
@@ -123,3 +170,39 @@ impl Plugin for HvConsolePlugin {
 }
 
 hv_core::plugin!(HvConsolePlugin);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eval_error_walks_whole_source_chain() {
+        let lua = Lua::new();
+        let fail = lua
+            .create_function(|_, ()| -> LuaResult<()> {
+                Err(anyhow!("tile not found"))
+                    .context("couldn't load level")
+                    .to_lua_err()
+            })
+            .unwrap();
+        lua.globals().set("fail", fail).unwrap();
+
+        let outputs = Console::eval(&lua, "fail()");
+        assert_eq!(outputs[0], ConsoleOutput::Echo("fail()".to_owned()));
+        match &outputs[1] {
+            ConsoleOutput::Error { chain, .. } => {
+                assert!(chain.len() >= 2, "chain too short: {:?}", chain);
+                assert!(chain.last().unwrap().contains("tile not found"));
+            }
+            other => panic!("expected an error, got {:?}", other),
+        }
+
+        assert_eq!(
+            Console::eval(&lua, "return 'hi'"),
+            [
+                ConsoleOutput::Echo("return 'hi'".to_owned()),
+                ConsoleOutput::Value("\"hi\"".to_owned()),
+            ]
+        );
+    }
+}
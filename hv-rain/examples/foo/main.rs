@@ -1,11 +1,11 @@
 use hv_core::{
     conf::Conf,
-    engine::{Engine, EventHandler, LuaExt},
+    engine::{Engine, EventHandler},
     filesystem::Filesystem,
     input::{InputBinding, InputState, KeyCode, MouseButton},
     prelude::*,
     spaces::{
-        object_table::{ObjectTableComponent, ObjectTableRegistry, UpdateHookComponent},
+        object_table::{call_method_on_all, UpdateHookComponent},
         Space, Spaces,
     },
 };
@@ -67,7 +67,7 @@ impl Game {
         {
             let mut gfx = gfx_resource.lock();
 
-            gfx.set_projection(Orthographic3::new(0., 640., 0., 480., -1., 1.).to_homogeneous());
+            gfx.set_default_projection(640., 480.);
 
             mesh = MeshBuilder::new(gfx.state.null_texture.clone())
                 .polygon(
@@ -112,33 +112,7 @@ impl Game {
                 pos.integrate_mut(vel, dt);
             }
 
-            {
-                let lua = engine.lua();
-                let object_table_registry = lua.get_resource::<ObjectTableRegistry>()?;
-                for (object, (object_table,)) in self
-                    .space
-                    .borrow_mut()
-                    .query_mut::<(&ObjectTableComponent,)>()
-                    .with::<UpdateHookComponent>()
-                {
-                    let maybe_table = object_table_registry
-                        .borrow()
-                        .by_index(object_table.index())
-                        .map(|entry| lua.registry_value::<LuaTable>(entry.key()))
-                        .transpose()?;
-
-                    if let Some(table) = maybe_table {
-                        if let Some(update) = table.get::<_, Option<LuaFunction>>("update")? {
-                            update.call(table)?;
-                        }
-                    } else {
-                        log::error!(
-                            "{:?} has object table component but no corresponding table entry",
-                            object
-                        );
-                    }
-                }
-            }
+            call_method_on_all::<UpdateHookComponent, _>(&engine.lua(), &self.space, "update", ())?;
 
             engine
                 .lua()
@@ -19,6 +19,10 @@ function Danmaku:update(dt) self._danmaku:update(dt) end
 
 function Danmaku:draw() self._danmaku:draw() end
 
+function Danmaku:snapshot() return self._danmaku:snapshot() end
+
+function Danmaku:restore(snapshot) self._danmaku:restore(snapshot) end
+
 local Barrage = class("Barrage")
 
 function Barrage:new(danmaku)
@@ -37,11 +41,12 @@ function Barrage:init(inner)
     self._inner = inner
 end
 
-local barrage_0arg_keys = { "push", "pop", "fire", "flush" }
+local barrage_0arg_keys = { "push", "pop", "fire", "flush", "detach" }
 
 local barrage_1arg_keys = {
-    "set_lua_value", "prepend_origin", "append_origin", "prepend_linear_tx", "append_linear_tx",
-    "set_linear_tx", "add_polar_tx", "set_polar_tx", "add_linear_velocity", "set_linear_velocity",
+    "anchor_to", "set_lua_value", "prepend_origin", "append_origin", "prepend_linear_tx",
+    "append_linear_tx", "set_linear_tx", "add_polar_tx", "set_polar_tx", "add_linear_velocity",
+    "set_linear_velocity",
     "add_linear_velocity_wrt_world", "set_linear_velocity_wrt_world", "add_polar_velocity",
     "set_polar_velocity", "add_linear_acceleration", "set_linear_acceleration",
     "add_linear_acceleration_wrt_world", "set_linear_acceleration_wrt_world",
@@ -16,5 +16,6 @@ return {
     sequence = bind("sequence"),
     sprite = bind("sprite"),
     sprite_sequence = bind("sprite_sequence"),
+    transition = bind("transition"),
     wait = bind("wait"),
 }
@@ -1,6 +1,6 @@
 use hv_core::{engine::LuaResource, mlua::prelude::*};
 use hv_friends::graphics::{
-    pipeline::Pipeline,
+    pipeline::{Pipeline, PipelineLayout, VertexFormat},
     sprite::{AnimationState, CachedSpriteSheet},
     CachedTexture, SpriteBatch,
 };
@@ -30,6 +30,17 @@ pub struct ProjectileSprite {
 
 impl LuaUserData for ProjectileSprite {}
 
+/// The layout of the color bullet pipeline: the default layout, plus each bullet's age in seconds
+/// (`a_Age`) and its [`kind`](crate::pattern::Parameters::kind) (`a_Kind`) as custom instance
+/// attributes, in that order.
+pub fn bullet_pipeline_layout() -> PipelineLayout {
+    PipelineLayout::with_custom_instance_attributes(vec![
+        ("a_Age", VertexFormat::Float1),
+        ("a_Kind", VertexFormat::Float1),
+    ])
+    .expect("bullet attributes fit in an instance's custom data")
+}
+
 pub struct ProjectileSpriteBatch {
     pub sheet: CachedSpriteSheet,
     pub sprites: SpriteBatch<CachedTexture>,
@@ -77,3 +88,33 @@ impl LuaUserData for ProjectileSpriteRegistry {}
 impl LuaResource for ProjectileSpriteRegistry {
     const REGISTRY_KEY: &'static str = "HV_DANMAKU_PROJECTILE_SPRITE_REGISTRY";
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bullet_layout_matches_bullet_shader() {
+        let vertex = include_str!("graphics/bullet_es300.glslv");
+        let layout = bullet_pipeline_layout();
+        for attribute in &layout.attributes {
+            let ty = match attribute.ty {
+                VertexFormat::Float1 => "float",
+                VertexFormat::Float2 => "vec2",
+                VertexFormat::Float3 => "vec3",
+                VertexFormat::Float4 => "vec4",
+                VertexFormat::Mat4 => "mat4",
+                other => panic!("unexpected attribute format {:?}", other),
+            };
+            let declaration = format!("in mediump {} {};", ty, attribute.name);
+            // The default layout's `a_Page` isn't needed by the bullet shader.
+            assert!(
+                vertex.contains(&declaration) || attribute.name == "a_Page",
+                "bullet shader is missing `{}`",
+                declaration
+            );
+        }
+
+        assert_eq!(layout.custom_instance_floats(), 2);
+    }
+}
@@ -5,6 +5,7 @@ use smallvec::SmallVec;
 use hv_core::{
     components::DynamicComponentConstructor,
     engine::{Engine, LuaExt, WeakResourceCache},
+    hecs::EntityBuilder,
     plugins::Plugin,
     prelude::*,
     shared::Weak,
@@ -12,7 +13,7 @@ use hv_core::{
 };
 use hv_friends::{
     graphics::{
-        pipeline::{Pipeline, PipelineLayout, Shader, ShaderLayout},
+        pipeline::{Pipeline, Shader, ShaderLayout},
         sprite::CachedSpriteSheet,
         CachedTexture, Color, DrawableMut, Graphics, GraphicsLock, GraphicsLockExt, Instance,
         SpriteBatch,
@@ -22,9 +23,13 @@ use hv_friends::{
 
 use crate::{
     graphics::{
-        ProjectileSprite, ProjectileSpriteBatch, ProjectileSpriteBatchId, ProjectileSpriteRegistry,
+        bullet_pipeline_layout, ProjectileSprite, ProjectileSpriteBatch, ProjectileSpriteBatchId,
+        ProjectileSpriteRegistry,
+    },
+    pattern::{
+        Barrage, LuaComponentFunctionShotType, ParameterOverrides, ParameterRegistry, Parameters,
+        ShotTypeRegistry,
     },
-    pattern::{Barrage, LuaComponentFunctionShotType, Parameters, ShotTypeRegistry},
     sm::{StateIndex, StateMachine, StateRegistry},
 };
 
@@ -40,6 +45,7 @@ pub struct ProjectileGroup(pub u8);
 
 #[derive(Debug, Clone, Copy)]
 pub struct ProjectileState {
+    /// How long the projectile has been alive, in seconds.
     pub time: f32,
 
     pub origin: Isometry2<f32>,
@@ -54,6 +60,7 @@ pub struct ProjectileState {
 
     pub color: Color,
     pub sprite: Option<ProjectileSprite>,
+    pub kind: u32,
 
     sm_init: bool,
     kill: bool,
@@ -72,6 +79,7 @@ impl ProjectileState {
             polar_accel: params.polar_accel,
             color: params.color,
             sprite: params.sprite,
+            kind: params.kind,
             sm_init: false,
             kill: false,
         }
@@ -113,6 +121,77 @@ pub struct ProjectileTrail {
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Bullet(Object);
 
+/// A single projectile as captured by [`Danmaku::snapshot`].
+#[derive(Clone)]
+struct ProjectileSnapshot {
+    state: ProjectileState,
+    state_machine: Option<StateMachine>,
+    linear_velocity: bool,
+    polar_velocity: bool,
+    linear_acceleration: bool,
+    polar_acceleration: bool,
+}
+
+impl ProjectileSnapshot {
+    fn build(&self) -> EntityBuilder {
+        let mut builder = EntityBuilder::new();
+        builder.add(self.state);
+
+        if let Some(state_machine) = self.state_machine.clone() {
+            builder.add(state_machine);
+        }
+
+        if self.linear_velocity {
+            builder.add(LinearVelocity);
+        }
+
+        if self.polar_velocity {
+            builder.add(PolarVelocity);
+        }
+
+        if self.linear_acceleration {
+            builder.add(LinearAcceleration);
+        }
+
+        if self.polar_acceleration {
+            builder.add(PolarAcceleration);
+        }
+
+        builder
+    }
+}
+
+/// The state of every projectile in a [`Danmaku`]'s space at some point in time, for rewinding
+/// the bullet field with [`Danmaku::restore`].
+///
+/// Snapshots are kept in memory rather than serialized, because the extra state of composite state
+/// machine states (like [`sm::Sequence`] and [`sm::Parallel`]) can't go through serde. Everything
+/// the danmaku system knows about is captured - [`ProjectileState`] (including its sprite and
+/// animation state), [`StateMachine`], and the movement marker components - but any other
+/// components added to projectiles by shot types are not.
+#[derive(Clone)]
+pub struct DanmakuSnapshot {
+    projectiles: Vec<ProjectileSnapshot>,
+}
+
+impl DanmakuSnapshot {
+    /// The number of projectiles in the snapshot.
+    pub fn len(&self) -> usize {
+        self.projectiles.len()
+    }
+
+    /// Whether the snapshot has no projectiles in it.
+    pub fn is_empty(&self) -> bool {
+        self.projectiles.is_empty()
+    }
+}
+
+impl LuaUserData for DanmakuSnapshot {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("len", |_, this, ()| Ok(this.len()));
+    }
+}
+
 pub struct Danmaku {
     space: Weak<Space>,
 }
@@ -157,6 +236,8 @@ impl Danmaku {
                     Option<&PolarVelocity>,
                 ),
             )>() {
+                projectile.time += dt;
+
                 if maybe_lin_accel.is_some() {
                     projectile.linear_vel += projectile.linear_accel * dt;
                 }
@@ -191,7 +272,8 @@ impl Danmaku {
                             .translate2(tx.translation.vector)
                             .rotate2(tx.rotation.angle())
                             .translate2(frame.offset)
-                            .color(projectile.color),
+                            .color(projectile.color)
+                            .with_custom([projectile.time, projectile.kind as f32]),
                     );
                 }
             }
@@ -200,6 +282,84 @@ impl Danmaku {
         Ok(())
     }
 
+    /// Copy out the state of every projectile currently in the space, ordered by slot. Since the
+    /// result is a copy, the space isn't borrowed while it's being looked at, so callers (Lua
+    /// callbacks especially) are free to spawn and despawn projectiles as they go; changes to be
+    /// made to the projectiles themselves should be collected and applied afterwards, for example
+    /// with [`Danmaku::add_linear_velocities`].
+    pub fn projectiles(&self) -> Vec<(Object, ProjectileState)> {
+        let space = &mut self.space.borrow_mut();
+        let mut projectiles = space
+            .query_mut::<&ProjectileState>()
+            .map(|(object, projectile)| (object, *projectile))
+            .collect::<Vec<_>>();
+        projectiles.sort_by_key(|(object, _)| object.slot());
+        projectiles
+    }
+
+    /// Add to the linear velocities of projectiles in bulk. Objects which have since been
+    /// despawned or aren't projectiles are skipped. Only projectiles with [`LinearVelocity`]
+    /// actually move according to their linear velocity.
+    pub fn add_linear_velocities(&self, deltas: impl IntoIterator<Item = (Object, Vector2<f32>)>) {
+        let space = &mut self.space.borrow_mut();
+        for (object, delta) in deltas {
+            if let Ok(projectile) = space.query_one_mut::<&mut ProjectileState>(object) {
+                projectile.linear_vel.linear += delta;
+            }
+        }
+    }
+
+    /// Capture the state of every projectile currently in the space.
+    pub fn snapshot(&self) -> DanmakuSnapshot {
+        let space = &mut self.space.borrow_mut();
+        let projectiles = space
+            .query_mut::<(
+                &ProjectileState,
+                Option<&StateMachine>,
+                (
+                    Option<&LinearVelocity>,
+                    Option<&PolarVelocity>,
+                    Option<&LinearAcceleration>,
+                    Option<&PolarAcceleration>,
+                ),
+            )>()
+            .map(
+                |(_, (state, state_machine, (lin_vel, polar_vel, lin_accel, polar_accel)))| {
+                    ProjectileSnapshot {
+                        state: *state,
+                        state_machine: state_machine.cloned(),
+                        linear_velocity: lin_vel.is_some(),
+                        polar_velocity: polar_vel.is_some(),
+                        linear_acceleration: lin_accel.is_some(),
+                        polar_acceleration: polar_accel.is_some(),
+                    }
+                },
+            )
+            .collect();
+
+        DanmakuSnapshot { projectiles }
+    }
+
+    /// Despawn every projectile currently in the space, and respawn the projectiles from a
+    /// snapshot in their place.
+    pub fn restore(&self, snapshot: &DanmakuSnapshot) -> Result<()> {
+        let space = &mut self.space.borrow_mut();
+        let current = space
+            .query_mut::<&ProjectileState>()
+            .map(|(object, _)| object)
+            .collect::<Vec<_>>();
+
+        for object in current {
+            space.despawn(object)?;
+        }
+
+        for projectile in &snapshot.projectiles {
+            space.spawn(projectile.build().build());
+        }
+
+        Ok(())
+    }
+
     pub fn draw(&self, lua: &Lua, gfx: &mut Graphics) -> Result<()> {
         let sprite_registry_resource = lua.get_resource::<ProjectileSpriteRegistry>()?;
         let sprite_registry = &mut sprite_registry_resource.borrow_mut();
@@ -230,6 +390,51 @@ impl LuaUserData for Danmaku {
             Ok(())
         });
 
+        methods.add_method("snapshot", |_, this, ()| Ok(this.snapshot()));
+
+        methods.add_method("restore", |_, this, snapshot: LuaAnyUserData| {
+            this.restore(&snapshot.borrow::<DanmakuSnapshot>()?)
+                .to_lua_err()
+        });
+
+        // Calls the function with the position, linear velocity, and color of every projectile.
+        methods.add_method("for_each", |_, this, f: LuaFunction| {
+            for (_, projectile) in this.projectiles() {
+                let translation = projectile.tx().translation;
+                let velocity = projectile.linear_vel.linear;
+                f.call::<_, ()>((
+                    translation.x,
+                    translation.y,
+                    velocity.x,
+                    velocity.y,
+                    projectile.color,
+                ))?;
+            }
+            Ok(())
+        });
+
+        // Calls the function with the position and linear velocity of every projectile, and
+        // accelerates each projectile by the acceleration it returns (if any) for `dt` seconds.
+        methods.add_method("apply_force", |_, this, (f, dt): (LuaFunction, f32)| {
+            let mut deltas = Vec::new();
+            for (object, projectile) in this.projectiles() {
+                let translation = projectile.tx().translation;
+                let velocity = projectile.linear_vel.linear;
+                let (ax, ay) = f.call::<_, (Option<f32>, Option<f32>)>((
+                    translation.x,
+                    translation.y,
+                    velocity.x,
+                    velocity.y,
+                ))?;
+                if ax.is_some() || ay.is_some() {
+                    let accel = Vector2::new(ax.unwrap_or(0.), ay.unwrap_or(0.));
+                    deltas.push((object, accel * dt));
+                }
+            }
+            this.add_linear_velocities(deltas);
+            Ok(())
+        });
+
         methods.add_method("draw", |lua, this, ()| {
             let gfx_lock = lua.get_resource::<GraphicsLock>()?;
             this.draw(lua, &mut gfx_lock.lock()).to_lua_err()?;
@@ -238,6 +443,9 @@ impl LuaUserData for Danmaku {
     }
 }
 
+/// The number of bullets a projectile sprite batch has room for when no capacity is given.
+pub const DEFAULT_PROJECTILE_BATCH_CAPACITY: usize = 1024;
+
 struct HvRainPlugin;
 
 impl Plugin for HvRainPlugin {
@@ -260,6 +468,9 @@ impl Plugin for HvRainPlugin {
         let sprite_registry = engine.insert(ProjectileSpriteRegistry::new());
         lua.insert_resource(sprite_registry.clone())?;
 
+        let parameter_registry = engine.insert(ParameterRegistry::new());
+        lua.insert_resource(parameter_registry.clone())?;
+
         let create_danmaku_object =
             lua.create_function_mut(move |_lua, space| Danmaku::new(&space).to_lua_err())?;
 
@@ -274,14 +485,25 @@ impl Plugin for HvRainPlugin {
         let weak_registry = Shared::downgrade(&sprite_registry);
         let create_projectile_sprite_batch = lua.create_function(
             move |lua,
-                  (texture, sheet, pipeline): (
+                  (texture, sheet, pipeline, capacity): (
                 CachedTexture,
                 CachedSpriteSheet,
                 Option<Pipeline>,
+                Option<usize>,
             )| {
                 let registry = &mut weak_registry.borrow_mut();
                 let gfx_lock = lua.get_resource::<GraphicsLock>()?;
-                let batch = SpriteBatch::new(&mut gfx_lock.lock(), texture);
+                let gfx = &mut gfx_lock.lock();
+                // Bullet batches are cleared and refilled every frame, so reserving room for as
+                // many bullets as the game expects avoids regrowing the batch as the field fills.
+                let mut batch = SpriteBatch::with_capacity(
+                    gfx,
+                    texture,
+                    capacity.unwrap_or(DEFAULT_PROJECTILE_BATCH_CAPACITY),
+                );
+                if let Some(pipeline) = pipeline.as_ref() {
+                    batch.set_custom_layout(gfx, &pipeline.layout);
+                }
 
                 Ok(ProjectileSpriteBatchId(registry.defs.insert(
                     ProjectileSpriteBatch {
@@ -295,6 +517,17 @@ impl Plugin for HvRainPlugin {
 
         let get_state_registry = lua.create_function(move |_, ()| Ok(state_registry.clone()))?;
 
+        let weak_registry = Shared::downgrade(&parameter_registry);
+        let register_parameter_preset = lua.create_function(
+            move |_, (name, params): (String, Option<ParameterOverrides>)| {
+                let params = params
+                    .unwrap_or_default()
+                    .applied_to(&Parameters::default());
+                weak_registry.borrow_mut().register(name, params);
+                Ok(())
+            },
+        )?;
+
         let linear_velocity_component_constructor =
             DynamicComponentConstructor::new(|_: &Lua, _| Ok(LinearVelocity));
         let polar_velocity_component_constructor =
@@ -328,7 +561,7 @@ impl Plugin for HvRainPlugin {
                     .to_lua_err()?;
 
                     let bullet_pipeline =
-                        Pipeline::new(gfx, PipelineLayout::default(), bullet_shader, None)
+                        Pipeline::new(gfx, bullet_pipeline_layout(), bullet_shader, None)
                             .to_lua_err()?;
                     color_bullet_pipeline = Some(bullet_pipeline.clone());
 
@@ -372,6 +605,7 @@ impl Plugin for HvRainPlugin {
                     state_machine_component_constructor = $state_machine_component_constructor,
                     projectile_sprite_component_constructor = $projectile_sprite_component_constructor,
                     get_state_registry = $get_state_registry,
+                    register_parameter_preset = $register_parameter_preset,
                     get_color_bullet_pipeline = $get_color_bullet_pipeline,
                     nil
                 }
@@ -392,3 +626,116 @@ impl Plugin for HvRainPlugin {
 hv_core::plugin!(HvRainPlugin);
 
 pub fn link_me() {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hv_core::spaces::Spaces;
+
+    fn positions(space: &Shared<Space>) -> Vec<(f32, f32)> {
+        let mut positions = space
+            .borrow_mut()
+            .query_mut::<&ProjectileState>()
+            .map(|(_, projectile)| {
+                let translation = projectile.tx().translation;
+                (translation.x, translation.y)
+            })
+            .collect::<Vec<_>>();
+        positions.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        positions
+    }
+
+    #[test]
+    fn snapshot_restores_bullet_field() {
+        let space = Spaces::new().create_space();
+        let danmaku = Danmaku::new(&space).unwrap();
+        let state_index = StateRegistry::new().insert(());
+
+        for i in 0..10 {
+            let params = Parameters {
+                origin: Isometry2::translation(i as f32, 2. * i as f32),
+                ..Parameters::default()
+            };
+            let mut state_machine = StateMachine::new(state_index);
+            state_machine.time = i as f32;
+            space.borrow_mut().spawn((
+                ProjectileState::from_parameters(&params),
+                state_machine,
+                LinearVelocity,
+            ));
+        }
+
+        let before = positions(&space);
+        let snapshot = danmaku.snapshot();
+        assert_eq!(snapshot.len(), 10);
+
+        danmaku
+            .restore(&DanmakuSnapshot {
+                projectiles: Vec::new(),
+            })
+            .unwrap();
+        assert!(positions(&space).is_empty());
+
+        danmaku.restore(&snapshot).unwrap();
+        assert_eq!(positions(&space), before);
+
+        let mut times = space
+            .borrow_mut()
+            .query_mut::<(&StateMachine, &LinearVelocity)>()
+            .map(|(_, (state_machine, _))| {
+                assert_eq!(state_machine.index, state_index);
+                state_machine.time
+            })
+            .collect::<Vec<_>>();
+        times.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(times, (0..10).map(|i| i as f32).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn lua_visits_each_projectile_once() -> Result<()> {
+        let space = Spaces::new().create_space();
+        for i in 0..3 {
+            let params = Parameters {
+                origin: Isometry2::translation(i as f32, 0.),
+                ..Parameters::default()
+            };
+            space
+                .borrow_mut()
+                .spawn((ProjectileState::from_parameters(&params), LinearVelocity));
+        }
+
+        let lua = Lua::new();
+        lua.globals().set("danmaku", Danmaku::new(&space)?)?;
+        lua.load(
+            r#"
+            local visits = {}
+            danmaku:for_each(function(x, y, vx, vy, color)
+                visits[x] = (visits[x] or 0) + 1
+                assert(y == 0 and vx == 0 and vy == 0 and color.a == 1)
+            end)
+            assert(visits[0] == 1 and visits[1] == 1 and visits[2] == 1)
+
+            -- A gravity well at the origin, which only pulls on bullets to its right.
+            danmaku:apply_force(function(x, y, vx, vy)
+                if x > 0 then
+                    return -x, nil
+                end
+            end, 0.5)
+            "#,
+        )
+        .exec()?;
+
+        let mut velocities = space
+            .borrow_mut()
+            .query_mut::<&ProjectileState>()
+            .map(|(_, projectile)| {
+                let translation = projectile.tx().translation;
+                (translation.x, projectile.linear_vel.linear.x)
+            })
+            .collect::<Vec<_>>();
+        velocities.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(velocities, vec![(0., 0.), (1., -0.5), (2., -1.)]);
+
+        Ok(())
+    }
+}
@@ -4,9 +4,9 @@ use hv_core::{
     hecs::EntityBuilder,
     prelude::*,
     shared::Weak,
-    spaces::Space,
+    spaces::{Object, Space},
 };
-use hv_friends::{graphics::Color, math::*};
+use hv_friends::{graphics::Color, math::*, Position};
 use std::collections::HashMap;
 use thunderdome::{Arena, Index};
 
@@ -117,6 +117,9 @@ pub struct Parameters {
 
     pub color: Color,
     pub sprite: Option<ProjectileSprite>,
+    /// A game-defined kind of bullet, passed to the color bullet shader so that it can vary its
+    /// look per kind.
+    pub kind: u32,
 
     pub lua_value: Option<Index>,
 }
@@ -136,12 +139,128 @@ impl Default for Parameters {
 
             color: Color::WHITE,
             sprite: None,
+            kind: 0,
 
             lua_value: None,
         }
     }
 }
 
+/// Overrides for some of the fields of a [`Parameters`], for instantiating a preset from a
+/// [`ParameterRegistry`]. Fields left as `None` keep the preset's value.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ParameterOverrides {
+    pub origin: Option<Isometry2<f32>>,
+
+    pub linear_tx: Option<Isometry2<f32>>,
+    pub linear_vel: Option<Velocity2<f32>>,
+    pub linear_accel: Option<Velocity2<f32>>,
+
+    pub polar_tx: Option<Isometry2<f32>>,
+    pub polar_vel: Option<Velocity2<f32>>,
+    pub polar_accel: Option<Velocity2<f32>>,
+
+    pub color: Option<Color>,
+    pub sprite: Option<ProjectileSprite>,
+    pub kind: Option<u32>,
+}
+
+impl ParameterOverrides {
+    /// Overwrite every field of `params` which has an override.
+    pub fn apply(&self, params: &mut Parameters) {
+        macro_rules! apply {
+            ($($field:ident),*) => {
+                $(if let Some(value) = self.$field {
+                    params.$field = value;
+                })*
+            };
+        }
+
+        apply!(
+            origin,
+            linear_tx,
+            linear_vel,
+            linear_accel,
+            polar_tx,
+            polar_vel,
+            polar_accel,
+            color,
+            kind
+        );
+
+        if let Some(sprite) = self.sprite {
+            params.sprite = Some(sprite);
+        }
+    }
+
+    /// Apply these overrides to a copy of `params`.
+    pub fn applied_to(&self, params: &Parameters) -> Parameters {
+        let mut params = *params;
+        self.apply(&mut params);
+        params
+    }
+}
+
+impl<'lua> FromLua<'lua> for ParameterOverrides {
+    /// Read overrides from a table with any of the fields of [`ParameterOverrides`]. Transforms
+    /// (`origin`, `linear_tx` and `polar_tx`) must be 2D isometries.
+    fn from_lua(lua_value: LuaValue<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        let table = LuaTable::from_lua(lua_value, lua)?;
+        let isometry = |key: &str| -> LuaResult<Option<Isometry2<f32>>> {
+            table
+                .get::<_, Option<Tx<f32>>>(key)?
+                .map(|tx| {
+                    tx.to_isometry2()
+                        .ok_or_else(|| anyhow!("`{}` must be a 2D isometry", key))
+                        .to_lua_err()
+                })
+                .transpose()
+        };
+
+        Ok(Self {
+            origin: isometry("origin")?,
+            linear_tx: isometry("linear_tx")?,
+            linear_vel: table.get("linear_vel")?,
+            linear_accel: table.get("linear_accel")?,
+            polar_tx: isometry("polar_tx")?,
+            polar_vel: table.get("polar_vel")?,
+            polar_accel: table.get("polar_accel")?,
+            color: table.get("color")?,
+            sprite: table.get("sprite")?,
+            kind: table.get("kind")?,
+        })
+    }
+}
+
+/// Named [`Parameters`] templates ("presets"), so that common shots like a five-way spread can be
+/// defined once and fired from any [`Barrage`] with [`Barrage::fire_preset`].
+#[derive(Debug, Default)]
+pub struct ParameterRegistry {
+    presets: HashMap<String, Parameters>,
+}
+
+impl LuaResource for ParameterRegistry {
+    const REGISTRY_KEY: &'static str = "HV_DANMAKU_PARAMETER_REGISTRY";
+}
+
+impl LuaUserData for ParameterRegistry {}
+
+impl ParameterRegistry {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a preset, replacing any existing preset with the same name.
+    pub fn register(&mut self, name: impl Into<String>, params: Parameters) {
+        self.presets.insert(name.into(), params);
+    }
+
+    /// Look up a preset by name.
+    pub fn get(&self, name: &str) -> Option<&Parameters> {
+        self.presets.get(name)
+    }
+}
+
 #[derive(Debug, Clone, Copy, Default)]
 struct Frame {
     params: Parameters,
@@ -153,6 +272,8 @@ pub struct Barrage {
     stack: Vec<Frame>,
     batches: HashMap<ShotTypeIndex, Vec<Parameters>>,
     lua_slots: Arena<LuaRegistryKey>,
+    anchor: Option<Object>,
+    anchor_tx: Translation2<f32>,
 }
 
 impl Barrage {
@@ -162,6 +283,39 @@ impl Barrage {
             stack: vec![Default::default()],
             batches: HashMap::new(),
             lua_slots: Arena::new(),
+            anchor: None,
+            anchor_tx: Translation2::identity(),
+        }
+    }
+
+    /// Fire relative to an object's [`Position`], so that the origins of fired shots follow the
+    /// object as it moves. Only the translation of the object's position is used. The object must
+    /// live in the same space as the barrage.
+    ///
+    /// If the object is despawned or loses its [`Position`], the barrage detaches itself and keeps
+    /// firing from the last position it saw.
+    pub fn anchor_to(&mut self, object: Object) {
+        self.anchor = Some(object);
+        self.update_anchor();
+    }
+
+    /// Stop following the anchor object, if any. Shots keep being fired relative to its last known
+    /// position.
+    pub fn detach(&mut self) {
+        self.anchor = None;
+    }
+
+    /// The object this barrage is currently anchored to, if any.
+    pub fn anchor(&self) -> Option<Object> {
+        self.anchor
+    }
+
+    fn update_anchor(&mut self) {
+        if let Some(object) = self.anchor {
+            match self.space.borrow().get::<Position>(object) {
+                Ok(position) => self.anchor_tx = position.0.translation,
+                Err(_) => self.anchor = None,
+            }
         }
     }
 
@@ -292,12 +446,47 @@ impl Barrage {
         self.top_params_mut().sprite = *sprite;
     }
 
+    pub fn set_kind(&mut self, kind: u32) {
+        self.top_params_mut().kind = kind;
+    }
+
     pub fn fire(&mut self) {
+        self.update_anchor();
+        let top = self.stack.last().expect("empty stack");
+        let mut params = top.params;
+        params.origin = self.anchor_tx * params.origin;
+        self.batches
+            .entry(top.shot_type.expect("no shot type set"))
+            .or_default()
+            .push(params);
+    }
+
+    /// Fire a shot using a preset from `registry` in place of the current parameters, with
+    /// `overrides` applied on top of it. Fields without an override keep the preset's values; the
+    /// preset's origin is relative to the barrage's current origin (and anchor), so presets fire
+    /// from wherever the barrage is. The current shot type and Lua value are used as with
+    /// [`Barrage::fire`].
+    pub fn fire_preset(
+        &mut self,
+        registry: &ParameterRegistry,
+        name: &str,
+        overrides: &ParameterOverrides,
+    ) -> Result<()> {
+        let preset = registry
+            .get(name)
+            .ok_or_else(|| anyhow!("no such parameter preset `{}`", name))?;
+
+        self.update_anchor();
         let top = self.stack.last().expect("empty stack");
+        let mut params = overrides.applied_to(preset);
+        params.origin = self.anchor_tx * top.params.origin * params.origin;
+        params.lua_value = top.params.lua_value;
         self.batches
             .entry(top.shot_type.expect("no shot type set"))
             .or_default()
-            .push(top.params);
+            .push(params);
+
+        Ok(())
     }
 
     pub fn flush(&mut self, lua: &Lua) -> Result<()> {
@@ -332,6 +521,29 @@ impl LuaUserData for Barrage {
             Ok(())
         });
 
+        methods.add_method_mut(
+            "fire_preset",
+            |lua, this, (name, overrides): (LuaString, Option<ParameterOverrides>)| {
+                let registry = lua.get_resource::<ParameterRegistry>()?;
+                this.fire_preset(
+                    &registry.borrow(),
+                    name.to_str()?,
+                    &overrides.unwrap_or_default(),
+                )
+                .to_lua_err()
+            },
+        );
+
+        methods.add_method_mut("anchor_to", |_, this, object| {
+            this.anchor_to(object);
+            Ok(())
+        });
+
+        methods.add_method_mut("detach", |_, this, ()| {
+            this.detach();
+            Ok(())
+        });
+
         methods.add_method_mut("set_lua_value", |lua, this, lua_value: LuaValue| {
             this.set_lua_value(lua.create_registry_value(lua_value)?);
             Ok(())
@@ -454,9 +666,116 @@ impl LuaUserData for Barrage {
             Ok(())
         });
 
+        methods.add_method_mut("set_kind", |_, this, kind| {
+            this.set_kind(kind);
+            Ok(())
+        });
+
         methods.add_method_mut("flush", |lua, this, ()| {
             this.flush(lua).to_lua_err()?;
             Ok(())
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hv_core::spaces::Spaces;
+
+    struct NullShotType;
+
+    impl ShotType for NullShotType {
+        fn spawn(
+            &self,
+            _lua: &Lua,
+            _slots: &Arena<LuaRegistryKey>,
+            _space: &mut Space,
+            _shots: &[Parameters],
+        ) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn fired_translations(barrage: &Barrage, shot_type: ShotTypeIndex) -> Vec<Vector2<f32>> {
+        barrage.batches[&shot_type]
+            .iter()
+            .map(|params| params.origin.translation.vector)
+            .collect()
+    }
+
+    #[test]
+    fn anchored_origins_follow_the_anchor() {
+        let space = Spaces::new().create_space();
+        let turret = space
+            .borrow_mut()
+            .spawn((Position(Position2::translation(10., 5.)),));
+        let shot_type = ShotTypeRegistry::new().register(Box::new(NullShotType));
+
+        let mut barrage = Barrage::new(&space);
+        barrage.set_shot_type(shot_type);
+        barrage.append_origin(&Isometry2::translation(1., 0.));
+        barrage.anchor_to(turret);
+        barrage.fire();
+
+        space.borrow().get_mut::<Position>(turret).unwrap().0 = Position2::translation(20., -5.);
+        barrage.fire();
+
+        // Once the turret is gone, the barrage stays where the turret was last seen.
+        space.borrow_mut().despawn(turret).unwrap();
+        barrage.fire();
+        assert_eq!(barrage.anchor(), None);
+
+        assert_eq!(
+            fired_translations(&barrage, shot_type),
+            vec![
+                Vector2::new(11., 5.),
+                Vector2::new(21., -5.),
+                Vector2::new(21., -5.)
+            ]
+        );
+    }
+
+    #[test]
+    fn presets_fire_with_partial_overrides() {
+        let space = Spaces::new().create_space();
+        let shot_type = ShotTypeRegistry::new().register(Box::new(NullShotType));
+
+        let mut registry = ParameterRegistry::new();
+        let spread = Parameters {
+            origin: Isometry2::translation(0., 2.),
+            linear_vel: Velocity2::new(Vector2::new(0., -60.), 0.),
+            color: Color::new(1., 0., 0., 1.),
+            kind: 3,
+            ..Parameters::default()
+        };
+        registry.register("spread5", spread);
+
+        let mut barrage = Barrage::new(&space);
+        barrage.set_shot_type(shot_type);
+        barrage.append_origin(&Isometry2::translation(10., 0.));
+        barrage
+            .fire_preset(
+                &registry,
+                "spread5",
+                &ParameterOverrides {
+                    kind: Some(7),
+                    ..ParameterOverrides::default()
+                },
+            )
+            .unwrap();
+        assert!(barrage
+            .fire_preset(&registry, "spiral", &ParameterOverrides::default())
+            .is_err());
+
+        let fired = barrage.batches[&shot_type].as_slice();
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[0].kind, 7);
+        assert_eq!(fired[0].linear_vel.linear, spread.linear_vel.linear);
+        assert_eq!(fired[0].color, spread.color);
+        // The preset's origin is relative to the barrage's.
+        assert_eq!(fired[0].origin.translation.vector, Vector2::new(10., 2.));
+        // The preset itself is left untouched.
+        assert_eq!(registry.get("spread5").unwrap().kind, 3);
+    }
+}
@@ -4,7 +4,7 @@ use hv_core::{
 };
 use hv_friends::math::*;
 use smallbox::{smallbox, space::S4, SmallBox};
-use std::{any::Any, sync::Arc};
+use std::{any::Any, collections::HashMap, sync::Arc};
 use thunderdome::{Arena, Index};
 
 use crate::{graphics::ProjectileSprite, ProjectileState};
@@ -49,6 +49,9 @@ impl dyn ExtraSmState {
 pub struct StateMachine {
     pub index: StateIndex,
     pub time: f32,
+    /// How long the current state has been active, in seconds. This is kept up to date by the
+    /// [`StateRegistry`] and is what time-based [`Guard`]s check against.
+    pub elapsed: f32,
 
     pub linear_velocity: Velocity2<f32>,
     pub polar_velocity: Velocity2<f32>,
@@ -61,6 +64,7 @@ impl Clone for StateMachine {
         Self {
             index: self.index,
             time: self.time,
+            elapsed: self.elapsed,
             linear_velocity: self.linear_velocity,
             polar_velocity: self.polar_velocity,
             extra: self.extra.as_deref().map(ExtraSmState::small_box_clone),
@@ -73,6 +77,7 @@ impl StateMachine {
         Self {
             index: initial_state,
             time: 0.,
+            elapsed: 0.,
             linear_velocity: Velocity2::zero(),
             polar_velocity: Velocity2::zero(),
             extra: None,
@@ -117,6 +122,55 @@ pub trait State: Send + Sync + 'static {
     }
 }
 
+/// A condition under which a state machine should leave its current state, checked once per update
+/// by [`StateRegistry::update`].
+pub enum Guard {
+    /// True once the current state has been active for at least this many seconds.
+    After(f32),
+    /// A Lua function called with the projectile's position (`x`, `y`) and the number of seconds
+    /// the current state has been active, returning whether to take the transition.
+    Lua(LuaRegistryKey),
+    /// An arbitrary Rust predicate.
+    Custom(Box<dyn Fn(&ProjectileState, &StateMachine) -> bool + Send + Sync>),
+}
+
+impl Guard {
+    pub fn check(&self, lua: &Lua, projectile_state: &ProjectileState, fsm: &StateMachine) -> bool {
+        match self {
+            Guard::After(t) => fsm.elapsed >= *t,
+            Guard::Lua(key) => {
+                let translation = projectile_state.tx().translation;
+                let result = lua
+                    .registry_value::<LuaFunction>(key)
+                    .and_then(|f| f.call::<_, bool>((translation.x, translation.y, fsm.elapsed)));
+
+                match result {
+                    Ok(fire) => fire,
+                    Err(err) => {
+                        log::error!("error evaluating state transition guard: {}", err);
+                        false
+                    }
+                }
+            }
+            Guard::Custom(predicate) => predicate(projectile_state, fsm),
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for Guard {
+    fn from_lua(lua_value: LuaValue<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        match lua_value {
+            LuaValue::Function(f) => Ok(Guard::Lua(lua.create_registry_value(f)?)),
+            other => Ok(Guard::After(f32::from_lua(other, lua)?)),
+        }
+    }
+}
+
+struct GuardedTransition {
+    guard: Guard,
+    target: StateIndex,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct StateIndex(Index);
 
@@ -139,6 +193,7 @@ impl<'lua> FromLua<'lua> for StateIndex {
 pub struct StateRegistry {
     terminal: StateIndex,
     behaviors: Arena<Box<dyn State>>,
+    transitions: HashMap<StateIndex, Vec<GuardedTransition>>,
 }
 
 impl Default for StateRegistry {
@@ -155,6 +210,7 @@ impl StateRegistry {
         Self {
             terminal,
             behaviors,
+            transitions: HashMap::new(),
         }
     }
 
@@ -162,6 +218,17 @@ impl StateRegistry {
         StateIndex(self.behaviors.insert(Box::new(state)))
     }
 
+    /// Register a transition from one state to another, taken when `guard` is true. Guards are
+    /// checked at the start of every update, before the current state's own update, and in the
+    /// order they were added; the first one which passes wins. Targets may be any state, including
+    /// ones which transition back, so cycles like `aim -> fire -> cooldown -> aim` are fine.
+    pub fn add_transition(&mut self, from: StateIndex, to: StateIndex, guard: Guard) {
+        self.transitions
+            .entry(from)
+            .or_default()
+            .push(GuardedTransition { guard, target: to });
+    }
+
     pub fn enter(
         &self,
         lua: &Lua,
@@ -169,9 +236,23 @@ impl StateRegistry {
         fsm_state: &mut StateMachine,
     ) {
         let machine_state = fsm_state.index;
+        fsm_state.elapsed = 0.;
         self.behaviors[machine_state.0].enter(lua, self, projectile_state, fsm_state);
     }
 
+    fn transition(
+        &self,
+        lua: &Lua,
+        projectile_state: &mut ProjectileState,
+        fsm_state: &mut StateMachine,
+        new_state: StateIndex,
+    ) {
+        let machine_state = fsm_state.index;
+        fsm_state.index = new_state;
+        self.behaviors[machine_state.0].cleanup(lua, self, projectile_state, fsm_state);
+        self.enter(lua, projectile_state, fsm_state);
+    }
+
     pub fn update(
         &self,
         lua: &Lua,
@@ -179,14 +260,30 @@ impl StateRegistry {
         projectile_state: &mut ProjectileState,
         fsm_state: &mut StateMachine,
     ) -> bool {
+        fsm_state.elapsed += dt;
+
+        // Guards are only checked once per update, so that two states with guards which are both
+        // true can't bounce back and forth forever.
+        let guarded_target = self
+            .transitions
+            .get(&fsm_state.index)
+            .and_then(|transitions| {
+                transitions
+                    .iter()
+                    .find(|transition| transition.guard.check(lua, projectile_state, fsm_state))
+                    .map(|transition| transition.target)
+            });
+
+        if let Some(new_state) = guarded_target {
+            self.transition(lua, projectile_state, fsm_state, new_state);
+        }
+
         loop {
             let machine_state = fsm_state.index;
             match self.behaviors[machine_state.0].update(lua, self, dt, projectile_state, fsm_state)
             {
                 Transition::To(new_state) => {
-                    fsm_state.index = new_state;
-                    self.behaviors[machine_state.0].cleanup(lua, self, projectile_state, fsm_state);
-                    self.behaviors[new_state.0].enter(lua, self, projectile_state, fsm_state);
+                    self.transition(lua, projectile_state, fsm_state, new_state);
                     continue;
                 }
                 Transition::Done => {
@@ -264,6 +361,11 @@ impl LuaUserData for StateRegistry {
 
         methods.add_method_mut("kill", |_lua, this, ()| Ok(this.insert(Kill)));
 
+        methods.add_method_mut("transition", |_lua, this, (from, to, guard)| {
+            this.add_transition(from, to, guard);
+            Ok(from)
+        });
+
         methods.add_method_mut("halt", |_lua, this, ()| Ok(this.terminal));
     }
 }
@@ -594,3 +696,89 @@ impl State for Sprite {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pattern::Parameters;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    #[derive(Default)]
+    struct Counts {
+        entered: AtomicUsize,
+        exited: AtomicUsize,
+    }
+
+    impl Counts {
+        fn get(&self) -> (usize, usize) {
+            (
+                self.entered.load(Ordering::SeqCst),
+                self.exited.load(Ordering::SeqCst),
+            )
+        }
+    }
+
+    struct Counting(Arc<Counts>);
+
+    impl State for Counting {
+        fn enter(&self, _: &Lua, _: &StateRegistry, _: &mut ProjectileState, _: &mut StateMachine) {
+            self.0.entered.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn cleanup(
+            &self,
+            _: &Lua,
+            _: &StateRegistry,
+            _: &mut ProjectileState,
+            _: &mut StateMachine,
+        ) {
+            self.0.exited.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn guarded_transition_waits_for_its_condition() {
+        let lua = Lua::new();
+        let mut registry = StateRegistry::new();
+        let (aim_counts, fire_counts) = (Arc::new(Counts::default()), Arc::new(Counts::default()));
+        let aim = registry.insert(Counting(aim_counts.clone()));
+        let fire = registry.insert(Counting(fire_counts.clone()));
+
+        let in_range = Arc::new(AtomicBool::new(false));
+        let guard_in_range = in_range.clone();
+        registry.add_transition(
+            aim,
+            fire,
+            Guard::Custom(Box::new(move |_, _| guard_in_range.load(Ordering::SeqCst))),
+        );
+        registry.add_transition(fire, aim, Guard::After(0.5));
+
+        let mut projectile = ProjectileState::from_parameters(&Parameters::default());
+        let mut fsm = StateMachine::new(aim);
+        registry.enter(&lua, &mut projectile, &mut fsm);
+
+        for _ in 0..10 {
+            registry.update(&lua, 0.1, &mut projectile, &mut fsm);
+        }
+        assert_eq!(fsm.index, aim);
+        assert_eq!(aim_counts.get(), (1, 0));
+        assert_eq!(fire_counts.get(), (0, 0));
+
+        in_range.store(true, Ordering::SeqCst);
+        registry.update(&lua, 0.1, &mut projectile, &mut fsm);
+        assert_eq!(fsm.index, fire);
+        assert_eq!(aim_counts.get(), (1, 1));
+        assert_eq!(fire_counts.get(), (1, 0));
+
+        // The time guard only passes once `fire` has been active for half a second.
+        in_range.store(false, Ordering::SeqCst);
+        for _ in 0..4 {
+            registry.update(&lua, 0.1, &mut projectile, &mut fsm);
+        }
+        assert_eq!(fsm.index, fire);
+        registry.update(&lua, 0.1, &mut projectile, &mut fsm);
+        assert_eq!(fsm.index, aim);
+        assert_eq!(aim_counts.get(), (2, 1));
+        assert_eq!(fire_counts.get(), (1, 1));
+    }
+}
@@ -1,6 +1,8 @@
 use hv_core::{engine::Engine, plugins::Plugin, prelude::*};
 
 pub mod components;
+pub mod snap;
+pub mod undo;
 
 struct TalismanPlugin;
 
@@ -0,0 +1,190 @@
+//! Object-to-object snapping ("smart guides") for dragging objects around in the editor.
+//!
+//! While an object is being dragged, its left/center/right and bottom/center/top are compared
+//! against the same anchors on every other snap target. If any pair is within a threshold, the
+//! dragged object is moved to line them up, and a [`Guide`] describing the line they now share is
+//! returned so the editor can draw it.
+//!
+//! All of the math here is done in whatever space the boxes are given in. To snap within a fixed
+//! number of pixels regardless of zoom, pass screen-space boxes, or divide the pixel threshold by
+//! the camera's scale before passing world-space ones.
+
+use hv_core::spaces::{Object, Space};
+use hv_friends::math::*;
+
+use crate::components::Visible;
+
+/// Which way a [`Guide`] runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GuideAxis {
+    /// A vertical line, at a fixed x coordinate. Produced by snapping horizontally.
+    Vertical,
+    /// A horizontal line, at a fixed y coordinate. Produced by snapping vertically.
+    Horizontal,
+}
+
+/// A line along which a dragged object was aligned with another object.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Guide {
+    /// Which way the guide runs.
+    pub axis: GuideAxis,
+    /// The x coordinate of a vertical guide, or the y coordinate of a horizontal one.
+    pub position: f32,
+    /// Where the guide starts and ends along its axis, spanning both aligned objects.
+    pub extent: (f32, f32),
+}
+
+/// The result of [`snap_box`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snap {
+    /// How far to move the dragged box to align it. Zero on any axis which didn't snap.
+    pub offset: Vector2<f32>,
+    /// At most one guide per axis, for the nearest alignment found on that axis.
+    pub guides: Vec<Guide>,
+}
+
+fn anchors(min: f32, max: f32) -> [f32; 3] {
+    [min, (min + max) / 2., max]
+}
+
+/// Find the nearest alignment on one axis: the smallest offset within `threshold` which lines up
+/// an anchor of the dragged span with an anchor of one of the targets. Returns the offset, the
+/// coordinate of the target's anchor, and the index of the target.
+fn nearest_alignment(
+    dragged: (f32, f32),
+    targets: impl Iterator<Item = (f32, f32)>,
+    threshold: f32,
+) -> Option<(f32, f32, usize)> {
+    let mut best: Option<(f32, f32, usize)> = None;
+    for (i, (min, max)) in targets.enumerate() {
+        for &from in anchors(dragged.0, dragged.1).iter() {
+            for &to in anchors(min, max).iter() {
+                let offset = to - from;
+                if offset.abs() <= threshold
+                    && best.map_or(true, |(best, _, _)| offset.abs() < best.abs())
+                {
+                    best = Some((offset, to, i));
+                }
+            }
+        }
+    }
+    best
+}
+
+/// Snap a dragged box to the nearest edges or centers of the target boxes, independently on each
+/// axis. Only anchors within `threshold` of each other are considered, and when there are several
+/// candidates on an axis the one requiring the smallest move wins.
+pub fn snap_box(dragged: &Box2<f32>, targets: &[Box2<f32>], threshold: f32) -> Snap {
+    let xs = targets.iter().map(|t| (t.mins.x, t.maxs.x));
+    let x_snap = nearest_alignment((dragged.mins.x, dragged.maxs.x), xs, threshold);
+    let ys = targets.iter().map(|t| (t.mins.y, t.maxs.y));
+    let y_snap = nearest_alignment((dragged.mins.y, dragged.maxs.y), ys, threshold);
+
+    let offset = Vector2::new(
+        x_snap.map_or(0., |(dx, _, _)| dx),
+        y_snap.map_or(0., |(dy, _, _)| dy),
+    );
+    let moved = Box2::from_corners(dragged.mins + offset, dragged.maxs + offset);
+
+    let mut guides = Vec::new();
+    if let Some((_, position, i)) = x_snap {
+        guides.push(Guide {
+            axis: GuideAxis::Vertical,
+            position,
+            extent: (
+                moved.mins.y.min(targets[i].mins.y),
+                moved.maxs.y.max(targets[i].maxs.y),
+            ),
+        });
+    }
+
+    if let Some((_, position, i)) = y_snap {
+        guides.push(Guide {
+            axis: GuideAxis::Horizontal,
+            position,
+            extent: (
+                moved.mins.x.min(targets[i].mins.x),
+                moved.maxs.x.max(targets[i].maxs.x),
+            ),
+        });
+    }
+
+    Snap { offset, guides }
+}
+
+/// Collect the bounds of every object in the space which can be snapped to: objects which aren't
+/// part of the selection being dragged and aren't hidden with [`Visible`]`(false)`. Objects for
+/// which `bounds` returns `None` are skipped.
+pub fn snap_targets(
+    space: &Space,
+    selection: &[Object],
+    bounds: impl FnMut(Object) -> Option<Box2<f32>>,
+) -> Vec<Box2<f32>> {
+    let candidates = space
+        .query::<Option<&Visible>>()
+        .iter()
+        .filter(|(object, visible)| {
+            !selection.contains(object) && visible.map_or(true, |visible| visible.0)
+        })
+        .map(|(object, _)| object)
+        .collect::<Vec<_>>();
+
+    candidates.into_iter().filter_map(bounds).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hv_core::spaces::Spaces;
+
+    #[test]
+    fn dragged_box_snaps_left_edges() {
+        let target = Box2::new(100., 0., 50., 20.);
+        // Three pixels to the right of the target's left edge, and well below it.
+        let dragged = Box2::new(103., 80., 30., 10.);
+
+        let snap = snap_box(&dragged, &[target], 5.);
+        assert_eq!(snap.offset, Vector2::new(-3., 0.));
+        assert_eq!(
+            snap.guides,
+            [Guide {
+                axis: GuideAxis::Vertical,
+                position: 100.,
+                extent: (0., 90.),
+            }]
+        );
+
+        // Out of range, nothing happens.
+        let snap = snap_box(&dragged, &[target], 2.);
+        assert_eq!(snap.offset, Vector2::zeros());
+        assert!(snap.guides.is_empty());
+    }
+
+    #[test]
+    fn nearest_candidate_wins() {
+        let far = Box2::new(96., 200., 10., 10.);
+        let near = Box2::new(101., 300., 10., 10.);
+        let dragged = Box2::new(100., 0., 10., 10.);
+
+        let snap = snap_box(&dragged, &[far, near], 5.);
+        assert_eq!(snap.offset.x, 1.);
+        assert_eq!(snap.guides[0].position, 101.);
+    }
+
+    #[test]
+    fn selected_and_invisible_objects_are_not_targets() {
+        let space = Spaces::new().create_space();
+        let mut space = space.borrow_mut();
+        let selected = space.spawn((Box2::new(0., 0., 1., 1.),));
+        space.spawn((Box2::new(1., 0., 1., 1.), Visible(false)));
+        space.spawn((Box2::new(2., 0., 1., 1.), Visible(true)));
+        space.spawn((Box2::new(3., 0., 1., 1.),));
+
+        let targets = snap_targets(&space, &[selected], |obj| {
+            space.get::<Box2<f32>>(obj).ok().map(|b| *b)
+        });
+        let mut xs = targets.iter().map(|b| b.mins.x).collect::<Vec<_>>();
+        xs.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(xs, [2., 3.]);
+    }
+}
@@ -0,0 +1,374 @@
+//! Undo tracking for property edits made through the editor.
+//!
+//! Properties shown in the inspector implement [`ObjectProperty`]. Committing a new value through
+//! [`ObjectProperty::commit`] applies it, stages the change in the [`EditContext`], and returns an
+//! [`EditResult`] telling the editor loop whether to mark an undo point; passing that to
+//! [`UndoTracker::apply`] turns the staged changes into an undo node. This way every property
+//! participates in undo the same way, without any per-property bookkeeping.
+//!
+//! Continuous edits, like dragging a slider, should be wrapped in [`UndoTracker::begin_batch`] and
+//! [`UndoTracker::end_batch`]: every undo point marked while a batch is open is coalesced into a
+//! single node, which restores the value from before the drag started.
+
+use std::{any::Any, marker::PhantomData, sync::Arc};
+
+use hv_core::{
+    prelude::*,
+    spaces::{Component, Object, Space},
+};
+
+/// What the editor loop should do after a property edit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EditResult {
+    /// Nothing changed; no undo point is needed.
+    Unchanged,
+    /// Something changed, and an undo point with the given label should be marked.
+    MarkUndoPoint(String),
+}
+
+/// A single undoable change.
+pub trait Change: Send + Sync + 'static {
+    /// Revert the change.
+    fn undo(&self, space: &mut Space) -> Result<()>;
+
+    /// Re-apply the change after it was undone.
+    fn redo(&self, space: &mut Space) -> Result<()>;
+
+    /// Try to fold a newer change into this one, so that undoing this one undoes both. Returns
+    /// `false` if the changes can't be merged.
+    fn merge(&mut self, newer: &dyn Change) -> bool;
+
+    #[doc(hidden)]
+    fn as_any(&self) -> &dyn Any;
+}
+
+/// A property of an object which can be edited through the inspector.
+pub trait ObjectProperty: Send + Sync + 'static {
+    /// The type of the property's value.
+    type Value: Clone + PartialEq + Send + Sync + 'static;
+
+    /// The name of the property, used as the label for its undo points.
+    fn label(&self) -> &str;
+
+    /// Read the property's current value.
+    fn get(&self, space: &Space, object: Object) -> Result<Self::Value>;
+
+    /// Overwrite the property's value.
+    fn set(&self, space: &mut Space, object: Object, value: Self::Value) -> Result<()>;
+
+    /// Apply a new value to the object being edited and stage the change for undo. Returns
+    /// [`EditResult::Unchanged`] if the new value is the same as the old one.
+    fn commit(self: &Arc<Self>, ctx: &mut EditContext, new_value: Self::Value) -> Result<EditResult>
+    where
+        Self: Sized,
+    {
+        let old_value = self.get(ctx.space, ctx.object)?;
+        if old_value == new_value {
+            return Ok(EditResult::Unchanged);
+        }
+
+        self.set(ctx.space, ctx.object, new_value.clone())?;
+        ctx.stage(PropertyChange {
+            property: self.clone(),
+            object: ctx.object,
+            old_value,
+            new_value,
+        });
+
+        Ok(EditResult::MarkUndoPoint(self.label().to_owned()))
+    }
+}
+
+/// The change produced by committing a new value to an [`ObjectProperty`].
+pub struct PropertyChange<P: ObjectProperty> {
+    property: Arc<P>,
+    object: Object,
+    old_value: P::Value,
+    new_value: P::Value,
+}
+
+impl<P: ObjectProperty> Change for PropertyChange<P> {
+    fn undo(&self, space: &mut Space) -> Result<()> {
+        self.property
+            .set(space, self.object, self.old_value.clone())
+    }
+
+    fn redo(&self, space: &mut Space) -> Result<()> {
+        self.property
+            .set(space, self.object, self.new_value.clone())
+    }
+
+    fn merge(&mut self, newer: &dyn Change) -> bool {
+        match newer.as_any().downcast_ref::<Self>() {
+            Some(newer)
+                if Arc::ptr_eq(&self.property, &newer.property) && self.object == newer.object =>
+            {
+                self.new_value = newer.new_value.clone();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// An [`ObjectProperty`] reading and writing part of a single component through a pair of
+/// accessor functions, for the common case where a property maps directly onto a component field.
+pub struct ComponentProperty<C: Component, V> {
+    label: String,
+    get: fn(&C) -> V,
+    set: fn(&mut C, V),
+    _marker: PhantomData<fn(C)>,
+}
+
+impl<C: Component, V> ComponentProperty<C, V> {
+    /// Create a property with the given label and accessors.
+    pub fn new(label: impl Into<String>, get: fn(&C) -> V, set: fn(&mut C, V)) -> Arc<Self> {
+        Arc::new(Self {
+            label: label.into(),
+            get,
+            set,
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<C, V> ObjectProperty for ComponentProperty<C, V>
+where
+    C: Component,
+    V: Clone + PartialEq + Send + Sync + 'static,
+{
+    type Value = V;
+
+    fn label(&self) -> &str {
+        &self.label
+    }
+
+    fn get(&self, space: &Space, object: Object) -> Result<V> {
+        Ok((self.get)(&*space.get::<C>(object)?))
+    }
+
+    fn set(&self, space: &mut Space, object: Object, value: V) -> Result<()> {
+        (self.set)(&mut *space.get_mut::<C>(object)?, value);
+        Ok(())
+    }
+}
+
+/// The object being edited, and the changes staged by edits to it which haven't been turned into
+/// an undo node yet.
+pub struct EditContext<'a> {
+    /// The space containing the object.
+    pub space: &'a mut Space,
+    /// The object being edited.
+    pub object: Object,
+    staged: Vec<Box<dyn Change>>,
+}
+
+impl<'a> EditContext<'a> {
+    /// Start editing an object.
+    pub fn new(space: &'a mut Space, object: Object) -> Self {
+        Self {
+            space,
+            object,
+            staged: Vec::new(),
+        }
+    }
+
+    /// Stage a change to be recorded by the next [`UndoTracker::mark`].
+    pub fn stage(&mut self, change: impl Change) {
+        self.staged.push(Box::new(change));
+    }
+}
+
+struct UndoNode {
+    label: String,
+    changes: Vec<Box<dyn Change>>,
+}
+
+/// Undo and redo history, as a list of labeled nodes of changes.
+#[derive(Default)]
+pub struct UndoTracker {
+    undo: Vec<UndoNode>,
+    redo: Vec<UndoNode>,
+    batch: Option<String>,
+}
+
+impl UndoTracker {
+    /// Create an empty undo history.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handle the result of an edit: if it asks for an undo point, [`mark`](UndoTracker::mark)
+    /// one.
+    pub fn apply(&mut self, result: EditResult, ctx: &mut EditContext) {
+        if let EditResult::MarkUndoPoint(label) = result {
+            self.mark(&label, ctx);
+        }
+    }
+
+    /// Record all changes staged in the context as an undo node with the given label, clearing the
+    /// redo history. While a batch is open, changes are merged into the batch's node instead.
+    pub fn mark(&mut self, label: &str, ctx: &mut EditContext) {
+        if ctx.staged.is_empty() {
+            return;
+        }
+
+        self.redo.clear();
+        let staged = ctx.staged.drain(..);
+
+        let in_batch = matches!(
+            (&self.batch, self.undo.last()),
+            (Some(batch), Some(node)) if batch == &node.label
+        );
+
+        if in_batch {
+            let node = self.undo.last_mut().unwrap();
+            for change in staged {
+                if !node
+                    .changes
+                    .iter_mut()
+                    .any(|existing| existing.merge(&*change))
+                {
+                    node.changes.push(change);
+                }
+            }
+        } else {
+            if let Some(batch) = &mut self.batch {
+                *batch = label.to_owned();
+            }
+
+            self.undo.push(UndoNode {
+                label: label.to_owned(),
+                changes: staged.collect(),
+            });
+        }
+    }
+
+    /// Start coalescing undo points into a single node, such as at the start of a slider drag.
+    pub fn begin_batch(&mut self) {
+        // The batch takes on the label of the first undo point marked inside it; until then, it
+        // can't match any existing node.
+        self.batch = Some(String::new());
+    }
+
+    /// Stop coalescing undo points.
+    pub fn end_batch(&mut self) {
+        self.batch = None;
+    }
+
+    /// The labels of every undo node, oldest first.
+    pub fn labels(&self) -> impl Iterator<Item = &str> {
+        self.undo.iter().map(|node| node.label.as_str())
+    }
+
+    /// The number of undo nodes.
+    pub fn len(&self) -> usize {
+        self.undo.len()
+    }
+
+    /// Whether there's nothing to undo.
+    pub fn is_empty(&self) -> bool {
+        self.undo.is_empty()
+    }
+
+    /// Revert the most recent undo node, returning its label.
+    pub fn undo(&mut self, space: &mut Space) -> Result<Option<String>> {
+        self.batch = None;
+        let node = match self.undo.pop() {
+            Some(node) => node,
+            None => return Ok(None),
+        };
+
+        for change in node.changes.iter().rev() {
+            change.undo(space)?;
+        }
+
+        let label = node.label.clone();
+        self.redo.push(node);
+        Ok(Some(label))
+    }
+
+    /// Re-apply the most recently undone node, returning its label.
+    pub fn redo(&mut self, space: &mut Space) -> Result<Option<String>> {
+        self.batch = None;
+        let node = match self.redo.pop() {
+            Some(node) => node,
+            None => return Ok(None),
+        };
+
+        for change in node.changes.iter() {
+            change.redo(space)?;
+        }
+
+        let label = node.label.clone();
+        self.undo.push(node);
+        Ok(Some(label))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hv_core::spaces::Spaces;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Health(f32);
+
+    #[test]
+    fn slider_drag_is_one_undo_node() {
+        let space = Spaces::new().create_space();
+        let mut space = space.borrow_mut();
+        let object = space.spawn((Health(10.),));
+        let health = ComponentProperty::<Health, f32>::new("Health", |h| h.0, |h, v| h.0 = v);
+        let mut tracker = UndoTracker::new();
+
+        tracker.begin_batch();
+        for &value in [12., 15., 20.].iter() {
+            let mut ctx = EditContext::new(&mut space, object);
+            let result = health.commit(&mut ctx, value).unwrap();
+            assert_eq!(result, EditResult::MarkUndoPoint("Health".to_owned()));
+            tracker.apply(result, &mut ctx);
+        }
+        tracker.end_batch();
+
+        assert_eq!(tracker.labels().collect::<Vec<_>>(), ["Health"]);
+        assert_eq!(*space.get::<Health>(object).unwrap(), Health(20.));
+
+        // Committing the same value again doesn't mark anything.
+        let mut ctx = EditContext::new(&mut space, object);
+        let result = health.commit(&mut ctx, 20.).unwrap();
+        assert_eq!(result, EditResult::Unchanged);
+        tracker.apply(result, &mut ctx);
+        assert_eq!(tracker.len(), 1);
+
+        assert_eq!(tracker.undo(&mut space).unwrap().as_deref(), Some("Health"));
+        assert_eq!(*space.get::<Health>(object).unwrap(), Health(10.));
+        assert!(tracker.is_empty());
+
+        tracker.redo(&mut space).unwrap();
+        assert_eq!(*space.get::<Health>(object).unwrap(), Health(20.));
+    }
+
+    #[test]
+    fn edits_outside_a_batch_are_separate() {
+        let space = Spaces::new().create_space();
+        let mut space = space.borrow_mut();
+        let object = space.spawn((Health(10.),));
+        let health = ComponentProperty::<Health, f32>::new("Health", |h| h.0, |h, v| h.0 = v);
+        let mut tracker = UndoTracker::new();
+
+        for &value in [1., 2.].iter() {
+            let mut ctx = EditContext::new(&mut space, object);
+            let result = health.commit(&mut ctx, value).unwrap();
+            tracker.apply(result, &mut ctx);
+        }
+
+        assert_eq!(tracker.len(), 2);
+        tracker.undo(&mut space).unwrap();
+        assert_eq!(*space.get::<Health>(object).unwrap(), Health(1.));
+    }
+}
@@ -0,0 +1,298 @@
+use crate::*;
+
+use image::RgbaImage;
+
+/// Options for packing tileset images into shared texture atlases. See
+/// [`TilesetRenderData::new_packed`].
+#[derive(Debug, Clone, Copy)]
+pub struct AtlasOptions {
+    /// The maximum width and height of a single atlas texture. Tilesets are never split across
+    /// atlases; a tileset too large to fit in an empty atlas is left in its own texture.
+    pub max_size: u32,
+    /// The number of pixels each tile's edge is extruded by in the atlas. Without this, linear
+    /// filtering and subpixel offsets can sample neighboring tiles and cause seams.
+    pub padding: u32,
+}
+
+impl Default for AtlasOptions {
+    fn default() -> Self {
+        Self {
+            max_size: 4096,
+            padding: 1,
+        }
+    }
+}
+
+/// Where a tileset ended up after packing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TilesetPlacement {
+    /// The tileset's tiles are laid out in a grid with its top left corner at `x`, `y` (in pixels,
+    /// with the origin at the top left of the atlas) in the atlas with index `atlas`.
+    Atlas { atlas: usize, x: u32, y: u32 },
+    /// The tileset was too large to pack and keeps its original image.
+    Standalone,
+}
+
+/// The result of packing a set of tilesets into atlases; this contains no image data, only the
+/// positions of each tileset inside the atlases and the sizes of the atlases themselves.
+#[derive(Debug, Clone)]
+pub struct TilesetAtlasLayout {
+    /// One placement per tileset, in the same order as the [`Tilesets`].
+    pub placements: Vec<TilesetPlacement>,
+    /// The width and height in pixels of each atlas.
+    pub atlas_sizes: Vec<(u32, u32)>,
+    /// The padding used when packing.
+    pub padding: u32,
+}
+
+impl TilesetAtlasLayout {
+    /// Compute a packing for the given tilesets using a simple shelf packer. Tilesets are packed in
+    /// order of decreasing height, and a new atlas is started whenever the current one is full.
+    pub fn pack(tilesets: &Tilesets, options: &AtlasOptions) -> Self {
+        let padding = options.padding;
+        let sizes = tilesets
+            .0
+            .iter()
+            .map(|tileset| packed_size(tileset, padding))
+            .collect::<Vec<_>>();
+
+        let mut order = (0..sizes.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&i| std::cmp::Reverse(sizes[i].1));
+
+        let mut placements = vec![TilesetPlacement::Standalone; sizes.len()];
+        let mut atlas_sizes: Vec<(u32, u32)> = Vec::new();
+
+        // Cursor state for the current atlas and shelf.
+        let (mut cursor_x, mut shelf_y, mut shelf_height) = (0, 0, 0);
+
+        for i in order {
+            let (w, h) = sizes[i];
+            if w == 0 || h == 0 || w > options.max_size || h > options.max_size {
+                continue;
+            }
+
+            if atlas_sizes.is_empty() {
+                atlas_sizes.push((0, 0));
+            }
+
+            if cursor_x + w > options.max_size {
+                // Start a new shelf.
+                shelf_y += shelf_height;
+                cursor_x = 0;
+                shelf_height = 0;
+            }
+
+            if shelf_y + h > options.max_size {
+                // Start a new atlas.
+                atlas_sizes.push((0, 0));
+                cursor_x = 0;
+                shelf_y = 0;
+                shelf_height = 0;
+            }
+
+            let atlas = atlas_sizes.len() - 1;
+            placements[i] = TilesetPlacement::Atlas {
+                atlas,
+                x: cursor_x,
+                y: shelf_y,
+            };
+
+            cursor_x += w;
+            shelf_height = shelf_height.max(h);
+
+            let size = &mut atlas_sizes[atlas];
+            size.0 = size.0.max(cursor_x);
+            size.1 = size.1.max(shelf_y + shelf_height);
+        }
+
+        Self {
+            placements,
+            atlas_sizes,
+            padding,
+        }
+    }
+
+    /// The UVs of a tile inside its atlas, given the tileset's index and the tile's index local to
+    /// the tileset. Returns `None` if the tileset is [`TilesetPlacement::Standalone`].
+    ///
+    /// Like textures loaded through [`Texture::from_memory`], atlases are flipped vertically when
+    /// uploaded, so the returned UVs have their origin at the bottom left.
+    pub fn tile_uvs(
+        &self,
+        tileset_index: usize,
+        tileset: &Tileset,
+        local_id: u32,
+    ) -> Option<Box2<f32>> {
+        let (atlas, x, y) = match self.placements[tileset_index] {
+            TilesetPlacement::Atlas { atlas, x, y } => (atlas, x, y),
+            TilesetPlacement::Standalone => return None,
+        };
+
+        let (atlas_w, atlas_h) = self.atlas_sizes[atlas];
+        let (px, py) = self.tile_pixel_position(x, y, tileset, local_id);
+
+        Some(Box2::new(
+            px as f32 / atlas_w as f32,
+            (atlas_h - py - tileset.tile_height) as f32 / atlas_h as f32,
+            tileset.tile_width as f32 / atlas_w as f32,
+            tileset.tile_height as f32 / atlas_h as f32,
+        ))
+    }
+
+    /// Top left corner of a tile's (unpadded) pixels inside its atlas, in top-down image space.
+    fn tile_pixel_position(&self, x: u32, y: u32, tileset: &Tileset, local_id: u32) -> (u32, u32) {
+        let (column, row) = (local_id % tileset.columns, local_id / tileset.columns);
+        (
+            x + column * (tileset.tile_width + 2 * self.padding) + self.padding,
+            y + row * (tileset.tile_height + 2 * self.padding) + self.padding,
+        )
+    }
+
+    /// Compose the atlas images from the tilesets' source images (in top-down order, as decoded.)
+    /// Each tile is copied out of its source image, skipping margins and spacing, and its border
+    /// pixels are extruded into the surrounding padding.
+    pub fn compose(&self, tilesets: &Tilesets, images: &[RgbaImage]) -> Vec<RgbaImage> {
+        let mut atlases = self
+            .atlas_sizes
+            .iter()
+            .map(|&(w, h)| RgbaImage::new(w, h))
+            .collect::<Vec<_>>();
+
+        for (i, (tileset, source)) in tilesets.0.iter().zip(images).enumerate() {
+            let (atlas, x, y) = match self.placements[i] {
+                TilesetPlacement::Atlas { atlas, x, y } => (atlas, x, y),
+                TilesetPlacement::Standalone => continue,
+            };
+
+            let pad = self.padding as i64;
+            let (tw, th) = (tileset.tile_width as i64, tileset.tile_height as i64);
+            for local_id in 0..tile_count(tileset) {
+                let (column, row) = (local_id % tileset.columns, local_id / tileset.columns);
+                let src_x =
+                    (tileset.margin + column * (tileset.tile_width + tileset.spacing)) as i64;
+                let src_y = (tileset.margin + row * (tileset.tile_height + tileset.spacing)) as i64;
+                let (dst_x, dst_y) = self.tile_pixel_position(x, y, tileset, local_id);
+
+                for dy in -pad..th + pad {
+                    for dx in -pad..tw + pad {
+                        let sx = (src_x + dx.clamp(0, tw - 1)).min(source.width() as i64 - 1);
+                        let sy = (src_y + dy.clamp(0, th - 1)).min(source.height() as i64 - 1);
+                        let pixel = *source.get_pixel(sx as u32, sy as u32);
+                        atlases[atlas].put_pixel(
+                            (dst_x as i64 + dx) as u32,
+                            (dst_y as i64 + dy) as u32,
+                            pixel,
+                        );
+                    }
+                }
+            }
+        }
+
+        atlases
+    }
+}
+
+fn tile_count(tileset: &Tileset) -> u32 {
+    (tileset.tilecount / tileset.columns) * tileset.columns
+}
+
+/// The size of a tileset once its tiles are laid out with padding and without margin/spacing.
+fn packed_size(tileset: &Tileset, padding: u32) -> (u32, u32) {
+    if tileset.columns == 0 {
+        return (0, 0);
+    }
+
+    let rows = tileset.tilecount / tileset.columns;
+    (
+        tileset.columns * (tileset.tile_width + 2 * padding),
+        rows * (tileset.tile_height + 2 * padding),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tileset(first_gid: u32, columns: u32, rows: u32, margin: u32, spacing: u32) -> Tileset {
+        Tileset {
+            first_gid,
+            name: String::new(),
+            tile_width: 8,
+            tile_height: 8,
+            spacing,
+            margin,
+            tilecount: columns * rows,
+            columns,
+            tiles: HashMap::new(),
+            properties: Properties(HashMap::new()),
+            images: Vec::new(),
+            wang_sets: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn two_small_tilesets_share_an_atlas() {
+        let tilesets = Tilesets(vec![tileset(1, 2, 2, 1, 2), tileset(5, 4, 1, 0, 0)]);
+        let options = AtlasOptions {
+            max_size: 64,
+            padding: 1,
+        };
+        let layout = TilesetAtlasLayout::pack(&tilesets, &options);
+
+        // The 2x2 tileset is taller, so it's packed first; the 4x1 one goes next to it on the same
+        // shelf.
+        assert_eq!(layout.atlas_sizes, vec![(20 + 40, 20)]);
+        assert_eq!(
+            layout.placements,
+            vec![
+                TilesetPlacement::Atlas {
+                    atlas: 0,
+                    x: 0,
+                    y: 0
+                },
+                TilesetPlacement::Atlas {
+                    atlas: 0,
+                    x: 20,
+                    y: 0
+                },
+            ]
+        );
+
+        // Bottom right tile of the first tileset: pixels (11, 11)..(19, 19) in top-down space.
+        let uvs = layout.tile_uvs(0, &tilesets.0[0], 3).unwrap();
+        assert_eq!(uvs, Box2::new(11. / 60., 1. / 20., 8. / 60., 8. / 20.));
+
+        // Third tile of the second tileset: pixels (41, 1)..(49, 9).
+        let uvs = layout.tile_uvs(1, &tilesets.0[1], 2).unwrap();
+        assert_eq!(uvs, Box2::new(41. / 60., 11. / 20., 8. / 60., 8. / 20.));
+
+        // Compose and make sure margin/spacing were skipped and edges were extruded.
+        let mut first = RgbaImage::new(1 + 8 + 2 + 8 + 1, 1 + 8 + 2 + 8 + 1);
+        first.put_pixel(11, 11, image::Rgba([255, 0, 0, 255]));
+        let second = RgbaImage::new(32, 8);
+        let atlases = layout.compose(&tilesets, &[first, second]);
+        assert_eq!(atlases.len(), 1);
+        // Top left pixel of tile 3, plus its extruded neighbors in the padding.
+        assert_eq!(atlases[0].get_pixel(11, 11).0, [255, 0, 0, 255]);
+        assert_eq!(atlases[0].get_pixel(10, 10).0, [255, 0, 0, 255]);
+        assert_eq!(atlases[0].get_pixel(12, 12).0, [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn oversized_tilesets_fall_back() {
+        let tilesets = Tilesets(vec![
+            tileset(1, 4, 4, 0, 0),
+            tileset(17, 4, 4, 0, 0),
+            tileset(33, 10, 10, 0, 0),
+        ]);
+        let options = AtlasOptions {
+            max_size: 40,
+            padding: 0,
+        };
+        let layout = TilesetAtlasLayout::pack(&tilesets, &options);
+
+        assert_eq!(layout.atlas_sizes, vec![(32, 32), (32, 32)]);
+        assert_eq!(layout.placements[2], TilesetPlacement::Standalone);
+        assert!(layout.tile_uvs(2, &tilesets.0[2], 0).is_none());
+    }
+}
@@ -0,0 +1,960 @@
+//! A parser for maps exported from Tiled as JSON (`.tmj`/`.json`), producing the same [`Map`] as
+//! [`lua_parser`](crate::lua_parser). Tile data may be CSV or base64 encoded, with or without
+//! compression. External tilesets aren't supported; tilesets must be embedded in the map.
+
+use crate::*;
+use hv_core::prelude::*;
+use serde_json::Value;
+use std::convert::TryFrom;
+
+trait ValueExt {
+    fn field(&self, key: &str) -> Result<&Value, Error>;
+    fn str_field(&self, key: &str) -> Result<&str, Error>;
+    fn u32_field(&self, key: &str) -> Result<u32, Error>;
+    fn f64_field(&self, key: &str) -> Result<f64, Error>;
+    fn bool_field(&self, key: &str) -> Result<bool, Error>;
+    fn array_field(&self, key: &str) -> Result<&[Value], Error>;
+}
+
+impl ValueExt for Value {
+    fn field(&self, key: &str) -> Result<&Value, Error> {
+        self.get(key)
+            .ok_or_else(|| anyhow!("missing field {:?}", key))
+    }
+
+    fn str_field(&self, key: &str) -> Result<&str, Error> {
+        self.field(key)?
+            .as_str()
+            .ok_or_else(|| anyhow!("expected {:?} to be a string", key))
+    }
+
+    fn u32_field(&self, key: &str) -> Result<u32, Error> {
+        as_u32(self.field(key)?).with_context(|| format!("bad value for {:?}", key))
+    }
+
+    fn f64_field(&self, key: &str) -> Result<f64, Error> {
+        self.field(key)?
+            .as_f64()
+            .ok_or_else(|| anyhow!("expected {:?} to be a number", key))
+    }
+
+    fn bool_field(&self, key: &str) -> Result<bool, Error> {
+        self.field(key)?
+            .as_bool()
+            .ok_or_else(|| anyhow!("expected {:?} to be a boolean", key))
+    }
+
+    fn array_field(&self, key: &str) -> Result<&[Value], Error> {
+        self.field(key)?
+            .as_array()
+            .map(Vec::as_slice)
+            .ok_or_else(|| anyhow!("expected {:?} to be an array", key))
+    }
+}
+
+fn as_u32(value: &Value) -> Result<u32, Error> {
+    let n = value
+        .as_u64()
+        .ok_or_else(|| anyhow!("expected an unsigned integer, got {}", value))?;
+    Ok(u32::try_from(n)?)
+}
+
+fn parse_layer_type(t: &Value) -> Result<LayerType, Error> {
+    match t.str_field("type")? {
+        "objectgroup" => Ok(LayerType::Object),
+        "tilelayer" => Ok(LayerType::Tile),
+        s => Err(anyhow!("Unsupported layer type: {}", s)),
+    }
+}
+
+/// Unlike the Lua export, the JSON export records the type of every property, so colors and files
+/// come through as [`Property::Color`] and [`Property::File`] rather than plain strings.
+fn parse_properties(t: &Value) -> Result<Properties, Error> {
+    let mut properties = HashMap::new();
+    let props = match t.get("properties") {
+        Some(props) => props,
+        None => return Ok(Properties(properties)),
+    };
+
+    for prop in props
+        .as_array()
+        .ok_or_else(|| anyhow!("expected properties to be an array"))?
+    {
+        let name = prop.str_field("name")?;
+        let val = match prop.str_field("type")? {
+            "bool" => Property::Bool(prop.bool_field("value")?),
+            "int" => Property::Int(
+                prop.field("value")?
+                    .as_i64()
+                    .ok_or_else(|| anyhow!("expected int property {:?} to be an integer", name))?,
+            ),
+            "float" => Property::Float(prop.f64_field("value")?),
+            "string" => Property::String(prop.str_field("value")?.to_owned()),
+            "color" => Property::Color(prop.str_field("value")?.to_owned()),
+            "file" => Property::File(prop.str_field("value")?.to_owned()),
+            "object" => Property::Obj(ObjectId::new(prop.u32_field("value")?, true)),
+            ty => return Err(anyhow!("Got an unsupported property type: {}", ty)),
+        };
+        properties.insert(name.to_owned(), val);
+    }
+
+    Ok(Properties(properties))
+}
+
+fn parse_map_meta_data(map: &Value) -> Result<MapMetaData, Error> {
+    let render_order = match map.str_field("renderorder")? {
+        "right-down" => RenderOrder::RightDown,
+        "right-up" => RenderOrder::RightUp,
+        "left-down" => RenderOrder::LeftDown,
+        "left-up" => RenderOrder::LeftUp,
+        r => return Err(anyhow!("Got an unsupported renderorder: {}", r)),
+    };
+
+    let orientation = match map.str_field("orientation")? {
+        "orthogonal" => Orientation::Orthogonal,
+        "isometric" => Orientation::Isometric,
+        o => return Err(anyhow!("Got an unsupported orientation: {}", o)),
+    };
+
+    // Older versions of Tiled wrote the format version as a number rather than a string.
+    let tsx_ver = match map.field("version")? {
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+    };
+
+    Ok(MapMetaData {
+        width: map.u32_field("width")?,
+        height: map.u32_field("height")?,
+        tilewidth: map.u32_field("tilewidth")?,
+        tileheight: map.u32_field("tileheight")?,
+        tsx_ver,
+        lua_ver: None,
+        tiled_ver: map.str_field("tiledversion")?.to_owned(),
+        nextlayerid: map.u32_field("nextlayerid")?,
+        nextobjectid: map.u32_field("nextobjectid")?,
+        properties: parse_properties(map)?,
+        orientation,
+        render_order,
+    })
+}
+
+fn parse_tile_data(
+    t: &Value,
+    encoding: &Encoding,
+    compression: &Option<Compression>,
+    tile_buffer: &[u32],
+) -> Result<Vec<TileId>, Error> {
+    let gids = match (encoding, t.field("data")?) {
+        (Encoding::Csv, Value::Array(data)) => {
+            data.iter().map(as_u32).collect::<Result<Vec<_>, _>>()?
+        }
+        (Encoding::Csv, Value::String(data)) => decode_csv(data)?,
+        (Encoding::Base64, Value::String(data)) => decode_base64(data, compression)?,
+        (e, _) => return Err(anyhow!("Got malformed tile data for {:?} encoding", e)),
+    };
+
+    Ok(gids_to_tile_ids(gids, tile_buffer))
+}
+
+fn parse_chunk(
+    t: &Value,
+    encoding: &Encoding,
+    compression: &Option<Compression>,
+    tile_buffer: &[u32],
+) -> Result<(Chunk, i32, i32), Error> {
+    let width = t.u32_field("width")?;
+    let height = t.u32_field("height")?;
+
+    ensure!(
+        width == CHUNK_SIZE && height == CHUNK_SIZE,
+        "chunk sizes should always be {}, got {}x{}",
+        CHUNK_SIZE,
+        width,
+        height
+    );
+
+    Ok((
+        Chunk(parse_tile_data(t, encoding, compression, tile_buffer)?),
+        t.f64_field("x")? as i32,
+        t.f64_field("y")? as i32,
+    ))
+}
+
+fn parse_tile_layer(t: &Value, llid: u32, tile_buffer: &[u32]) -> Result<TileLayer, Error> {
+    let encoding = match t.get("encoding").and_then(Value::as_str) {
+        None | Some("csv") => Encoding::Csv,
+        Some("base64") => Encoding::Base64,
+        Some(e) => return Err(anyhow!("Got an unsupported encoding type: {}", e)),
+    };
+
+    let compression = match t.get("compression").and_then(Value::as_str) {
+        None | Some("") => None,
+        Some("gzip") => Some(Compression::GZip),
+        Some("zlib") => Some(Compression::ZLib),
+        Some("zstd") => return Err(anyhow!("Zstd compression is not supported!")),
+        Some(e) => return Err(anyhow!("Got a corrupted compression format: {}", e)),
+    };
+
+    let width = t.u32_field("width")?;
+    let height = t.u32_field("height")?;
+
+    let tile_data = match t.get("chunks") {
+        Some(_) => {
+            let mut chunks = HashMap::new();
+            for chunk in t.array_field("chunks")? {
+                let (chunk, tile_x, tile_y) =
+                    parse_chunk(chunk, &encoding, &compression, tile_buffer)?;
+                let chunk_x = tile_x / CHUNK_SIZE as i32;
+                let chunk_y = tile_y / CHUNK_SIZE as i32;
+                chunks.insert((chunk_x, chunk_y), chunk);
+            }
+            Chunks(chunks)
+        }
+        None => to_chunks(
+            &parse_tile_data(t, &encoding, &compression, tile_buffer)?,
+            width,
+            height,
+        ),
+    };
+
+    Ok(TileLayer {
+        id: TileLayerId {
+            glid: t.u32_field("id")?,
+            llid,
+        },
+        name: t.str_field("name")?.to_owned(),
+        x: t.f64_field("x")? as i32,
+        y: t.f64_field("y")? as i32,
+        visible: t.bool_field("visible")?,
+        opacity: t.f64_field("opacity")?,
+        offset_x: t.get("offsetx").and_then(Value::as_f64).unwrap_or(0.) as i32,
+        offset_y: t.get("offsety").and_then(Value::as_f64).unwrap_or(0.) as i32,
+        properties: parse_properties(t)?,
+        data: tile_data,
+        layer_type: LayerType::Tile,
+        width,
+        height,
+    })
+}
+
+fn parse_draw_order(t: &Value) -> Result<DrawOrder, Error> {
+    // Object groups attached to tiles don't have a draw order.
+    match t.get("draworder").and_then(Value::as_str) {
+        None | Some("topdown") => Ok(DrawOrder::TopDown),
+        Some("index") => Ok(DrawOrder::Index),
+        Some(s) => Err(anyhow!("Unsupported draw order: {}", s)),
+    }
+}
+
+fn parse_text(t: &Value) -> Result<Text, Error> {
+    let string = |key: &str, default: &str| {
+        t.get(key)
+            .and_then(Value::as_str)
+            .unwrap_or(default)
+            .to_owned()
+    };
+    let flag = |key: &str, default: bool| t.get(key).and_then(Value::as_bool).unwrap_or(default);
+
+    let halign = match string("halign", "left").as_str() {
+        "left" => Halign::Left,
+        "center" => Halign::Center,
+        "right" => Halign::Right,
+        "justify" => Halign::Justify,
+        s => return Err(anyhow!("Unsupported halign value: {}", s)),
+    };
+
+    let valign = match string("valign", "top").as_str() {
+        "top" => Valign::Top,
+        "center" => Valign::Center,
+        "bottom" => Valign::Bottom,
+        s => return Err(anyhow!("Unsupported valign value: {}", s)),
+    };
+
+    Ok(Text {
+        text: t.str_field("text")?.to_owned(),
+        pixelsize: t.get("pixelsize").map(as_u32).transpose()?.unwrap_or(16),
+        wrapping: flag("wrap", false),
+        color: match t.get("color").and_then(Value::as_str) {
+            Some(hex) => Color::from_tiled_hex(hex)?,
+            None => Color::BLACK,
+        },
+        bold: flag("bold", false),
+        italic: flag("italic", false),
+        underline: flag("underline", false),
+        strikeout: flag("strikeout", false),
+        kerning: flag("kerning", true),
+        fontfamily: string("fontfamily", "sans-serif"),
+        halign,
+        valign,
+    })
+}
+
+fn parse_object(
+    obj: &Value,
+    from_obj_layer: bool,
+    tileset_ids: Option<&[u32]>,
+) -> Result<Object, Error> {
+    let flag = |key: &str| obj.get(key).and_then(Value::as_bool).unwrap_or(false);
+
+    let (shape, text) = if let Some(text) = obj.get("text") {
+        (None, Some(parse_text(text)?))
+    } else {
+        // The JSON export marks shapes by which keys are present, rather than with a name as in
+        // the Lua export; map those back to the names so both are handled identically.
+        let shape = if flag("ellipse") {
+            "ellipse"
+        } else if flag("point") {
+            "point"
+        } else if obj.get("polygon").is_some() {
+            "polygon"
+        } else if obj.get("polyline").is_some() {
+            "polyline"
+        } else {
+            "rectangle"
+        };
+        (Some(ObjectShape::from_string(shape)?), None)
+    };
+
+    let tile_id = match obj.get("gid") {
+        Some(gid) => {
+            let tileset_ids =
+                tileset_ids.ok_or_else(|| anyhow!("got a tile object within a tileset"))?;
+            Some(TileId::from_gid(as_u32(gid)?, tileset_ids))
+        }
+        None => None,
+    };
+
+    Ok(Object {
+        id: ObjectId::new(obj.u32_field("id")?, from_obj_layer),
+        name: obj.str_field("name")?.to_owned(),
+        obj_type: obj
+            .get("type")
+            .and_then(Value::as_str)
+            .unwrap_or("")
+            .to_owned(),
+        x: obj.f64_field("x")? as f32,
+        y: obj.f64_field("y")? as f32,
+        width: obj.f64_field("width")? as f32,
+        height: obj.f64_field("height")? as f32,
+        properties: parse_properties(obj)?,
+        rotation: obj.f64_field("rotation")? as f32,
+        visible: obj.bool_field("visible")?,
+        tile_id,
+        shape,
+        text,
+    })
+}
+
+fn parse_object_group(
+    objg: &Value,
+    llid: u32,
+    from_obj_layer: bool,
+    slab: &mut slab::Slab<Object>,
+    tileset_ids: Option<&[u32]>,
+) -> Result<(ObjectGroup, Vec<(ObjectId, ObjectRef)>), Error> {
+    let mut obj_ids_and_refs = Vec::new();
+    let mut object_name_map = HashMap::new();
+
+    for object in objg.array_field("objects")? {
+        let object = parse_object(object, from_obj_layer, tileset_ids)?;
+
+        object_name_map
+            .entry(object.name.clone())
+            .or_insert_with(Vec::new)
+            .push(object.id);
+
+        obj_ids_and_refs.push((object.id, ObjectRef(slab.insert(object))));
+    }
+
+    let color = match objg.get("color").and_then(Value::as_str) {
+        Some(s) => Color::from_tiled_hex(s)?,
+        None => Color::from_rgb(0xA0, 0xA0, 0x0A4),
+    };
+
+    let offset = |key: &str| objg.get(key).and_then(Value::as_f64).unwrap_or(0.) as u32;
+
+    Ok((
+        ObjectGroup {
+            id: ObjectLayerId {
+                glid: objg.get("id").map(as_u32).transpose()?.unwrap_or(0),
+                llid,
+            },
+            name: objg
+                .get("name")
+                .and_then(Value::as_str)
+                .unwrap_or("")
+                .to_owned(),
+            opacity: objg.get("opacity").and_then(Value::as_f64).unwrap_or(1.) as f32,
+            visible: objg.get("visible").and_then(Value::as_bool).unwrap_or(true),
+            layer_index: None,
+            properties: parse_properties(objg)?,
+            draworder: parse_draw_order(objg)?,
+            obj_group_type: ObjGroupType::ObjectGroup,
+            tintcolor: objg
+                .get("tintcolor")
+                .and_then(Value::as_str)
+                .map(Color::from_tiled_hex)
+                .transpose()?,
+            off_x: offset("offsetx"),
+            off_y: offset("offsety"),
+            object_refs: obj_ids_and_refs.iter().map(|i| i.1).collect(),
+            color,
+            object_name_map,
+        },
+        obj_ids_and_refs,
+    ))
+}
+
+fn parse_animation(frames: &[Value], tileset: u32) -> Result<Animation, Error> {
+    let mut animation_buffer = Vec::new();
+    for frame in frames {
+        animation_buffer.push((
+            TileId(
+                frame.u32_field("tileid")?,
+                TileMetaData::new(tileset, false, false, false),
+            ),
+            frame.u32_field("duration")?,
+        ));
+    }
+    Ok(Animation(animation_buffer))
+}
+
+fn parse_tile(
+    tile: &Value,
+    tileset_num: u32,
+    slab: &mut slab::Slab<Object>,
+) -> Result<Tile, Error> {
+    let objectgroup = match tile.get("objectgroup") {
+        Some(t) => Some(parse_object_group(t, u32::MAX, false, slab, None)?.0),
+        None => None,
+    };
+
+    Ok(Tile {
+        // Tiled data stores tile IDs + 1, so as in the Lua parser we add 1 here for consistency.
+        id: TileId(
+            tile.u32_field("id")? + 1,
+            TileMetaData::new(tileset_num, false, false, false),
+        ),
+        tile_type: tile.get("type").and_then(Value::as_str).map(str::to_owned),
+        probability: tile
+            .get("probability")
+            .and_then(Value::as_f64)
+            .unwrap_or(0.0) as f32,
+        animation: match tile.get("animation") {
+            Some(_) => Some(parse_animation(
+                tile.array_field("animation")?,
+                tileset_num,
+            )?),
+            None => None,
+        },
+        properties: parse_properties(tile)?,
+        objectgroup,
+    })
+}
+
+fn parse_wang_type(t: &Value) -> Result<WangSetType, Error> {
+    // Tiled 1.9 renamed `type` to `wangsettype`.
+    let wang_type = match t.get("wangsettype") {
+        Some(_) => t.str_field("wangsettype")?,
+        None => t.str_field("type")?,
+    };
+
+    match wang_type {
+        "corner" => Ok(WangSetType::Corner),
+        "edge" => Ok(WangSetType::Edge),
+        "mixed" => Ok(WangSetType::Mixed),
+        s => Err(anyhow!("Unsupported Wang set type: {}", s)),
+    }
+}
+
+fn parse_wang_set(t: &Value, first_gid: u32, tileset_num: u32) -> Result<WangSet, Error> {
+    let mut colors = Vec::new();
+    for color in t.array_field("colors")? {
+        colors.push(WangColor {
+            name: color.str_field("name")?.to_owned(),
+            color: Color::from_tiled_hex(color.str_field("color")?)?,
+            probability: color
+                .get("probability")
+                .and_then(Value::as_f64)
+                .unwrap_or(1.0) as f32,
+            properties: parse_properties(color)?,
+        });
+    }
+
+    let mut tiles = Vec::new();
+    for wang_tile in t.array_field("wangtiles")? {
+        let ids = match wang_tile.field("wangid")? {
+            Value::Array(ids) => ids
+                .iter()
+                .map(|id| Ok(u8::try_from(as_u32(id)?)?))
+                .collect::<Result<Vec<_>, Error>>()?,
+            _ => bail!(
+                "Wang sets from before Tiled 1.5 aren't supported, re-save the map to upgrade"
+            ),
+        };
+        tiles.push((
+            TileId::new(
+                first_gid - 1 + wang_tile.u32_field("tileid")?,
+                tileset_num,
+                false,
+                false,
+                false,
+            ),
+            WangId::from_slice(&ids)?,
+        ));
+    }
+
+    Ok(WangSet {
+        name: t.str_field("name")?.to_owned(),
+        wang_type: parse_wang_type(t)?,
+        properties: parse_properties(t)?,
+        colors,
+        tiles,
+    })
+}
+
+fn parse_tileset(
+    ts: &Value,
+    path_prefix: Option<&str>,
+    tileset_number: u32,
+    slab: &mut slab::Slab<Object>,
+) -> Result<Tileset, Error> {
+    if let Some(source) = ts.get("source").and_then(Value::as_str) {
+        bail!(
+            "external tileset {:?} isn't supported; embed it in the map instead",
+            source
+        );
+    }
+
+    let first_gid = ts.u32_field("firstgid")?;
+    let mut tiles = HashMap::new();
+    let mut terrain_tiles = Vec::new();
+    if ts.get("tiles").is_some() {
+        for tile_json in ts.array_field("tiles")? {
+            let tile = parse_tile(tile_json, tileset_number, slab)?;
+            if tile_json.get("terrain").is_some() {
+                let corners = tile_json
+                    .array_field("terrain")?
+                    .iter()
+                    .map(|c| {
+                        c.as_i64()
+                            .map(|c| c as i32)
+                            .ok_or_else(|| anyhow!("expected terrain indices to be integers"))
+                    })
+                    .collect::<Result<Vec<_>, Error>>()?;
+                ensure!(corners.len() == 4, "tile terrains should have 4 corners");
+                // Tile IDs in tilesets are stored + 1; see `parse_tile`.
+                let local_id = tile.id.0 - 1;
+                let gid = TileId::new(
+                    first_gid - 1 + local_id,
+                    tileset_number,
+                    false,
+                    false,
+                    false,
+                );
+                terrain_tiles.push((gid, [corners[0], corners[1], corners[2], corners[3]]));
+            }
+            tiles.insert(tile.id, tile);
+        }
+    }
+
+    let mut wang_sets = Vec::new();
+    if ts.get("terrains").is_some() {
+        let names = ts
+            .array_field("terrains")?
+            .iter()
+            .map(|terrain| Ok(terrain.str_field("name")?.to_owned()))
+            .collect::<Result<Vec<_>, Error>>()?;
+        wang_sets.push(WangSet::from_terrains(names, terrain_tiles)?);
+    }
+    if ts.get("wangsets").is_some() {
+        for set in ts.array_field("wangsets")? {
+            wang_sets.push(parse_wang_set(set, first_gid, tileset_number)?);
+        }
+    }
+
+    let image = Image {
+        source: path_prefix.unwrap_or("").to_owned() + ts.str_field("image")?,
+        width: ts.u32_field("imagewidth")?,
+        height: ts.u32_field("imageheight")?,
+        trans_color: ts
+            .get("transparentcolor")
+            .and_then(Value::as_str)
+            .map(Color::from_tiled_hex)
+            .transpose()?,
+    };
+
+    Ok(Tileset {
+        name: ts.str_field("name")?.to_owned(),
+        first_gid,
+        tile_width: ts.u32_field("tilewidth")?,
+        tile_height: ts.u32_field("tileheight")?,
+        spacing: ts.u32_field("spacing")?,
+        margin: ts.u32_field("margin")?,
+        columns: ts.u32_field("columns")?,
+        images: vec![image],
+        tilecount: ts.u32_field("tilecount")?,
+        properties: parse_properties(ts)?,
+        tiles,
+        wang_sets,
+    })
+}
+
+pub fn parse_map(map_path: &str, engine: &Engine, path_prefix: Option<&str>) -> Result<Map, Error> {
+    let mut fs = engine.fs();
+    let mut tiled_json_map = fs.open(Path::new(map_path))?;
+    drop(fs);
+
+    let mut tiled_buffer = String::new();
+    tiled_json_map.read_to_string(&mut tiled_buffer)?;
+    parse_map_str(&tiled_buffer, path_prefix).with_context(|| format!("error parsing {}", map_path))
+}
+
+/// Parse a map from the contents of a Tiled JSON file.
+pub fn parse_map_str(json: &str, path_prefix: Option<&str>) -> Result<Map, Error> {
+    let tiled_json: Value = serde_json::from_str(json)?;
+    let meta_data = parse_map_meta_data(&tiled_json)?;
+
+    let mut tilesets = Vec::new();
+    let mut obj_slab = slab::Slab::new();
+
+    for (tileset, i) in tiled_json.array_field("tilesets")?.iter().zip(0..) {
+        tilesets.push(parse_tileset(tileset, path_prefix, i, &mut obj_slab)?);
+    }
+    let tile_buffer = gid_tileset_buffer(&tilesets);
+
+    let mut tile_layers = Vec::new();
+    let mut object_layers = Vec::new();
+
+    let mut tile_layer_map = HashMap::new();
+    let mut object_layer_map = HashMap::new();
+
+    let mut obj_id_to_ref_map = HashMap::new();
+
+    let mut tile_llid = 0;
+    let mut obj_llid = 0;
+
+    for layer in tiled_json.array_field("layers")? {
+        match parse_layer_type(layer)? {
+            LayerType::Tile => {
+                let tile_layer = parse_tile_layer(layer, tile_llid, &tile_buffer)?;
+                tile_layer_map.insert(tile_layer.name.clone(), tile_layer.id);
+                tile_layers.push(tile_layer);
+                tile_llid += 1;
+            }
+            LayerType::Object => {
+                let (obj_group, obj_ids_and_refs) =
+                    parse_object_group(layer, obj_llid, true, &mut obj_slab, Some(&tile_buffer))?;
+                for (obj_id, obj_ref) in obj_ids_and_refs.iter() {
+                    obj_id_to_ref_map.insert(*obj_id, *obj_ref);
+                }
+                object_layer_map.insert(obj_group.name.clone(), obj_group.id);
+                object_layers.push(obj_group);
+                obj_llid += 1;
+            }
+        }
+    }
+
+    Ok(Map::new(
+        meta_data,
+        tile_layers,
+        object_layers,
+        Tilesets(tilesets),
+        tile_layer_map,
+        object_layer_map,
+        obj_slab,
+        obj_id_to_ref_map,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LUA_MAP: &str = r#"
+        return {
+          version = "1.5",
+          luaversion = "5.1",
+          tiledversion = "1.7.0",
+          orientation = "orthogonal",
+          renderorder = "right-down",
+          width = 4,
+          height = 3,
+          tilewidth = 16,
+          tileheight = 16,
+          nextlayerid = 4,
+          nextobjectid = 2,
+          properties = {},
+          tilesets = {
+            {
+              name = "blocks",
+              firstgid = 1,
+              tilewidth = 16,
+              tileheight = 16,
+              spacing = 0,
+              margin = 0,
+              columns = 4,
+              image = "blocks.png",
+              imagewidth = 64,
+              imageheight = 16,
+              tilecount = 4,
+              properties = {},
+              tiles = {}
+            }
+          },
+          layers = {
+            {
+              type = "tilelayer",
+              x = 0, y = 0, width = 4, height = 3,
+              id = 1,
+              name = "Ground",
+              visible = true,
+              opacity = 1,
+              offsetx = 0, offsety = 0,
+              properties = {},
+              encoding = "lua",
+              data = {
+                1, 2, 0, 4,
+                2147483650, 1073741827, 536870916, 3758096385,
+                3, 3, 0, 1
+              }
+            },
+            {
+              type = "tilelayer",
+              x = 0, y = 0, width = 4, height = 3,
+              id = 2,
+              name = "Decor",
+              visible = true,
+              opacity = 1,
+              offsetx = 0, offsety = 0,
+              properties = {},
+              encoding = "csv",
+              data = "0,0,0,0,\n0,2147483651,3,0,\n0,0,0,0"
+            },
+            {
+              type = "objectgroup",
+              draworder = "topdown",
+              id = 3,
+              name = "Things",
+              visible = true,
+              opacity = 1,
+              offsetx = 0, offsety = 0,
+              properties = {},
+              objects = {
+                {
+                  id = 1,
+                  name = "coin",
+                  type = "",
+                  shape = "rectangle",
+                  x = 16, y = 32, width = 16, height = 16,
+                  rotation = 0,
+                  gid = 1073741826,
+                  visible = true,
+                  properties = {}
+                }
+              }
+            }
+          }
+        }
+    "#;
+
+    const JSON_MAP: &str = r#"
+        {
+          "version": "1.6",
+          "tiledversion": "1.7.0",
+          "type": "map",
+          "orientation": "orthogonal",
+          "renderorder": "right-down",
+          "width": 4,
+          "height": 3,
+          "tilewidth": 16,
+          "tileheight": 16,
+          "infinite": false,
+          "nextlayerid": 4,
+          "nextobjectid": 2,
+          "tilesets": [
+            {
+              "name": "blocks",
+              "firstgid": 1,
+              "tilewidth": 16,
+              "tileheight": 16,
+              "spacing": 0,
+              "margin": 0,
+              "columns": 4,
+              "image": "blocks.png",
+              "imagewidth": 64,
+              "imageheight": 16,
+              "tilecount": 4
+            }
+          ],
+          "layers": [
+            {
+              "type": "tilelayer",
+              "x": 0, "y": 0, "width": 4, "height": 3,
+              "id": 1,
+              "name": "Ground",
+              "visible": true,
+              "opacity": 1,
+              "data": [
+                1, 2, 0, 4,
+                2147483650, 1073741827, 536870916, 3758096385,
+                3, 3, 0, 1
+              ]
+            },
+            {
+              "type": "tilelayer",
+              "x": 0, "y": 0, "width": 4, "height": 3,
+              "id": 2,
+              "name": "Decor",
+              "visible": true,
+              "opacity": 1,
+              "encoding": "base64",
+              "compression": "zlib",
+              "data": "eJxjYMAEzAwMDcxYxEEAAA1MAIc="
+            },
+            {
+              "type": "objectgroup",
+              "draworder": "topdown",
+              "id": 3,
+              "name": "Things",
+              "visible": true,
+              "opacity": 1,
+              "x": 0, "y": 0,
+              "objects": [
+                {
+                  "id": 1,
+                  "name": "coin",
+                  "type": "",
+                  "x": 16, "y": 32, "width": 16, "height": 16,
+                  "rotation": 0,
+                  "gid": 1073741826,
+                  "visible": true
+                }
+              ]
+            }
+          ]
+        }
+    "#;
+
+    fn grid(map: &Map, layer: &str) -> Vec<(i32, i32, TileId)> {
+        let layer_id = map.tile_layer_map[layer];
+        map.tile_layers[layer_id.llid as usize].tiles_in_render_order(RenderOrder::RightDown)
+    }
+
+    #[test]
+    fn lua_and_json_maps_are_identical() {
+        let lua = Lua::new();
+        let table = lua.load(LUA_MAP).eval::<LuaTable>().unwrap();
+        let from_lua = crate::lua_parser::parse_map_table(&table, None).unwrap();
+        let from_json = parse_map_str(JSON_MAP, None).unwrap();
+
+        for layer in ["Ground", "Decor"].iter() {
+            assert_eq!(grid(&from_lua, layer), grid(&from_json, layer));
+        }
+
+        // Flip flags come off the GIDs the same way for every encoding.
+        let ground = grid(&from_json, "Ground");
+        assert_eq!(ground.len(), 10);
+        let flips = |tile: TileId| (tile.1.flipx(), tile.1.flipy(), tile.1.diag_flip());
+        let row = ground
+            .iter()
+            .filter(|&&(_, y, _)| y == 1)
+            .map(|&(_, _, tile)| (tile.to_index(), flips(tile)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            row,
+            [
+                (Some(1), (true, false, false)),
+                (Some(2), (false, true, false)),
+                (Some(3), (false, false, true)),
+                (Some(0), (true, true, true)),
+            ]
+        );
+
+        let decor = grid(&from_json, "Decor");
+        assert_eq!(decor.len(), 2);
+        assert_eq!((decor[0].0, decor[0].1), (1, 1));
+        assert_eq!(flips(decor[0].2), (true, false, false));
+
+        let object_tile = |map: &Map| {
+            map.get_object_from_id(&ObjectId::new(1, true))
+                .unwrap()
+                .tile_id
+        };
+        assert_eq!(object_tile(&from_lua), object_tile(&from_json));
+        assert_eq!(
+            object_tile(&from_json).map(flips),
+            Some((false, true, false))
+        );
+    }
+
+    #[test]
+    fn wang_tiles_resolve_from_color_signatures() {
+        let ts: Value = serde_json::from_str(
+            r##"
+            {
+              "name": "terrain",
+              "firstgid": 5,
+              "tilewidth": 16, "tileheight": 16,
+              "spacing": 0, "margin": 0,
+              "columns": 4, "tilecount": 4,
+              "image": "terrain.png", "imagewidth": 64, "imageheight": 16,
+              "wangsets": [
+                {
+                  "name": "Ground",
+                  "type": "corner",
+                  "tile": -1,
+                  "colors": [
+                    { "name": "Grass", "color": "#00ff00", "probability": 1, "tile": -1 },
+                    { "name": "Dirt", "color": "#804000", "probability": 1, "tile": -1 }
+                  ],
+                  "wangtiles": [
+                    { "tileid": 0, "wangid": [0, 1, 0, 1, 0, 1, 0, 1] },
+                    { "tileid": 1, "wangid": [0, 1, 0, 2, 0, 2, 0, 1] }
+                  ]
+                },
+                {
+                  "name": "Walls",
+                  "type": "edge",
+                  "tile": -1,
+                  "colors": [
+                    { "name": "Brick", "color": "#ff0000", "probability": 1, "tile": -1 }
+                  ],
+                  "wangtiles": [
+                    { "tileid": 2, "wangid": [1, 0, 0, 0, 0, 0, 1, 0] }
+                  ]
+                }
+              ]
+            }
+            "##,
+        )
+        .unwrap();
+        let tileset = parse_tileset(&ts, None, 0, &mut slab::Slab::new()).unwrap();
+
+        assert_eq!(tileset.wang_sets.len(), 2);
+        let ground = &tileset.wang_sets[0];
+        assert_eq!(ground.wang_type, WangSetType::Corner);
+        assert_eq!(ground.colors[1].name, "Dirt");
+
+        // Grass along the top, dirt along the bottom. IDs are global, offset by `firstgid`.
+        let gid = |colors| tileset.wang_tile(colors).map(|tile| tile.0);
+        assert_eq!(gid(WangId::corners(1, 2, 2, 1)), Some(6));
+        assert_eq!(gid(WangId::corners(1, 1, 1, 1)), Some(5));
+        assert_eq!(gid(WangId::corners(2, 2, 2, 2)), None);
+        assert_eq!(gid(WangId::edges(1, 0, 0, 1)), Some(7));
+
+        // Edge colors don't matter to a corner set, and vice versa.
+        let noisy = WangId([9, 1, 9, 2, 9, 2, 9, 1]);
+        assert_eq!(ground.wang_tile(noisy).map(|tile| tile.0), Some(6));
+        assert_eq!(tileset.wang_sets[1].wang_tile(noisy), None);
+    }
+
+    #[test]
+    fn csv_decoding_ignores_whitespace() {
+        assert_eq!(
+            decode_csv("1, 2,\n3,\r\n2147483652,").unwrap(),
+            [1, 2, 3, 0x8000_0004]
+        );
+        assert!(decode_csv("1,x").is_err());
+    }
+}
@@ -1,9 +1,14 @@
+pub mod atlas;
+pub mod json_parser;
 pub mod lua_parser;
+pub mod map_set;
 pub mod object_layer;
 pub mod render;
 pub mod tile_layer;
 
+pub use crate::atlas::*;
 use crate::lua_parser::ColorExt;
+pub use crate::map_set::*;
 use crate::object_layer::*;
 pub use crate::render::*;
 use crate::tile_layer::*;
@@ -12,15 +17,22 @@ use hv_core::{engine::Engine, prelude::*};
 
 use hv_friends::{
     graphics::{
-        sprite::{AnimationState, Direction, Frame, SpriteSheet, Tag, TagId},
+        sprite::{
+            AnimationState, Direction, Frame, FrameId, SpriteSheet, SpriteSheetSource, Tag, TagId,
+        },
         CachedTexture, Color, Drawable, DrawableMut, Graphics, GraphicsLock, GraphicsLockExt,
         Instance, SpriteBatch, SpriteId, Texture,
     },
     math::Box2,
+    math::Point2,
     math::Vector2,
 };
 
-use std::{collections::HashMap, io::Read, path::Path};
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    io::Read,
+    path::Path,
+};
 
 const EMPTY_TILE: TileId = TileId(0, TileMetaData(0));
 const CHUNK_SIZE: u32 = 16;
@@ -75,6 +87,30 @@ impl Property {
             p => Err(anyhow!("Attempted to get a color from a {:?}", p)),
         }
     }
+
+    /// Convert this property into the Lua value of the corresponding type. Colors are converted to
+    /// [`Color`]s, and object references are looked up in the map held by `map_ud`, producing the
+    /// referenced [`Object`] or `nil` if there's no such object.
+    pub fn to_lua_with_map<'lua>(
+        &self,
+        lua: &'lua Lua,
+        map_ud: &LuaAnyUserData<'lua>,
+    ) -> LuaResult<LuaValue<'lua>> {
+        match self {
+            Property::Bool(b) => b.to_lua(lua),
+            Property::Float(f) => f.to_lua(lua),
+            Property::Int(i) => i.to_lua(lua),
+            Property::String(s) | Property::File(s) => s.as_str().to_lua(lua),
+            Property::Color(_) => self.as_color().to_lua_err()?.to_lua(lua),
+            Property::Obj(obj_id) => {
+                let object = map_ud.borrow::<Map>()?.get_object_from_id(obj_id).cloned();
+                match object {
+                    Some(object) => object.to_lua_with_map(lua, map_ud),
+                    None => Ok(LuaValue::Nil),
+                }
+            }
+        }
+    }
 }
 
 pub trait BoxExt {
@@ -119,6 +155,20 @@ impl Properties {
     pub fn get_property(&self, key: &str) -> Option<&Property> {
         self.0.get(key)
     }
+
+    /// Convert these properties into a Lua table, converting each property as per
+    /// [`Property::to_lua_with_map`].
+    pub fn to_lua_with_map<'lua>(
+        &self,
+        lua: &'lua Lua,
+        map_ud: &LuaAnyUserData<'lua>,
+    ) -> LuaResult<LuaTable<'lua>> {
+        let table = lua.create_table_with_capacity(0, self.0.len() as i32)?;
+        for (key, property) in self.0.iter() {
+            table.set(key.as_str(), property.to_lua_with_map(lua, map_ud)?)?;
+        }
+        Ok(table)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -127,7 +177,10 @@ pub enum Orientation {
     Isometric,
 }
 
-#[derive(Debug, Clone)]
+/// The order in which Tiled draws the tiles of a layer: which way to go along a row, and which way
+/// to move between rows. Later tiles are drawn over earlier ones, which matters for tiles which
+/// overlap, such as the tiles of an isometric map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RenderOrder {
     RightDown,
     RightUp,
@@ -182,6 +235,14 @@ impl TileId {
         )
     }
 
+    /// This tile ID with its flip flags cleared, as used to look up the tile in its tileset.
+    pub fn without_flags(&self) -> TileId {
+        TileId(
+            self.0,
+            TileMetaData::new(self.1.tileset_id(), false, false, false),
+        )
+    }
+
     fn from_gid(mut gid: u32, tile_buffer: &[u32]) -> TileId {
         // For each tile, we check the flip flags and set the metadata with them.
         // We then unset the flip flags in the tile ID
@@ -197,6 +258,12 @@ impl TileId {
     }
 }
 
+impl LuaUserData for TileId {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("to_index", |_, this, ()| Ok(this.to_index()));
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct MapMetaData {
     pub tsx_ver: String,
@@ -242,6 +309,80 @@ pub enum TileChange {
     TileAddition(TileAddition),
 }
 
+impl TileChange {
+    /// A change placing `tile` at the given tile coordinates, for use with
+    /// [`Map::apply_changes`]. The tile it replaces is filled in when the change is applied.
+    pub fn set(layer_id: TileLayerId, x: i32, y: i32, tile: TileId) -> Self {
+        TileChange::TileAddition(TileAddition {
+            changed_id: None,
+            new_id: tile,
+            layer_id,
+            x,
+            y,
+        })
+    }
+
+    /// A change clearing the tile at the given tile coordinates, for use with
+    /// [`Map::apply_changes`].
+    pub fn remove(layer_id: TileLayerId, x: i32, y: i32) -> Self {
+        TileChange::TileRemoval(TileRemoval {
+            id: EMPTY_TILE,
+            layer_id,
+            x,
+            y,
+        })
+    }
+}
+
+/// The net change to a single cell of a tile layer over a batch of [`TileChange`]s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileCellDelta {
+    pub layer_id: TileLayerId,
+    pub x: i32,
+    pub y: i32,
+    /// The tile in the cell before the batch was applied.
+    pub old: Option<TileId>,
+    /// The tile in the cell after the batch was applied.
+    pub new: Option<TileId>,
+}
+
+/// The combined result of applying a batch of [`TileChange`]s with [`Map::apply_changes`]. Each
+/// changed cell appears exactly once, with only its state before and after the whole batch, so
+/// that [`TileLayerBatches::apply_delta`] touches every cell at most once no matter how many times
+/// it was changed.
+#[derive(Debug, Clone, Default)]
+pub struct MapBatchDelta {
+    cells: Vec<TileCellDelta>,
+}
+
+impl MapBatchDelta {
+    /// The changed cells, in the order they were first changed in.
+    pub fn cells(&self) -> &[TileCellDelta] {
+        &self.cells
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+}
+
+/// The first solid tile hit by [`Map::cast_ray`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TileRayHit {
+    /// The tile which was hit.
+    pub tile: TileId,
+    /// The tile coordinates of the cell which was hit.
+    pub x: i32,
+    pub y: i32,
+    /// Where the ray entered the cell, in pixels.
+    pub point: Point2<f32>,
+    /// The normal of the cell face the ray entered through, pointing back towards the ray's
+    /// origin. Zero if the ray started inside the cell.
+    pub normal: Vector2<f32>,
+    /// How far along the ray the hit is, in pixels.
+    pub distance: f32,
+}
+
 #[derive(Debug, Clone)]
 pub enum ObjectChange {
     ObjectRemoval(ObjectRemoval),
@@ -258,6 +399,9 @@ pub struct Map {
     pub object_layer_map: HashMap<String, ObjectLayerId>,
     obj_slab: slab::Slab<Object>,
     obj_id_to_ref_map: HashMap<ObjectId, ObjectRef>,
+    // The lowest local layer id which has never been given to a tile layer, so that the ids of
+    // removed layers aren't reused
+    next_tile_layer_llid: u32,
     pub chunk_changes: shrev::EventChannel<TileChange>,
     pub object_changes: shrev::EventChannel<ObjectChange>,
 }
@@ -273,13 +417,84 @@ impl Clone for Map {
             object_layer_map: self.object_layer_map.clone(),
             obj_slab: self.obj_slab.clone(),
             obj_id_to_ref_map: self.obj_id_to_ref_map.clone(),
+            next_tile_layer_llid: self.next_tile_layer_llid,
             chunk_changes: shrev::EventChannel::new(),
             object_changes: shrev::EventChannel::new(),
         }
     }
 }
 
-impl LuaUserData for Map {}
+impl LuaUserData for Map {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_function(
+            "get_tile",
+            |_, (this, x, y, layer_name): (LuaAnyUserData, i32, i32, LuaString)| {
+                let map = this.borrow::<Map>()?;
+                let layer_id = map.tile_layer_id(layer_name.to_str()?).to_lua_err()?;
+                Ok(map.get_tile(x, y, layer_id, CoordSpace::Tile))
+            },
+        );
+
+        methods.add_method("tile_to_pixel", |_, this, (x, y): (i32, i32)| {
+            Ok(this.tile_to_pixel(x, y))
+        });
+
+        // Fractional pixel coordinates are truncated the same way `BoxExt::floor_to_i32` does
+        // before being converted, so Lua and Rust agree on which tile a position is in.
+        methods.add_method("pixel_to_tile", |_, this, (x, y): (f32, f32)| {
+            Ok(this.pixel_to_tile(x as i32, y as i32))
+        });
+
+        methods.add_method("screen_to_tile_iso", |_, this, (x, y): (f32, f32)| {
+            Ok(this.screen_to_tile_iso(Point2::new(x, y)))
+        });
+
+        methods.add_method("tile_center_iso", |_, this, (x, y): (i32, i32)| {
+            let center = this.tile_center_iso(x, y);
+            Ok((center.x, center.y))
+        });
+
+        methods.add_method(
+            "tiles_in_bb",
+            |lua, this, (x, y, w, h, layer_name): (f32, f32, f32, f32, LuaString)| {
+                let layer_id = this.tile_layer_id(layer_name.to_str()?).to_lua_err()?;
+                let bb = Box2::new(x, y, w, h).floor_to_i32();
+                let tiles = lua.create_table()?;
+                let found = this.get_tiles_in_bb(bb, layer_id, CoordSpace::Pixel);
+                for (i, (tile, x, y)) in found.enumerate() {
+                    let entry = lua.create_table()?;
+                    entry.set("x", x)?;
+                    entry.set("y", y)?;
+                    entry.set("tile", tile)?;
+                    tiles.raw_set(i + 1, entry)?;
+                }
+                Ok(tiles)
+            },
+        );
+
+        methods.add_function(
+            "get_tile_properties",
+            |lua, (this, tile_id): (LuaAnyUserData, TileId)| {
+                let map = this.borrow::<Map>()?;
+                map.tilesets
+                    .get_tile(&tile_id.without_flags())
+                    .map(|tile| tile.properties.to_lua_with_map(lua, &this))
+                    .transpose()
+            },
+        );
+
+        methods.add_function("get_object", |lua, (this, id): (LuaAnyUserData, u32)| {
+            let object = this
+                .borrow::<Map>()?
+                .get_object_from_id(&ObjectId::new(id, true))
+                .cloned();
+            match object {
+                Some(object) => object.to_lua_with_map(lua, &this),
+                None => Ok(LuaValue::Nil),
+            }
+        });
+    }
+}
 
 #[derive(Debug, Clone)]
 pub enum CoordSpace {
@@ -308,11 +523,197 @@ impl Map {
             object_layer_map,
             obj_slab,
             obj_id_to_ref_map,
+            next_tile_layer_llid: 0,
             chunk_changes: shrev::EventChannel::new(),
             object_changes: shrev::EventChannel::new(),
         }
     }
 
+    /// Look up the ID of the tile layer with the given name.
+    pub fn tile_layer_id(&self, name: &str) -> Result<TileLayerId> {
+        self.tile_layer_map
+            .get(name)
+            .copied()
+            .ok_or_else(|| anyhow!("no tile layer named {:?}", name))
+    }
+
+    /// Look up a tile layer by its ID.
+    pub fn tile_layer(&self, layer_id: TileLayerId) -> Option<&TileLayer> {
+        self.tile_layer_index(layer_id)
+            .map(|index| &self.tile_layers[index])
+    }
+
+    /// Look up a tile layer by its ID, mutably.
+    pub fn tile_layer_mut(&mut self, layer_id: TileLayerId) -> Option<&mut TileLayer> {
+        self.tile_layer_index(layer_id)
+            .map(move |index| &mut self.tile_layers[index])
+    }
+
+    /// The position of a tile layer in `tile_layers`, which is also the order the layers are
+    /// drawn in. Until layers are removed or reordered, this is the layer's local ID.
+    fn tile_layer_index(&self, layer_id: TileLayerId) -> Option<usize> {
+        find_layer_index(&self.tile_layers, layer_id, |layer| layer.id)
+    }
+
+    fn expect_tile_layer_index(&self, layer_id: TileLayerId) -> usize {
+        self.tile_layer_index(layer_id)
+            .unwrap_or_else(|| panic!("no tile layer with id {:?}", layer_id))
+    }
+
+    /// Add an empty tile layer named `name`, drawn above every other tile layer. The new layer has
+    /// the size of the map and no properties; it can be changed afterwards through
+    /// [`Map::tile_layer_mut`]. Fails if there's already a tile layer with the same name.
+    ///
+    /// Render batches created before the layer was added won't draw it until
+    /// [`TileLayerBatches::sync_layers`] is called.
+    pub fn add_tile_layer(&mut self, name: &str) -> Result<TileLayerId> {
+        ensure!(
+            !self.tile_layer_map.contains_key(name),
+            "there is already a tile layer named {:?}",
+            name
+        );
+
+        let max_id = |f: fn(&TileLayerId) -> u32| {
+            self.tile_layers
+                .iter()
+                .map(|layer| f(&layer.id) + 1)
+                .max()
+                .unwrap_or(0)
+        };
+        let id = TileLayerId {
+            glid: max_id(|id| id.glid).max(self.meta_data.nextlayerid),
+            llid: max_id(|id| id.llid).max(self.next_tile_layer_llid),
+        };
+        self.meta_data.nextlayerid = id.glid + 1;
+        self.next_tile_layer_llid = id.llid + 1;
+
+        self.tile_layers.push(TileLayer {
+            layer_type: LayerType::Tile,
+            id,
+            name: name.to_owned(),
+            x: 0,
+            y: 0,
+            width: self.meta_data.width,
+            height: self.meta_data.height,
+            visible: true,
+            opacity: 1.,
+            offset_x: 0,
+            offset_y: 0,
+            properties: Properties(HashMap::new()),
+            data: Chunks::new(),
+        });
+        self.tile_layer_map.insert(name.to_owned(), id);
+
+        Ok(id)
+    }
+
+    /// Remove a tile layer, returning it. The IDs of the remaining layers are unaffected, and the
+    /// removed layer's ID is never reused.
+    ///
+    /// Render batches created before the layer was removed will keep drawing it until
+    /// [`TileLayerBatches::sync_layers`] is called.
+    pub fn remove_tile_layer(&mut self, layer_id: TileLayerId) -> Result<TileLayer> {
+        let index = self
+            .tile_layer_index(layer_id)
+            .ok_or_else(|| anyhow!("no tile layer with id {:?}", layer_id))?;
+        let layer = self.tile_layers.remove(index);
+        self.tile_layer_map.retain(|_, id| *id != layer_id);
+        self.next_tile_layer_llid = self.next_tile_layer_llid.max(layer_id.llid + 1);
+        Ok(layer)
+    }
+
+    /// Change the order the tile layers are drawn in. `new_order` lists the ID of every tile layer
+    /// exactly once, in the order they should be drawn, so the last layer is drawn on top.
+    ///
+    /// Render batches created before the layers were reordered will keep drawing them in the old
+    /// order until [`TileLayerBatches::sync_layers`] is called.
+    pub fn reorder_layers(&mut self, new_order: &[TileLayerId]) -> Result<()> {
+        ensure!(
+            new_order.len() == self.tile_layers.len(),
+            "expected {} tile layers in the new order, but got {}",
+            self.tile_layers.len(),
+            new_order.len()
+        );
+
+        let mut seen = HashSet::new();
+        for &layer_id in new_order {
+            ensure!(
+                self.tile_layer_index(layer_id).is_some(),
+                "no tile layer with id {:?}",
+                layer_id
+            );
+            ensure!(
+                seen.insert(layer_id.llid),
+                "tile layer {:?} is listed more than once",
+                layer_id
+            );
+        }
+
+        let mut layers = self.tile_layers.drain(..).map(Some).collect::<Vec<_>>();
+        let reordered = new_order
+            .iter()
+            .map(|&layer_id| {
+                let index = layers
+                    .iter()
+                    .position(|layer| layer.as_ref().map_or(false, |l| l.id == layer_id))
+                    .unwrap();
+                layers[index].take().unwrap()
+            })
+            .collect();
+
+        self.tile_layers = reordered;
+        Ok(())
+    }
+
+    /// Convert pixel coordinates to the coordinates of the tile containing them. This is the
+    /// conversion used for [`CoordSpace::Pixel`] by [`Map::get_tile`], [`Map::set_tile`] and
+    /// [`Map::remove_tile`].
+    pub fn pixel_to_tile(&self, x: i32, y: i32) -> (i32, i32) {
+        (
+            x / self.meta_data.tilewidth as i32,
+            y / self.meta_data.tileheight as i32,
+        )
+    }
+
+    /// Convert tile coordinates to the pixel coordinates of the tile's top-left corner.
+    pub fn tile_to_pixel(&self, x: i32, y: i32) -> (i32, i32) {
+        (
+            x * self.meta_data.tilewidth as i32,
+            y * self.meta_data.tileheight as i32,
+        )
+    }
+
+    /// The center of an isometric tile's diamond in world space, as drawn by [`TileLayerBatch`]:
+    /// tile `(0, 0)` is centered on the origin, increasing X moves right and down the screen, and
+    /// increasing Y moves right and up, with Y pointing up.
+    pub fn tile_center_iso(&self, x: i32, y: i32) -> Point2<f32> {
+        let (half_w, half_h) = self.iso_half_extents();
+        Point2::new((x + y) as f32 * half_w, (y - x) as f32 * half_h)
+    }
+
+    /// Find the isometric tile whose diamond contains a point in world space, as drawn by
+    /// [`TileLayerBatch`]; this is the inverse of [`Map::tile_center_iso`]. Only meaningful for
+    /// maps with [`Orientation::Isometric`]; for orthogonal maps, use [`Map::pixel_to_tile`].
+    ///
+    /// The diamonds are the size of the map's tiles. A point exactly on the edge between two
+    /// diamonds belongs to the one with the greater tile coordinate, so every point falls in
+    /// exactly one tile.
+    pub fn screen_to_tile_iso(&self, world_point: Point2<f32>) -> (i32, i32) {
+        let (half_w, half_h) = self.iso_half_extents();
+        // In units of half a tile, the sum and difference of the tile coordinates.
+        let (u, v) = (world_point.x / half_w, world_point.y / half_h);
+        // Each diamond is a unit square around its tile coordinates in this space.
+        let (x, y) = ((u - v) / 2., (u + v) / 2.);
+        ((x + 0.5).floor() as i32, (y + 0.5).floor() as i32)
+    }
+
+    fn iso_half_extents(&self) -> (f32, f32) {
+        (
+            self.meta_data.tilewidth as f32 / 2.,
+            self.meta_data.tileheight as f32 / 2.,
+        )
+    }
+
     pub fn remove_tile(
         &mut self,
         x: i32,
@@ -321,17 +722,12 @@ impl Map {
         layer_id: TileLayerId,
     ) {
         let (x, y) = match coordinate_space {
-            CoordSpace::Pixel => (
-                x / (self.meta_data.tilewidth) as i32,
-                y / (self.meta_data.tileheight as i32),
-            ),
+            CoordSpace::Pixel => self.pixel_to_tile(x, y),
             CoordSpace::Tile => (x, y),
         };
 
-        if let Some(tile_id) = self.tile_layers[layer_id.llid as usize]
-            .data
-            .remove_tile(x, y)
-        {
+        let index = self.expect_tile_layer_index(layer_id);
+        if let Some(tile_id) = self.tile_layers[index].data.remove_tile(x, y) {
             assert!(tile_id.to_index().is_some());
             self.chunk_changes
                 .single_write(TileChange::TileRemoval(TileRemoval {
@@ -352,15 +748,12 @@ impl Map {
         coordinate_space: CoordSpace,
     ) {
         let (x, y) = match coordinate_space {
-            CoordSpace::Pixel => (
-                x / (self.meta_data.tilewidth as i32),
-                y / (self.meta_data.tileheight as i32),
-            ),
+            CoordSpace::Pixel => self.pixel_to_tile(x, y),
             CoordSpace::Tile => (x, y),
         };
 
-        let layer = &mut self.tile_layers[layer_id.llid as usize];
-        let changed_id = layer.data.set_tile(x, y, tile);
+        let index = self.expect_tile_layer_index(layer_id);
+        let changed_id = self.tile_layers[index].data.set_tile(x, y, tile);
         self.chunk_changes
             .single_write(TileChange::TileAddition(TileAddition {
                 new_id: tile,
@@ -371,6 +764,47 @@ impl Map {
             }));
     }
 
+    /// Apply a batch of tile changes (in tile coordinates) at once, returning their combined
+    /// effect. If a cell is changed more than once, the last change wins. Unlike
+    /// [`Map::set_tile`] and [`Map::remove_tile`], this doesn't write anything to
+    /// `chunk_changes`; pass the returned delta to [`TileLayerBatches::apply_delta`] instead.
+    pub fn apply_changes(&mut self, changes: &[TileChange]) -> MapBatchDelta {
+        let mut cells: Vec<TileCellDelta> = Vec::new();
+        let mut cell_indices = HashMap::new();
+
+        for change in changes {
+            let (layer_id, x, y, new) = match change {
+                TileChange::TileAddition(a) => (a.layer_id, a.x, a.y, Some(a.new_id)),
+                TileChange::TileRemoval(r) => (r.layer_id, r.x, r.y, None),
+            };
+
+            let index = self.expect_tile_layer_index(layer_id);
+            let data = &mut self.tile_layers[index].data;
+            let old = match new {
+                Some(tile) => data.set_tile(x, y, tile),
+                None => data.remove_tile(x, y),
+            };
+
+            match cell_indices.entry((layer_id.llid, x, y)) {
+                Entry::Occupied(entry) => cells[*entry.get()].new = new,
+                Entry::Vacant(entry) => {
+                    entry.insert(cells.len());
+                    cells.push(TileCellDelta {
+                        layer_id,
+                        x,
+                        y,
+                        old,
+                        new,
+                    });
+                }
+            }
+        }
+
+        // Cells which were changed and then changed back don't need to be touched at all.
+        cells.retain(|cell| cell.old != cell.new);
+        MapBatchDelta { cells }
+    }
+
     pub fn get_tile(
         &self,
         x: i32,
@@ -379,14 +813,11 @@ impl Map {
         coordinate_space: CoordSpace,
     ) -> Option<TileId> {
         let (x, y) = match coordinate_space {
-            CoordSpace::Pixel => (
-                x / (self.meta_data.tilewidth as i32),
-                y / (self.meta_data.tileheight as i32),
-            ),
+            CoordSpace::Pixel => self.pixel_to_tile(x, y),
             CoordSpace::Tile => (x, y),
         };
 
-        let layer = &self.tile_layers[layer_id.llid as usize];
+        let layer = &self.tile_layers[self.expect_tile_layer_index(layer_id)];
 
         match layer.data.get_tile(x, y) {
             Some(t_id) if t_id.to_index().is_some() => Some(t_id),
@@ -423,6 +854,78 @@ impl Map {
         })
     }
 
+    /// Cast a ray through a tile layer, returning the first tile within `max_dist` for which
+    /// `is_solid` returns true. The origin and distance are in pixels, and `dir` doesn't need to be
+    /// normalized.
+    ///
+    /// Cells are visited in the order the ray passes through them (a DDA grid traversal), so only
+    /// the cells along the ray are ever looked at. A ray starting inside a solid tile hits it
+    /// immediately, with a distance of zero and a zero normal. `max_dist` must be finite, as tile
+    /// layers have no bounds to stop at.
+    pub fn cast_ray(
+        &self,
+        layer_id: TileLayerId,
+        origin: Point2<f32>,
+        dir: Vector2<f32>,
+        max_dist: f32,
+        mut is_solid: impl FnMut(TileId) -> bool,
+    ) -> Option<TileRayHit> {
+        let dir = dir.try_normalize(f32::EPSILON)?;
+        let cell_size = Vector2::new(
+            self.meta_data.tilewidth as f32,
+            self.meta_data.tileheight as f32,
+        );
+
+        let mut cell = [
+            (origin.x / cell_size.x).floor() as i32,
+            (origin.y / cell_size.y).floor() as i32,
+        ];
+        // For each axis: which way the ray steps between cells, the distance along the ray to the
+        // first cell boundary, and the distance between boundaries.
+        let axis = |origin: f32, dir: f32, size: f32, cell: i32| {
+            if dir > 0. {
+                (1, ((cell + 1) as f32 * size - origin) / dir, size / dir)
+            } else if dir < 0. {
+                (-1, (cell as f32 * size - origin) / dir, -size / dir)
+            } else {
+                (0, f32::INFINITY, f32::INFINITY)
+            }
+        };
+        let (step_x, t_max_x, t_delta_x) = axis(origin.x, dir.x, cell_size.x, cell[0]);
+        let (step_y, t_max_y, t_delta_y) = axis(origin.y, dir.y, cell_size.y, cell[1]);
+        let step = [step_x, step_y];
+        let mut t_max = [t_max_x, t_max_y];
+        let t_delta = [t_delta_x, t_delta_y];
+
+        let mut distance = 0.;
+        let mut normal = Vector2::zeros();
+        loop {
+            if let Some(tile) = self.get_tile(cell[0], cell[1], layer_id, CoordSpace::Tile) {
+                if is_solid(tile) {
+                    return Some(TileRayHit {
+                        tile,
+                        x: cell[0],
+                        y: cell[1],
+                        point: origin + dir * distance,
+                        normal,
+                        distance,
+                    });
+                }
+            }
+
+            let axis = if t_max[0] < t_max[1] { 0 } else { 1 };
+            distance = t_max[axis];
+            if distance > max_dist {
+                return None;
+            }
+
+            cell[axis] += step[axis];
+            t_max[axis] += t_delta[axis];
+            normal = Vector2::zeros();
+            normal[axis] = -step[axis] as f32;
+        }
+    }
+
     pub fn get_obj_from_ref(&self, obj_ref: &ObjectRef) -> &Object {
         &self.obj_slab[obj_ref.0]
     }
@@ -511,12 +1014,191 @@ pub struct Tileset {
     pub tiles: HashMap<TileId, Tile>,
     pub properties: Properties,
     pub images: Vec<Image>,
+    pub wang_sets: Vec<WangSet>,
 }
 
 impl Tileset {
     fn get_tile(&self, tile_id: &TileId) -> Option<&Tile> {
         self.tiles.get(tile_id)
     }
+
+    /// Build a sprite sheet from a tile's animation, so that animated objects spawned from tiles
+    /// can be driven by a [`SpriteAnimation`](hv_friends::graphics::sprite::SpriteAnimation). The
+    /// tile ID is the one used as a key in [`Tileset::tiles`].
+    ///
+    /// The sheet has one frame per animation frame, in order, with UVs into the tileset's image and
+    /// the same durations in milliseconds, and a single unnamed tag ([`TagId`] `0`) running through
+    /// all of them. Returns `None` if the tile has no animation or the tileset has no image.
+    pub fn animation_as_spritesheet(&self, tile_id: &TileId) -> Option<SpriteSheet> {
+        let animation = self.get_tile(tile_id)?.animation.as_ref()?;
+        let image = self.images.first()?;
+        let uvs = tileset_uvs(self, image.width, image.height).collect::<Vec<_>>();
+
+        let mut sprite_sheet = SpriteSheet::new();
+        sprite_sheet.source = Some(SpriteSheetSource {
+            image: Some(image.source.clone()),
+            size: Vector2::new(image.width, image.height),
+        });
+
+        for (frame_tile, duration) in animation.0.iter() {
+            sprite_sheet.insert_frame(Frame {
+                source: None,
+                offset: Vector2::new(0.0, 0.0),
+                uvs: *uvs.get(frame_tile.0 as usize)?,
+                duration: *duration,
+            });
+        }
+
+        sprite_sheet.insert_tag(Tag {
+            name: None,
+            from: FrameId(0),
+            to: sprite_sheet.last_frame_id(),
+            direction: Direction::Forward,
+        });
+
+        Some(sprite_sheet)
+    }
+
+    /// Find a tile matching the given color signature in any of this tileset's Wang sets, checking
+    /// the sets in order. See [`WangSet::wang_tile`].
+    pub fn wang_tile(&self, colors: WangId) -> Option<TileId> {
+        self.wang_sets.iter().find_map(|set| set.wang_tile(colors))
+    }
+}
+
+/// Which parts of a tile the colors of a [`WangSet`] are assigned to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WangSetType {
+    Corner,
+    Edge,
+    Mixed,
+}
+
+#[derive(Debug, Clone)]
+pub struct WangColor {
+    pub name: String,
+    pub color: Color,
+    pub probability: f32,
+    pub properties: Properties,
+}
+
+/// The colors of a tile's edges and corners in a [`WangSet`], in Tiled's order: top, top-right,
+/// right, bottom-right, bottom, bottom-left, left, top-left. Colors are 1-based indices into
+/// [`WangSet::colors`], and `0` means no color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct WangId(pub [u8; 8]);
+
+impl WangId {
+    /// Build a signature from the eight color IDs Tiled stores for each tile in a Wang set.
+    pub fn from_slice(ids: &[u8]) -> Result<Self, Error> {
+        ensure!(
+            ids.len() == 8,
+            "expected 8 Wang color IDs, got {}",
+            ids.len()
+        );
+        let mut wang_id = WangId::default();
+        wang_id.0.copy_from_slice(ids);
+        Ok(wang_id)
+    }
+
+    /// A signature for a corner set, given the color of each corner.
+    pub fn corners(top_right: u8, bottom_right: u8, bottom_left: u8, top_left: u8) -> Self {
+        WangId([0, top_right, 0, bottom_right, 0, bottom_left, 0, top_left])
+    }
+
+    /// A signature for an edge set, given the color of each edge.
+    pub fn edges(top: u8, right: u8, bottom: u8, left: u8) -> Self {
+        WangId([top, 0, right, 0, bottom, 0, left, 0])
+    }
+
+    /// Clear the parts of the signature which a set of the given type doesn't use, so that
+    /// signatures only differing in those parts compare equal.
+    pub fn masked(self, wang_type: WangSetType) -> Self {
+        let mut masked = self;
+        for (i, color) in masked.0.iter_mut().enumerate() {
+            let is_corner = i % 2 == 1;
+            match wang_type {
+                WangSetType::Corner if !is_corner => *color = 0,
+                WangSetType::Edge if is_corner => *color = 0,
+                _ => {}
+            }
+        }
+        masked
+    }
+}
+
+/// A Tiled Wang set (called a terrain set in Tiled's UI), assigning colors to the corners and/or
+/// edges of tiles for auto-tiling.
+#[derive(Debug, Clone)]
+pub struct WangSet {
+    pub name: String,
+    pub wang_type: WangSetType,
+    pub colors: Vec<WangColor>,
+    /// Every tile in the set and its color signature, in the order Tiled lists them. The tile IDs
+    /// are global, as stored in tile layers, so they can be placed directly with
+    /// [`TileChange::set`].
+    pub tiles: Vec<(TileId, WangId)>,
+    pub properties: Properties,
+}
+
+impl WangSet {
+    /// Convert a tileset's old-style terrains (from before Tiled 1.5) into a corner set. Each tile
+    /// is given with the indices of the terrains at its top-left, top-right, bottom-left and
+    /// bottom-right corners, as Tiled stores them, where `-1` means no terrain.
+    pub fn from_terrains(
+        names: Vec<String>,
+        tiles: impl IntoIterator<Item = (TileId, [i32; 4])>,
+    ) -> Result<Self, Error> {
+        let color = |terrain: i32| -> Result<u8, Error> {
+            ensure!(
+                terrain < names.len() as i32,
+                "tile refers to terrain {}, but there are only {}",
+                terrain,
+                names.len()
+            );
+            Ok((terrain + 1) as u8)
+        };
+
+        let mut wang_tiles = Vec::new();
+        for (tile, [top_left, top_right, bottom_left, bottom_right]) in tiles {
+            wang_tiles.push((
+                tile,
+                WangId::corners(
+                    color(top_right)?,
+                    color(bottom_right)?,
+                    color(bottom_left)?,
+                    color(top_left)?,
+                ),
+            ));
+        }
+
+        Ok(WangSet {
+            name: "terrains".to_owned(),
+            wang_type: WangSetType::Corner,
+            colors: names
+                .into_iter()
+                .map(|name| WangColor {
+                    name,
+                    color: Color::BLACK,
+                    probability: 1.,
+                    properties: Properties(HashMap::new()),
+                })
+                .collect(),
+            tiles: wang_tiles,
+            properties: Properties(HashMap::new()),
+        })
+    }
+
+    /// Find the first tile whose colors match the given signature. Only the parts of the signature
+    /// this set uses are compared: corners for a corner set, edges for an edge set, and everything
+    /// for a mixed set.
+    pub fn wang_tile(&self, colors: WangId) -> Option<TileId> {
+        let colors = colors.masked(self.wang_type);
+        self.tiles
+            .iter()
+            .find(|(_, wang_id)| wang_id.masked(self.wang_type) == colors)
+            .map(|&(tile, _)| tile)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -527,3 +1209,465 @@ impl Tilesets {
         self.0[tile_id.1.tileset_id() as usize].get_tile(tile_id)
     }
 }
+
+/// Build the lookup table used by [`TileId::from_gid`] to find which tileset a GID belongs to: the
+/// entry at index `gid` is the index of the tileset containing that GID. Index `0` is the empty
+/// tile, which belongs to no tileset, and is mapped to `0`.
+pub(crate) fn gid_tileset_buffer(tilesets: &[Tileset]) -> Vec<u32> {
+    let mut tile_buffer = vec![0];
+    for (tileset, i) in tilesets.iter().zip(0..) {
+        tile_buffer.resize(tileset.first_gid as usize, i);
+        tile_buffer.resize((tileset.first_gid + tileset.tilecount) as usize, i);
+    }
+    tile_buffer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn empty_properties() -> Properties {
+        Properties(HashMap::new())
+    }
+
+    fn map_with_tile_properties(tile_id: TileId) -> Map {
+        let mut properties = HashMap::new();
+        properties.insert("kind".to_owned(), Property::String("brick".to_owned()));
+        properties.insert("hittable".to_owned(), Property::Int(3));
+        properties.insert("tint".to_owned(), Property::Color("#00ff00".to_owned()));
+        properties.insert("target".to_owned(), Property::Obj(ObjectId::new(7, true)));
+
+        let mut tiles = HashMap::new();
+        tiles.insert(
+            tile_id,
+            Tile {
+                id: tile_id,
+                tile_type: None,
+                probability: 1.,
+                properties: Properties(properties),
+                objectgroup: None,
+                animation: None,
+            },
+        );
+
+        let tileset = Tileset {
+            first_gid: 1,
+            name: String::new(),
+            tile_width: 16,
+            tile_height: 16,
+            spacing: 0,
+            margin: 0,
+            tilecount: 1,
+            columns: 1,
+            tiles,
+            properties: empty_properties(),
+            images: Vec::new(),
+            wang_sets: Vec::new(),
+        };
+
+        let meta_data = MapMetaData {
+            tsx_ver: "1.5".to_owned(),
+            lua_ver: None,
+            tiled_ver: "1.7.0".to_owned(),
+            orientation: Orientation::Orthogonal,
+            render_order: RenderOrder::RightDown,
+            width: 1,
+            height: 1,
+            tilewidth: 16,
+            tileheight: 16,
+            nextlayerid: 1,
+            nextobjectid: 8,
+            properties: empty_properties(),
+        };
+
+        let mut obj_slab = slab::Slab::new();
+        let object_ref = ObjectRef(obj_slab.insert(Object {
+            id: ObjectId::new(7, true),
+            name: "pipe".to_owned(),
+            obj_type: String::new(),
+            x: 0.,
+            y: 0.,
+            width: 16.,
+            height: 16.,
+            rotation: 0.,
+            tile_id: None,
+            visible: true,
+            properties: empty_properties(),
+            shape: Some(ObjectShape::Rect),
+            text: None,
+        }));
+        let mut obj_id_to_ref_map = HashMap::new();
+        obj_id_to_ref_map.insert(ObjectId::new(7, true), object_ref);
+
+        Map::new(
+            meta_data,
+            Vec::new(),
+            Vec::new(),
+            Tilesets(vec![tileset]),
+            HashMap::new(),
+            HashMap::new(),
+            obj_slab,
+            obj_id_to_ref_map,
+        )
+    }
+
+    #[test]
+    fn batched_changes_coalesce_per_cell() {
+        let tile = |i| TileId::new(i, 0, false, false, false);
+        let layer_id = TileLayerId { glid: 1, llid: 0 };
+        let mut map = map_with_tile_properties(tile(0));
+        map.tile_layers.push(TileLayer {
+            layer_type: LayerType::Tile,
+            id: layer_id,
+            name: "Foreground".to_owned(),
+            x: 0,
+            y: 0,
+            width: 10,
+            height: 10,
+            visible: true,
+            opacity: 1.,
+            offset_x: 0,
+            offset_y: 0,
+            properties: empty_properties(),
+            data: to_chunks(&[tile(9)], 1, 1),
+        });
+        let mut reader = map.chunk_changes.register_reader();
+
+        // 50 changes over a 5x5 area: every cell is set twice, and the last write should win.
+        // The pre-existing tile at (0, 0) is overwritten and then removed.
+        let mut changes = Vec::new();
+        for pass in 0..2 {
+            for i in 0..25 {
+                changes.push(TileChange::set(
+                    layer_id,
+                    i % 5,
+                    i / 5,
+                    tile(pass * 100 + i as u32),
+                ));
+            }
+        }
+        changes[25] = TileChange::remove(layer_id, 0, 0);
+        assert_eq!(changes.len(), 50);
+
+        let delta = map.apply_changes(&changes);
+        assert_eq!(map.chunk_changes.read(&mut reader).count(), 0);
+        assert_eq!(delta.cells().len(), 25);
+
+        for (i, cell) in delta.cells().iter().enumerate() {
+            let i = i as i32;
+            assert_eq!((cell.x, cell.y), (i % 5, i / 5));
+            if i == 0 {
+                assert_eq!(cell.old, Some(tile(9)));
+                assert_eq!(cell.new, None);
+            } else {
+                assert_eq!(cell.old, None);
+                assert_eq!(cell.new, Some(tile(100 + i as u32)));
+            }
+            assert_eq!(
+                map.get_tile(cell.x, cell.y, layer_id, CoordSpace::Tile),
+                cell.new
+            );
+        }
+
+        // Setting a cell and then restoring it leaves nothing to upload.
+        let delta = map.apply_changes(&[
+            TileChange::set(layer_id, 1, 0, tile(7)),
+            TileChange::set(layer_id, 1, 0, tile(101)),
+        ]);
+        assert!(delta.is_empty());
+    }
+
+    #[test]
+    fn tile_layers_can_be_added_reordered_and_removed() {
+        let tile = |i| TileId::new(i, 0, false, false, false);
+        let ground = TileLayerId { glid: 1, llid: 0 };
+        let mut map = map_with_tile_properties(tile(0));
+        map.tile_layers.push(TileLayer {
+            layer_type: LayerType::Tile,
+            id: ground,
+            name: "Ground".to_owned(),
+            x: 0,
+            y: 0,
+            width: 1,
+            height: 1,
+            visible: true,
+            opacity: 1.,
+            offset_x: 0,
+            offset_y: 0,
+            properties: empty_properties(),
+            data: to_chunks(&[tile(3)], 1, 1),
+        });
+        map.tile_layer_map.insert("Ground".to_owned(), ground);
+
+        let overlay = map.add_tile_layer("Overlay").unwrap();
+        assert_ne!(overlay, ground);
+        assert!(map.add_tile_layer("Overlay").is_err());
+        map.set_tile(0, 0, tile(5), overlay, CoordSpace::Tile);
+
+        // New layers are drawn last; reordering moves the overlay underneath the ground.
+        let draw_order = |map: &Map| map.tile_layers.iter().map(|l| l.id).collect::<Vec<_>>();
+        assert_eq!(draw_order(&map), [ground, overlay]);
+        map.reorder_layers(&[overlay, ground]).unwrap();
+        assert_eq!(draw_order(&map), [overlay, ground]);
+        assert!(map.reorder_layers(&[overlay, overlay]).is_err());
+        assert_eq!(draw_order(&map), [overlay, ground]);
+
+        // Lookups go by ID rather than by position, so they survive the reorder.
+        assert_eq!(map.get_tile(0, 0, overlay, CoordSpace::Tile), Some(tile(5)));
+        assert_eq!(map.get_tile(0, 0, ground, CoordSpace::Tile), Some(tile(3)));
+
+        // Removing a layer leaves the remaining IDs valid, and removed IDs aren't reused.
+        let removed = map.remove_tile_layer(ground).unwrap();
+        assert_eq!(removed.name, "Ground");
+        assert!(map.remove_tile_layer(ground).is_err());
+        assert!(map.tile_layer_id("Ground").is_err());
+        assert_eq!(map.tile_layer_id("Overlay").unwrap(), overlay);
+        assert_eq!(map.get_tile(0, 0, overlay, CoordSpace::Tile), Some(tile(5)));
+
+        let detail = map.add_tile_layer("Detail").unwrap();
+        assert!(detail.llid != ground.llid && detail.glid != ground.glid);
+        assert_eq!(draw_order(&map), [overlay, detail]);
+    }
+
+    #[test]
+    fn lua_reads_tile_properties() {
+        let lua = Lua::new();
+        let tile_id = TileId::new(0, 0, false, false, false);
+        let map = map_with_tile_properties(tile_id);
+        // Flip flags shouldn't affect which tile's properties are found.
+        let flipped = TileId::new(0, 0, true, false, true);
+
+        lua.globals().set("map", map).unwrap();
+        lua.globals().set("flipped", flipped).unwrap();
+        lua.load(
+            r#"
+            local props = map:get_tile_properties(flipped)
+            assert(props.kind == "brick")
+            assert(props.hittable == 3)
+            assert(props.tint.g == 1 and props.tint.r == 0)
+            assert(props.target.name == "pipe")
+            assert(props.target:get_property("missing") == nil)
+            assert(map:get_object(7).name == "pipe")
+            "#,
+        )
+        .exec()
+        .unwrap();
+    }
+
+    #[test]
+    fn lua_converts_between_pixels_and_tiles() {
+        let lua = Lua::new();
+        let (t, e) = (TileId::new(1, 0, false, false, false), EMPTY_TILE);
+        let layer_id = TileLayerId { glid: 1, llid: 0 };
+        let mut map = map_with_tile_properties(t);
+        map.tile_layers.push(TileLayer {
+            layer_type: LayerType::Tile,
+            id: layer_id,
+            name: "Ground".to_owned(),
+            x: 0,
+            y: 0,
+            width: 3,
+            height: 2,
+            visible: true,
+            opacity: 1.,
+            offset_x: 0,
+            offset_y: 0,
+            properties: empty_properties(),
+            data: to_chunks(&[t, e, t, e, t, e], 3, 2),
+        });
+        map.tile_layer_map.insert("Ground".to_owned(), layer_id);
+
+        assert_eq!(map.pixel_to_tile(40, 17), (2, 1));
+        lua.globals().set("map", map).unwrap();
+        lua.load(
+            r#"
+            local tx, ty = map:pixel_to_tile(40.9, 17.5)
+            assert(tx == 2 and ty == 1)
+            local px, py = map:tile_to_pixel(tx, ty)
+            assert(px == 32 and py == 16)
+            assert(select(1, map:pixel_to_tile(px, py)) == tx)
+
+            local tiles = map:tiles_in_bb(0, 0, 40, 8, "Ground")
+            assert(#tiles == 3)
+            assert(tiles[1].x == 0 and tiles[1].y == 0)
+            assert(tiles[2].x == 2 and tiles[2].y == 0)
+            assert(tiles[3].x == 1 and tiles[3].y == 1)
+            "#,
+        )
+        .exec()
+        .unwrap();
+    }
+
+    #[test]
+    fn iso_tile_centers_round_trip() {
+        let mut map = map_with_tile_properties(TileId::new(1, 0, false, false, false));
+        map.meta_data.orientation = Orientation::Isometric;
+        map.meta_data.tilewidth = 32;
+        map.meta_data.tileheight = 16;
+
+        for &(x, y) in &[(0, 0), (1, 0), (0, 1), (3, 2), (-2, 5), (7, -1)] {
+            let center = map.tile_center_iso(x, y);
+            assert_eq!(map.screen_to_tile_iso(center), (x, y));
+
+            // Just inside each corner of the diamond is still the same tile.
+            for &(dx, dy) in &[(15.9, 0.), (-15.9, 0.), (0., 7.9), (0., -7.9)] {
+                let point = center + Vector2::new(dx, dy);
+                assert_eq!(map.screen_to_tile_iso(point), (x, y), "{:?}", point);
+            }
+        }
+
+        // Just outside the diamond of (0, 0), across each of its edges, are its four neighbors;
+        // the top right edge is shared with (0, 1), and so on.
+        assert_eq!(map.screen_to_tile_iso(Point2::new(8.5, 4.5)), (0, 1));
+        assert_eq!(map.screen_to_tile_iso(Point2::new(-8.5, -4.5)), (0, -1));
+        assert_eq!(map.screen_to_tile_iso(Point2::new(8.5, -4.5)), (1, 0));
+        assert_eq!(map.screen_to_tile_iso(Point2::new(-8.5, 4.5)), (-1, 0));
+        // Exactly on an edge goes to the greater tile coordinate.
+        assert_eq!(map.screen_to_tile_iso(Point2::new(8., 4.)), (0, 1));
+    }
+
+    #[test]
+    fn tile_animation_converts_to_spritesheet() {
+        let local = |id| TileId(id, TileMetaData::new(0, false, false, false));
+        let mut tiles = HashMap::new();
+        tiles.insert(
+            local(0),
+            Tile {
+                id: local(0),
+                tile_type: None,
+                probability: 1.,
+                properties: empty_properties(),
+                objectgroup: None,
+                animation: Some(Animation(vec![
+                    (local(0), 100),
+                    (local(2), 250),
+                    (local(1), 50),
+                ])),
+            },
+        );
+
+        let tileset = Tileset {
+            first_gid: 1,
+            name: String::new(),
+            tile_width: 16,
+            tile_height: 16,
+            spacing: 0,
+            margin: 0,
+            tilecount: 3,
+            columns: 3,
+            tiles,
+            properties: empty_properties(),
+            images: vec![Image {
+                source: "coin.png".to_owned(),
+                width: 48,
+                height: 16,
+                trans_color: None,
+            }],
+            wang_sets: Vec::new(),
+        };
+
+        let sheet = tileset.animation_as_spritesheet(&local(0)).unwrap();
+        assert!(tileset.animation_as_spritesheet(&local(1)).is_none());
+        assert_eq!(sheet.frames.len(), 3);
+        assert_eq!(
+            sheet.frames.iter().map(|f| f.duration).collect::<Vec<_>>(),
+            [100, 250, 50]
+        );
+        assert_eq!(
+            sheet
+                .frames
+                .iter()
+                .map(|f| f.uvs.mins.x)
+                .collect::<Vec<_>>(),
+            [0., 32. / 48., 16. / 48.]
+        );
+
+        // Tiled durations are in milliseconds, as are animation state timings, so 0.1 seconds
+        // should land exactly on the end of the first frame.
+        let mut anim = sheet.at_tag(TagId(0), true);
+        assert_eq!(sheet.update_animation(0.099, &mut anim), None);
+        assert_eq!(sheet.update_animation(0.002, &mut anim), Some(FrameId(1)));
+        assert_eq!(sheet.update_animation(0.25, &mut anim), Some(FrameId(2)));
+    }
+
+    #[test]
+    fn ray_crosses_empty_cells_into_wall() {
+        let tile = |i| TileId::new(i, 0, false, false, false);
+        let (wall, grass, e) = (tile(1), tile(2), EMPTY_TILE);
+        let layer_id = TileLayerId { glid: 1, llid: 0 };
+        let mut map = map_with_tile_properties(tile(0));
+        #[rustfmt::skip]
+        let data = [
+            e,    e, e,     e, e, e,    e,
+            wall, e, grass, e, e, wall, e,
+            e,    e, e,     e, e, e,    e,
+        ];
+        map.tile_layers.push(TileLayer {
+            layer_type: LayerType::Tile,
+            id: layer_id,
+            name: "Walls".to_owned(),
+            x: 0,
+            y: 0,
+            width: 7,
+            height: 3,
+            visible: true,
+            opacity: 1.,
+            offset_x: 0,
+            offset_y: 0,
+            properties: empty_properties(),
+            data: to_chunks(&data, 7, 3),
+        });
+        let is_wall = |t: TileId| t == wall;
+
+        // Starting in the middle of cell (1, 1), passing over the grass.
+        let origin = Point2::new(24., 24.);
+        let hit = map
+            .cast_ray(layer_id, origin, Vector2::new(2., 0.), 100., is_wall)
+            .unwrap();
+        assert_eq!((hit.x, hit.y), (5, 1));
+        assert_eq!(hit.normal, Vector2::new(-1., 0.));
+        assert_eq!(hit.distance, 56.);
+        assert_eq!(hit.point, Point2::new(80., 24.));
+
+        let hit = map
+            .cast_ray(layer_id, origin, Vector2::new(-1., 0.), 100., is_wall)
+            .unwrap();
+        assert_eq!((hit.x, hit.y), (0, 1));
+        assert_eq!(hit.normal, Vector2::new(1., 0.));
+        assert_eq!(hit.distance, 8.);
+
+        // Short of the wall, or missing it entirely.
+        assert!(map
+            .cast_ray(layer_id, origin, Vector2::new(1., 0.), 50., is_wall)
+            .is_none());
+        assert!(map
+            .cast_ray(layer_id, origin, Vector2::new(0., 1.), 100., is_wall)
+            .is_none());
+
+        // Down and to the right from above the wall, entering it through its top face.
+        let hit = map
+            .cast_ray(
+                layer_id,
+                Point2::new(84., 12.),
+                Vector2::new(1., 1.),
+                100.,
+                is_wall,
+            )
+            .unwrap();
+        assert_eq!((hit.x, hit.y), (5, 1));
+        assert_eq!(hit.normal, Vector2::new(0., -1.));
+
+        // Starting inside the wall hits it immediately.
+        let hit = map
+            .cast_ray(
+                layer_id,
+                Point2::new(88., 24.),
+                Vector2::new(1., 0.),
+                100.,
+                is_wall,
+            )
+            .unwrap();
+        assert_eq!((hit.x, hit.y, hit.distance), (5, 1, 0.));
+        assert_eq!(hit.normal, Vector2::zeros());
+    }
+}
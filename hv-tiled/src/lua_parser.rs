@@ -61,7 +61,9 @@ fn parse_properties(props: &LuaTable) -> Result<Properties, Error> {
             LuaValue::Integer(i) => Property::Int(i),
             LuaValue::Number(n) => Property::Float(n),
             LuaValue::String(s) => Property::String(s.to_str()?.to_owned()),
-            LuaValue::Table(t) => Property::Obj(ObjectId::new(t.get("id")?, false)), // I believe tables will only come through for Object properties
+            // I believe tables will only come through for Object properties. These can only refer
+            // to objects in object layers, so we look them up as such.
+            LuaValue::Table(t) => Property::Obj(ObjectId::new(t.get("id")?, true)),
             l => {
                 return Err(anyhow!(
                     "Got an unexpected value in the properties section: {:?}",
@@ -77,6 +79,9 @@ fn parse_properties(props: &LuaTable) -> Result<Properties, Error> {
 fn parse_map_meta_data(map_table: &LuaTable) -> Result<MapMetaData, Error> {
     let render_order = match map_table.get::<_, LuaString>("renderorder")?.to_str()? {
         "right-down" => RenderOrder::RightDown,
+        "right-up" => RenderOrder::RightUp,
+        "left-down" => RenderOrder::LeftDown,
+        "left-up" => RenderOrder::LeftUp,
         r => return Err(anyhow!("Got an unsupported renderorder: {}", r)),
     };
 
@@ -154,6 +159,7 @@ fn parse_tile_layer(t: &LuaTable, llid: u32, tile_buffer: &[u32]) -> Result<Tile
     let encoding = match t.get::<_, LuaString>("encoding")?.to_str()? {
         "lua" => Encoding::Lua,
         "base64" => Encoding::Base64,
+        "csv" => Encoding::Csv,
         e => return Err(anyhow!("Got an unsupported encoding type: {}", e)),
     };
 
@@ -405,21 +411,116 @@ fn parse_tile(
     })
 }
 
+fn parse_wang_type(t: &LuaTable) -> Result<WangSetType, Error> {
+    // Tiled 1.9 renamed `type` to `wangsettype`.
+    let wang_type = match t.get::<_, LuaString>("wangsettype") {
+        Ok(s) => s,
+        Err(_) => t.get::<_, LuaString>("type")?,
+    };
+
+    match wang_type.to_str()? {
+        "corner" => Ok(WangSetType::Corner),
+        "edge" => Ok(WangSetType::Edge),
+        "mixed" => Ok(WangSetType::Mixed),
+        s => Err(anyhow!("Unsupported Wang set type: {}", s)),
+    }
+}
+
+fn parse_wang_set(t: &LuaTable, first_gid: u32, tileset_num: u32) -> Result<WangSet, Error> {
+    let mut colors = Vec::new();
+    for color_table in t.get::<_, LuaTable>("colors")?.sequence_values() {
+        let color_table: LuaTable = color_table?;
+        colors.push(WangColor {
+            name: color_table
+                .get::<_, LuaString>("name")?
+                .to_str()?
+                .to_owned(),
+            color: Color::from_tiled_lua_table(&color_table)?,
+            probability: color_table.get("probability").unwrap_or(1.0),
+            properties: match color_table.get::<_, LuaTable>("properties") {
+                Ok(_) => parse_properties(&color_table)?,
+                Err(_) => Properties(HashMap::new()),
+            },
+        });
+    }
+
+    let mut tiles = Vec::new();
+    for wang_tile in t.get::<_, LuaTable>("wangtiles")?.sequence_values() {
+        let wang_tile: LuaTable = wang_tile?;
+        let wang_id = match wang_tile.get::<_, LuaTable>("wangid") {
+            Ok(ids) => WangId::from_slice(&ids.sequence_values().collect::<LuaResult<Vec<u8>>>()?)?,
+            Err(_) => {
+                return Err(anyhow!(
+                    "Wang sets from before Tiled 1.5 aren't supported, re-save the map to upgrade"
+                ))
+            }
+        };
+        let tile_id: u32 = wang_tile.get("tileid")?;
+        tiles.push((
+            TileId::new(first_gid - 1 + tile_id, tileset_num, false, false, false),
+            wang_id,
+        ));
+    }
+
+    Ok(WangSet {
+        name: t.get::<_, LuaString>("name")?.to_str()?.to_owned(),
+        wang_type: parse_wang_type(t)?,
+        properties: match t.get::<_, LuaTable>("properties") {
+            Ok(_) => parse_properties(t)?,
+            Err(_) => Properties(HashMap::new()),
+        },
+        colors,
+        tiles,
+    })
+}
+
 fn parse_tileset(
     ts: &LuaTable,
     path_prefix: Option<&str>,
     tileset_number: u32,
     slab: &mut slab::Slab<Object>,
 ) -> Result<Tileset, Error> {
+    let first_gid: u32 = ts.get("firstgid")?;
     let mut tiles = HashMap::new();
+    let mut terrain_tiles = Vec::new();
     for tile_table in ts.get::<_, LuaTable>("tiles")?.sequence_values() {
-        let tile = parse_tile(&tile_table?, tileset_number, slab)?;
+        let tile_table: LuaTable = tile_table?;
+        let tile = parse_tile(&tile_table, tileset_number, slab)?;
+        if let Ok(terrain) = tile_table.get::<_, LuaTable>("terrain") {
+            let corners = terrain.sequence_values().collect::<LuaResult<Vec<i32>>>()?;
+            ensure!(corners.len() == 4, "tile terrains should have 4 corners");
+            // Tile IDs in tilesets are stored + 1; see `parse_tile`.
+            let local_id = tile.id.0 - 1;
+            let gid = TileId::new(
+                first_gid - 1 + local_id,
+                tileset_number,
+                false,
+                false,
+                false,
+            );
+            terrain_tiles.push((gid, [corners[0], corners[1], corners[2], corners[3]]));
+        }
         tiles.insert(tile.id, tile);
     }
 
+    let mut wang_sets = Vec::new();
+    if let Ok(terrains) = ts.get::<_, LuaTable>("terrains") {
+        let mut names = Vec::new();
+        for terrain in terrains.sequence_values() {
+            let terrain: LuaTable = terrain?;
+            names.push(terrain.get::<_, LuaString>("name")?.to_str()?.to_owned());
+        }
+        wang_sets.push(WangSet::from_terrains(names, terrain_tiles)?);
+    }
+    if let Ok(sets) = ts.get::<_, LuaTable>("wangsets") {
+        for set in sets.sequence_values() {
+            wang_sets.push(parse_wang_set(&set?, first_gid, tileset_number)?);
+        }
+    }
+
     Ok(Tileset {
         name: ts.get::<_, LuaString>("name")?.to_str()?.to_owned(),
-        first_gid: ts.get("firstgid")?,
+        first_gid,
         tile_width: ts.get("tilewidth")?,
         tile_height: ts.get("tileheight")?,
         spacing: ts.get("spacing")?,
@@ -429,6 +530,7 @@ fn parse_tileset(
         tilecount: ts.get("tilecount")?,
         properties: parse_properties(ts)?,
         tiles,
+        wang_sets,
     })
 }
 
@@ -443,12 +545,40 @@ pub fn parse_map(map_path: &str, engine: &Engine, path_prefix: Option<&str>) ->
     tiled_lua_map.read_to_end(&mut tiled_buffer)?;
     let lua_chunk = lua.load(&tiled_buffer);
     let tiled_lua_table = lua_chunk.eval::<LuaTable>()?;
-    let meta_data = parse_map_meta_data(&tiled_lua_table)?;
+    let map = parse_map_table(&tiled_lua_table, path_prefix)?;
+
+    drop(tiled_lua_table);
+    drop(lua);
+
+    Ok(map)
+}
+
+/// Parse a map from the table produced by evaluating Tiled's Lua export.
+pub fn parse_map_table(
+    tiled_lua_table: &LuaTable,
+    path_prefix: Option<&str>,
+) -> Result<Map, Error> {
+    let (mut map, mut layer_parser) = parse_map_header(tiled_lua_table, path_prefix)?;
+
+    for layer in tiled_lua_table
+        .get::<_, LuaTable>("layers")?
+        .sequence_values::<LuaTable>()
+    {
+        layer_parser.parse_into(&layer?, &mut map)?;
+    }
+
+    Ok(map)
+}
+
+/// Parse everything about a map except for its layers, producing a map with no layers and the
+/// state needed to parse its layers into it.
+fn parse_map_header(
+    tiled_lua_table: &LuaTable,
+    path_prefix: Option<&str>,
+) -> Result<(Map, LayerParser), Error> {
+    let meta_data = parse_map_meta_data(tiled_lua_table)?;
 
     let mut tilesets = Vec::new();
-    // We initialize the tile_buffer with 1 0'd out TileId to account for the fact
-    // that layer indexing starts at 1 instead of 0
-    let mut tile_buffer = vec![0];
     let mut obj_slab = slab::Slab::new();
 
     for (tileset, i) in tiled_lua_table
@@ -456,62 +586,303 @@ pub fn parse_map(map_path: &str, engine: &Engine, path_prefix: Option<&str>) ->
         .sequence_values::<LuaTable>()
         .zip(0..)
     {
-        let tileset = parse_tileset(&tileset?, path_prefix, i, &mut obj_slab)?;
-        tile_buffer.reserve(tileset.tilecount as usize);
-        for _ in tileset.first_gid..tileset.tilecount {
-            tile_buffer.push(i);
-        }
-        tilesets.push(tileset);
+        tilesets.push(parse_tileset(&tileset?, path_prefix, i, &mut obj_slab)?);
     }
 
-    let mut tile_layers = Vec::new();
-    let mut object_layers = Vec::new();
+    let layer_parser = LayerParser {
+        tile_buffer: gid_tileset_buffer(&tilesets),
+        tile_llid: 0,
+        obj_llid: 0,
+    };
 
-    let mut tile_layer_map = HashMap::new();
-    let mut object_layer_map = HashMap::new();
+    let map = Map::new(
+        meta_data,
+        Vec::new(),
+        Vec::new(),
+        Tilesets(tilesets),
+        HashMap::new(),
+        HashMap::new(),
+        obj_slab,
+        HashMap::new(),
+    );
 
-    let mut obj_id_to_ref_map = HashMap::new();
+    Ok((map, layer_parser))
+}
 
-    let mut tile_llid = 0;
-    let mut obj_llid = 0;
+/// Parses layers into a map one at a time, in the order they appear in the Lua export.
+struct LayerParser {
+    tile_buffer: Vec<u32>,
+    tile_llid: u32,
+    obj_llid: u32,
+}
 
-    for layer in tiled_lua_table
-        .get::<_, LuaTable>("layers")?
-        .sequence_values::<LuaTable>()
-    {
-        let layer = layer?;
-        let layer_type = parse_layer_type(&layer)?;
-        match layer_type {
+impl LayerParser {
+    fn parse_into(&mut self, layer: &LuaTable, map: &mut Map) -> Result<(), Error> {
+        match parse_layer_type(layer)? {
             LayerType::Tile => {
-                let tile_layer = parse_tile_layer(&layer, tile_llid, &tile_buffer)?;
-                tile_layer_map.insert(tile_layer.name.clone(), tile_layer.id);
-                tile_layers.push(tile_layer);
-                tile_llid += 1;
+                let tile_layer = parse_tile_layer(layer, self.tile_llid, &self.tile_buffer)?;
+                map.tile_layer_map
+                    .insert(tile_layer.name.clone(), tile_layer.id);
+                map.tile_layers.push(tile_layer);
+                self.tile_llid += 1;
             }
             LayerType::Object => {
-                let (obj_group, obj_ids_and_refs) =
-                    parse_object_group(&layer, obj_llid, true, &mut obj_slab, Some(&tile_buffer))?;
+                let (obj_group, obj_ids_and_refs) = parse_object_group(
+                    layer,
+                    self.obj_llid,
+                    true,
+                    &mut map.obj_slab,
+                    Some(&self.tile_buffer),
+                )?;
                 for (obj_id, obj_ref) in obj_ids_and_refs.iter() {
-                    obj_id_to_ref_map.insert(*obj_id, *obj_ref);
+                    map.obj_id_to_ref_map.insert(*obj_id, *obj_ref);
                 }
-                object_layer_map.insert(obj_group.name.clone(), obj_group.id);
-                object_layers.push(obj_group);
-                obj_llid += 1;
+                map.object_layer_map
+                    .insert(obj_group.name.clone(), obj_group.id);
+                map.object_layers.push(obj_group);
+                self.obj_llid += 1;
             }
         }
+
+        Ok(())
     }
+}
 
-    drop(tiled_lua_table);
-    drop(lua);
+/// How far along a [`MapLoader`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoadProgress {
+    /// The number of layers parsed so far.
+    pub loaded_layers: usize,
+    /// The number of layers in the map.
+    pub total_layers: usize,
+}
 
-    Ok(Map::new(
-        meta_data,
-        tile_layers,
-        object_layers,
-        Tilesets(tilesets),
-        tile_layer_map,
-        object_layer_map,
-        obj_slab,
-        obj_id_to_ref_map,
-    ))
+impl LoadProgress {
+    /// Whether every layer has been parsed.
+    pub fn is_done(&self) -> bool {
+        self.loaded_layers == self.total_layers
+    }
+
+    /// The fraction of layers parsed so far, from `0.` to `1.`, for progress bars. A map with no
+    /// layers is done as soon as it's opened, so this is `1.` for it.
+    pub fn fraction(&self) -> f32 {
+        if self.total_layers == 0 {
+            1.
+        } else {
+            self.loaded_layers as f32 / self.total_layers as f32
+        }
+    }
+}
+
+/// Parses a map from Tiled's Lua export incrementally, so that loading a large map doesn't block
+/// for the whole parse.
+///
+/// The map's metadata and tilesets are parsed up front; after that, each call to
+/// [`MapLoader::poll`] parses one more layer. The partially loaded map is available from
+/// [`MapLoader::map`] the whole time, and contains every layer parsed so far, so its size and
+/// tilesets can be used (and its loaded layers queried or drawn) before loading finishes.
+///
+/// The evaluated Lua table is kept in the Lua registry until the loader is dropped, so the [`Lua`]
+/// context passed to [`MapLoader::poll`] must be the one the loader was created with.
+pub struct MapLoader {
+    layers: LuaRegistryKey,
+    map: Map,
+    layer_parser: LayerParser,
+    progress: LoadProgress,
+}
+
+impl MapLoader {
+    /// Read and evaluate the Lua map at `map_path`, and parse its metadata and tilesets.
+    pub fn open(map_path: &str, engine: &Engine, path_prefix: Option<&str>) -> Result<Self, Error> {
+        let mut tiled_lua_map = engine.fs().open(Path::new(map_path))?;
+        let mut tiled_buffer: Vec<u8> = Vec::new();
+        tiled_lua_map.read_to_end(&mut tiled_buffer)?;
+
+        let lua = engine.lua();
+        let tiled_lua_table = lua.load(&tiled_buffer).eval::<LuaTable>()?;
+        Self::new(&lua, &tiled_lua_table, path_prefix)
+    }
+
+    /// Parse the metadata and tilesets of a map from the table produced by evaluating Tiled's Lua
+    /// export, leaving its layers to be parsed by [`MapLoader::poll`].
+    pub fn new(
+        lua: &Lua,
+        tiled_lua_table: &LuaTable,
+        path_prefix: Option<&str>,
+    ) -> Result<Self, Error> {
+        let (map, layer_parser) = parse_map_header(tiled_lua_table, path_prefix)?;
+        let layers = tiled_lua_table.get::<_, LuaTable>("layers")?;
+        let total_layers = layers.raw_len() as usize;
+
+        Ok(Self {
+            layers: lua.create_registry_value(layers)?,
+            map,
+            layer_parser,
+            progress: LoadProgress {
+                loaded_layers: 0,
+                total_layers,
+            },
+        })
+    }
+
+    /// Parse the next layer, if there are any left, and report how far along loading is.
+    pub fn poll(&mut self, lua: &Lua) -> Result<LoadProgress, Error> {
+        if !self.progress.is_done() {
+            let layers = lua.registry_value::<LuaTable>(&self.layers)?;
+            let layer = layers.raw_get::<_, LuaTable>(self.progress.loaded_layers + 1)?;
+            self.layer_parser
+                .parse_into(&layer, &mut self.map)
+                .with_context(|| {
+                    format!("error parsing layer {}", self.progress.loaded_layers + 1)
+                })?;
+            self.progress.loaded_layers += 1;
+        }
+
+        Ok(self.progress)
+    }
+
+    /// How far along loading is, without parsing anything.
+    pub fn progress(&self) -> LoadProgress {
+        self.progress
+    }
+
+    /// The map as loaded so far.
+    pub fn map(&self) -> &Map {
+        &self.map
+    }
+
+    /// Parse all remaining layers and return the finished map.
+    pub fn finish(mut self, lua: &Lua) -> Result<Map, Error> {
+        while !self.poll(lua)?.is_done() {}
+        Ok(self.map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_loader_parses_one_layer_per_poll() {
+        let lua = Lua::new();
+        let table = lua
+            .load(
+                r#"
+                return {
+                  version = "1.5", luaversion = "5.1", tiledversion = "1.7.0",
+                  orientation = "orthogonal", renderorder = "right-down",
+                  width = 2, height = 1, tilewidth = 16, tileheight = 16,
+                  nextlayerid = 3, nextobjectid = 1,
+                  properties = {},
+                  tilesets = {
+                    {
+                      name = "blocks", firstgid = 1,
+                      tilewidth = 16, tileheight = 16, spacing = 0, margin = 0,
+                      columns = 2, tilecount = 2,
+                      image = "blocks.png", imagewidth = 32, imageheight = 16,
+                      properties = {}, tiles = {}
+                    }
+                  },
+                  layers = {
+                    {
+                      type = "tilelayer", id = 1, name = "Ground",
+                      x = 0, y = 0, width = 2, height = 1,
+                      visible = true, opacity = 1, offsetx = 0, offsety = 0,
+                      properties = {}, encoding = "lua", data = { 1, 2 }
+                    },
+                    {
+                      type = "tilelayer", id = 2, name = "Decor",
+                      x = 0, y = 0, width = 2, height = 1,
+                      visible = true, opacity = 1, offsetx = 0, offsety = 0,
+                      properties = {}, encoding = "lua", data = { 0, 1 }
+                    }
+                  }
+                }
+                "#,
+            )
+            .eval::<LuaTable>()
+            .unwrap();
+
+        let mut loader = MapLoader::new(&lua, &table, None).unwrap();
+        assert_eq!(loader.progress().total_layers, 2);
+        assert_eq!(loader.map().meta_data.width, 2);
+        assert!(loader.map().tile_layers.is_empty());
+
+        let progress = loader.poll(&lua).unwrap();
+        assert_eq!(progress.loaded_layers, 1);
+        assert!(!progress.is_done());
+        assert_eq!(progress.fraction(), 0.5);
+        // The layer which has been parsed can already be queried.
+        let ground = loader.map().tile_layer_id("Ground").unwrap();
+        assert!(loader.map().tile_layer(ground).is_some());
+        assert!(loader.map().tile_layer_id("Decor").is_err());
+
+        assert!(loader.poll(&lua).unwrap().is_done());
+        assert!(loader.poll(&lua).unwrap().is_done());
+        let map = loader.finish(&lua).unwrap();
+        assert_eq!(map.tile_layers.len(), 2);
+        assert_eq!(map.tile_layer_id("Decor").unwrap().llid, 1);
+    }
+
+    #[test]
+    fn terrains_and_wang_sets_become_wang_sets() {
+        let lua = Lua::new();
+        let ts = lua
+            .load(
+                r#"
+                return {
+                  name = "terrain",
+                  firstgid = 1,
+                  tilewidth = 16, tileheight = 16,
+                  spacing = 0, margin = 0,
+                  columns = 2, tilecount = 2,
+                  image = "terrain.png", imagewidth = 32, imageheight = 16,
+                  properties = {},
+                  terrains = {
+                    { name = "Water", tile = -1, properties = {} },
+                    { name = "Sand", tile = -1, properties = {} }
+                  },
+                  wangsets = {
+                    {
+                      name = "Paths",
+                      type = "edge",
+                      tile = -1,
+                      properties = {},
+                      colors = {
+                        { color = { 128, 128, 128 }, name = "Stone", probability = 1, tile = -1 }
+                      },
+                      wangtiles = {
+                        { wangid = { 1, 0, 0, 0, 1, 0, 0, 0 }, tileid = 1 }
+                      }
+                    }
+                  },
+                  tiles = {
+                    { id = 0, terrain = { 0, 0, 1, -1 } },
+                    { id = 1 }
+                  }
+                }
+                "#,
+            )
+            .eval::<LuaTable>()
+            .unwrap();
+        let tileset = parse_tileset(&ts, None, 0, &mut slab::Slab::new()).unwrap();
+
+        assert_eq!(tileset.wang_sets.len(), 2);
+        let terrains = &tileset.wang_sets[0];
+        assert_eq!(terrains.wang_type, WangSetType::Corner);
+        assert_eq!(terrains.colors[1].name, "Sand");
+        // Water on top, sand in the bottom-left, nothing in the bottom-right.
+        assert_eq!(
+            tileset.wang_tile(WangId::corners(1, 0, 2, 1)),
+            Some(TileId::new(0, 0, false, false, false))
+        );
+
+        let paths = &tileset.wang_sets[1];
+        assert_eq!(paths.wang_type, WangSetType::Edge);
+        assert_eq!(paths.colors[0].color, Color::from_rgb(128, 128, 128));
+        assert_eq!(
+            tileset.wang_tile(WangId::edges(1, 0, 1, 0)),
+            Some(TileId::new(1, 0, false, false, false))
+        );
+    }
 }
@@ -0,0 +1,299 @@
+//! Keeping several parsed maps loaded at once, for games split into many rooms or levels.
+//!
+//! A [`MapSet`] parses each map the first time it's asked for and keeps it around afterwards, so
+//! moving back and forth between rooms doesn't re-parse them. It also shares tileset render data
+//! between maps, at two levels:
+//!
+//! - Maps whose tilesets are laid out identically (same tilesets, in the same order, starting at the
+//!   same GIDs, at the same map tile size) share a single [`TilesetRenderData`].
+//! - Maps which use the same tileset images but at different GIDs can't share render data, since
+//!   its UVs are indexed by GID; they get their own [`TilesetRenderData`], but it's built from the
+//!   same textures, so each tileset image is still only uploaded once.
+
+use crate::*;
+
+use std::rc::Rc;
+
+/// Everything about a tileset which the [`TilesetRenderData`] built for it depends on.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct TilesetKey {
+    name: String,
+    image: Option<String>,
+    first_gid: u32,
+    tile_width: u32,
+    tile_height: u32,
+    spacing: u32,
+    margin: u32,
+    tilecount: u32,
+    columns: u32,
+}
+
+/// Two maps with equal keys can share the same [`TilesetRenderData`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct RenderDataKey {
+    tile_width: u32,
+    tile_height: u32,
+    tilesets: Vec<TilesetKey>,
+}
+
+impl RenderDataKey {
+    fn new(map: &Map) -> Self {
+        Self {
+            tile_width: map.meta_data.tilewidth,
+            tile_height: map.meta_data.tileheight,
+            tilesets: map
+                .tilesets
+                .0
+                .iter()
+                .map(|tileset| TilesetKey {
+                    name: tileset.name.clone(),
+                    image: tileset.images.first().map(|image| image.source.clone()),
+                    first_gid: tileset.first_gid,
+                    tile_width: tileset.tile_width,
+                    tile_height: tileset.tile_height,
+                    spacing: tileset.spacing,
+                    margin: tileset.margin,
+                    tilecount: tileset.tilecount,
+                    columns: tileset.columns,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A map loaded by a [`MapSet`], along with the render data for its tilesets, which may be shared
+/// with other maps in the set.
+pub struct LoadedMap {
+    pub map: Map,
+    pub render_data: Rc<TilesetRenderData>,
+}
+
+/// A cache of parsed maps, keyed by path, with one of them being the current map.
+///
+/// Maps are parsed with [`json_parser::parse_map`] if their path ends in `.json`, and with
+/// [`lua_parser::parse_map`] otherwise.
+#[derive(Default)]
+pub struct MapSet {
+    path_prefix: Option<String>,
+    maps: HashMap<String, LoadedMap>,
+    current: Option<String>,
+    render_data: HashMap<RenderDataKey, Rc<TilesetRenderData>>,
+    textures: HashMap<String, CachedTexture>,
+}
+
+impl MapSet {
+    /// Create an empty map set. The path prefix is passed along to the parser for every map loaded.
+    pub fn new(path_prefix: Option<&str>) -> Self {
+        Self {
+            path_prefix: path_prefix.map(str::to_owned),
+            ..Self::default()
+        }
+    }
+
+    /// Get the map at `path`, parsing it and building render data for its tilesets if it isn't
+    /// loaded yet.
+    pub fn get_or_load(&mut self, path: &str, engine: &Engine) -> Result<&mut LoadedMap> {
+        let path_prefix = self.path_prefix.clone();
+        self.get_or_load_with(
+            path,
+            |path| {
+                if path.ends_with(".json") {
+                    json_parser::parse_map(path, engine, path_prefix.as_deref())
+                } else {
+                    lua_parser::parse_map(path, engine, path_prefix.as_deref())
+                }
+            },
+            |map, textures| {
+                TilesetRenderData::with_shared_textures(
+                    map.meta_data.tilewidth,
+                    map.meta_data.tileheight,
+                    &map.tilesets,
+                    engine,
+                    textures,
+                )
+            },
+        )
+    }
+
+    fn get_or_load_with(
+        &mut self,
+        path: &str,
+        load_map: impl FnOnce(&str) -> Result<Map>,
+        load_render_data: impl FnOnce(
+            &Map,
+            &mut HashMap<String, CachedTexture>,
+        ) -> Result<TilesetRenderData>,
+    ) -> Result<&mut LoadedMap> {
+        if !self.maps.contains_key(path) {
+            let map = load_map(path)?;
+            let render_data = match self.render_data.entry(RenderDataKey::new(&map)) {
+                Entry::Occupied(occupied) => occupied.get().clone(),
+                Entry::Vacant(vacant) => {
+                    let render_data = load_render_data(&map, &mut self.textures)
+                        .with_context(|| format!("error loading tilesets for {}", path))?;
+                    vacant.insert(Rc::new(render_data)).clone()
+                }
+            };
+
+            self.maps
+                .insert(path.to_owned(), LoadedMap { map, render_data });
+        }
+
+        Ok(self.maps.get_mut(path).unwrap())
+    }
+
+    /// Make the map at `path` the current map, loading it first if need be.
+    pub fn switch_to(&mut self, path: &str, engine: &Engine) -> Result<&mut LoadedMap> {
+        self.get_or_load(path, engine)?;
+        self.current = Some(path.to_owned());
+        Ok(self.maps.get_mut(path).unwrap())
+    }
+
+    /// The path of the current map, if one has been switched to.
+    pub fn current_path(&self) -> Option<&str> {
+        self.current.as_deref()
+    }
+
+    pub fn current(&self) -> Option<&LoadedMap> {
+        self.maps.get(self.current.as_deref()?)
+    }
+
+    pub fn current_mut(&mut self) -> Option<&mut LoadedMap> {
+        self.maps.get_mut(self.current.as_deref()?)
+    }
+
+    /// Get the map at `path`, if it's loaded.
+    pub fn get(&self, path: &str) -> Option<&LoadedMap> {
+        self.maps.get(path)
+    }
+
+    pub fn get_mut(&mut self, path: &str) -> Option<&mut LoadedMap> {
+        self.maps.get_mut(path)
+    }
+
+    /// Whether the map at `path` is loaded.
+    pub fn contains(&self, path: &str) -> bool {
+        self.maps.contains_key(path)
+    }
+
+    /// Unload the map at `path`, returning it if it was loaded. If it was the current map, there's
+    /// no current map afterwards. Its tileset render data and textures stay cached until
+    /// [`MapSet::clear_unused_tilesets`] is called.
+    pub fn remove(&mut self, path: &str) -> Option<LoadedMap> {
+        if self.current.as_deref() == Some(path) {
+            self.current = None;
+        }
+
+        self.maps.remove(path)
+    }
+
+    /// Drop any cached tileset render data and textures which no loaded map is using.
+    pub fn clear_unused_tilesets(&mut self) {
+        self.render_data
+            .retain(|_, render_data| Rc::strong_count(render_data) > 1);
+
+        let images_in_use = self
+            .render_data
+            .keys()
+            .flat_map(|key| key.tilesets.iter())
+            .filter_map(|tileset| tileset.image.as_deref())
+            .collect::<HashSet<_>>();
+        self.textures
+            .retain(|source, _| images_in_use.contains(source.as_str()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A map with no layers, using the given `(name, first_gid)` tilesets. Every tileset named
+    /// `name` uses the image `name.png`.
+    fn room(tilesets: &[(&str, u32)]) -> Map {
+        let tilesets = tilesets
+            .iter()
+            .map(|(name, first_gid)| {
+                format!(
+                    r#"{{
+                        "name": "{0}", "firstgid": {1},
+                        "tilewidth": 16, "tileheight": 16, "spacing": 0, "margin": 0,
+                        "columns": 4, "tilecount": 4,
+                        "image": "{0}.png", "imagewidth": 64, "imageheight": 16
+                    }}"#,
+                    name, first_gid
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+
+        json_parser::parse_map_str(
+            &format!(
+                r#"{{
+                    "version": "1.6", "tiledversion": "1.7.0",
+                    "orientation": "orthogonal", "renderorder": "right-down",
+                    "width": 1, "height": 1, "tilewidth": 16, "tileheight": 16,
+                    "nextlayerid": 1, "nextobjectid": 1,
+                    "tilesets": [{}],
+                    "layers": []
+                }}"#,
+                tilesets
+            ),
+            None,
+        )
+        .unwrap()
+    }
+
+    fn load(set: &mut MapSet, path: &str, loads: &mut usize) -> Rc<TilesetRenderData> {
+        let map = match path {
+            "a" | "b" => room(&[("blocks", 1)]),
+            "c" => room(&[("props", 1), ("blocks", 5)]),
+            _ => unreachable!(),
+        };
+
+        let loaded = set
+            .get_or_load_with(
+                path,
+                |_| Ok(map),
+                |map, _| {
+                    *loads += 1;
+                    Ok(TilesetRenderData::from_parts(
+                        16,
+                        16,
+                        &map.tilesets,
+                        Vec::new(),
+                        Vec::new(),
+                        Vec::new(),
+                    ))
+                },
+            )
+            .unwrap();
+        loaded.render_data.clone()
+    }
+
+    #[test]
+    fn maps_with_the_same_tilesets_share_render_data() {
+        let mut set = MapSet::new(None);
+        let mut loads = 0;
+
+        let a = load(&mut set, "a", &mut loads);
+        let b = load(&mut set, "b", &mut loads);
+        assert!(Rc::ptr_eq(&a, &b));
+        assert_eq!(loads, 1);
+
+        // Loading an already loaded map doesn't rebuild anything.
+        let a_again = load(&mut set, "a", &mut loads);
+        assert!(Rc::ptr_eq(&a, &a_again));
+        assert_eq!(loads, 1);
+
+        // The same tileset at a different first GID needs its own render data.
+        let c = load(&mut set, "c", &mut loads);
+        assert!(!Rc::ptr_eq(&a, &c));
+        assert_eq!(loads, 2);
+
+        drop((a, b, a_again, c));
+        set.remove("c");
+        set.clear_unused_tilesets();
+        assert_eq!(set.render_data.len(), 1);
+        assert_eq!(set.maps.len(), 2);
+    }
+}
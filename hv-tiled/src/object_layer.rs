@@ -1,5 +1,14 @@
 use crate::*;
 
+use hv_friends::{
+    collision::Collider,
+    math::{Isometry2, Position2},
+    parry2d::shape::SharedShape,
+};
+
+/// How many points the collider for an ellipse object which isn't a circle is approximated with.
+const ELLIPSE_SEGMENTS: usize = 16;
+
 #[derive(Debug, Clone)]
 pub enum ObjGroupType {
     ObjectGroup,
@@ -98,6 +107,93 @@ pub struct Object {
     pub text: Option<Text>,
 }
 
+impl Object {
+    /// The object's origin and rotation. Tiled rotates objects clockwise by [`Object::rotation`]
+    /// degrees around their origin, which is the top-left corner of shapes and the bottom-left
+    /// corner of tile objects. An entity spawned for this object should be placed here, with the
+    /// collider from [`Object::collider`].
+    pub fn isometry(&self) -> Isometry2<f32> {
+        Isometry2::new(Vector2::new(self.x, self.y), self.rotation.to_radians())
+    }
+
+    /// A collider matching the object's shape, relative to [`Object::isometry`], so that it rotates
+    /// around the same point Tiled does. Ellipses which aren't circles are approximated with
+    /// polygons. Returns `None` for points, polygons, polylines, text, and shapes with no area.
+    pub fn collider(&self) -> Option<Collider> {
+        let (w, h) = (self.width, self.height);
+        if w <= 0. || h <= 0. {
+            return None;
+        }
+
+        // Tile objects extend upwards from their origin, rather than downwards.
+        let top = if self.tile_id.is_some() { -h } else { 0. };
+        let center = Isometry2::translation(w / 2., top + h / 2.);
+
+        let shape = match self.shape.as_ref()? {
+            ObjectShape::Rect => SharedShape::cuboid(w / 2., h / 2.),
+            ObjectShape::Ellipse if (w - h).abs() <= f32::EPSILON => SharedShape::ball(w / 2.),
+            ObjectShape::Ellipse => {
+                let points = (0..ELLIPSE_SEGMENTS)
+                    .map(|i| {
+                        let t = i as f32 / ELLIPSE_SEGMENTS as f32 * std::f32::consts::TAU;
+                        Point2::new(w / 2. * t.cos(), h / 2. * t.sin())
+                    })
+                    .collect::<Vec<_>>();
+                SharedShape::convex_hull(&points)?
+            }
+            ObjectShape::Point | ObjectShape::Polyline { .. } | ObjectShape::Polygon { .. } => {
+                return None
+            }
+        };
+
+        Some(Collider::new(center, shape))
+    }
+
+    /// Convert this object into Lua userdata which remembers the map held by `map_ud`, so that
+    /// object properties referring to other objects can be resolved.
+    pub fn to_lua_with_map<'lua>(
+        self,
+        lua: &'lua Lua,
+        map_ud: &LuaAnyUserData<'lua>,
+    ) -> LuaResult<LuaValue<'lua>> {
+        let ud = lua.create_userdata(self)?;
+        ud.set_user_value(map_ud.clone())?;
+        Ok(LuaValue::UserData(ud))
+    }
+}
+
+impl LuaUserData for Object {
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_field_method_get("id", |_, this| Ok(this.id.id));
+        fields.add_field_method_get("name", |_, this| Ok(this.name.clone()));
+        fields.add_field_method_get("type", |_, this| Ok(this.obj_type.clone()));
+        fields.add_field_method_get("x", |_, this| Ok(this.x));
+        fields.add_field_method_get("y", |_, this| Ok(this.y));
+        fields.add_field_method_get("width", |_, this| Ok(this.width));
+        fields.add_field_method_get("height", |_, this| Ok(this.height));
+        fields.add_field_method_get("rotation", |_, this| Ok(this.rotation));
+        fields.add_field_method_get("visible", |_, this| Ok(this.visible));
+    }
+
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("position", |_, this, ()| {
+            Ok(Position2::from(this.isometry()))
+        });
+        methods.add_method("collider", |_, this, ()| Ok(this.collider()));
+        methods.add_function(
+            "get_property",
+            |lua, (this, key): (LuaAnyUserData, LuaString)| {
+                let map_ud = this.get_user_value::<LuaAnyUserData>()?;
+                let object = this.borrow::<Object>()?;
+                match object.properties.get_property(key.to_str()?) {
+                    Some(property) => property.to_lua_with_map(lua, &map_ud),
+                    None => Ok(LuaValue::Nil),
+                }
+            },
+        );
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct ObjectLayerId {
     // global layer id and local layer id
@@ -142,3 +238,78 @@ impl ObjectGroup {
 }
 
 pub type ObjectLayer = ObjectGroup;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rectangle(x: f32, y: f32, width: f32, height: f32, rotation: f32) -> Object {
+        Object {
+            id: ObjectId::new(1, true),
+            name: String::new(),
+            obj_type: String::new(),
+            x,
+            y,
+            width,
+            height,
+            rotation,
+            tile_id: None,
+            visible: true,
+            properties: Properties(HashMap::new()),
+            shape: Some(ObjectShape::Rect),
+            text: None,
+        }
+    }
+
+    fn assert_box_eq(actual: Box2<f32>, mins: (f32, f32), maxs: (f32, f32)) {
+        let close = |a: Point2<f32>, (x, y): (f32, f32)| (a - Point2::new(x, y)).norm() < 1e-4;
+        assert!(
+            close(actual.mins, mins) && close(actual.maxs, maxs),
+            "expected {:?}..{:?}, got {:?}",
+            mins,
+            maxs,
+            actual
+        );
+    }
+
+    #[test]
+    fn rotated_rectangles_rotate_around_their_top_left() {
+        let object = rectangle(10., 20., 4., 2., 0.);
+        let collider = object.collider().unwrap();
+        assert_box_eq(
+            collider.compute_aabb(&object.isometry()),
+            (10., 20.),
+            (14., 22.),
+        );
+
+        // Rotated a quarter turn clockwise (on a y-down screen) around (10, 20), the rectangle now
+        // hangs down and to the left of its top-left corner.
+        let object = rectangle(10., 20., 4., 2., 90.);
+        let collider = object.collider().unwrap();
+        assert_box_eq(
+            collider.compute_aabb(&object.isometry()),
+            (8., 20.),
+            (10., 24.),
+        );
+
+        // Tile objects are anchored at their bottom-left instead.
+        let tile_object = Object {
+            tile_id: Some(TileId(1, TileMetaData::new(0, false, false, false))),
+            ..rectangle(10., 20., 4., 2., 0.)
+        };
+        assert_box_eq(
+            tile_object
+                .collider()
+                .unwrap()
+                .compute_aabb(&tile_object.isometry()),
+            (10., 18.),
+            (14., 20.),
+        );
+
+        let point = Object {
+            shape: Some(ObjectShape::Point),
+            ..rectangle(10., 20., 0., 0., 0.)
+        };
+        assert!(point.collider().is_none());
+    }
+}
@@ -1,5 +1,18 @@
 use crate::*;
 
+use hv_core::filesystem::File;
+use hv_friends::{
+    graphics::{Canvas, ClearOptions, SharedTexture},
+    math::Matrix4,
+};
+
+use std::{collections::HashSet, mem};
+
+/// The largest texture a baked tile layer is rendered into, along either axis. Layers bigger than
+/// this are baked into several textures. This is the largest texture size OpenGL ES 3.0 guarantees
+/// support for.
+pub const MAX_BAKE_SIZE: u32 = 2048;
+
 // TODO: implement this struct. How do we want to draw objects?
 // pub struct ObjectLayerBatch;
 
@@ -17,8 +30,12 @@ pub enum TileRenderData {
 pub struct TilesetRenderData {
     // Box2<f32> is the uvs
     uvs: Vec<Box2<f32>>,
-    // We pair the Texture and the related SpriteSheet of that texture
-    textures_and_spritesheets: Vec<(CachedTexture, SpriteSheet)>,
+    // One texture per tileset, unless the tilesets were packed into atlases
+    textures: Vec<CachedTexture>,
+    // One sprite sheet per tileset, holding the frames of its animated tiles
+    sprite_sheets: Vec<SpriteSheet>,
+    // Relates a tileset ID to the index of the texture its tiles are drawn from
+    tileset_textures: Vec<usize>,
     // Relates a TileId to a TagId, which is used to get the relevant sprite sheet info
     tile_to_tag_map: HashMap<TileId, TagId>,
     tile_width: u32,
@@ -32,49 +49,147 @@ impl TilesetRenderData {
         tilesets: &Tilesets,
         engine: &Engine,
     ) -> Result<Self, Error> {
-        let mut textures_and_spritesheets = Vec::with_capacity(tilesets.0.len());
+        Self::with_shared_textures(
+            tile_width,
+            tile_height,
+            tilesets,
+            engine,
+            &mut HashMap::new(),
+        )
+    }
+
+    /// Like [`TilesetRenderData::new`], but looks for each tileset's image in `shared_textures`
+    /// (keyed by image source) before loading it, and adds the textures it does load. Passing the
+    /// same map of textures when building render data for several maps means a tileset image used
+    /// by all of them is only uploaded once, even if the maps give the tileset different GIDs.
+    pub fn with_shared_textures(
+        tile_width: u32,
+        tile_height: u32,
+        tilesets: &Tilesets,
+        engine: &Engine,
+        shared_textures: &mut HashMap<String, CachedTexture>,
+    ) -> Result<Self, Error> {
+        let mut textures = Vec::with_capacity(tilesets.0.len());
         let mut uvs = Vec::new();
-        let mut tile_to_tag_map = HashMap::new();
 
         for tileset in tilesets.0.iter() {
-            if tileset.images.len() > 1 {
-                return Err(anyhow!(
-                    "Multiple images per tilesets aren't supported yet. Expected 1 image, got {}",
-                    tileset.images.len()
-                ));
-            }
+            let texture = match shared_textures.entry(tileset_image_source(tileset)?.to_owned()) {
+                Entry::Occupied(occupied) => occupied.get().clone(),
+                Entry::Vacant(vacant) => {
+                    let mut tileset_img_path = open_tileset_image(tileset, engine)?;
+                    let graphics_lock = engine.get::<GraphicsLock>();
+                    let mut acquired_lock = GraphicsLockExt::lock(&graphics_lock);
+                    let texture_obj =
+                        Texture::from_reader(&mut acquired_lock, &mut tileset_img_path)?;
 
-            let mut fs = engine.fs();
-            let mut tileset_img_path = fs.open(&mut Path::new(
-                &("/".to_owned() + &tileset.images[0].source),
-            ))?;
-            let graphics_lock = engine.get::<GraphicsLock>();
-            let mut acquired_lock = GraphicsLockExt::lock(&graphics_lock);
-            let texture_obj = Texture::from_reader(&mut acquired_lock, &mut tileset_img_path)?;
+                    drop(acquired_lock);
 
-            drop(acquired_lock);
+                    vacant.insert(CachedTexture::from(texture_obj)).clone()
+                }
+            };
+
+            let (texture_width, texture_height) = {
+                let texture = texture.get();
+                (texture.width(), texture.height())
+            };
+            uvs.extend(tileset_uvs(tileset, texture_width, texture_height));
+            textures.push(texture);
+        }
+
+        let tileset_textures = (0..textures.len()).collect();
+        Ok(Self::from_parts(
+            tile_width,
+            tile_height,
+            tilesets,
+            uvs,
+            textures,
+            tileset_textures,
+        ))
+    }
+
+    /// Like [`TilesetRenderData::new`], but packs the images of all tilesets into as few texture
+    /// atlases as possible, so that a tile layer using several tilesets can be drawn with fewer
+    /// batches and draw calls. Tilesets which don't fit into an atlas of `options.max_size` keep
+    /// their own texture.
+    pub fn new_packed(
+        tile_width: u32,
+        tile_height: u32,
+        tilesets: &Tilesets,
+        engine: &Engine,
+        options: AtlasOptions,
+    ) -> Result<Self, Error> {
+        let mut images = Vec::with_capacity(tilesets.0.len());
+        for tileset in tilesets.0.iter() {
+            let mut buf = Vec::new();
+            open_tileset_image(tileset, engine)?.read_to_end(&mut buf)?;
+            images.push(image::load_from_memory(&buf)?.to_rgba8());
+        }
+
+        let layout = TilesetAtlasLayout::pack(tilesets, &options);
+        let graphics_lock = engine.get::<GraphicsLock>();
+        let mut acquired_lock = GraphicsLockExt::lock(&graphics_lock);
+
+        let mut textures = Vec::new();
+        for mut atlas in layout.compose(tilesets, &images) {
+            image::imageops::flip_vertical_in_place(&mut atlas);
+            textures.push(CachedTexture::from(Texture::from_rgba8(
+                &mut acquired_lock,
+                atlas.width() as u16,
+                atlas.height() as u16,
+                &atlas,
+            )));
+        }
 
-            let rows = tileset.tilecount / tileset.columns;
-            let top = (rows * (tileset.spacing + tileset.tile_height)) + tileset.margin;
-            for row in 1..=rows {
-                for column in 0..tileset.columns {
-                    uvs.push(Box2::new(
-                        (tileset.margin
-                            + ((column * tileset.tile_width) + column * tileset.spacing))
-                            as f32
-                            / texture_obj.width() as f32,
-                        (tileset.spacing
-                            + (top
-                                - (tileset.margin
-                                    + ((row * tileset.tile_height) + row * tileset.spacing))))
-                            as f32
-                            / texture_obj.height() as f32,
-                        tileset.tile_width as f32 / texture_obj.width() as f32,
-                        tileset.tile_height as f32 / texture_obj.height() as f32,
-                    ));
+        let mut uvs = Vec::new();
+        let mut tileset_textures = Vec::with_capacity(tilesets.0.len());
+        for (i, (tileset, source)) in tilesets.0.iter().zip(images).enumerate() {
+            match layout.placements[i] {
+                TilesetPlacement::Atlas { atlas, .. } => {
+                    let count = tileset.tilecount / tileset.columns * tileset.columns;
+                    uvs.extend(
+                        (0..count).map(|local_id| layout.tile_uvs(i, tileset, local_id).unwrap()),
+                    );
+                    tileset_textures.push(atlas);
+                }
+                TilesetPlacement::Standalone => {
+                    let mut source = source;
+                    image::imageops::flip_vertical_in_place(&mut source);
+                    uvs.extend(tileset_uvs(tileset, source.width(), source.height()));
+                    tileset_textures.push(textures.len());
+                    textures.push(CachedTexture::from(Texture::from_rgba8(
+                        &mut acquired_lock,
+                        source.width() as u16,
+                        source.height() as u16,
+                        &source,
+                    )));
                 }
             }
+        }
+
+        drop(acquired_lock);
+
+        Ok(Self::from_parts(
+            tile_width,
+            tile_height,
+            tilesets,
+            uvs,
+            textures,
+            tileset_textures,
+        ))
+    }
 
+    pub(crate) fn from_parts(
+        tile_width: u32,
+        tile_height: u32,
+        tilesets: &Tilesets,
+        uvs: Vec<Box2<f32>>,
+        textures: Vec<CachedTexture>,
+        tileset_textures: Vec<usize>,
+    ) -> Self {
+        let mut sprite_sheets = Vec::with_capacity(tilesets.0.len());
+        let mut tile_to_tag_map = HashMap::new();
+
+        for tileset in tilesets.0.iter() {
             let mut sprite_sheet = SpriteSheet::new();
 
             for (_, tile) in tileset.tiles.iter() {
@@ -101,16 +216,18 @@ impl TilesetRenderData {
                 }
             }
 
-            textures_and_spritesheets.push((CachedTexture::from(texture_obj), sprite_sheet));
+            sprite_sheets.push(sprite_sheet);
         }
 
-        Ok(TilesetRenderData {
+        TilesetRenderData {
             tile_height,
             tile_width,
             uvs,
-            textures_and_spritesheets,
+            textures,
+            sprite_sheets,
+            tileset_textures,
             tile_to_tag_map,
-        })
+        }
     }
 
     pub fn get_render_data_for_tile(
@@ -123,23 +240,74 @@ impl TilesetRenderData {
         } else {
             TileRenderData::Static(self.uvs[tile.0 as usize])
         };
-        let (ss, ct) = &self.textures_and_spritesheets[tile.1.tileset_id() as usize];
-        (render_data, ct, ss)
+        let (ss, ct) = self.get_tileset_texture_and_spritesheet(tile.1.tileset_id());
+        (render_data, ss, ct)
     }
 
     pub fn get_tileset_texture_and_spritesheet(
         &self,
         tileset_id: u32,
     ) -> (&SpriteSheet, &CachedTexture) {
-        let (ss, ct) = &self.textures_and_spritesheets[tileset_id as usize];
-        (ct, ss)
+        let tileset_id = tileset_id as usize;
+        (
+            &self.sprite_sheets[tileset_id],
+            &self.textures[self.tileset_textures[tileset_id]],
+        )
+    }
+
+    /// The number of textures (and thus sprite batches per tile layer) the tilesets are drawn
+    /// from.
+    pub fn texture_count(&self) -> usize {
+        self.textures.len()
     }
 }
 
+fn tileset_image_source(tileset: &Tileset) -> Result<&str, Error> {
+    if tileset.images.len() != 1 {
+        return Err(anyhow!(
+            "Only tilesets with a single image are supported for now. Expected 1 image, got {}",
+            tileset.images.len()
+        ));
+    }
+
+    Ok(&tileset.images[0].source)
+}
+
+fn open_tileset_image(tileset: &Tileset, engine: &Engine) -> Result<File, Error> {
+    engine.fs().open(Path::new(
+        &("/".to_owned() + tileset_image_source(tileset)?),
+    ))
+}
+
+pub(crate) fn tileset_uvs(
+    tileset: &Tileset,
+    texture_width: u32,
+    texture_height: u32,
+) -> impl Iterator<Item = Box2<f32>> + '_ {
+    let rows = tileset.tilecount / tileset.columns;
+    let top = (rows * (tileset.spacing + tileset.tile_height)) + tileset.margin;
+    (1..=rows).flat_map(move |row| {
+        (0..tileset.columns).map(move |column| {
+            Box2::new(
+                (tileset.margin + ((column * tileset.tile_width) + column * tileset.spacing))
+                    as f32
+                    / texture_width as f32,
+                (tileset.spacing
+                    + (top
+                        - (tileset.margin + ((row * tileset.tile_height) + row * tileset.spacing))))
+                    as f32
+                    / texture_height as f32,
+                tileset.tile_width as f32 / texture_width as f32,
+                tileset.tile_height as f32 / texture_height as f32,
+            )
+        })
+    })
+}
+
 impl Drawable for TilesetRenderData {
     fn draw(&self, ctx: &mut Graphics, instance: Instance) {
         let mut y_offset = 0.0;
-        for (texture, _) in self.textures_and_spritesheets.iter() {
+        for texture in self.textures.iter() {
             texture.draw(ctx, instance.translate2(Vector2::new(0.0, y_offset)));
             y_offset += texture.get().height() as f32;
         }
@@ -186,12 +354,40 @@ impl TileLayerBatches {
         }
     }
 
+    /// Bring the batches in line with the map's tile layers after layers have been added,
+    /// removed, or reordered with [`Map::add_tile_layer`], [`Map::remove_tile_layer`] or
+    /// [`Map::reorder_layers`]. Batches of layers still in the map are kept as they are, batches
+    /// are built for new layers, and the batches are drawn in the map's new layer order.
+    pub fn sync_layers(&mut self, map: &Map, ts_render_data: &TilesetRenderData, engine: &Engine) {
+        let mut old_batches = self.batches.drain(..).map(Some).collect::<Vec<_>>();
+        for tile_layer in map.tile_layers.iter() {
+            let existing = old_batches
+                .iter()
+                .position(|batch| batch.as_ref().map_or(false, |b| b.id == tile_layer.id))
+                .and_then(|index| old_batches[index].take());
+
+            self.batches.push(existing.unwrap_or_else(|| {
+                TileLayerBatch::new(tile_layer, ts_render_data, engine, &map.meta_data)
+            }));
+        }
+    }
+
+    fn layer_index(&self, layer_id: TileLayerId) -> Option<usize> {
+        find_layer_index(&self.batches, layer_id, |batch| batch.id)
+    }
+
     pub fn get_layer(&self, layer_id: TileLayerId) -> &TileLayerBatch {
-        &self.batches[layer_id.llid as usize]
+        let index = self
+            .layer_index(layer_id)
+            .unwrap_or_else(|| panic!("no tile layer batch with id {:?}", layer_id));
+        &self.batches[index]
     }
 
     pub fn get_layer_mut(&mut self, layer_id: TileLayerId) -> &mut TileLayerBatch {
-        &mut self.batches[layer_id.llid as usize]
+        let index = self
+            .layer_index(layer_id)
+            .unwrap_or_else(|| panic!("no tile layer batch with id {:?}", layer_id));
+        &mut self.batches[index]
     }
 
     pub fn get_tile_batch_layers(&mut self) -> impl Iterator<Item = &mut TileLayerBatch> + '_ {
@@ -203,67 +399,57 @@ impl TileLayerBatches {
         addition: &TileAddition,
         ts_render_data: &TilesetRenderData,
     ) -> Option<SpriteId> {
+        // Changes to layers which have since been removed have nothing left to update
+        let layer_index = self.layer_index(addition.layer_id)?;
+
         // Remove the existing sprite id and any animated metadata associatd with it
-        let ret_val = if self.batches[addition.layer_id.llid as usize]
+        let ret_val = if self.batches[layer_index]
             .sprite_id_map
             .contains_key(&(addition.x, addition.y))
         {
-            self.remove_tile(&TileRemoval {
-                id: addition.changed_id.unwrap(),
-                layer_id: addition.layer_id,
-                x: addition.x,
-                y: addition.y,
-            })
+            self.remove_tile(
+                &TileRemoval {
+                    id: addition.changed_id.unwrap(),
+                    layer_id: addition.layer_id,
+                    x: addition.x,
+                    y: addition.y,
+                },
+                ts_render_data,
+            )
         } else {
             None
         };
 
-        // Insert the new tile into the sprite sheet
+        // Insert the new tile into the sprite batch of the chunk containing it
         let index = addition.new_id.to_index().unwrap();
-        let tile_batch = &mut self.batches[addition.layer_id.llid as usize];
-        let sprite_id = tile_batch.sprite_batches[addition.new_id.1.tileset_id() as usize].insert(
-            Instance::new()
-                .src(ts_render_data.uvs[index])
-                .color(Color::new(1.0, 1.0, 1.0, tile_batch.opacity as f32))
-                .translate2(Vector2::new(
-                    (addition.x * ts_render_data.tile_width as i32) as f32,
-                    // TODO: make sure that this is correct, we subtract one because our origin is 1 unit
-                    // lower than tiled's system
-                    ((addition.y - 1) * ts_render_data.tile_height as i32) as f32,
-                )),
+        let tile_batch = &mut self.batches[layer_index];
+        let instance = Instance::new()
+            .src(ts_render_data.uvs[index])
+            .color(Color::new(1.0, 1.0, 1.0, tile_batch.opacity as f32))
+            .translate2(Vector2::new(
+                (addition.x * ts_render_data.tile_width as i32) as f32,
+                // TODO: make sure that this is correct, we subtract one because our origin is 1 unit
+                // lower than tiled's system
+                ((addition.y - 1) * ts_render_data.tile_height as i32) as f32,
+            ));
+        tile_batch.insert_tile(
+            addition.x,
+            addition.y,
+            addition.new_id,
+            instance,
+            ts_render_data,
         );
 
-        // If it's an animated tile, add it to the sprite sheet state hashmap so that it'll get updated correctly
-        if let Some(t) = ts_render_data.tile_to_tag_map.get(&addition.new_id) {
-            let anim_state = ts_render_data.textures_and_spritesheets
-                [addition.new_id.1.tileset_id() as usize]
-                .1
-                .at_tag(*t, true);
-            tile_batch.sprite_sheet_info[addition.new_id.1.tileset_id() as usize]
-                .insert(sprite_id, SpriteSheetState { anim_state });
-        }
-
-        // Insert the new sprite id, we unwrap() here to trigger a panic in the event
-        // that we somehow inserted a tile that already existed
-        let res = tile_batch
-            .sprite_id_map
-            .insert((addition.x, addition.y), sprite_id);
-        assert!(res.is_none(),
-                 "There is a bug in the hv_tiled remove_tile function, remove_tile should've removed the tile, but instead we got {:?}", res.unwrap());
-
         ret_val
     }
 
-    fn remove_tile(&mut self, removal: &TileRemoval) -> Option<SpriteId> {
-        let tile_batch = &mut self.batches[removal.layer_id.llid as usize];
-        if let Some(old_sprite_id) = tile_batch.sprite_id_map.remove(&(removal.x, removal.y)) {
-            // Attempt to remove the sprite sheet info if it exists since we don't want to update animation info for a sprite that doesn't exist
-            tile_batch.sprite_sheet_info[removal.id.1.tileset_id() as usize].remove(&old_sprite_id);
-            tile_batch.sprite_batches[removal.id.1.tileset_id() as usize].remove(old_sprite_id);
-            Some(old_sprite_id)
-        } else {
-            None
-        }
+    fn remove_tile(
+        &mut self,
+        removal: &TileRemoval,
+        ts_render_data: &TilesetRenderData,
+    ) -> Option<SpriteId> {
+        let layer_index = self.layer_index(removal.layer_id)?;
+        self.batches[layer_index].remove_tile(removal.x, removal.y, removal.id, ts_render_data)
     }
 
     pub fn resolve_delta(
@@ -273,7 +459,41 @@ impl TileLayerBatches {
     ) -> Option<SpriteId> {
         match change {
             TileChange::TileAddition(a) => self.set_tile(a, ts_render_data),
-            TileChange::TileRemoval(r) => self.remove_tile(r),
+            TileChange::TileRemoval(r) => self.remove_tile(r, ts_render_data),
+        }
+    }
+
+    /// Apply the combined result of [`Map::apply_changes`] to the batches. Each changed cell is
+    /// updated once, and only the chunks containing changed cells are re-uploaded, the next time
+    /// they're drawn.
+    pub fn apply_delta(&mut self, delta: &MapBatchDelta, ts_render_data: &TilesetRenderData) {
+        for cell in delta.cells() {
+            match (cell.old, cell.new) {
+                (changed_id, Some(new_id)) => {
+                    self.set_tile(
+                        &TileAddition {
+                            changed_id,
+                            new_id,
+                            layer_id: cell.layer_id,
+                            x: cell.x,
+                            y: cell.y,
+                        },
+                        ts_render_data,
+                    );
+                }
+                (Some(id), None) => {
+                    self.remove_tile(
+                        &TileRemoval {
+                            id,
+                            layer_id: cell.layer_id,
+                            x: cell.x,
+                            y: cell.y,
+                        },
+                        ts_render_data,
+                    );
+                }
+                (None, None) => {}
+            }
         }
     }
 
@@ -286,28 +506,264 @@ impl TileLayerBatches {
             .map(|change| self.resolve_delta(change, ts_render_data))
             .collect()
     }
+
+    /// Pre-render the layer into one or more textures (more than one only if it's bigger than
+    /// [`MAX_BAKE_SIZE`]), so that from then on it's drawn as one quad per texture rather than
+    /// through its sprite batches. This is worth it for large layers which never change, such as
+    /// backgrounds. Baking an already baked layer re-renders it.
+    ///
+    /// Editing a baked layer (including advancing its animated tiles in
+    /// [`TileLayerBatches::update_all_batches`]) makes its bake stale. Stale layers are drawn
+    /// through their sprite batches, as if they weren't baked, until they're re-baked with
+    /// [`TileLayerBatches::rebake_stale`].
+    ///
+    /// This draws to its own render targets, so it must be called outside of any render pass.
+    pub fn bake_layer(&mut self, ctx: &mut Graphics, layer_id: TileLayerId) -> &[BakedTexture] {
+        let batch = self.get_layer_mut(layer_id);
+        batch.bake(ctx);
+        &batch.baked
+    }
+
+    /// Re-bake every layer whose bake has gone stale since it was baked; see
+    /// [`TileLayerBatches::bake_layer`]. Calling this once per frame, before drawing starts, keeps
+    /// edited layers baked. Must be called outside of any render pass.
+    pub fn rebake_stale(&mut self, ctx: &mut Graphics) {
+        for batch in self.batches.iter_mut() {
+            if batch.bake_state == BakeState::Stale {
+                batch.bake(ctx);
+            }
+        }
+    }
+
+    /// Drop a layer's bake, if it has one, going back to drawing it through its sprite batches.
+    pub fn unbake_layer(&mut self, layer_id: TileLayerId) {
+        let batch = self.get_layer_mut(layer_id);
+        batch.baked.clear();
+        batch.bake_state = BakeState::Unbaked;
+    }
+
+    /// Draw the visible layers, skipping any chunk which lies entirely outside of `view`. `view`
+    /// is in the map's pixel coordinates, before `instance` is applied.
+    pub fn draw_visible(&mut self, ctx: &mut Graphics, instance: Instance, view: &Box2<f32>) {
+        for tile_layer in self.batches.iter_mut() {
+            if tile_layer.visible {
+                let offset = Vector2::new(tile_layer.offset_x, -tile_layer.offset_y);
+                let local_view = Box2::from_corners(view.mins - offset, view.maxs - offset);
+                tile_layer.draw_chunks(ctx, instance.translate2(offset), Some(&local_view));
+            }
+        }
+    }
 }
 
 impl DrawableMut for TileLayerBatches {
     fn draw_mut(&mut self, ctx: &mut Graphics, instance: Instance) {
         for tile_layer in self.batches.iter_mut() {
             if tile_layer.visible {
-                for batch in tile_layer.sprite_batches.iter_mut() {
-                    batch.draw_mut(
-                        ctx,
-                        instance
-                            .translate2(Vector2::new(tile_layer.offset_x, -tile_layer.offset_y)),
-                    );
-                }
+                tile_layer.draw_mut(
+                    ctx,
+                    instance.translate2(Vector2::new(tile_layer.offset_x, -tile_layer.offset_y)),
+                );
             }
         }
     }
 }
 
-pub struct TileLayerBatch {
+/// The chunks of a tile layer which have been edited since they were last drawn.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct DirtyChunks(HashSet<(i32, i32)>);
+
+impl DirtyChunks {
+    /// Mark the chunk containing the tile at `(x, y)` as dirty, returning its coordinates.
+    pub(crate) fn mark_cell(&mut self, x: i32, y: i32) -> (i32, i32) {
+        let chunk = chunk_of_cell(x, y);
+        self.0.insert(chunk);
+        chunk
+    }
+
+    pub(crate) fn mark_chunk(&mut self, chunk: (i32, i32)) {
+        self.0.insert(chunk);
+    }
+
+    pub(crate) fn clear_chunk(&mut self, chunk: (i32, i32)) {
+        self.0.remove(&chunk);
+    }
+
+    pub(crate) fn contains(&self, chunk: (i32, i32)) -> bool {
+        self.0.contains(&chunk)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+/// Whether a tile layer has been baked with [`TileLayerBatches::bake_layer`], and if so, whether
+/// the bake is still up to date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BakeState {
+    /// The layer is drawn through its sprite batches.
+    Unbaked,
+    /// The layer is drawn from its baked textures.
+    Baked,
+    /// The layer has been edited since it was baked, and is drawn through its sprite batches until
+    /// it's baked again.
+    Stale,
+}
+
+impl Default for BakeState {
+    fn default() -> Self {
+        Self::Unbaked
+    }
+}
+
+impl BakeState {
+    /// Note that the layer was edited.
+    pub(crate) fn edit(&mut self) {
+        if *self == BakeState::Baked {
+            *self = BakeState::Stale;
+        }
+    }
+}
+
+/// One of the textures a tile layer was baked into, and the region of the layer it covers.
+#[derive(Debug)]
+pub struct BakedTexture {
+    canvas: Canvas,
+    bounds: Box2<f32>,
+}
+
+impl BakedTexture {
+    /// The texture the region was rendered into.
+    pub fn texture(&self) -> &SharedTexture {
+        &self.canvas.color_buffer
+    }
+
+    /// The region of the layer this texture covers, in the layer's pixel coordinates. It's drawn
+    /// with its corner at `bounds.mins`.
+    pub fn bounds(&self) -> Box2<f32> {
+        self.bounds
+    }
+}
+
+/// Split the area covered by a layer into regions no bigger than `max_size` along either axis,
+/// rounding it out to whole pixels first. Each region becomes one baked texture.
+fn bake_regions(bounds: &Box2<f32>, max_size: u32) -> Vec<Box2<f32>> {
+    let mins = Point2::new(bounds.mins.x.floor(), bounds.mins.y.floor());
+    let maxs = Point2::new(bounds.maxs.x.ceil(), bounds.maxs.y.ceil());
+    let max_size = max_size as f32;
+
+    let mut regions = Vec::new();
+    let mut y = mins.y;
+    while y < maxs.y {
+        let h = (maxs.y - y).min(max_size);
+        let mut x = mins.x;
+        while x < maxs.x {
+            let w = (maxs.x - x).min(max_size);
+            regions.push(Box2::new(x, y, w, h));
+            x += w;
+        }
+        y += h;
+    }
+
+    regions
+}
+
+/// The pixel position the tile at `(x, y)` is drawn at, before any flipping.
+fn cell_to_pixel(
+    orientation: &Orientation,
+    tile_width: u32,
+    tile_height: u32,
+    x: i32,
+    y: i32,
+) -> Point2<f32> {
+    let tile_y_global = y - 1;
+    match orientation {
+        Orientation::Orthogonal => Point2::new(
+            (x * tile_width as i32) as f32,
+            (tile_y_global * tile_height as i32) as f32,
+        ),
+        Orientation::Isometric => Point2::new(
+            ((x + tile_y_global) * tile_width as i32) as f32 / 2.0,
+            ((x + (-tile_y_global)) * tile_height as i32) as f32 / -2.0,
+        ),
+    }
+}
+
+/// The area covered by the tiles of a chunk, in pixels, padded by a tile on every side to account
+/// for flipped and rotated tiles.
+fn chunk_bounds(
+    orientation: &Orientation,
+    tile_width: u32,
+    tile_height: u32,
+    (chunk_x, chunk_y): (i32, i32),
+) -> Box2<f32> {
+    let size = CHUNK_SIZE as i32;
+    let (x0, x1) = (chunk_x * size, chunk_x * size + size - 1);
+    // Chunks are stored with the y axis flipped; see `chunk_of_cell`.
+    let (y0, y1) = (-(chunk_y * size + size - 1), -(chunk_y * size));
+
+    let corners = [(x0, y0), (x1, y0), (x0, y1), (x1, y1)]
+        .iter()
+        .map(|&(x, y)| cell_to_pixel(orientation, tile_width, tile_height, x, y))
+        .collect::<Vec<_>>();
+    let mins = corners.iter().fold(corners[0], |a, b| a.inf(b));
+    let maxs = corners.iter().fold(corners[0], |a, b| a.sup(b));
+
+    Box2::from_corners(mins, maxs).loosened(tile_width.max(tile_height) as f32)
+}
+
+/// The sprite batches for the tiles of one `CHUNK_SIZE` by `CHUNK_SIZE` chunk of a tile layer.
+struct ChunkBatch {
+    // One sprite batch per texture, created when the chunk's first tile from it is inserted
+    sprite_batches: Vec<Option<SpriteBatch<CachedTexture>>>,
+    // One set of animation states per tileset
     sprite_sheet_info: Vec<HashMap<SpriteId, SpriteSheetState>>,
+    bounds: Box2<f32>,
+}
+
+impl ChunkBatch {
+    fn new(ts_render_data: &TilesetRenderData, bounds: Box2<f32>) -> Self {
+        Self {
+            sprite_batches: ts_render_data.textures.iter().map(|_| None).collect(),
+            sprite_sheet_info: vec![HashMap::new(); ts_render_data.sprite_sheets.len()],
+            bounds,
+        }
+    }
+
+    fn batch_mut(
+        &mut self,
+        ctx: &mut Graphics,
+        ts_render_data: &TilesetRenderData,
+        texture: usize,
+        capacity: usize,
+    ) -> &mut SpriteBatch<CachedTexture> {
+        self.sprite_batches[texture].get_or_insert_with(|| {
+            SpriteBatch::with_capacity(
+                ctx,
+                ts_render_data.textures[texture].clone(),
+                usize::max(capacity, 1),
+            )
+        })
+    }
+}
+
+/// The render data for a single tile layer. The layer is split into `CHUNK_SIZE` by `CHUNK_SIZE`
+/// chunks with their own sprite batches, so that editing a tile only re-uploads the chunk it's in,
+/// and chunks outside the view can be skipped by [`TileLayerBatches::draw_visible`].
+pub struct TileLayerBatch {
+    id: TileLayerId,
+    chunks: HashMap<(i32, i32), ChunkBatch>,
+    // Chunk coordinates in the order their chunks are drawn in
+    chunk_order: Vec<(i32, i32)>,
+    dirty_chunks: DirtyChunks,
+    bake_state: BakeState,
+    baked: Vec<BakedTexture>,
     pub sprite_id_map: HashMap<(i32, i32), SpriteId>,
-    sprite_batches: Vec<SpriteBatch<CachedTexture>>,
+    graphics_lock: Shared<GraphicsLock>,
+    orientation: Orientation,
+    render_order: RenderOrder,
+    tile_width: u32,
+    tile_height: u32,
     pub visible: bool,
     pub opacity: f64,
     _x: f32,
@@ -318,9 +774,7 @@ pub struct TileLayerBatch {
 
 impl DrawableMut for TileLayerBatch {
     fn draw_mut(&mut self, ctx: &mut Graphics, instance: Instance) {
-        for batch in self.sprite_batches.iter_mut() {
-            batch.draw_mut(ctx, instance);
-        }
+        self.draw_chunks(ctx, instance, None);
     }
 }
 
@@ -331,122 +785,445 @@ impl TileLayerBatch {
         engine: &Engine,
         map_meta_data: &MapMetaData,
     ) -> Self {
-        // We need 1 sprite batch per texture
-        let mut sprite_batches = Vec::with_capacity(ts_render_data.textures_and_spritesheets.len());
-        let mut ss_state = vec![HashMap::new(); ts_render_data.textures_and_spritesheets.len()];
-        let mut sprite_id_map = HashMap::new();
+        let mut this = TileLayerBatch {
+            id: layer.id,
+            chunks: HashMap::new(),
+            chunk_order: Vec::new(),
+            dirty_chunks: DirtyChunks::default(),
+            bake_state: BakeState::default(),
+            baked: Vec::new(),
+            sprite_id_map: HashMap::new(),
+            graphics_lock: engine.get::<GraphicsLock>(),
+            orientation: map_meta_data.orientation.clone(),
+            render_order: map_meta_data.render_order,
+            tile_width: map_meta_data.tilewidth,
+            tile_height: map_meta_data.tileheight,
+            visible: layer.visible,
+            opacity: layer.opacity,
+            _x: (layer.x * (map_meta_data.tilewidth as i32)) as f32,
+            _y: (layer.y * (map_meta_data.tileheight as i32)) as f32,
+            offset_x: layer.offset_x as f32,
+            offset_y: layer.offset_y as f32,
+        };
 
-        let graphics_lock = engine.get::<GraphicsLock>();
+        // Insert tiles in the map's render order, so that overlapping tiles are drawn over each
+        // other the same way Tiled draws them.
+        let tiles = layer.tiles_in_render_order(map_meta_data.render_order);
+
+        // Count the tiles each chunk draws from each texture first, so that every batch can be
+        // created with exactly enough room for its tiles.
+        let mut tile_counts = HashMap::<((i32, i32), usize), usize>::new();
+        for (x, y, tile) in tiles.iter() {
+            if tile.to_index().is_some() {
+                let texture = ts_render_data.tileset_textures[tile.1.tileset_id() as usize];
+                *tile_counts
+                    .entry((chunk_of_cell(*x, *y), texture))
+                    .or_default() += 1;
+            }
+        }
+
+        let graphics_lock = this.graphics_lock.clone();
+        let mut acquired_lock = GraphicsLockExt::lock(&graphics_lock);
+        for (&(chunk, texture), &count) in tile_counts.iter() {
+            this.chunk_mut(chunk, ts_render_data).batch_mut(
+                &mut acquired_lock,
+                ts_render_data,
+                texture,
+                count,
+            );
+        }
+        drop(acquired_lock);
+
+        for (x, y, tile) in tiles {
+            // Tile indices start at 1, 0 represents no tile, so we offset the tile by 1
+            if let Some(index) = tile.to_index() {
+                let (scale_x, trans_fix_x) = if tile.1.flipx() {
+                    (-1.0, -1.0 * map_meta_data.tilewidth as f32)
+                } else {
+                    (1.0, 0.0)
+                };
+
+                let (scale_y, trans_fix_y) = if tile.1.flipy() {
+                    (-1.0, -1.0 * map_meta_data.tileheight as f32)
+                } else {
+                    (1.0, 0.0)
+                };
+
+                let (rotation, y_scale, x_trans, y_trans) = if tile.1.diag_flip() {
+                    (
+                        std::f32::consts::FRAC_PI_2,
+                        -1.0,
+                        map_meta_data.tilewidth as f32,
+                        map_meta_data.tileheight as f32 * -1.0,
+                    )
+                } else {
+                    (0.0, 1.0, 0.0, 0.0)
+                };
+
+                let pixel = cell_to_pixel(
+                    &map_meta_data.orientation,
+                    map_meta_data.tilewidth,
+                    map_meta_data.tileheight,
+                    x,
+                    y,
+                );
+
+                // Todo: I think the reason why be add 1 here is due to the render data
+                // being offset by 1 from the actual map data, but this needs to be checked
+                this.insert_tile(
+                    x,
+                    y,
+                    tile,
+                    Instance::new()
+                        .src(ts_render_data.uvs[index])
+                        .color(Color::new(1.0, 1.0, 1.0, layer.opacity as f32))
+                        .translate2(pixel.coords)
+                        .scale2(Vector2::new(scale_x, scale_y))
+                        .translate2(Vector2::new(trans_fix_x, trans_fix_y))
+                        .scale2(Vector2::new(1.0, y_scale))
+                        .translate2(Vector2::new(x_trans, y_trans))
+                        .rotate2(rotation),
+                    ts_render_data,
+                );
+            }
+        }
+
+        this
+    }
+
+    /// The ID of the tile layer this batch draws.
+    pub fn id(&self) -> TileLayerId {
+        self.id
+    }
+
+    /// The number of chunks this layer has render data for.
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Whether the given chunk has been edited since it was last drawn, and will be re-uploaded
+    /// the next time it's drawn. Chunk coordinates are those returned by
+    /// [`tile_layer::chunk_of_cell`].
+    pub fn is_chunk_dirty(&self, chunk: (i32, i32)) -> bool {
+        self.dirty_chunks.contains(chunk)
+    }
+
+    /// The number of chunks which have been edited since they were last drawn.
+    pub fn dirty_chunk_count(&self) -> usize {
+        self.dirty_chunks.len()
+    }
 
-        for (texture, _) in ts_render_data.textures_and_spritesheets.iter() {
+    /// Whether this layer is baked; see [`TileLayerBatches::bake_layer`].
+    pub fn bake_state(&self) -> BakeState {
+        self.bake_state
+    }
+
+    /// Render every chunk into canvases covering the layer, reusing the canvases of the previous
+    /// bake for regions which haven't changed.
+    fn bake(&mut self, ctx: &mut Graphics) {
+        let bounds = self
+            .chunks
+            .values()
+            .map(|chunk_batch| chunk_batch.bounds)
+            .reduce(|a, b| a.merged(&b));
+        let regions = bounds.map_or_else(Vec::new, |bounds| bake_regions(&bounds, MAX_BAKE_SIZE));
+
+        let projection = *ctx.projection();
+        let default_projection_size = ctx.default_projection_size();
+        let mut old_baked = mem::take(&mut self.baked);
+        for region in regions {
+            let size = region.extents();
+            let canvas = match old_baked.iter().position(|baked| baked.bounds == region) {
+                Some(index) => old_baked.swap_remove(index).canvas,
+                None => Canvas::new(ctx, size.x as u32, size.y as u32),
+            };
+
+            ctx.set_projection(ctx.y_axis().orthographic(size.x, size.y));
+            ctx.modelview_mut()
+                .push(Matrix4::identity())
+                .translate2(-region.mins.coords);
+            ctx.begin_render_pass(
+                Some(&canvas.render_pass),
+                Some(ClearOptions {
+                    color: Some(Color::ZEROS),
+                    ..ClearOptions::default()
+                }),
+            );
+
+            for chunk in self.chunk_order.iter() {
+                let chunk_batch = self.chunks.get_mut(chunk).unwrap();
+                for batch in chunk_batch.sprite_batches.iter_mut().flatten() {
+                    batch.draw_mut(ctx, Instance::new());
+                }
+            }
+
+            ctx.end_render_pass();
+            ctx.modelview_mut().pop();
+            self.baked.push(BakedTexture {
+                canvas,
+                bounds: region,
+            });
+        }
+
+        match default_projection_size {
+            Some(size) => ctx.set_default_projection(size.x, size.y),
+            None => ctx.set_projection(projection),
+        }
+
+        // Every chunk's batches were uploaded while drawing them into the bake.
+        for chunk in self.chunk_order.iter() {
+            self.dirty_chunks.clear_chunk(*chunk);
+        }
+        self.bake_state = BakeState::Baked;
+    }
+
+    fn chunk_mut(
+        &mut self,
+        chunk: (i32, i32),
+        ts_render_data: &TilesetRenderData,
+    ) -> &mut ChunkBatch {
+        if !self.chunks.contains_key(&chunk) {
+            let bounds = chunk_bounds(&self.orientation, self.tile_width, self.tile_height, chunk);
+            self.chunks
+                .insert(chunk, ChunkBatch::new(ts_render_data, bounds));
+
+            let size = CHUNK_SIZE as i32;
+            let render_order = self.render_order;
+            self.chunk_order.push(chunk);
+            // Chunks are stored with the y axis flipped, so the chunk's row in Tiled's coordinates
+            // is `-chunk_y`; see `TileLayer::tiles_in_render_order`.
+            self.chunk_order.sort_by_key(|&(chunk_x, chunk_y)| {
+                let (x, y) = (chunk_x * size, -chunk_y * size);
+                match render_order {
+                    RenderOrder::RightDown => (y, x),
+                    RenderOrder::RightUp => (-y, x),
+                    RenderOrder::LeftDown => (y, -x),
+                    RenderOrder::LeftUp => (-y, -x),
+                }
+            });
+        }
+
+        self.chunks.get_mut(&chunk).unwrap()
+    }
+
+    /// Insert a tile into the sprite batch of the chunk containing it, marking that chunk dirty.
+    fn insert_tile(
+        &mut self,
+        x: i32,
+        y: i32,
+        tile: TileId,
+        instance: Instance,
+        ts_render_data: &TilesetRenderData,
+    ) -> SpriteId {
+        let chunk = self.dirty_chunks.mark_cell(x, y);
+        self.bake_state.edit();
+        let tileset_id = tile.1.tileset_id() as usize;
+        let texture = ts_render_data.tileset_textures[tileset_id];
+
+        let graphics_lock = self.graphics_lock.clone();
+        let chunk_batch = self.chunk_mut(chunk, ts_render_data);
+        // Only take the graphics lock if this is the first tile of its texture in the chunk
+        if chunk_batch.sprite_batches[texture].is_none() {
             let mut acquired_lock = GraphicsLockExt::lock(&graphics_lock);
-            sprite_batches.push(SpriteBatch::new(&mut acquired_lock, texture.clone()));
+            chunk_batch.batch_mut(&mut acquired_lock, ts_render_data, texture, 1);
             drop(acquired_lock);
         }
+        let sprite_id = chunk_batch.sprite_batches[texture]
+            .as_mut()
+            .unwrap()
+            .insert(instance);
 
-        for ((chunk_x, chunk_y), chunk) in layer.data.0.iter() {
-            for tile_y in 0..CHUNK_SIZE {
-                for tile_x in 0..CHUNK_SIZE {
-                    let tile = chunk.0[(tile_y * CHUNK_SIZE + tile_x) as usize];
-                    // Tile indices start at 1, 0 represents no tile, so we offset the tile by 1
-                    // first, and skip making the instance param if the tile is 0
-                    if let Some(index) = tile.to_index() {
-                        let (scale_x, trans_fix_x) = if tile.1.flipx() {
-                            (-1.0, -1.0 * map_meta_data.tilewidth as f32)
-                        } else {
-                            (1.0, 0.0)
-                        };
-
-                        let (scale_y, trans_fix_y) = if tile.1.flipy() {
-                            (-1.0, -1.0 * map_meta_data.tileheight as f32)
-                        } else {
-                            (1.0, 0.0)
-                        };
-
-                        let (rotation, y_scale, x_trans, y_trans) = if tile.1.diag_flip() {
-                            (
-                                std::f32::consts::FRAC_PI_2,
-                                -1.0,
-                                map_meta_data.tilewidth as f32,
-                                map_meta_data.tileheight as f32 * -1.0,
-                            )
-                        } else {
-                            (0.0, 1.0, 0.0, 0.0)
-                        };
-
-                        let tile_x_global = (chunk_x * CHUNK_SIZE as i32) + tile_x as i32;
-                        let tile_y_global = (((chunk_y * -1) - 1) * CHUNK_SIZE as i32)
-                            + (CHUNK_SIZE - tile_y) as i32
-                            - 1;
-
-                        let (pixel_x, pixel_y) = match map_meta_data.orientation {
-                            Orientation::Orthogonal => (
-                                (tile_x_global * map_meta_data.tilewidth as i32) as f32,
-                                (tile_y_global * map_meta_data.tileheight as i32) as f32,
-                            ),
-                            Orientation::Isometric => (
-                                ((tile_x_global + tile_y_global) * map_meta_data.tilewidth as i32)
-                                    as f32
-                                    / 2.0,
-                                (((tile_x_global + (-tile_y_global))
-                                    * map_meta_data.tileheight as i32)
-                                    as f32
-                                    / -2.0),
-                            ),
-                        };
-
-                        let sprite_id = sprite_batches[tile.1.tileset_id() as usize].insert(
-                            Instance::new()
-                                .src(ts_render_data.uvs[index])
-                                .color(Color::new(1.0, 1.0, 1.0, layer.opacity as f32))
-                                .translate2(Vector2::new(pixel_x, pixel_y))
-                                .scale2(Vector2::new(scale_x, scale_y))
-                                .translate2(Vector2::new(trans_fix_x, trans_fix_y))
-                                .scale2(Vector2::new(1.0, y_scale))
-                                .translate2(Vector2::new(x_trans, y_trans))
-                                .rotate2(rotation),
-                        );
-
-                        // Todo: I think the reason why be add 1 here is due to the render data
-                        // being offset by 1 from the actual map data, but this needs to be checked
-                        sprite_id_map.insert((tile_x_global, tile_y_global + 1), sprite_id);
-
-                        if let Some(t) = ts_render_data.tile_to_tag_map.get(&tile) {
-                            let anim_state = ts_render_data.textures_and_spritesheets
-                                [tile.1.tileset_id() as usize]
-                                .1
-                                .at_tag(*t, true);
-                            ss_state[tile.1.tileset_id() as usize]
-                                .insert(sprite_id, SpriteSheetState { anim_state });
-                        }
-                    }
+        // If it's an animated tile, add it to the sprite sheet state hashmap so that it'll get updated correctly
+        if let Some(t) = ts_render_data.tile_to_tag_map.get(&tile) {
+            let anim_state = ts_render_data.sprite_sheets[tileset_id].at_tag(*t, true);
+            chunk_batch.sprite_sheet_info[tileset_id]
+                .insert(sprite_id, SpriteSheetState { anim_state });
+        }
+
+        // Insert the new sprite id, we unwrap() here to trigger a panic in the event
+        // that we somehow inserted a tile that already existed
+        let res = self.sprite_id_map.insert((x, y), sprite_id);
+        assert!(res.is_none(),
+                 "There is a bug in the hv_tiled remove_tile function, remove_tile should've removed the tile, but instead we got {:?}", res.unwrap());
+
+        sprite_id
+    }
+
+    /// Remove the tile at `(x, y)` from the sprite batch of the chunk containing it, marking that
+    /// chunk dirty.
+    fn remove_tile(
+        &mut self,
+        x: i32,
+        y: i32,
+        tile: TileId,
+        ts_render_data: &TilesetRenderData,
+    ) -> Option<SpriteId> {
+        let old_sprite_id = self.sprite_id_map.remove(&(x, y))?;
+        let chunk = self.dirty_chunks.mark_cell(x, y);
+        self.bake_state.edit();
+        let chunk_batch = self.chunks.get_mut(&chunk)?;
+        let tileset_id = tile.1.tileset_id() as usize;
+
+        // Attempt to remove the sprite sheet info if it exists since we don't want to update animation info for a sprite that doesn't exist
+        chunk_batch.sprite_sheet_info[tileset_id].remove(&old_sprite_id);
+        if let Some(batch) =
+            &mut chunk_batch.sprite_batches[ts_render_data.tileset_textures[tileset_id]]
+        {
+            batch.remove(old_sprite_id);
+        }
+
+        Some(old_sprite_id)
+    }
+
+    /// Draw every chunk, or only the chunks overlapping `view` (in the layer's pixel coordinates)
+    /// if given. Dirty chunks are re-uploaded as they're drawn. Baked layers draw their baked
+    /// textures instead, unless the bake is stale.
+    fn draw_chunks(&mut self, ctx: &mut Graphics, instance: Instance, view: Option<&Box2<f32>>) {
+        if self.bake_state == BakeState::Baked {
+            for baked in self.baked.iter() {
+                if view.map_or(true, |view| view.intersects(&baked.bounds)) {
+                    baked
+                        .texture()
+                        .draw(ctx, instance.translate2(baked.bounds.mins.coords));
                 }
             }
+            return;
         }
 
-        TileLayerBatch {
-            sprite_sheet_info: ss_state,
-            visible: layer.visible,
-            opacity: layer.opacity,
-            _x: (layer.x * (map_meta_data.tilewidth as i32)) as f32,
-            _y: (layer.y * (map_meta_data.tileheight as i32)) as f32,
-            offset_x: layer.offset_x as f32,
-            offset_y: layer.offset_y as f32,
-            sprite_batches,
-            sprite_id_map,
+        for chunk in self.chunk_order.iter() {
+            let chunk_batch = self.chunks.get_mut(chunk).unwrap();
+            if view.map_or(false, |view| !view.intersects(&chunk_batch.bounds)) {
+                continue;
+            }
+
+            for batch in chunk_batch.sprite_batches.iter_mut().flatten() {
+                batch.draw_mut(ctx, instance);
+            }
+            self.dirty_chunks.clear_chunk(*chunk);
         }
     }
 
     pub fn update_batches(&mut self, dt: f32, ts_render_data: &TilesetRenderData) {
-        for (i, batch) in self.sprite_batches.iter_mut().enumerate() {
-            for (sprite_index, ss_state) in self.sprite_sheet_info[i].iter_mut() {
-                let sprite_sheet = &ts_render_data.textures_and_spritesheets[i].1;
-                if let Some(new_frame_id) =
-                    sprite_sheet.update_animation(dt, &mut ss_state.anim_state)
-                {
-                    batch[*sprite_index].src = sprite_sheet[new_frame_id].uvs;
+        for (&chunk, chunk_batch) in self.chunks.iter_mut() {
+            for (i, ss_states) in chunk_batch.sprite_sheet_info.iter_mut().enumerate() {
+                let batch =
+                    match &mut chunk_batch.sprite_batches[ts_render_data.tileset_textures[i]] {
+                        Some(batch) => batch,
+                        None => continue,
+                    };
+                let sprite_sheet = &ts_render_data.sprite_sheets[i];
+                for (sprite_index, ss_state) in ss_states.iter_mut() {
+                    if let Some(new_frame_id) =
+                        sprite_sheet.update_animation(dt, &mut ss_state.anim_state)
+                    {
+                        batch[*sprite_index].src = sprite_sheet[new_frame_id].uvs;
+                        self.dirty_chunks.mark_chunk(chunk);
+                        self.bake_state.edit();
+                    }
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn editing_one_tile_marks_exactly_one_chunk_dirty() {
+        let tile = TileId(1, TileMetaData(0));
+        let size = CHUNK_SIZE as i32;
+
+        // Every cell around the chunk boundaries, including negative coordinates, maps to the same
+        // chunk the map itself stores that cell in.
+        for &(x, y) in &[
+            (0, 0),
+            (size - 1, 0),
+            (size, 0),
+            (-1, 0),
+            (0, -1),
+            (0, 1),
+            (0, size),
+            (-size, -size - 1),
+        ] {
+            let mut chunks = Chunks::new();
+            chunks.set_tile(x, y, tile);
+            let mut dirty = DirtyChunks::default();
+            let chunk = dirty.mark_cell(x, y);
+
+            assert_eq!(dirty.len(), 1, "({}, {})", x, y);
+            assert_eq!(chunks.0.keys().copied().collect::<Vec<_>>(), [chunk]);
+            assert!(chunks.0[&chunk].0.contains(&tile));
+        }
+
+        // Editing more cells of the same chunk doesn't dirty any others.
+        let mut dirty = DirtyChunks::default();
+        let chunk = dirty.mark_cell(1, 1);
+        dirty.mark_cell(2, 3);
+        dirty.mark_cell(0, 0);
+        assert_eq!(dirty.len(), 1);
+        assert!(dirty.contains(chunk));
+
+        dirty.mark_cell(size, 0);
+        assert_eq!(dirty.len(), 2);
+        dirty.clear_chunk(chunk);
+        assert!(!dirty.contains(chunk));
+        assert_eq!(dirty.len(), 1);
+    }
+
+    #[test]
+    fn baked_layers_draw_as_one_quad_and_go_stale_on_edits() {
+        // A 40 by 30 layer of 16 by 16 tiles fits in a single texture, so it's drawn as one quad.
+        let bounds = Box2::new(0., -480., 640., 480.);
+        assert_eq!(bake_regions(&bounds, MAX_BAKE_SIZE), [bounds]);
+        // Bounds are rounded out to whole pixels.
+        assert_eq!(
+            bake_regions(&Box2::new(0.5, 0., 9., 10.25), MAX_BAKE_SIZE),
+            [Box2::new(0., 0., 10., 11.)]
+        );
+
+        // Editing an unbaked layer doesn't matter; editing a baked one makes it stale until it's
+        // baked again.
+        let mut state = BakeState::default();
+        state.edit();
+        assert_eq!(state, BakeState::Unbaked);
+        state = BakeState::Baked;
+        state.edit();
+        assert_eq!(state, BakeState::Stale);
+        state.edit();
+        assert_eq!(state, BakeState::Stale);
+    }
+
+    #[test]
+    fn layers_bigger_than_a_texture_are_baked_in_tiles() {
+        let bounds = Box2::new(-100., 0., 5000., 2100.);
+        let regions = bake_regions(&bounds, MAX_BAKE_SIZE);
+        assert_eq!(regions.len(), 3 * 2);
+
+        let mut covered = regions[0];
+        let mut area = 0.;
+        for region in regions.iter() {
+            let size = region.extents();
+            assert!(size.x <= MAX_BAKE_SIZE as f32 && size.y <= MAX_BAKE_SIZE as f32);
+            covered.merge(region);
+            area += size.x * size.y;
+        }
+        // The regions cover the layer exactly, without overlapping.
+        assert_eq!(covered, bounds);
+        assert_eq!(area, 5000. * 2100.);
+    }
+
+    #[test]
+    fn chunk_bounds_cover_their_tiles() {
+        let size = CHUNK_SIZE as i32;
+        for orientation in &[Orientation::Orthogonal, Orientation::Isometric] {
+            for &(x, y) in &[(0, 0), (size - 1, -(size - 1)), (-1, 1), (37, -5)] {
+                let bounds = chunk_bounds(orientation, 16, 8, chunk_of_cell(x, y));
+                let pixel = cell_to_pixel(orientation, 16, 8, x, y);
+                let tile = Box2::new(pixel.x, pixel.y, 16., 8.);
+                assert!(bounds.contains(&tile), "{:?} ({}, {})", orientation, x, y);
+            }
+        }
+    }
+}
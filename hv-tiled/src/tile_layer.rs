@@ -9,6 +9,20 @@ pub struct TileLayerId {
     pub llid: u32,
 }
 
+/// Find the index of the layer with the given ID in a list of layers (or things belonging to
+/// layers.) Layers start out at the index of their local layer ID, so that's checked first, but
+/// they can move around once layers are added, removed, or reordered at runtime.
+pub(crate) fn find_layer_index<T>(
+    layers: &[T],
+    layer_id: TileLayerId,
+    id_of: impl Fn(&T) -> TileLayerId,
+) -> Option<usize> {
+    match layers.get(layer_id.llid as usize) {
+        Some(layer) if id_of(layer) == layer_id => Some(layer_id.llid as usize),
+        _ => layers.iter().position(|layer| id_of(layer) == layer_id),
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Chunk(pub Vec<TileId>);
 
@@ -31,6 +45,13 @@ fn to_chunk_indices_and_subindices(x: i32, y: i32) -> (i32, i32, u32, u32) {
     (chunk_x, chunk_y, tile_x, tile_y)
 }
 
+/// The coordinates of the chunk which the tile at `(x, y)` is stored in, as used for the keys of
+/// [`Chunks`].
+pub fn chunk_of_cell(x: i32, y: i32) -> (i32, i32) {
+    let (chunk_x, chunk_y, _, _) = to_chunk_indices_and_subindices(x, y);
+    (chunk_x, chunk_y)
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct Chunks(pub HashMap<(i32, i32), Chunk>);
 
@@ -93,6 +114,7 @@ pub fn to_chunks(data: &[TileId], width: u32, height: u32) -> Chunks {
 pub enum Encoding {
     Lua,
     Base64,
+    Csv,
 }
 
 #[derive(Debug, Clone)]
@@ -119,61 +141,167 @@ pub struct TileLayer {
 }
 
 impl TileLayer {
+    /// The non-empty tiles of this layer as `(x, y, tile)`, in Tiled's tile coordinates (where
+    /// `y` increases downwards), sorted into the order the given [`RenderOrder`] draws them in.
+    pub fn tiles_in_render_order(&self, render_order: RenderOrder) -> Vec<(i32, i32, TileId)> {
+        let mut tiles = Vec::new();
+        for ((chunk_x, chunk_y), chunk) in self.data.0.iter() {
+            for tile_y in 0..CHUNK_SIZE {
+                for tile_x in 0..CHUNK_SIZE {
+                    let tile = chunk.0[(tile_y * CHUNK_SIZE + tile_x) as usize];
+                    if tile != EMPTY_TILE {
+                        // Chunks are stored with the y axis flipped; see
+                        // `to_chunk_indices_and_subindices`.
+                        let x = chunk_x * CHUNK_SIZE as i32 + tile_x as i32;
+                        let y = -(chunk_y * CHUNK_SIZE as i32 + tile_y as i32);
+                        tiles.push((x, y, tile));
+                    }
+                }
+            }
+        }
+
+        match render_order {
+            RenderOrder::RightDown => tiles.sort_by_key(|&(x, y, _)| (y, x)),
+            RenderOrder::RightUp => tiles.sort_by_key(|&(x, y, _)| (-y, x)),
+            RenderOrder::LeftDown => tiles.sort_by_key(|&(x, y, _)| (y, -x)),
+            RenderOrder::LeftUp => tiles.sort_by_key(|&(x, y, _)| (-y, -x)),
+        }
+
+        tiles
+    }
+
     pub fn parse_tile_data(
         encoding: &Encoding,
         compression: &Option<Compression>,
         t: &LuaTable,
         tile_buffer: &[u32],
     ) -> Result<Vec<TileId>, Error> {
-        // TODO: Vector capacity can be pre-calculated here, optimize this
-        let mut tile_data = Vec::new();
-
-        match encoding {
-            Encoding::Lua => {
-                for tile in t
-                    .get::<_, LuaTable>("data")?
-                    .sequence_values::<LuaInteger>()
-                {
-                    tile_data.push(tile? as u32);
-                }
+        let gids = match encoding {
+            Encoding::Lua => t
+                .get::<_, LuaTable>("data")?
+                .sequence_values::<LuaInteger>()
+                .map(|tile| Ok(tile? as u32))
+                .collect::<Result<Vec<_>, Error>>()?,
+            Encoding::Base64 => {
+                decode_base64(t.get::<_, LuaString>("data")?.to_str()?, compression)?
             }
+            Encoding::Csv => decode_csv(t.get::<_, LuaString>("data")?.to_str()?)?,
+        };
 
-            Encoding::Base64 => {
-                let str_data = t.get::<_, LuaString>("data")?.to_str()?.to_owned();
-
-                let decoded_bytes = base64::decode_config(str_data, base64::STANDARD)?;
-
-                let level_bytes = match compression {
-                    Some(c) => match c {
-                        Compression::GZip => {
-                            let mut d = flate2::read::GzDecoder::new(decoded_bytes.as_slice());
-                            let mut s = Vec::new();
-                            d.read_to_end(&mut s).unwrap();
-                            s
-                        }
-                        Compression::ZLib => {
-                            let mut d = flate2::read::ZlibDecoder::new(decoded_bytes.as_slice());
-                            let mut s = Vec::new();
-                            d.read_to_end(&mut s).unwrap();
-                            s
-                        }
-                    },
-                    None => decoded_bytes,
-                };
-
-                for i in (0..level_bytes.len()).step_by(4) {
-                    let val = level_bytes[i] as u32
-                        | (level_bytes[i + 1] as u32) << 8
-                        | (level_bytes[i + 2] as u32) << 16
-                        | (level_bytes[i + 3] as u32) << 24;
-                    tile_data.push(val);
-                }
+        Ok(gids_to_tile_ids(gids, tile_buffer))
+    }
+}
+
+/// Decode base64-encoded, optionally compressed layer data into raw GIDs (still carrying their
+/// flip flags.)
+pub fn decode_base64(data: &str, compression: &Option<Compression>) -> Result<Vec<u32>, Error> {
+    let decoded_bytes = base64::decode_config(data.trim(), base64::STANDARD)?;
+
+    let level_bytes = match compression {
+        Some(c) => match c {
+            Compression::GZip => {
+                let mut d = flate2::read::GzDecoder::new(decoded_bytes.as_slice());
+                let mut s = Vec::new();
+                d.read_to_end(&mut s)?;
+                s
+            }
+            Compression::ZLib => {
+                let mut d = flate2::read::ZlibDecoder::new(decoded_bytes.as_slice());
+                let mut s = Vec::new();
+                d.read_to_end(&mut s)?;
+                s
             }
+        },
+        None => decoded_bytes,
+    };
+
+    ensure!(
+        level_bytes.len() % 4 == 0,
+        "layer data should be a whole number of 32-bit GIDs, got {} bytes",
+        level_bytes.len()
+    );
+
+    Ok(level_bytes
+        .chunks_exact(4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect())
+}
+
+/// Decode CSV-encoded layer data into raw GIDs (still carrying their flip flags.) Whitespace and
+/// newlines between values are ignored, as is a trailing comma.
+pub fn decode_csv(data: &str) -> Result<Vec<u32>, Error> {
+    data.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            s.parse::<u32>()
+                .with_context(|| format!("invalid GID in CSV layer data: {:?}", s))
+        })
+        .collect()
+}
+
+/// Convert raw GIDs into [`TileId`]s, splitting off their flip flags. This is shared by every map
+/// format so that flipped tiles decode the same way no matter where they came from.
+pub fn gids_to_tile_ids(gids: Vec<u32>, tile_buffer: &[u32]) -> Vec<TileId> {
+    gids.into_iter()
+        .map(|gid| TileId::from_gid(gid, tile_buffer))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer(width: u32, height: u32) -> TileLayer {
+        let data = (0..width * height)
+            .map(|i| TileId::new(i, 0, false, false, false))
+            .collect::<Vec<_>>();
+        TileLayer {
+            layer_type: LayerType::Tile,
+            id: TileLayerId { glid: 1, llid: 0 },
+            name: String::new(),
+            x: 0,
+            y: 0,
+            width,
+            height,
+            visible: true,
+            opacity: 1.,
+            offset_x: 0,
+            offset_y: 0,
+            properties: Properties(HashMap::new()),
+            data: to_chunks(&data, width, height),
         }
+    }
+
+    #[test]
+    fn left_up_reverses_right_down() {
+        // Big enough to span several chunks, so that chunk iteration order can't leak through.
+        let layer = layer(CHUNK_SIZE + 3, CHUNK_SIZE + 2);
+
+        let right_down = layer.tiles_in_render_order(RenderOrder::RightDown);
+        assert_eq!(
+            right_down.len(),
+            ((CHUNK_SIZE + 3) * (CHUNK_SIZE + 2)) as usize
+        );
+        for (i, &(x, y, tile)) in right_down.iter().enumerate() {
+            let i = i as u32;
+            assert_eq!(
+                (x, y),
+                ((i % (CHUNK_SIZE + 3)) as i32, (i / (CHUNK_SIZE + 3)) as i32)
+            );
+            assert_eq!(tile, TileId::new(i, 0, false, false, false));
+        }
+
+        let mut left_up = layer.tiles_in_render_order(RenderOrder::LeftUp);
+        left_up.reverse();
+        assert_eq!(left_up, right_down);
 
-        Ok(tile_data
-            .into_iter()
-            .map(|tile| TileId::from_gid(tile, tile_buffer))
-            .collect())
+        // The other two orders only reverse one axis.
+        let right_up = layer.tiles_in_render_order(RenderOrder::RightUp);
+        assert_eq!(right_up[0].0, 0);
+        assert_eq!(right_up[0].1, (CHUNK_SIZE + 1) as i32);
+        let left_down = layer.tiles_in_render_order(RenderOrder::LeftDown);
+        assert_eq!(left_down[0].0, (CHUNK_SIZE + 2) as i32);
+        assert_eq!(left_down[0].1, 0);
     }
 }